@@ -0,0 +1,123 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(missing_docs)]
+
+//! Benchmarks of the decoding hot path: attribute stores, wide attribute sets, parent id
+//! delta decoding and full traces batches. Run with `cargo bench --features bench --bench attribute_store`.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+use otel_arrow_rust::otlp::attributes::decoder::Attrs16ParentIdDecoder;
+use otel_arrow_rust::otlp::attributes::store::Attribute16Store;
+use otel_arrow_rust::otlp::options::{CoercionAction, CoercionPolicy, DecoderOptions};
+use otel_arrow_rust::otlp::traces::traces_from;
+use otel_arrow_rust::proto::opentelemetry::common::v1::any_value::Value;
+use otel_arrow_rust::test_util::workloads::{
+    cbor_map_attrs, dictionary_attrs, high_cardinality_attrs, sparse_int_attrs, traces_batch,
+    wide_attrs,
+};
+
+const SIZES: [usize; 3] = [128, 1536, 8192];
+
+fn bench_attribute_store(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attribute_store");
+
+    for size in SIZES {
+        let workloads = [
+            ("high_cardinality_keys", high_cardinality_attrs(size)),
+            ("large_cbor_maps", cbor_map_attrs(size, 32)),
+            ("dictionaries", dictionary_attrs(size, 16)),
+        ];
+        for (name, input) in workloads {
+            let _ = group.bench_with_input(BenchmarkId::new(name, size), &input, |b, input| {
+                b.iter(|| {
+                    let _ =
+                        Attribute16Store::try_from(input).expect("function should not error here");
+                });
+            });
+        }
+
+        // the rows without value in the int column are looked up in the string column
+        let input = sparse_int_attrs(size);
+        let options = DecoderOptions::default()
+            .with_attribute_coercion(CoercionPolicy::new(CoercionAction::Coerce));
+        let _ = group.bench_with_input(
+            BenchmarkId::new("sparse_typed_column", size),
+            &input,
+            |b, input| {
+                b.iter(|| {
+                    let _ = Attribute16Store::try_from_with_options(input, &options)
+                        .expect("function should not error here");
+                });
+            },
+        );
+    }
+
+    group.finish()
+}
+
+fn bench_wide_attribute_sets(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wide_attribute_sets");
+
+    for width in [8, 64, 256] {
+        let input = wide_attrs(8192, width);
+        let _ = group.bench_with_input(BenchmarkId::new("width", width), &input, |b, input| {
+            b.iter(|| {
+                let _ = Attribute16Store::try_from(input).expect("function should not error here");
+            });
+        });
+    }
+
+    group.finish()
+}
+
+fn bench_parent_id_decoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parent_id_decoding");
+
+    for size in SIZES {
+        // runs of 16 identical key / value pairs, delta encoded within a run
+        let input: Vec<_> = (0..size)
+            .map(|i| {
+                let run = i / 16;
+                let delta = u16::from(i % 16 != 0);
+                (delta, format!("key{run}"), Value::IntValue(run as i64))
+            })
+            .collect();
+        let _ = group.bench_with_input(BenchmarkId::new("delta_runs", size), &input, |b, input| {
+            b.iter(|| {
+                let mut decoder = Attrs16ParentIdDecoder::default();
+                for (delta, key, value) in input {
+                    let _ = black_box(decoder.decode(*delta, key, value));
+                }
+            });
+        });
+    }
+
+    group.finish()
+}
+
+fn bench_traces_decoding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traces_decoding");
+
+    for size in SIZES {
+        let _ = group.bench_function(BenchmarkId::new("traces_from", size), |b| {
+            b.iter_batched(
+                || traces_batch(size, 8),
+                |input| traces_from(input).expect("function should not error here"),
+                BatchSize::SmallInput,
+            );
+        });
+    }
+
+    group.finish()
+}
+
+criterion_group!(
+    benches,
+    bench_attribute_store,
+    bench_wide_attribute_sets,
+    bench_parent_id_decoding,
+    bench_traces_decoding
+);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(missing_docs)]
+
+//! Benchmarks of the production of traces batches with different sort orders. Run with
+//! `cargo bench --features bench --bench sorter`.
+
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+
+use otel_arrow_rust::encode::TracesProducer;
+use otel_arrow_rust::encode::sorter::{SpanSortKey, SpanSorter};
+use otel_arrow_rust::otlp::traces::traces_from;
+use otel_arrow_rust::test_util::workloads::traces_batch;
+
+const SIZES: [usize; 3] = [128, 1536, 8192];
+
+fn sorters() -> Vec<(&'static str, SpanSorter)> {
+    vec![
+        ("name_trace_id", SpanSorter::default()),
+        (
+            "trace_id_name",
+            SpanSorter::new(vec![SpanSortKey::TraceId, SpanSortKey::Name]),
+        ),
+        (
+            "kind_start_time",
+            SpanSorter::new(vec![SpanSortKey::Kind, SpanSortKey::StartTime]),
+        ),
+        (
+            "attribute",
+            SpanSorter::new(vec![
+                SpanSortKey::Attribute("key0".into()),
+                SpanSortKey::Name,
+            ]),
+        ),
+    ]
+}
+
+fn bench_traces_sorting(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traces_sorting");
+
+    for size in SIZES {
+        let request = traces_from(traces_batch(size, 4)).expect("function should not error here");
+        for (name, sorter) in sorters() {
+            let producer = TracesProducer::with_sorter(sorter);
+            let _ = group.bench_with_input(BenchmarkId::new(name, size), &request, |b, input| {
+                b.iter(|| {
+                    let _ = black_box(
+                        producer
+                            .produce(input)
+                            .expect("function should not error here"),
+                    );
+                });
+            });
+        }
+    }
+
+    group.finish()
+}
+
+criterion_group!(benches, bench_traces_sorting);
+criterion_main!(benches);
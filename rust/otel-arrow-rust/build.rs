@@ -35,6 +35,11 @@ fn prost_cfg() -> prost_build::Config {
     // Disable prettyplease, otherwise 'cargo fmt' will reformat
     // compared with 'cargo build'.
     cfg.format(false);
+    // The derives are only compiled with the `serde` feature.
+    cfg.type_attribute(
+        ".",
+        r#"#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]"#,
+    );
     cfg
 }
 
@@ -0,0 +1,324 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Archives of the `BatchArrowRecords` of a stream, e.g. the test corpora shared by the Go
+//! and Rust implementations or the input of offline pipelines.
+//!
+//! Like a [recording](crate::replay), an archive holds the batches of a single stream in
+//! order, to be decoded by a single [`Consumer`](crate::Consumer), but without their times
+//! and over any reader or writer, optionally zstd compressed as a whole. The layout of an
+//! archive is:
+//!
+//! - the magic bytes `OTAB`.
+//! - the version of the layout, [`ARCHIVE_VERSION`], on one byte.
+//! - the compression of the rest of the archive, on one byte, see [`ArchiveCompression`].
+//! - for every batch, the length of the batch, a little endian u32, and the protobuf
+//!   encoded `BatchArrowRecords`, the batches being a single zstd stream if the archive is
+//!   compressed.
+//!
+//! zstd is the only compression, as it is the one the crate already depends on for the
+//! payloads, and compresses the batches better than gzip. It requires the `zstd` feature.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use prost::Message;
+use snafu::{OptionExt, ResultExt, ensure};
+
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+
+/// Magic bytes starting every archive.
+pub const ARCHIVE_MAGIC: [u8; 4] = *b"OTAB";
+
+/// Version of the layout of the archives written by this crate.
+pub const ARCHIVE_VERSION: u8 = 1;
+
+/// Compression of the batches of an archive.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ArchiveCompression {
+    /// The batches are stored as they are, `0` in the header.
+    #[default]
+    None,
+    /// The batches are a zstd stream, `1` in the header.
+    Zstd,
+}
+
+impl ArchiveCompression {
+    /// Returns true if this build can write and read archives using this compression.
+    #[must_use]
+    pub fn is_supported(&self) -> bool {
+        *self != Self::Zstd || cfg!(feature = "zstd")
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::None),
+            1 => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::None => 0,
+            Self::Zstd => 1,
+        }
+    }
+}
+
+enum Sink<W: Write> {
+    Plain(W),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+}
+
+/// Writes the batches of a stream to an archive, see the module documentation.
+pub struct ArchiveWriter<W: Write> {
+    sink: Sink<W>,
+}
+
+impl ArchiveWriter<BufWriter<File>> {
+    /// Creates the archive at the path, replacing the file if it exists.
+    pub fn create(path: impl AsRef<Path>, compression: ArchiveCompression) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).context(error::IoSnafu { path })?;
+        Self::new(BufWriter::new(file), compression)
+    }
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    /// Starts an archive written to `writer`.
+    pub fn new(mut writer: W, compression: ArchiveCompression) -> Result<Self> {
+        ensure!(
+            compression.is_supported(),
+            error::UnsupportedCompressionSnafu {
+                compression: format!("{compression:?}"),
+            }
+        );
+        writer
+            .write_all(&ARCHIVE_MAGIC)
+            .and_then(|()| writer.write_all(&[ARCHIVE_VERSION, compression.to_byte()]))
+            .context(error::ArchiveIoSnafu)?;
+        let sink = match compression {
+            ArchiveCompression::None => Sink::Plain(writer),
+            #[cfg(feature = "zstd")]
+            ArchiveCompression::Zstd => Sink::Zstd(
+                zstd::stream::write::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)
+                    .context(error::ArchiveIoSnafu)?,
+            ),
+            #[cfg(not(feature = "zstd"))]
+            ArchiveCompression::Zstd => {
+                return error::UnsupportedCompressionSnafu {
+                    compression: "Zstd",
+                }
+                .fail();
+            }
+        };
+        Ok(Self { sink })
+    }
+
+    /// Appends the batch to the archive.
+    pub fn write(&mut self, records: &BatchArrowRecords) -> Result<()> {
+        let body = records.encode_to_vec();
+        let body_len = u32::try_from(body.len()).map_err(|_| {
+            error::InvalidArchiveSnafu {
+                reason: format!("batch of {} bytes is too large", body.len()),
+            }
+            .build()
+        })?;
+        // the plain sources and sinks are the only ones without the zstd feature
+        #[cfg_attr(not(feature = "zstd"), allow(clippy::infallible_destructuring_match))]
+        let writer: &mut dyn Write = match &mut self.sink {
+            Sink::Plain(writer) => writer,
+            #[cfg(feature = "zstd")]
+            Sink::Zstd(encoder) => encoder,
+        };
+        writer
+            .write_all(&body_len.to_le_bytes())
+            .and_then(|()| writer.write_all(&body))
+            .context(error::ArchiveIoSnafu)
+    }
+
+    /// Ends the archive, flushing its writer, which is returned.
+    pub fn finish(self) -> Result<W> {
+        // the plain sources and sinks are the only ones without the zstd feature
+        #[cfg_attr(not(feature = "zstd"), allow(clippy::infallible_destructuring_match))]
+        let mut writer = match self.sink {
+            Sink::Plain(writer) => writer,
+            #[cfg(feature = "zstd")]
+            Sink::Zstd(encoder) => encoder.finish().context(error::ArchiveIoSnafu)?,
+        };
+        writer.flush().context(error::ArchiveIoSnafu)?;
+        Ok(writer)
+    }
+}
+
+enum Source<R: Read> {
+    Plain(R),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<R>>),
+}
+
+/// Reads the batches of an archive in the order they were written.
+pub struct ArchiveReader<R: Read> {
+    source: Source<R>,
+    compression: ArchiveCompression,
+    done: bool,
+}
+
+impl ArchiveReader<BufReader<File>> {
+    /// Opens the archive at the path, checking its header.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).context(error::IoSnafu { path })?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> ArchiveReader<R> {
+    /// Starts reading the archive from `reader`, checking its header.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut header = [0; ARCHIVE_MAGIC.len() + 2];
+        reader
+            .read_exact(&mut header)
+            .context(error::ArchiveIoSnafu)?;
+        ensure!(
+            header[..ARCHIVE_MAGIC.len()] == ARCHIVE_MAGIC,
+            error::InvalidArchiveSnafu {
+                reason: "bad magic bytes"
+            }
+        );
+        let version = header[ARCHIVE_MAGIC.len()];
+        ensure!(version == ARCHIVE_VERSION, error::InvalidArchiveSnafu {
+            reason: format!("unsupported version {version}"),
+        });
+        let byte = header[ARCHIVE_MAGIC.len() + 1];
+        let compression =
+            ArchiveCompression::from_byte(byte).context(error::InvalidArchiveSnafu {
+                reason: format!("unsupported compression {byte}"),
+            })?;
+        let source = match compression {
+            ArchiveCompression::None => Source::Plain(reader),
+            #[cfg(feature = "zstd")]
+            ArchiveCompression::Zstd => Source::Zstd(
+                zstd::stream::read::Decoder::new(reader).context(error::ArchiveIoSnafu)?,
+            ),
+            #[cfg(not(feature = "zstd"))]
+            ArchiveCompression::Zstd => {
+                return error::UnsupportedCompressionSnafu {
+                    compression: "Zstd",
+                }
+                .fail();
+            }
+        };
+        Ok(Self {
+            source,
+            compression,
+            done: false,
+        })
+    }
+
+    /// Returns the compression of the archive.
+    #[must_use]
+    pub fn compression(&self) -> ArchiveCompression {
+        self.compression
+    }
+
+    fn read_batch(&mut self) -> Result<Option<BatchArrowRecords>> {
+        // the plain sources and sinks are the only ones without the zstd feature
+        #[cfg_attr(not(feature = "zstd"), allow(clippy::infallible_destructuring_match))]
+        let reader: &mut dyn Read = match &mut self.source {
+            Source::Plain(reader) => reader,
+            #[cfg(feature = "zstd")]
+            Source::Zstd(decoder) => decoder,
+        };
+        let mut body_len = [0; 4];
+        // the archive ends where a batch would start, the zstd decoder failing with
+        // `UnexpectedEof` on a truncated stream
+        let mut filled = 0;
+        while filled < body_len.len() {
+            match reader.read(&mut body_len[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => {
+                    return error::InvalidArchiveSnafu {
+                        reason: "truncated batch length",
+                    }
+                    .fail();
+                }
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).context(error::ArchiveIoSnafu),
+            }
+        }
+        let mut body = vec![0; u32::from_le_bytes(body_len) as usize];
+        reader
+            .read_exact(&mut body)
+            .context(error::ArchiveIoSnafu)?;
+        BatchArrowRecords::decode(body.as_slice())
+            .map(Some)
+            .map_err(|e| {
+                error::InvalidArchiveSnafu {
+                    reason: e.to_string(),
+                }
+                .build()
+            })
+    }
+}
+
+impl<R: Read> Iterator for ArchiveReader<R> {
+    type Item = Result<BatchArrowRecords>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let batch = self.read_batch().transpose();
+        // a truncated or corrupted archive ends with its error
+        self.done = !matches!(batch, Some(Ok(_)));
+        batch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::stream::StreamEncoder;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_archive_round_trip() {
+        let otap_batch = traces_batch(8, 2);
+        let mut encoder = StreamEncoder::new();
+        let batches: Vec<_> = (0..3)
+            .map(|_| encoder.encode(&otap_batch).unwrap())
+            .collect();
+
+        for compression in [ArchiveCompression::None, ArchiveCompression::Zstd]
+            .into_iter()
+            .filter(ArchiveCompression::is_supported)
+        {
+            let mut writer = ArchiveWriter::new(Vec::new(), compression).unwrap();
+            for records in &batches {
+                writer.write(records).unwrap();
+            }
+            let bytes = writer.finish().unwrap();
+            assert_eq!(bytes[..4], ARCHIVE_MAGIC);
+
+            let reader = ArchiveReader::new(bytes.as_slice()).unwrap();
+            assert_eq!(reader.compression(), compression);
+            let read: Vec<_> = reader.map(Result::unwrap).collect();
+            assert_eq!(read, batches);
+
+            // a truncated archive ends with an error
+            let truncated = &bytes[..bytes.len() - 8];
+            let results: Vec<_> = ArchiveReader::new(truncated).unwrap().collect();
+            assert!(results.last().unwrap().is_err());
+        }
+
+        assert!(ArchiveReader::new(&b"OTAF\x01\x00"[..]).is_err());
+        assert!(ArchiveReader::new(&b"OTAB\x01\x07"[..]).is_err());
+    }
+}
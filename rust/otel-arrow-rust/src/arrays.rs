@@ -12,10 +12,11 @@
 
 use crate::error;
 use arrow::array::{
-    Array, ArrayRef, ArrowPrimitiveType, BinaryArray, BooleanArray, DictionaryArray,
-    FixedSizeBinaryArray, Float32Array, Float64Array, Int8Array, Int16Array, Int32Array,
-    Int64Array, PrimitiveArray, RecordBatch, StringArray, StructArray, TimestampNanosecondArray,
-    UInt8Array, UInt16Array, UInt32Array, UInt64Array,
+    Array, ArrayRef, ArrowPrimitiveType, BinaryArray, BinaryViewArray, BooleanArray,
+    DictionaryArray, DurationMillisecondArray, FixedSizeBinaryArray, Float32Array, Float64Array,
+    Int8Array, Int16Array, Int32Array, Int64Array, LargeBinaryArray, LargeStringArray,
+    PrimitiveArray, RecordBatch, StringArray, StringViewArray, StructArray,
+    TimestampNanosecondArray, UInt8Array, UInt16Array, UInt32Array, UInt64Array,
 };
 use arrow::datatypes::{
     ArrowDictionaryKeyType, ArrowNativeType, DataType, TimeUnit, UInt8Type, UInt16Type,
@@ -85,39 +86,91 @@ impl NullableArrayAccessor for BooleanArray {
     }
 }
 
-impl NullableArrayAccessor for BinaryArray {
-    type Native = Vec<u8>;
+/// Implements [`NullableArrayAccessor`] for Arrow arrays whose values are borrowed, the
+/// accessor returning an owned copy of the value.
+macro_rules! impl_nullable_accessor {
+    ($($array_type:ty => $native:ty),* $(,)?) => {
+        $(
+            impl NullableArrayAccessor for $array_type {
+                type Native = $native;
+
+                fn value_at(&self, idx: usize) -> Option<Self::Native> {
+                    if self.is_valid(idx) {
+                        Some(self.value(idx).to_owned())
+                    } else {
+                        None
+                    }
+                }
+            }
+        )*
+    };
+}
 
-    fn value_at(&self, idx: usize) -> Option<Self::Native> {
-        if self.is_valid(idx) {
-            Some(self.value(idx).to_vec())
-        } else {
-            None
-        }
-    }
+impl_nullable_accessor!(
+    BinaryArray => Vec<u8>,
+    LargeBinaryArray => Vec<u8>,
+    BinaryViewArray => Vec<u8>,
+    FixedSizeBinaryArray => Vec<u8>,
+    StringArray => String,
+    LargeStringArray => String,
+    StringViewArray => String,
+);
+
+/// An Arrow array that the accessors can read, either unencoded or as the values of a
+/// dictionary array, see [`MaybeDictArrayAccessor`].
+pub trait ColumnArray: Array + NullableArrayAccessor + 'static {
+    /// The data type of the unencoded array.
+    const DATA_TYPE: DataType;
 }
 
-impl NullableArrayAccessor for FixedSizeBinaryArray {
-    type Native = Vec<u8>;
+impl<T> ColumnArray for PrimitiveArray<T>
+where
+    T: ArrowPrimitiveType,
+{
+    const DATA_TYPE: DataType = T::DATA_TYPE;
+}
 
-    fn value_at(&self, idx: usize) -> Option<Self::Native> {
-        if self.is_valid(idx) {
-            Some(self.value(idx).to_vec())
-        } else {
-            None
-        }
+macro_rules! impl_column_array {
+    ($($array_type:ty => $data_type:ident),* $(,)?) => {
+        $(
+            impl ColumnArray for $array_type {
+                const DATA_TYPE: DataType = DataType::$data_type;
+            }
+        )*
+    };
+}
+
+impl_column_array!(
+    BooleanArray => Boolean,
+    BinaryArray => Binary,
+    LargeBinaryArray => LargeBinary,
+    BinaryViewArray => BinaryView,
+    StringArray => Utf8,
+    LargeStringArray => LargeUtf8,
+    StringViewArray => Utf8View,
+);
+
+/// A [`ColumnArray`] of strings, which the accessors can return without copying them.
+pub trait StringColumnArray: ColumnArray {
+    /// Returns the string at the given index, ignoring its validity.
+    fn str_value(&self, idx: usize) -> &str;
+}
+
+impl StringColumnArray for StringArray {
+    fn str_value(&self, idx: usize) -> &str {
+        self.value(idx)
     }
 }
 
-impl NullableArrayAccessor for StringArray {
-    type Native = String;
+impl StringColumnArray for LargeStringArray {
+    fn str_value(&self, idx: usize) -> &str {
+        self.value(idx)
+    }
+}
 
-    fn value_at(&self, idx: usize) -> Option<Self::Native> {
-        if self.is_valid(idx) {
-            Some(self.value(idx).to_string())
-        } else {
-            None
-        }
+impl StringColumnArray for StringViewArray {
+    fn str_value(&self, idx: usize) -> &str {
+        self.value(idx)
     }
 }
 
@@ -244,63 +297,62 @@ where
     }
 }
 
+/// An accessor to the values of a column, whichever of the data types it supports the
+/// column has.
+///
+/// Reading a new type of column only takes a [`ColumnArray`] implementation for its Arrow
+/// array, [`MaybeDictArrayAccessor`] then reads it either unencoded or dictionary encoded.
+pub trait ColumnAccessor<'a>: NullableArrayAccessor + Sized {
+    /// Creates the accessor of the array, or returns an error if its data type is not
+    /// supported.
+    fn try_new(arr: &'a ArrayRef) -> error::Result<Self>;
+
+    /// Creates the accessor of a column that the caller requires to be in the record
+    /// batch. If the column is not in the record batch, returns `ColumnNotFound` error
+    fn try_new_for_column(record_batch: &'a RecordBatch, column_name: &str) -> error::Result<Self> {
+        Self::try_new(get_required_array(record_batch, column_name)?)
+    }
+
+    /// Creates the accessor of a column if it is in the record batch.
+    fn try_new_for_column_opt(
+        record_batch: &'a RecordBatch,
+        column_name: &str,
+    ) -> error::Result<Option<Self>> {
+        record_batch
+            .column_by_name(column_name)
+            .map(Self::try_new)
+            .transpose()
+    }
+}
+
+/// Returns the value type of a dictionary array, or the type of any other array.
+fn value_data_type(arr: &ArrayRef) -> &DataType {
+    match arr.data_type() {
+        DataType::Dictionary(_, val) => val,
+        data_type => data_type,
+    }
+}
+
 /// Wrapper around various arrays that may return a byte slice. Note that
 /// this delegates to the underlying NullableArrayAccessor implementation
 /// for the Arrow array which copies the bytes when value_at is called
 pub enum ByteArrayAccessor<'a> {
     Binary(MaybeDictArrayAccessor<'a, BinaryArray>),
+    LargeBinary(MaybeDictArrayAccessor<'a, LargeBinaryArray>),
+    BinaryView(MaybeDictArrayAccessor<'a, BinaryViewArray>),
     FixedSizeBinary(MaybeDictArrayAccessor<'a, FixedSizeBinaryArray>),
 }
 
-impl<'a> ByteArrayAccessor<'a> {
-    pub fn try_new_for_column(
-        record_batch: &'a RecordBatch,
-        column_name: &str,
-    ) -> error::Result<Self> {
-        Self::try_new(get_required_array(record_batch, column_name)?)
-    }
-
-    pub fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
-        match arr.data_type() {
-            DataType::Binary => {
-                MaybeDictArrayAccessor::<BinaryArray>::try_new(arr).map(Self::Binary)
-            }
+impl<'a> ColumnAccessor<'a> for ByteArrayAccessor<'a> {
+    fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
+        match value_data_type(arr) {
+            DataType::LargeBinary => MaybeDictArrayAccessor::try_new(arr).map(Self::LargeBinary),
+            DataType::BinaryView => MaybeDictArrayAccessor::try_new(arr).map(Self::BinaryView),
             DataType::FixedSizeBinary(dims) => {
-                MaybeDictArrayAccessor::<FixedSizeBinaryArray>::try_new(arr, *dims)
+                MaybeDictArrayAccessor::try_new_with_datatype(DataType::FixedSizeBinary(*dims), arr)
                     .map(Self::FixedSizeBinary)
             }
-            DataType::Dictionary(_, val) => match **val {
-                DataType::Binary => {
-                    MaybeDictArrayAccessor::<BinaryArray>::try_new(arr).map(Self::Binary)
-                }
-                DataType::FixedSizeBinary(dims) => {
-                    MaybeDictArrayAccessor::<FixedSizeBinaryArray>::try_new(arr, dims)
-                        .map(Self::FixedSizeBinary)
-                }
-                _ => error::UnsupportedDictionaryValueTypeSnafu {
-                    expect_oneof: vec![DataType::Binary, DataType::FixedSizeBinary(-1)],
-                    actual: (**val).clone(),
-                }
-                .fail(),
-            },
-            _ => error::InvalidListArraySnafu {
-                expect_oneof: vec![
-                    DataType::Binary,
-                    DataType::FixedSizeBinary(-1),
-                    DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Binary)),
-                    DataType::Dictionary(Box::new(DataType::UInt16), Box::new(DataType::Binary)),
-                    DataType::Dictionary(
-                        Box::new(DataType::UInt8),
-                        Box::new(DataType::FixedSizeBinary(-1)),
-                    ),
-                    DataType::Dictionary(
-                        Box::new(DataType::UInt16),
-                        Box::new(DataType::FixedSizeBinary(-1)),
-                    ),
-                ],
-                actual: arr.data_type().clone(),
-            }
-            .fail(),
+            _ => MaybeDictArrayAccessor::try_new(arr).map(Self::Binary),
         }
     }
 }
@@ -311,6 +363,8 @@ impl NullableArrayAccessor for ByteArrayAccessor<'_> {
     fn value_at(&self, idx: usize) -> Option<Self::Native> {
         match self {
             Self::Binary(b) => b.value_at(idx),
+            Self::LargeBinary(b) => b.value_at(idx),
+            Self::BinaryView(b) => b.value_at(idx),
             Self::FixedSizeBinary(b) => b.value_at(idx),
         }
     }
@@ -408,50 +462,102 @@ where
     }
 }
 
-impl<'a, V> MaybeDictArrayAccessor<'a, PrimitiveArray<V>>
+impl<'a, T> ColumnAccessor<'a> for MaybeDictArrayAccessor<'a, T>
 where
-    V: ArrowPrimitiveType,
+    T: ColumnArray,
 {
-    pub fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
-        Self::try_new_with_datatype(V::DATA_TYPE, arr)
+    fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
+        Self::try_new_with_datatype(T::DATA_TYPE, arr)
     }
+}
 
-    pub fn try_new_for_column(
-        record_batch: &'a RecordBatch,
-        column_name: &str,
-    ) -> error::Result<Self> {
-        Self::try_new(get_required_array(record_batch, column_name)?)
+impl<'a, T> MaybeDictArrayAccessor<'a, T>
+where
+    T: StringColumnArray,
+{
+    /// Returns the string at the given index without copying it.
+    pub fn str_at(&self, idx: usize) -> Option<&'a str> {
+        match self {
+            Self::Native(s) => s.is_valid(idx).then(|| s.str_value(idx)),
+            Self::Dictionary8(d) => d.str_at(idx),
+            Self::Dictionary16(d) => d.str_at(idx),
+        }
     }
 }
 
-impl<'a> MaybeDictArrayAccessor<'a, BinaryArray> {
-    pub fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
-        Self::try_new_with_datatype(BinaryArray::DATA_TYPE, arr)
+pub type BooleanArrayAccessor<'a> = MaybeDictArrayAccessor<'a, BooleanArray>;
+pub type Int32ArrayAccessor<'a> = MaybeDictArrayAccessor<'a, Int32Array>;
+pub type Int64ArrayAccessor<'a> = MaybeDictArrayAccessor<'a, Int64Array>;
+pub type UInt8ArrayAccessor<'a> = MaybeDictArrayAccessor<'a, UInt8Array>;
+pub type DurationMillisArrayAccessor<'a> = MaybeDictArrayAccessor<'a, DurationMillisecondArray>;
+
+/// Wrapper around the arrays of the enum columns, e.g. the span kinds: `Int32` arrays, or
+/// `UInt8` arrays as their values fit in a byte, either unencoded or dictionary encoded.
+pub enum EnumArrayAccessor<'a> {
+    Int32(Int32ArrayAccessor<'a>),
+    UInt8(UInt8ArrayAccessor<'a>),
+}
+
+impl<'a> ColumnAccessor<'a> for EnumArrayAccessor<'a> {
+    fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
+        match value_data_type(arr) {
+            DataType::UInt8 => MaybeDictArrayAccessor::try_new(arr).map(Self::UInt8),
+            _ => MaybeDictArrayAccessor::try_new(arr).map(Self::Int32),
+        }
     }
 }
 
-impl<'a> MaybeDictArrayAccessor<'a, FixedSizeBinaryArray> {
-    pub fn try_new(arr: &'a ArrayRef, dims: i32) -> error::Result<Self> {
-        Self::try_new_with_datatype(DataType::FixedSizeBinary(dims), arr)
+impl NullableArrayAccessor for EnumArrayAccessor<'_> {
+    type Native = i32;
+
+    fn value_at(&self, idx: usize) -> Option<Self::Native> {
+        match self {
+            Self::Int32(a) => a.value_at(idx),
+            Self::UInt8(a) => a.value_at(idx).map(i32::from),
+        }
     }
 }
 
-impl<'a> MaybeDictArrayAccessor<'a, StringArray> {
-    pub fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
-        Self::try_new_with_datatype(StringArray::DATA_TYPE, arr)
+/// Wrapper around the arrays that may return a string: `Utf8`, `LargeUtf8` and `Utf8View`
+/// arrays, either unencoded or dictionary encoded with `UInt8` or `UInt16` keys.
+pub enum StringArrayAccessor<'a> {
+    Utf8(MaybeDictArrayAccessor<'a, StringArray>),
+    LargeUtf8(MaybeDictArrayAccessor<'a, LargeStringArray>),
+    Utf8View(MaybeDictArrayAccessor<'a, StringViewArray>),
+}
+
+impl<'a> ColumnAccessor<'a> for StringArrayAccessor<'a> {
+    fn try_new(arr: &'a ArrayRef) -> error::Result<Self> {
+        match value_data_type(arr) {
+            DataType::LargeUtf8 => MaybeDictArrayAccessor::try_new(arr).map(Self::LargeUtf8),
+            DataType::Utf8View => MaybeDictArrayAccessor::try_new(arr).map(Self::Utf8View),
+            _ => MaybeDictArrayAccessor::try_new(arr).map(Self::Utf8),
+        }
     }
+}
 
-    pub fn try_new_for_column(
-        record_batch: &'a RecordBatch,
-        column_name: &str,
-    ) -> error::Result<Self> {
-        Self::try_new(get_required_array(record_batch, column_name)?)
+impl<'a> StringArrayAccessor<'a> {
+    /// Returns the string at the given index without copying it.
+    pub fn str_at(&self, idx: usize) -> Option<&'a str> {
+        match self {
+            Self::Utf8(s) => s.str_at(idx),
+            Self::LargeUtf8(s) => s.str_at(idx),
+            Self::Utf8View(s) => s.str_at(idx),
+        }
     }
 }
 
-pub type Int32ArrayAccessor<'a> = MaybeDictArrayAccessor<'a, Int32Array>;
-pub type Int64ArrayAccessor<'a> = MaybeDictArrayAccessor<'a, Int64Array>;
-pub type StringArrayAccessor<'a> = MaybeDictArrayAccessor<'a, StringArray>;
+impl NullableArrayAccessor for StringArrayAccessor<'_> {
+    type Native = String;
+
+    fn value_at(&self, idx: usize) -> Option<Self::Native> {
+        match self {
+            Self::Utf8(s) => s.value_at(idx),
+            Self::LargeUtf8(s) => s.value_at(idx),
+            Self::Utf8View(s) => s.value_at(idx),
+        }
+    }
+}
 
 pub struct DictionaryArrayAccessor<'a, K, V>
 where
@@ -491,6 +597,26 @@ where
     }
 }
 
+impl<'a, K, V> DictionaryArrayAccessor<'a, K, V>
+where
+    K: ArrowDictionaryKeyType,
+    V: StringColumnArray,
+{
+    fn str_at(&self, idx: usize) -> Option<&'a str> {
+        if self.inner.is_valid(idx) {
+            let offset = self
+                .inner
+                .key(idx)
+                .expect("dictionary should be valid at index");
+            self.value
+                .is_valid(offset)
+                .then(|| self.value.str_value(offset))
+        } else {
+            None
+        }
+    }
+}
+
 /// Helper for accessing columns of a struct array
 ///
 /// Methods return various errors into this crate's Error type if
@@ -533,6 +659,17 @@ impl<'a> StructColumnAccessor<'a> {
             .transpose()
     }
 
+    /// Returns the accessor of the column, if it is in the struct array.
+    pub fn accessor_column_op<A: ColumnAccessor<'a>>(
+        &self,
+        column_name: &str,
+    ) -> error::Result<Option<A>> {
+        self.inner
+            .column_by_name(column_name)
+            .map(A::try_new)
+            .transpose()
+    }
+
     pub fn bool_column_op(&self, column_name: &str) -> error::Result<Option<&'a BooleanArray>> {
         self.inner
             .column_by_name(column_name)
@@ -547,33 +684,18 @@ impl<'a> StructColumnAccessor<'a> {
             })
             .transpose()
     }
-
-    pub fn string_column_op(
-        &self,
-        column_name: &str,
-    ) -> error::Result<Option<StringArrayAccessor<'a>>> {
-        self.inner
-            .column_by_name(column_name)
-            .map(StringArrayAccessor::try_new)
-            .transpose()
-    }
-
-    pub fn byte_array_column_op(
-        &self,
-        column_name: &str,
-    ) -> error::Result<Option<ByteArrayAccessor<'a>>> {
-        self.inner
-            .column_by_name(column_name)
-            .map(ByteArrayAccessor::try_new)
-            .transpose()
-    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::arrays::{NullableArrayAccessor, StringArrayAccessor};
-    use arrow::array::{ArrayRef, DictionaryArray};
-    use arrow::datatypes::UInt16Type;
+    use crate::arrays::{
+        ByteArrayAccessor, ColumnAccessor, NullableArrayAccessor, StringArrayAccessor,
+    };
+    use arrow::array::{
+        ArrayRef, BinaryArray, BinaryViewArray, DictionaryArray, FixedSizeBinaryArray, Int32Array,
+        LargeBinaryArray, LargeStringArray, StringArray, StringViewArray,
+    };
+    use arrow::datatypes::{UInt8Type, UInt16Type};
     use std::sync::Arc;
 
     #[test]
@@ -586,4 +708,114 @@ mod tests {
         assert_eq!("b", accessor.value_at(2).unwrap());
         assert_eq!("c", accessor.value_at(3).unwrap());
     }
+
+    #[test]
+    fn test_string_accessor_variants() {
+        let values = vec![Some("a"), None, Some("b"), Some("a")];
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from(values.clone())),
+            Arc::new(LargeStringArray::from(values.clone())),
+            Arc::new(StringViewArray::from(values.clone())),
+            Arc::new(
+                values
+                    .iter()
+                    .copied()
+                    .collect::<DictionaryArray<UInt8Type>>(),
+            ),
+            Arc::new(
+                values
+                    .iter()
+                    .copied()
+                    .collect::<DictionaryArray<UInt16Type>>(),
+            ),
+            Arc::new(
+                DictionaryArray::<UInt8Type>::try_new(
+                    vec![0u8, 1, 2, 0].into(),
+                    Arc::new(LargeStringArray::from(vec![Some("a"), None, Some("b")])),
+                )
+                .unwrap(),
+            ),
+            Arc::new(
+                DictionaryArray::<UInt16Type>::try_new(
+                    vec![0u16, 1, 2, 0].into(),
+                    Arc::new(StringViewArray::from(vec![Some("a"), None, Some("b")])),
+                )
+                .unwrap(),
+            ),
+        ];
+        for arr in &arrays {
+            let accessor = StringArrayAccessor::try_new(arr).unwrap();
+            for (idx, value) in values.iter().enumerate() {
+                assert_eq!(accessor.value_at(idx).as_deref(), *value, "{arr:?}");
+                assert_eq!(accessor.str_at(idx), *value, "{arr:?}");
+            }
+        }
+
+        let arr = Arc::new(Int32Array::from(vec![1])) as ArrayRef;
+        assert!(StringArrayAccessor::try_new(&arr).is_err());
+    }
+
+    #[test]
+    fn test_byte_array_accessor_variants() {
+        let values: Vec<Option<&[u8]>> = vec![Some(b"a"), None, Some(b"bc")];
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(BinaryArray::from(values.clone())),
+            Arc::new(LargeBinaryArray::from(values.clone())),
+            Arc::new(BinaryViewArray::from(values.clone())),
+            Arc::new(
+                DictionaryArray::<UInt16Type>::try_new(
+                    vec![0u16, 1, 2].into(),
+                    Arc::new(LargeBinaryArray::from(values.clone())),
+                )
+                .unwrap(),
+            ),
+        ];
+        for arr in &arrays {
+            let accessor = ByteArrayAccessor::try_new(arr).unwrap();
+            for (idx, value) in values.iter().enumerate() {
+                assert_eq!(accessor.value_at(idx), value.map(<[u8]>::to_vec), "{arr:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_fixed_size_binary_accessor_variants() {
+        let values = [Some([1u8; 16]), None, Some([2u8; 16]), Some([1u8; 16])];
+        let ids = || {
+            FixedSizeBinaryArray::try_from_sparse_iter_with_size(values.iter().copied(), 16)
+                .unwrap()
+        };
+        let dict_values = Arc::new(
+            FixedSizeBinaryArray::try_from_iter(vec![[1u8; 16], [2u8; 16]].into_iter()).unwrap(),
+        );
+        let arrays: Vec<ArrayRef> = vec![
+            Arc::new(ids()),
+            Arc::new(
+                DictionaryArray::<UInt8Type>::try_new(
+                    vec![Some(0u8), None, Some(1), Some(0)].into(),
+                    dict_values.clone(),
+                )
+                .unwrap(),
+            ),
+            Arc::new(
+                DictionaryArray::<UInt16Type>::try_new(
+                    vec![Some(0u16), None, Some(1), Some(0)].into(),
+                    dict_values,
+                )
+                .unwrap(),
+            ),
+        ];
+        for arr in &arrays {
+            let accessor = ByteArrayAccessor::try_new(arr).unwrap();
+            for (idx, value) in values.iter().enumerate() {
+                assert_eq!(accessor.value_at(idx), value.map(Vec::from), "{arr:?}");
+            }
+        }
+
+        let span_ids =
+            Arc::new(FixedSizeBinaryArray::try_from_iter(vec![[3u8; 8]].into_iter()).unwrap())
+                as ArrayRef;
+        let accessor = ByteArrayAccessor::try_new(&span_ids).unwrap();
+        assert_eq!(accessor.value_at(0), Some(vec![3; 8]));
+    }
 }
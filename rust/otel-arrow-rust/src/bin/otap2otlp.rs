@@ -0,0 +1,156 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Converts OTAP batches into a protobuf encoded OTLP export request, e.g. to check the
+//! batches of another implementation decode to the expected request.
+//!
+//! ```text
+//! otap2otlp [--json] [INPUT [OUTPUT]]
+//! ```
+//!
+//! The batches are read from `INPUT`, or from the standard input, as a sequence of length
+//! delimited `BatchArrowRecords` messages of the same stream, see `otlp2otap`. They are
+//! decoded in order and merged into a single export request of their signal, written to
+//! `OUTPUT`, or to the standard output. With `--json`, the request of every batch is
+//! written as a line of OTLP/JSON instead.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+
+use prost::Message;
+
+use otel_arrow_rust::otlp::json::to_json_line;
+use otel_arrow_rust::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+use otel_arrow_rust::{Consumer, ExportRequest};
+
+const USAGE: &str = "usage: otap2otlp [--json] [INPUT [OUTPUT]]";
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut paths: Vec<_> = std::env::args().skip(1).collect();
+    let json = paths.first().is_some_and(|arg| arg == "--json");
+    if json {
+        let _ = paths.remove(0);
+    }
+    if paths.len() > 2 || paths.iter().any(|path| path.starts_with('-')) {
+        return Err(USAGE.into());
+    }
+    let input = match paths.first() {
+        Some(path) => fs::read(path)?,
+        None => {
+            let mut input = Vec::new();
+            let _ = io::stdin().read_to_end(&mut input)?;
+            input
+        }
+    };
+
+    let output = if json {
+        convert_json(&input)?
+    } else {
+        convert(&input)?
+    };
+    match paths.get(1) {
+        Some(path) => fs::write(path, output)?,
+        None => io::stdout().lock().write_all(&output)?,
+    }
+    Ok(())
+}
+
+fn convert_json(mut input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut consumer = Consumer::default();
+    let mut output = Vec::new();
+    while !input.is_empty() {
+        let mut batch = BatchArrowRecords::decode_length_delimited(&mut input)?;
+        let request = consumer.consume_batches(&mut batch)?;
+        output.extend_from_slice(to_json_line(&request).as_bytes());
+        output.push(b'\n');
+    }
+    Ok(output)
+}
+
+fn convert(mut input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut consumer = Consumer::default();
+    let mut merged: Option<ExportRequest> = None;
+    while !input.is_empty() {
+        let mut batch = BatchArrowRecords::decode_length_delimited(&mut input)?;
+        let request = consumer.consume_batches(&mut batch)?;
+        merged = Some(match (merged, request) {
+            (None, request) => request,
+            (Some(ExportRequest::Logs(mut merged)), ExportRequest::Logs(request)) => {
+                merged.resource_logs.extend(request.resource_logs);
+                ExportRequest::Logs(merged)
+            }
+            (Some(ExportRequest::Metrics(mut merged)), ExportRequest::Metrics(request)) => {
+                merged.resource_metrics.extend(request.resource_metrics);
+                ExportRequest::Metrics(merged)
+            }
+            (Some(ExportRequest::Traces(mut merged)), ExportRequest::Traces(request)) => {
+                merged.resource_spans.extend(request.resource_spans);
+                ExportRequest::Traces(merged)
+            }
+            _ => return Err("the batches carry different signals".into()),
+        });
+    }
+
+    Ok(match merged.ok_or("no batch in input")? {
+        ExportRequest::Logs(request) => request.encode_to_vec(),
+        ExportRequest::Metrics(request) => request.encode_to_vec(),
+        ExportRequest::Traces(request) => request.encode_to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use otel_arrow_rust::encode::LogsProducer;
+    use otel_arrow_rust::encode::split::to_batch_arrow_records;
+    use otel_arrow_rust::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+    use otel_arrow_rust::proto::opentelemetry::common::v1::InstrumentationScope;
+    use otel_arrow_rust::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+    use otel_arrow_rust::proto::opentelemetry::resource::v1::Resource;
+
+    fn request(time_unix_nano: u64) -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource::default()),
+                scope_logs: vec![ScopeLogs {
+                    scope: Some(InstrumentationScope::default()),
+                    log_records: vec![LogRecord {
+                        time_unix_nano,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_convert_merges_batches() {
+        let mut input = Vec::new();
+        for batch_id in 0..2 {
+            let otap_batch = LogsProducer::new()
+                .produce(&request(batch_id as u64 + 1))
+                .unwrap();
+            to_batch_arrow_records(&otap_batch, batch_id)
+                .unwrap()
+                .encode_length_delimited(&mut input)
+                .unwrap();
+        }
+
+        let output = convert(&input).unwrap();
+        let decoded = ExportLogsServiceRequest::decode(output.as_slice()).unwrap();
+        let mut expected = request(1);
+        expected.resource_logs.extend(request(2).resource_logs);
+        assert_eq!(decoded, expected);
+
+        assert!(convert(&[]).is_err());
+
+        let output = String::from_utf8(convert_json(&input).unwrap()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains(r#""timeUnixNano":"2""#));
+    }
+}
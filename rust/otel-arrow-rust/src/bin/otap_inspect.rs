@@ -0,0 +1,236 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dumps the contents of serialized `BatchArrowRecords` messages, to debug the batches
+//! exchanged with other OTAP implementations.
+//!
+//! ```text
+//! otap-inspect [--delimited] [--schemas] [--rows] [--otlp] [FILE]
+//! ```
+//!
+//! The messages are read from `FILE`, or from the standard input if no file is given. By
+//! default the input holds a single protobuf encoded message, `--delimited` reads a
+//! sequence of length delimited messages instead, the batches of the same stream being
+//! decoded by the same consumer. For every batch, the id, schema id, compression, size and
+//! number of rows of each payload are printed. `--schemas` also prints the schema of every
+//! payload, `--rows` its rows as JSON lines, and `--otlp` prints the OTLP request the batch
+//! decodes to.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+
+use arrow::json::LineDelimitedWriter;
+use prost::Message;
+
+use otel_arrow_rust::Consumer;
+use otel_arrow_rust::compression::payload_compression;
+use otel_arrow_rust::otap::OtapBatch;
+use otel_arrow_rust::otlp::logs::logs_from;
+use otel_arrow_rust::otlp::metrics::metrics_from;
+use otel_arrow_rust::otlp::traces::traces_from;
+use otel_arrow_rust::proto::opentelemetry::arrow::v1::{ArrowPayloadType, BatchArrowRecords};
+
+const USAGE: &str = "usage: otap-inspect [--delimited] [--schemas] [--rows] [--otlp] [FILE]";
+
+#[derive(Default)]
+struct Options {
+    delimited: bool,
+    schemas: bool,
+    rows: bool,
+    otlp: bool,
+    path: Option<String>,
+}
+
+impl Options {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        for arg in args {
+            match arg.as_str() {
+                "--delimited" => options.delimited = true,
+                "--schemas" => options.schemas = true,
+                "--rows" => options.rows = true,
+                "--otlp" => options.otlp = true,
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {arg}\n{USAGE}")),
+                _ if options.path.is_some() => return Err(USAGE.to_string()),
+                _ => options.path = Some(arg),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let input = match &options.path {
+        Some(path) => fs::read(path)?,
+        None => {
+            let mut input = Vec::new();
+            let _ = io::stdin().read_to_end(&mut input)?;
+            input
+        }
+    };
+
+    let mut out = io::stdout().lock();
+    inspect(&input, &options, &mut out)
+}
+
+fn inspect(
+    mut input: &[u8],
+    options: &Options,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    let mut consumer = Consumer::default();
+    if !options.delimited {
+        let mut records = BatchArrowRecords::decode(input)?;
+        return inspect_batch(&mut consumer, &mut records, options, out);
+    }
+    while !input.is_empty() {
+        let mut records = BatchArrowRecords::decode_length_delimited(&mut input)?;
+        inspect_batch(&mut consumer, &mut records, options, out)?;
+    }
+    Ok(())
+}
+
+fn inspect_batch(
+    consumer: &mut Consumer,
+    records: &mut BatchArrowRecords,
+    options: &Options,
+    out: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(
+        out,
+        "batch {}: {} payloads, {} bytes",
+        records.batch_id,
+        records.arrow_payloads.len(),
+        records.encoded_len()
+    )?;
+    let payloads: Vec<_> = records
+        .arrow_payloads
+        .iter()
+        .map(|payload| {
+            (
+                ArrowPayloadType::try_from(payload.r#type).ok(),
+                payload.schema_id.clone(),
+                payload.record.len(),
+                payload_compression(&payload.record).ok().flatten(),
+            )
+        })
+        .collect();
+    let otap_batch = consumer.consume_otap_batch(records)?;
+
+    for (payload_type, schema_id, len, compression) in payloads {
+        let Some(payload_type) = payload_type else {
+            writeln!(
+                out,
+                "  unknown payload type, schema id {schema_id}, {len} bytes"
+            )?;
+            continue;
+        };
+        let record_batch = otap_batch.get(payload_type);
+        writeln!(
+            out,
+            "  {}: schema id {}, {} bytes, compression {}, {} rows",
+            payload_type.as_str_name(),
+            schema_id,
+            len,
+            compression.map_or_else(|| "unknown".to_string(), |c| format!("{c:?}")),
+            record_batch.map_or(0, |rb| rb.num_rows()),
+        )?;
+        let Some(record_batch) = record_batch else {
+            continue;
+        };
+        if options.schemas {
+            for field in record_batch.schema().fields() {
+                writeln!(
+                    out,
+                    "    {}: {}{}",
+                    field.name(),
+                    field.data_type(),
+                    if field.is_nullable() {
+                        " (nullable)"
+                    } else {
+                        ""
+                    }
+                )?;
+            }
+        }
+        if options.rows {
+            let mut writer = LineDelimitedWriter::new(&mut *out);
+            writer.write(record_batch)?;
+            writer.finish()?;
+        }
+    }
+
+    if options.otlp {
+        match otap_batch {
+            OtapBatch::Logs(_) => writeln!(out, "{:#?}", logs_from(otap_batch)?)?,
+            OtapBatch::Metrics(_) => writeln!(out, "{:#?}", metrics_from(otap_batch)?)?,
+            OtapBatch::Traces(_) => writeln!(out, "{:#?}", traces_from(otap_batch)?)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use otel_arrow_rust::encode::TracesProducer;
+    use otel_arrow_rust::encode::split::to_batch_arrow_records;
+    use otel_arrow_rust::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+    use otel_arrow_rust::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+    #[test]
+    fn test_inspect_delimited() {
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        trace_id: vec![1; 16],
+                        span_id: vec![2; 8],
+                        name: "span".into(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        let mut input = Vec::new();
+        for batch_id in 0..2 {
+            to_batch_arrow_records(&otap_batch, batch_id)
+                .unwrap()
+                .encode_length_delimited(&mut input)
+                .unwrap();
+        }
+
+        let options = Options::parse(
+            ["--delimited", "--schemas", "--rows", "--otlp"]
+                .into_iter()
+                .map(String::from),
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        inspect(&input, &options, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("batch 0: 1 payloads"));
+        assert!(out.contains("batch 1: 1 payloads"));
+        assert!(out.contains("SPANS: schema id spans:1"));
+        assert!(out.contains("compression None, 1 rows"));
+        assert!(out.contains("    name: Dictionary(UInt8, Utf8)"));
+        assert!(out.contains(r#""name":"span""#));
+        assert!(out.contains("ExportTraceServiceRequest"));
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = Options::parse(["file.pb".to_string()].into_iter()).unwrap();
+        assert_eq!(options.path.as_deref(), Some("file.pb"));
+        assert!(!options.delimited);
+        assert!(Options::parse(["--unknown".to_string()].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Converts a protobuf encoded OTLP export request into OTAP batches, e.g. to generate test
+//! fixtures.
+//!
+//! ```text
+//! otlp2otap --signal logs|metrics|traces [--max-bytes N] [--sizes] [INPUT [OUTPUT]]
+//! ```
+//!
+//! The request is read from `INPUT`, or from the standard input, and the batches are
+//! written to `OUTPUT`, or to the standard output, as a sequence of length delimited
+//! `BatchArrowRecords` messages, see `otap-inspect --delimited` and `otap2otlp`. A single
+//! batch is written unless `--max-bytes` is given, in which case the request is split into
+//! batches of at most this size.
+//!
+//! With `--sizes`, the batches are not written: the sizes of the request encoded as OTLP and
+//! as OTAP, with and without zstd compression, are reported instead, see `SizeReport`.
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read, Write};
+
+use prost::Message;
+
+use otel_arrow_rust::ExportRequest;
+use otel_arrow_rust::encode::BatchSplitter;
+use otel_arrow_rust::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use otel_arrow_rust::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use otel_arrow_rust::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use otel_arrow_rust::size_report::SizeReport;
+
+const USAGE: &str =
+    "usage: otlp2otap --signal logs|metrics|traces [--max-bytes N] [--sizes] [INPUT [OUTPUT]]";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Signal {
+    Logs,
+    Metrics,
+    Traces,
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    signal: Option<Signal>,
+    max_bytes: Option<usize>,
+    sizes: bool,
+    paths: Vec<String>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--signal" => {
+                    options.signal = Some(match args.next().as_deref() {
+                        Some("logs") => Signal::Logs,
+                        Some("metrics") => Signal::Metrics,
+                        Some("traces") => Signal::Traces,
+                        _ => return Err(USAGE.to_string()),
+                    });
+                }
+                "--max-bytes" => {
+                    let max_bytes = args.next().and_then(|n| n.parse().ok());
+                    options.max_bytes = Some(max_bytes.ok_or_else(|| USAGE.to_string())?);
+                }
+                "--sizes" => options.sizes = true,
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ if arg.starts_with('-') => return Err(format!("unknown option {arg}\n{USAGE}")),
+                _ => options.paths.push(arg),
+            }
+        }
+        if options.signal.is_none() || options.paths.len() > 2 {
+            return Err(USAGE.to_string());
+        }
+        Ok(options)
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let input = match options.paths.first() {
+        Some(path) => fs::read(path)?,
+        None => {
+            let mut input = Vec::new();
+            let _ = io::stdin().read_to_end(&mut input)?;
+            input
+        }
+    };
+
+    let output = convert(&input, &options)?;
+    match options.paths.get(1) {
+        Some(path) => fs::write(path, output)?,
+        None => io::stdout().lock().write_all(&output)?,
+    }
+    Ok(())
+}
+
+fn convert(input: &[u8], options: &Options) -> Result<Vec<u8>, Box<dyn Error>> {
+    let request = match options.signal {
+        Some(Signal::Logs) => ExportRequest::Logs(ExportLogsServiceRequest::decode(input)?),
+        Some(Signal::Metrics) => {
+            ExportRequest::Metrics(ExportMetricsServiceRequest::decode(input)?)
+        }
+        Some(Signal::Traces) | None => {
+            ExportRequest::Traces(ExportTraceServiceRequest::decode(input)?)
+        }
+    };
+    if options.sizes {
+        let mut report = SizeReport::try_new(&request)?.to_string();
+        report.push('\n');
+        return Ok(report.into_bytes());
+    }
+
+    let mut splitter = BatchSplitter::new(options.max_bytes.unwrap_or(usize::MAX));
+    let batches = match &request {
+        ExportRequest::Logs(request) => splitter.split_logs(request)?,
+        ExportRequest::Metrics(request) => splitter.split_metrics(request)?,
+        ExportRequest::Traces(request) => splitter.split_traces(request)?,
+    };
+
+    let mut output = Vec::new();
+    for batch in batches {
+        batch.encode_length_delimited(&mut output)?;
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use otel_arrow_rust::Consumer;
+    use otel_arrow_rust::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+    use otel_arrow_rust::proto::opentelemetry::common::v1::AnyValue;
+    use otel_arrow_rust::proto::opentelemetry::common::v1::any_value::Value;
+    use otel_arrow_rust::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_convert_logs() {
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: (0..32)
+                        .map(|i| LogRecord {
+                            time_unix_nano: i,
+                            body: Some(AnyValue {
+                                value: Some(Value::StringValue(format!("log {i} ").repeat(32))),
+                            }),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let options = Options::parse(args(&["--signal", "logs", "--max-bytes", "4096"])).unwrap();
+        let output = convert(&request.encode_to_vec(), &options).unwrap();
+
+        let mut consumer = Consumer::default();
+        let mut input = output.as_slice();
+        let mut log_records = Vec::new();
+        while !input.is_empty() {
+            let mut batch = BatchArrowRecords::decode_length_delimited(&mut input).unwrap();
+            let decoded = consumer.consume_logs_batches(&mut batch).unwrap();
+            log_records.extend(
+                decoded
+                    .resource_logs
+                    .into_iter()
+                    .flat_map(|rl| rl.scope_logs)
+                    .flat_map(|sl| sl.log_records),
+            );
+        }
+        assert_eq!(
+            log_records,
+            request.resource_logs[0].scope_logs[0].log_records
+        );
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let options = Options::parse(args(&["--signal", "metrics", "in.pb", "out.pb"])).unwrap();
+        assert_eq!(options.signal, Some(Signal::Metrics));
+        assert_eq!(options.paths, vec!["in.pb", "out.pb"]);
+        assert!(Options::parse(args(&["in.pb"])).is_err());
+        assert!(Options::parse(args(&["--signal", "events"])).is_err());
+        assert!(Options::parse(args(&["--signal", "logs", "--max-bytes", "many"])).is_err());
+        assert!(
+            Options::parse(args(&["--signal", "logs", "--sizes"]))
+                .unwrap()
+                .sizes
+        );
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Cancellation of the conversions of large batches.
+//!
+//! A receiver passes a [`CancellationToken`] to the decoder, with
+//! [`DecoderOptions::with_cancellation`](crate::otlp::options::DecoderOptions::with_cancellation),
+//! or to the producers and the [`BatchSplitter`](crate::encode::BatchSplitter), and cancels
+//! it when the client disconnects. The conversions check the token between rows and fail
+//! with a cancellation error once it is cancelled, freeing the CPU without waiting for the
+//! end of the batch.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{self, Result};
+
+/// Shared flag cancelling the conversions it is passed to. The clones of a token share its
+/// state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token that is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the conversions using the token, or one of its clones.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns whether the token was cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Fails if the token is cancelled.
+pub(crate) fn check_cancelled(token: Option<&CancellationToken>) -> Result<()> {
+    match token {
+        Some(token) if token.is_cancelled() => error::CancelledSnafu.fail(),
+        _ => Ok(()),
+    }
+}
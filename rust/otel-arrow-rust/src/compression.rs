@@ -0,0 +1,320 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compression of the Arrow IPC streams carried by OTAP payloads.
+//!
+//! Like the Go implementation, OTAP relies on the body compression of the Arrow IPC
+//! format: the buffers of every record batch and dictionary batch message are compressed,
+//! and the codec is declared in the `BodyCompression` field of the message header. Readers
+//! don't need any out-of-band negotiation, but both sides must support the codec. zstd
+//! requires the `zstd` feature, enabled by default, and lz4 the `lz4` feature.
+//!
+//! The compression is chosen per stream by the producer, see [`PayloadWriter`]. The
+//! compression used by a received payload can be inspected with [`payload_compression`].
+//!
+//! [`PayloadWriter::try_new_with_delta_dictionaries`] also keeps the dictionaries of the
+//! stream, sending only the values added since the previous payloads. The consumers of this
+//! crate read such dictionary deltas.
+
+use arrow::array::RecordBatch;
+use arrow::datatypes::SchemaRef;
+use arrow::ipc::writer::{IpcWriteOptions, StreamWriter};
+use arrow::ipc::{CompressionType, Message, MessageHeader, root_as_message};
+use snafu::{ResultExt, ensure};
+
+use crate::delta::DeltaWriter;
+use crate::error::{self, Result};
+
+pub(crate) const CONTINUATION_MARKER: [u8; 4] = [0xff; 4];
+
+/// Codec used to compress the bodies of the Arrow IPC messages of a payload.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum PayloadCompression {
+    /// Bodies are not compressed.
+    #[default]
+    None,
+    /// Bodies are compressed with zstd. This is what the Go exporter uses by default.
+    Zstd,
+    /// Bodies are compressed with lz4 frames.
+    Lz4,
+}
+
+impl PayloadCompression {
+    /// Returns the codecs this build can encode and decode, in order of preference.
+    #[must_use]
+    pub fn supported() -> Vec<Self> {
+        let mut supported = Vec::new();
+        if cfg!(feature = "zstd") {
+            supported.push(Self::Zstd);
+        }
+        if cfg!(feature = "lz4") {
+            supported.push(Self::Lz4);
+        }
+        supported.push(Self::None);
+        supported
+    }
+
+    /// Returns true if this build can encode and decode payloads using this codec.
+    #[must_use]
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Self::None => true,
+            Self::Zstd => cfg!(feature = "zstd"),
+            Self::Lz4 => cfg!(feature = "lz4"),
+        }
+    }
+
+    /// Returns the Arrow IPC write options producing payloads compressed with this codec.
+    pub fn ipc_write_options(&self) -> Result<IpcWriteOptions> {
+        ensure!(self.is_supported(), error::UnsupportedCompressionSnafu {
+            compression: format!("{self:?}"),
+        });
+        IpcWriteOptions::default()
+            .try_with_compression(self.compression_type())
+            .context(error::WriteRecordBatchSnafu)
+    }
+
+    fn compression_type(&self) -> Option<CompressionType> {
+        match self {
+            Self::None => None,
+            Self::Zstd => Some(CompressionType::ZSTD),
+            Self::Lz4 => Some(CompressionType::LZ4_FRAME),
+        }
+    }
+}
+
+/// Writes the Arrow IPC stream of a payload type, one `ArrowPayload` record at a time.
+///
+/// The first record starts with the schema, and every record carries the dictionaries
+/// that changed since the previous one, so the writer must be kept for as long as the
+/// schema id of the payload type is in use.
+pub struct PayloadWriter {
+    compression: PayloadCompression,
+    stream: PayloadStream,
+}
+
+enum PayloadStream {
+    Replacements(StreamWriter<Vec<u8>>),
+    Deltas(Box<DeltaWriter>),
+}
+
+impl PayloadWriter {
+    /// Creates a writer for payloads of the given schema.
+    pub fn try_new(schema: &SchemaRef, compression: PayloadCompression) -> Result<Self> {
+        let stream_writer = StreamWriter::try_new_with_options(
+            Vec::new(),
+            schema,
+            compression.ipc_write_options()?,
+        )
+        .context(error::WriteRecordBatchSnafu)?;
+        Ok(Self {
+            compression,
+            stream: PayloadStream::Replacements(stream_writer),
+        })
+    }
+
+    /// Creates a writer for payloads of the given schema sending the dictionaries as
+    /// deltas: the keys of every record batch are remapped onto the dictionaries of the
+    /// stream, and only the values they don't hold yet are sent. A dictionary is sent again
+    /// in full once its values no longer fit its key type.
+    ///
+    /// The receivers must support dictionary deltas. Falls back to [`PayloadWriter::try_new`]
+    /// if the schema has dictionaries nested in lists, maps or other dictionaries.
+    pub fn try_new_with_delta_dictionaries(
+        schema: &SchemaRef,
+        compression: PayloadCompression,
+    ) -> Result<Self> {
+        match DeltaWriter::try_new(schema, compression.ipc_write_options()?)? {
+            Some(delta_writer) => Ok(Self {
+                compression,
+                stream: PayloadStream::Deltas(Box::new(delta_writer)),
+            }),
+            None => Self::try_new(schema, compression),
+        }
+    }
+
+    /// Returns the codec used by this writer.
+    #[must_use]
+    pub fn compression(&self) -> PayloadCompression {
+        self.compression
+    }
+
+    /// Encodes the record batch, returning the bytes to send as the `record` of the next
+    /// `ArrowPayload` of this payload type.
+    pub fn write(&mut self, record_batch: &RecordBatch) -> Result<Vec<u8>> {
+        match &mut self.stream {
+            PayloadStream::Replacements(stream_writer) => {
+                stream_writer
+                    .write(record_batch)
+                    .context(error::WriteRecordBatchSnafu)?;
+                Ok(std::mem::take(stream_writer.get_mut()))
+            }
+            PayloadStream::Deltas(delta_writer) => delta_writer.write(record_batch),
+        }
+    }
+}
+
+/// Returns the codec declared by the data messages of the `record` of an `ArrowPayload`.
+/// Returns `None` if the record doesn't contain any record batch or dictionary batch.
+pub fn payload_compression(record: &[u8]) -> Result<Option<PayloadCompression>> {
+    for IpcMessage { message, .. } in split_ipc_messages(record)? {
+        let compression = match message.header_type() {
+            MessageHeader::RecordBatch => message
+                .header_as_record_batch()
+                .and_then(|rb| rb.compression()),
+            MessageHeader::DictionaryBatch => message
+                .header_as_dictionary_batch()
+                .and_then(|db| db.data())
+                .and_then(|rb| rb.compression()),
+            _ => continue,
+        };
+        return match compression.map(|c| c.codec()) {
+            None => Ok(Some(PayloadCompression::None)),
+            Some(CompressionType::ZSTD) => Ok(Some(PayloadCompression::Zstd)),
+            Some(CompressionType::LZ4_FRAME) => Ok(Some(PayloadCompression::Lz4)),
+            Some(codec) => error::UnsupportedCompressionSnafu {
+                compression: format!("{codec:?}"),
+            }
+            .fail(),
+        };
+    }
+    Ok(None)
+}
+
+/// A message of an Arrow IPC stream.
+#[derive(Clone, Copy)]
+pub(crate) struct IpcMessage<'a> {
+    /// The flatbuffer header of the message.
+    pub(crate) header: &'a [u8],
+    /// The header, checked by the flatbuffer verifier.
+    pub(crate) message: Message<'a>,
+    /// The body of the message.
+    pub(crate) body: &'a [u8],
+}
+
+/// Splits the Arrow IPC stream of a payload into its messages, the headers being verified
+/// once here.
+pub(crate) fn split_ipc_messages(mut bytes: &[u8]) -> Result<Vec<IpcMessage<'_>>> {
+    let invalid = |reason: &str| {
+        error::InvalidIpcStreamSnafu {
+            reason: reason.to_string(),
+        }
+        .build()
+    };
+
+    let mut messages = Vec::new();
+    while !bytes.is_empty() {
+        if let Some(rest) = bytes.strip_prefix(&CONTINUATION_MARKER) {
+            bytes = rest;
+        }
+        let (len, rest) = bytes
+            .split_first_chunk::<4>()
+            .ok_or_else(|| invalid("truncated message length"))?;
+        let len = i32::from_le_bytes(*len);
+        if len == 0 {
+            // end of stream
+            break;
+        }
+        let len = usize::try_from(len).map_err(|_| invalid("negative message length"))?;
+        let (header, rest) = rest
+            .split_at_checked(len)
+            .ok_or_else(|| invalid("truncated message header"))?;
+        let message = root_as_message(header).map_err(|e| invalid(&e.to_string()))?;
+        let body_len =
+            usize::try_from(message.bodyLength()).map_err(|_| invalid("negative body length"))?;
+        let (body, rest) = rest
+            .split_at_checked(body_len)
+            .ok_or_else(|| invalid("truncated message body"))?;
+        messages.push(IpcMessage {
+            header,
+            message,
+            body,
+        });
+        bytes = rest;
+    }
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use arrow::array::{Array, DictionaryArray, UInt16Array};
+    use arrow::datatypes::{DataType, Field, Schema, UInt8Type};
+    use arrow::ipc::reader::StreamReader;
+
+    fn record_batch(keys: Vec<&str>) -> RecordBatch {
+        let ids = UInt16Array::from_iter_values(0..keys.len() as u16);
+        let keys: DictionaryArray<UInt8Type> = keys.into_iter().collect();
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::UInt16, true),
+                Field::new("key", keys.data_type().clone(), true),
+            ])),
+            vec![Arc::new(ids), Arc::new(keys)],
+        )
+        .unwrap()
+    }
+
+    fn round_trip(compression: PayloadCompression) {
+        let batches = [record_batch(vec!["a", "b"]), record_batch(vec!["c"; 64])];
+        let mut writer = PayloadWriter::try_new(&batches[0].schema(), compression).unwrap();
+        let mut reader: Option<StreamReader<Cursor<Vec<u8>>>> = None;
+        for batch in &batches {
+            let record = writer.write(batch).unwrap();
+            assert_eq!(payload_compression(&record).unwrap(), Some(compression));
+
+            let reader = match &mut reader {
+                Some(reader) => {
+                    *reader.get_mut() = Cursor::new(record);
+                    reader
+                }
+                None => reader.insert(StreamReader::try_new(Cursor::new(record), None).unwrap()),
+            };
+            assert_eq!(&reader.next().unwrap().unwrap(), batch);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(PayloadCompression::None);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd_round_trip() {
+        round_trip(PayloadCompression::Zstd);
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zstd_not_supported() {
+        assert!(!PayloadCompression::Zstd.is_supported());
+        assert!(!PayloadCompression::supported().contains(&PayloadCompression::Zstd));
+        assert!(
+            PayloadWriter::try_new(&record_batch(vec![]).schema(), PayloadCompression::Zstd)
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4_round_trip() {
+        round_trip(PayloadCompression::Lz4);
+    }
+
+    #[cfg(not(feature = "lz4"))]
+    #[test]
+    fn test_lz4_not_supported() {
+        assert!(!PayloadCompression::Lz4.is_supported());
+        assert!(!PayloadCompression::supported().contains(&PayloadCompression::Lz4));
+        assert!(
+            PayloadWriter::try_new(&record_batch(vec![]).schema(), PayloadCompression::Lz4)
+                .is_err()
+        );
+    }
+}
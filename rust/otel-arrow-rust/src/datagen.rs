@@ -0,0 +1,579 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Synthetic telemetry shaped like the one of a fleet of services, for benchmarking the
+//! receivers without a Go producer in the loop.
+//!
+//! A [`Generator`] builds OTLP requests and encodes them with the producers of
+//! [`crate::encode`], so the OTAP batches are sorted, delta encoded and dictionary encoded
+//! the way a producer does. The shape of the telemetry is set by a [`DatagenConfig`]:
+//!
+//! - the services are the resources, each with its name, version and host.
+//! - a trace is a tree of spans starting at a server span of one service and fanning out
+//!   to the client and internal spans of the services it calls, nested in time.
+//! - the attributes of the records take their values from pools whose size is set by the
+//!   [`Cardinality`] profile, from a few values per key to a value per record.
+//! - the metrics are the gauges, counters and latency histograms of every service.
+//!
+//! The generation is deterministic: the same configuration, seed included, always produces
+//! the same batches.
+
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs, SeverityNumber};
+use crate::proto::opentelemetry::metrics::v1::metric::Data;
+use crate::proto::opentelemetry::metrics::v1::{
+    AggregationTemporality, Gauge, Histogram, HistogramDataPoint, Metric, NumberDataPoint,
+    ResourceMetrics, ScopeMetrics, Sum, number_data_point,
+};
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::proto::opentelemetry::trace::v1::span::SpanKind;
+use crate::proto::opentelemetry::trace::v1::status::StatusCode;
+use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+
+const HTTP_METHODS: [&str; 4] = ["GET", "POST", "PUT", "DELETE"];
+const OPERATIONS: [&str; 6] = ["checkout", "login", "search", "cart", "payment", "profile"];
+const LATENCY_BOUNDS: [f64; 8] = [5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0];
+
+/// Number of distinct values the attributes of the records take.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Cardinality {
+    /// A handful of values per key, e.g. the regions of a deployment.
+    Low,
+    /// Hundreds of values per key, e.g. the routes of a service.
+    #[default]
+    Medium,
+    /// A value per record, e.g. request or user ids.
+    High,
+}
+
+impl Cardinality {
+    fn pool_size(self) -> u64 {
+        match self {
+            Self::Low => 8,
+            Self::Medium => 512,
+            Self::High => u64::MAX,
+        }
+    }
+}
+
+/// Shape of the generated telemetry, see the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatagenConfig {
+    seed: u64,
+    services: usize,
+    cardinality: Cardinality,
+    attributes_per_record: usize,
+    spans_per_trace: usize,
+    error_rate: f64,
+}
+
+impl Default for DatagenConfig {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            services: 8,
+            cardinality: Cardinality::default(),
+            attributes_per_record: 4,
+            spans_per_trace: 8,
+            error_rate: 0.02,
+        }
+    }
+}
+
+impl DatagenConfig {
+    /// Sets the seed the telemetry is drawn from.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the number of services emitting the telemetry, at least one.
+    #[must_use]
+    pub fn with_services(mut self, services: usize) -> Self {
+        self.services = services.max(1);
+        self
+    }
+
+    /// Sets the cardinality of the attributes.
+    #[must_use]
+    pub fn with_cardinality(mut self, cardinality: Cardinality) -> Self {
+        self.cardinality = cardinality;
+        self
+    }
+
+    /// Sets the number of attributes of every record, on top of the ones describing it,
+    /// e.g. the HTTP method of a span.
+    #[must_use]
+    pub fn with_attributes_per_record(mut self, attributes_per_record: usize) -> Self {
+        self.attributes_per_record = attributes_per_record;
+        self
+    }
+
+    /// Sets the number of spans of every trace, at least one.
+    #[must_use]
+    pub fn with_spans_per_trace(mut self, spans_per_trace: usize) -> Self {
+        self.spans_per_trace = spans_per_trace.max(1);
+        self
+    }
+
+    /// Sets the fraction of the spans failing, and of the logs being errors, clamped to
+    /// `[0, 1]`.
+    #[must_use]
+    pub fn with_error_rate(mut self, error_rate: f64) -> Self {
+        self.error_rate = error_rate.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// Generates synthetic OTLP requests and OTAP batches, see the module documentation.
+///
+/// The timestamps of the generated records advance from one request to the next, like the
+/// ones of a live stream.
+#[derive(Debug)]
+pub struct Generator {
+    config: DatagenConfig,
+    rng: u64,
+    now: u64,
+    next_id: u64,
+}
+
+impl Generator {
+    /// Creates a generator of telemetry with the given shape.
+    #[must_use]
+    pub fn new(config: DatagenConfig) -> Self {
+        Self {
+            // xorshift is stuck at 0
+            rng: config.seed | 1,
+            config,
+            now: 1_700_000_000_000_000_000,
+            next_id: 1,
+        }
+    }
+
+    /// Returns the shape of the generated telemetry.
+    #[must_use]
+    pub fn config(&self) -> &DatagenConfig {
+        &self.config
+    }
+
+    /// Generates a request of `traces` traces, of the configured number of spans each.
+    pub fn traces_request(&mut self, traces: usize) -> ExportTraceServiceRequest {
+        let mut spans_by_service = vec![Vec::new(); self.config.services];
+        for _ in 0..traces {
+            let trace_id = self.id::<16>();
+            let root_service = self.below(self.config.services as u64) as usize;
+            let start = self.tick(1_000_000);
+            let duration = 1_000_000 + self.below(500_000_000);
+            let root = self.span(&trace_id, &[], SpanKind::Server, start, duration);
+            // the spans of the trace the next ones are children of, with their service,
+            // start, duration and id
+            let mut tree = vec![(root_service, start, duration, root.span_id.clone())];
+            spans_by_service[root_service].push(root);
+            while tree.len() < self.config.spans_per_trace {
+                let parent = self.below(tree.len() as u64) as usize;
+                let (parent_service, parent_start, parent_duration, parent_id) =
+                    tree[parent].clone();
+                let (service, kind) = if self.chance(0.5) {
+                    let callee = self.below(self.config.services as u64) as usize;
+                    (callee, SpanKind::Client)
+                } else {
+                    (parent_service, SpanKind::Internal)
+                };
+                let start = parent_start + self.below(parent_duration / 2 + 1);
+                let duration = self.below(parent_start + parent_duration - start) + 1;
+                let span = self.span(&trace_id, &parent_id, kind, start, duration);
+                tree.push((service, start, duration, span.span_id.clone()));
+                spans_by_service[service].push(span);
+            }
+        }
+
+        let resource_spans = spans_by_service
+            .into_iter()
+            .enumerate()
+            .filter(|(_, spans)| !spans.is_empty())
+            .map(|(service, spans)| ResourceSpans {
+                resource: Some(self.resource(service)),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(scope()),
+                    spans,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect();
+        ExportTraceServiceRequest { resource_spans }
+    }
+
+    /// Generates a request of `log_records` log records, spread over the services.
+    pub fn logs_request(&mut self, log_records: usize) -> ExportLogsServiceRequest {
+        let mut logs_by_service = vec![Vec::new(); self.config.services];
+        for _ in 0..log_records {
+            let service = self.below(self.config.services as u64) as usize;
+            let time = self.tick(100_000);
+            let (severity, text) = if self.chance(self.config.error_rate) {
+                (SeverityNumber::Error, "ERROR")
+            } else if self.chance(0.1) {
+                (SeverityNumber::Warn, "WARN")
+            } else if self.chance(0.3) {
+                (SeverityNumber::Debug, "DEBUG")
+            } else {
+                (SeverityNumber::Info, "INFO")
+            };
+            let operation = self.pick(&OPERATIONS);
+            let body = format!(
+                "{operation} completed for user {} in {}ms",
+                self.value(u64::MAX),
+                self.below(1000)
+            );
+            // half of the logs are emitted in a span
+            let (trace_id, span_id) = if self.chance(0.5) {
+                (self.id::<16>(), self.id::<8>())
+            } else {
+                (Vec::new(), Vec::new())
+            };
+            let mut attributes = vec![string_attr("operation", operation)];
+            attributes.extend(self.attributes());
+            logs_by_service[service].push(LogRecord {
+                time_unix_nano: time,
+                observed_time_unix_nano: time + self.below(1_000_000),
+                severity_number: severity as i32,
+                severity_text: text.into(),
+                body: Some(AnyValue {
+                    value: Some(Value::StringValue(body)),
+                }),
+                attributes,
+                trace_id,
+                span_id,
+                ..Default::default()
+            });
+        }
+
+        let resource_logs = logs_by_service
+            .into_iter()
+            .enumerate()
+            .filter(|(_, log_records)| !log_records.is_empty())
+            .map(|(service, log_records)| ResourceLogs {
+                resource: Some(self.resource(service)),
+                scope_logs: vec![ScopeLogs {
+                    scope: Some(scope()),
+                    log_records,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect();
+        ExportLogsServiceRequest { resource_logs }
+    }
+
+    /// Generates a request of the metrics of every service, with `data_points` data points
+    /// per metric.
+    pub fn metrics_request(&mut self, data_points: usize) -> ExportMetricsServiceRequest {
+        let start = self.now;
+        let time = self.tick(10_000_000_000);
+        let resource_metrics = (0..self.config.services)
+            .map(|service| {
+                let cpu = (0..data_points)
+                    .map(|_| NumberDataPoint {
+                        attributes: self.attributes(),
+                        time_unix_nano: time,
+                        value: Some(number_data_point::Value::AsDouble(
+                            self.below(10_000) as f64 / 100.0,
+                        )),
+                        ..Default::default()
+                    })
+                    .collect();
+                let requests = (0..data_points)
+                    .map(|_| {
+                        let mut attributes =
+                            vec![string_attr("http.method", self.pick(&HTTP_METHODS))];
+                        attributes.extend(self.attributes());
+                        NumberDataPoint {
+                            attributes,
+                            start_time_unix_nano: start,
+                            time_unix_nano: time,
+                            value: Some(
+                                number_data_point::Value::AsInt(self.below(100_000) as i64),
+                            ),
+                            ..Default::default()
+                        }
+                    })
+                    .collect();
+                let latencies = (0..data_points)
+                    .map(|_| self.latency(start, time))
+                    .collect();
+                let metrics = vec![
+                    Metric {
+                        name: "system.cpu.utilization".into(),
+                        unit: "%".into(),
+                        data: Some(Data::Gauge(Gauge { data_points: cpu })),
+                        ..Default::default()
+                    },
+                    Metric {
+                        name: "http.server.requests".into(),
+                        unit: "{request}".into(),
+                        data: Some(Data::Sum(Sum {
+                            data_points: requests,
+                            aggregation_temporality: AggregationTemporality::Cumulative as i32,
+                            is_monotonic: true,
+                        })),
+                        ..Default::default()
+                    },
+                    Metric {
+                        name: "http.server.duration".into(),
+                        unit: "ms".into(),
+                        data: Some(Data::Histogram(Histogram {
+                            data_points: latencies,
+                            aggregation_temporality: AggregationTemporality::Delta as i32,
+                        })),
+                        ..Default::default()
+                    },
+                ];
+                ResourceMetrics {
+                    resource: Some(self.resource(service)),
+                    scope_metrics: vec![ScopeMetrics {
+                        scope: Some(scope()),
+                        metrics,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }
+            })
+            .collect();
+        ExportMetricsServiceRequest { resource_metrics }
+    }
+
+    /// Generates the OTAP batch of `traces` traces, see [`Generator::traces_request`].
+    pub fn traces(&mut self, traces: usize) -> Result<OtapBatch> {
+        TracesProducer::new().produce(&self.traces_request(traces))
+    }
+
+    /// Generates the OTAP batch of `log_records` log records, see
+    /// [`Generator::logs_request`].
+    pub fn logs(&mut self, log_records: usize) -> Result<OtapBatch> {
+        LogsProducer::new().produce(&self.logs_request(log_records))
+    }
+
+    /// Generates the OTAP batch of the metrics of every service, see
+    /// [`Generator::metrics_request`].
+    pub fn metrics(&mut self, data_points: usize) -> Result<OtapBatch> {
+        MetricsProducer::new().produce(&self.metrics_request(data_points))
+    }
+
+    fn span(
+        &mut self,
+        trace_id: &[u8],
+        parent_span_id: &[u8],
+        kind: SpanKind,
+        start: u64,
+        duration: u64,
+    ) -> Span {
+        let operation = self.pick(&OPERATIONS);
+        let mut attributes = vec![
+            string_attr("http.method", self.pick(&HTTP_METHODS)),
+            string_attr("http.route", &format!("/api/{operation}")),
+        ];
+        let failed = self.chance(self.config.error_rate);
+        attributes.push(KeyValue {
+            key: "http.status_code".into(),
+            value: Some(AnyValue {
+                value: Some(Value::IntValue(if failed { 500 } else { 200 })),
+            }),
+        });
+        attributes.extend(self.attributes());
+        Span {
+            trace_id: trace_id.to_vec(),
+            span_id: self.id::<8>(),
+            parent_span_id: parent_span_id.to_vec(),
+            name: format!("{} {operation}", kind.as_str_name()),
+            kind: kind as i32,
+            start_time_unix_nano: start,
+            end_time_unix_nano: start + duration,
+            attributes,
+            status: failed.then(|| Status {
+                code: StatusCode::Error as i32,
+                message: "internal error".into(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn latency(&mut self, start: u64, time: u64) -> HistogramDataPoint {
+        let mut bucket_counts = vec![0; LATENCY_BOUNDS.len() + 1];
+        let mut sum = 0.0;
+        let count = 1 + self.below(256);
+        for _ in 0..count {
+            // mostly fast requests, with a long tail
+            let latency = (self.below(100) * self.below(100)) as f64 / 10.0;
+            sum += latency;
+            let bucket = LATENCY_BOUNDS
+                .iter()
+                .position(|bound| latency <= *bound)
+                .unwrap_or(LATENCY_BOUNDS.len());
+            bucket_counts[bucket] += 1;
+        }
+        HistogramDataPoint {
+            attributes: self.attributes(),
+            start_time_unix_nano: start,
+            time_unix_nano: time,
+            count,
+            sum: Some(sum),
+            bucket_counts,
+            explicit_bounds: LATENCY_BOUNDS.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn resource(&mut self, service: usize) -> Resource {
+        Resource {
+            attributes: vec![
+                string_attr("service.name", &format!("service-{service}")),
+                string_attr("service.version", &format!("1.{}.0", service % 4)),
+                string_attr("host.name", &format!("host-{}", service % 3)),
+            ],
+            ..Default::default()
+        }
+    }
+
+    /// The attributes of a record on top of the ones describing it.
+    fn attributes(&mut self) -> Vec<KeyValue> {
+        let pool_size = self.config.cardinality.pool_size();
+        (0..self.config.attributes_per_record)
+            .map(|i| {
+                let value = self.value(pool_size);
+                string_attr(&format!("attr.{i}"), &value)
+            })
+            .collect()
+    }
+
+    /// A value of a pool of `pool_size` values.
+    fn value(&mut self, pool_size: u64) -> String {
+        if pool_size == u64::MAX {
+            self.next_id += 1;
+            format!("value-{}", self.next_id)
+        } else {
+            format!("value-{}", self.below(pool_size))
+        }
+    }
+
+    fn id<const N: usize>(&mut self) -> Vec<u8> {
+        let mut id = vec![0; N];
+        for chunk in id.chunks_mut(8) {
+            let bytes = self.next_random().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        id
+    }
+
+    fn pick<'a>(&mut self, values: &[&'a str]) -> &'a str {
+        values[self.below(values.len() as u64) as usize]
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_random() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    /// Advances the time by up to `max_step` nanoseconds, returning the new time.
+    fn tick(&mut self, max_step: u64) -> u64 {
+        self.now += 1 + self.below(max_step);
+        self.now
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_random() % bound.max(1)
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+}
+
+fn string_attr(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.into(),
+        value: Some(AnyValue {
+            value: Some(Value::StringValue(value.into())),
+        }),
+    }
+}
+
+fn scope() -> InstrumentationScope {
+    InstrumentationScope {
+        name: "otel-arrow-datagen".into(),
+        version: env!("CARGO_PKG_VERSION").into(),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+    use crate::{Consumer, ExportRequest};
+
+    #[test]
+    fn test_generator() {
+        let config = DatagenConfig::default()
+            .with_services(3)
+            .with_spans_per_trace(5)
+            .with_cardinality(Cardinality::Low);
+        let request = Generator::new(config.clone()).traces_request(4);
+        let spans: Vec<_> = request
+            .resource_spans
+            .iter()
+            .flat_map(|rs| &rs.scope_spans[0].spans)
+            .collect();
+        assert_eq!(spans.len(), 20);
+        let roots = spans.iter().filter(|s| s.parent_span_id.is_empty()).count();
+        assert_eq!(roots, 4);
+        for span in &spans {
+            // the spans are nested in their parent
+            if let Some(parent) = spans.iter().find(|p| p.span_id == span.parent_span_id) {
+                assert!(span.start_time_unix_nano >= parent.start_time_unix_nano);
+                assert!(span.end_time_unix_nano <= parent.end_time_unix_nano);
+            }
+        }
+        // the generation is deterministic
+        assert_eq!(Generator::new(config.clone()).traces_request(4), request);
+
+        let mut generator = Generator::new(config);
+        let otap_batch = generator.traces(4).unwrap();
+        let mut records = to_batch_arrow_records(&otap_batch, 0).unwrap();
+        let Ok(ExportRequest::Traces(decoded)) = Consumer::default().consume_batches(&mut records)
+        else {
+            panic!("expected traces");
+        };
+        let decoded_spans: usize = decoded
+            .resource_spans
+            .iter()
+            .map(|rs| rs.scope_spans[0].spans.len())
+            .sum();
+        assert_eq!(decoded_spans, 20);
+
+        let otap_batch = generator.logs(50).unwrap();
+        assert_eq!(
+            otap_batch.get(ArrowPayloadType::Logs).unwrap().num_rows(),
+            50
+        );
+        let otap_batch = generator.metrics(2).unwrap();
+        let data_points = otap_batch.get(ArrowPayloadType::NumberDataPoints).unwrap();
+        assert_eq!(data_points.num_rows(), 3 * 2 * 2);
+        assert!(
+            otap_batch
+                .get(ArrowPayloadType::HistogramDataPoints)
+                .is_some()
+        );
+    }
+}
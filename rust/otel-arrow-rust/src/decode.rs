@@ -10,5 +10,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod checkpoint;
 pub mod decoder;
+pub mod passthrough;
+pub mod payload_registry;
 pub mod record_message;
+pub mod routing;
+pub mod schema_registry;
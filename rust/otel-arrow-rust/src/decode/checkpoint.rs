@@ -0,0 +1,125 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checkpoints of the streams of a [`Consumer`](crate::Consumer), so that a receiver can
+//! resume a stream after a restart without the client resetting it.
+//!
+//! The payloads of a stream rely on the schema and dictionaries received earlier on the
+//! stream. Once the checkpoints are enabled, the consumer keeps the IPC messages of the
+//! schema and dictionaries of every stream, the dictionaries replaced by later ones being
+//! dropped, and a checkpoint holds them. Restoring a checkpoint replays them to new
+//! readers. A checkpoint is serialized as a protobuf message, see
+//! [`ConsumerCheckpoint::to_bytes`].
+
+use arrow::ipc::MessageHeader;
+use prost::Message;
+
+use crate::compression::{CONTINUATION_MARKER, IpcMessage, split_ipc_messages};
+use crate::error::{self, Result};
+
+/// The state of the streams of a consumer, see the module documentation.
+#[derive(Clone, PartialEq, Message)]
+pub struct ConsumerCheckpoint {
+    #[prost(message, repeated, tag = "1")]
+    pub(crate) streams: Vec<StreamCheckpoint>,
+}
+
+impl ConsumerCheckpoint {
+    /// Returns the number of streams in the checkpoint.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns true if the checkpoint has no stream.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Serializes the checkpoint.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode_to_vec()
+    }
+
+    /// Deserializes a checkpoint serialized with [`Self::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::decode(bytes).map_err(|e| {
+            error::InvalidCheckpointSnafu {
+                reason: e.to_string(),
+            }
+            .build()
+        })
+    }
+}
+
+/// The state of the stream of a payload.
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct StreamCheckpoint {
+    #[prost(int32, tag = "1")]
+    pub(crate) main_payload_type: i32,
+    #[prost(string, tag = "2")]
+    pub(crate) schema_id: String,
+    #[prost(int32, tag = "3")]
+    pub(crate) payload_type: i32,
+    #[prost(message, optional, tag = "4")]
+    pub(crate) state: Option<StreamState>,
+}
+
+/// The IPC messages of the schema and current dictionaries of a stream, in order.
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct StreamState {
+    #[prost(bytes = "vec", tag = "1")]
+    pub(crate) schema: Vec<u8>,
+    #[prost(message, repeated, tag = "2")]
+    pub(crate) dictionaries: Vec<DictionaryState>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub(crate) struct DictionaryState {
+    #[prost(int64, tag = "1")]
+    pub(crate) id: i64,
+    #[prost(bytes = "vec", tag = "2")]
+    pub(crate) message: Vec<u8>,
+}
+
+impl StreamState {
+    /// Returns the state after the IPC messages of a payload of the stream.
+    pub(crate) fn from_ipc(bytes: &[u8], mut state: Self) -> Result<Self> {
+        for IpcMessage {
+            header,
+            message,
+            body,
+        } in split_ipc_messages(bytes)?
+        {
+            let mut encoded = Vec::with_capacity(8 + header.len() + body.len());
+            encoded.extend_from_slice(&CONTINUATION_MARKER);
+            encoded.extend_from_slice(&(header.len() as i32).to_le_bytes());
+            encoded.extend_from_slice(header);
+            encoded.extend_from_slice(body);
+            match message.header_type() {
+                MessageHeader::Schema => {
+                    state.schema = encoded;
+                    state.dictionaries.clear();
+                }
+                MessageHeader::DictionaryBatch => {
+                    // safety: the header type was just checked
+                    let dictionary = message
+                        .header_as_dictionary_batch()
+                        .expect("dictionary batch header");
+                    let id = dictionary.id();
+                    if !dictionary.isDelta() {
+                        state.dictionaries.retain(|d| d.id != id);
+                    }
+                    state.dictionaries.push(DictionaryState {
+                        id,
+                        message: encoded,
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(state)
+    }
+}
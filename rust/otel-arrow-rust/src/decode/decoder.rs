@@ -10,39 +10,101 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::decode::checkpoint::{ConsumerCheckpoint, StreamCheckpoint, StreamState};
+use crate::decode::payload_registry::{PayloadRegistry, PayloadRoute};
 use crate::decode::record_message::RecordMessage;
+use crate::decode::schema_registry::{SchemaEvent, SchemaRegistry};
+use crate::delta::DeltaReader;
 use crate::error;
 use crate::otap::{OtapBatch, from_record_messages};
-use crate::otlp::logs::logs_from;
-use crate::otlp::metrics::metrics_from;
+use crate::otlp::logs::logs_from_with_report;
+use crate::otlp::metrics::metrics_from_with_report;
+use crate::otlp::options::{DecoderOptions, UnknownPayloadPolicy};
+use crate::otlp::report::DecodeReport;
+#[cfg(feature = "runtime")]
+use crate::otlp::task::spawn_request_from;
+use crate::otlp::traces::{TracesBytesDecoder, traces_from_with_report};
 use crate::proto::opentelemetry::arrow::v1::{ArrowPayload, ArrowPayloadType, BatchArrowRecords};
 use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
 use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::telemetry::{self, DecodeStats, DecodeStatsCallback, MetricsSink};
 use arrow::array::RecordBatch;
 use arrow::error::ArrowError;
 use arrow::ipc::reader::StreamReader;
+use prost::Message;
+use prost::bytes::Bytes;
 use snafu::{ResultExt, ensure};
 use std::collections::HashMap;
 use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Instant;
 
 pub struct StreamConsumer {
-    payload_type: ArrowPayloadType,
-    stream_reader: StreamReader<Cursor<Vec<u8>>>,
+    payload_type: i32,
+    stream_reader: StreamReader<Cursor<Bytes>>,
+    /// The schema and dictionaries of the stream, if the consumer records checkpoints.
+    state: Option<StreamState>,
+    /// Dictionaries restored from a checkpoint, read before the next payload.
+    pending: Vec<u8>,
+    /// Rewrites the dictionary deltas, which the stream reader doesn't support.
+    deltas: DeltaReader,
 }
 
 impl StreamConsumer {
-    fn new(payload: ArrowPayloadType, initial_bytes: Vec<u8>) -> error::Result<Self> {
+    fn new(payload: i32, initial_bytes: Vec<u8>, checkpoints: bool) -> error::Result<Self> {
+        let mut deltas = DeltaReader::default();
+        let initial_bytes = deltas.rewrite(initial_bytes)?;
+        let state = checkpoints
+            .then(|| StreamState::from_ipc(&initial_bytes, StreamState::default()))
+            .transpose()?;
         let data = Cursor::new(initial_bytes);
         let stream_reader =
             StreamReader::try_new(data.clone(), None).context(error::BuildStreamReaderSnafu)?;
         Ok(Self {
             payload_type: payload,
             stream_reader,
+            state,
+            pending: Vec::new(),
+            deltas,
         })
     }
 
-    fn replace_bytes(&mut self, bytes: Vec<u8>) {
+    /// Creates the consumer of a stream from its checkpointed state.
+    fn restore(payload: i32, state: StreamState) -> error::Result<Self> {
+        let stream_reader = StreamReader::try_new(Cursor::new(state.schema.clone().into()), None)
+            .context(error::BuildStreamReaderSnafu)?;
+        let pending: Vec<u8> = state
+            .dictionaries
+            .iter()
+            .flat_map(|d| d.message.clone())
+            .collect();
+        // the checkpoints only hold replacements, the reader learns the dictionaries that
+        // the next deltas extend
+        let mut deltas = DeltaReader::default();
+        let _ = deltas.rewrite(state.schema.clone())?;
+        let pending = Vec::from(deltas.rewrite(pending)?);
+        Ok(Self {
+            payload_type: payload,
+            stream_reader,
+            pending,
+            state: Some(state),
+            deltas,
+        })
+    }
+
+    fn replace_bytes(&mut self, bytes: Vec<u8>) -> error::Result<()> {
+        let mut bytes = self.deltas.rewrite(bytes)?;
+        if let Some(state) = self.state.take() {
+            self.state = Some(StreamState::from_ipc(&bytes, state)?);
+        }
+        if !self.pending.is_empty() {
+            let mut pending = std::mem::take(&mut self.pending);
+            pending.extend_from_slice(&bytes);
+            bytes = pending.into();
+        }
         *self.stream_reader.get_mut() = Cursor::new(bytes);
+        Ok(())
     }
 
     fn next(&mut self) -> Option<Result<RecordBatch, ArrowError>> {
@@ -50,46 +112,395 @@ impl StreamConsumer {
     }
 }
 
+/// Identifies the sub-stream a payload belongs to.
+///
+/// Logs, metrics and traces can be multiplexed over the same OTAP stream. Their payloads
+/// may use the same schema id (e.g. the resource attributes of logs and spans) while
+/// having their own schemas and dictionaries, so the payloads of each signal are tracked
+/// separately, keyed by the main payload type of the batch they arrive in.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct StreamKey {
+    main_payload_type: ArrowPayloadType,
+    schema_id: String,
+}
+
+/// OTLP export request decoded from a `BatchArrowRecords` of any signal.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExportRequest {
+    /// Decoded logs.
+    Logs(ExportLogsServiceRequest),
+    /// Decoded metrics.
+    Metrics(ExportMetricsServiceRequest),
+    /// Decoded traces.
+    Traces(ExportTraceServiceRequest),
+}
+
+/// OTLP export requests decoded from a `BatchArrowRecords` carrying the payloads of several
+/// signals, see [`Consumer::consume_multi_signal_batches`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MultiSignalRequest {
+    /// Decoded logs, if the batch carries logs.
+    pub logs: Option<ExportLogsServiceRequest>,
+    /// Decoded metrics, if the batch carries metrics.
+    pub metrics: Option<ExportMetricsServiceRequest>,
+    /// Decoded traces, if the batch carries traces.
+    pub traces: Option<ExportTraceServiceRequest>,
+}
+
+/// Signals of a `BatchArrowRecords` decoded before the deadline of the consumer, see
+/// [`Consumer::consume_multi_signal_batches_partial`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartialMultiSignalRequest {
+    /// Requests of the signals decoded.
+    pub request: MultiSignalRequest,
+    /// Payloads of the signals left to decode, `None` if all were decoded.
+    pub continuation: Option<BatchArrowRecords>,
+}
+
+/// Payload of an unknown type skipped by the [`UnknownPayloadPolicy::Skip`] policy, kept as
+/// received.
+///
+/// The payloads of a type form an IPC stream: forwarding them, in order, to a receiver that
+/// knows the type lets it decode them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkippedPayload {
+    /// Id of the batch the payload arrived in.
+    pub batch_id: i64,
+    /// The payload.
+    pub payload: ArrowPayload,
+}
+
 /// Consumer consumes OTAP `BatchArrowRecords` and converts them into OTLP messages.
+///
+/// A single consumer can be used for a stream that carries batches of several signals.
 #[derive(Default)]
 pub struct Consumer {
-    stream_consumers: HashMap<String, StreamConsumer>,
+    stream_consumers: HashMap<StreamKey, StreamConsumer>,
+    options: DecoderOptions,
+    schema_registry: SchemaRegistry,
+    schema_events: Vec<SchemaEvent>,
+    skipped_payloads: Vec<SkippedPayload>,
+    decode_report: DecodeReport,
+    metrics_sink: Option<Arc<dyn MetricsSink>>,
+    stats_callback: Option<Arc<dyn DecodeStatsCallback>>,
+    deadline: Option<Instant>,
+    /// Statistics of the batch being decoded, if a callback receives them.
+    batch_stats: Option<DecodeStats>,
+    traces_bytes_decoder: TracesBytesDecoder,
+    payload_registry: PayloadRegistry,
+    checkpoints: bool,
 }
 
 impl Consumer {
+    /// Creates a consumer decoding the batches with the given options.
+    #[must_use]
+    pub fn with_options(options: DecoderOptions) -> Self {
+        Self {
+            options,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the registry of the schemas received on the stream.
+    #[must_use]
+    pub fn schema_registry(&self) -> &SchemaRegistry {
+        &self.schema_registry
+    }
+
+    /// Returns the schema changes observed since the last call, in the order they were
+    /// received.
+    pub fn take_schema_events(&mut self) -> Vec<SchemaEvent> {
+        std::mem::take(&mut self.schema_events)
+    }
+
+    /// Returns the payloads skipped because of [`UnknownPayloadPolicy::Skip`] since the last
+    /// call, in the order they were received.
+    pub fn take_skipped_payloads(&mut self) -> Vec<SkippedPayload> {
+        std::mem::take(&mut self.skipped_payloads)
+    }
+
+    /// Returns the rows dropped because of [`DecoderOptions::skip_bad_rows`] and the out of
+    /// range delta ids since the last call.
+    pub fn take_decode_report(&mut self) -> DecodeReport {
+        std::mem::take(&mut self.decode_report)
+    }
+
+    /// Sets the decoders of the payload types that are not part of the OTAP specification,
+    /// the payloads of other unknown types are handled by the [`UnknownPayloadPolicy`].
+    pub fn set_payload_registry(&mut self, registry: PayloadRegistry) {
+        self.payload_registry = registry;
+    }
+
+    /// Reports the measurements of the consumer to the given sink.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics_sink = Some(sink);
+    }
+
+    /// Passes the statistics of every batch decoded from now on to the given callback.
+    pub fn set_decode_stats_callback(&mut self, callback: Arc<dyn DecodeStatsCallback>) {
+        self.stats_callback = Some(callback);
+    }
+
+    /// Sets the instant the batches must be decoded by, e.g. the deadline of the gRPC request
+    /// carrying them, `None` to decode them however long it takes.
+    ///
+    /// The deadline is checked before reading each payload and before converting the
+    /// payloads into the OTLP request, the decoding failing with a `DeadlineExceeded` error
+    /// listing the payloads read past it. The conversion itself is bounded by
+    /// [`DecodeLimits::max_decode_time`](crate::otlp::options::DecodeLimits::max_decode_time).
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Records the schema and dictionaries of the streams opened from now on, so that the
+    /// state of the consumer can be saved with [`Self::checkpoint`]. The streams opened
+    /// before keep not being recorded.
+    pub fn enable_checkpoints(&mut self) {
+        self.checkpoints = true;
+    }
+
+    /// Returns the state of the streams of the consumer, to resume them with
+    /// [`Self::restore`] e.g. after a restart of the process. Returns `None` if the
+    /// checkpoints are not enabled.
+    ///
+    /// The ids of a batch are relative to the batch, so the state of a stream is its
+    /// schema and dictionaries. The streams opened before the checkpoints were enabled are
+    /// left out.
+    #[must_use]
+    pub fn checkpoint(&self) -> Option<ConsumerCheckpoint> {
+        if !self.checkpoints {
+            return None;
+        }
+        let streams = self
+            .stream_consumers
+            .iter()
+            .filter_map(|(key, consumer)| {
+                Some(StreamCheckpoint {
+                    main_payload_type: key.main_payload_type as i32,
+                    schema_id: key.schema_id.clone(),
+                    payload_type: consumer.payload_type,
+                    state: Some(consumer.state.clone()?),
+                })
+            })
+            .collect();
+        Some(ConsumerCheckpoint { streams })
+    }
+
+    /// Replaces the streams of the consumer with the ones of the checkpoint, and enables
+    /// the checkpoints. The schemas of the streams are registered without schema events.
+    pub fn restore(&mut self, checkpoint: &ConsumerCheckpoint) -> error::Result<()> {
+        let mut stream_consumers = HashMap::with_capacity(checkpoint.streams.len());
+        let mut schema_registry = SchemaRegistry::default();
+        for stream in &checkpoint.streams {
+            let main_payload_type =
+                ArrowPayloadType::try_from(stream.main_payload_type).map_err(|_| {
+                    error::UnsupportedPayloadTypeSnafu {
+                        actual: stream.main_payload_type,
+                    }
+                    .build()
+                })?;
+            let consumer = StreamConsumer::restore(
+                stream.payload_type,
+                stream.state.clone().unwrap_or_default(),
+            )?;
+            // the payloads of the custom types are not registered, see `consume_bar`
+            if let Ok(payload_type) = ArrowPayloadType::try_from(stream.payload_type) {
+                let _ = schema_registry.register(
+                    main_payload_type,
+                    payload_type,
+                    &stream.schema_id,
+                    &consumer.stream_reader.schema(),
+                )?;
+            }
+            let _ = stream_consumers.insert(
+                StreamKey {
+                    main_payload_type,
+                    schema_id: stream.schema_id.clone(),
+                },
+                consumer,
+            );
+        }
+        self.stream_consumers = stream_consumers;
+        self.schema_registry = schema_registry;
+        self.checkpoints = true;
+        Ok(())
+    }
+
+    fn record_report<T>(&mut self, (request, report): (T, DecodeReport)) -> T {
+        if let Some(sink) = &self.metrics_sink {
+            for (payload_type, reason, count) in report.iter() {
+                sink.add_counter(telemetry::DROPPED_ROWS, count, &[
+                    (
+                        telemetry::PAYLOAD_TYPE_ATTRIBUTE,
+                        payload_type.as_str_name(),
+                    ),
+                    (telemetry::REASON_ATTRIBUTE, &format!("{reason:?}")),
+                ]);
+            }
+            for (payload_type, count) in report.iter_delta_id_anomalies() {
+                sink.add_counter(telemetry::DELTA_ID_ANOMALIES, count, &[(
+                    telemetry::PAYLOAD_TYPE_ATTRIBUTE,
+                    payload_type.as_str_name(),
+                )]);
+            }
+        }
+        self.decode_report.merge(&report);
+        request
+    }
+
+    /// Runs the decoding of a batch of the signal, measuring it if a sink is set.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn instrument<T: DecodedSize>(
+        &mut self,
+        signal: &'static str,
+        batch_id: i64,
+        decode: impl FnOnce(&mut Self) -> error::Result<T>,
+    ) -> error::Result<T> {
+        telemetry::trace_span!("otap.decode_batch", signal, batch_id);
+        let start = self.start_clock(signal, batch_id);
+        let result = decode(self);
+        self.record_decode(signal, start, &result);
+        result
+    }
+
+    /// Returns the start of a decoding if a sink or a stats callback measures it. The clock
+    /// is only read then, as it panics on the targets without one, e.g.
+    /// `wasm32-unknown-unknown`.
+    fn start_clock(&mut self, signal: &'static str, batch_id: i64) -> Option<Instant> {
+        if self.stats_callback.is_some() {
+            self.batch_stats = Some(DecodeStats {
+                signal,
+                batch_id,
+                ..Default::default()
+            });
+        }
+        (self.metrics_sink.is_some() || self.stats_callback.is_some()).then(Instant::now)
+    }
+
+    fn record_decode<T: DecodedSize>(
+        &mut self,
+        signal: &'static str,
+        start: Option<Instant>,
+        result: &error::Result<T>,
+    ) {
+        if let (Some(callback), Some(mut stats), Some(start)) =
+            (&self.stats_callback, self.batch_stats.take(), start)
+        {
+            stats.success = result.is_ok();
+            stats.bytes_out = result.as_ref().map_or(0, DecodedSize::decoded_size);
+            stats.convert_duration = start.elapsed().saturating_sub(stats.read_duration);
+            callback.on_batch_decoded(&stats);
+        }
+        if let (Some(sink), Some(start)) = (&self.metrics_sink, start) {
+            let attributes = [
+                (telemetry::SIGNAL_ATTRIBUTE, signal),
+                (telemetry::OUTCOME_ATTRIBUTE, telemetry::outcome(result)),
+            ];
+            sink.add_counter(telemetry::BATCHES_DECODED, 1, &attributes);
+            sink.record_histogram(
+                telemetry::DECODE_DURATION,
+                start.elapsed().as_secs_f64(),
+                &attributes,
+            );
+        }
+    }
+
     /// consume and deserialize record batches
     pub fn consume_bar(
         &mut self,
         bar: &mut BatchArrowRecords,
     ) -> error::Result<Vec<RecordMessage>> {
-        let mut records = Vec::with_capacity(bar.arrow_payloads.len());
+        let records = Vec::with_capacity(bar.arrow_payloads.len());
+        if bar.arrow_payloads.is_empty() {
+            return Ok(records);
+        }
+        let main_payload_type = get_main_payload_type(bar)?;
+        self.options
+            .limits
+            .check_payloads(bar.arrow_payloads.len())?;
+        let payloads = std::mem::take(&mut bar.arrow_payloads);
+        self.consume_payloads(bar.batch_id, main_payload_type, payloads, records)
+    }
+
+    /// Reads the payloads of the batch of the given id and main payload type into `records`.
+    fn consume_payloads(
+        &mut self,
+        batch_id: i64,
+        main_payload_type: ArrowPayloadType,
+        payloads: Vec<ArrowPayload>,
+        records: Vec<RecordMessage>,
+    ) -> error::Result<Vec<RecordMessage>> {
+        let start = self.batch_stats.is_some().then(Instant::now);
+        let result = self
+            .read_payloads(batch_id, main_payload_type, payloads, records)
+            .and_then(|records| {
+                self.check_deadline(&records)?;
+                Ok(records)
+            });
+        if let (Some(stats), Some(start)) = (self.batch_stats.as_mut(), start) {
+            stats.read_duration += start.elapsed();
+        }
+        result
+    }
 
-        for payload in std::mem::take(&mut bar.arrow_payloads) {
+    fn read_payloads(
+        &mut self,
+        batch_id: i64,
+        main_payload_type: ArrowPayloadType,
+        payloads: Vec<ArrowPayload>,
+        mut records: Vec<RecordMessage>,
+    ) -> error::Result<Vec<RecordMessage>> {
+        for payload in payloads {
+            self.check_deadline(&records)?;
+            if self.options.unknown_payload_policy == UnknownPayloadPolicy::Skip
+                && ArrowPayloadType::try_from(payload.r#type).is_err()
+                && !self.payload_registry.contains(payload.r#type)
+            {
+                if let Some(sink) = &self.metrics_sink {
+                    sink.add_counter(telemetry::SKIPPED_PAYLOADS, 1, &[(
+                        telemetry::PAYLOAD_TYPE_ATTRIBUTE,
+                        &payload.r#type.to_string(),
+                    )]);
+                }
+                self.skipped_payloads
+                    .push(SkippedPayload { batch_id, payload });
+                continue;
+            }
             let ArrowPayload {
                 schema_id,
                 r#type,
                 record,
             } = payload;
-            let payload_type = ArrowPayloadType::try_from(r#type)
-                .map_err(|_| error::UnsupportedPayloadTypeSnafu { actual: r#type }.build())?;
+            if let Some(stats) = self.batch_stats.as_mut() {
+                stats.bytes_in += record.len();
+            }
+            let route = self.payload_registry.route(r#type)?;
+            telemetry::trace_span!(
+                "otap.read_payload",
+                batch_id,
+                schema_id = %schema_id,
+                payload_type = r#type,
+            );
 
-            let stream_consumer = match self.stream_consumers.get_mut(&schema_id) {
+            let key = StreamKey {
+                main_payload_type,
+                schema_id,
+            };
+            let stream_consumer = match self.stream_consumers.get_mut(&key) {
                 None => {
                     // stream consumer does not exist, remove all stream consumer with
-                    // the same payload_type since schema already changed for that payload.
-                    let new_stream_consumer: HashMap<String, StreamConsumer> =
-                        (std::mem::take(&mut self.stream_consumers))
-                            .into_iter()
-                            .filter(|(_, v)| v.payload_type != payload_type)
-                            .collect::<HashMap<_, _>>();
-                    self.stream_consumers = new_stream_consumer;
+                    // the same payload_type of the same signal since schema already
+                    // changed for that payload.
+                    self.stream_consumers.retain(|k, v| {
+                        k.main_payload_type != main_payload_type || v.payload_type != r#type
+                    });
                     self.stream_consumers
-                        .entry(schema_id.clone())
-                        .or_insert(StreamConsumer::new(payload_type, record)?)
+                        .entry(key.clone())
+                        .or_insert(StreamConsumer::new(r#type, record, self.checkpoints)?)
                 }
                 Some(s) => {
                     // stream consumer exists for given schema id, just reset the bytes.
-                    s.replace_bytes(record);
+                    s.replace_bytes(record)?;
                     s
                 }
             };
@@ -97,9 +508,50 @@ impl Consumer {
             if let Some(rs) = stream_consumer.next() {
                 // the encoder side ensures there should be only one record here.
                 let record = rs.context(error::ReadRecordBatchSnafu)?;
+                let known_type =
+                    ArrowPayloadType::try_from(r#type).unwrap_or(ArrowPayloadType::Unknown);
+                self.options
+                    .limits
+                    .check_rows(known_type, record.num_rows())?;
+                if let Some(stats) = self.batch_stats.as_mut() {
+                    stats.add_payload(known_type, record.num_rows());
+                }
+                let payload_type = match route {
+                    PayloadRoute::Standard(payload_type) => payload_type,
+                    PayloadRoute::Custom(decoder) => {
+                        decoder.decode(batch_id, &key.schema_id, record)?;
+                        continue;
+                    }
+                };
+                if let Some(event) = self.schema_registry.register(
+                    main_payload_type,
+                    payload_type,
+                    &key.schema_id,
+                    &record.schema(),
+                )? {
+                    if let (Some(sink), SchemaEvent::Reset { diff, .. }) =
+                        (&self.metrics_sink, &event)
+                    {
+                        let attributes = [(
+                            telemetry::PAYLOAD_TYPE_ATTRIBUTE,
+                            payload_type.as_str_name(),
+                        )];
+                        sink.add_counter(telemetry::DICTIONARY_RESETS, 1, &attributes);
+                        if !diff.is_compatible() {
+                            sink.add_counter(telemetry::INCOMPATIBLE_SCHEMA_RESETS, 1, &attributes);
+                        }
+                    }
+                    self.schema_events.push(event);
+                }
+                if let Some(sink) = &self.metrics_sink {
+                    sink.add_counter(telemetry::ROWS_DECODED, record.num_rows() as u64, &[(
+                        telemetry::PAYLOAD_TYPE_ATTRIBUTE,
+                        payload_type.as_str_name(),
+                    )]);
+                }
                 records.push(RecordMessage {
-                    batch_id: bar.batch_id,
-                    schema_id,
+                    batch_id,
+                    schema_id: key.schema_id,
                     payload_type,
                     record,
                 });
@@ -110,6 +562,63 @@ impl Consumer {
         Ok(records)
     }
 
+    /// Fails if the deadline passed, `records` being the payloads read so far.
+    fn check_deadline(&self, records: &[RecordMessage]) -> error::Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => error::DeadlineExceededSnafu {
+                completed_payloads: records
+                    .iter()
+                    .map(|record| record.payload_type)
+                    .collect::<Vec<_>>(),
+            }
+            .fail(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Consumes all the arrow payloads in the passed OTAP `BatchArrayRecords` into the
+    /// `OtapBatch` of the signal identified by the main payload type, without decoding them
+    /// into OTLP messages
+    pub fn consume_otap_batch(
+        &mut self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<OtapBatch> {
+        let main_payload_type = get_main_payload_type(records)?;
+        check_main_payload_type(main_payload_type)?;
+        Ok(otap_batch_from(
+            main_payload_type,
+            self.consume_bar(records)?,
+        ))
+    }
+
+    /// Reads the payloads of the batch whose type is selected into the `OtapBatch` of the
+    /// signal, leaving the batch untouched. The payloads of the other types are not read,
+    /// their streams are not tracked.
+    pub(crate) fn consume_selected(
+        &mut self,
+        records: &BatchArrowRecords,
+        selected: impl Fn(i32) -> bool,
+    ) -> error::Result<OtapBatch> {
+        let main_payload_type = get_main_payload_type(records)?;
+        check_main_payload_type(main_payload_type)?;
+        self.options
+            .limits
+            .check_payloads(records.arrow_payloads.len())?;
+        let payloads: Vec<_> = records
+            .arrow_payloads
+            .iter()
+            .filter(|payload| selected(payload.r#type))
+            .cloned()
+            .collect();
+        let record_messages = self.consume_payloads(
+            records.batch_id,
+            main_payload_type,
+            payloads,
+            Vec::with_capacity(records.arrow_payloads.len()),
+        )?;
+        Ok(otap_batch_from(main_payload_type, record_messages))
+    }
+
     /// Consumes all the arrow payloads in the passed OTAP `BatchArrayRecords` and decodes them
     /// into OTLP messages, then constructs the `ExportMetricsServiceRequest` containing the
     /// metrics messages
@@ -117,17 +626,22 @@ impl Consumer {
         &mut self,
         records: &mut BatchArrowRecords,
     ) -> error::Result<ExportMetricsServiceRequest> {
-        match get_main_payload_type(records)? {
-            ArrowPayloadType::UnivariateMetrics => {
-                let record_messages = self.consume_bar(records)?;
-                let otap_batch = OtapBatch::Metrics(from_record_messages(record_messages));
-                metrics_from(otap_batch)
-            }
-            main_record_type => error::UnsupportedPayloadTypeSnafu {
-                actual: main_record_type,
-            }
-            .fail(),
-        }
+        self.instrument(
+            "metrics",
+            records.batch_id,
+            |consumer| match get_main_payload_type(records)? {
+                ArrowPayloadType::UnivariateMetrics => {
+                    let record_messages = consumer.consume_bar(records)?;
+                    let otap_batch = OtapBatch::Metrics(from_record_messages(record_messages));
+                    metrics_from_with_report(otap_batch, &consumer.options)
+                        .map(|decoded| consumer.record_report(decoded))
+                }
+                main_record_type => error::UnsupportedPayloadTypeSnafu {
+                    actual: main_record_type,
+                }
+                .fail(),
+            },
+        )
     }
 
     /// Consumes all the arrow payloads in the passed OTAP `BatchArrayRecords` and decodes them
@@ -137,18 +651,344 @@ impl Consumer {
         &mut self,
         records: &mut BatchArrowRecords,
     ) -> error::Result<ExportLogsServiceRequest> {
+        self.instrument(
+            "logs",
+            records.batch_id,
+            |consumer| match get_main_payload_type(records)? {
+                ArrowPayloadType::Logs => {
+                    let record_messages = consumer.consume_bar(records)?;
+                    let otap_batch = OtapBatch::Logs(from_record_messages(record_messages));
+                    logs_from_with_report(otap_batch, &consumer.options)
+                        .map(|decoded| consumer.record_report(decoded))
+                }
+                main_record_type => error::UnsupportedPayloadTypeSnafu {
+                    actual: main_record_type,
+                }
+                .fail(),
+            },
+        )
+    }
+
+    /// Consumes all the arrow payloads in the passed OTAP `BatchArrayRecords` and decodes them
+    /// into OTLP messages, then constructs the `ExportTraceServiceRequest` containing the
+    /// spans messages
+    pub fn consume_traces_batches(
+        &mut self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<ExportTraceServiceRequest> {
+        self.instrument(
+            "traces",
+            records.batch_id,
+            |consumer| match get_main_payload_type(records)? {
+                ArrowPayloadType::Spans => {
+                    let record_messages = consumer.consume_bar(records)?;
+                    let otap_batch = OtapBatch::Traces(from_record_messages(record_messages));
+                    traces_from_with_report(otap_batch, &consumer.options)
+                        .map(|decoded| consumer.record_report(decoded))
+                }
+                main_record_type => error::UnsupportedPayloadTypeSnafu {
+                    actual: main_record_type,
+                }
+                .fail(),
+            },
+        )
+    }
+
+    /// Consumes all the arrow payloads in the passed OTAP `BatchArrayRecords` and decodes them
+    /// straight into a protobuf encoded `ExportTraceServiceRequest`, without building the
+    /// intermediate OTLP messages
+    pub fn consume_traces_batches_bytes(
+        &mut self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<Vec<u8>> {
+        let mut request = Vec::new();
+        self.consume_traces_batches_bytes_into(records, &mut request)?;
+        Ok(request)
+    }
+
+    /// Same as [`Consumer::consume_traces_batches_bytes`], writing the request to `out`
+    /// instead, see [`TracesBytesDecoder::decode_into`]. The buffers of the consumer and
+    /// `out` are reused across the batches.
+    pub fn consume_traces_batches_bytes_into(
+        &mut self,
+        records: &mut BatchArrowRecords,
+        out: &mut Vec<u8>,
+    ) -> error::Result<()> {
+        self.instrument(
+            "traces",
+            records.batch_id,
+            |consumer| match get_main_payload_type(records)? {
+                ArrowPayloadType::Spans => {
+                    let record_messages = consumer.consume_bar(records)?;
+                    let otap_batch = OtapBatch::Traces(from_record_messages(record_messages));
+                    consumer
+                        .traces_bytes_decoder
+                        .decode_into(otap_batch, &consumer.options, out)
+                        .map(|report| consumer.record_report((out.len(), report)))
+                }
+                main_record_type => error::UnsupportedPayloadTypeSnafu {
+                    actual: main_record_type,
+                }
+                .fail(),
+            },
+        )
+        .map(|_| ())
+    }
+
+    /// Consumes all the arrow payloads in the passed OTAP `BatchArrayRecords` and decodes them
+    /// into the OTLP export request of the signal identified by the main payload type.
+    pub fn consume_batches(
+        &mut self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<ExportRequest> {
         match get_main_payload_type(records)? {
-            ArrowPayloadType::Logs => {
-                let record_messages = self.consume_bar(records)?;
-                let otap_batch = OtapBatch::Logs(from_record_messages(record_messages));
-                logs_from(otap_batch)
-            }
+            ArrowPayloadType::Logs => self.consume_logs_batches(records).map(ExportRequest::Logs),
+            ArrowPayloadType::UnivariateMetrics => self
+                .consume_metrics_batches(records)
+                .map(ExportRequest::Metrics),
+            ArrowPayloadType::Spans => self
+                .consume_traces_batches(records)
+                .map(ExportRequest::Traces),
             main_record_type => error::UnsupportedPayloadTypeSnafu {
                 actual: main_record_type,
             }
             .fail(),
         }
     }
+
+    /// Consumes a batch carrying the payloads of several signals and decodes them into the
+    /// OTLP export requests of the signals. The payloads of a signal start with its main
+    /// payload, e.g. the spans, followed by the payloads related to it, e.g. the resource
+    /// attributes of the spans, up to the main payload of the next signal. A batch that
+    /// carries a signal twice is rejected, so is one that doesn't start with a main payload.
+    pub fn consume_multi_signal_batches(
+        &mut self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<MultiSignalRequest> {
+        let signals = self.signal_payloads(records)?;
+        let mut request = MultiSignalRequest::default();
+        for (main_payload_type, payloads) in signals {
+            self.consume_signal(records.batch_id, main_payload_type, payloads, &mut request)?;
+        }
+        Ok(request)
+    }
+
+    /// Like [`Consumer::consume_multi_signal_batches`], but stops at the deadline set with
+    /// [`Consumer::set_deadline`] instead of failing. The signals not started by then are
+    /// left in the continuation, to pass back to this method once there is time to decode
+    /// them. A signal started is decoded in full, and at least one signal is decoded per
+    /// call, so that passing the continuations back makes progress.
+    pub fn consume_multi_signal_batches_partial(
+        &mut self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<PartialMultiSignalRequest> {
+        let mut signals = self.signal_payloads(records)?.into_iter();
+        let deadline = self.deadline.take();
+        let mut partial = PartialMultiSignalRequest::default();
+        let mut result = Ok(());
+        let mut decoded_any = false;
+        while let Some((main_payload_type, payloads)) = signals.next() {
+            if decoded_any && deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                let mut remaining = payloads;
+                remaining.extend(signals.flat_map(|(_, payloads)| payloads));
+                partial.continuation = Some(BatchArrowRecords {
+                    batch_id: records.batch_id,
+                    arrow_payloads: remaining,
+                    headers: records.headers.clone(),
+                });
+                break;
+            }
+            result = self.consume_signal(
+                records.batch_id,
+                main_payload_type,
+                payloads,
+                &mut partial.request,
+            );
+            if result.is_err() {
+                break;
+            }
+            decoded_any = true;
+        }
+        self.deadline = deadline;
+        result.map(|()| partial)
+    }
+
+    /// Splits the payloads of a batch carrying several signals by signal, see
+    /// [`Consumer::consume_multi_signal_batches`].
+    fn signal_payloads(
+        &self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<Vec<(ArrowPayloadType, Vec<ArrowPayload>)>> {
+        check_main_payload_type(get_main_payload_type(records)?)?;
+        self.options
+            .limits
+            .check_payloads(records.arrow_payloads.len())?;
+        let mut signals: Vec<(ArrowPayloadType, Vec<ArrowPayload>)> = Vec::new();
+        for payload in std::mem::take(&mut records.arrow_payloads) {
+            match ArrowPayloadType::try_from(payload.r#type) {
+                Ok(
+                    main_payload_type @ (ArrowPayloadType::Logs
+                    | ArrowPayloadType::UnivariateMetrics
+                    | ArrowPayloadType::Spans),
+                ) => {
+                    ensure!(
+                        signals
+                            .iter()
+                            .all(|(signal, _)| *signal != main_payload_type),
+                        error::DuplicateSignalSnafu { main_payload_type }
+                    );
+                    signals.push((main_payload_type, vec![payload]));
+                }
+                _ => {
+                    // safety: the first payload is a main payload, checked above
+                    let (_, payloads) = signals.last_mut().expect("batch starts with a signal");
+                    payloads.push(payload);
+                }
+            }
+        }
+        Ok(signals)
+    }
+
+    /// Decodes the payloads of a signal of a multi signal batch into its request.
+    fn consume_signal(
+        &mut self,
+        batch_id: i64,
+        main_payload_type: ArrowPayloadType,
+        payloads: Vec<ArrowPayload>,
+        request: &mut MultiSignalRequest,
+    ) -> error::Result<()> {
+        let read = |consumer: &mut Self| {
+            consumer.consume_payloads(batch_id, main_payload_type, payloads, Vec::new())
+        };
+        match main_payload_type {
+            ArrowPayloadType::Logs => {
+                request.logs = Some(self.instrument("logs", batch_id, |consumer| {
+                    let record_messages = read(consumer)?;
+                    let otap_batch = OtapBatch::Logs(from_record_messages(record_messages));
+                    logs_from_with_report(otap_batch, &consumer.options)
+                        .map(|decoded| consumer.record_report(decoded))
+                })?);
+            }
+            ArrowPayloadType::UnivariateMetrics => {
+                request.metrics = Some(self.instrument("metrics", batch_id, |consumer| {
+                    let record_messages = read(consumer)?;
+                    let otap_batch = OtapBatch::Metrics(from_record_messages(record_messages));
+                    metrics_from_with_report(otap_batch, &consumer.options)
+                        .map(|decoded| consumer.record_report(decoded))
+                })?);
+            }
+            _ => {
+                request.traces = Some(self.instrument("traces", batch_id, |consumer| {
+                    let record_messages = read(consumer)?;
+                    let otap_batch = OtapBatch::Traces(from_record_messages(record_messages));
+                    traces_from_with_report(otap_batch, &consumer.options)
+                        .map(|decoded| consumer.record_report(decoded))
+                })?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Consumer::consume_batches`], but converts the payloads into the OTLP export
+    /// request on the blocking thread pool of tokio, see [`spawn_request_from`]. The
+    /// payloads are still read by the calling task, as the state of their streams lives in
+    /// the consumer.
+    #[cfg(feature = "runtime")]
+    pub async fn consume_batches_async(
+        &mut self,
+        records: &mut BatchArrowRecords,
+    ) -> error::Result<ExportRequest> {
+        let signal = match get_main_payload_type(records)? {
+            ArrowPayloadType::Logs => "logs",
+            ArrowPayloadType::UnivariateMetrics => "metrics",
+            ArrowPayloadType::Spans => "traces",
+            main_record_type => {
+                return error::UnsupportedPayloadTypeSnafu {
+                    actual: main_record_type,
+                }
+                .fail();
+            }
+        };
+        let start = self.start_clock(signal, records.batch_id);
+        let result = match self.consume_otap_batch(records) {
+            Ok(otap_batch) => spawn_request_from(otap_batch, &self.options)
+                .await
+                .map(|decoded| self.record_report(decoded)),
+            Err(error) => Err(error),
+        };
+        self.record_decode(signal, start, &result);
+        result
+    }
+}
+
+/// Output of the decoding of a batch, whose protobuf encoded size is reported in the
+/// [`DecodeStats`].
+trait DecodedSize {
+    fn decoded_size(&self) -> usize;
+}
+
+impl DecodedSize for ExportLogsServiceRequest {
+    fn decoded_size(&self) -> usize {
+        self.encoded_len()
+    }
+}
+
+impl DecodedSize for ExportMetricsServiceRequest {
+    fn decoded_size(&self) -> usize {
+        self.encoded_len()
+    }
+}
+
+impl DecodedSize for ExportTraceServiceRequest {
+    fn decoded_size(&self) -> usize {
+        self.encoded_len()
+    }
+}
+
+impl DecodedSize for ExportRequest {
+    fn decoded_size(&self) -> usize {
+        match self {
+            Self::Logs(request) => request.encoded_len(),
+            Self::Metrics(request) => request.encoded_len(),
+            Self::Traces(request) => request.encoded_len(),
+        }
+    }
+}
+
+/// Request already protobuf encoded, of the given size.
+impl DecodedSize for usize {
+    fn decoded_size(&self) -> usize {
+        *self
+    }
+}
+
+/// Fails unless the main payload type is the one of a signal.
+fn check_main_payload_type(main_payload_type: ArrowPayloadType) -> error::Result<()> {
+    ensure!(
+        matches!(
+            main_payload_type,
+            ArrowPayloadType::Logs | ArrowPayloadType::UnivariateMetrics | ArrowPayloadType::Spans
+        ),
+        error::UnsupportedPayloadTypeSnafu {
+            actual: main_payload_type,
+        }
+    );
+    Ok(())
+}
+
+/// Collects the records into the `OtapBatch` of the signal of the main payload type, which
+/// is checked by [`check_main_payload_type`].
+fn otap_batch_from(
+    main_payload_type: ArrowPayloadType,
+    record_messages: Vec<RecordMessage>,
+) -> OtapBatch {
+    match main_payload_type {
+        ArrowPayloadType::Logs => OtapBatch::Logs(from_record_messages(record_messages)),
+        ArrowPayloadType::UnivariateMetrics => {
+            OtapBatch::Metrics(from_record_messages(record_messages))
+        }
+        _ => OtapBatch::Traces(from_record_messages(record_messages)),
+    }
 }
 
 /// Get the main logs, metrics, or traces from a received BatchArrowRecords message.
@@ -167,11 +1007,198 @@ fn get_main_payload_type(records: &BatchArrowRecords) -> error::Result<ArrowPayl
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::test_util::{create_record_batch, create_test_schema};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use arrow::ipc::writer::StreamWriter;
     use std::io::Cursor;
     use std::sync::Arc;
 
+    fn next_message(writer: &mut StreamWriter<Vec<u8>>, record_batch: &RecordBatch) -> Vec<u8> {
+        writer.write(record_batch).unwrap();
+        writer.flush().unwrap();
+        std::mem::take(writer.get_mut())
+    }
+
+    #[test]
+    fn test_multiplexed_signals() {
+        // the logs and traces of the stream use the same schema ids for their payloads,
+        // but each payload has its own schema.
+        let logs_schema = Arc::new(Schema::new(vec![Field::new("a", DataType::UInt16, true)]));
+        let spans_schema = Arc::new(create_test_schema());
+        let attrs_schema = Arc::new(Schema::new(vec![Field::new("b", DataType::Utf8, true)]));
+        let mut logs_writer = StreamWriter::try_new(vec![], &logs_schema).unwrap();
+        let mut logs_attrs_writer = StreamWriter::try_new(vec![], &attrs_schema).unwrap();
+        let mut spans_writer = StreamWriter::try_new(vec![], &spans_schema).unwrap();
+        let mut spans_attrs_writer = StreamWriter::try_new(vec![], &logs_schema).unwrap();
+
+        let mut consumer = Consumer::default();
+        for batch_id in 0..3 {
+            let logs = create_record_batch(logs_schema.clone(), 3);
+            let logs_attrs = create_record_batch(attrs_schema.clone(), 4);
+            let mut logs_bar = BatchArrowRecords {
+                batch_id: batch_id * 2,
+                arrow_payloads: vec![
+                    ArrowPayload {
+                        schema_id: "0".to_string(),
+                        r#type: ArrowPayloadType::Logs as i32,
+                        record: next_message(&mut logs_writer, &logs),
+                    },
+                    ArrowPayload {
+                        schema_id: "1".to_string(),
+                        r#type: ArrowPayloadType::ResourceAttrs as i32,
+                        record: next_message(&mut logs_attrs_writer, &logs_attrs),
+                    },
+                ],
+                headers: vec![],
+            };
+            let records = consumer.consume_bar(&mut logs_bar).unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].record, logs);
+            assert_eq!(records[1].record, logs_attrs);
+
+            let spans = create_record_batch(spans_schema.clone(), 5);
+            let spans_attrs = create_record_batch(logs_schema.clone(), 6);
+            let mut spans_bar = BatchArrowRecords {
+                batch_id: batch_id * 2 + 1,
+                arrow_payloads: vec![
+                    ArrowPayload {
+                        schema_id: "0".to_string(),
+                        r#type: ArrowPayloadType::Spans as i32,
+                        record: next_message(&mut spans_writer, &spans),
+                    },
+                    ArrowPayload {
+                        schema_id: "1".to_string(),
+                        r#type: ArrowPayloadType::ResourceAttrs as i32,
+                        record: next_message(&mut spans_attrs_writer, &spans_attrs),
+                    },
+                ],
+                headers: vec![],
+            };
+            let records = consumer.consume_bar(&mut spans_bar).unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].record, spans);
+            assert_eq!(records[1].record, spans_attrs);
+        }
+        assert_eq!(consumer.stream_consumers.len(), 4);
+
+        // each payload of each signal registered its schema once
+        let events = consumer.take_schema_events();
+        assert_eq!(events.len(), 4);
+        assert!(
+            events
+                .iter()
+                .all(|event| matches!(event, SchemaEvent::New { .. }))
+        );
+        assert_eq!(
+            consumer
+                .schema_registry()
+                .current_schema(ArrowPayloadType::Spans, ArrowPayloadType::ResourceAttrs),
+            Some(("1", &logs_schema))
+        );
+    }
+
+    #[test]
+    fn test_multi_signal_batch() {
+        use crate::datagen::{DatagenConfig, Generator};
+        use crate::encode::split::to_batch_arrow_records;
+        use crate::encode::{LogsProducer, TracesProducer};
+
+        let mut generator = Generator::new(DatagenConfig::default());
+        let traces = TracesProducer::new()
+            .produce(&generator.traces_request(4))
+            .unwrap();
+        let logs = LogsProducer::new()
+            .produce(&generator.logs_request(8))
+            .unwrap();
+        let traces_bar = to_batch_arrow_records(&traces, 0).unwrap();
+        let logs_bar = to_batch_arrow_records(&logs, 0).unwrap();
+        let mut bar = traces_bar.clone();
+        bar.arrow_payloads.extend(logs_bar.arrow_payloads.clone());
+
+        let request = Consumer::default()
+            .consume_multi_signal_batches(&mut bar.clone())
+            .unwrap();
+        assert_eq!(
+            request.traces,
+            Some(
+                Consumer::default()
+                    .consume_traces_batches(&mut traces_bar.clone())
+                    .unwrap()
+            )
+        );
+        assert_eq!(
+            request.logs,
+            Some(
+                Consumer::default()
+                    .consume_logs_batches(&mut logs_bar.clone())
+                    .unwrap()
+            )
+        );
+        assert!(request.metrics.is_none());
+
+        // a signal carried twice is rejected
+        bar.arrow_payloads.extend(traces_bar.arrow_payloads);
+        assert!(matches!(
+            Consumer::default().consume_multi_signal_batches(&mut bar),
+            Err(error::Error::DuplicateSignal {
+                main_payload_type: ArrowPayloadType::Spans,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_deadline() {
+        use crate::datagen::{DatagenConfig, Generator};
+        use crate::encode::split::to_batch_arrow_records;
+        use crate::encode::{LogsProducer, TracesProducer};
+
+        let mut generator = Generator::new(DatagenConfig::default());
+        let traces = TracesProducer::new()
+            .produce(&generator.traces_request(4))
+            .unwrap();
+        let logs = LogsProducer::new()
+            .produce(&generator.logs_request(8))
+            .unwrap();
+        let traces_bar = to_batch_arrow_records(&traces, 3).unwrap();
+        let logs_bar = to_batch_arrow_records(&logs, 3).unwrap();
+        let mut bar = traces_bar.clone();
+        bar.arrow_payloads.extend(logs_bar.arrow_payloads.clone());
+        let expected = Consumer::default()
+            .consume_multi_signal_batches(&mut bar.clone())
+            .unwrap();
+
+        // past the deadline, the decoding fails before reading a payload
+        let mut consumer = Consumer::default();
+        consumer.set_deadline(Some(Instant::now()));
+        assert!(matches!(
+            consumer.consume_traces_batches(&mut traces_bar.clone()),
+            Err(error::Error::DeadlineExceeded { completed_payloads, .. })
+                if completed_payloads.is_empty()
+        ));
+
+        // the partial decoding decodes the first signal and leaves the others
+        let mut consumer = Consumer::default();
+        consumer.set_deadline(Some(Instant::now()));
+        let partial = consumer
+            .consume_multi_signal_batches_partial(&mut bar)
+            .unwrap();
+        assert_eq!(partial.request.traces, expected.traces);
+        assert!(partial.request.logs.is_none());
+        let mut continuation = partial.continuation.unwrap();
+        assert_eq!(continuation, logs_bar);
+
+        consumer.set_deadline(None);
+        let partial = consumer
+            .consume_multi_signal_batches_partial(&mut continuation)
+            .unwrap();
+        assert_eq!(partial.request.logs, expected.logs);
+        assert!(partial.continuation.is_none());
+    }
+
     #[test]
+    #[allow(unused_qualifications)]
     fn test_replace_bytes() {
         let schema = Arc::new(create_test_schema());
         let mut writer = arrow::ipc::writer::StreamWriter::try_new(vec![], &schema).unwrap();
@@ -195,4 +1222,236 @@ mod tests {
         *reader.get_mut() = Cursor::new(std::mem::take(writer.get_mut()));
         assert_eq!(batch2, reader.next().unwrap().unwrap());
     }
+
+    #[test]
+    fn test_checkpoint_restore() {
+        use arrow::array::DictionaryArray;
+        use arrow::datatypes::UInt8Type;
+
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "name",
+            DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8)),
+            true,
+        )]));
+        let batch = |names: Vec<&str>| {
+            let names: DictionaryArray<UInt8Type> = names.into_iter().collect();
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(names)]).unwrap()
+        };
+        let mut writer = StreamWriter::try_new(vec![], &schema).unwrap();
+        let mut bar = |record_batch: &RecordBatch| BatchArrowRecords {
+            batch_id: 0,
+            arrow_payloads: vec![ArrowPayload {
+                schema_id: "0".to_string(),
+                r#type: ArrowPayloadType::Logs as i32,
+                record: next_message(&mut writer, record_batch),
+            }],
+            headers: vec![],
+        };
+
+        let mut consumer = Consumer::default();
+        assert!(consumer.checkpoint().is_none());
+        consumer.enable_checkpoints();
+        for names in [vec!["a", "b"], vec!["c"]] {
+            let records = consumer
+                .consume_bar(&mut bar(&batch(names.clone())))
+                .unwrap();
+            assert_eq!(records[0].record, batch(names));
+        }
+        let checkpoint = consumer.checkpoint().unwrap();
+        assert_eq!(checkpoint.len(), 1);
+        // the dictionary of the first batch was replaced
+        assert_eq!(
+            checkpoint.streams[0]
+                .state
+                .as_ref()
+                .unwrap()
+                .dictionaries
+                .len(),
+            1
+        );
+
+        // the next payload only carries the record batch, the dictionary is the restored one
+        let mut restored = Consumer::default();
+        restored
+            .restore(&ConsumerCheckpoint::from_bytes(&checkpoint.to_bytes()).unwrap())
+            .unwrap();
+        let mut next = bar(&batch(vec!["c", "c"]));
+        assert!(Consumer::default().consume_bar(&mut next.clone()).is_err());
+        let records = restored.consume_bar(&mut next).unwrap();
+        assert_eq!(records[0].record, batch(vec!["c", "c"]));
+        assert!(restored.take_schema_events().is_empty());
+
+        assert!(ConsumerCheckpoint::from_bytes(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn test_consumer_limits() {
+        use crate::encode::split::to_batch_arrow_records;
+        use crate::otlp::options::DecodeLimits;
+        use crate::test_util::workloads::traces_batch;
+
+        let bar = to_batch_arrow_records(&traces_batch(8, 2), 0).unwrap();
+        let consume = |limits| {
+            Consumer::with_options(DecoderOptions::default().with_limits(limits))
+                .consume_bar(&mut bar.clone())
+                .map(|records| records.len())
+        };
+        assert!(consume(DecodeLimits::new().with_max_rows_per_payload(16)).is_ok());
+        let err = consume(DecodeLimits::new().with_max_payloads_per_batch(1)).unwrap_err();
+        assert!(matches!(err, error::Error::TooManyPayloads {
+            payloads: 2,
+            ..
+        }));
+        let err = consume(DecodeLimits::new().with_max_rows_per_payload(8)).unwrap_err();
+        assert!(matches!(err, error::Error::TooManyRows { rows: 16, .. }));
+    }
+
+    #[cfg(feature = "runtime")]
+    #[tokio::test]
+    async fn test_consume_batches_async() {
+        use crate::encode::TracesProducer;
+        use crate::encode::split::to_batch_arrow_records;
+        use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+        fn assert_send<T: Send>(value: T) -> T {
+            value
+        }
+
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        trace_id: vec![1; 16],
+                        span_id: vec![2; 8],
+                        name: "span".into(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        let mut records = to_batch_arrow_records(&otap_batch, 0).unwrap();
+        let expected = Consumer::default()
+            .consume_batches(&mut records.clone())
+            .unwrap();
+
+        let mut consumer = Consumer::default();
+        let decoded = assert_send(consumer.consume_batches_async(&mut records))
+            .await
+            .unwrap();
+        assert_eq!(decoded, expected);
+        assert!(
+            consumer
+                .consume_batches_async(&mut BatchArrowRecords::default())
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_custom_payload_type() {
+        use crate::decode::payload_registry::PayloadDecoder;
+        use crate::encode::TracesProducer;
+        use crate::encode::split::{to_arrow_payload, to_batch_arrow_records};
+        use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+        use std::sync::Mutex;
+
+        const PROFILES: i32 = 1000;
+
+        #[derive(Default)]
+        struct Recorder(Arc<Mutex<Vec<(i64, usize)>>>);
+
+        impl PayloadDecoder for Recorder {
+            fn decode(
+                &self,
+                batch_id: i64,
+                _schema_id: &str,
+                record: RecordBatch,
+            ) -> error::Result<()> {
+                self.0.lock().unwrap().push((batch_id, record.num_rows()));
+                Ok(())
+            }
+        }
+
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        trace_id: vec![1; 16],
+                        span_id: vec![2; 8],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        let schema = Arc::new(create_test_schema());
+        let mut records = Vec::new();
+        for batch_id in 0..2 {
+            let mut bar = to_batch_arrow_records(&otap_batch, batch_id).unwrap();
+            bar.arrow_payloads.push(
+                to_arrow_payload(
+                    PROFILES,
+                    format!("profiles:{batch_id}"),
+                    &create_record_batch(schema.clone(), 2),
+                )
+                .unwrap(),
+            );
+            records.push(bar);
+        }
+        let mut unconsumed = records.clone();
+
+        assert!(matches!(
+            Consumer::default().consume_traces_batches(&mut records[0].clone()),
+            Err(error::Error::UnsupportedPayloadType {
+                actual: PROFILES,
+                ..
+            })
+        ));
+
+        let decoded = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = PayloadRegistry::new();
+        assert!(
+            registry
+                .register(ArrowPayloadType::Spans as i32, Recorder::default())
+                .is_err()
+        );
+        registry
+            .register(PROFILES, Recorder(decoded.clone()))
+            .unwrap();
+        assert!(registry.contains(PROFILES));
+
+        let mut consumer = Consumer::default();
+        consumer.set_payload_registry(registry);
+        for bar in &mut records {
+            let traces = consumer.consume_traces_batches(bar).unwrap();
+            assert_eq!(
+                traces.resource_spans[0].scope_spans[0].spans,
+                request.resource_spans[0].scope_spans[0].spans
+            );
+        }
+        assert_eq!(*decoded.lock().unwrap(), vec![(0, 2), (1, 2)]);
+
+        // the unknown payloads are skipped and kept as received
+        let mut consumer = Consumer::with_options(
+            DecoderOptions::default().with_unknown_payload_policy(UnknownPayloadPolicy::Skip),
+        );
+        let expected: Vec<_> = unconsumed
+            .iter()
+            .map(|bar| SkippedPayload {
+                batch_id: bar.batch_id,
+                payload: bar.arrow_payloads.last().unwrap().clone(),
+            })
+            .collect();
+        for bar in &mut unconsumed {
+            let traces = consumer.consume_traces_batches(bar).unwrap();
+            assert_eq!(traces.resource_spans.len(), 1);
+        }
+        assert_eq!(consumer.take_skipped_payloads(), expected);
+        assert!(consumer.take_skipped_payloads().is_empty());
+    }
 }
@@ -0,0 +1,117 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Partial decoding of the batches of a stream that is forwarded as received, e.g. by a
+//! gateway routing the batches on their resource attributes.
+//!
+//! A [`PassthroughConsumer`] only reads the payloads of the selected types. The batches
+//! keep their payloads as received, so they are re-exported without being encoded again,
+//! and the receivers they are forwarded to decode them with the schemas and dictionaries
+//! sent earlier on the stream. The payloads of the other types are never read, which saves
+//! their decoding, including the payloads of the types unknown to the crate.
+//!
+//! The payloads of a type form an IPC stream whose messages only decode in order, so the
+//! selection is fixed for the lifetime of the consumer.
+
+use std::collections::HashSet;
+
+use crate::Consumer;
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayloadType, BatchArrowRecords};
+
+/// A batch read by a [`PassthroughConsumer`].
+pub struct PassthroughBatch {
+    /// The batch as received, to forward.
+    pub records: BatchArrowRecords,
+    /// The payloads of the selected types, in the `OtapBatch` of the signal of the batch.
+    pub decoded: OtapBatch,
+}
+
+/// Reads the payloads of the selected types of the batches of a stream, see the module
+/// documentation.
+pub struct PassthroughConsumer {
+    consumer: Consumer,
+    payload_types: HashSet<i32>,
+}
+
+impl PassthroughConsumer {
+    /// Creates a consumer reading the payloads of the given types with a default consumer.
+    #[must_use]
+    pub fn new(payload_types: impl IntoIterator<Item = ArrowPayloadType>) -> Self {
+        Self::with_consumer(Consumer::default(), payload_types)
+    }
+
+    /// Creates a consumer reading the payloads of the given types with the given consumer,
+    /// e.g. one with the limits of the gateway. The consumer must not have read any
+    /// payload of the stream.
+    #[must_use]
+    pub fn with_consumer(
+        consumer: Consumer,
+        payload_types: impl IntoIterator<Item = ArrowPayloadType>,
+    ) -> Self {
+        Self {
+            consumer,
+            payload_types: payload_types.into_iter().map(|t| t as i32).collect(),
+        }
+    }
+
+    /// Returns whether the payloads of the type are read.
+    #[must_use]
+    pub fn is_selected(&self, payload_type: ArrowPayloadType) -> bool {
+        self.payload_types.contains(&(payload_type as i32))
+    }
+
+    /// Returns the consumer reading the selected payloads, e.g. to take its schema events.
+    pub fn consumer_mut(&mut self) -> &mut Consumer {
+        &mut self.consumer
+    }
+
+    /// Reads the selected payloads of the batch, which is returned untouched.
+    pub fn consume(&mut self, records: BatchArrowRecords) -> Result<PassthroughBatch> {
+        let decoded = self.consumer.consume_selected(&records, |payload_type| {
+            self.payload_types.contains(&payload_type)
+        })?;
+        Ok(PassthroughBatch { records, decoded })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::encode::stream::StreamEncoder;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_passthrough() {
+        let otap_batch = traces_batch(8, 2);
+        let mut encoder = StreamEncoder::new().with_delta_dictionaries(true);
+        let mut passthrough = PassthroughConsumer::new([ArrowPayloadType::ResourceAttrs]);
+        let mut receiver = Consumer::default();
+        let expected = Consumer::default()
+            .consume_batches(&mut to_batch_arrow_records(&otap_batch, 0).unwrap())
+            .unwrap();
+        for _ in 0..3 {
+            let records = encoder.encode(&otap_batch).unwrap();
+            let batch = passthrough.consume(records.clone()).unwrap();
+            assert_eq!(batch.records, records);
+            assert_eq!(
+                batch.decoded.get(ArrowPayloadType::ResourceAttrs),
+                otap_batch.get(ArrowPayloadType::ResourceAttrs)
+            );
+            assert!(batch.decoded.get(ArrowPayloadType::Spans).is_none());
+
+            // the forwarded batches decode on the streams of the receiver
+            assert_eq!(
+                receiver
+                    .consume_batches(&mut batch.records.clone())
+                    .unwrap(),
+                expected
+            );
+        }
+        assert!(passthrough.is_selected(ArrowPayloadType::ResourceAttrs));
+        assert!(!passthrough.is_selected(ArrowPayloadType::Spans));
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Routing of the payloads of a `BatchArrowRecords` by payload type.
+//!
+//! The payload types of the OTAP specification are decoded by the crate. Other payload
+//! types, e.g. the ones of an experimental signal or of profiling data, can be carried in
+//! the same batches: their decoder is registered in a [`PayloadRegistry`] and receives the
+//! record batches of the payloads of its type, read from their IPC stream like the
+//! standard payloads. A payload of a type that is neither standard nor registered fails
+//! the batch, unless the decoder options skip them, see
+//! [`UnknownPayloadPolicy`](crate::otlp::options::UnknownPayloadPolicy).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use arrow::array::RecordBatch;
+use snafu::ensure;
+
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+/// Decoder of a payload type that is not part of the OTAP specification.
+pub trait PayloadDecoder: Send + Sync {
+    /// Receives the record batch of a payload of the registered type. An error fails the
+    /// batch the payload arrived in.
+    fn decode(&self, batch_id: i64, schema_id: &str, record: RecordBatch) -> Result<()>;
+}
+
+/// Where the payloads of a type are routed to.
+pub(crate) enum PayloadRoute<'a> {
+    /// Decoded by the crate, as part of the OTAP batch of the signal.
+    Standard(ArrowPayloadType),
+    /// Passed to a registered decoder.
+    Custom(&'a dyn PayloadDecoder),
+}
+
+/// Decoders of the payload types that are not part of the OTAP specification.
+#[derive(Clone, Default)]
+pub struct PayloadRegistry {
+    decoders: HashMap<i32, Arc<dyn PayloadDecoder>>,
+}
+
+impl fmt::Debug for PayloadRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut payload_types: Vec<_> = self.decoders.keys().collect();
+        payload_types.sort();
+        f.debug_struct("PayloadRegistry")
+            .field("payload_types", &payload_types)
+            .finish()
+    }
+}
+
+impl PayloadRegistry {
+    /// Creates a registry without any decoder.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the decoder of the payloads of the given type, replacing the previous one.
+    /// Fails if the type is one of the [`ArrowPayloadType`]s of the specification.
+    pub fn register(
+        &mut self,
+        payload_type: i32,
+        decoder: impl PayloadDecoder + 'static,
+    ) -> Result<()> {
+        ensure!(
+            ArrowPayloadType::try_from(payload_type).is_err(),
+            error::ReservedPayloadTypeSnafu { payload_type }
+        );
+        let _ = self.decoders.insert(payload_type, Arc::new(decoder));
+        Ok(())
+    }
+
+    /// Returns true if a decoder is registered for the given type.
+    #[must_use]
+    pub fn contains(&self, payload_type: i32) -> bool {
+        self.decoders.contains_key(&payload_type)
+    }
+
+    pub(crate) fn route(&self, payload_type: i32) -> Result<PayloadRoute<'_>> {
+        if let Ok(payload_type) = ArrowPayloadType::try_from(payload_type) {
+            return Ok(PayloadRoute::Standard(payload_type));
+        }
+        match self.decoders.get(&payload_type) {
+            Some(decoder) => Ok(PayloadRoute::Custom(decoder.as_ref())),
+            None => error::UnsupportedPayloadTypeSnafu {
+                actual: payload_type,
+            }
+            .fail(),
+        }
+    }
+}
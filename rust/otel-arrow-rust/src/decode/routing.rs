@@ -0,0 +1,161 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Extraction of the routing keys of the batches of a stream, e.g. the `tenant.id` or the
+//! `service.name` resource attribute, for the load balancers and shard routers forwarding
+//! the batches.
+//!
+//! A [`RoutingKeyExtractor`] reads the resource attributes of the batches with a
+//! [`PassthroughConsumer`], so the spans, logs and metrics are never decoded and the
+//! batches are forwarded as received. The resources without any attribute have no row in
+//! the resource attributes, so they have no routing key.
+
+use crate::Consumer;
+use crate::decode::passthrough::PassthroughConsumer;
+use crate::error::Result;
+use crate::otlp::attributes::store::Attribute16Store;
+use crate::otlp::options::DecoderOptions;
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayloadType, BatchArrowRecords};
+use crate::proto::opentelemetry::common::v1::AnyValue;
+
+/// Values of the routing attributes of a resource of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoutingKey {
+    /// Id of the resource in the batch.
+    pub resource_id: u16,
+    /// Values of the routing attributes, in the order of the extractor, `None` for the
+    /// attributes the resource doesn't have.
+    pub values: Vec<Option<AnyValue>>,
+}
+
+/// A batch whose routing keys were extracted.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoutedBatch {
+    /// The batch as received, to forward.
+    pub records: BatchArrowRecords,
+    /// Routing keys of the resources of the batch, by increasing resource id.
+    pub keys: Vec<RoutingKey>,
+}
+
+impl RoutedBatch {
+    /// Returns the values of the routing attributes if all the resources of the batch share
+    /// them, i.e. the batch can be routed as a whole.
+    #[must_use]
+    pub fn uniform_key(&self) -> Option<&[Option<AnyValue>]> {
+        let (first, others) = self.keys.split_first()?;
+        others
+            .iter()
+            .all(|key| key.values == first.values)
+            .then_some(first.values.as_slice())
+    }
+}
+
+/// Extracts the routing keys of the batches of a stream, see the module documentation.
+pub struct RoutingKeyExtractor {
+    passthrough: PassthroughConsumer,
+    attributes: Vec<String>,
+    options: DecoderOptions,
+}
+
+impl RoutingKeyExtractor {
+    /// Creates an extractor of the given resource attributes.
+    #[must_use]
+    pub fn new(attributes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::with_options(DecoderOptions::default(), attributes)
+    }
+
+    /// Creates an extractor of the given resource attributes, reading the batches with the
+    /// given options, e.g. the limits of the router.
+    #[must_use]
+    pub fn with_options(
+        options: DecoderOptions,
+        attributes: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            passthrough: PassthroughConsumer::with_consumer(
+                Consumer::with_options(options.clone()),
+                [ArrowPayloadType::ResourceAttrs],
+            ),
+            attributes: attributes.into_iter().map(Into::into).collect(),
+            options,
+        }
+    }
+
+    /// Extracts the routing keys of the batch, which is returned untouched.
+    pub fn extract(&mut self, records: BatchArrowRecords) -> Result<RoutedBatch> {
+        let batch = self.passthrough.consume(records)?;
+        let keys = match batch.decoded.get(ArrowPayloadType::ResourceAttrs) {
+            Some(resource_attrs) => {
+                let store = Attribute16Store::try_from_with_options(resource_attrs, &self.options)?;
+                store
+                    .iter()
+                    .map(|(resource_id, attrs)| RoutingKey {
+                        resource_id,
+                        values: self
+                            .attributes
+                            .iter()
+                            .map(|name| {
+                                attrs
+                                    .iter()
+                                    .find(|attr| attr.key == *name)
+                                    .and_then(|attr| attr.value.clone())
+                            })
+                            .collect(),
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        Ok(RoutedBatch {
+            records: batch.records,
+            keys,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::datagen::{DatagenConfig, Generator};
+    use crate::encode::TracesProducer;
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+
+    #[test]
+    fn test_routing_keys() {
+        let request = Generator::new(DatagenConfig::default().with_services(3)).traces_request(8);
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        let records = to_batch_arrow_records(&otap_batch, 0).unwrap();
+
+        let mut extractor = RoutingKeyExtractor::new(["service.name", "tenant.id"]);
+        let routed = extractor.extract(records.clone()).unwrap();
+        assert_eq!(routed.records, records);
+        let service_names: Vec<_> = routed
+            .keys
+            .iter()
+            .map(|key| match &key.values[..] {
+                [
+                    Some(AnyValue {
+                        value: Some(Value::StringValue(name)),
+                    }),
+                    None,
+                ] => name.clone(),
+                values => panic!("unexpected routing key {values:?}"),
+            })
+            .collect();
+        let expected: Vec<_> = request
+            .resource_spans
+            .iter()
+            .filter_map(|rs| rs.resource.as_ref())
+            .flat_map(|resource| &resource.attributes)
+            .filter(|attr| attr.key == "service.name")
+            .filter_map(|attr| match attr.value.as_ref()?.value.as_ref()? {
+                Value::StringValue(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(service_names, expected);
+        assert_eq!(routed.uniform_key().is_some(), expected.len() == 1);
+    }
+}
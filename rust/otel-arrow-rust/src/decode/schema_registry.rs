@@ -0,0 +1,216 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracking of the schemas used by the payloads of an OTAP stream.
+
+use std::collections::HashMap;
+
+use arrow::datatypes::SchemaRef;
+use snafu::ensure;
+
+use crate::error;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::compat::{SchemaDiff, compare_schemas};
+
+/// Change of the schema used for a payload type, reported by [`SchemaRegistry::register`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaEvent {
+    /// A schema was registered for a payload type for the first time.
+    New {
+        /// Main payload type of the signal the payload belongs to.
+        main_payload_type: ArrowPayloadType,
+        /// Type of the payload.
+        payload_type: ArrowPayloadType,
+        /// Id of the schema.
+        schema_id: String,
+        /// The schema.
+        schema: SchemaRef,
+    },
+    /// The schema used for a payload type changed. Dictionaries and any other state
+    /// related to the previous schema have been reset.
+    Reset {
+        /// Main payload type of the signal the payload belongs to.
+        main_payload_type: ArrowPayloadType,
+        /// Type of the payload.
+        payload_type: ArrowPayloadType,
+        /// Id of the schema previously used for this payload type.
+        previous_schema_id: String,
+        /// Id of the new schema.
+        schema_id: String,
+        /// The new schema.
+        schema: SchemaRef,
+        /// Changes of the new schema from the previous one.
+        diff: SchemaDiff,
+    },
+}
+
+/// Tracks the schema id and Arrow schema of every payload type of a stream.
+///
+/// Payloads of several signals can be multiplexed over the same stream, so payload types
+/// are tracked per signal, identified by the main payload type of their batch. Within a
+/// signal, a schema id identifies a single payload type and schema.
+#[derive(Debug, Default)]
+pub struct SchemaRegistry {
+    current: HashMap<(ArrowPayloadType, ArrowPayloadType), String>,
+    schemas: HashMap<(ArrowPayloadType, String), (ArrowPayloadType, SchemaRef)>,
+}
+
+impl SchemaRegistry {
+    /// Registers the schema of a received payload. Returns the schema change this payload
+    /// introduces, if any.
+    ///
+    /// Fails if the schema id is empty, or if it was already declared for another payload
+    /// type or with a different schema.
+    pub fn register(
+        &mut self,
+        main_payload_type: ArrowPayloadType,
+        payload_type: ArrowPayloadType,
+        schema_id: &str,
+        schema: &SchemaRef,
+    ) -> error::Result<Option<SchemaEvent>> {
+        ensure!(!schema_id.is_empty(), error::InvalidSchemaIdSnafu {
+            schema_id,
+            message: "schema id is empty",
+        });
+
+        if let Some((known_payload_type, known_schema)) = self
+            .schemas
+            .get(&(main_payload_type, schema_id.to_string()))
+        {
+            ensure!(
+                *known_payload_type == payload_type,
+                error::InvalidSchemaIdSnafu {
+                    schema_id,
+                    message: format!(
+                        "schema id already declared for payload type {}",
+                        known_payload_type.as_str_name()
+                    ),
+                }
+            );
+            ensure!(
+                known_schema.fields() == schema.fields(),
+                error::InvalidSchemaIdSnafu {
+                    schema_id,
+                    message: "schema id already declared with a different schema",
+                }
+            );
+        }
+
+        let previous_schema_id = self
+            .current
+            .insert((main_payload_type, payload_type), schema_id.to_string());
+        match previous_schema_id {
+            Some(previous_schema_id) if previous_schema_id == schema_id => Ok(None),
+            Some(previous_schema_id) => {
+                let diff = self
+                    .schemas
+                    .remove(&(main_payload_type, previous_schema_id.clone()))
+                    .map(|(_, previous)| compare_schemas(payload_type, &previous, schema))
+                    .unwrap_or_default();
+                let _ = self.schemas.insert(
+                    (main_payload_type, schema_id.to_string()),
+                    (payload_type, schema.clone()),
+                );
+                Ok(Some(SchemaEvent::Reset {
+                    main_payload_type,
+                    payload_type,
+                    previous_schema_id,
+                    schema_id: schema_id.to_string(),
+                    schema: schema.clone(),
+                    diff,
+                }))
+            }
+            None => {
+                let _ = self.schemas.insert(
+                    (main_payload_type, schema_id.to_string()),
+                    (payload_type, schema.clone()),
+                );
+                Ok(Some(SchemaEvent::New {
+                    main_payload_type,
+                    payload_type,
+                    schema_id: schema_id.to_string(),
+                    schema: schema.clone(),
+                }))
+            }
+        }
+    }
+
+    /// Returns the id and schema currently used for the payload type of the given signal.
+    #[must_use]
+    pub fn current_schema(
+        &self,
+        main_payload_type: ArrowPayloadType,
+        payload_type: ArrowPayloadType,
+    ) -> Option<(&str, &SchemaRef)> {
+        let schema_id = self.current.get(&(main_payload_type, payload_type))?;
+        let (_, schema) = self.schemas.get(&(main_payload_type, schema_id.clone()))?;
+        Some((schema_id, schema))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    fn schema(name: &str) -> SchemaRef {
+        Arc::new(Schema::new(vec![Field::new(name, DataType::UInt16, true)]))
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = SchemaRegistry::default();
+        let logs = ArrowPayloadType::Logs;
+        let attrs = ArrowPayloadType::LogAttrs;
+
+        let event = registry.register(logs, logs, "0", &schema("id")).unwrap();
+        assert!(matches!(event, Some(SchemaEvent::New { schema_id, .. }) if schema_id == "0"));
+        assert_eq!(
+            registry.register(logs, logs, "0", &schema("id")).unwrap(),
+            None
+        );
+        assert_eq!(
+            registry.current_schema(logs, logs),
+            Some(("0", &schema("id")))
+        );
+
+        // same schema id used with another schema or for another payload type
+        assert!(
+            registry
+                .register(logs, logs, "0", &schema("other"))
+                .is_err()
+        );
+        assert!(registry.register(logs, attrs, "0", &schema("id")).is_err());
+        assert!(registry.register(logs, attrs, "", &schema("id")).is_err());
+
+        assert_eq!(
+            registry
+                .register(logs, logs, "2", &schema("other"))
+                .unwrap(),
+            Some(SchemaEvent::Reset {
+                main_payload_type: logs,
+                payload_type: logs,
+                previous_schema_id: "0".to_string(),
+                schema_id: "2".to_string(),
+                schema: schema("other"),
+                diff: compare_schemas(logs, &schema("id"), &schema("other")),
+            })
+        );
+        // the previous schema id can be reused once it was replaced
+        assert!(registry.register(logs, attrs, "0", &schema("id")).is_ok());
+
+        // other signals have their own schema ids
+        let event = registry
+            .register(
+                ArrowPayloadType::Spans,
+                ArrowPayloadType::Spans,
+                "0",
+                &schema("span"),
+            )
+            .unwrap();
+        assert!(matches!(event, Some(SchemaEvent::New { .. })));
+    }
+}
@@ -0,0 +1,751 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dictionary deltas, so the payloads of a stream only carry the dictionary values that
+//! were not sent by the previous payloads.
+//!
+//! Arrow IPC streams replace a dictionary by sending a new dictionary batch message for its
+//! id, or extend it with a dictionary batch message flagged `isDelta`, whose values are
+//! appended to the ones received before. The writer of arrow-rs only sends replacements,
+//! i.e. every dictionary used by a record batch is sent again in full, and its reader
+//! rejects the deltas.
+//!
+//! [`DeltaWriter`] keeps the dictionaries of the stream: the keys of every record batch
+//! are remapped onto them, the values they don't hold yet are appended and sent as a delta,
+//! and nothing is sent for the dictionaries that didn't change, e.g. the service names or
+//! the attribute keys of a steady workload. A dictionary is reset, i.e. sent in full with
+//! only the values of the record batch, once its values no longer fit its key type.
+//!
+//! [`DeltaReader`] rewrites the deltas received by a consumer into replacements holding
+//! the values received so far, before the payload is read by arrow-rs.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, AsArray, RecordBatch, StructArray, UInt32Array, UInt64Array, make_array,
+};
+use arrow::buffer::Buffer;
+use arrow::compute::{cast, concat, take};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::ipc::reader::read_record_batch;
+use arrow::ipc::writer::{
+    DictionaryTracker, EncodedData, IpcDataGenerator, IpcWriteOptions, write_message,
+};
+use arrow::ipc::{self, Message, MessageHeader, root_as_message};
+use arrow::row::{RowConverter, Rows, SortField};
+use flatbuffers::{FlatBufferBuilder, ForwardsUOffset, Vector};
+use prost::bytes::Bytes;
+use snafu::{OptionExt, ResultExt};
+
+use crate::compression::{CONTINUATION_MARKER, IpcMessage, split_ipc_messages};
+use crate::error::{self, Result};
+
+/// Writes the Arrow IPC stream of a payload type, sending the dictionaries as deltas, see
+/// the module documentation.
+pub(crate) struct DeltaWriter {
+    generator: IpcDataGenerator,
+    tracker: DictionaryTracker,
+    options: IpcWriteOptions,
+    /// The dictionaries of the stream, in the order of their ids.
+    dictionaries: Vec<StreamDictionary>,
+    /// The schema message, sent before the first record batch.
+    schema: Vec<u8>,
+}
+
+impl DeltaWriter {
+    /// Creates the writer, none if the schema has dictionaries nested in other types than
+    /// structs, whose ids can't be tracked.
+    pub(crate) fn try_new(schema: &SchemaRef, options: IpcWriteOptions) -> Result<Option<Self>> {
+        let mut key_types = Vec::new();
+        if !dictionary_key_types(schema.fields().iter().map(AsRef::as_ref), &mut key_types) {
+            return Ok(None);
+        }
+        let generator = IpcDataGenerator::default();
+        let mut tracker = DictionaryTracker::new(false);
+        let encoded =
+            generator.schema_to_bytes_with_dictionary_tracker(schema, &mut tracker, &options);
+        let mut schema = Vec::new();
+        let _ =
+            write_message(&mut schema, encoded, &options).context(error::WriteRecordBatchSnafu)?;
+        let ids = tracker.dict_id().to_vec();
+        if ids.len() != key_types.len() {
+            return Ok(None);
+        }
+        let dictionaries = ids
+            .into_iter()
+            .zip(key_types)
+            .map(|(id, (key_type, value_type))| StreamDictionary::try_new(id, key_type, value_type))
+            .collect::<Result<_>>()?;
+        Ok(Some(Self {
+            generator,
+            tracker,
+            options,
+            dictionaries,
+            schema,
+        }))
+    }
+
+    /// Encodes the record batch, preceded by the dictionary messages it needs.
+    pub(crate) fn write(&mut self, record_batch: &RecordBatch) -> Result<Vec<u8>> {
+        let mut out = std::mem::take(&mut self.schema);
+        let mut next = 0;
+        let columns = record_batch
+            .columns()
+            .iter()
+            .map(|column| self.unify_column(column, &mut next, &mut out))
+            .collect::<Result<Vec<_>>>()?;
+        let record_batch = RecordBatch::try_new(record_batch.schema(), columns)
+            .context(error::WriteRecordBatchSnafu)?;
+        // the dictionaries were all inserted in the tracker, so only the record batch is left
+        let (dictionaries, encoded) = self
+            .generator
+            .encoded_batch(&record_batch, &mut self.tracker, &self.options)
+            .context(error::WriteRecordBatchSnafu)?;
+        for encoded in dictionaries.into_iter().chain([encoded]) {
+            let _ = write_message(&mut out, encoded, &self.options)
+                .context(error::WriteRecordBatchSnafu)?;
+        }
+        Ok(out)
+    }
+
+    /// Remaps the dictionaries of the column, in the order of their ids, onto the ones of
+    /// the stream, writing the messages updating the dictionaries of the stream to `out`.
+    fn unify_column(
+        &mut self,
+        column: &ArrayRef,
+        next: &mut usize,
+        out: &mut Vec<u8>,
+    ) -> Result<ArrayRef> {
+        match column.data_type() {
+            DataType::Struct(fields) => {
+                let struct_array = column.as_struct();
+                let children = struct_array
+                    .columns()
+                    .iter()
+                    .map(|child| self.unify_column(child, next, out))
+                    .collect::<Result<Vec<_>>>()?;
+                let struct_array =
+                    StructArray::try_new(fields.clone(), children, struct_array.nulls().cloned())
+                        .context(error::WriteRecordBatchSnafu)?;
+                Ok(Arc::new(struct_array))
+            }
+            DataType::Dictionary(..) => {
+                let dictionary = &mut self.dictionaries[*next];
+                *next += 1;
+                let (column, update) = dictionary
+                    .unify(column)
+                    .context(error::WriteRecordBatchSnafu)?;
+                let id = dictionary.id;
+                if let Some((values, is_delta)) = update {
+                    out.extend(dictionary_message(id, &values, is_delta, &self.options)?);
+                }
+                let _ = self
+                    .tracker
+                    .insert(id, &column)
+                    .context(error::WriteRecordBatchSnafu)?;
+                Ok(column)
+            }
+            _ => Ok(column.clone()),
+        }
+    }
+}
+
+/// A dictionary of a stream, and the index of its values.
+struct StreamDictionary {
+    id: i64,
+    capacity: usize,
+    converter: RowConverter,
+    values: Option<ArrayRef>,
+    index: HashMap<Box<[u8]>, usize>,
+}
+
+impl StreamDictionary {
+    fn try_new(id: i64, key_type: DataType, value_type: DataType) -> Result<Self> {
+        let converter = RowConverter::new(vec![SortField::new(value_type)])
+            .context(error::WriteRecordBatchSnafu)?;
+        Ok(Self {
+            id,
+            capacity: key_capacity(&key_type),
+            converter,
+            values: None,
+            index: HashMap::new(),
+        })
+    }
+
+    /// Remaps the keys of the dictionary column onto the values of the stream. Returns the
+    /// column and, if the dictionary changed, the values to send and whether they are a
+    /// delta.
+    fn unify(
+        &mut self,
+        column: &ArrayRef,
+    ) -> std::result::Result<(ArrayRef, Option<(ArrayRef, bool)>), ArrowError> {
+        let dictionary = column.as_any_dictionary();
+        let values = dictionary.values();
+        let rows = self.converter.convert_columns(&[values.clone()])?;
+        let (mapping, added) = match self.map(&rows) {
+            Some(mapping) => mapping,
+            None => {
+                self.reset();
+                match self.map(&rows) {
+                    Some(mapping) => mapping,
+                    None => {
+                        // more values than the keys can address, sent as they are
+                        self.reset();
+                        return Ok((column.clone(), Some((values.clone(), false))));
+                    }
+                }
+            }
+        };
+
+        let added = take(values, &UInt32Array::from(added), None)?;
+        let (merged, update) = match self.values.take() {
+            Some(previous) if added.is_empty() => (previous, None),
+            Some(previous) => (concat(&[&previous, &added])?, Some((added, true))),
+            None => (added.clone(), Some((added, false))),
+        };
+        let keys = dictionary.keys();
+        let mapping = cast(&UInt64Array::from(mapping), keys.data_type())?;
+        let keys = take(&mapping, keys, None)?;
+        let data = keys
+            .into_data()
+            .into_builder()
+            .data_type(column.data_type().clone())
+            .child_data(vec![merged.to_data()])
+            .build()?;
+        self.values = Some(merged);
+        Ok((make_array(data), update))
+    }
+
+    /// Returns the index of every value in the dictionary of the stream, and the values to
+    /// append to it. None if they don't fit the keys.
+    fn map(&mut self, rows: &Rows) -> Option<(Vec<u64>, Vec<u32>)> {
+        let len = self.values.as_ref().map_or(0, |values| values.len());
+        let mut mapping = Vec::with_capacity(rows.num_rows());
+        let mut added = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let next = len + added.len();
+            let index = *self.index.entry(row.as_ref().into()).or_insert_with(|| {
+                added.push(i as u32);
+                next
+            });
+            if index >= self.capacity {
+                return None;
+            }
+            mapping.push(index as u64);
+        }
+        Some((mapping, added))
+    }
+
+    fn reset(&mut self) {
+        self.values = None;
+        self.index.clear();
+    }
+}
+
+/// Rewrites the dictionary deltas of the payloads of a stream into replacements, see the
+/// module documentation.
+///
+/// Until the stream sends a delta, the dictionaries are only recorded as slices of the
+/// payload they were received in, shared with the stream reader. Once it has sent one, the
+/// dictionaries are copied out of their payload, which they would otherwise keep alive
+/// until the next delta or replacement of their id.
+#[derive(Default)]
+pub(crate) struct DeltaReader {
+    value_types: HashMap<i64, DataType>,
+    dictionaries: HashMap<i64, Dictionary>,
+    /// Whether the stream has sent a delta.
+    sends_deltas: bool,
+}
+
+/// The last dictionary received for an id, decoded once a delta extends it.
+enum Dictionary {
+    Encoded { header: Bytes, body: Bytes },
+    Decoded(ArrayRef),
+}
+
+impl DeltaReader {
+    /// Returns the IPC messages of the payload, the dictionary deltas being replaced by the
+    /// dictionaries they extend.
+    pub(crate) fn rewrite(&mut self, bytes: Vec<u8>) -> Result<Bytes> {
+        let bytes = Bytes::from(bytes);
+        let messages = split_ipc_messages(&bytes)?;
+        let has_delta = messages.iter().any(|ipc_message| {
+            ipc_message
+                .message
+                .header_as_dictionary_batch()
+                .is_some_and(|dictionary| dictionary.isDelta())
+        });
+        if has_delta && !self.sends_deltas {
+            self.sends_deltas = true;
+            for dictionary in self.dictionaries.values_mut() {
+                if let Dictionary::Encoded { header, body } = dictionary {
+                    *header = Bytes::copy_from_slice(header);
+                    *body = Bytes::copy_from_slice(body);
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        for IpcMessage {
+            header,
+            message,
+            body,
+        } in messages
+        {
+            match message.header_type() {
+                MessageHeader::Schema => {
+                    self.value_types.clear();
+                    self.dictionaries.clear();
+                    if let Some(fields) = message.header_as_schema().and_then(|s| s.fields()) {
+                        dictionary_value_types(fields, &mut self.value_types);
+                    }
+                }
+                MessageHeader::DictionaryBatch => {
+                    // safety: the header type was just checked
+                    let dictionary = message
+                        .header_as_dictionary_batch()
+                        .expect("dictionary batch header");
+                    let id = dictionary.id();
+                    if dictionary.isDelta() {
+                        let values = self.merge(id, message, body)?;
+                        out.extend(dictionary_message(
+                            id,
+                            &values,
+                            false,
+                            &IpcWriteOptions::default(),
+                        )?);
+                        let _ = self.dictionaries.insert(id, Dictionary::Decoded(values));
+                        continue;
+                    }
+                    let (header, body) = if self.sends_deltas {
+                        (Bytes::copy_from_slice(header), Bytes::copy_from_slice(body))
+                    } else {
+                        (bytes.slice_ref(header), bytes.slice_ref(body))
+                    };
+                    let _ = self
+                        .dictionaries
+                        .insert(id, Dictionary::Encoded { header, body });
+                }
+                _ => {}
+            }
+            if has_delta {
+                out.extend_from_slice(&CONTINUATION_MARKER);
+                out.extend_from_slice(&(header.len() as i32).to_le_bytes());
+                out.extend_from_slice(header);
+                out.extend_from_slice(body);
+            }
+        }
+        Ok(if has_delta { out.into() } else { bytes })
+    }
+
+    /// Returns the values of the dictionary extended by the delta.
+    fn merge(&mut self, id: i64, message: Message<'_>, body: &[u8]) -> Result<ArrayRef> {
+        let invalid = |reason: String| error::InvalidDictionaryDeltaSnafu { id, reason };
+        let value_type = self
+            .value_types
+            .get(&id)
+            .with_context(|| invalid("no field of the schema uses it".to_string()))?;
+        let previous = match self.dictionaries.remove(&id) {
+            Some(Dictionary::Decoded(values)) => values,
+            Some(Dictionary::Encoded { header, body }) => {
+                // safety: the header was checked by split_ipc_messages
+                let message = root_as_message(&header).expect("valid message header");
+                decode_values(id, message, &body, value_type)?
+            }
+            None => return invalid("no dictionary to extend".to_string()).fail(),
+        };
+        let delta = decode_values(id, message, body, value_type)?;
+        concat(&[&previous, &delta]).context(error::ReadRecordBatchSnafu)
+    }
+}
+
+/// Returns the values of a dictionary batch message.
+fn decode_values(
+    id: i64,
+    message: Message<'_>,
+    body: &[u8],
+    value_type: &DataType,
+) -> Result<ArrayRef> {
+    let data = message
+        .header_as_dictionary_batch()
+        .and_then(|dictionary| dictionary.data())
+        .context(error::InvalidDictionaryDeltaSnafu {
+            id,
+            reason: "the dictionary batch has no data",
+        })?;
+    let schema = Arc::new(Schema::new(vec![Field::new("", value_type.clone(), true)]));
+    let record_batch = read_record_batch(
+        &Buffer::from_vec(body.to_vec()),
+        data,
+        schema,
+        &HashMap::new(),
+        None,
+        &message.version(),
+    )
+    .context(error::ReadRecordBatchSnafu)?;
+    Ok(record_batch.column(0).clone())
+}
+
+/// Returns the framed dictionary batch message sending the values for the id.
+fn dictionary_message(
+    id: i64,
+    values: &ArrayRef,
+    is_delta: bool,
+    options: &IpcWriteOptions,
+) -> Result<Vec<u8>> {
+    // the body of a dictionary batch is the one of a record batch holding the values
+    let schema = Arc::new(Schema::new(vec![Field::new(
+        "",
+        values.data_type().clone(),
+        true,
+    )]));
+    let record_batch =
+        RecordBatch::try_new(schema, vec![values.clone()]).context(error::WriteRecordBatchSnafu)?;
+    let (_, encoded) = IpcDataGenerator::default()
+        .encoded_batch(&record_batch, &mut DictionaryTracker::new(false), options)
+        .context(error::WriteRecordBatchSnafu)?;
+    let encoded = EncodedData {
+        ipc_message: dictionary_header(&encoded.ipc_message, id, is_delta),
+        arrow_data: encoded.arrow_data,
+    };
+    let mut out = Vec::new();
+    let _ = write_message(&mut out, encoded, options).context(error::WriteRecordBatchSnafu)?;
+    Ok(out)
+}
+
+/// Wraps the record batch of a message header into a dictionary batch.
+fn dictionary_header(record_batch_header: &[u8], id: i64, is_delta: bool) -> Vec<u8> {
+    // safety: the header was just written by the IPC data generator
+    let message = root_as_message(record_batch_header).expect("valid message header");
+    let record_batch = message
+        .header_as_record_batch()
+        .expect("record batch header");
+
+    let mut fbb = FlatBufferBuilder::new();
+    let nodes = record_batch
+        .nodes()
+        .map(|nodes| fbb.create_vector(&nodes.iter().copied().collect::<Vec<_>>()));
+    let buffers = record_batch
+        .buffers()
+        .map(|buffers| fbb.create_vector(&buffers.iter().copied().collect::<Vec<_>>()));
+    let variadic_buffer_counts = record_batch
+        .variadicBufferCounts()
+        .map(|counts| fbb.create_vector(&counts.iter().collect::<Vec<_>>()));
+    let compression = record_batch.compression().map(|compression| {
+        let mut builder = ipc::BodyCompressionBuilder::new(&mut fbb);
+        builder.add_codec(compression.codec());
+        builder.add_method(compression.method());
+        builder.finish()
+    });
+
+    let data = {
+        let mut builder = ipc::RecordBatchBuilder::new(&mut fbb);
+        builder.add_length(record_batch.length());
+        if let Some(nodes) = nodes {
+            builder.add_nodes(nodes);
+        }
+        if let Some(buffers) = buffers {
+            builder.add_buffers(buffers);
+        }
+        if let Some(compression) = compression {
+            builder.add_compression(compression);
+        }
+        if let Some(counts) = variadic_buffer_counts {
+            builder.add_variadicBufferCounts(counts);
+        }
+        builder.finish()
+    };
+    let header = {
+        let mut builder = ipc::DictionaryBatchBuilder::new(&mut fbb);
+        builder.add_id(id);
+        builder.add_data(data);
+        builder.add_isDelta(is_delta);
+        builder.finish().as_union_value()
+    };
+    let root = {
+        let mut builder = ipc::MessageBuilder::new(&mut fbb);
+        builder.add_version(message.version());
+        builder.add_header_type(MessageHeader::DictionaryBatch);
+        builder.add_bodyLength(message.bodyLength());
+        builder.add_header(header);
+        builder.finish()
+    };
+    fbb.finish(root, None);
+    fbb.finished_data().to_vec()
+}
+
+/// Collects the key and value types of the dictionaries of the fields, in the order their
+/// ids are assigned. Returns false if a dictionary is nested in another type than a struct.
+fn dictionary_key_types<'a>(
+    fields: impl Iterator<Item = &'a Field>,
+    key_types: &mut Vec<(DataType, DataType)>,
+) -> bool {
+    for field in fields {
+        match field.data_type() {
+            DataType::Dictionary(key_type, value_type) => {
+                if has_dictionary(value_type) {
+                    return false;
+                }
+                key_types.push((key_type.as_ref().clone(), value_type.as_ref().clone()));
+            }
+            DataType::Struct(children) => {
+                if !dictionary_key_types(children.iter().map(AsRef::as_ref), key_types) {
+                    return false;
+                }
+            }
+            data_type if has_dictionary(data_type) => return false,
+            _ => {}
+        }
+    }
+    true
+}
+
+fn has_dictionary(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Dictionary(..) => true,
+        DataType::List(field)
+        | DataType::LargeList(field)
+        | DataType::ListView(field)
+        | DataType::LargeListView(field)
+        | DataType::FixedSizeList(field, _)
+        | DataType::Map(field, _) => has_dictionary(field.data_type()),
+        DataType::Struct(fields) => fields.iter().any(|f| has_dictionary(f.data_type())),
+        DataType::Union(fields, _) => fields.iter().any(|(_, f)| has_dictionary(f.data_type())),
+        DataType::RunEndEncoded(_, values) => has_dictionary(values.data_type()),
+        _ => false,
+    }
+}
+
+/// Returns the number of values the keys of the type can address.
+fn key_capacity(key_type: &DataType) -> usize {
+    match key_type {
+        DataType::Int8 => 1 << 7,
+        DataType::UInt8 => 1 << 8,
+        DataType::Int16 => 1 << 15,
+        DataType::UInt16 => 1 << 16,
+        DataType::Int32 => 1 << 31,
+        _ => usize::MAX,
+    }
+}
+
+/// Collects the value types of the dictionaries of the schema fields by id.
+fn dictionary_value_types(
+    fields: Vector<'_, ForwardsUOffset<ipc::Field<'_>>>,
+    value_types: &mut HashMap<i64, DataType>,
+) {
+    for field in fields {
+        if let Some(dictionary) = field.dictionary() {
+            if let DataType::Dictionary(_, value_type) = Field::from(field).data_type() {
+                let _ = value_types.insert(dictionary.id(), value_type.as_ref().clone());
+            }
+        }
+        if let Some(children) = field.children() {
+            dictionary_value_types(children, value_types);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use arrow::array::{DictionaryArray, UInt16Array};
+    use arrow::datatypes::{Fields, UInt8Type};
+    use arrow::ipc::reader::StreamReader;
+
+    use crate::Consumer;
+    use crate::compression::{PayloadCompression, PayloadWriter};
+    use crate::encode::split::{schema_id, to_batch_arrow_records};
+    use crate::proto::opentelemetry::arrow::v1::{ArrowPayload, BatchArrowRecords};
+    use crate::test_util::workloads::traces_batch;
+
+    fn record_batch(keys: Vec<String>, names: Vec<&str>) -> RecordBatch {
+        let ids = UInt16Array::from_iter_values(0..keys.len() as u16);
+        let keys: DictionaryArray<UInt8Type> = keys.iter().map(String::as_str).collect();
+        let names: DictionaryArray<UInt8Type> = names.into_iter().collect();
+        let resource_fields =
+            Fields::from(vec![Field::new("name", names.data_type().clone(), true)]);
+        let resource = StructArray::new(resource_fields.clone(), vec![Arc::new(names)], None);
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::UInt16, true),
+                Field::new("key", keys.data_type().clone(), true),
+                Field::new("resource", DataType::Struct(resource_fields), true),
+            ])),
+            vec![Arc::new(ids), Arc::new(keys), Arc::new(resource)],
+        )
+        .unwrap()
+    }
+
+    fn strings(prefix: &str, range: std::ops::Range<usize>) -> Vec<String> {
+        range.map(|i| format!("{prefix}{i}")).collect()
+    }
+
+    /// Returns the ids and delta flags of the dictionary batch messages of the payload.
+    fn dictionary_messages(record: &[u8]) -> Vec<(i64, bool)> {
+        split_ipc_messages(record)
+            .unwrap()
+            .into_iter()
+            .filter_map(|ipc_message| {
+                let dictionary = ipc_message.message.header_as_dictionary_batch()?;
+                Some((dictionary.id(), dictionary.isDelta()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_delta_dictionaries() {
+        let batches = [
+            record_batch(strings("k", 0..4), vec!["a", "a", "b", "a"]),
+            // a new key, the names are unchanged
+            record_batch(strings("k", 2..5), vec!["b", "a", "b"]),
+            record_batch(strings("k", 0..2), vec!["a", "a"]),
+            // more keys than the dictionary of the stream can address
+            record_batch(strings("x", 0..255), vec!["c"; 255]),
+            record_batch(strings("k", 0..3), vec!["c", "c", "c"]),
+        ];
+        let expected_messages = [
+            vec![(0, false), (1, false)],
+            vec![(0, true)],
+            vec![],
+            vec![(0, false), (1, true)],
+            vec![(0, false)],
+        ];
+        for compression in PayloadCompression::supported() {
+            let mut writer =
+                PayloadWriter::try_new_with_delta_dictionaries(&batches[0].schema(), compression)
+                    .unwrap();
+            let mut deltas = DeltaReader::default();
+            let mut reader: Option<StreamReader<Cursor<Bytes>>> = None;
+            for (batch, expected) in batches.iter().zip(&expected_messages) {
+                let record = writer.write(batch).unwrap();
+                assert_eq!(&dictionary_messages(&record), expected);
+                let record = deltas.rewrite(record).unwrap();
+                assert!(dictionary_messages(&record).iter().all(|(_, delta)| !delta));
+
+                let reader = match &mut reader {
+                    Some(reader) => {
+                        *reader.get_mut() = Cursor::new(record);
+                        reader
+                    }
+                    None => {
+                        reader.insert(StreamReader::try_new(Cursor::new(record), None).unwrap())
+                    }
+                };
+                let decoded = reader.next().unwrap().unwrap();
+                let strings = |record_batch: &RecordBatch| {
+                    let names = record_batch.column(2).as_struct().column(0).clone();
+                    [record_batch.column(1).clone(), names]
+                        .map(|column| cast(&column, &DataType::Utf8).unwrap())
+                };
+                assert_eq!(strings(&decoded), strings(batch));
+            }
+        }
+
+        // a delta without the dictionary it extends
+        let mut writer = PayloadWriter::try_new_with_delta_dictionaries(
+            &batches[0].schema(),
+            PayloadCompression::None,
+        )
+        .unwrap();
+        let first = writer.write(&batches[0]).unwrap();
+        let second = writer.write(&batches[1]).unwrap();
+        let mut deltas = DeltaReader::default();
+        let schema_only = split_ipc_messages(&first).unwrap()[0].header;
+        let mut schema = CONTINUATION_MARKER.to_vec();
+        schema.extend_from_slice(&(schema_only.len() as i32).to_le_bytes());
+        schema.extend_from_slice(schema_only);
+        let _ = deltas.rewrite(schema).unwrap();
+        assert!(matches!(
+            deltas.rewrite(second),
+            Err(error::Error::InvalidDictionaryDelta { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dictionaries_copied_once_the_stream_sends_deltas() {
+        let batches = [
+            record_batch(strings("k", 0..4), vec!["a", "a", "b", "a"]),
+            record_batch(strings("k", 2..5), vec!["b", "a", "b"]),
+        ];
+        let mut writer = PayloadWriter::try_new_with_delta_dictionaries(
+            &batches[0].schema(),
+            PayloadCompression::None,
+        )
+        .unwrap();
+        let mut deltas = DeltaReader::default();
+        let in_payload = |deltas: &DeltaReader, record: &Bytes| {
+            deltas
+                .dictionaries
+                .values()
+                .filter(|dictionary| {
+                    matches!(dictionary, Dictionary::Encoded { header, .. }
+                        if record.as_ptr_range().contains(&header.as_ptr()))
+                })
+                .count()
+        };
+
+        // no delta yet, the dictionaries are slices of the payload
+        let first = deltas.rewrite(writer.write(&batches[0]).unwrap()).unwrap();
+        assert!(!deltas.sends_deltas);
+        assert_eq!(in_payload(&deltas, &first), 2);
+
+        // the dictionary of the keys is extended, the one of the names is copied
+        let _ = deltas.rewrite(writer.write(&batches[1]).unwrap()).unwrap();
+        assert!(deltas.sends_deltas);
+        assert!(matches!(deltas.dictionaries[&0], Dictionary::Decoded(_)));
+        assert!(matches!(
+            deltas.dictionaries[&1],
+            Dictionary::Encoded { .. }
+        ));
+        assert_eq!(in_payload(&deltas, &first), 0);
+    }
+
+    #[test]
+    fn test_consumer_reads_deltas() {
+        let otap_batch = traces_batch(8, 2);
+        let expected = Consumer::default()
+            .consume_batches(&mut to_batch_arrow_records(&otap_batch, 0).unwrap())
+            .unwrap();
+
+        let mut writers: HashMap<_, PayloadWriter> = HashMap::new();
+        let mut consumer = Consumer::default();
+        let mut sizes = Vec::new();
+        for batch_id in 0..3 {
+            let mut records = BatchArrowRecords {
+                batch_id,
+                ..Default::default()
+            };
+            for payload_type in otap_batch.payload_types() {
+                let record_batch = otap_batch.get(payload_type).unwrap();
+                let writer = writers.entry(payload_type).or_insert_with(|| {
+                    PayloadWriter::try_new_with_delta_dictionaries(
+                        &record_batch.schema(),
+                        PayloadCompression::None,
+                    )
+                    .unwrap()
+                });
+                records.arrow_payloads.push(ArrowPayload {
+                    schema_id: schema_id(payload_type, 0),
+                    r#type: payload_type as i32,
+                    record: writer.write(record_batch).unwrap(),
+                });
+            }
+            sizes.push(
+                records
+                    .arrow_payloads
+                    .iter()
+                    .map(|p| p.record.len())
+                    .sum::<usize>(),
+            );
+            assert_eq!(consumer.consume_batches(&mut records).unwrap(), expected);
+        }
+        // the dictionaries are only sent by the first batch
+        assert!(sizes[1] < sizes[0]);
+        assert_eq!(sizes[1], sizes[2]);
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Encoding of OTLP requests into OTAP batches, the reverse of [`crate::otlp`]. The rows are
+//! sorted and their ids delta encoded like the Go producer does.
+//!
+//! The encoding is deterministic: the sorts are stable and their ties keep the order of the
+//! request, the dictionaries are built in the order of the sorted rows and no hash map
+//! order leaks into the batches, so a request always encodes to the same bytes, e.g. for
+//! golden files or caching. This is the only mode of the encoders, there is no option to
+//! enable it. The batches of a [`StreamEncoder`] are deterministic as well.
+
+pub(crate) mod attributes;
+pub mod batcher;
+pub mod cardinality;
+mod common;
+pub mod logs;
+pub mod merge;
+pub mod metrics;
+pub(crate) mod record;
+pub mod sorter;
+pub mod split;
+pub mod stream;
+pub mod traces;
+
+pub use batcher::{BatcherConfig, RequestBatcher};
+pub use cardinality::{CardinalityAlert, CardinalityHook, CardinalityTracker};
+pub use logs::LogsProducer;
+pub use merge::BatchMerger;
+pub use metrics::MetricsProducer;
+pub use sorter::{LogSorter, MetricSorter, SpanSorter};
+pub use split::BatchSplitter;
+pub use stream::StreamEncoder;
+pub use traces::TracesProducer;
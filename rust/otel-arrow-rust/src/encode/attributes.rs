@@ -0,0 +1,215 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Production of the attribute record batches.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, ArrowPrimitiveType, BinaryArray, BooleanArray, Float64Array, Int64Array,
+    PrimitiveArray, RecordBatch, StringArray, UInt8Array,
+};
+use arrow::datatypes::{ArrowNativeTypeOp, DataType, UInt16Type, UInt32Type};
+
+use crate::encode::record::{Columns, DictionaryKey, dictionary};
+use crate::error::Result;
+use crate::otlp::attributes::decoder::is_same_group_value;
+use crate::otlp::attributes::store::AttributeValueType;
+use crate::proto::opentelemetry::common::v1::KeyValue;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::schema::consts;
+use crate::value::cbor::encode_pcommon_val;
+
+pub(crate) type Attributes16Accumulator<'a> = AttributesAccumulator<'a, UInt16Type>;
+pub(crate) type Attributes32Accumulator<'a> = AttributesAccumulator<'a, UInt32Type>;
+
+struct Attribute<'a, T> {
+    parent_id: T,
+    key: &'a str,
+    value_type: AttributeValueType,
+    value: &'a Value,
+}
+
+/// Attributes of the entities of a payload, accumulated to produce the attributes record
+/// batch of the payload.
+pub(crate) struct AttributesAccumulator<'a, T: ArrowPrimitiveType> {
+    attrs: Vec<Attribute<'a, T::Native>>,
+}
+
+impl<T: ArrowPrimitiveType> Default for AttributesAccumulator<'_, T> {
+    fn default() -> Self {
+        Self { attrs: Vec::new() }
+    }
+}
+
+impl<'a, T> AttributesAccumulator<'a, T>
+where
+    T: ArrowPrimitiveType,
+    T::Native: Ord,
+{
+    /// Adds the attributes of the entity with the given id.
+    pub(crate) fn append(&mut self, parent_id: T::Native, attrs: &'a [KeyValue]) {
+        for kv in attrs {
+            // the decoder drops the attributes without value, and the Go encoder doesn't
+            // support them either
+            let Some(value) = kv.value.as_ref().and_then(|v| v.value.as_ref()) else {
+                continue;
+            };
+            self.attrs.push(Attribute {
+                parent_id,
+                key: &kv.key,
+                value_type: AttributeValueType::from(Some(value)),
+                value,
+            });
+        }
+    }
+
+    /// Returns the attributes record batch, sorted by value type, key, value and parent id.
+    /// The parent ids of consecutive attributes with the same key and value are delta
+    /// encoded.
+    pub(crate) fn finish(mut self) -> Result<Option<RecordBatch>> {
+        if self.attrs.is_empty() {
+            return Ok(None);
+        }
+
+        self.attrs.sort_by(|a, b| {
+            (a.value_type as u8)
+                .cmp(&(b.value_type as u8))
+                .then_with(|| a.key.cmp(b.key))
+                .then_with(|| compare_values(a.value, b.value))
+                .then_with(|| a.parent_id.cmp(&b.parent_id))
+        });
+
+        let mut prev: Option<&Attribute<'_, T::Native>> = None;
+        let parent_ids = PrimitiveArray::<T>::from_iter_values(self.attrs.iter().map(|attr| {
+            let parent_id = match prev {
+                Some(prev)
+                    if prev.key == attr.key && is_same_group_value(prev.value, attr.value) =>
+                {
+                    attr.parent_id.sub_wrapping(prev.parent_id)
+                }
+                _ => attr.parent_id,
+            };
+            prev = Some(attr);
+            parent_id
+        }));
+        let mut parent_ids: ArrayRef = Arc::new(parent_ids);
+        if T::DATA_TYPE == DataType::UInt32 {
+            // the Go encoder also dictionary encodes the 32 bits parent ids
+            parent_ids = dictionary(parent_ids, DictionaryKey::U8);
+        }
+
+        let mut values = ValueColumns::default();
+        for attr in &self.attrs {
+            values.append(Some(attr.value));
+        }
+        let keys = StringArray::from_iter_values(self.attrs.iter().map(|attr| attr.key));
+
+        let mut columns = Columns::default();
+        columns.required(consts::PARENT_ID, parent_ids);
+        columns.required(
+            consts::ATTRIBUTE_KEY,
+            dictionary(Arc::new(keys), DictionaryKey::U8),
+        );
+        // the decoder requires the string column, even when it only holds nulls
+        values.finish(&mut columns, true);
+
+        columns.into_record_batch().map(Some)
+    }
+}
+
+/// Columns of the values of attributes or log bodies, one column per value type.
+#[derive(Default)]
+pub(crate) struct ValueColumns<'a> {
+    types: Vec<u8>,
+    strs: Vec<Option<&'a str>>,
+    ints: Vec<Option<i64>>,
+    doubles: Vec<Option<f64>>,
+    bools: Vec<Option<bool>>,
+    bytes: Vec<Option<&'a [u8]>>,
+    sers: Vec<Option<Vec<u8>>>,
+}
+
+impl<'a> ValueColumns<'a> {
+    /// Adds a value, the maps and slices are CBOR serialized.
+    pub(crate) fn append(&mut self, value: Option<&'a Value>) {
+        self.types.push(AttributeValueType::from(value) as u8);
+        let mut str = None;
+        let mut int = None;
+        let mut double = None;
+        let mut bool = None;
+        let mut bin = None;
+        let mut ser = None;
+        match value {
+            None => {}
+            Some(Value::StringValue(s)) => str = Some(s.as_str()),
+            Some(Value::IntValue(i)) => int = Some(*i),
+            Some(Value::DoubleValue(d)) => double = Some(*d),
+            Some(Value::BoolValue(b)) => bool = Some(*b),
+            Some(Value::BytesValue(b)) => bin = Some(b.as_slice()),
+            Some(Value::KvlistValue(_) | Value::ArrayValue(_)) => {
+                ser = Some(encode_pcommon_val(value));
+            }
+        }
+        self.strs.push(str);
+        self.ints.push(int);
+        self.doubles.push(double);
+        self.bools.push(bool);
+        self.bytes.push(bin);
+        self.sers.push(ser);
+    }
+
+    /// Adds the `type` column and the value columns to `columns`, the value columns only
+    /// holding nulls are left out, except the string one if `str_required` is set.
+    pub(crate) fn finish(self, columns: &mut Columns, str_required: bool) {
+        columns.required(
+            consts::ATTRIBUTE_TYPE,
+            Arc::new(UInt8Array::from(self.types)),
+        );
+        let strs = dictionary(Arc::new(StringArray::from(self.strs)), DictionaryKey::U16);
+        if str_required {
+            columns.required(consts::ATTRIBUTE_STR, strs);
+        } else {
+            columns.optional(consts::ATTRIBUTE_STR, strs);
+        }
+        columns.optional(
+            consts::ATTRIBUTE_INT,
+            dictionary(Arc::new(Int64Array::from(self.ints)), DictionaryKey::U16),
+        );
+        columns.optional(
+            consts::ATTRIBUTE_DOUBLE,
+            Arc::new(Float64Array::from(self.doubles)),
+        );
+        columns.optional(
+            consts::ATTRIBUTE_BOOL,
+            Arc::new(BooleanArray::from(self.bools)),
+        );
+        columns.optional(
+            consts::ATTRIBUTE_BYTES,
+            dictionary(Arc::new(BinaryArray::from(self.bytes)), DictionaryKey::U16),
+        );
+        columns.optional(
+            consts::ATTRIBUTE_SER,
+            dictionary(
+                Arc::new(BinaryArray::from_iter(
+                    self.sers.iter().map(|ser| ser.as_deref()),
+                )),
+                DictionaryKey::U16,
+            ),
+        );
+    }
+}
+
+/// Orders the values of the same type like the `Compare` function of the Go implementation.
+/// Maps and slices are left unordered.
+pub(crate) fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::StringValue(a), Value::StringValue(b)) => a.cmp(b),
+        (Value::IntValue(a), Value::IntValue(b)) => a.cmp(b),
+        (Value::DoubleValue(a), Value::DoubleValue(b)) => a.total_cmp(b),
+        (Value::BoolValue(a), Value::BoolValue(b)) => a.cmp(b),
+        (Value::BytesValue(a), Value::BytesValue(b)) => a.cmp(b),
+        _ => Ordering::Equal,
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Coalescing of the small OTLP requests of an exporter into larger OTAP batches.
+//!
+//! The columnar encoding pays off on batches of many records: a batch of a few spans costs
+//! its schemas and the framing of every payload for little data. A [`RequestBatcher`]
+//! buffers the requests of every signal and produces the batch of a signal once it holds
+//! enough records or bytes, or once its oldest request waited for the maximum age. The
+//! batcher reads no clock, the times are passed by the caller, which polls the batcher at
+//! its [`RequestBatcher::deadline`] to flush the signals that aged out.
+//!
+//! The requests are merged like with a [`BatchMerger`](crate::encode::merge::BatchMerger),
+//! and a request is never split: a request larger than the thresholds is produced at once,
+//! alone or with the ones buffered before it.
+
+use std::time::{Duration, Instant};
+
+use prost::Message;
+
+use crate::decode::decoder::ExportRequest;
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::metrics::v1::metric::Data;
+
+/// Thresholds of a [`RequestBatcher`], see the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatcherConfig {
+    max_records: usize,
+    max_bytes: Option<usize>,
+    max_age: Duration,
+}
+
+impl Default for BatcherConfig {
+    /// Batches of 8192 records, of any size, buffered for at most 200 milliseconds, like
+    /// the batch processor of the collector.
+    fn default() -> Self {
+        Self {
+            max_records: 8192,
+            max_bytes: None,
+            max_age: Duration::from_millis(200),
+        }
+    }
+}
+
+impl BatcherConfig {
+    /// Sets the number of spans, log records or data points a batch is produced at.
+    #[must_use]
+    pub fn with_max_records(mut self, max_records: usize) -> Self {
+        self.max_records = max_records;
+        self
+    }
+
+    /// Sets the protobuf encoded size of the buffered requests a batch is produced at.
+    #[must_use]
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Sets how long a request is buffered at most.
+    #[must_use]
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+}
+
+/// Request of a signal that the batcher buffers.
+trait BufferedRequest: Default + Message {
+    /// Returns the number of spans, log records or data points of the request.
+    fn records(&self) -> usize;
+
+    /// Appends the resources of `other` to the request.
+    fn append(&mut self, other: Self);
+
+    /// Produces the OTAP batch of the request.
+    fn produce(&self) -> Result<OtapBatch>;
+}
+
+impl BufferedRequest for ExportLogsServiceRequest {
+    fn records(&self) -> usize {
+        self.resource_logs
+            .iter()
+            .flat_map(|rl| &rl.scope_logs)
+            .map(|sl| sl.log_records.len())
+            .sum()
+    }
+
+    fn append(&mut self, other: Self) {
+        self.resource_logs.extend(other.resource_logs);
+    }
+
+    fn produce(&self) -> Result<OtapBatch> {
+        LogsProducer::new().produce(self)
+    }
+}
+
+impl BufferedRequest for ExportMetricsServiceRequest {
+    fn records(&self) -> usize {
+        self.resource_metrics
+            .iter()
+            .flat_map(|rm| &rm.scope_metrics)
+            .flat_map(|sm| &sm.metrics)
+            .filter_map(|metric| metric.data.as_ref())
+            .map(|data| match data {
+                Data::Gauge(g) => g.data_points.len(),
+                Data::Sum(s) => s.data_points.len(),
+                Data::Histogram(h) => h.data_points.len(),
+                Data::ExponentialHistogram(h) => h.data_points.len(),
+                Data::Summary(s) => s.data_points.len(),
+            })
+            .sum()
+    }
+
+    fn append(&mut self, other: Self) {
+        self.resource_metrics.extend(other.resource_metrics);
+    }
+
+    fn produce(&self) -> Result<OtapBatch> {
+        MetricsProducer::new().produce(self)
+    }
+}
+
+impl BufferedRequest for ExportTraceServiceRequest {
+    fn records(&self) -> usize {
+        self.resource_spans
+            .iter()
+            .flat_map(|rs| &rs.scope_spans)
+            .map(|ss| ss.spans.len())
+            .sum()
+    }
+
+    fn append(&mut self, other: Self) {
+        self.resource_spans.extend(other.resource_spans);
+    }
+
+    fn produce(&self) -> Result<OtapBatch> {
+        TracesProducer::new().produce(self)
+    }
+}
+
+/// The buffered requests of a signal.
+#[derive(Default)]
+struct Buffer<R> {
+    request: R,
+    records: usize,
+    bytes: usize,
+    /// When the oldest buffered request was pushed, `None` if the buffer is empty.
+    since: Option<Instant>,
+}
+
+impl<R: BufferedRequest> Buffer<R> {
+    fn push(&mut self, request: R, now: Instant) {
+        self.records += request.records();
+        self.bytes += request.encoded_len();
+        self.request.append(request);
+        let _ = self.since.get_or_insert(now);
+    }
+
+    fn is_full(&self, config: &BatcherConfig) -> bool {
+        self.records >= config.max_records
+            || config
+                .max_bytes
+                .is_some_and(|max_bytes| self.bytes >= max_bytes)
+    }
+
+    fn deadline(&self, config: &BatcherConfig) -> Option<Instant> {
+        self.since.map(|since| since + config.max_age)
+    }
+
+    /// Produces the batch of the buffered requests, `None` if there are none, and empties
+    /// the buffer.
+    fn finish(&mut self) -> Result<Option<OtapBatch>> {
+        let buffer = std::mem::take(self);
+        buffer.since.map(|_| buffer.request.produce()).transpose()
+    }
+}
+
+/// Buffers the requests of every signal until their batch is due, see the module
+/// documentation.
+#[derive(Default)]
+pub struct RequestBatcher {
+    config: BatcherConfig,
+    logs: Buffer<ExportLogsServiceRequest>,
+    metrics: Buffer<ExportMetricsServiceRequest>,
+    traces: Buffer<ExportTraceServiceRequest>,
+}
+
+impl RequestBatcher {
+    /// Creates an empty batcher with the given thresholds.
+    #[must_use]
+    pub fn new(config: BatcherConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Buffers the request received at `now`, returning the batch of its signal if the
+    /// request filled it.
+    pub fn push(&mut self, request: ExportRequest, now: Instant) -> Result<Option<OtapBatch>> {
+        let config = &self.config;
+        match request {
+            ExportRequest::Logs(request) => {
+                self.logs.push(request, now);
+                if self.logs.is_full(config) {
+                    return self.logs.finish();
+                }
+            }
+            ExportRequest::Metrics(request) => {
+                self.metrics.push(request, now);
+                if self.metrics.is_full(config) {
+                    return self.metrics.finish();
+                }
+            }
+            ExportRequest::Traces(request) => {
+                self.traces.push(request, now);
+                if self.traces.is_full(config) {
+                    return self.traces.finish();
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the earliest time a buffered signal reaches its maximum age, `None` if no
+    /// request is buffered.
+    #[must_use]
+    pub fn deadline(&self) -> Option<Instant> {
+        [
+            self.logs.deadline(&self.config),
+            self.metrics.deadline(&self.config),
+            self.traces.deadline(&self.config),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    /// Produces the batches of the signals whose oldest request reached the maximum age at
+    /// `now`.
+    pub fn poll(&mut self, now: Instant) -> Result<Vec<OtapBatch>> {
+        let config = &self.config;
+        let due = |deadline: Option<Instant>| deadline.is_some_and(|deadline| deadline <= now);
+        let mut batches = Vec::new();
+        if due(self.logs.deadline(config)) {
+            batches.extend(self.logs.finish()?);
+        }
+        if due(self.metrics.deadline(config)) {
+            batches.extend(self.metrics.finish()?);
+        }
+        if due(self.traces.deadline(config)) {
+            batches.extend(self.traces.finish()?);
+        }
+        Ok(batches)
+    }
+
+    /// Produces the batches of all the buffered requests, e.g. when the exporter shuts down.
+    pub fn flush(&mut self) -> Result<Vec<OtapBatch>> {
+        let mut batches = Vec::new();
+        batches.extend(self.logs.finish()?);
+        batches.extend(self.metrics.finish()?);
+        batches.extend(self.traces.finish()?);
+        Ok(batches)
+    }
+
+    /// Returns whether no request is buffered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.deadline().is_none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::datagen::{DatagenConfig, Generator};
+    use crate::otlp::traces::traces_from;
+
+    #[test]
+    fn test_request_batcher() {
+        let mut generator = Generator::new(DatagenConfig::default());
+        let first = generator.traces_request(4);
+        let second = generator.traces_request(4);
+        let config = BatcherConfig::default()
+            .with_max_records(first.records() + 1)
+            .with_max_age(Duration::from_millis(100));
+        let mut batcher = RequestBatcher::new(config);
+        let start = Instant::now();
+        assert!(batcher.is_empty());
+        assert!(batcher.deadline().is_none());
+
+        // the traces are produced once they reach the records threshold
+        let pushed = batcher
+            .push(ExportRequest::Traces(first.clone()), start)
+            .unwrap();
+        assert!(pushed.is_none());
+        let logs = generator.logs_request(4);
+        let later = start + Duration::from_millis(50);
+        assert!(
+            batcher
+                .push(ExportRequest::Logs(logs.clone()), later)
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(batcher.deadline(), Some(start + Duration::from_millis(100)));
+        let batch = batcher
+            .push(ExportRequest::Traces(second.clone()), later)
+            .unwrap()
+            .unwrap();
+        let mut expected = first;
+        expected.append(second);
+        assert_eq!(
+            traces_from(batch).unwrap(),
+            traces_from(expected.produce().unwrap()).unwrap()
+        );
+
+        // the logs are produced once they reach the maximum age
+        assert_eq!(batcher.deadline(), Some(later + Duration::from_millis(100)));
+        assert!(
+            batcher
+                .poll(start + Duration::from_millis(100))
+                .unwrap()
+                .is_empty()
+        );
+        let batches = batcher.poll(later + Duration::from_millis(100)).unwrap();
+        assert_eq!(batches.len(), 1);
+        assert!(matches!(batches[0], OtapBatch::Logs(_)));
+        assert!(batcher.is_empty());
+
+        let _ = batcher.push(ExportRequest::Logs(logs), later).unwrap();
+        assert_eq!(batcher.flush().unwrap().len(), 1);
+        assert!(batcher.flush().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,427 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Detection of the attribute keys whose number of distinct values explodes, e.g. a metric
+//! label carrying a request id, so the agents catch them before they reach the backends.
+//!
+//! A [`CardinalityTracker`] estimates the distinct values of every attribute key of every
+//! attributes payload, e.g. the `http.route` of the span attributes, with a HyperLogLog
+//! sketch of about 1 KiB per key, "HyperLogLog: the analysis of a near-optimal cardinality
+//! estimation algorithm" (Flajolet, Fusy, Gandouet, Meunier), whose estimates are within a
+//! few percent. The estimates cover tumbling windows: the first time a key goes over the
+//! threshold in a window, the [`CardinalityHook`] of the tracker fires, and the sketches
+//! restart with the next window.
+//!
+//! The producers set with a tracker, e.g. [`TracesProducer::with_cardinality_tracker`],
+//! feed it the attributes of the resources, scopes, records, span events and links and
+//! data points of the requests they encode. A tracker sketches at most
+//! [`MAX_TRACKED_KEYS`] keys per window, the keys seen after them are not tracked.
+//!
+//! [`TracesProducer::with_cardinality_tracker`]:
+//! crate::encode::TracesProducer::with_cardinality_tracker
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{InstrumentationScope, KeyValue};
+use crate::proto::opentelemetry::metrics::v1::metric::Data;
+use crate::proto::opentelemetry::resource::v1::Resource;
+
+/// Largest number of keys a tracker sketches per window.
+pub const MAX_TRACKED_KEYS: usize = 1024;
+
+/// Bits of the hashes selecting the register of a sketch.
+const PRECISION: u32 = 10;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// An attribute key over the cardinality threshold.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CardinalityAlert {
+    /// Attributes payload of the key, e.g. the span attributes.
+    pub payload_type: ArrowPayloadType,
+    /// The attribute key.
+    pub key: String,
+    /// Estimated number of distinct values of the key in the window.
+    pub estimate: u64,
+    /// The threshold of the tracker.
+    pub threshold: u64,
+}
+
+impl fmt::Display for CardinalityAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "attribute {} of {} has about {} distinct values, over {}",
+            self.key,
+            self.payload_type.as_str_name(),
+            self.estimate,
+            self.threshold
+        )
+    }
+}
+
+/// Receives the alerts of a [`CardinalityTracker`]. It is implemented for closures taking
+/// the alerts.
+pub trait CardinalityHook: Send + Sync {
+    /// Called the first time a key goes over the threshold in a window.
+    fn on_exceeded(&self, alert: &CardinalityAlert);
+}
+
+impl<F: Fn(&CardinalityAlert) + Send + Sync> CardinalityHook for F {
+    fn on_exceeded(&self, alert: &CardinalityAlert) {
+        self(alert)
+    }
+}
+
+/// HyperLogLog sketch of the values of a key.
+struct Sketch {
+    registers: Box<[u8; REGISTERS]>,
+}
+
+impl Sketch {
+    fn new() -> Self {
+        Self {
+            registers: Box::new([0; REGISTERS]),
+        }
+    }
+
+    /// Adds the hash of a value, returning whether the sketch changed.
+    fn insert(&mut self, hash: u64) -> bool {
+        let idx = (hash >> (64 - PRECISION)) as usize;
+        let rank = ((hash << PRECISION) | (1 << (PRECISION - 1))).leading_zeros() as u8 + 1;
+        let register = &mut self.registers[idx];
+        let changed = rank > *register;
+        *register = (*register).max(rank);
+        changed
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|r| 2f64.powi(-i32::from(*r)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // linear counting, more accurate for the small cardinalities
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+#[derive(Default)]
+struct Window {
+    start: Option<Instant>,
+    keys: HashMap<(ArrowPayloadType, String), (Sketch, bool)>,
+}
+
+/// Estimates the cardinality of the attribute keys, see the module documentation.
+pub struct CardinalityTracker {
+    threshold: u64,
+    window: Duration,
+    hook: Box<dyn CardinalityHook>,
+    state: Mutex<Window>,
+}
+
+impl fmt::Debug for CardinalityTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CardinalityTracker")
+            .field("threshold", &self.threshold)
+            .field("window", &self.window)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CardinalityTracker {
+    /// Creates a tracker calling `hook` when a key has more than `threshold` distinct
+    /// values within a window of the given duration.
+    pub fn new(threshold: u64, window: Duration, hook: impl CardinalityHook + 'static) -> Self {
+        Self {
+            threshold,
+            window,
+            hook: Box::new(hook),
+            state: Mutex::new(Window::default()),
+        }
+    }
+
+    /// Returns the estimated number of distinct values of the key in the current window,
+    /// `None` if the key is not tracked.
+    #[must_use]
+    pub fn estimate(&self, payload_type: ArrowPayloadType, key: &str) -> Option<u64> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .keys
+            .get(&(payload_type, key.to_owned()))
+            .map(|(sketch, _)| sketch.estimate())
+    }
+
+    /// Adds the attributes of an entity of the payload, observed at `now`.
+    pub fn observe(&self, payload_type: ArrowPayloadType, attrs: &[KeyValue], now: Instant) {
+        self.observe_with(now, |observer| observer.add(payload_type, attrs));
+    }
+
+    /// Adds the attributes of the logs request, observed at `now`.
+    pub fn observe_logs(&self, request: &ExportLogsServiceRequest, now: Instant) {
+        self.observe_with(now, |observer| {
+            for rl in &request.resource_logs {
+                observer.add_resource(rl.resource.as_ref());
+                for sl in &rl.scope_logs {
+                    observer.add_scope(sl.scope.as_ref());
+                    for log in &sl.log_records {
+                        observer.add(ArrowPayloadType::LogAttrs, &log.attributes);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Adds the attributes of the traces request, observed at `now`.
+    pub fn observe_traces(&self, request: &ExportTraceServiceRequest, now: Instant) {
+        self.observe_with(now, |observer| {
+            for rs in &request.resource_spans {
+                observer.add_resource(rs.resource.as_ref());
+                for ss in &rs.scope_spans {
+                    observer.add_scope(ss.scope.as_ref());
+                    for span in &ss.spans {
+                        observer.add(ArrowPayloadType::SpanAttrs, &span.attributes);
+                        for event in &span.events {
+                            observer.add(ArrowPayloadType::SpanEventAttrs, &event.attributes);
+                        }
+                        for link in &span.links {
+                            observer.add(ArrowPayloadType::SpanLinkAttrs, &link.attributes);
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Adds the attributes of the metrics request, observed at `now`.
+    pub fn observe_metrics(&self, request: &ExportMetricsServiceRequest, now: Instant) {
+        self.observe_with(now, |observer| {
+            for rm in &request.resource_metrics {
+                observer.add_resource(rm.resource.as_ref());
+                for sm in &rm.scope_metrics {
+                    observer.add_scope(sm.scope.as_ref());
+                    let metrics = sm.metrics.iter().filter_map(|m| m.data.as_ref());
+                    for data in metrics {
+                        match data {
+                            Data::Gauge(g) => observer.add_all(
+                                ArrowPayloadType::NumberDpAttrs,
+                                g.data_points.iter().map(|dp| &dp.attributes),
+                            ),
+                            Data::Sum(s) => observer.add_all(
+                                ArrowPayloadType::NumberDpAttrs,
+                                s.data_points.iter().map(|dp| &dp.attributes),
+                            ),
+                            Data::Histogram(h) => observer.add_all(
+                                ArrowPayloadType::HistogramDpAttrs,
+                                h.data_points.iter().map(|dp| &dp.attributes),
+                            ),
+                            Data::ExponentialHistogram(h) => observer.add_all(
+                                ArrowPayloadType::ExpHistogramDpAttrs,
+                                h.data_points.iter().map(|dp| &dp.attributes),
+                            ),
+                            Data::Summary(s) => observer.add_all(
+                                ArrowPayloadType::SummaryDpAttrs,
+                                s.data_points.iter().map(|dp| &dp.attributes),
+                            ),
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Runs `observe` on the window of `now`, then calls the hook with the alerts raised.
+    fn observe_with(&self, now: Instant, observe: impl FnOnce(&mut Observer<'_>)) {
+        let alerts = {
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            if state
+                .start
+                .is_none_or(|start| now.saturating_duration_since(start) >= self.window)
+            {
+                state.start = Some(now);
+                state.keys.clear();
+            }
+            let mut observer = Observer {
+                threshold: self.threshold,
+                window: &mut state,
+                alerts: Vec::new(),
+            };
+            observe(&mut observer);
+            observer.alerts
+        };
+        for alert in &alerts {
+            self.hook.on_exceeded(alert);
+        }
+    }
+}
+
+/// Adds the attributes of a request to the sketches of a window.
+struct Observer<'a> {
+    threshold: u64,
+    window: &'a mut Window,
+    alerts: Vec<CardinalityAlert>,
+}
+
+impl Observer<'_> {
+    fn add_resource(&mut self, resource: Option<&Resource>) {
+        if let Some(resource) = resource {
+            self.add(ArrowPayloadType::ResourceAttrs, &resource.attributes);
+        }
+    }
+
+    fn add_scope(&mut self, scope: Option<&InstrumentationScope>) {
+        if let Some(scope) = scope {
+            self.add(ArrowPayloadType::ScopeAttrs, &scope.attributes);
+        }
+    }
+
+    fn add_all<'b>(
+        &mut self,
+        payload_type: ArrowPayloadType,
+        attrs: impl Iterator<Item = &'b Vec<KeyValue>>,
+    ) {
+        for attrs in attrs {
+            self.add(payload_type, attrs);
+        }
+    }
+
+    fn add(&mut self, payload_type: ArrowPayloadType, attrs: &[KeyValue]) {
+        for kv in attrs {
+            let Some(value) = kv.value.as_ref().and_then(|v| v.value.as_ref()) else {
+                continue;
+            };
+            let key = (payload_type, kv.key.clone());
+            if self.window.keys.len() >= MAX_TRACKED_KEYS && !self.window.keys.contains_key(&key) {
+                continue;
+            }
+            let (sketch, alerted) = self
+                .window
+                .keys
+                .entry(key)
+                .or_insert_with(|| (Sketch::new(), false));
+            let mut hasher = DefaultHasher::new();
+            hash_value(value, &mut hasher);
+            if !sketch.insert(hasher.finish()) || *alerted {
+                continue;
+            }
+            let estimate = sketch.estimate();
+            if estimate > self.threshold {
+                *alerted = true;
+                self.alerts.push(CardinalityAlert {
+                    payload_type,
+                    key: kv.key.clone(),
+                    estimate,
+                    threshold: self.threshold,
+                });
+            }
+        }
+    }
+}
+
+/// Hashes the value, the values of different types never being equal.
+fn hash_value(value: &Value, hasher: &mut impl Hasher) {
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        Value::StringValue(s) => s.hash(hasher),
+        Value::BoolValue(b) => b.hash(hasher),
+        Value::IntValue(i) => i.hash(hasher),
+        Value::DoubleValue(d) => d.to_bits().hash(hasher),
+        Value::BytesValue(b) => b.hash(hasher),
+        Value::ArrayValue(array) => {
+            for value in array.values.iter().filter_map(|v| v.value.as_ref()) {
+                hash_value(value, hasher);
+            }
+        }
+        Value::KvlistValue(list) => {
+            for kv in &list.values {
+                kv.key.hash(hasher);
+                if let Some(value) = kv.value.as_ref().and_then(|v| v.value.as_ref()) {
+                    hash_value(value, hasher);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::proto::opentelemetry::common::v1::AnyValue;
+
+    fn attr(key: &str, value: i64) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: Some(AnyValue {
+                value: Some(Value::IntValue(value)),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_cardinality_alerts() {
+        let alerts = Arc::new(Mutex::new(Vec::new()));
+        let received = alerts.clone();
+        let tracker = CardinalityTracker::new(
+            100,
+            Duration::from_secs(60),
+            move |alert: &CardinalityAlert| received.lock().unwrap().push(alert.clone()),
+        );
+        let start = Instant::now();
+        for value in 0..1000 {
+            tracker.observe(
+                ArrowPayloadType::SpanAttrs,
+                &[attr("request.id", value), attr("status", value % 3)],
+                start,
+            );
+        }
+
+        // the estimates are close to the distinct values
+        let estimate = tracker
+            .estimate(ArrowPayloadType::SpanAttrs, "request.id")
+            .unwrap();
+        assert!((900..1100).contains(&estimate), "{estimate}");
+        assert_eq!(
+            tracker.estimate(ArrowPayloadType::SpanAttrs, "status"),
+            Some(3)
+        );
+        assert_eq!(tracker.estimate(ArrowPayloadType::LogAttrs, "status"), None);
+
+        // the key over the threshold is reported once per window
+        let received = std::mem::take(&mut *alerts.lock().unwrap());
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].key, "request.id");
+        assert_eq!(received[0].payload_type, ArrowPayloadType::SpanAttrs);
+        assert!(received[0].estimate > 100);
+
+        let next_window = start + Duration::from_secs(60);
+        tracker.observe(
+            ArrowPayloadType::SpanAttrs,
+            &[attr("request.id", 0)],
+            next_window,
+        );
+        assert_eq!(
+            tracker.estimate(ArrowPayloadType::SpanAttrs, "request.id"),
+            Some(1)
+        );
+        assert!(alerts.lock().unwrap().is_empty());
+    }
+}
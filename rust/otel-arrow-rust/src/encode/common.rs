@@ -0,0 +1,315 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Production of the `resource` and `scope` struct columns shared by all the signals.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, RecordBatch, StringArray, UInt32Array};
+use arrow::datatypes::UInt16Type;
+use snafu::OptionExt;
+
+use crate::encode::attributes::Attributes16Accumulator;
+use crate::encode::record::{
+    Columns, DictionaryKey, delta_encode, dictionary, non_empty, non_zero,
+};
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{InstrumentationScope, KeyValue};
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::schema::consts;
+
+/// Returns the key identifying a resource, same as the `ResourceID` of the Go
+/// implementation. The resources with the same key are encoded once.
+pub(crate) fn resource_key(resource: Option<&Resource>, schema_url: &str) -> String {
+    let mut key = String::new();
+    write_attributes_key(resource.map_or(&[], |r| &r.attributes), &mut key);
+    let dropped_attributes_count = resource.map_or(0, |r| r.dropped_attributes_count);
+    let _ = write!(key, "|{dropped_attributes_count}|{schema_url}");
+    key
+}
+
+/// Returns the key identifying a scope, same as the `ScopeID` of the Go implementation.
+pub(crate) fn scope_key(scope: Option<&InstrumentationScope>, schema_url: &str) -> String {
+    let mut key = String::new();
+    let _ = write!(
+        key,
+        "name:{}|version:{}|",
+        scope.map_or("", |s| &s.name),
+        scope.map_or("", |s| &s.version)
+    );
+    write_attributes_key(scope.map_or(&[], |s| &s.attributes), &mut key);
+    let dropped_attributes_count = scope.map_or(0, |s| s.dropped_attributes_count);
+    let _ = write!(key, "|{dropped_attributes_count}|{schema_url}");
+    key
+}
+
+fn write_attributes_key(attrs: &[KeyValue], key: &mut String) {
+    let mut attrs: Vec<_> = attrs.iter().collect();
+    attrs.sort_by(|a, b| a.key.cmp(&b.key));
+
+    key.push('{');
+    for (i, kv) in attrs.iter().enumerate() {
+        if i > 0 {
+            key.push(',');
+        }
+        key.push_str(&kv.key);
+        key.push(':');
+        write_value_key(kv.value.as_ref().and_then(|v| v.value.as_ref()), key);
+    }
+    key.push('}');
+}
+
+fn write_value_key(value: Option<&Value>, key: &mut String) {
+    match value {
+        None => {}
+        Some(Value::StringValue(s)) => key.push_str(s),
+        Some(Value::IntValue(i)) => {
+            let _ = write!(key, "{i}");
+        }
+        Some(Value::DoubleValue(d)) => key.push_str(&format_double(*d)),
+        Some(Value::BoolValue(b)) => {
+            let _ = write!(key, "{b}");
+        }
+        Some(Value::BytesValue(bytes)) => {
+            for b in bytes {
+                let _ = write!(key, "{b:02x}");
+            }
+        }
+        Some(Value::KvlistValue(kvs)) => write_attributes_key(&kvs.values, key),
+        Some(Value::ArrayValue(array)) => {
+            key.push('[');
+            for (i, element) in array.values.iter().enumerate() {
+                if i > 0 {
+                    key.push(',');
+                }
+                write_value_key(element.value.as_ref(), key);
+            }
+            key.push(']');
+        }
+    }
+}
+
+/// Formats a double like `strconv.FormatFloat(d, 'E', -1, 64)` in Go, e.g. `1.5E+00`.
+fn format_double(d: f64) -> String {
+    if d.is_nan() {
+        return "NaN".to_string();
+    }
+    if d.is_infinite() {
+        return if d > 0.0 { "+Inf" } else { "-Inf" }.to_string();
+    }
+    let formatted = format!("{d:E}");
+    let (mantissa, exponent) = formatted
+        .split_once('E')
+        .unwrap_or((formatted.as_str(), "0"));
+    let exponent: i32 = exponent.parse().unwrap_or_default();
+    let sign = if exponent < 0 { '-' } else { '+' };
+    format!("{mantissa}E{sign}{:02}", exponent.abs())
+}
+
+/// Returns the id following `prev`, or 0 for the first id.
+pub(crate) fn next_id(prev: Option<u16>, name: &'static str) -> Result<u16> {
+    match prev {
+        None => Ok(0),
+        Some(prev) => prev.checked_add(1).context(error::IdOverflowSnafu { name }),
+    }
+}
+
+/// Assigns the ids of the resources and scopes of the rows of a payload, sorted by resource
+/// and scope keys, and builds their columns and attributes. Consecutive rows with the same
+/// key share the same resource or scope.
+#[derive(Default)]
+pub(crate) struct ResourceScopeBuilder<'a> {
+    prev_resource_key: Option<&'a str>,
+    prev_scope_key: Option<&'a str>,
+    resource_id: u16,
+    scope_id: u16,
+    resources: ResourceColumnBuilder<'a>,
+    scopes: ScopeColumnBuilder<'a>,
+    resource_attrs: Attributes16Accumulator<'a>,
+    scope_attrs: Attributes16Accumulator<'a>,
+}
+
+/// The `resource` and `scope` columns of a payload, with the resource and scope attributes
+/// record batches.
+pub(crate) struct ResourceScopeColumns {
+    pub(crate) resource: ArrayRef,
+    pub(crate) scope: ArrayRef,
+    pub(crate) resource_attrs: Option<RecordBatch>,
+    pub(crate) scope_attrs: Option<RecordBatch>,
+}
+
+impl<'a> ResourceScopeBuilder<'a> {
+    /// Adds the resource and scope of the next row.
+    pub(crate) fn append(
+        &mut self,
+        resource_key: &'a str,
+        resource: Option<&'a Resource>,
+        resource_schema_url: &'a str,
+        scope_key: &'a str,
+        scope: Option<&'a InstrumentationScope>,
+    ) -> Result<()> {
+        if self.prev_resource_key != Some(resource_key) {
+            if self.prev_resource_key.is_some() {
+                self.resource_id = next_id(Some(self.resource_id), "resources")?;
+            }
+            self.prev_resource_key = Some(resource_key);
+            if let Some(resource) = resource {
+                self.resource_attrs
+                    .append(self.resource_id, &resource.attributes);
+            }
+        }
+        self.resources
+            .append(self.resource_id, resource, resource_schema_url);
+
+        if self.prev_scope_key != Some(scope_key) {
+            if self.prev_scope_key.is_some() {
+                self.scope_id = next_id(Some(self.scope_id), "scopes")?;
+            }
+            self.prev_scope_key = Some(scope_key);
+            if let Some(scope) = scope {
+                self.scope_attrs.append(self.scope_id, &scope.attributes);
+            }
+        }
+        self.scopes.append(self.scope_id, scope);
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<ResourceScopeColumns> {
+        Ok(ResourceScopeColumns {
+            resource: self.resources.finish()?,
+            scope: self.scopes.finish()?,
+            resource_attrs: self.resource_attrs.finish()?,
+            scope_attrs: self.scope_attrs.finish()?,
+        })
+    }
+}
+
+/// Builds the `resource` struct column of a payload.
+#[derive(Default)]
+struct ResourceColumnBuilder<'a> {
+    ids: Vec<Option<u16>>,
+    schema_urls: Vec<Option<&'a str>>,
+    dropped_attributes_counts: Vec<Option<u32>>,
+}
+
+impl<'a> ResourceColumnBuilder<'a> {
+    fn append(&mut self, id: u16, resource: Option<&Resource>, schema_url: &'a str) {
+        self.ids.push(Some(id));
+        self.schema_urls.push(non_empty(schema_url));
+        self.dropped_attributes_counts
+            .push(resource.and_then(|r| non_zero(r.dropped_attributes_count)));
+    }
+
+    fn finish(self) -> Result<ArrayRef> {
+        let len = self.ids.len();
+        let mut columns = Columns::default();
+        columns.required(consts::ID, Arc::new(delta_encode::<UInt16Type>(&self.ids)));
+        columns.optional(
+            consts::SCHEMA_URL,
+            dictionary(
+                Arc::new(StringArray::from(self.schema_urls)),
+                DictionaryKey::U8,
+            ),
+        );
+        columns.optional(
+            consts::DROPPED_ATTRIBUTES_COUNT,
+            Arc::new(UInt32Array::from(self.dropped_attributes_counts)),
+        );
+        columns.into_struct_array(len, None)
+    }
+}
+
+/// Builds the `scope` struct column of a payload.
+#[derive(Default)]
+struct ScopeColumnBuilder<'a> {
+    ids: Vec<Option<u16>>,
+    names: Vec<Option<&'a str>>,
+    versions: Vec<Option<&'a str>>,
+    dropped_attributes_counts: Vec<Option<u32>>,
+}
+
+impl<'a> ScopeColumnBuilder<'a> {
+    fn append(&mut self, id: u16, scope: Option<&'a InstrumentationScope>) {
+        self.ids.push(Some(id));
+        self.names.push(scope.and_then(|s| non_empty(&s.name)));
+        self.versions
+            .push(scope.and_then(|s| non_empty(&s.version)));
+        self.dropped_attributes_counts
+            .push(scope.and_then(|s| non_zero(s.dropped_attributes_count)));
+    }
+
+    fn finish(self) -> Result<ArrayRef> {
+        let len = self.ids.len();
+        let mut columns = Columns::default();
+        columns.required(consts::ID, Arc::new(delta_encode::<UInt16Type>(&self.ids)));
+        columns.optional(
+            consts::NAME,
+            dictionary(Arc::new(StringArray::from(self.names)), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::VERSION,
+            dictionary(
+                Arc::new(StringArray::from(self.versions)),
+                DictionaryKey::U8,
+            ),
+        );
+        columns.optional(
+            consts::DROPPED_ATTRIBUTES_COUNT,
+            Arc::new(UInt32Array::from(self.dropped_attributes_counts)),
+        );
+        columns.into_struct_array(len, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::common::v1::{AnyValue, ArrayValue};
+
+    #[test]
+    fn test_resource_key() {
+        let attr = |key: &str, value: Value| KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        };
+        let resource = Resource {
+            attributes: vec![
+                attr("b", Value::DoubleValue(1.5)),
+                attr("a", Value::StringValue("x".into())),
+                attr(
+                    "c",
+                    Value::ArrayValue(ArrayValue {
+                        values: vec![
+                            AnyValue {
+                                value: Some(Value::BytesValue(vec![0xab, 0x01])),
+                            },
+                            AnyValue {
+                                value: Some(Value::DoubleValue(-2.5e-7)),
+                            },
+                        ],
+                    }),
+                ),
+            ],
+            dropped_attributes_count: 2,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            resource_key(Some(&resource), "url"),
+            "{a:x,b:1.5E+00,c:[ab01,-2.5E-07]}|2|url"
+        );
+        assert_eq!(
+            scope_key(
+                Some(&InstrumentationScope {
+                    name: "lib".into(),
+                    ..Default::default()
+                }),
+                ""
+            ),
+            "name:lib|version:|{}|0|"
+        );
+    }
+}
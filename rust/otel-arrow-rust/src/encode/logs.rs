@@ -0,0 +1,447 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Production of the OTAP logs batches.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use arrow::array::{ArrayRef, Int32Array, StringArray, TimestampNanosecondArray, UInt32Array};
+use arrow::buffer::NullBuffer;
+use arrow::datatypes::UInt16Type;
+use snafu::{OptionExt, ensure};
+
+use crate::cancel::{CancellationToken, check_cancelled};
+use crate::encode::attributes::{Attributes16Accumulator, ValueColumns};
+use crate::encode::cardinality::CardinalityTracker;
+use crate::encode::common::{ResourceScopeBuilder, resource_key, scope_key};
+use crate::encode::record::{
+    Columns, DictionaryKey, delta_encode, dictionary, fixed_size_binary, non_empty,
+    non_empty_bytes, non_zero,
+};
+use crate::encode::sorter::LogSorter;
+use crate::error::{self, Result};
+use crate::memory::{MemoryPool, reserve};
+use crate::otap::{Logs, OtapBatch};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::common::v1::AnyValue;
+use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+use crate::schema::consts;
+
+const TRACE_ID_LEN: i32 = 16;
+const SPAN_ID_LEN: i32 = 8;
+
+/// Produces the OTAP batches of logs requests.
+///
+/// The batches are produced the way the Go producer does with its default configuration:
+/// - log records are sorted by resource, scope, then time unless another
+///   [`LogSorter`] is set, the resources and scopes that are
+///   equal are encoded once,
+/// - attributes are sorted by type, key, value and parent id, the parent ids of
+///   consecutive attributes with the same key and value are delta encoded,
+/// - the ids of resources, scopes and log records are delta encoded, and the log records
+///   without attributes have no id,
+/// - the bodies are stored in a struct with one column per value type, the maps and slices
+///   being CBOR serialized.
+///
+/// Zero numbers and empty strings are written as nulls, and the columns only holding nulls
+/// are left out of the batches.
+#[derive(Debug, Default)]
+pub struct LogsProducer {
+    sorter: LogSorter,
+    cancellation: Option<CancellationToken>,
+    memory_pool: Option<Arc<dyn MemoryPool>>,
+    cardinality_tracker: Option<Arc<CardinalityTracker>>,
+}
+
+/// A log record along with the resource and scope it belongs to.
+struct FlattenedLogRecord<'a> {
+    resource_key: &'a str,
+    resource_logs: &'a ResourceLogs,
+    scope_key: &'a str,
+    scope_logs: &'a ScopeLogs,
+    log_record: &'a LogRecord,
+}
+
+impl LogsProducer {
+    /// Creates a new producer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a producer sorting the log records of each scope with the given sorter.
+    #[must_use]
+    pub fn with_sorter(sorter: LogSorter) -> Self {
+        Self {
+            sorter,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the token cancelling the production of the batches, checked between log records.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets the pool the memory of the batches is reserved from, see [`crate::memory`].
+    #[must_use]
+    pub fn with_memory_pool(mut self, pool: Arc<dyn MemoryPool>) -> Self {
+        self.memory_pool = Some(pool);
+        self
+    }
+
+    /// Sets the tracker fed the attributes of the requests, see [`crate::encode::cardinality`].
+    #[must_use]
+    pub fn with_cardinality_tracker(mut self, tracker: Arc<CardinalityTracker>) -> Self {
+        self.cardinality_tracker = Some(tracker);
+        self
+    }
+
+    /// Produces the OTAP batch of the request.
+    pub fn produce(&self, request: &ExportLogsServiceRequest) -> Result<OtapBatch> {
+        if let Some(tracker) = &self.cardinality_tracker {
+            tracker.observe_logs(request, Instant::now());
+        }
+        let keys: Vec<(String, Vec<String>)> = request
+            .resource_logs
+            .iter()
+            .map(|resource_logs| {
+                let scope_keys = resource_logs
+                    .scope_logs
+                    .iter()
+                    .map(|scope_logs| scope_key(scope_logs.scope.as_ref(), &scope_logs.schema_url))
+                    .collect();
+                (
+                    resource_key(resource_logs.resource.as_ref(), &resource_logs.schema_url),
+                    scope_keys,
+                )
+            })
+            .collect();
+
+        let mut log_records = Vec::new();
+        for (resource_logs, (resource_key, scope_keys)) in request.resource_logs.iter().zip(&keys) {
+            for (scope_logs, scope_key) in resource_logs.scope_logs.iter().zip(scope_keys) {
+                log_records.extend(scope_logs.log_records.iter().map(|log_record| {
+                    FlattenedLogRecord {
+                        resource_key,
+                        resource_logs,
+                        scope_key,
+                        scope_logs,
+                        log_record,
+                    }
+                }));
+            }
+        }
+        log_records.sort_by(|a, b| {
+            a.resource_key
+                .cmp(b.resource_key)
+                .then_with(|| a.scope_key.cmp(b.scope_key))
+                .then_with(|| self.sorter.compare(a.log_record, b.log_record))
+        });
+
+        let mut resources_scopes = ResourceScopeBuilder::default();
+        let mut log_attrs = Attributes16Accumulator::default();
+        let mut next_log_id = 0u32;
+
+        let len = log_records.len();
+        let mut ids = Vec::with_capacity(len);
+        let mut schema_urls = Vec::with_capacity(len);
+        let mut times = Vec::with_capacity(len);
+        let mut observed_times = Vec::with_capacity(len);
+        let mut trace_ids = Vec::with_capacity(len);
+        let mut span_ids = Vec::with_capacity(len);
+        let mut severity_numbers = Vec::with_capacity(len);
+        let mut severity_texts = Vec::with_capacity(len);
+        let mut event_names = Vec::with_capacity(len);
+        let mut bodies = Vec::with_capacity(len);
+        let mut dropped_attributes_counts = Vec::with_capacity(len);
+        let mut flags = Vec::with_capacity(len);
+
+        for (idx, flattened) in log_records.iter().enumerate() {
+            check_cancelled(self.cancellation.as_ref())?;
+            let log_record = flattened.log_record;
+
+            resources_scopes.append(
+                flattened.resource_key,
+                flattened.resource_logs.resource.as_ref(),
+                &flattened.resource_logs.schema_url,
+                flattened.scope_key,
+                flattened.scope_logs.scope.as_ref(),
+            )?;
+            schema_urls.push(non_empty(&flattened.scope_logs.schema_url));
+
+            if log_record.attributes.is_empty() {
+                ids.push(None);
+            } else {
+                let id = u16::try_from(next_log_id)
+                    .ok()
+                    .context(error::IdOverflowSnafu {
+                        name: "log records",
+                    })?;
+                next_log_id += 1;
+                ids.push(Some(id));
+                log_attrs.append(id, &log_record.attributes);
+            }
+
+            ensure!(
+                log_record.trace_id.is_empty()
+                    || log_record.trace_id.len() == TRACE_ID_LEN as usize,
+                error::InvalidTraceIdSnafu {
+                    message: format!("index = {}, trace_id = {:?}", idx, log_record.trace_id),
+                }
+            );
+            ensure!(
+                log_record.span_id.is_empty() || log_record.span_id.len() == SPAN_ID_LEN as usize,
+                error::InvalidSpanIdSnafu {
+                    message: format!("index = {}, span_id = {:?}", idx, log_record.span_id),
+                }
+            );
+
+            times.push(non_zero(log_record.time_unix_nano as i64));
+            observed_times.push(non_zero(log_record.observed_time_unix_nano as i64));
+            trace_ids.push(non_empty_bytes(&log_record.trace_id));
+            span_ids.push(non_empty_bytes(&log_record.span_id));
+            severity_numbers.push(non_zero(log_record.severity_number));
+            severity_texts.push(non_empty(&log_record.severity_text));
+            event_names.push(non_empty(&log_record.event_name));
+            bodies.push(log_record.body.as_ref());
+            dropped_attributes_counts.push(non_zero(log_record.dropped_attributes_count));
+            flags.push(non_zero(log_record.flags));
+        }
+
+        let resources_scopes = resources_scopes.finish()?;
+        let mut columns = Columns::default();
+        // the decoder requires the id column, even when it only holds nulls
+        columns.required(consts::ID, Arc::new(delta_encode::<UInt16Type>(&ids)));
+        columns.required(consts::RESOURCE, resources_scopes.resource);
+        columns.required(consts::SCOPE, resources_scopes.scope);
+        columns.optional(
+            consts::SCHEMA_URL,
+            dictionary(Arc::new(StringArray::from(schema_urls)), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::TIME_UNIX_NANO,
+            Arc::new(TimestampNanosecondArray::from(times)),
+        );
+        columns.optional(
+            consts::OBSERVED_TIME_UNIX_NANO,
+            Arc::new(TimestampNanosecondArray::from(observed_times)),
+        );
+        columns.optional(
+            consts::TRACE_ID,
+            dictionary(
+                fixed_size_binary(trace_ids, TRACE_ID_LEN),
+                DictionaryKey::U8,
+            ),
+        );
+        columns.optional(
+            consts::SPAN_ID,
+            dictionary(fixed_size_binary(span_ids, SPAN_ID_LEN), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::SEVERITY_NUMBER,
+            dictionary(
+                Arc::new(Int32Array::from(severity_numbers)),
+                DictionaryKey::U8,
+            ),
+        );
+        columns.optional(
+            consts::SEVERITY_TEXT,
+            dictionary(
+                Arc::new(StringArray::from(severity_texts)),
+                DictionaryKey::U8,
+            ),
+        );
+        columns.optional(
+            consts::EVENT_NAME,
+            dictionary(Arc::new(StringArray::from(event_names)), DictionaryKey::U8),
+        );
+        columns.optional(consts::BODY, body_column(&bodies)?);
+        columns.optional(
+            consts::DROPPED_ATTRIBUTES_COUNT,
+            Arc::new(UInt32Array::from(dropped_attributes_counts)),
+        );
+        columns.optional(consts::FLAGS, Arc::new(UInt32Array::from(flags)));
+
+        let mut otap_batch = OtapBatch::Logs(Logs::default());
+        otap_batch.set(ArrowPayloadType::Logs, columns.into_record_batch()?);
+        let related = [
+            (
+                ArrowPayloadType::ResourceAttrs,
+                resources_scopes.resource_attrs,
+            ),
+            (ArrowPayloadType::ScopeAttrs, resources_scopes.scope_attrs),
+            (ArrowPayloadType::LogAttrs, log_attrs.finish()?),
+        ];
+        for (payload_type, record_batch) in related {
+            if let Some(record_batch) = record_batch {
+                otap_batch.set(payload_type, record_batch);
+            }
+        }
+
+        let reservation = reserve(self.memory_pool.as_ref(), otap_batch.memory_size())?;
+        otap_batch.set_reservation(reservation);
+        Ok(otap_batch)
+    }
+}
+
+/// Returns the `body` struct column, the bodies without value are null.
+fn body_column(bodies: &[Option<&AnyValue>]) -> Result<ArrayRef> {
+    let mut values = ValueColumns::default();
+    for body in bodies {
+        values.append(body.and_then(|body| body.value.as_ref()));
+    }
+    let mut columns = Columns::default();
+    values.finish(&mut columns, false);
+    columns.into_struct_array(
+        bodies.len(),
+        Some(NullBuffer::from_iter(
+            bodies
+                .iter()
+                .map(|body| body.is_some_and(|b| b.value.is_some())),
+        )),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otlp::logs::{logs_from, logs_from_with_options};
+    use crate::otlp::options::DecoderOptions;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{ArrayValue, InstrumentationScope, KeyValue};
+    use crate::proto::opentelemetry::resource::v1::Resource;
+
+    fn attr(key: &str, value: Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn log_record(time_unix_nano: u64, body: Option<Value>) -> LogRecord {
+        LogRecord {
+            time_unix_nano,
+            observed_time_unix_nano: time_unix_nano + 1,
+            body: body.map(|value| AnyValue { value: Some(value) }),
+            ..Default::default()
+        }
+    }
+
+    /// Request already in the order of the produced batch, so it decodes back to itself.
+    fn request() -> ExportLogsServiceRequest {
+        let mut a = log_record(1, Some(Value::StringValue("started".into())));
+        a.attributes = vec![
+            attr("user", Value::StringValue("alice".into())),
+            attr("code", Value::IntValue(7)),
+        ];
+        a.severity_number = 9;
+        a.severity_text = "INFO".into();
+        a.event_name = "session.start".into();
+        a.trace_id = vec![1; 16];
+        a.span_id = vec![2; 8];
+        a.flags = 1;
+
+        // no attributes, it must not take the ones of the previous record
+        let b = log_record(
+            2,
+            Some(Value::ArrayValue(ArrayValue {
+                values: vec![AnyValue {
+                    value: Some(Value::IntValue(-1)),
+                }],
+            })),
+        );
+
+        let mut c = log_record(3, None);
+        c.attributes = vec![attr("user", Value::StringValue("alice".into()))];
+        c.dropped_attributes_count = 2;
+
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![attr("service.name", Value::StringValue("svc".into()))],
+                    ..Default::default()
+                }),
+                scope_logs: vec![
+                    ScopeLogs {
+                        scope: Some(InstrumentationScope {
+                            name: "a".into(),
+                            attributes: vec![attr("sdk", Value::StringValue("rust".into()))],
+                            dropped_attributes_count: 1,
+                            ..Default::default()
+                        }),
+                        log_records: vec![a, b],
+                        schema_url: "https://scope".into(),
+                    },
+                    ScopeLogs {
+                        scope: Some(InstrumentationScope {
+                            name: "b".into(),
+                            ..Default::default()
+                        }),
+                        log_records: vec![c, log_record(4, Some(Value::DoubleValue(0.5)))],
+                        ..Default::default()
+                    },
+                ],
+                schema_url: "https://resource".into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_produce_round_trip() {
+        let request = request();
+        let otap_batch = LogsProducer::new().produce(&request).unwrap();
+        assert_eq!(otap_batch.payload_types(), vec![
+            ArrowPayloadType::Logs,
+            ArrowPayloadType::ResourceAttrs,
+            ArrowPayloadType::ScopeAttrs,
+            ArrowPayloadType::LogAttrs,
+        ]);
+
+        let decoded = logs_from(otap_batch).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn test_produce_sorted_by_time() {
+        let mut request = request();
+        request.resource_logs[0].scope_logs[0].log_records.reverse();
+        let otap_batch = LogsProducer::new().produce(&request).unwrap();
+
+        let logs = otap_batch.get(ArrowPayloadType::Logs).unwrap();
+        assert_eq!(
+            logs.column_by_name(consts::ID).unwrap().as_ref(),
+            &arrow::array::UInt16Array::from(vec![Some(0), None, Some(1), None])
+                as &dyn arrow::array::Array
+        );
+
+        let decoded = logs_from(otap_batch).unwrap();
+        assert_eq!(decoded, self::request());
+    }
+
+    #[test]
+    fn test_derive_severity_text() {
+        let mut request = request();
+        let records = &mut request.resource_logs[0].scope_logs[0].log_records;
+        records[0].severity_text.clear();
+        records[1].severity_number = 13;
+        records[1].severity_text = "warning".into();
+        let otap_batch = LogsProducer::new().produce(&request).unwrap();
+
+        let options = DecoderOptions::default().with_derive_severity_text(true);
+        let decoded = logs_from_with_options(otap_batch, &options).unwrap();
+        let texts: Vec<_> = decoded.resource_logs[0]
+            .scope_logs
+            .iter()
+            .flat_map(|s| &s.log_records)
+            .map(|r| r.severity_text.as_str())
+            .collect();
+        // the records without a severity number keep their empty text
+        assert_eq!(texts, vec!["INFO", "warning", "", ""]);
+    }
+}
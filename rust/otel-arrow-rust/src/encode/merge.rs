@@ -0,0 +1,192 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Merging of several OTLP requests or OTAP batches into one larger OTAP batch, e.g. by
+//! exporters buffering the batches they receive.
+//!
+//! The merged batch is produced from scratch: its ids are re-assigned and its parent ids
+//! re-delta encoded over all the merged rows, and the resources and scopes shared by the
+//! merged batches are only encoded once, which compresses better than the batches taken
+//! one by one.
+
+use crate::decode::decoder::{Consumer, ExportRequest};
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+
+/// Buffers the requests and batches of every signal until they are merged.
+///
+/// The `BatchArrowRecords` pushed to the merger are decoded by its own [`Consumer`], so
+/// they must be pushed in the order they were received on their stream.
+#[derive(Default)]
+pub struct BatchMerger {
+    consumer: Consumer,
+    logs: ExportLogsServiceRequest,
+    metrics: ExportMetricsServiceRequest,
+    traces: ExportTraceServiceRequest,
+}
+
+impl BatchMerger {
+    /// Creates an empty merger.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty merger decoding the pushed batches with the given consumer.
+    #[must_use]
+    pub fn with_consumer(consumer: Consumer) -> Self {
+        Self {
+            consumer,
+            ..Default::default()
+        }
+    }
+
+    /// Buffers a logs request.
+    pub fn push_logs(&mut self, request: ExportLogsServiceRequest) {
+        self.logs.resource_logs.extend(request.resource_logs);
+    }
+
+    /// Buffers a metrics request.
+    pub fn push_metrics(&mut self, request: ExportMetricsServiceRequest) {
+        self.metrics
+            .resource_metrics
+            .extend(request.resource_metrics);
+    }
+
+    /// Buffers a traces request.
+    pub fn push_traces(&mut self, request: ExportTraceServiceRequest) {
+        self.traces.resource_spans.extend(request.resource_spans);
+    }
+
+    /// Decodes a batch of any signal and buffers its request.
+    pub fn push_batch(&mut self, records: &mut BatchArrowRecords) -> Result<()> {
+        match self.consumer.consume_batches(records)? {
+            ExportRequest::Logs(request) => self.push_logs(request),
+            ExportRequest::Metrics(request) => self.push_metrics(request),
+            ExportRequest::Traces(request) => self.push_traces(request),
+        }
+        Ok(())
+    }
+
+    /// Returns whether no logs, metrics or traces are buffered.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.logs.resource_logs.is_empty()
+            && self.metrics.resource_metrics.is_empty()
+            && self.traces.resource_spans.is_empty()
+    }
+
+    /// Produces the batch of the buffered logs, or `None` if no logs are buffered, and
+    /// empties the logs buffer.
+    pub fn finish_logs(&mut self) -> Result<Option<OtapBatch>> {
+        let request = std::mem::take(&mut self.logs);
+        if request.resource_logs.is_empty() {
+            return Ok(None);
+        }
+        LogsProducer::new().produce(&request).map(Some)
+    }
+
+    /// Produces the batch of the buffered metrics, or `None` if no metrics are buffered,
+    /// and empties the metrics buffer.
+    pub fn finish_metrics(&mut self) -> Result<Option<OtapBatch>> {
+        let request = std::mem::take(&mut self.metrics);
+        if request.resource_metrics.is_empty() {
+            return Ok(None);
+        }
+        MetricsProducer::new().produce(&request).map(Some)
+    }
+
+    /// Produces the batch of the buffered traces, or `None` if no traces are buffered, and
+    /// empties the traces buffer.
+    pub fn finish_traces(&mut self) -> Result<Option<OtapBatch>> {
+        let request = std::mem::take(&mut self.traces);
+        if request.resource_spans.is_empty() {
+            return Ok(None);
+        }
+        TracesProducer::new().produce(&request).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrow::array::{Array, UInt16Array};
+
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::otlp::traces::traces_from;
+    use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+    use crate::proto::opentelemetry::resource::v1::Resource;
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+    use crate::schema::consts;
+
+    fn request(names: &[&str]) -> ExportTraceServiceRequest {
+        ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![KeyValue {
+                        key: "service.name".into(),
+                        value: Some(AnyValue {
+                            value: Some(Value::StringValue("svc".into())),
+                        }),
+                    }],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(InstrumentationScope::default()),
+                    spans: names
+                        .iter()
+                        .map(|name| Span {
+                            trace_id: vec![1; 16],
+                            span_id: vec![2; 8],
+                            name: (*name).into(),
+                            attributes: vec![KeyValue {
+                                key: "name".into(),
+                                value: Some(AnyValue {
+                                    value: Some(Value::StringValue((*name).into())),
+                                }),
+                            }],
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_merge_batches() {
+        let mut merger = BatchMerger::new();
+        for (batch_id, names) in [["c", "a"], ["b", "d"]].iter().enumerate() {
+            let otap_batch = TracesProducer::new().produce(&request(names)).unwrap();
+            let mut records = to_batch_arrow_records(&otap_batch, batch_id as i64).unwrap();
+            merger.push_batch(&mut records).unwrap();
+        }
+        assert!(!merger.is_empty());
+
+        let merged = merger.finish_traces().unwrap().unwrap();
+        assert!(merger.is_empty());
+        assert!(merger.finish_traces().unwrap().is_none());
+
+        // the ids are re-assigned across the spans of both batches
+        let spans = merged.get(ArrowPayloadType::Spans).unwrap();
+        assert_eq!(
+            spans.column_by_name(consts::ID).unwrap().as_ref(),
+            &UInt16Array::from(vec![0, 1, 1, 1]) as &dyn Array
+        );
+        // the shared resource is encoded once
+        let resource_attrs = merged.get(ArrowPayloadType::ResourceAttrs).unwrap();
+        assert_eq!(resource_attrs.num_rows(), 1);
+
+        assert_eq!(traces_from(merged).unwrap(), request(&["a", "b", "c", "d"]));
+    }
+}
@@ -0,0 +1,706 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Production of the OTAP metrics batches.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use arrow::array::{BooleanArray, Int32Array, StringArray, UInt8Array};
+use arrow::datatypes::UInt16Type;
+use snafu::OptionExt;
+
+use crate::cancel::{CancellationToken, check_cancelled};
+use crate::encode::cardinality::CardinalityTracker;
+use crate::encode::common::{ResourceScopeBuilder, resource_key, scope_key};
+use crate::encode::record::{
+    Columns, DictionaryKey, delta_encode, dictionary, non_empty, non_zero,
+};
+use crate::encode::sorter::MetricSorter;
+use crate::error::{self, Result};
+use crate::memory::{MemoryPool, reserve};
+use crate::otap::{Metrics, OtapBatch};
+use crate::otlp::metrics::MetricType;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::metrics::v1::metric::Data;
+use crate::proto::opentelemetry::metrics::v1::{Metric, ResourceMetrics, ScopeMetrics};
+use crate::schema::consts;
+
+use data_points::{
+    DataPointsBatches, ExpHistogramDataPointsAccumulator, HistogramDataPointsAccumulator,
+    NumberDataPointsAccumulator, SummaryDataPointsAccumulator,
+};
+
+mod data_points;
+mod exemplars;
+
+/// Produces the OTAP batches of metrics requests.
+///
+/// The batches are produced the way the Go producer does with its default configuration:
+/// - metrics are sorted by resource, scope, then name unless another
+///   [`MetricSorter`] is set, the resources and scopes that are equal
+///   are encoded once,
+/// - the data points of each kind are stored in their own payload, gauges and sums sharing
+///   the number data points payload, along with their attributes and exemplars,
+/// - attributes are sorted by type, key, value and parent id, exemplars by value and parent
+///   id, the parent ids of consecutive attributes with the same key and value, or
+///   exemplars with the same value, are delta encoded,
+/// - the ids of resources, scopes, metrics, data points and exemplars are delta encoded, and
+///   the data points and exemplars without related data have no id.
+///
+/// Zero numbers and empty strings are written as nulls, unless the decoder requires the
+/// column, and the columns only holding nulls are left out of the batches.
+#[derive(Debug, Default)]
+pub struct MetricsProducer {
+    sorter: MetricSorter,
+    cancellation: Option<CancellationToken>,
+    memory_pool: Option<Arc<dyn MemoryPool>>,
+    cardinality_tracker: Option<Arc<CardinalityTracker>>,
+}
+
+/// A metric along with the resource and scope it belongs to.
+struct FlattenedMetric<'a> {
+    resource_key: &'a str,
+    resource_metrics: &'a ResourceMetrics,
+    scope_key: &'a str,
+    scope_metrics: &'a ScopeMetrics,
+    metric: &'a Metric,
+}
+
+impl MetricsProducer {
+    /// Creates a new producer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a producer sorting the metrics of each scope with the given sorter.
+    #[must_use]
+    pub fn with_sorter(sorter: MetricSorter) -> Self {
+        Self {
+            sorter,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the token cancelling the production of the batches, checked between metrics.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets the pool the memory of the batches is reserved from, see [`crate::memory`].
+    #[must_use]
+    pub fn with_memory_pool(mut self, pool: Arc<dyn MemoryPool>) -> Self {
+        self.memory_pool = Some(pool);
+        self
+    }
+
+    /// Sets the tracker fed the attributes of the requests, see [`crate::encode::cardinality`].
+    #[must_use]
+    pub fn with_cardinality_tracker(mut self, tracker: Arc<CardinalityTracker>) -> Self {
+        self.cardinality_tracker = Some(tracker);
+        self
+    }
+
+    /// Produces the OTAP batch of the request.
+    pub fn produce(&self, request: &ExportMetricsServiceRequest) -> Result<OtapBatch> {
+        if let Some(tracker) = &self.cardinality_tracker {
+            tracker.observe_metrics(request, Instant::now());
+        }
+        let keys: Vec<(String, Vec<String>)> = request
+            .resource_metrics
+            .iter()
+            .map(|resource_metrics| {
+                let scope_keys = resource_metrics
+                    .scope_metrics
+                    .iter()
+                    .map(|scope_metrics| {
+                        scope_key(scope_metrics.scope.as_ref(), &scope_metrics.schema_url)
+                    })
+                    .collect();
+                (
+                    resource_key(
+                        resource_metrics.resource.as_ref(),
+                        &resource_metrics.schema_url,
+                    ),
+                    scope_keys,
+                )
+            })
+            .collect();
+
+        let mut metrics = Vec::new();
+        for (resource_metrics, (resource_key, scope_keys)) in
+            request.resource_metrics.iter().zip(&keys)
+        {
+            for (scope_metrics, scope_key) in resource_metrics.scope_metrics.iter().zip(scope_keys)
+            {
+                metrics.extend(scope_metrics.metrics.iter().map(|metric| FlattenedMetric {
+                    resource_key,
+                    resource_metrics,
+                    scope_key,
+                    scope_metrics,
+                    metric,
+                }));
+            }
+        }
+        metrics.sort_by(|a, b| {
+            a.resource_key
+                .cmp(b.resource_key)
+                .then_with(|| a.scope_key.cmp(b.scope_key))
+                .then_with(|| self.sorter.compare(a.metric, b.metric))
+        });
+
+        let mut resources_scopes = ResourceScopeBuilder::default();
+        let mut number_data_points = NumberDataPointsAccumulator::default();
+        let mut histogram_data_points = HistogramDataPointsAccumulator::default();
+        let mut exp_histogram_data_points = ExpHistogramDataPointsAccumulator::default();
+        let mut summary_data_points = SummaryDataPointsAccumulator::default();
+
+        let len = metrics.len();
+        let mut ids = Vec::with_capacity(len);
+        let mut schema_urls = Vec::with_capacity(len);
+        let mut metric_types = Vec::with_capacity(len);
+        let mut names = Vec::with_capacity(len);
+        let mut descriptions = Vec::with_capacity(len);
+        let mut units = Vec::with_capacity(len);
+        let mut aggregation_temporalities = Vec::with_capacity(len);
+        let mut is_monotonics = Vec::with_capacity(len);
+
+        for (idx, flattened) in metrics.iter().enumerate() {
+            check_cancelled(self.cancellation.as_ref())?;
+            let metric = flattened.metric;
+
+            resources_scopes.append(
+                flattened.resource_key,
+                flattened.resource_metrics.resource.as_ref(),
+                &flattened.resource_metrics.schema_url,
+                flattened.scope_key,
+                flattened.scope_metrics.scope.as_ref(),
+            )?;
+            schema_urls.push(non_empty(&flattened.scope_metrics.schema_url));
+
+            // every metric has an id, the decoder looks their data points up by metric id
+            let id = u16::try_from(idx)
+                .ok()
+                .context(error::IdOverflowSnafu { name: "metrics" })?;
+            ids.push(Some(id));
+            names.push(metric.name.as_str());
+            descriptions.push(non_empty(&metric.description));
+            units.push(non_empty(&metric.unit));
+
+            let (metric_type, aggregation_temporality, is_monotonic) = match &metric.data {
+                Some(Data::Gauge(gauge)) => {
+                    number_data_points.append(id, &gauge.data_points);
+                    (MetricType::Gauge, 0, None)
+                }
+                Some(Data::Sum(sum)) => {
+                    number_data_points.append(id, &sum.data_points);
+                    (
+                        MetricType::Sum,
+                        sum.aggregation_temporality,
+                        Some(sum.is_monotonic),
+                    )
+                }
+                Some(Data::Histogram(histogram)) => {
+                    histogram_data_points.append(id, &histogram.data_points);
+                    (
+                        MetricType::Histogram,
+                        histogram.aggregation_temporality,
+                        None,
+                    )
+                }
+                Some(Data::ExponentialHistogram(histogram)) => {
+                    exp_histogram_data_points.append(id, &histogram.data_points);
+                    (
+                        MetricType::ExponentialHistogram,
+                        histogram.aggregation_temporality,
+                        None,
+                    )
+                }
+                Some(Data::Summary(summary)) => {
+                    summary_data_points.append(id, &summary.data_points);
+                    (MetricType::Summary, 0, None)
+                }
+                None => (MetricType::Empty, 0, None),
+            };
+            metric_types.push(metric_type as u8);
+            aggregation_temporalities.push(non_zero(aggregation_temporality));
+            is_monotonics.push(is_monotonic);
+        }
+
+        let resources_scopes = resources_scopes.finish()?;
+        let mut columns = Columns::default();
+        columns.required(consts::ID, Arc::new(delta_encode::<UInt16Type>(&ids)));
+        columns.required(consts::RESOURCE, resources_scopes.resource);
+        columns.required(consts::SCOPE, resources_scopes.scope);
+        columns.optional(
+            consts::SCHEMA_URL,
+            dictionary(Arc::new(StringArray::from(schema_urls)), DictionaryKey::U8),
+        );
+        columns.required(
+            consts::METRIC_TYPE,
+            Arc::new(UInt8Array::from(metric_types)),
+        );
+        columns.required(
+            consts::NAME,
+            dictionary(Arc::new(StringArray::from(names)), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::DESCRIPTION,
+            dictionary(Arc::new(StringArray::from(descriptions)), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::UNIT,
+            dictionary(Arc::new(StringArray::from(units)), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::AGGREGATION_TEMPORALITY,
+            dictionary(
+                Arc::new(Int32Array::from(aggregation_temporalities)),
+                DictionaryKey::U8,
+            ),
+        );
+        columns.optional(
+            consts::IS_MONOTONIC,
+            Arc::new(BooleanArray::from(is_monotonics)),
+        );
+
+        let mut otap_batch = OtapBatch::Metrics(Metrics::default());
+        otap_batch.set(
+            ArrowPayloadType::UnivariateMetrics,
+            columns.into_record_batch()?,
+        );
+        let mut related = vec![
+            (
+                ArrowPayloadType::ResourceAttrs,
+                resources_scopes.resource_attrs,
+            ),
+            (ArrowPayloadType::ScopeAttrs, resources_scopes.scope_attrs),
+        ];
+        let data_points = [
+            (number_data_points.finish()?, [
+                ArrowPayloadType::NumberDataPoints,
+                ArrowPayloadType::NumberDpAttrs,
+                ArrowPayloadType::NumberDpExemplars,
+                ArrowPayloadType::NumberDpExemplarAttrs,
+            ]),
+            (
+                summary_data_points.finish()?,
+                // the summaries have no exemplars
+                [
+                    ArrowPayloadType::SummaryDataPoints,
+                    ArrowPayloadType::SummaryDpAttrs,
+                    ArrowPayloadType::Unknown,
+                    ArrowPayloadType::Unknown,
+                ],
+            ),
+            (histogram_data_points.finish()?, [
+                ArrowPayloadType::HistogramDataPoints,
+                ArrowPayloadType::HistogramDpAttrs,
+                ArrowPayloadType::HistogramDpExemplars,
+                ArrowPayloadType::HistogramDpExemplarAttrs,
+            ]),
+            (exp_histogram_data_points.finish()?, [
+                ArrowPayloadType::ExpHistogramDataPoints,
+                ArrowPayloadType::ExpHistogramDpAttrs,
+                ArrowPayloadType::ExpHistogramDpExemplars,
+                ArrowPayloadType::ExpHistogramDpExemplarAttrs,
+            ]),
+        ];
+        for (batches, payload_types) in data_points {
+            let DataPointsBatches {
+                data_points,
+                attrs,
+                exemplars,
+                exemplar_attrs,
+            } = batches;
+            related.extend(payload_types.into_iter().zip([
+                data_points,
+                attrs,
+                exemplars,
+                exemplar_attrs,
+            ]));
+        }
+        for (payload_type, record_batch) in related {
+            if let Some(record_batch) = record_batch {
+                otap_batch.set(payload_type, record_batch);
+            }
+        }
+
+        let reservation = reserve(self.memory_pool.as_ref(), otap_batch.memory_size())?;
+        otap_batch.set_reservation(reservation);
+        Ok(otap_batch)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otlp::metrics::metrics_from;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+    use crate::proto::opentelemetry::metrics::v1::exemplar;
+    use crate::proto::opentelemetry::metrics::v1::exponential_histogram_data_point::Buckets;
+    use crate::proto::opentelemetry::metrics::v1::number_data_point;
+    use crate::proto::opentelemetry::metrics::v1::summary_data_point::ValueAtQuantile;
+    use crate::proto::opentelemetry::metrics::v1::{
+        DataPointFlags, Exemplar, ExponentialHistogram, ExponentialHistogramDataPoint, Gauge,
+        Histogram, HistogramDataPoint, NumberDataPoint, Sum, Summary, SummaryDataPoint,
+    };
+    use crate::proto::opentelemetry::resource::v1::Resource;
+
+    fn attr(key: &str, value: Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn metric(name: &str, data: Data) -> Metric {
+        Metric {
+            name: name.into(),
+            data: Some(data),
+            ..Default::default()
+        }
+    }
+
+    fn exemplar(value: exemplar::Value, span_id: u8) -> Exemplar {
+        Exemplar {
+            time_unix_nano: 5,
+            span_id: vec![span_id; 8],
+            trace_id: vec![1; 16],
+            value: Some(value),
+            ..Default::default()
+        }
+    }
+
+    /// Request already in the order of the produced batch, so it decodes back to itself.
+    fn request() -> ExportMetricsServiceRequest {
+        let number_data_point = |value, attrs| NumberDataPoint {
+            attributes: attrs,
+            start_time_unix_nano: 1,
+            time_unix_nano: 2,
+            value: Some(value),
+            ..Default::default()
+        };
+
+        let gauge = metric(
+            "a.gauge",
+            Data::Gauge(Gauge {
+                data_points: vec![
+                    NumberDataPoint {
+                        exemplars: vec![
+                            exemplar(exemplar::Value::AsInt(3), 1),
+                            exemplar(exemplar::Value::AsDouble(0.5), 2),
+                        ],
+                        ..number_data_point(number_data_point::Value::AsDouble(1.5), vec![attr(
+                            "host",
+                            Value::StringValue("a".into()),
+                        )])
+                    },
+                    number_data_point(number_data_point::Value::AsInt(3), vec![]),
+                ],
+            }),
+        );
+        let mut sum = metric(
+            "b.sum",
+            Data::Sum(Sum {
+                data_points: vec![number_data_point(
+                    number_data_point::Value::AsInt(10),
+                    vec![attr("host", Value::StringValue("a".into()))],
+                )],
+                aggregation_temporality: 2,
+                is_monotonic: true,
+            }),
+        );
+        sum.description = "a sum".into();
+        sum.unit = "By".into();
+
+        let histogram = metric(
+            "c.histogram",
+            Data::Histogram(Histogram {
+                data_points: vec![HistogramDataPoint {
+                    attributes: vec![attr("code", Value::IntValue(200))],
+                    time_unix_nano: 2,
+                    count: 3,
+                    sum: Some(4.5),
+                    bucket_counts: vec![1, 2],
+                    explicit_bounds: vec![1.0],
+                    exemplars: vec![
+                        Exemplar {
+                            filtered_attributes: vec![attr("user", Value::StringValue("x".into()))],
+                            ..exemplar(exemplar::Value::AsDouble(1.5), 3)
+                        },
+                        // recorded outside of a span
+                        Exemplar {
+                            time_unix_nano: 5,
+                            value: Some(exemplar::Value::AsDouble(2.0)),
+                            ..Default::default()
+                        },
+                    ],
+                    flags: 1,
+                    min: Some(0.5),
+                    max: Some(2.5),
+                    ..Default::default()
+                }],
+                aggregation_temporality: 1,
+            }),
+        );
+        let exp_histogram = metric(
+            "d.exp_histogram",
+            Data::ExponentialHistogram(ExponentialHistogram {
+                data_points: vec![ExponentialHistogramDataPoint {
+                    time_unix_nano: 2,
+                    count: 4,
+                    scale: -1,
+                    zero_count: 1,
+                    positive: Some(Buckets {
+                        offset: 2,
+                        bucket_counts: vec![1, 1],
+                    }),
+                    negative: Some(Buckets {
+                        offset: 0,
+                        bucket_counts: vec![1],
+                    }),
+                    ..Default::default()
+                }],
+                aggregation_temporality: 2,
+            }),
+        );
+        let summary = metric(
+            "e.summary",
+            Data::Summary(Summary {
+                data_points: vec![SummaryDataPoint {
+                    attributes: vec![attr("code", Value::IntValue(500))],
+                    time_unix_nano: 2,
+                    count: 2,
+                    sum: 3.0,
+                    quantile_values: vec![
+                        ValueAtQuantile {
+                            quantile: 0.5,
+                            value: 1.0,
+                        },
+                        ValueAtQuantile {
+                            quantile: 1.0,
+                            value: 2.0,
+                        },
+                    ],
+                    ..Default::default()
+                }],
+            }),
+        );
+
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource {
+                    attributes: vec![attr("service.name", Value::StringValue("svc".into()))],
+                    ..Default::default()
+                }),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: Some(InstrumentationScope {
+                        name: "meter".into(),
+                        attributes: vec![attr("sdk", Value::StringValue("rust".into()))],
+                        dropped_attributes_count: 1,
+                        ..Default::default()
+                    }),
+                    metrics: vec![gauge, sum, histogram, exp_histogram, summary],
+                    schema_url: "https://scope".into(),
+                }],
+                schema_url: "https://resource".into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_produce_round_trip() {
+        let request = request();
+        let otap_batch = MetricsProducer::new().produce(&request).unwrap();
+        assert_eq!(otap_batch.payload_types(), vec![
+            ArrowPayloadType::UnivariateMetrics,
+            ArrowPayloadType::ResourceAttrs,
+            ArrowPayloadType::ScopeAttrs,
+            ArrowPayloadType::NumberDataPoints,
+            ArrowPayloadType::SummaryDataPoints,
+            ArrowPayloadType::HistogramDataPoints,
+            ArrowPayloadType::ExpHistogramDataPoints,
+            ArrowPayloadType::NumberDpAttrs,
+            ArrowPayloadType::SummaryDpAttrs,
+            ArrowPayloadType::HistogramDpAttrs,
+            ArrowPayloadType::NumberDpExemplars,
+            ArrowPayloadType::HistogramDpExemplars,
+            ArrowPayloadType::HistogramDpExemplarAttrs,
+        ]);
+
+        let decoded = metrics_from(otap_batch).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn test_produce_no_recorded_value() {
+        // gaps in the time series, see `DataPointFlags::NoRecordedValueMask`
+        let flags = DataPointFlags::NoRecordedValueMask as u32;
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![
+                        metric(
+                            "a.gauge",
+                            Data::Gauge(Gauge {
+                                data_points: vec![NumberDataPoint {
+                                    time_unix_nano: 2,
+                                    flags,
+                                    ..Default::default()
+                                }],
+                            }),
+                        ),
+                        metric(
+                            "b.histogram",
+                            Data::Histogram(Histogram {
+                                data_points: vec![HistogramDataPoint {
+                                    time_unix_nano: 2,
+                                    flags,
+                                    ..Default::default()
+                                }],
+                                aggregation_temporality: 1,
+                            }),
+                        ),
+                        metric(
+                            "c.exp_histogram",
+                            Data::ExponentialHistogram(ExponentialHistogram {
+                                data_points: vec![ExponentialHistogramDataPoint {
+                                    time_unix_nano: 2,
+                                    flags,
+                                    positive: Some(Buckets::default()),
+                                    negative: Some(Buckets::default()),
+                                    ..Default::default()
+                                }],
+                                aggregation_temporality: 1,
+                            }),
+                        ),
+                        metric(
+                            "d.summary",
+                            Data::Summary(Summary {
+                                data_points: vec![SummaryDataPoint {
+                                    time_unix_nano: 2,
+                                    flags,
+                                    ..Default::default()
+                                }],
+                            }),
+                        ),
+                    ],
+                    scope: Some(InstrumentationScope::default()),
+                    ..Default::default()
+                }],
+                resource: Some(Resource::default()),
+                ..Default::default()
+            }],
+        };
+        let otap_batch = MetricsProducer::new().produce(&request).unwrap();
+        let decoded = metrics_from(otap_batch).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn test_produce_min_max() {
+        let data_point = |min, max| HistogramDataPoint {
+            time_unix_nano: 2,
+            count: 1,
+            min,
+            max,
+            ..Default::default()
+        };
+        let exp_data_point = |min, max| ExponentialHistogramDataPoint {
+            time_unix_nano: 2,
+            count: 1,
+            positive: Some(Buckets::default()),
+            negative: Some(Buckets::default()),
+            min,
+            max,
+            ..Default::default()
+        };
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource::default()),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: Some(InstrumentationScope::default()),
+                    metrics: vec![
+                        metric(
+                            "a.histogram",
+                            Data::Histogram(Histogram {
+                                data_points: vec![
+                                    data_point(Some(-1.0), Some(0.0)),
+                                    data_point(None, Some(2.0)),
+                                    data_point(Some(3.0), None),
+                                ],
+                                aggregation_temporality: 1,
+                            }),
+                        ),
+                        metric(
+                            "b.exp_histogram",
+                            Data::ExponentialHistogram(ExponentialHistogram {
+                                data_points: vec![
+                                    exp_data_point(None, None),
+                                    exp_data_point(Some(0.0), Some(1.5)),
+                                ],
+                                aggregation_temporality: 1,
+                            }),
+                        ),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let otap_batch = MetricsProducer::new().produce(&request).unwrap();
+        let decoded = metrics_from(otap_batch).unwrap();
+        assert_eq!(decoded, request);
+
+        // the columns are omitted when no data point has a min nor a max
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![metric(
+                        "a.histogram",
+                        Data::Histogram(Histogram {
+                            data_points: vec![data_point(None, None)],
+                            aggregation_temporality: 1,
+                        }),
+                    )],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let otap_batch = MetricsProducer::new().produce(&request).unwrap();
+        let data_points = otap_batch
+            .get(ArrowPayloadType::HistogramDataPoints)
+            .unwrap();
+        assert!(data_points.column_by_name(consts::HISTOGRAM_MIN).is_none());
+        assert!(data_points.column_by_name(consts::HISTOGRAM_MAX).is_none());
+    }
+
+    #[test]
+    fn test_produce_data_point_ids() {
+        let otap_batch = MetricsProducer::new().produce(&request()).unwrap();
+
+        let data_points = otap_batch.get(ArrowPayloadType::NumberDataPoints).unwrap();
+        // the second gauge data point has no attributes nor exemplars
+        assert_eq!(
+            data_points.column_by_name(consts::ID).unwrap().as_ref(),
+            &arrow::array::UInt32Array::from(vec![Some(0), None, Some(1)])
+                as &dyn arrow::array::Array
+        );
+        // the data points of the gauge, then of the sum
+        assert_eq!(
+            data_points
+                .column_by_name(consts::PARENT_ID)
+                .unwrap()
+                .as_ref(),
+            &arrow::array::UInt16Array::from(vec![0, 0, 1]) as &dyn arrow::array::Array
+        );
+    }
+}
@@ -0,0 +1,378 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Production of the data points record batches, one per kind of data points.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, Float64Array, Int32Array, Int64Array, ListArray, RecordBatch,
+    TimestampNanosecondArray, UInt16Array, UInt32Array, UInt64Array,
+};
+use arrow::buffer::OffsetBuffer;
+use arrow::datatypes::{Field, Float64Type, UInt32Type, UInt64Type};
+use snafu::{OptionExt, ResultExt};
+
+use crate::encode::attributes::Attributes32Accumulator;
+use crate::encode::metrics::exemplars::{ExemplarsAccumulator, ExemplarsBatches};
+use crate::encode::record::{Columns, delta_encode, non_zero};
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::common::v1::KeyValue;
+use crate::proto::opentelemetry::metrics::v1::exponential_histogram_data_point::Buckets;
+use crate::proto::opentelemetry::metrics::v1::number_data_point::Value;
+use crate::proto::opentelemetry::metrics::v1::{
+    Exemplar, ExponentialHistogramDataPoint, HistogramDataPoint, NumberDataPoint, SummaryDataPoint,
+};
+use crate::schema::consts;
+
+/// The record batches of a kind of data points: the data points, their attributes, their
+/// exemplars and the exemplar attributes.
+#[derive(Default)]
+pub(crate) struct DataPointsBatches {
+    pub(crate) data_points: Option<RecordBatch>,
+    pub(crate) attrs: Option<RecordBatch>,
+    pub(crate) exemplars: Option<RecordBatch>,
+    pub(crate) exemplar_attrs: Option<RecordBatch>,
+}
+
+/// Data points of one kind, along with the id of the metric they belong to. The metrics are
+/// appended in the order of their ids, so the data points are sorted by parent id.
+pub(crate) struct DataPointsAccumulator<'a, T> {
+    data_points: Vec<(u16, &'a T)>,
+}
+
+pub(crate) type NumberDataPointsAccumulator<'a> = DataPointsAccumulator<'a, NumberDataPoint>;
+pub(crate) type HistogramDataPointsAccumulator<'a> = DataPointsAccumulator<'a, HistogramDataPoint>;
+pub(crate) type ExpHistogramDataPointsAccumulator<'a> =
+    DataPointsAccumulator<'a, ExponentialHistogramDataPoint>;
+pub(crate) type SummaryDataPointsAccumulator<'a> = DataPointsAccumulator<'a, SummaryDataPoint>;
+
+impl<T> Default for DataPointsAccumulator<'_, T> {
+    fn default() -> Self {
+        Self {
+            data_points: Vec::new(),
+        }
+    }
+}
+
+impl<'a, T> DataPointsAccumulator<'a, T> {
+    /// Adds the data points of the metric with the given id.
+    pub(crate) fn append(&mut self, parent_id: u16, data_points: &'a [T]) {
+        self.data_points
+            .extend(data_points.iter().map(|data_point| (parent_id, data_point)));
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &'a T> + '_ {
+        self.data_points.iter().map(|(_, data_point)| *data_point)
+    }
+
+    /// Assigns the ids of the data points with attributes or exemplars, and adds the `id`
+    /// and `parent_id` columns. The id column is always added if `id_required` is set.
+    fn ids(
+        &self,
+        related: impl Fn(&'a T) -> (&'a [KeyValue], &'a [Exemplar]),
+        id_required: bool,
+        columns: &mut Columns,
+    ) -> Result<RelatedAccumulators<'a>> {
+        let mut accumulators = RelatedAccumulators::default();
+        let mut ids = Vec::with_capacity(self.data_points.len());
+        let mut next_id = 0u32;
+        for (_, data_point) in &self.data_points {
+            let (attrs, exemplars) = related(data_point);
+            if attrs.is_empty() && exemplars.is_empty() {
+                ids.push(None);
+            } else {
+                accumulators.attrs.append(next_id, attrs);
+                accumulators.exemplars.append(next_id, exemplars);
+                ids.push(Some(next_id));
+                next_id = next_id.checked_add(1).context(error::IdOverflowSnafu {
+                    name: "data points",
+                })?;
+            }
+        }
+
+        let mut prev_parent_id = 0;
+        let parent_ids =
+            UInt16Array::from_iter_values(self.data_points.iter().map(|(parent_id, _)| {
+                let delta = parent_id - prev_parent_id;
+                prev_parent_id = *parent_id;
+                delta
+            }));
+
+        let ids = Arc::new(delta_encode::<UInt32Type>(&ids));
+        if id_required {
+            columns.required(consts::ID, ids);
+        } else {
+            columns.optional(consts::ID, ids);
+        }
+        columns.required(consts::PARENT_ID, Arc::new(parent_ids));
+        Ok(accumulators)
+    }
+
+    fn timestamps(&self, time: impl Fn(&T) -> u64) -> TimestampNanosecondArray {
+        TimestampNanosecondArray::from_iter_values(self.iter().map(|dp| time(dp) as i64))
+    }
+
+    fn optional_timestamps(&self, time: impl Fn(&T) -> u64) -> TimestampNanosecondArray {
+        self.iter().map(|dp| non_zero(time(dp) as i64)).collect()
+    }
+}
+
+/// Attributes and exemplars of the data points.
+#[derive(Default)]
+struct RelatedAccumulators<'a> {
+    attrs: Attributes32Accumulator<'a>,
+    exemplars: ExemplarsAccumulator<'a>,
+}
+
+impl RelatedAccumulators<'_> {
+    fn finish(self, columns: Columns) -> Result<DataPointsBatches> {
+        let ExemplarsBatches { exemplars, attrs } = self.exemplars.finish()?;
+        Ok(DataPointsBatches {
+            data_points: Some(columns.into_record_batch()?),
+            attrs: self.attrs.finish()?,
+            exemplars,
+            exemplar_attrs: attrs,
+        })
+    }
+}
+
+fn u64_list<'b>(lists: impl Iterator<Item = &'b [u64]>) -> ArrayRef {
+    Arc::new(ListArray::from_iter_primitive::<UInt64Type, _, _>(
+        lists.map(|list| Some(list.iter().copied().map(Some))),
+    ))
+}
+
+fn f64_list<'b>(lists: impl Iterator<Item = &'b [f64]>) -> ArrayRef {
+    Arc::new(ListArray::from_iter_primitive::<Float64Type, _, _>(
+        lists.map(|list| Some(list.iter().copied().map(Some))),
+    ))
+}
+
+impl<'a> NumberDataPointsAccumulator<'a> {
+    /// Returns the record batches of the gauge and sum data points.
+    pub(crate) fn finish(self) -> Result<DataPointsBatches> {
+        if self.data_points.is_empty() {
+            return Ok(DataPointsBatches::default());
+        }
+        let mut columns = Columns::default();
+        // the decoder requires the id column, even when it only holds nulls
+        let related = self.ids(
+            |dp: &'a NumberDataPoint| (&dp.attributes, &dp.exemplars),
+            true,
+            &mut columns,
+        )?;
+        columns.optional(
+            consts::START_TIME_UNIX_NANO,
+            Arc::new(self.optional_timestamps(|dp| dp.start_time_unix_nano)),
+        );
+        columns.required(
+            consts::TIME_UNIX_NANO,
+            Arc::new(self.timestamps(|dp| dp.time_unix_nano)),
+        );
+        let int_values: Int64Array = self
+            .iter()
+            .map(|dp| match dp.value {
+                Some(Value::AsInt(i)) => Some(i),
+                _ => None,
+            })
+            .collect();
+        let double_values: Float64Array = self
+            .iter()
+            .map(|dp| match dp.value {
+                Some(Value::AsDouble(d)) => Some(d),
+                _ => None,
+            })
+            .collect();
+        columns.optional(consts::INT_VALUE, Arc::new(int_values));
+        columns.optional(consts::DOUBLE_VALUE, Arc::new(double_values));
+        let flags: UInt32Array = self.iter().map(|dp| non_zero(dp.flags)).collect();
+        columns.optional(consts::FLAGS, Arc::new(flags));
+        related.finish(columns)
+    }
+}
+
+impl<'a> HistogramDataPointsAccumulator<'a> {
+    /// Returns the record batches of the histogram data points.
+    pub(crate) fn finish(self) -> Result<DataPointsBatches> {
+        if self.data_points.is_empty() {
+            return Ok(DataPointsBatches::default());
+        }
+        let mut columns = Columns::default();
+        let related = self.ids(
+            |dp: &'a HistogramDataPoint| (&dp.attributes, &dp.exemplars),
+            false,
+            &mut columns,
+        )?;
+        columns.optional(
+            consts::START_TIME_UNIX_NANO,
+            Arc::new(self.optional_timestamps(|dp| dp.start_time_unix_nano)),
+        );
+        columns.required(
+            consts::TIME_UNIX_NANO,
+            Arc::new(self.timestamps(|dp| dp.time_unix_nano)),
+        );
+        columns.required(
+            consts::HISTOGRAM_COUNT,
+            Arc::new(UInt64Array::from_iter_values(
+                self.iter().map(|dp| dp.count),
+            )),
+        );
+        let sums: Float64Array = self.iter().map(|dp| dp.sum).collect();
+        columns.optional(consts::HISTOGRAM_SUM, Arc::new(sums));
+        columns.required(
+            consts::HISTOGRAM_BUCKET_COUNTS,
+            u64_list(self.iter().map(|dp| dp.bucket_counts.as_slice())),
+        );
+        columns.required(
+            consts::HISTOGRAM_EXPLICIT_BOUNDS,
+            f64_list(self.iter().map(|dp| dp.explicit_bounds.as_slice())),
+        );
+        let flags: UInt32Array = self.iter().map(|dp| non_zero(dp.flags)).collect();
+        columns.optional(consts::FLAGS, Arc::new(flags));
+        let mins: Float64Array = self.iter().map(|dp| dp.min).collect();
+        let maxs: Float64Array = self.iter().map(|dp| dp.max).collect();
+        columns.optional(consts::HISTOGRAM_MIN, Arc::new(mins));
+        columns.optional(consts::HISTOGRAM_MAX, Arc::new(maxs));
+        related.finish(columns)
+    }
+}
+
+impl<'a> ExpHistogramDataPointsAccumulator<'a> {
+    /// Returns the record batches of the exponential histogram data points.
+    pub(crate) fn finish(self) -> Result<DataPointsBatches> {
+        if self.data_points.is_empty() {
+            return Ok(DataPointsBatches::default());
+        }
+        let mut columns = Columns::default();
+        let related = self.ids(
+            |dp: &'a ExponentialHistogramDataPoint| (&dp.attributes, &dp.exemplars),
+            false,
+            &mut columns,
+        )?;
+        columns.optional(
+            consts::START_TIME_UNIX_NANO,
+            Arc::new(self.optional_timestamps(|dp| dp.start_time_unix_nano)),
+        );
+        columns.required(
+            consts::TIME_UNIX_NANO,
+            Arc::new(self.timestamps(|dp| dp.time_unix_nano)),
+        );
+        columns.required(
+            consts::HISTOGRAM_COUNT,
+            Arc::new(UInt64Array::from_iter_values(
+                self.iter().map(|dp| dp.count),
+            )),
+        );
+        let sums: Float64Array = self.iter().map(|dp| dp.sum).collect();
+        columns.optional(consts::HISTOGRAM_SUM, Arc::new(sums));
+        columns.required(
+            consts::EXP_HISTOGRAM_SCALE,
+            Arc::new(Int32Array::from_iter_values(self.iter().map(|dp| dp.scale))),
+        );
+        columns.required(
+            consts::EXP_HISTOGRAM_ZERO_COUNT,
+            Arc::new(UInt64Array::from_iter_values(
+                self.iter().map(|dp| dp.zero_count),
+            )),
+        );
+        columns.required(
+            consts::EXP_HISTOGRAM_POSITIVE,
+            self.buckets(|dp| dp.positive.as_ref())?,
+        );
+        columns.required(
+            consts::EXP_HISTOGRAM_NEGATIVE,
+            self.buckets(|dp| dp.negative.as_ref())?,
+        );
+        let flags: UInt32Array = self.iter().map(|dp| non_zero(dp.flags)).collect();
+        columns.optional(consts::FLAGS, Arc::new(flags));
+        let mins: Float64Array = self.iter().map(|dp| dp.min).collect();
+        let maxs: Float64Array = self.iter().map(|dp| dp.max).collect();
+        columns.optional(consts::HISTOGRAM_MIN, Arc::new(mins));
+        columns.optional(consts::HISTOGRAM_MAX, Arc::new(maxs));
+        related.finish(columns)
+    }
+
+    /// Returns the `positive` or `negative` struct column, the missing buckets are written
+    /// as empty buckets.
+    fn buckets(
+        &self,
+        buckets: impl Fn(&ExponentialHistogramDataPoint) -> Option<&Buckets>,
+    ) -> Result<ArrayRef> {
+        let offsets = Int32Array::from_iter_values(
+            self.iter()
+                .map(|dp| buckets(dp).map_or(0, |buckets| buckets.offset)),
+        );
+        let bucket_counts = u64_list(
+            self.iter()
+                .map(|dp| buckets(dp).map_or(&[][..], |buckets| &buckets.bucket_counts)),
+        );
+
+        let mut columns = Columns::default();
+        columns.required(consts::EXP_HISTOGRAM_OFFSET, Arc::new(offsets));
+        columns.required(consts::EXP_HISTOGRAM_BUCKET_COUNTS, bucket_counts);
+        columns.into_struct_array(self.data_points.len(), None)
+    }
+}
+
+impl<'a> SummaryDataPointsAccumulator<'a> {
+    /// Returns the record batches of the summary data points, they have no exemplars.
+    pub(crate) fn finish(self) -> Result<DataPointsBatches> {
+        if self.data_points.is_empty() {
+            return Ok(DataPointsBatches::default());
+        }
+        let mut columns = Columns::default();
+        let related = self.ids(
+            |dp: &'a SummaryDataPoint| (&dp.attributes, &[]),
+            false,
+            &mut columns,
+        )?;
+        columns.optional(
+            consts::START_TIME_UNIX_NANO,
+            Arc::new(self.optional_timestamps(|dp| dp.start_time_unix_nano)),
+        );
+        columns.required(
+            consts::TIME_UNIX_NANO,
+            Arc::new(self.timestamps(|dp| dp.time_unix_nano)),
+        );
+        columns.required(
+            consts::SUMMARY_COUNT,
+            Arc::new(UInt64Array::from_iter_values(
+                self.iter().map(|dp| dp.count),
+            )),
+        );
+        columns.required(
+            consts::SUMMARY_SUM,
+            Arc::new(Float64Array::from_iter_values(self.iter().map(|dp| dp.sum))),
+        );
+        columns.required(consts::SUMMARY_QUANTILE_VALUES, self.quantiles()?);
+        let flags: UInt32Array = self.iter().map(|dp| non_zero(dp.flags)).collect();
+        columns.optional(consts::FLAGS, Arc::new(flags));
+        related.finish(columns)
+    }
+
+    /// Returns the list column of the `quantile` and `value` structs.
+    fn quantiles(&self) -> Result<ArrayRef> {
+        let quantile_values = || self.iter().flat_map(|dp| &dp.quantile_values);
+        let mut columns = Columns::default();
+        columns.required(
+            consts::SUMMARY_QUANTILE,
+            Arc::new(Float64Array::from_iter_values(
+                quantile_values().map(|q| q.quantile),
+            )),
+        );
+        columns.required(
+            consts::SUMMARY_VALUE,
+            Arc::new(Float64Array::from_iter_values(
+                quantile_values().map(|q| q.value),
+            )),
+        );
+        let values = columns.into_struct_array(quantile_values().count(), None)?;
+
+        let field = Arc::new(Field::new_list_field(values.data_type().clone(), true));
+        let offsets = OffsetBuffer::from_lengths(self.iter().map(|dp| dp.quantile_values.len()));
+        let list = ListArray::try_new(field, offsets, values, None)
+            .context(error::WriteRecordBatchSnafu)?;
+        Ok(Arc::new(list))
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Production of the exemplar record batches of the data points.
+
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use arrow::array::{Float64Array, Int64Array, RecordBatch, TimestampNanosecondArray, UInt32Array};
+use arrow::datatypes::UInt32Type;
+use snafu::{OptionExt, ensure};
+
+use crate::encode::attributes::Attributes32Accumulator;
+use crate::encode::record::{Columns, delta_encode, fixed_size_binary, non_empty_bytes};
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::metrics::v1::Exemplar;
+use crate::proto::opentelemetry::metrics::v1::exemplar::Value;
+use crate::schema::consts;
+
+const TRACE_ID_LEN: i32 = 16;
+const SPAN_ID_LEN: i32 = 8;
+
+/// Exemplars of the data points of a payload, accumulated to produce the exemplars record
+/// batch of the payload.
+#[derive(Default)]
+pub(crate) struct ExemplarsAccumulator<'a> {
+    exemplars: Vec<(u32, &'a Exemplar)>,
+}
+
+/// The exemplars record batch of a payload, with the exemplar attributes record batch.
+pub(crate) struct ExemplarsBatches {
+    pub(crate) exemplars: Option<RecordBatch>,
+    pub(crate) attrs: Option<RecordBatch>,
+}
+
+impl<'a> ExemplarsAccumulator<'a> {
+    /// Adds the exemplars of the data point with the given id.
+    pub(crate) fn append(&mut self, parent_id: u32, exemplars: &'a [Exemplar]) {
+        self.exemplars
+            .extend(exemplars.iter().map(|exemplar| (parent_id, exemplar)));
+    }
+
+    /// Returns the exemplars record batch, sorted by value and parent id. The parent ids of
+    /// consecutive exemplars with the same value are delta encoded.
+    pub(crate) fn finish(mut self) -> Result<ExemplarsBatches> {
+        if self.exemplars.is_empty() {
+            return Ok(ExemplarsBatches {
+                exemplars: None,
+                attrs: None,
+            });
+        }
+        self.exemplars.sort_by(|(a_id, a), (b_id, b)| {
+            compare_values(a.value.as_ref(), b.value.as_ref()).then(a_id.cmp(b_id))
+        });
+
+        let mut attrs = Attributes32Accumulator::default();
+        let mut ids = Vec::with_capacity(self.exemplars.len());
+        let mut next_id = 0u32;
+        for (idx, (_, exemplar)) in self.exemplars.iter().enumerate() {
+            ensure!(
+                exemplar.trace_id.is_empty() || exemplar.trace_id.len() == TRACE_ID_LEN as usize,
+                error::InvalidTraceIdSnafu {
+                    message: format!(
+                        "index = {}, exemplar trace_id = {:?}",
+                        idx, exemplar.trace_id
+                    ),
+                }
+            );
+            ensure!(
+                exemplar.span_id.is_empty() || exemplar.span_id.len() == SPAN_ID_LEN as usize,
+                error::InvalidSpanIdSnafu {
+                    message: format!("index = {}, exemplar span_id = {:?}", idx, exemplar.span_id),
+                }
+            );
+
+            if exemplar.filtered_attributes.is_empty() {
+                ids.push(None);
+            } else {
+                attrs.append(next_id, &exemplar.filtered_attributes);
+                ids.push(Some(next_id));
+                next_id = next_id
+                    .checked_add(1)
+                    .context(error::IdOverflowSnafu { name: "exemplars" })?;
+            }
+        }
+
+        let mut prev: Option<(u32, Option<&Value>)> = None;
+        let parent_ids =
+            UInt32Array::from_iter_values(self.exemplars.iter().map(|(parent_id, exemplar)| {
+                let value = exemplar.value.as_ref();
+                let delta = match prev {
+                    // the decoder delta decodes the parent ids of the exemplars without value
+                    Some((prev_id, prev_value)) if value.is_none() || prev_value == value => {
+                        parent_id.wrapping_sub(prev_id)
+                    }
+                    _ => *parent_id,
+                };
+                prev = Some((*parent_id, value.or(prev.and_then(|(_, v)| v))));
+                delta
+            }));
+        let times = TimestampNanosecondArray::from_iter_values(
+            self.exemplars
+                .iter()
+                .map(|(_, exemplar)| exemplar.time_unix_nano as i64),
+        );
+        let int_values: Int64Array = self
+            .exemplars
+            .iter()
+            .map(|(_, exemplar)| match exemplar.value {
+                Some(Value::AsInt(i)) => Some(i),
+                _ => None,
+            })
+            .collect();
+        let double_values: Float64Array = self
+            .exemplars
+            .iter()
+            .map(|(_, exemplar)| match exemplar.value {
+                Some(Value::AsDouble(d)) => Some(d),
+                _ => None,
+            })
+            .collect();
+        let span_ids = self
+            .exemplars
+            .iter()
+            .map(|(_, exemplar)| non_empty_bytes(&exemplar.span_id))
+            .collect();
+        let trace_ids = self
+            .exemplars
+            .iter()
+            .map(|(_, exemplar)| non_empty_bytes(&exemplar.trace_id))
+            .collect();
+
+        let mut columns = Columns::default();
+        columns.optional(consts::ID, Arc::new(delta_encode::<UInt32Type>(&ids)));
+        columns.required(consts::PARENT_ID, Arc::new(parent_ids));
+        columns.required(consts::TIME_UNIX_NANO, Arc::new(times));
+        columns.optional(consts::INT_VALUE, Arc::new(int_values));
+        columns.optional(consts::DOUBLE_VALUE, Arc::new(double_values));
+        // the decoder requires the span and trace ids
+        columns.required(consts::SPAN_ID, fixed_size_binary(span_ids, SPAN_ID_LEN));
+        columns.required(consts::TRACE_ID, fixed_size_binary(trace_ids, TRACE_ID_LEN));
+
+        Ok(ExemplarsBatches {
+            exemplars: Some(columns.into_record_batch()?),
+            attrs: attrs.finish()?,
+        })
+    }
+}
+
+/// Orders the exemplars by value, the exemplars without value first, then the int values,
+/// then the double ones. The decoder adds the parent id of an exemplar without value to the
+/// one of the previous exemplar, so they come first: after the exemplars with a value,
+/// their parent ids could be lower than the previous one.
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(Value::AsInt(a)), Some(Value::AsInt(b))) => a.cmp(b),
+        (Some(Value::AsDouble(a)), Some(Value::AsDouble(b))) => a.total_cmp(b),
+        (None, None) => Ordering::Equal,
+        (None, _) | (Some(Value::AsInt(_)), Some(Value::AsDouble(_))) => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Helpers assembling the columns of the produced record batches.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, ArrowPrimitiveType, FixedSizeBinaryArray, PrimitiveArray, RecordBatch,
+    StructArray,
+};
+use arrow::buffer::NullBuffer;
+use arrow::compute::cast;
+use arrow::datatypes::{ArrowNativeTypeOp, DataType, Field, FieldRef, Schema};
+use snafu::ResultExt;
+
+use crate::error::{self, Result};
+
+pub(crate) use crate::schema::definitions::DictionaryKey;
+
+/// Dictionary encodes the column with the smallest key type fitting its cardinality, up to
+/// `max_key`, like the adaptive schemas of the Go encoder. The column is left as is when its
+/// cardinality is too high.
+pub(crate) fn dictionary(array: ArrayRef, max_key: DictionaryKey) -> ArrayRef {
+    let key_types: &[DataType] = match max_key {
+        DictionaryKey::U8 => &[DataType::UInt8],
+        DictionaryKey::U16 => &[DataType::UInt8, DataType::UInt16],
+    };
+    for key_type in key_types {
+        let dict_type = DataType::Dictionary(
+            Box::new(key_type.clone()),
+            Box::new(array.data_type().clone()),
+        );
+        // the cast fails when the keys overflow, or if the values can't be dictionary encoded
+        if let Ok(dict) = cast(&array, &dict_type) {
+            return dict;
+        }
+    }
+    array
+}
+
+/// Delta encodes the ids, every id is replaced by its difference with the previous non null
+/// id. The ids must be increasing.
+pub(crate) fn delta_encode<T: ArrowPrimitiveType>(ids: &[Option<T::Native>]) -> PrimitiveArray<T> {
+    let mut prev = T::Native::default();
+    ids.iter()
+        .map(|id| {
+            id.map(|id| {
+                let delta = id.sub_wrapping(prev);
+                prev = id;
+                delta
+            })
+        })
+        .collect()
+}
+
+/// Returns `None` for an empty string, the Go encoder writes them as nulls.
+pub(crate) fn non_empty(s: &str) -> Option<&str> {
+    (!s.is_empty()).then_some(s)
+}
+
+/// Returns `None` for empty bytes, e.g. a missing trace or span id.
+pub(crate) fn non_empty_bytes(bytes: &[u8]) -> Option<&[u8]> {
+    (!bytes.is_empty()).then_some(bytes)
+}
+
+/// Returns the fixed size binary column of the values, whose sizes must have been checked.
+pub(crate) fn fixed_size_binary(values: Vec<Option<&[u8]>>, size: i32) -> ArrayRef {
+    // safety: the sizes of the values are checked by the callers
+    let array = FixedSizeBinaryArray::try_from_sparse_iter_with_size(values.into_iter(), size)
+        .expect("values of the given size");
+    Arc::new(array)
+}
+
+/// Returns `None` for a zero value, the Go encoder writes them as nulls.
+pub(crate) fn non_zero<T: Default + PartialEq>(value: T) -> Option<T> {
+    (value != T::default()).then_some(value)
+}
+
+/// Columns of a record batch or of a struct array being produced.
+#[derive(Default)]
+pub(crate) struct Columns {
+    fields: Vec<FieldRef>,
+    arrays: Vec<ArrayRef>,
+}
+
+impl Columns {
+    /// Adds a column that is always present.
+    pub(crate) fn required(&mut self, name: &str, array: ArrayRef) {
+        let nullable = array.null_count() > 0;
+        self.push(name, array, nullable);
+    }
+
+    /// Adds a column, unless all its values are null. The Go encoder also leaves these
+    /// columns out of the schema.
+    pub(crate) fn optional(&mut self, name: &str, array: ArrayRef) {
+        if array.null_count() < array.len() {
+            self.push(name, array, true);
+        }
+    }
+
+    fn push(&mut self, name: &str, array: ArrayRef, nullable: bool) {
+        self.fields.push(Arc::new(Field::new(
+            name,
+            array.data_type().clone(),
+            nullable,
+        )));
+        self.arrays.push(array);
+    }
+
+    pub(crate) fn into_record_batch(self) -> Result<RecordBatch> {
+        RecordBatch::try_new(Arc::new(Schema::new(self.fields)), self.arrays)
+            .context(error::WriteRecordBatchSnafu)
+    }
+
+    /// Returns the struct array of `len` rows made of the columns.
+    pub(crate) fn into_struct_array(
+        self,
+        len: usize,
+        nulls: Option<NullBuffer>,
+    ) -> Result<ArrayRef> {
+        if self.fields.is_empty() {
+            return Ok(Arc::new(StructArray::new_empty_fields(len, nulls)));
+        }
+        let array = StructArray::try_new(self.fields.into(), self.arrays, nulls)
+            .context(error::WriteRecordBatchSnafu)?;
+        Ok(Arc::new(array))
+    }
+}
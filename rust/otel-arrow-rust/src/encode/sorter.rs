@@ -0,0 +1,277 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sort orders of the spans, log records and metrics of the produced batches.
+//!
+//! The producers always group the entities by resource and scope, so every resource and
+//! scope is encoded once, then sort them with a [`Sorter`]. Rows with equal values in the
+//! leading columns compress better and have their ids delta encoded over smaller deltas, so
+//! the best order depends on the shape of the data, e.g. sorting the spans of a high
+//! cardinality service by trace id first rather than by name.
+
+use std::cmp::Ordering;
+
+use crate::encode::attributes::compare_values;
+use crate::otlp::attributes::store::AttributeValueType;
+use crate::proto::opentelemetry::common::v1::KeyValue;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::logs::v1::LogRecord;
+use crate::proto::opentelemetry::metrics::v1::Metric;
+use crate::proto::opentelemetry::metrics::v1::metric::Data;
+use crate::proto::opentelemetry::trace::v1::Span;
+
+/// A key the entities of a batch can be sorted by.
+pub trait SortKey {
+    /// The sorted entity.
+    type Item;
+
+    /// Compares two entities by this key.
+    fn compare(&self, a: &Self::Item, b: &Self::Item) -> Ordering;
+}
+
+/// Sorts entities by a list of keys, the later keys breaking the ties of the earlier ones.
+#[derive(Clone, Debug)]
+pub struct Sorter<K> {
+    keys: Vec<K>,
+}
+
+impl<K: SortKey> Sorter<K> {
+    /// Creates a sorter comparing the entities by the given keys, in order.
+    #[must_use]
+    pub fn new(keys: Vec<K>) -> Self {
+        Self { keys }
+    }
+
+    /// Returns the keys of the sorter.
+    #[must_use]
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// Compares two entities by the keys of the sorter.
+    #[must_use]
+    pub fn compare(&self, a: &K::Item, b: &K::Item) -> Ordering {
+        self.keys.iter().fold(Ordering::Equal, |ordering, key| {
+            ordering.then_with(|| key.compare(a, b))
+        })
+    }
+
+    /// Sorts the entities in place. The sort is stable, so the entities with equal keys
+    /// keep their order.
+    pub fn sort(&self, items: &mut [&K::Item]) {
+        items.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+/// Sorter of the spans of the traces batches.
+pub type SpanSorter = Sorter<SpanSortKey>;
+
+/// Sorter of the log records of the logs batches.
+pub type LogSorter = Sorter<LogSortKey>;
+
+/// Sorter of the metrics of the metrics batches.
+pub type MetricSorter = Sorter<MetricSortKey>;
+
+/// Key of the spans.
+#[derive(Clone, Debug)]
+pub enum SpanSortKey {
+    /// The span name.
+    Name,
+    /// The trace id.
+    TraceId,
+    /// The start time.
+    StartTime,
+    /// The span kind.
+    Kind,
+    /// The value of the span attribute with this key, the spans without it come last.
+    Attribute(String),
+    /// A custom comparison.
+    Custom(fn(&Span, &Span) -> Ordering),
+}
+
+impl SortKey for SpanSortKey {
+    type Item = Span;
+
+    fn compare(&self, a: &Span, b: &Span) -> Ordering {
+        match self {
+            Self::Name => a.name.cmp(&b.name),
+            Self::TraceId => a.trace_id.cmp(&b.trace_id),
+            Self::StartTime => a.start_time_unix_nano.cmp(&b.start_time_unix_nano),
+            Self::Kind => a.kind.cmp(&b.kind),
+            Self::Attribute(key) => compare_attributes(key, &a.attributes, &b.attributes),
+            Self::Custom(compare) => compare(a, b),
+        }
+    }
+}
+
+/// The order of the Go producer: by name, then trace id.
+impl Default for SpanSorter {
+    fn default() -> Self {
+        Self::new(vec![SpanSortKey::Name, SpanSortKey::TraceId])
+    }
+}
+
+/// Key of the log records.
+#[derive(Clone, Debug)]
+pub enum LogSortKey {
+    /// The time of the event.
+    Time,
+    /// The time the event was observed.
+    ObservedTime,
+    /// The severity number.
+    Severity,
+    /// The trace id.
+    TraceId,
+    /// The value of the log record attribute with this key, the log records without it
+    /// come last.
+    Attribute(String),
+    /// A custom comparison.
+    Custom(fn(&LogRecord, &LogRecord) -> Ordering),
+}
+
+impl SortKey for LogSortKey {
+    type Item = LogRecord;
+
+    fn compare(&self, a: &LogRecord, b: &LogRecord) -> Ordering {
+        match self {
+            Self::Time => a.time_unix_nano.cmp(&b.time_unix_nano),
+            Self::ObservedTime => a.observed_time_unix_nano.cmp(&b.observed_time_unix_nano),
+            Self::Severity => a.severity_number.cmp(&b.severity_number),
+            Self::TraceId => a.trace_id.cmp(&b.trace_id),
+            Self::Attribute(key) => compare_attributes(key, &a.attributes, &b.attributes),
+            Self::Custom(compare) => compare(a, b),
+        }
+    }
+}
+
+/// The order of the Go producer: by time.
+impl Default for LogSorter {
+    fn default() -> Self {
+        Self::new(vec![LogSortKey::Time])
+    }
+}
+
+/// Key of the metrics.
+#[derive(Clone, Debug)]
+pub enum MetricSortKey {
+    /// The metric name.
+    Name,
+    /// The metric type, gauges first, then sums, histograms, exponential histograms and
+    /// summaries.
+    Type,
+    /// The metric unit.
+    Unit,
+    /// A custom comparison.
+    Custom(fn(&Metric, &Metric) -> Ordering),
+}
+
+impl SortKey for MetricSortKey {
+    type Item = Metric;
+
+    fn compare(&self, a: &Metric, b: &Metric) -> Ordering {
+        match self {
+            Self::Name => a.name.cmp(&b.name),
+            Self::Type => metric_type(a).cmp(&metric_type(b)),
+            Self::Unit => a.unit.cmp(&b.unit),
+            Self::Custom(compare) => compare(a, b),
+        }
+    }
+}
+
+/// The order of the Go producer: by name.
+impl Default for MetricSorter {
+    fn default() -> Self {
+        Self::new(vec![MetricSortKey::Name])
+    }
+}
+
+fn metric_type(metric: &Metric) -> u8 {
+    match metric.data {
+        Some(Data::Gauge(_)) => 0,
+        Some(Data::Sum(_)) => 1,
+        Some(Data::Histogram(_)) => 2,
+        Some(Data::ExponentialHistogram(_)) => 3,
+        Some(Data::Summary(_)) => 4,
+        None => 5,
+    }
+}
+
+/// Compares the values of the attributes with the given key, by type then value, the
+/// attribute lists without the key come last.
+fn compare_attributes(key: &str, a: &[KeyValue], b: &[KeyValue]) -> Ordering {
+    fn find<'a>(key: &str, attrs: &'a [KeyValue]) -> Option<&'a Value> {
+        attrs
+            .iter()
+            .find(|kv| kv.key == key)
+            .and_then(|kv| kv.value.as_ref())
+            .and_then(|value| value.value.as_ref())
+    }
+    match (find(key, a), find(key, b)) {
+        (Some(a), Some(b)) => (AttributeValueType::from(Some(a)) as u8)
+            .cmp(&(AttributeValueType::from(Some(b)) as u8))
+            .then_with(|| compare_values(a, b)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::common::v1::AnyValue;
+
+    fn span(name: &str, trace_id: u8, service: Option<&str>) -> Span {
+        Span {
+            name: name.into(),
+            trace_id: vec![trace_id; 16],
+            attributes: service
+                .map(|service| KeyValue {
+                    key: "service".into(),
+                    value: Some(AnyValue {
+                        value: Some(Value::StringValue(service.into())),
+                    }),
+                })
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn sorted_names(sorter: &SpanSorter, spans: &[Span]) -> Vec<String> {
+        let mut spans: Vec<_> = spans.iter().collect();
+        sorter.sort(&mut spans);
+        spans.iter().map(|span| span.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_span_sorter() {
+        let spans = [
+            span("b", 1, Some("y")),
+            span("a", 2, None),
+            span("a", 1, Some("x")),
+            span("c", 0, Some("x")),
+        ];
+        assert_eq!(sorted_names(&SpanSorter::default(), &spans), vec![
+            "a", "a", "b", "c"
+        ]);
+        assert_eq!(
+            sorted_names(
+                &SpanSorter::new(vec![SpanSortKey::TraceId, SpanSortKey::Name]),
+                &spans
+            ),
+            vec!["c", "a", "b", "a"]
+        );
+        assert_eq!(
+            sorted_names(
+                &SpanSorter::new(vec![
+                    SpanSortKey::Attribute("service".into()),
+                    SpanSortKey::Custom(|a, b| b.name.cmp(&a.name)),
+                ]),
+                &spans
+            ),
+            vec!["c", "a", "b", "a"]
+        );
+    }
+}
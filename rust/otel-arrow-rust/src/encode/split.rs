@@ -0,0 +1,452 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Splitting of OTLP requests into several `BatchArrowRecords`, each staying under a
+//! maximum encoded size, e.g. the maximum gRPC message size of the receiver.
+//!
+//! The requests are split between their spans, log records or metrics, so every split
+//! holds the related data (attributes, events, links, data points and exemplars) of its
+//! entities, along with their resources and scopes, and can be decoded on its own.
+
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+
+use arrow::array::RecordBatch;
+use arrow::ipc::writer::StreamWriter;
+use prost::Message;
+use snafu::{ResultExt, ensure};
+
+use crate::cancel::CancellationToken;
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayload, ArrowPayloadType, BatchArrowRecords};
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::telemetry::{self, MetricsSink};
+
+/// Returns the schema id of a payload of the given batch, unique to the batch so the
+/// payload streams of different batches can be read by the same consumer.
+#[must_use]
+pub fn schema_id(payload_type: ArrowPayloadType, batch_id: i64) -> String {
+    format!("{}:{}", payload_type.as_str_name().to_lowercase(), batch_id)
+}
+
+/// Serializes the record batches of an OTAP batch as a `BatchArrowRecords`, main payload
+/// first. Every payload is a complete IPC stream carrying its own schema and dictionaries,
+/// see [`schema_id`].
+pub fn to_batch_arrow_records(otap_batch: &OtapBatch, batch_id: i64) -> Result<BatchArrowRecords> {
+    let mut arrow_payloads = Vec::new();
+    for payload_type in otap_batch.payload_types() {
+        // safety: payload_types only returns types that are present in the batch
+        let record_batch = otap_batch
+            .get(payload_type)
+            .expect("payload type present in batch");
+        arrow_payloads.push(to_arrow_payload(
+            payload_type as i32,
+            schema_id(payload_type, batch_id),
+            record_batch,
+        )?);
+    }
+
+    Ok(BatchArrowRecords {
+        batch_id,
+        arrow_payloads,
+        headers: Vec::new(),
+    })
+}
+
+/// Serializes a record batch as a payload of the given type, e.g. one that is not part of
+/// the OTAP specification, to be added after the payloads of a `BatchArrowRecords` and
+/// decoded by the [`PayloadDecoder`](crate::PayloadDecoder) registered for it.
+pub fn to_arrow_payload(
+    payload_type: i32,
+    schema_id: String,
+    record_batch: &RecordBatch,
+) -> Result<ArrowPayload> {
+    let mut writer = StreamWriter::try_new(Vec::new(), &record_batch.schema())
+        .context(error::WriteRecordBatchSnafu)?;
+    writer
+        .write(record_batch)
+        .context(error::WriteRecordBatchSnafu)?;
+    let record = writer.into_inner().context(error::WriteRecordBatchSnafu)?;
+    Ok(ArrowPayload {
+        schema_id,
+        r#type: payload_type,
+        record,
+    })
+}
+
+/// Splits requests into `BatchArrowRecords` whose encoded size is at most `max_bytes`.
+///
+/// The batch ids are assigned in sequence, starting at 0, across all the split requests.
+pub struct BatchSplitter {
+    max_bytes: usize,
+    next_batch_id: i64,
+    metrics_sink: Option<Arc<dyn MetricsSink>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl fmt::Debug for BatchSplitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchSplitter")
+            .field("max_bytes", &self.max_bytes)
+            .field("next_batch_id", &self.next_batch_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BatchSplitter {
+    /// Creates a splitter producing batches of at most `max_bytes` once encoded.
+    #[must_use]
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            next_batch_id: 0,
+            metrics_sink: None,
+            cancellation: None,
+        }
+    }
+
+    /// Reports the measurements of the splitter to the given sink.
+    pub fn set_metrics_sink(&mut self, sink: Arc<dyn MetricsSink>) {
+        self.metrics_sink = Some(sink);
+    }
+
+    /// Sets the token cancelling the splitting of the requests, checked between the items
+    /// of the batches produced.
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+
+    /// Splits a traces request between its spans.
+    pub fn split_traces(
+        &mut self,
+        request: &ExportTraceServiceRequest,
+    ) -> Result<Vec<BatchArrowRecords>> {
+        self.split(request)
+    }
+
+    /// Splits a logs request between its log records.
+    pub fn split_logs(
+        &mut self,
+        request: &ExportLogsServiceRequest,
+    ) -> Result<Vec<BatchArrowRecords>> {
+        self.split(request)
+    }
+
+    /// Splits a metrics request between its metrics, the data points of a metric are never
+    /// split.
+    pub fn split_metrics(
+        &mut self,
+        request: &ExportMetricsServiceRequest,
+    ) -> Result<Vec<BatchArrowRecords>> {
+        self.split(request)
+    }
+
+    fn split<R: SplittableRequest>(&mut self, request: &R) -> Result<Vec<BatchArrowRecords>> {
+        // the clock is only read when measured, it panics on wasm32-unknown-unknown
+        let start = self.metrics_sink.as_ref().map(|_| Instant::now());
+        let mut batches = Vec::new();
+        let len = request.len();
+        if len > 0 {
+            self.split_range(request, 0..len, &mut batches)?;
+        }
+
+        if let (Some(sink), Some(start)) = (&self.metrics_sink, start) {
+            let attributes = [(telemetry::SIGNAL_ATTRIBUTE, R::SIGNAL)];
+            sink.add_counter(
+                telemetry::BATCHES_ENCODED,
+                batches.len() as u64,
+                &attributes,
+            );
+            for batch in &batches {
+                sink.record_histogram(
+                    telemetry::ENCODED_BYTES,
+                    batch.encoded_len() as f64,
+                    &attributes,
+                );
+            }
+            sink.record_histogram(
+                telemetry::ENCODE_DURATION,
+                start.elapsed().as_secs_f64(),
+                &attributes,
+            );
+        }
+        Ok(batches)
+    }
+
+    /// Encodes the entities of the range as one batch, or splits the range in halves if the
+    /// batch is too large.
+    fn split_range<R: SplittableRequest>(
+        &mut self,
+        request: &R,
+        range: Range<usize>,
+        batches: &mut Vec<BatchArrowRecords>,
+    ) -> Result<()> {
+        telemetry::trace_span!(
+            "otap.encode_batch",
+            signal = R::SIGNAL,
+            batch_id = self.next_batch_id,
+            items = range.len(),
+        );
+        let otap_batch = request
+            .slice(range.clone())
+            .produce(self.cancellation.as_ref())?;
+        let batch = to_batch_arrow_records(&otap_batch, self.next_batch_id)?;
+        let size = batch.encoded_len();
+        if size <= self.max_bytes {
+            self.next_batch_id += 1;
+            batches.push(batch);
+            return Ok(());
+        }
+
+        ensure!(range.len() > 1, error::BatchTooLargeSnafu {
+            size,
+            max_size: self.max_bytes,
+        });
+        let mid = range.start + range.len() / 2;
+        self.split_range(request, range.start..mid, batches)?;
+        self.split_range(request, mid..range.end, batches)
+    }
+}
+
+/// Request that can be split between its entities.
+trait SplittableRequest: Sized {
+    /// The signal of the request, as reported to the metrics sink.
+    const SIGNAL: &'static str;
+
+    /// Returns the number of entities of the request.
+    fn len(&self) -> usize;
+
+    /// Returns the request holding the entities of the range, with their resources and
+    /// scopes.
+    fn slice(&self, range: Range<usize>) -> Self;
+
+    /// Produces the OTAP batch of the request, checking the cancellation token if any.
+    fn produce(&self, cancellation: Option<&CancellationToken>) -> Result<OtapBatch>;
+}
+
+/// Keeps the entities of the nested lists whose index, counted across all the lists, is in
+/// the range. Returns whether any entity was kept.
+fn retain_range<T>(items: &mut Vec<T>, offset: &mut usize, range: &Range<usize>) -> bool {
+    let start = *offset;
+    *offset += items.len();
+    let mut idx = start;
+    items.retain(|_| {
+        let keep = range.contains(&idx);
+        idx += 1;
+        keep
+    });
+    !items.is_empty()
+}
+
+impl SplittableRequest for ExportTraceServiceRequest {
+    const SIGNAL: &'static str = "traces";
+
+    fn len(&self) -> usize {
+        self.resource_spans
+            .iter()
+            .flat_map(|rs| &rs.scope_spans)
+            .map(|ss| ss.spans.len())
+            .sum()
+    }
+
+    fn slice(&self, range: Range<usize>) -> Self {
+        let mut request = self.clone();
+        let mut offset = 0;
+        request.resource_spans.retain_mut(|rs| {
+            rs.scope_spans
+                .retain_mut(|ss| retain_range(&mut ss.spans, &mut offset, &range));
+            !rs.scope_spans.is_empty()
+        });
+        request
+    }
+
+    fn produce(&self, cancellation: Option<&CancellationToken>) -> Result<OtapBatch> {
+        let mut producer = TracesProducer::new();
+        if let Some(token) = cancellation {
+            producer = producer.with_cancellation(token.clone());
+        }
+        producer.produce(self)
+    }
+}
+
+impl SplittableRequest for ExportLogsServiceRequest {
+    const SIGNAL: &'static str = "logs";
+
+    fn len(&self) -> usize {
+        self.resource_logs
+            .iter()
+            .flat_map(|rl| &rl.scope_logs)
+            .map(|sl| sl.log_records.len())
+            .sum()
+    }
+
+    fn slice(&self, range: Range<usize>) -> Self {
+        let mut request = self.clone();
+        let mut offset = 0;
+        request.resource_logs.retain_mut(|rl| {
+            rl.scope_logs
+                .retain_mut(|sl| retain_range(&mut sl.log_records, &mut offset, &range));
+            !rl.scope_logs.is_empty()
+        });
+        request
+    }
+
+    fn produce(&self, cancellation: Option<&CancellationToken>) -> Result<OtapBatch> {
+        let mut producer = LogsProducer::new();
+        if let Some(token) = cancellation {
+            producer = producer.with_cancellation(token.clone());
+        }
+        producer.produce(self)
+    }
+}
+
+impl SplittableRequest for ExportMetricsServiceRequest {
+    const SIGNAL: &'static str = "metrics";
+
+    fn len(&self) -> usize {
+        self.resource_metrics
+            .iter()
+            .flat_map(|rm| &rm.scope_metrics)
+            .map(|sm| sm.metrics.len())
+            .sum()
+    }
+
+    fn slice(&self, range: Range<usize>) -> Self {
+        let mut request = self.clone();
+        let mut offset = 0;
+        request.resource_metrics.retain_mut(|rm| {
+            rm.scope_metrics
+                .retain_mut(|sm| retain_range(&mut sm.metrics, &mut offset, &range));
+            !rm.scope_metrics.is_empty()
+        });
+        request
+    }
+
+    fn produce(&self, cancellation: Option<&CancellationToken>) -> Result<OtapBatch> {
+        let mut producer = MetricsProducer::new();
+        if let Some(token) = cancellation {
+            producer = producer.with_cancellation(token.clone());
+        }
+        producer.produce(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::Consumer;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    fn request(len: usize) -> ExportLogsServiceRequest {
+        let log_record = |i: usize| LogRecord {
+            time_unix_nano: i as u64 + 1,
+            body: Some(AnyValue {
+                value: Some(Value::StringValue(format!("log record {i:04} ").repeat(32))),
+            }),
+            attributes: vec![KeyValue {
+                key: "i".into(),
+                value: Some(AnyValue {
+                    value: Some(Value::IntValue(i as i64)),
+                }),
+            }],
+            ..Default::default()
+        };
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![
+                    ScopeLogs {
+                        log_records: (0..len / 2).map(log_record).collect(),
+                        ..Default::default()
+                    },
+                    ScopeLogs {
+                        log_records: (len / 2..len).map(log_record).collect(),
+                        schema_url: "https://scope".into(),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_split_logs() {
+        let request = request(64);
+        let whole = BatchSplitter::new(usize::MAX).split_logs(&request).unwrap();
+        assert_eq!(whole.len(), 1);
+
+        let max_bytes = whole[0].encoded_len() / 3;
+        let mut splitter = BatchSplitter::new(max_bytes);
+        let batches = splitter.split_logs(&request).unwrap();
+        assert!(batches.len() > 3);
+        assert!(batches.iter().all(|batch| batch.encoded_len() <= max_bytes));
+
+        let mut consumer = Consumer::default();
+        let mut log_records = Vec::new();
+        for (idx, mut batch) in batches.into_iter().enumerate() {
+            assert_eq!(batch.batch_id, idx as i64);
+            let decoded = consumer.consume_logs_batches(&mut batch).unwrap();
+            log_records.extend(
+                decoded
+                    .resource_logs
+                    .into_iter()
+                    .flat_map(|rl| rl.scope_logs)
+                    .flat_map(|sl| sl.log_records),
+            );
+        }
+        let expected: Vec<_> = request
+            .resource_logs
+            .into_iter()
+            .flat_map(|rl| rl.scope_logs)
+            .flat_map(|sl| sl.log_records)
+            .collect();
+        assert_eq!(log_records, expected);
+    }
+
+    #[test]
+    fn test_split_too_large() {
+        let result = BatchSplitter::new(16).split_logs(&request(2));
+        assert!(matches!(result, Err(error::Error::BatchTooLarge { .. })));
+    }
+
+    #[test]
+    fn test_split_cancelled() {
+        let token = CancellationToken::new();
+        let mut splitter = BatchSplitter::new(usize::MAX);
+        splitter.set_cancellation(token.clone());
+        assert_eq!(splitter.split_logs(&request(4)).unwrap().len(), 1);
+
+        token.cancel();
+        let error = splitter.split_logs(&request(4)).unwrap_err();
+        assert!(matches!(error, error::Error::Cancelled { .. }));
+    }
+
+    #[test]
+    fn test_deterministic_batches() {
+        use crate::encode::TracesProducer;
+        use crate::otlp::traces::traces_from;
+        use crate::test_util::workloads::traces_batch;
+
+        // the spans share their names and attribute keys, the sorts have many ties
+        let request = traces_from(traces_batch(64, 4)).unwrap();
+        let encode = || {
+            let otap_batch = TracesProducer::new().produce(&request).unwrap();
+            to_batch_arrow_records(&otap_batch, 7)
+                .unwrap()
+                .encode_to_vec()
+        };
+        let bytes = encode();
+        for _ in 0..4 {
+            assert_eq!(encode(), bytes);
+        }
+    }
+}
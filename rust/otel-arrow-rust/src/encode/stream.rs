@@ -0,0 +1,349 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serialization of the OTAP batches of a stream, sending the schemas and dictionaries of
+//! the payloads once for the whole stream rather than with every batch, see
+//! [`to_batch_arrow_records`](crate::encode::split::to_batch_arrow_records).
+//!
+//! The stream is divided in epochs. Within an epoch, the payloads of a type keep their
+//! schema id and the IPC stream started by the first of them, so the receiver keeps their
+//! schema and dictionaries. A payload whose schema changed, e.g. because optional columns
+//! appeared, starts a new IPC stream with a new schema id. [`StreamEncoder::reset`] starts a
+//! new epoch: the next batch sends every payload with a new schema id, with its schema and
+//! dictionaries in full, so the receivers drop what they kept for the previous epoch. Hosts
+//! reset the stream to bound the dictionaries kept by the receivers, or to recover from a
+//! suspected desync.
+//!
+//! Logs, metrics and traces can be multiplexed over the same stream, each signal in its own
+//! sub-stream with its own payload streams, the way the [`Consumer`](crate::Consumer) tracks
+//! them on the receiving side. [`StreamEncoder::reset_signal`] starts a new epoch for the
+//! sub-stream of one signal, the others keeping their schemas and dictionaries.
+
+use std::collections::{BTreeMap, HashMap};
+
+use arrow::datatypes::SchemaRef;
+
+use crate::compression::{PayloadCompression, PayloadWriter};
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayload, ArrowPayloadType, BatchArrowRecords};
+
+/// Serializes the OTAP batches of a stream, see the module documentation.
+///
+/// The batch ids are assigned in sequence across the signals, starting at 0, and are not
+/// reset with the epochs.
+#[derive(Default)]
+pub struct StreamEncoder {
+    compression: PayloadCompression,
+    delta_dictionaries: bool,
+    epoch: u64,
+    next_batch_id: i64,
+    next_stream: u64,
+    sub_streams: BTreeMap<ArrowPayloadType, SubStream>,
+}
+
+/// The payload streams of a signal, keyed by the main payload type of its batches.
+#[derive(Default)]
+struct SubStream {
+    epoch: u64,
+    batches: u64,
+    streams: HashMap<ArrowPayloadType, PayloadStream>,
+}
+
+/// The IPC stream of a payload type of a signal in the current epoch.
+struct PayloadStream {
+    schema_id: String,
+    schema: SchemaRef,
+    writer: PayloadWriter,
+}
+
+impl StreamEncoder {
+    /// Creates an encoder writing uncompressed payloads and full dictionaries.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the codec compressing the payloads.
+    #[must_use]
+    pub fn with_compression(mut self, compression: PayloadCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sends the dictionaries as deltas, see
+    /// [`PayloadWriter::try_new_with_delta_dictionaries`].
+    #[must_use]
+    pub fn with_delta_dictionaries(mut self, delta_dictionaries: bool) -> Self {
+        self.delta_dictionaries = delta_dictionaries;
+        self
+    }
+
+    /// Returns the current epoch, 0 until the first reset.
+    #[must_use]
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Starts a new epoch: the next batch sends its schemas and dictionaries in full, with
+    /// new schema ids.
+    pub fn reset(&mut self) {
+        self.epoch += 1;
+        self.sub_streams.clear();
+    }
+
+    /// Starts a new epoch for the sub-stream of the signal whose batches have the main
+    /// payload type `signal`: its next batch sends its schemas and dictionaries in full,
+    /// with new schema ids, while the other signals keep theirs.
+    pub fn reset_signal(&mut self, signal: ArrowPayloadType) {
+        self.epoch += 1;
+        let _ = self.sub_streams.remove(&signal);
+    }
+
+    /// Returns the main payload types of the signals encoded since their last reset, in the
+    /// order of the payload types.
+    pub fn signals(&self) -> impl Iterator<Item = ArrowPayloadType> + '_ {
+        self.sub_streams.keys().copied()
+    }
+
+    /// Returns the epoch of the sub-stream of the signal, `None` if no batch of the signal
+    /// was encoded since its last reset.
+    #[must_use]
+    pub fn signal_epoch(&self, signal: ArrowPayloadType) -> Option<u64> {
+        self.sub_streams
+            .get(&signal)
+            .map(|sub_stream| sub_stream.epoch)
+    }
+
+    /// Returns the number of batches of the signal encoded since its last reset.
+    #[must_use]
+    pub fn signal_batches(&self, signal: ArrowPayloadType) -> u64 {
+        self.sub_streams
+            .get(&signal)
+            .map_or(0, |sub_stream| sub_stream.batches)
+    }
+
+    /// Serializes the batch, main payload first.
+    pub fn encode(&mut self, otap_batch: &OtapBatch) -> Result<BatchArrowRecords> {
+        let main_payload_type = otap_batch.main_payload_type();
+        let epoch = self.epoch;
+        let mut sub_stream = self
+            .sub_streams
+            .remove(&main_payload_type)
+            .unwrap_or(SubStream {
+                epoch,
+                ..SubStream::default()
+            });
+        let arrow_payloads = self.encode_payloads(&mut sub_stream, otap_batch);
+        let _ = self.sub_streams.insert(main_payload_type, sub_stream);
+        let arrow_payloads = arrow_payloads?;
+
+        let batch_id = self.next_batch_id;
+        self.next_batch_id += 1;
+        Ok(BatchArrowRecords {
+            batch_id,
+            arrow_payloads,
+            headers: Vec::new(),
+        })
+    }
+
+    fn encode_payloads(
+        &mut self,
+        sub_stream: &mut SubStream,
+        otap_batch: &OtapBatch,
+    ) -> Result<Vec<ArrowPayload>> {
+        let mut arrow_payloads = Vec::new();
+        for payload_type in otap_batch.payload_types() {
+            // safety: payload_types only returns types that are present in the batch
+            let record_batch = otap_batch
+                .get(payload_type)
+                .expect("payload type present in batch");
+            let schema = record_batch.schema();
+            let stream = match sub_stream.streams.get_mut(&payload_type) {
+                Some(stream) if stream.schema.fields() == schema.fields() => stream,
+                _ => {
+                    let stream = self.new_stream(sub_stream.epoch, payload_type, schema)?;
+                    sub_stream
+                        .streams
+                        .entry(payload_type)
+                        .insert_entry(stream)
+                        .into_mut()
+                }
+            };
+            arrow_payloads.push(ArrowPayload {
+                schema_id: stream.schema_id.clone(),
+                r#type: payload_type as i32,
+                record: stream.writer.write(record_batch)?,
+            });
+        }
+        sub_stream.batches += 1;
+        Ok(arrow_payloads)
+    }
+
+    fn new_stream(
+        &mut self,
+        epoch: u64,
+        payload_type: ArrowPayloadType,
+        schema: SchemaRef,
+    ) -> Result<PayloadStream> {
+        let writer = if self.delta_dictionaries {
+            PayloadWriter::try_new_with_delta_dictionaries(&schema, self.compression)?
+        } else {
+            PayloadWriter::try_new(&schema, self.compression)?
+        };
+        let schema_id = format!(
+            "{}:{}.{}",
+            payload_type.as_str_name().to_lowercase(),
+            epoch,
+            self.next_stream
+        );
+        self.next_stream += 1;
+        Ok(PayloadStream {
+            schema_id,
+            schema,
+            writer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use prost::Message;
+
+    use crate::decode::decoder::Consumer;
+    use crate::decode::schema_registry::SchemaEvent;
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_stream_encoder_epochs() {
+        let otap_batch = traces_batch(8, 2);
+        let expected = Consumer::default()
+            .consume_batches(&mut to_batch_arrow_records(&otap_batch, 0).unwrap())
+            .unwrap();
+
+        let mut encoder = StreamEncoder::new().with_delta_dictionaries(true);
+        let mut consumer = Consumer::default();
+        let mut batches = Vec::new();
+        for i in 0..4 {
+            if i == 2 {
+                encoder.reset();
+            }
+            let mut records = encoder.encode(&otap_batch).unwrap();
+            assert_eq!(records.batch_id, i);
+            batches.push(records.clone());
+            assert_eq!(consumer.consume_batches(&mut records).unwrap(), expected);
+        }
+        assert_eq!(encoder.epoch(), 1);
+
+        let schema_ids = |records: &BatchArrowRecords| {
+            records
+                .arrow_payloads
+                .iter()
+                .map(|payload| payload.schema_id.clone())
+                .collect::<Vec<_>>()
+        };
+        let size = |records: &BatchArrowRecords| {
+            records
+                .arrow_payloads
+                .iter()
+                .map(|payload| payload.record.len())
+                .sum::<usize>()
+        };
+        // the schemas and dictionaries are sent again after the reset
+        assert_eq!(schema_ids(&batches[0]), schema_ids(&batches[1]));
+        assert_ne!(schema_ids(&batches[1]), schema_ids(&batches[2]));
+        assert!(schema_ids(&batches[2])[0].starts_with("spans:1."));
+        assert!(size(&batches[1]) < size(&batches[0]));
+        assert_eq!(size(&batches[2]), size(&batches[0]));
+        let resets = consumer
+            .take_schema_events()
+            .into_iter()
+            .filter(|event| matches!(event, SchemaEvent::Reset { .. }))
+            .count();
+        assert_eq!(resets, batches[2].arrow_payloads.len());
+    }
+
+    #[test]
+    fn test_stream_encoder_deterministic() {
+        // two encoders send the same bytes for the same batches, with the delta dictionaries
+        // and the sub-streams of the signals
+        let otap_batch = traces_batch(64, 4);
+        let encode = || {
+            let mut encoder = StreamEncoder::new().with_delta_dictionaries(true);
+            (0..3)
+                .map(|_| encoder.encode(&otap_batch).unwrap().encode_to_vec())
+                .collect::<Vec<_>>()
+        };
+        let bytes = encode();
+        for _ in 0..4 {
+            assert_eq!(encode(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_stream_encoder_signals() {
+        use crate::datagen::{DatagenConfig, Generator};
+        use crate::encode::LogsProducer;
+
+        let mut generator = Generator::new(DatagenConfig::default());
+        let logs = LogsProducer::new()
+            .produce(&generator.logs_request(8))
+            .unwrap();
+        let traces = traces_batch(8, 2);
+        let decode = |otap_batch: &OtapBatch| {
+            Consumer::default()
+                .consume_batches(&mut to_batch_arrow_records(otap_batch, 0).unwrap())
+                .unwrap()
+        };
+        let (expected_logs, expected_traces) = (decode(&logs), decode(&traces));
+
+        // the signals are multiplexed over one stream, each in its own sub-stream
+        let mut encoder = StreamEncoder::new().with_delta_dictionaries(true);
+        let mut consumer = Consumer::default();
+        let mut schema_ids = Vec::new();
+        for i in 0..3 {
+            if i == 2 {
+                encoder.reset_signal(ArrowPayloadType::Logs);
+                assert_eq!(encoder.signal_batches(ArrowPayloadType::Logs), 0);
+            }
+            let mut logs_records = encoder.encode(&logs).unwrap();
+            let mut traces_records = encoder.encode(&traces).unwrap();
+            assert_eq!(traces_records.batch_id, logs_records.batch_id + 1);
+            schema_ids.push((
+                logs_records.arrow_payloads[0].schema_id.clone(),
+                traces_records.arrow_payloads[0].schema_id.clone(),
+            ));
+            assert_eq!(
+                consumer.consume_batches(&mut logs_records).unwrap(),
+                expected_logs
+            );
+            assert_eq!(
+                consumer.consume_batches(&mut traces_records).unwrap(),
+                expected_traces
+            );
+        }
+
+        let signals: Vec<_> = encoder.signals().collect();
+        assert_eq!(signals, vec![
+            ArrowPayloadType::Logs,
+            ArrowPayloadType::Spans
+        ]);
+        assert_eq!(encoder.signal_epoch(ArrowPayloadType::Logs), Some(1));
+        assert_eq!(encoder.signal_epoch(ArrowPayloadType::Spans), Some(0));
+        assert_eq!(
+            encoder.signal_epoch(ArrowPayloadType::UnivariateMetrics),
+            None
+        );
+        assert_eq!(encoder.signal_batches(ArrowPayloadType::Logs), 1);
+        assert_eq!(encoder.signal_batches(ArrowPayloadType::Spans), 3);
+
+        // only the logs were sent with new schema ids after their reset
+        assert_eq!(schema_ids[0], schema_ids[1]);
+        assert_ne!(schema_ids[1].0, schema_ids[2].0);
+        assert!(schema_ids[2].0.starts_with("logs:1."));
+        assert_eq!(schema_ids[1].1, schema_ids[2].1);
+    }
+}
@@ -0,0 +1,710 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Production of the OTAP traces batches.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use arrow::array::{
+    ArrayRef, DurationMillisecondArray, Int32Array, RecordBatch, StringArray,
+    TimestampNanosecondArray, UInt16Array, UInt32Array,
+};
+use arrow::buffer::NullBuffer;
+use arrow::datatypes::{UInt16Type, UInt32Type};
+use snafu::{OptionExt, ensure};
+
+use crate::cancel::{CancellationToken, check_cancelled};
+use crate::encode::attributes::{Attributes16Accumulator, Attributes32Accumulator};
+use crate::encode::cardinality::CardinalityTracker;
+use crate::encode::common::{ResourceScopeBuilder, resource_key, scope_key};
+use crate::encode::record::{
+    Columns, DictionaryKey, delta_encode, dictionary, fixed_size_binary, non_empty,
+    non_empty_bytes, non_zero,
+};
+use crate::encode::sorter::SpanSorter;
+use crate::error::{self, Result};
+use crate::memory::{MemoryPool, reserve};
+use crate::otap::{OtapBatch, Traces};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::trace::v1::span::{Event, Link};
+use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+use crate::schema::consts;
+
+const TRACE_ID_LEN: i32 = 16;
+const SPAN_ID_LEN: i32 = 8;
+
+/// Produces the OTAP batches of trace requests.
+///
+/// The batches are produced the way the Go producer does with its default configuration:
+/// - spans are sorted by resource, scope, then name and trace id unless another
+///   [`SpanSorter`] is set, the resources and scopes that
+///   are equal are encoded once,
+/// - events are sorted by name and span, links by trace id and span, the span ids of
+///   consecutive events with the same name, or links with the same trace id, are delta
+///   encoded,
+/// - attributes are sorted by type, key, value and parent id, the parent ids of
+///   consecutive attributes with the same key and value are delta encoded,
+/// - the ids of resources, scopes, spans, events and links are delta encoded, and the spans,
+///   events and links without related data have no id.
+///
+/// Zero numbers and empty strings are written as nulls, and the columns only holding nulls
+/// are left out of the batches.
+#[derive(Debug, Default)]
+pub struct TracesProducer {
+    sorter: SpanSorter,
+    cancellation: Option<CancellationToken>,
+    memory_pool: Option<Arc<dyn MemoryPool>>,
+    cardinality_tracker: Option<Arc<CardinalityTracker>>,
+}
+
+/// A span along with the resource and scope it belongs to.
+struct FlattenedSpan<'a> {
+    resource_key: &'a str,
+    resource_spans: &'a ResourceSpans,
+    scope_key: &'a str,
+    scope_spans: &'a ScopeSpans,
+    span: &'a Span,
+}
+
+impl TracesProducer {
+    /// Creates a new producer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a producer sorting the spans of each scope with the given sorter.
+    #[must_use]
+    pub fn with_sorter(sorter: SpanSorter) -> Self {
+        Self {
+            sorter,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the token cancelling the production of the batches, checked between spans.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets the pool the memory of the batches is reserved from, see [`crate::memory`].
+    #[must_use]
+    pub fn with_memory_pool(mut self, pool: Arc<dyn MemoryPool>) -> Self {
+        self.memory_pool = Some(pool);
+        self
+    }
+
+    /// Sets the tracker fed the attributes of the requests, see [`crate::encode::cardinality`].
+    #[must_use]
+    pub fn with_cardinality_tracker(mut self, tracker: Arc<CardinalityTracker>) -> Self {
+        self.cardinality_tracker = Some(tracker);
+        self
+    }
+
+    /// Produces the OTAP batch of the request.
+    pub fn produce(&self, request: &ExportTraceServiceRequest) -> Result<OtapBatch> {
+        if let Some(tracker) = &self.cardinality_tracker {
+            tracker.observe_traces(request, Instant::now());
+        }
+        let keys: Vec<(String, Vec<String>)> = request
+            .resource_spans
+            .iter()
+            .map(|resource_spans| {
+                let scope_keys = resource_spans
+                    .scope_spans
+                    .iter()
+                    .map(|scope_spans| {
+                        scope_key(scope_spans.scope.as_ref(), &scope_spans.schema_url)
+                    })
+                    .collect();
+                (
+                    resource_key(resource_spans.resource.as_ref(), &resource_spans.schema_url),
+                    scope_keys,
+                )
+            })
+            .collect();
+
+        let mut spans = Vec::new();
+        for (resource_spans, (resource_key, scope_keys)) in request.resource_spans.iter().zip(&keys)
+        {
+            for (scope_spans, scope_key) in resource_spans.scope_spans.iter().zip(scope_keys) {
+                spans.extend(scope_spans.spans.iter().map(|span| FlattenedSpan {
+                    resource_key,
+                    resource_spans,
+                    scope_key,
+                    scope_spans,
+                    span,
+                }));
+            }
+        }
+        spans.sort_by(|a, b| {
+            a.resource_key
+                .cmp(b.resource_key)
+                .then_with(|| a.scope_key.cmp(b.scope_key))
+                .then_with(|| self.sorter.compare(a.span, b.span))
+        });
+
+        let mut span_attrs = Attributes16Accumulator::default();
+        let mut events = Vec::new();
+        let mut links = Vec::new();
+
+        let mut resources_scopes = ResourceScopeBuilder::default();
+        let mut next_span_id = 0u32;
+
+        let mut ids = Vec::with_capacity(spans.len());
+        let mut schema_urls = Vec::with_capacity(spans.len());
+        let mut start_times = Vec::with_capacity(spans.len());
+        let mut durations = Vec::with_capacity(spans.len());
+        let mut trace_ids = Vec::with_capacity(spans.len());
+        let mut span_ids = Vec::with_capacity(spans.len());
+        let mut trace_states = Vec::with_capacity(spans.len());
+        let mut parent_span_ids = Vec::with_capacity(spans.len());
+        let mut names = Vec::with_capacity(spans.len());
+        let mut kinds = Vec::with_capacity(spans.len());
+        let mut flags = Vec::with_capacity(spans.len());
+        let mut dropped_attributes_counts = Vec::with_capacity(spans.len());
+        let mut dropped_events_counts = Vec::with_capacity(spans.len());
+        let mut dropped_links_counts = Vec::with_capacity(spans.len());
+        let mut statuses = Vec::with_capacity(spans.len());
+
+        for (idx, flattened) in spans.iter().enumerate() {
+            check_cancelled(self.cancellation.as_ref())?;
+            let span = flattened.span;
+
+            resources_scopes.append(
+                flattened.resource_key,
+                flattened.resource_spans.resource.as_ref(),
+                &flattened.resource_spans.schema_url,
+                flattened.scope_key,
+                flattened.scope_spans.scope.as_ref(),
+            )?;
+            schema_urls.push(non_empty(&flattened.scope_spans.schema_url));
+
+            if span.attributes.is_empty() && span.events.is_empty() && span.links.is_empty() {
+                ids.push(None);
+            } else {
+                let id = u16::try_from(next_span_id)
+                    .ok()
+                    .context(error::IdOverflowSnafu { name: "spans" })?;
+                next_span_id += 1;
+                ids.push(Some(id));
+
+                span_attrs.append(id, &span.attributes);
+                events.extend(span.events.iter().map(|event| (id, event)));
+                links.extend(span.links.iter().map(|link| (id, link)));
+            }
+
+            ensure!(
+                span.trace_id.len() == TRACE_ID_LEN as usize,
+                error::InvalidTraceIdSnafu {
+                    message: format!("index = {}, trace_id = {:?}", idx, span.trace_id),
+                }
+            );
+            ensure!(
+                span.span_id.len() == SPAN_ID_LEN as usize,
+                error::InvalidSpanIdSnafu {
+                    message: format!("index = {}, span_id = {:?}", idx, span.span_id),
+                }
+            );
+            ensure!(
+                span.parent_span_id.is_empty() || span.parent_span_id.len() == SPAN_ID_LEN as usize,
+                error::InvalidSpanIdSnafu {
+                    message: format!(
+                        "index = {}, parent_span_id = {:?}",
+                        idx, span.parent_span_id
+                    ),
+                }
+            );
+
+            start_times.push(span.start_time_unix_nano as i64);
+            // the duration column holds nanoseconds even though it is typed as milliseconds,
+            // same as the decoder.
+            durations.push(
+                span.end_time_unix_nano
+                    .wrapping_sub(span.start_time_unix_nano) as i64,
+            );
+            trace_ids.push(Some(span.trace_id.as_slice()));
+            span_ids.push(Some(span.span_id.as_slice()));
+            trace_states.push(non_empty(&span.trace_state));
+            parent_span_ids.push(non_empty_bytes(&span.parent_span_id));
+            names.push(non_empty(&span.name));
+            kinds.push(non_zero(span.kind));
+            flags.push(non_zero(span.flags));
+            dropped_attributes_counts.push(non_zero(span.dropped_attributes_count));
+            dropped_events_counts.push(non_zero(span.dropped_events_count));
+            dropped_links_counts.push(non_zero(span.dropped_links_count));
+            statuses.push(span.status.as_ref());
+        }
+
+        let mut columns = Columns::default();
+        columns.optional(consts::ID, Arc::new(delta_encode::<UInt16Type>(&ids)));
+        let resources_scopes = resources_scopes.finish()?;
+        columns.required(consts::RESOURCE, resources_scopes.resource);
+        columns.required(consts::SCOPE, resources_scopes.scope);
+        columns.optional(
+            consts::SCHEMA_URL,
+            dictionary(Arc::new(StringArray::from(schema_urls)), DictionaryKey::U8),
+        );
+        columns.required(
+            consts::START_TIME_UNIX_NANO,
+            Arc::new(TimestampNanosecondArray::from(start_times)),
+        );
+        columns.required(
+            consts::DURATION_TIME_UNIX_NANO,
+            dictionary(
+                Arc::new(DurationMillisecondArray::from(durations)),
+                DictionaryKey::U8,
+            ),
+        );
+        columns.required(consts::TRACE_ID, fixed_size_binary(trace_ids, TRACE_ID_LEN));
+        columns.required(consts::SPAN_ID, fixed_size_binary(span_ids, SPAN_ID_LEN));
+        columns.optional(
+            consts::TRACE_STATE,
+            dictionary(Arc::new(StringArray::from(trace_states)), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::PARENT_SPAN_ID,
+            fixed_size_binary(parent_span_ids, SPAN_ID_LEN),
+        );
+        columns.required(
+            consts::NAME,
+            dictionary(Arc::new(StringArray::from(names)), DictionaryKey::U8),
+        );
+        columns.optional(
+            consts::KIND,
+            dictionary(Arc::new(Int32Array::from(kinds)), DictionaryKey::U8),
+        );
+        columns.optional(consts::FLAGS, Arc::new(UInt32Array::from(flags)));
+        columns.optional(
+            consts::DROPPED_ATTRIBUTES_COUNT,
+            Arc::new(UInt32Array::from(dropped_attributes_counts)),
+        );
+        columns.optional(
+            consts::DROPPED_EVENTS_COUNT,
+            Arc::new(UInt32Array::from(dropped_events_counts)),
+        );
+        columns.optional(
+            consts::DROPPED_LINKS_COUNT,
+            Arc::new(UInt32Array::from(dropped_links_counts)),
+        );
+        columns.optional(consts::STATUS, status_column(&statuses)?);
+
+        let mut event_attrs = Attributes32Accumulator::default();
+        let mut link_attrs = Attributes32Accumulator::default();
+        let events = events_record_batch(events, &mut event_attrs)?;
+        let links = links_record_batch(links, &mut link_attrs)?;
+
+        let mut otap_batch = OtapBatch::Traces(Traces::default());
+        otap_batch.set(ArrowPayloadType::Spans, columns.into_record_batch()?);
+        let related = [
+            (
+                ArrowPayloadType::ResourceAttrs,
+                resources_scopes.resource_attrs,
+            ),
+            (ArrowPayloadType::ScopeAttrs, resources_scopes.scope_attrs),
+            (ArrowPayloadType::SpanAttrs, span_attrs.finish()?),
+            (ArrowPayloadType::SpanEvents, events),
+            (ArrowPayloadType::SpanEventAttrs, event_attrs.finish()?),
+            (ArrowPayloadType::SpanLinks, links),
+            (ArrowPayloadType::SpanLinkAttrs, link_attrs.finish()?),
+        ];
+        for (payload_type, record_batch) in related {
+            if let Some(record_batch) = record_batch {
+                otap_batch.set(payload_type, record_batch);
+            }
+        }
+
+        let reservation = reserve(self.memory_pool.as_ref(), otap_batch.memory_size())?;
+        otap_batch.set_reservation(reservation);
+        Ok(otap_batch)
+    }
+}
+
+fn status_column(statuses: &[Option<&Status>]) -> Result<ArrayRef> {
+    let codes: Int32Array = statuses
+        .iter()
+        .map(|status| status.and_then(|s| non_zero(s.code)))
+        .collect();
+    let messages: StringArray = statuses
+        .iter()
+        .map(|status| status.and_then(|s| non_empty(&s.message)))
+        .collect();
+
+    let mut columns = Columns::default();
+    columns.optional(
+        consts::STATUS_CODE,
+        dictionary(Arc::new(codes), DictionaryKey::U8),
+    );
+    columns.optional(
+        consts::STATUS_MESSAGE,
+        dictionary(Arc::new(messages), DictionaryKey::U8),
+    );
+    columns.into_struct_array(
+        statuses.len(),
+        Some(NullBuffer::from_iter(statuses.iter().map(Option::is_some))),
+    )
+}
+
+/// Returns the span events record batch, the attributes of the events are added to
+/// `attrs`.
+fn events_record_batch<'a>(
+    mut events: Vec<(u16, &'a Event)>,
+    attrs: &mut Attributes32Accumulator<'a>,
+) -> Result<Option<RecordBatch>> {
+    if events.is_empty() {
+        return Ok(None);
+    }
+    events.sort_by(|(a_id, a), (b_id, b)| a.name.cmp(&b.name).then(a_id.cmp(b_id)));
+
+    let mut ids = Vec::with_capacity(events.len());
+    let mut next_event_id = 0u32;
+    for (_, event) in &events {
+        if event.attributes.is_empty() {
+            ids.push(None);
+        } else {
+            attrs.append(next_event_id, &event.attributes);
+            ids.push(Some(next_event_id));
+            next_event_id = next_event_id
+                .checked_add(1)
+                .context(error::IdOverflowSnafu {
+                    name: "span events",
+                })?;
+        }
+    }
+
+    let mut prev: Option<(u16, &str)> = None;
+    let parent_ids = UInt16Array::from_iter_values(events.iter().map(|(parent_id, event)| {
+        let delta = match prev {
+            Some((prev_id, prev_name)) if prev_name == event.name => parent_id - prev_id,
+            _ => *parent_id,
+        };
+        prev = Some((*parent_id, &event.name));
+        delta
+    }));
+    let times = TimestampNanosecondArray::from_iter_values(
+        events.iter().map(|(_, event)| event.time_unix_nano as i64),
+    );
+    let names: StringArray = events
+        .iter()
+        .map(|(_, event)| non_empty(&event.name))
+        .collect();
+    let dropped_attributes_counts: UInt32Array = events
+        .iter()
+        .map(|(_, event)| non_zero(event.dropped_attributes_count))
+        .collect();
+
+    let mut columns = Columns::default();
+    columns.optional(consts::ID, Arc::new(delta_encode::<UInt32Type>(&ids)));
+    columns.required(consts::PARENT_ID, Arc::new(parent_ids));
+    columns.optional(consts::TIME_UNIX_NANO, Arc::new(times));
+    columns.required(consts::NAME, dictionary(Arc::new(names), DictionaryKey::U8));
+    columns.optional(
+        consts::DROPPED_ATTRIBUTES_COUNT,
+        Arc::new(dropped_attributes_counts),
+    );
+    columns.into_record_batch().map(Some)
+}
+
+/// Returns the span links record batch, the attributes of the links are added to `attrs`.
+fn links_record_batch<'a>(
+    mut links: Vec<(u16, &'a Link)>,
+    attrs: &mut Attributes32Accumulator<'a>,
+) -> Result<Option<RecordBatch>> {
+    if links.is_empty() {
+        return Ok(None);
+    }
+    links.sort_by(|(a_id, a), (b_id, b)| a.trace_id.cmp(&b.trace_id).then(a_id.cmp(b_id)));
+
+    let mut ids = Vec::with_capacity(links.len());
+    let mut next_link_id = 0u32;
+    for (idx, (_, link)) in links.iter().enumerate() {
+        ensure!(
+            link.trace_id.is_empty() || link.trace_id.len() == TRACE_ID_LEN as usize,
+            error::InvalidTraceIdSnafu {
+                message: format!("index = {}, link trace_id = {:?}", idx, link.trace_id),
+            }
+        );
+        ensure!(
+            link.span_id.is_empty() || link.span_id.len() == SPAN_ID_LEN as usize,
+            error::InvalidSpanIdSnafu {
+                message: format!("index = {}, link span_id = {:?}", idx, link.span_id),
+            }
+        );
+
+        if link.attributes.is_empty() {
+            ids.push(None);
+        } else {
+            attrs.append(next_link_id, &link.attributes);
+            ids.push(Some(next_link_id));
+            next_link_id = next_link_id
+                .checked_add(1)
+                .context(error::IdOverflowSnafu { name: "span links" })?;
+        }
+    }
+
+    let mut prev: Option<(u16, &[u8])> = None;
+    let parent_ids = UInt16Array::from_iter_values(links.iter().map(|(parent_id, link)| {
+        let delta = match prev {
+            Some((prev_id, prev_trace_id)) if prev_trace_id == link.trace_id => parent_id - prev_id,
+            _ => *parent_id,
+        };
+        prev = Some((*parent_id, &link.trace_id));
+        delta
+    }));
+    let trace_ids = links
+        .iter()
+        .map(|(_, link)| non_empty_bytes(&link.trace_id))
+        .collect();
+    let span_ids = links
+        .iter()
+        .map(|(_, link)| non_empty_bytes(&link.span_id))
+        .collect();
+    let trace_states: StringArray = links
+        .iter()
+        .map(|(_, link)| non_empty(&link.trace_state))
+        .collect();
+    let flags: UInt32Array = links.iter().map(|(_, link)| non_zero(link.flags)).collect();
+    let dropped_attributes_counts: UInt32Array = links
+        .iter()
+        .map(|(_, link)| non_zero(link.dropped_attributes_count))
+        .collect();
+
+    let mut columns = Columns::default();
+    columns.optional(consts::ID, Arc::new(delta_encode::<UInt32Type>(&ids)));
+    columns.required(consts::PARENT_ID, Arc::new(parent_ids));
+    columns.optional(
+        consts::TRACE_ID,
+        dictionary(
+            fixed_size_binary(trace_ids, TRACE_ID_LEN),
+            DictionaryKey::U8,
+        ),
+    );
+    columns.optional(
+        consts::SPAN_ID,
+        dictionary(fixed_size_binary(span_ids, SPAN_ID_LEN), DictionaryKey::U8),
+    );
+    columns.optional(
+        consts::TRACE_STATE,
+        dictionary(Arc::new(trace_states), DictionaryKey::U8),
+    );
+    columns.optional(consts::FLAGS, Arc::new(flags));
+    columns.optional(
+        consts::DROPPED_ATTRIBUTES_COUNT,
+        Arc::new(dropped_attributes_counts),
+    );
+    columns.into_record_batch().map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otlp::traces::traces_from;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{
+        AnyValue, InstrumentationScope, KeyValue, KeyValueList,
+    };
+    use crate::proto::opentelemetry::resource::v1::Resource;
+
+    fn attr(key: &str, value: Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn span(name: &str, trace_id: u8, span_id: u8) -> Span {
+        Span {
+            trace_id: vec![trace_id; 16],
+            span_id: vec![span_id; 8],
+            name: name.to_string(),
+            start_time_unix_nano: 1_000,
+            end_time_unix_nano: 1_500,
+            ..Default::default()
+        }
+    }
+
+    /// Request already in the order of the produced batch, so it decodes back to itself.
+    fn request() -> ExportTraceServiceRequest {
+        let resource = |name: &str| Resource {
+            attributes: vec![attr("service.name", Value::StringValue(name.into()))],
+            ..Default::default()
+        };
+        let scope = |name: &str| InstrumentationScope {
+            name: name.into(),
+            version: "1.0".into(),
+            attributes: vec![attr("lib", Value::BoolValue(true))],
+            dropped_attributes_count: 1,
+        };
+
+        let mut a = span("a", 1, 1);
+        a.attributes = vec![
+            attr("http.method", Value::StringValue("GET".into())),
+            attr("http.status_code", Value::IntValue(200)),
+            attr(
+                "map",
+                Value::KvlistValue(KeyValueList {
+                    values: vec![attr("k", Value::DoubleValue(1.5))],
+                }),
+            ),
+        ];
+        a.kind = 2;
+        a.trace_state = "vendor=a".into();
+        // sampled, with the parent known to be remote
+        a.flags = 0x301;
+        a.dropped_attributes_count = 1;
+        a.dropped_events_count = 2;
+        a.status = Some(Status {
+            code: 2,
+            message: "boom".into(),
+        });
+        a.events = vec![
+            Event {
+                time_unix_nano: 1_100,
+                name: "exception".into(),
+                attributes: vec![attr("exception.type", Value::StringValue("E".into()))],
+                dropped_attributes_count: 1,
+            },
+            Event {
+                time_unix_nano: 1_200,
+                name: "log".into(),
+                ..Default::default()
+            },
+        ];
+        a.links = vec![Link {
+            trace_id: vec![9; 16],
+            span_id: vec![9; 8],
+            trace_state: "vendor=l".into(),
+            attributes: vec![attr("link", Value::IntValue(1))],
+            flags: 0x101,
+            ..Default::default()
+        }];
+
+        let mut b = span("b", 1, 2);
+        b.parent_span_id = vec![1; 8];
+        b.attributes = vec![attr("http.method", Value::StringValue("GET".into()))];
+        b.events = vec![Event {
+            time_unix_nano: 1_300,
+            name: "exception".into(),
+            attributes: vec![attr("exception.type", Value::StringValue("E".into()))],
+            ..Default::default()
+        }];
+        b.dropped_links_count = 3;
+
+        ExportTraceServiceRequest {
+            resource_spans: vec![
+                ResourceSpans {
+                    resource: Some(resource("a")),
+                    scope_spans: vec![ScopeSpans {
+                        scope: Some(scope("s")),
+                        spans: vec![a, b],
+                        schema_url: "https://scope".into(),
+                    }],
+                    schema_url: "https://resource".into(),
+                },
+                ResourceSpans {
+                    resource: Some(resource("b")),
+                    scope_spans: vec![ScopeSpans {
+                        scope: Some(scope("s")),
+                        spans: vec![span("c", 2, 3)],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_produce_round_trip() {
+        let request = request();
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        assert_eq!(otap_batch.payload_types(), vec![
+            ArrowPayloadType::Spans,
+            ArrowPayloadType::ResourceAttrs,
+            ArrowPayloadType::ScopeAttrs,
+            ArrowPayloadType::SpanAttrs,
+            ArrowPayloadType::SpanEvents,
+            ArrowPayloadType::SpanLinks,
+            ArrowPayloadType::SpanEventAttrs,
+            ArrowPayloadType::SpanLinkAttrs,
+        ]);
+
+        let decoded = traces_from(otap_batch).unwrap();
+        assert_eq!(decoded, request);
+    }
+
+    #[test]
+    fn test_produce_ids() {
+        let mut request = request();
+        // the first span of the second resource comes first once sorted by name
+        request.resource_spans[0].scope_spans[0].spans[0].name = "d".into();
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+
+        let spans = otap_batch.get(ArrowPayloadType::Spans).unwrap();
+        let ids = spans.column_by_name(consts::ID).unwrap();
+        // the third span has no related data
+        assert_eq!(
+            ids.as_ref(),
+            &UInt16Array::from(vec![Some(0), Some(1), None]) as &dyn arrow::array::Array
+        );
+
+        let events = otap_batch.get(ArrowPayloadType::SpanEvents).unwrap();
+        // the exception events of the spans 1 and 0, then the log event of the span 1
+        assert_eq!(
+            events.column_by_name(consts::PARENT_ID).unwrap().as_ref(),
+            &UInt16Array::from(vec![0, 1, 1]) as &dyn arrow::array::Array
+        );
+
+        let span_attrs = otap_batch.get(ArrowPayloadType::SpanAttrs).unwrap();
+        // the b span attribute shares its http.method value with the d span
+        assert_eq!(
+            span_attrs
+                .column_by_name(consts::PARENT_ID)
+                .unwrap()
+                .as_ref(),
+            &UInt16Array::from(vec![0, 1, 1, 1]) as &dyn arrow::array::Array
+        );
+
+        let decoded = traces_from(otap_batch).unwrap();
+        let names: Vec<_> = decoded.resource_spans[0].scope_spans[0]
+            .spans
+            .iter()
+            .map(|span| span.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["b", "d"]);
+    }
+
+    #[test]
+    fn test_produce_schema_urls() {
+        // the same resource and scope with different schema urls stay apart
+        let scope_spans = |schema_url: &str, name: &str| ScopeSpans {
+            scope: Some(InstrumentationScope::default()),
+            spans: vec![span(name, 1, 1)],
+            schema_url: schema_url.into(),
+        };
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![
+                ResourceSpans {
+                    resource: Some(Resource::default()),
+                    scope_spans: vec![
+                        scope_spans("https://scope/1", "a"),
+                        scope_spans("https://scope/2", "b"),
+                    ],
+                    schema_url: "https://resource/1".into(),
+                },
+                ResourceSpans {
+                    resource: Some(Resource::default()),
+                    scope_spans: vec![scope_spans("https://scope/1", "c")],
+                    schema_url: "https://resource/2".into(),
+                },
+            ],
+        };
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        assert_eq!(traces_from(otap_batch).unwrap(), request);
+    }
+}
@@ -12,10 +12,14 @@
 
 use crate::otlp::attributes::store::AttributeValueType;
 use crate::otlp::metrics::MetricType;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use arrow::datatypes::DataType;
 use arrow::error::ArrowError;
 use num_enum::TryFromPrimitiveError;
-use snafu::{Location, Snafu};
+use snafu::{IntoError, Location, Snafu};
+use std::fmt::{self, Display, Write};
+use std::path::PathBuf;
+use std::time::Duration;
 use std::{backtrace::Backtrace, num::TryFromIntError};
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -61,6 +65,29 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display("Attribute {} of type {:?} is stored as {:?}", key, expect, actual))]
+    AttributeValueTypeMismatch {
+        key: String,
+        expect: AttributeValueType,
+        actual: AttributeValueType,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Duplicate attribute {}", key))]
+    DuplicateAttributeKey {
+        key: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Conflicting values for attribute {}", key))]
+    AttributeMergeConflict {
+        key: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Invalid bytes for serialized attribute value"))]
     InvalidSerializedAttributeBytes {
         source: ciborium::de::Error<std::io::Error>,
@@ -68,6 +95,55 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display(
+        "Serialized attribute value nests more than {} maps and slices",
+        max_depth
+    ))]
+    SerializedValueTooDeep {
+        max_depth: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Serialized attribute value has more than {} items", max_elements))]
+    TooManySerializedElements {
+        max_elements: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Malformed serialized attribute value at byte {}: {}", offset, reason))]
+    MalformedSerializedValue {
+        offset: usize,
+        reason: &'static str,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Serialized attribute value not in the preferred serialization at byte {}: {}",
+        offset,
+        reason
+    ))]
+    NonCanonicalSerializedValue {
+        offset: usize,
+        reason: &'static str,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Attribute value of {} bytes is over the limit of {} bytes",
+        len,
+        max_len
+    ))]
+    AttributeValueTooLarge {
+        len: usize,
+        max_len: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Invalid serialized integer attribute value"))]
     InvalidSerializedIntAttributeValue {
         source: TryFromIntError,
@@ -113,6 +189,20 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display("Column {} is not part of the payload schema", name))]
+    UnknownColumn {
+        name: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid timestamps, message: {}", message))]
+    InvalidTimestamp {
+        message: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Invalid trace id in exemplar data, message: {}", message))]
     InvalidQuantileType {
         message: String,
@@ -146,6 +236,16 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display(
+        "Payload type {} is defined by the specification, a decoder can't be registered for it",
+        payload_type
+    ))]
+    ReservedPayloadType {
+        payload_type: i32,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Failed to build stream reader"))]
     BuildStreamReader {
         #[snafu(source)]
@@ -162,12 +262,208 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display("Failed to write record batch"))]
+    WriteRecordBatch {
+        #[snafu(source)]
+        source: ArrowError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("IO error for path: {}", path.display()))]
+    Io {
+        path: PathBuf,
+        #[snafu(source)]
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[cfg(feature = "parquet")]
+    #[snafu(display("Failed to write parquet file: {}", path.display()))]
+    WriteParquet {
+        path: PathBuf,
+        #[snafu(source)]
+        source: parquet::errors::ParquetError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[cfg(feature = "parquet")]
+    #[snafu(display("Failed to read parquet file: {}", path.display()))]
+    ReadParquet {
+        path: PathBuf,
+        #[snafu(source)]
+        source: parquet::errors::ParquetError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid Arrow IPC stream: {}", reason))]
+    InvalidIpcStream {
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid OTAP recording: {}", reason))]
+    InvalidRecording {
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid OTAP archive: {}", reason))]
+    InvalidArchive {
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("IO error on an OTAP archive"))]
+    ArchiveIo {
+        #[snafu(source)]
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid OTAP frame: {}", reason))]
+    InvalidFrame {
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Batch hash mismatch, expected {:016x}, actual {:016x}",
+        expected,
+        actual
+    ))]
+    BatchHashMismatch {
+        expected: u64,
+        actual: u64,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid delta of dictionary {}: {}", id, reason))]
+    InvalidDictionaryDelta {
+        id: i64,
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Truncated HPACK block in the headers of the batch"))]
+    InvalidBatchHeaders {
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid BatchArrowRecords message: {}", source))]
+    InvalidBatchArrowRecords {
+        source: prost::DecodeError,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid consumer checkpoint: {}", reason))]
+    InvalidCheckpoint {
+        reason: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[cfg(feature = "flight")]
+    #[snafu(display("Invalid OTAP flight descriptor: {}", descriptor))]
+    InvalidFlightDescriptor {
+        descriptor: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[cfg(feature = "flight")]
+    #[snafu(display("Invalid OTAP flight metadata of {} bytes", len))]
+    InvalidFlightMetadata {
+        len: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[cfg(feature = "flight")]
+    #[snafu(display("Flight data received without payload descriptor"))]
+    MissingFlightDescriptor {
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[cfg(feature = "flight")]
+    #[snafu(display("Payloads of batch {} were not received in order", batch_id))]
+    IncompleteFlightBatch {
+        batch_id: i64,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[cfg(feature = "id-remap")]
+    #[snafu(display("Original value of remapped id {:?} not found", id))]
+    RemappedIdNotFound {
+        id: Vec<u8>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid {} value {}", name, value))]
+    InvalidEnumValue {
+        name: &'static str,
+        value: i32,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Invalid schema id {:?}: {}", schema_id, message))]
+    InvalidSchemaId {
+        schema_id: String,
+        message: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to compress the OTLP request"))]
+    CompressRequest {
+        #[snafu(source)]
+        source: std::io::Error,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Payload compression {} is not supported by this build", compression))]
+    UnsupportedCompression {
+        compression: String,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Failed to decode {}: {}", context, source))]
+    Decode {
+        context: ErrorContext,
+        source: Box<Error>,
+    },
+
     #[snafu(display("Batch is empty"))]
     EmptyBatch {
         #[snafu(implicit)]
         location: Location,
     },
 
+    #[snafu(display("Batch carries the payloads of {:?} more than once", main_payload_type))]
+    DuplicateSignal {
+        main_payload_type: ArrowPayloadType,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Log record not found"))]
     LogRecordNotFound {
         #[snafu(implicit)]
@@ -180,6 +476,43 @@ pub enum Error {
         location: Location,
     },
 
+    #[snafu(display("Span record not found"))]
+    SpanRecordNotFound {
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "A single item is {} bytes once encoded, over the {} bytes limit",
+        size,
+        max_size
+    ))]
+    BatchTooLarge {
+        size: usize,
+        max_size: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Delta {} applied to the id {} is out of the range of the id type",
+        delta,
+        last_id
+    ))]
+    InvalidDeltaId {
+        last_id: i128,
+        delta: i128,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Too many {} in batch, their ids overflow the id column", name))]
+    IdOverflow {
+        name: &'static str,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
     #[snafu(display("Record batch is in unexpected state. reason: {}", reason))]
     UnexpectedRecordBatchState {
         reason: String,
@@ -224,4 +557,307 @@ pub enum Error {
         #[snafu(implicit)]
         location: Location,
     },
+
+    #[snafu(display(
+        "Payload {:?} has {} rows, over the limit of {}",
+        payload_type,
+        rows,
+        max_rows
+    ))]
+    TooManyRows {
+        payload_type: ArrowPayloadType,
+        rows: usize,
+        max_rows: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Batch has {} payloads, over the limit of {}", payloads, max_payloads))]
+    TooManyPayloads {
+        payloads: usize,
+        max_payloads: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Decoding the batch took longer than {:?}", max_decode_time))]
+    DecodeTimeExceeded {
+        max_decode_time: Duration,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Decoding missed its deadline, after reading the payloads {:?}",
+        completed_payloads
+    ))]
+    DeadlineExceeded {
+        completed_payloads: Vec<ArrowPayloadType>,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Conversion was cancelled"))]
+    Cancelled {
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Decoding task was cancelled before completing"))]
+    DecodeTaskCancelled {
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Memory pool refused {} bytes with {} bytes already reserved",
+        requested,
+        reserved
+    ))]
+    MemoryLimitExceeded {
+        requested: usize,
+        reserved: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display(
+        "Mask of {} rows doesn't match the {} rows of the payload",
+        actual,
+        expected
+    ))]
+    MaskLengthMismatch {
+        expected: usize,
+        actual: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+
+    #[snafu(display("Slice {}..{} is out of the {} rows of the payload", start, end, rows))]
+    InvalidSliceRange {
+        start: usize,
+        end: usize,
+        rows: usize,
+        #[snafu(implicit)]
+        location: Location,
+    },
+}
+
+/// Location in an OTAP batch of the data that failed to decode.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ErrorContext {
+    /// Payload the data belongs to.
+    pub payload_type: Option<ArrowPayloadType>,
+    /// Column the data was read from.
+    pub column_name: Option<String>,
+    /// Index of the row in the record batch of the payload.
+    pub row_index: Option<usize>,
+    /// Decoded parent id of the row.
+    pub parent_id: Option<i128>,
+}
+
+impl ErrorContext {
+    /// Sets the payload the data belongs to.
+    #[must_use]
+    pub fn payload(mut self, payload_type: ArrowPayloadType) -> Self {
+        self.payload_type = Some(payload_type);
+        self
+    }
+
+    /// Sets the column the data was read from.
+    #[must_use]
+    pub fn column(mut self, column_name: &str) -> Self {
+        self.column_name = Some(column_name.to_string());
+        self
+    }
+
+    /// Sets the index of the row in the record batch.
+    #[must_use]
+    pub fn row(mut self, row_index: usize) -> Self {
+        self.row_index = Some(row_index);
+        self
+    }
+
+    /// Sets the decoded parent id of the row.
+    #[must_use]
+    pub fn parent_id(mut self, parent_id: impl Into<i128>) -> Self {
+        self.parent_id = Some(parent_id.into());
+        self
+    }
+
+    /// Fills the fields that are not set yet with the ones of `other`.
+    fn merge(&mut self, other: Self) {
+        self.payload_type = self.payload_type.or(other.payload_type);
+        self.column_name = self.column_name.take().or(other.column_name);
+        self.row_index = self.row_index.or(other.row_index);
+        self.parent_id = self.parent_id.or(other.parent_id);
+    }
+}
+
+impl Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(payload_type) = self.payload_type {
+            parts.push(format!("payload {}", payload_type.as_str_name()));
+        }
+        if let Some(column_name) = &self.column_name {
+            parts.push(format!("column {column_name}"));
+        }
+        if let Some(row_index) = self.row_index {
+            parts.push(format!("row {row_index}"));
+        }
+        if let Some(parent_id) = self.parent_id {
+            parts.push(format!("parent id {parent_id}"));
+        }
+        if parts.is_empty() {
+            f.write_str("batch")
+        } else {
+            f.write_str(&parts.join(", "))
+        }
+    }
+}
+
+impl Error {
+    /// Attaches decoding context to the error. If the error already carries a context,
+    /// the fields it doesn't set yet are filled from `context`.
+    #[must_use]
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        match self {
+            Error::Decode {
+                context: mut current,
+                source,
+            } => {
+                current.merge(context);
+                Error::Decode {
+                    context: current,
+                    source,
+                }
+            }
+            error => DecodeSnafu { context }.into_error(Box::new(error)),
+        }
+    }
+
+    /// Returns the decoding context of the error, if any.
+    #[must_use]
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::Decode { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Formats the error as a multi-line report meant for operators: the error, where in
+    /// the batch it happened, and the chain of its causes.
+    #[must_use]
+    pub fn diagnostic_report(&self) -> String {
+        let (error, context) = match self {
+            Error::Decode { context, source } => (source.as_ref(), Some(context)),
+            error => (error, None),
+        };
+
+        let mut report = format!("error: {error}");
+        if let Some(context) = context {
+            let fields = [
+                (
+                    "payload",
+                    context.payload_type.map(|p| p.as_str_name().to_string()),
+                ),
+                ("column", context.column_name.clone()),
+                ("row", context.row_index.map(|r| r.to_string())),
+                ("parent id", context.parent_id.map(|p| p.to_string())),
+            ];
+            for (name, value) in fields {
+                if let Some(value) = value {
+                    // safety: writing to a String can't fail
+                    write!(report, "\n  {name}: {value}").expect("write to string");
+                }
+            }
+        }
+
+        let mut source = std::error::Error::source(error);
+        while let Some(cause) = source {
+            write!(report, "\ncaused by: {cause}").expect("write to string");
+            source = cause.source();
+        }
+        report
+    }
+}
+
+/// Extension trait attaching decoding context to the errors of results.
+pub trait ErrorContextExt<T> {
+    /// Attaches the context built by `f` to the error, see [`Error::with_context`].
+    fn error_context(self, f: impl FnOnce() -> ErrorContext) -> Result<T>;
+
+    /// Attaches the payload type to the error.
+    fn in_payload(self, payload_type: ArrowPayloadType) -> Result<T>;
+}
+
+impl<T> ErrorContextExt<T> for Result<T> {
+    fn error_context(self, f: impl FnOnce() -> ErrorContext) -> Result<T> {
+        self.map_err(|e| e.with_context(f()))
+    }
+
+    fn in_payload(self, payload_type: ArrowPayloadType) -> Result<T> {
+        self.error_context(|| ErrorContext::default().payload(payload_type))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{RecordBatch, StringArray, UInt8Array, UInt16Array};
+    use arrow::datatypes::{Field, Schema};
+
+    use crate::otlp::attributes::store::Attribute16Store;
+    use crate::schema::consts;
+
+    #[test]
+    fn test_decode_error_context() {
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 1])),
+                Arc::new(UInt8Array::from(vec![AttributeValueType::Str as u8, 42])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(StringArray::from(vec!["x", "y"])),
+            ],
+        )
+        .unwrap();
+
+        let error = Attribute16Store::try_from(&rb)
+            .map(|_| ())
+            .in_payload(ArrowPayloadType::LogAttrs)
+            .unwrap_err();
+        assert_eq!(
+            error.context(),
+            Some(&ErrorContext {
+                payload_type: Some(ArrowPayloadType::LogAttrs),
+                column_name: Some(consts::ATTRIBUTE_TYPE.to_string()),
+                row_index: Some(1),
+                parent_id: None,
+            })
+        );
+        assert_eq!(
+            error.to_string(),
+            "Failed to decode payload LOG_ATTRS, column type, row 1: Cannot recognize attribute value type"
+        );
+
+        let report = error.diagnostic_report();
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines[..4], [
+            "error: Cannot recognize attribute value type",
+            "  payload: LOG_ATTRS",
+            "  column: type",
+            "  row: 1",
+        ]);
+        assert!(lines[4].starts_with("caused by: "));
+    }
 }
@@ -0,0 +1,302 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! C bindings of the decoder and the encoders, built with the `ffi` feature, so that the
+//! collectors and agents not written in Rust can embed the crate as a shared library:
+//!
+//! ```text
+//! cargo rustc --release --no-default-features --features ffi --crate-type cdylib
+//! ```
+//!
+//! Every function converts one protobuf encoded message into another and has the
+//! signature below, the output buffer being released with [`otel_arrow_free_buffer`]:
+//!
+//! ```c
+//! int32_t otel_arrow_decode_traces(const uint8_t *input, size_t input_len,
+//!                                  uint8_t **output, size_t *output_len);
+//! void otel_arrow_free_buffer(uint8_t *data, size_t len);
+//! ```
+//!
+//! The decoders take a `BatchArrowRecords` message whose payloads carry their own schemas
+//! and dictionaries, e.g. one produced by the encoders, and return the OTLP export request.
+//! The encoders do the reverse. The functions return an [`OtelArrowStatus`], the output is
+//! only set on success.
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use prost::Message;
+
+use crate::decode::decoder::Consumer;
+use crate::encode::split::to_batch_arrow_records;
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+
+/// Outcome of a conversion.
+#[repr(i32)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OtelArrowStatus {
+    /// The output is set.
+    Ok = 0,
+    /// A pointer is null.
+    NullPointer = 1,
+    /// The input is not a valid protobuf message of the expected type.
+    InvalidMessage = 2,
+    /// The `BatchArrowRecords` could not be decoded.
+    DecodeFailed = 3,
+    /// The OTLP request could not be encoded.
+    EncodeFailed = 4,
+    /// The conversion panicked.
+    Panic = 5,
+}
+
+/// Decodes a `BatchArrowRecords` message of traces into an `ExportTraceServiceRequest`.
+///
+/// # Safety
+///
+/// `input` must point to `input_len` readable bytes, `output` and `output_len` to writable
+/// locations.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otel_arrow_decode_traces(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> OtelArrowStatus {
+    // safety: forwarded to the caller
+    unsafe {
+        convert(input, input_len, output, output_len, |input| {
+            decode(input, Consumer::consume_traces_batches)
+        })
+    }
+}
+
+/// Decodes a `BatchArrowRecords` message of logs into an `ExportLogsServiceRequest`.
+///
+/// # Safety
+///
+/// See [`otel_arrow_decode_traces`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otel_arrow_decode_logs(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> OtelArrowStatus {
+    // safety: forwarded to the caller
+    unsafe {
+        convert(input, input_len, output, output_len, |input| {
+            decode(input, Consumer::consume_logs_batches)
+        })
+    }
+}
+
+/// Decodes a `BatchArrowRecords` message of metrics into an `ExportMetricsServiceRequest`.
+///
+/// # Safety
+///
+/// See [`otel_arrow_decode_traces`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otel_arrow_decode_metrics(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> OtelArrowStatus {
+    // safety: forwarded to the caller
+    unsafe {
+        convert(input, input_len, output, output_len, |input| {
+            decode(input, Consumer::consume_metrics_batches)
+        })
+    }
+}
+
+/// Encodes an `ExportTraceServiceRequest` into a `BatchArrowRecords` message.
+///
+/// # Safety
+///
+/// See [`otel_arrow_decode_traces`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otel_arrow_encode_traces(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> OtelArrowStatus {
+    // safety: forwarded to the caller
+    unsafe {
+        convert(input, input_len, output, output_len, |input| {
+            encode(input, |request: &ExportTraceServiceRequest| {
+                TracesProducer::new().produce(request)
+            })
+        })
+    }
+}
+
+/// Encodes an `ExportLogsServiceRequest` into a `BatchArrowRecords` message.
+///
+/// # Safety
+///
+/// See [`otel_arrow_decode_traces`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otel_arrow_encode_logs(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> OtelArrowStatus {
+    // safety: forwarded to the caller
+    unsafe {
+        convert(input, input_len, output, output_len, |input| {
+            encode(input, |request: &ExportLogsServiceRequest| {
+                LogsProducer::new().produce(request)
+            })
+        })
+    }
+}
+
+/// Encodes an `ExportMetricsServiceRequest` into a `BatchArrowRecords` message.
+///
+/// # Safety
+///
+/// See [`otel_arrow_decode_traces`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otel_arrow_encode_metrics(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+) -> OtelArrowStatus {
+    // safety: forwarded to the caller
+    unsafe {
+        convert(input, input_len, output, output_len, |input| {
+            encode(input, |request: &ExportMetricsServiceRequest| {
+                MetricsProducer::new().produce(request)
+            })
+        })
+    }
+}
+
+/// Releases a buffer returned by a conversion. Does nothing if `data` is null.
+///
+/// # Safety
+///
+/// `data` and `len` must be the output of a conversion, not released yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn otel_arrow_free_buffer(data: *mut u8, len: usize) {
+    if data.is_null() {
+        return;
+    }
+    // safety: the buffer was leaked by `convert` with this length
+    drop(unsafe { Box::from_raw(std::ptr::slice_from_raw_parts_mut(data, len)) });
+}
+
+/// Runs the conversion of the input, catching its panics, and leaks its output into the
+/// output pointers.
+unsafe fn convert(
+    input: *const u8,
+    input_len: usize,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+    convert: impl FnOnce(&[u8]) -> Result<Vec<u8>, OtelArrowStatus>,
+) -> OtelArrowStatus {
+    if (input.is_null() && input_len > 0) || output.is_null() || output_len.is_null() {
+        return OtelArrowStatus::NullPointer;
+    }
+    let input = if input_len == 0 {
+        &[]
+    } else {
+        // safety: the caller guarantees that input points to input_len bytes
+        unsafe { std::slice::from_raw_parts(input, input_len) }
+    };
+    match catch_unwind(AssertUnwindSafe(|| convert(input))) {
+        Ok(Ok(bytes)) => {
+            let bytes = Box::into_raw(bytes.into_boxed_slice());
+            // safety: the caller guarantees that the output pointers are writable
+            unsafe {
+                *output_len = bytes.len();
+                *output = bytes.cast::<u8>();
+            }
+            OtelArrowStatus::Ok
+        }
+        Ok(Err(status)) => status,
+        Err(_) => OtelArrowStatus::Panic,
+    }
+}
+
+fn decode<T: Message>(
+    input: &[u8],
+    consume: impl FnOnce(&mut Consumer, &mut BatchArrowRecords) -> crate::error::Result<T>,
+) -> Result<Vec<u8>, OtelArrowStatus> {
+    let mut records =
+        BatchArrowRecords::decode(input).map_err(|_| OtelArrowStatus::InvalidMessage)?;
+    let request = consume(&mut Consumer::default(), &mut records)
+        .map_err(|_| OtelArrowStatus::DecodeFailed)?;
+    Ok(request.encode_to_vec())
+}
+
+fn encode<R: Message + Default>(
+    input: &[u8],
+    produce: impl FnOnce(&R) -> crate::error::Result<OtapBatch>,
+) -> Result<Vec<u8>, OtelArrowStatus> {
+    let request = R::decode(input).map_err(|_| OtelArrowStatus::InvalidMessage)?;
+    let records = produce(&request)
+        .and_then(|otap_batch| to_batch_arrow_records(&otap_batch, 0))
+        .map_err(|_| OtelArrowStatus::EncodeFailed)?;
+    Ok(records.encode_to_vec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otlp::traces::traces_from;
+    use crate::test_util::workloads::traces_batch;
+
+    fn call(
+        function: unsafe extern "C" fn(
+            *const u8,
+            usize,
+            *mut *mut u8,
+            *mut usize,
+        ) -> OtelArrowStatus,
+        input: &[u8],
+    ) -> Result<Vec<u8>, OtelArrowStatus> {
+        let mut output = std::ptr::null_mut();
+        let mut output_len = 0;
+        // safety: the pointers are valid and the output is released right after its copy
+        unsafe {
+            match function(input.as_ptr(), input.len(), &mut output, &mut output_len) {
+                OtelArrowStatus::Ok => {
+                    let bytes = std::slice::from_raw_parts(output, output_len).to_vec();
+                    otel_arrow_free_buffer(output, output_len);
+                    Ok(bytes)
+                }
+                status => Err(status),
+            }
+        }
+    }
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let request = traces_from(traces_batch(4, 2)).unwrap().encode_to_vec();
+        let records = call(otel_arrow_encode_traces, &request).unwrap();
+        assert_eq!(call(otel_arrow_decode_traces, &records).unwrap(), request);
+        assert_eq!(
+            call(otel_arrow_decode_logs, &records),
+            Err(OtelArrowStatus::DecodeFailed)
+        );
+        assert_eq!(
+            call(otel_arrow_decode_traces, b"\xff"),
+            Err(OtelArrowStatus::InvalidMessage)
+        );
+
+        // safety: null pointers are rejected before being read
+        let status =
+            unsafe { otel_arrow_decode_traces(std::ptr::null(), 1, std::ptr::null_mut(), &mut 0) };
+        assert_eq!(status, OtelArrowStatus::NullPointer);
+    }
+}
@@ -0,0 +1,546 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Arrow Flight transport for OTAP payloads.
+//!
+//! This module maps `BatchArrowRecords` onto Flight `DoPut` / `DoExchange` streams so OTAP
+//! data can be ingested by existing Flight infrastructure instead of the OTel Arrow gRPC
+//! services. The mapping is:
+//!
+//! - every Arrow IPC message of a payload is sent as one `FlightData`.
+//! - the first `FlightData` of each payload carries a path descriptor
+//!   `["otap", "<payload type>", "<schema id>"]`, see [`payload_descriptor`], and the
+//!   [`PayloadMetadata`] of the payload in its `app_metadata`.
+//! - the server replies with one `BatchStatus` per batch, protobuf encoded in the
+//!   `app_metadata` of a `PutResult` (for `DoPut`) or of a `FlightData` (for `DoExchange`).
+//!
+//! Batch headers are not part of the mapping, Flight users are expected to rely on the
+//! gRPC metadata of the stream instead.
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use arrow::ipc::{MessageHeader, root_as_message};
+use arrow_flight::flight_descriptor::DescriptorType;
+use arrow_flight::flight_service_server::FlightService;
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult, Ticket,
+};
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use prost::Message;
+use snafu::{OptionExt, ensure};
+use tonic_flight::{Request, Response, Status, Streaming};
+
+use crate::Consumer;
+use crate::compression::{CONTINUATION_MARKER, IpcMessage, split_ipc_messages};
+use crate::decode::decoder::ExportRequest;
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::{
+    ArrowPayload, ArrowPayloadType, BatchArrowRecords, BatchStatus, StatusCode,
+};
+
+/// First element of the path of every OTAP Flight descriptor.
+pub const DESCRIPTOR_PREFIX: &str = "otap";
+
+/// Returns the Flight descriptor identifying a payload of the given type and schema id.
+#[must_use]
+pub fn payload_descriptor(payload_type: ArrowPayloadType, schema_id: &str) -> FlightDescriptor {
+    FlightDescriptor::new_path(vec![
+        DESCRIPTOR_PREFIX.to_string(),
+        payload_type.as_str_name().to_lowercase(),
+        schema_id.to_string(),
+    ])
+}
+
+/// Returns the payload type and schema id identified by a descriptor created with
+/// [`payload_descriptor`].
+pub fn parse_payload_descriptor(
+    descriptor: &FlightDescriptor,
+) -> Result<(ArrowPayloadType, String)> {
+    let invalid = || error::InvalidFlightDescriptorSnafu {
+        descriptor: format!("{:?}", descriptor.path),
+    };
+    ensure!(descriptor.r#type == DescriptorType::Path as i32, invalid());
+    let [prefix, payload_type, schema_id] = descriptor.path.as_slice() else {
+        return invalid().fail();
+    };
+    ensure!(prefix == DESCRIPTOR_PREFIX, invalid());
+    let payload_type =
+        ArrowPayloadType::from_str_name(&payload_type.to_uppercase()).with_context(invalid)?;
+    Ok((payload_type, schema_id.clone()))
+}
+
+/// Metadata sent in the `app_metadata` of the first `FlightData` of every payload.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PayloadMetadata {
+    /// Id of the batch the payload belongs to.
+    pub batch_id: i64,
+    /// Position of the payload in the batch.
+    pub payload_index: u32,
+    /// Number of payloads in the batch.
+    pub payload_count: u32,
+}
+
+impl PayloadMetadata {
+    const ENCODED_LEN: usize = 16;
+
+    /// Encodes the metadata as little endian `batch_id`, `payload_index`, `payload_count`.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(Self::ENCODED_LEN);
+        buf.extend_from_slice(&self.batch_id.to_le_bytes());
+        buf.extend_from_slice(&self.payload_index.to_le_bytes());
+        buf.extend_from_slice(&self.payload_count.to_le_bytes());
+        buf
+    }
+
+    /// Decodes metadata encoded with [`PayloadMetadata::encode`].
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let invalid = || error::InvalidFlightMetadataSnafu { len: buf.len() };
+        ensure!(buf.len() == Self::ENCODED_LEN, invalid());
+        let (batch_id, rest) = buf.split_at(8);
+        let (payload_index, payload_count) = rest.split_at(4);
+        Ok(Self {
+            batch_id: i64::from_le_bytes(batch_id.try_into().ok().with_context(invalid)?),
+            payload_index: u32::from_le_bytes(payload_index.try_into().ok().with_context(invalid)?),
+            payload_count: u32::from_le_bytes(payload_count.try_into().ok().with_context(invalid)?),
+        })
+    }
+}
+
+/// Converts the payloads of a `BatchArrowRecords` into the `FlightData` messages to send
+/// on a `DoPut` or `DoExchange` stream.
+pub fn to_flight_data(bar: &BatchArrowRecords) -> Result<Vec<FlightData>> {
+    let mut flight_data = Vec::new();
+    let payload_count = bar.arrow_payloads.len() as u32;
+
+    for (payload_index, payload) in bar.arrow_payloads.iter().enumerate() {
+        let payload_type = ArrowPayloadType::try_from(payload.r#type).map_err(|_| {
+            error::UnsupportedPayloadTypeSnafu {
+                actual: payload.r#type,
+            }
+            .build()
+        })?;
+
+        let mut messages = split_ipc_messages(&payload.record)?.into_iter();
+        let IpcMessage {
+            header: data_header,
+            body: data_body,
+            ..
+        } = messages.next().context(error::EmptyBatchSnafu)?;
+        flight_data.push(FlightData {
+            flight_descriptor: Some(payload_descriptor(payload_type, &payload.schema_id)),
+            data_header: data_header.to_vec().into(),
+            app_metadata: PayloadMetadata {
+                batch_id: bar.batch_id,
+                payload_index: payload_index as u32,
+                payload_count,
+            }
+            .encode()
+            .into(),
+            data_body: data_body.to_vec().into(),
+        });
+        flight_data.extend(messages.map(|message| FlightData {
+            data_header: message.header.to_vec().into(),
+            data_body: message.body.to_vec().into(),
+            ..Default::default()
+        }));
+    }
+
+    Ok(flight_data)
+}
+
+/// Appends an IPC message to a payload's Arrow IPC stream.
+fn append_ipc_message(record: &mut Vec<u8>, data_header: &[u8], data_body: &[u8]) {
+    // headers are padded so the body starts at an 8 byte boundary
+    let padding = (8 - (data_header.len() + 8) % 8) % 8;
+    record.extend_from_slice(&CONTINUATION_MARKER);
+    record.extend_from_slice(&((data_header.len() + padding) as i32).to_le_bytes());
+    record.extend_from_slice(data_header);
+    record.extend(std::iter::repeat_n(0, padding));
+    record.extend_from_slice(data_body);
+}
+
+/// Reassembles `BatchArrowRecords` from the `FlightData` messages received on a stream.
+#[derive(Default)]
+pub struct FlightDataAssembler {
+    current: Option<(BatchArrowRecords, u32)>,
+}
+
+impl FlightDataAssembler {
+    /// Adds a message received on the stream. Returns the batch once all of its payloads
+    /// were received.
+    pub fn push(&mut self, flight_data: FlightData) -> Result<Option<BatchArrowRecords>> {
+        if let Some(descriptor) = &flight_data.flight_descriptor {
+            let (payload_type, schema_id) = parse_payload_descriptor(descriptor)?;
+            let metadata = PayloadMetadata::decode(&flight_data.app_metadata)?;
+            if metadata.payload_index == 0 {
+                ensure!(self.current.is_none(), error::IncompleteFlightBatchSnafu {
+                    batch_id: metadata.batch_id,
+                });
+                self.current = Some((
+                    BatchArrowRecords {
+                        batch_id: metadata.batch_id,
+                        arrow_payloads: Vec::with_capacity(metadata.payload_count as usize),
+                        headers: Vec::new(),
+                    },
+                    metadata.payload_count,
+                ));
+            }
+
+            let (bar, _) = self
+                .current
+                .as_mut()
+                .context(error::IncompleteFlightBatchSnafu {
+                    batch_id: metadata.batch_id,
+                })?;
+            ensure!(
+                bar.batch_id == metadata.batch_id
+                    && bar.arrow_payloads.len() == metadata.payload_index as usize,
+                error::IncompleteFlightBatchSnafu {
+                    batch_id: bar.batch_id,
+                }
+            );
+            bar.arrow_payloads.push(ArrowPayload {
+                schema_id,
+                r#type: payload_type as i32,
+                record: Vec::new(),
+            });
+        }
+
+        let (bar, payload_count) = self
+            .current
+            .as_mut()
+            .context(error::MissingFlightDescriptorSnafu)?;
+        // safety: a payload is always pushed with the batch
+        let payload = bar
+            .arrow_payloads
+            .last_mut()
+            .expect("batch has at least one payload");
+        append_ipc_message(
+            &mut payload.record,
+            &flight_data.data_header,
+            &flight_data.data_body,
+        );
+
+        let is_record_batch = root_as_message(&flight_data.data_header)
+            .map(|message| message.header_type() == MessageHeader::RecordBatch)
+            .unwrap_or_default();
+        if is_record_batch && bar.arrow_payloads.len() == *payload_count as usize {
+            Ok(self.current.take().map(|(bar, _)| bar))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns true if a batch was partially received.
+    #[must_use]
+    pub fn has_pending_batch(&self) -> bool {
+        self.current.is_some()
+    }
+}
+
+/// Handles the OTLP export requests decoded by [`OtapFlightService`].
+pub trait ExportHandler: Send + Sync + 'static {
+    /// Handles the decoded request. The error message is returned to the client in the
+    /// status of the batch.
+    fn export(&self, request: ExportRequest) -> std::result::Result<(), String>;
+}
+
+/// Flight service accepting OTAP payloads on `DoPut` and `DoExchange`. One [`Consumer`]
+/// is used per stream, so a stream can carry batches of every signal.
+pub struct OtapFlightService<H> {
+    handler: Arc<H>,
+}
+
+impl<H: ExportHandler> OtapFlightService<H> {
+    /// Creates a service passing the decoded requests to `handler`.
+    pub fn new(handler: H) -> Self {
+        Self {
+            handler: Arc::new(handler),
+        }
+    }
+}
+
+struct StreamState<H> {
+    input: Streaming<FlightData>,
+    assembler: FlightDataAssembler,
+    consumer: Consumer,
+    handler: Arc<H>,
+    done: bool,
+}
+
+impl<H: ExportHandler> StreamState<H> {
+    fn process(&mut self, mut bar: BatchArrowRecords) -> BatchStatus {
+        let batch_id = bar.batch_id;
+        match self.consumer.consume_batches(&mut bar) {
+            Ok(request) => match self.handler.export(request) {
+                Ok(()) => BatchStatus::ok(batch_id),
+                Err(status_message) => BatchStatus {
+                    batch_id,
+                    status_code: StatusCode::Unavailable as i32,
+                    status_message,
+                },
+            },
+            Err(e) => BatchStatus::from_error(batch_id, &e),
+        }
+    }
+
+    async fn next_status(mut self) -> Option<(std::result::Result<BatchStatus, Status>, Self)> {
+        while !self.done {
+            match self.input.next().await {
+                Some(Ok(flight_data)) => match self.assembler.push(flight_data) {
+                    Ok(Some(bar)) => {
+                        let status = self.process(bar);
+                        return Some((Ok(status), self));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.done = true;
+                        return Some((Err(Status::invalid_argument(e.to_string())), self));
+                    }
+                },
+                Some(Err(status)) => {
+                    self.done = true;
+                    return Some((Err(status), self));
+                }
+                None => {
+                    self.done = true;
+                    if self.assembler.has_pending_batch() {
+                        return Some((
+                            Err(Status::invalid_argument(
+                                "stream ended in the middle of a batch",
+                            )),
+                            self,
+                        ));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn into_stream(self) -> impl Stream<Item = std::result::Result<BatchStatus, Status>> {
+        stream::unfold(self, Self::next_status)
+    }
+}
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = std::result::Result<T, Status>> + Send>>;
+
+#[tonic_flight::async_trait]
+impl<H: ExportHandler> FlightService for OtapFlightService<H> {
+    type HandshakeStream = ResponseStream<HandshakeResponse>;
+    type ListFlightsStream = ResponseStream<FlightInfo>;
+    type DoGetStream = ResponseStream<FlightData>;
+    type DoPutStream = ResponseStream<PutResult>;
+    type DoExchangeStream = ResponseStream<FlightData>;
+    type DoActionStream = ResponseStream<arrow_flight::Result>;
+    type ListActionsStream = ResponseStream<ActionType>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> std::result::Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("handshake is not supported"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> std::result::Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("list_flights is not supported"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("get_flight_info is not supported"))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info is not supported"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> std::result::Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("get_schema is not supported"))
+    }
+
+    async fn do_get(
+        &self,
+        _request: Request<Ticket>,
+    ) -> std::result::Result<Response<Self::DoGetStream>, Status> {
+        Err(Status::unimplemented("do_get is not supported"))
+    }
+
+    async fn do_put(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoPutStream>, Status> {
+        let statuses = self.stream_state(request).into_stream();
+        let output: BoxStream<'static, _> = statuses
+            .map(|status| {
+                status.map(|status| PutResult {
+                    app_metadata: status.encode_to_vec().into(),
+                })
+            })
+            .boxed();
+        Ok(Response::new(output))
+    }
+
+    async fn do_exchange(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> std::result::Result<Response<Self::DoExchangeStream>, Status> {
+        let statuses = self.stream_state(request).into_stream();
+        let output: BoxStream<'static, _> = statuses
+            .map(|status| {
+                status.map(|status| FlightData {
+                    app_metadata: status.encode_to_vec().into(),
+                    ..Default::default()
+                })
+            })
+            .boxed();
+        Ok(Response::new(output))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> std::result::Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action is not supported"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> std::result::Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions is not supported"))
+    }
+}
+
+impl<H: ExportHandler> OtapFlightService<H> {
+    fn stream_state(&self, request: Request<Streaming<FlightData>>) -> StreamState<H> {
+        StreamState {
+            input: request.into_inner(),
+            assembler: FlightDataAssembler::default(),
+            consumer: Consumer::default(),
+            handler: self.handler.clone(),
+            done: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrow::array::{DictionaryArray, RecordBatch, UInt16Array};
+    use arrow::datatypes::{DataType, Field, Schema, UInt8Type};
+    use arrow::ipc::writer::StreamWriter;
+
+    fn record_batch(keys: Vec<&str>) -> RecordBatch {
+        let len = keys.len();
+        let keys: DictionaryArray<UInt8Type> = keys.into_iter().collect();
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::UInt16, false),
+                Field::new(
+                    "key",
+                    DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8)),
+                    false,
+                ),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from_iter_values(0..len as u16)),
+                Arc::new(keys),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_descriptor_round_trip() {
+        let descriptor = payload_descriptor(ArrowPayloadType::SpanEventAttrs, "3");
+        assert_eq!(descriptor.path, vec!["otap", "span_event_attrs", "3"]);
+        assert_eq!(
+            parse_payload_descriptor(&descriptor).unwrap(),
+            (ArrowPayloadType::SpanEventAttrs, "3".to_string())
+        );
+
+        assert!(
+            parse_payload_descriptor(&FlightDescriptor::new_path(vec![
+                "otap".to_string(),
+                "not_a_payload".to_string(),
+                "0".to_string(),
+            ]))
+            .is_err()
+        );
+        assert!(parse_payload_descriptor(&FlightDescriptor::new_cmd("otap")).is_err());
+    }
+
+    #[test]
+    fn test_flight_data_round_trip() {
+        let schema = record_batch(vec![]).schema();
+        let mut logs_writer = StreamWriter::try_new(Vec::new(), &schema).unwrap();
+        let mut attrs_writer = StreamWriter::try_new(Vec::new(), &schema).unwrap();
+
+        let mut assembler = FlightDataAssembler::default();
+        let mut consumer = Consumer::default();
+        for batch_id in 0..3 {
+            let logs = record_batch(vec!["a", "b"]);
+            let attrs = record_batch(vec!["c", "c", "d"]);
+            logs_writer.write(&logs).unwrap();
+            attrs_writer.write(&attrs).unwrap();
+            let bar = BatchArrowRecords {
+                batch_id,
+                arrow_payloads: vec![
+                    ArrowPayload {
+                        schema_id: "0".to_string(),
+                        r#type: ArrowPayloadType::Logs as i32,
+                        record: std::mem::take(logs_writer.get_mut()),
+                    },
+                    ArrowPayload {
+                        schema_id: "1".to_string(),
+                        r#type: ArrowPayloadType::LogAttrs as i32,
+                        record: std::mem::take(attrs_writer.get_mut()),
+                    },
+                ],
+                headers: Vec::new(),
+            };
+
+            let flight_data = to_flight_data(&bar).unwrap();
+            let descriptors = flight_data
+                .iter()
+                .filter(|fd| fd.flight_descriptor.is_some())
+                .count();
+            assert_eq!(descriptors, 2);
+
+            let mut received = None;
+            for (i, fd) in flight_data.into_iter().enumerate() {
+                assert!(received.is_none(), "batch completed early at message {i}");
+                received = assembler.push(fd).unwrap();
+            }
+            let mut received = received.expect("batch is complete");
+            assert_eq!(received, bar);
+            assert!(!assembler.has_pending_batch());
+
+            let records = consumer.consume_bar(&mut received).unwrap();
+            assert_eq!(records[0].record, logs);
+            assert_eq!(records[1].record, attrs);
+        }
+    }
+
+    #[test]
+    fn test_assembler_rejects_data_without_descriptor() {
+        let mut assembler = FlightDataAssembler::default();
+        assert!(assembler.push(FlightData::default()).is_err());
+    }
+}
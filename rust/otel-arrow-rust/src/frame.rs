@@ -0,0 +1,127 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Self-contained byte frames of OTAP batches, for transports without stream state such as
+//! Kafka topics or files of an object store.
+//!
+//! The payloads of a `BatchArrowRecords` sent on a gRPC stream usually rely on the schemas
+//! and dictionaries sent earlier on the stream. A frame holds a `BatchArrowRecords` whose
+//! every payload is a complete IPC stream, see
+//! [`to_batch_arrow_records`], so it can be decoded on its own by a new [`Consumer`]. The
+//! layout of a frame is:
+//!
+//! - the magic bytes `OTAF`.
+//! - the version of the layout, [`FRAME_VERSION`], on one byte.
+//! - the length of the body, a little endian u32.
+//! - the body, the protobuf encoded `BatchArrowRecords`.
+//!
+//! The frames are self delimiting, so several of them can be concatenated in a file and read
+//! back with [`read_frame`].
+
+use prost::Message;
+use snafu::ensure;
+
+use crate::Consumer;
+use crate::encode::split::to_batch_arrow_records;
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+
+/// Magic bytes starting every frame.
+pub const FRAME_MAGIC: [u8; 4] = *b"OTAF";
+
+/// Version of the layout of the frames written by this crate.
+pub const FRAME_VERSION: u8 = 1;
+
+const HEADER_LEN: usize = FRAME_MAGIC.len() + 1 + 4;
+
+/// Serializes the OTAP batch as a frame.
+pub fn to_frame(otap_batch: &OtapBatch, batch_id: i64) -> Result<Vec<u8>> {
+    let records = to_batch_arrow_records(otap_batch, batch_id)?;
+    let body_len = records.encoded_len();
+    let invalid = || error::InvalidFrameSnafu {
+        reason: format!("body of {body_len} bytes is too large"),
+    };
+    ensure!(u32::try_from(body_len).is_ok(), invalid());
+
+    let mut frame = Vec::with_capacity(HEADER_LEN + body_len);
+    frame.extend_from_slice(&FRAME_MAGIC);
+    frame.push(FRAME_VERSION);
+    frame.extend_from_slice(&(body_len as u32).to_le_bytes());
+    records.encode(&mut frame).map_err(|e| {
+        error::InvalidFrameSnafu {
+            reason: e.to_string(),
+        }
+        .build()
+    })?;
+    Ok(frame)
+}
+
+/// Reads the frame at the start of `bytes`. Returns its `BatchArrowRecords` and the length
+/// of the frame, the next frame, if any, starting right after.
+pub fn read_frame(bytes: &[u8]) -> Result<(BatchArrowRecords, usize)> {
+    let invalid = |reason: &'static str| error::InvalidFrameSnafu { reason };
+    ensure!(bytes.len() >= HEADER_LEN, invalid("truncated header"));
+    let (magic, rest) = bytes.split_at(FRAME_MAGIC.len());
+    ensure!(magic == FRAME_MAGIC, invalid("bad magic bytes"));
+    let (version, rest) = rest.split_at(1);
+    ensure!(version[0] == FRAME_VERSION, error::InvalidFrameSnafu {
+        reason: format!("unsupported version {}", version[0]),
+    });
+    let (body_len, rest) = rest.split_at(4);
+    // safety: the header has the length of a u32
+    let body_len = u32::from_le_bytes(body_len.try_into().expect("u32 length")) as usize;
+    ensure!(rest.len() >= body_len, invalid("truncated body"));
+
+    let records = BatchArrowRecords::decode(&rest[..body_len]).map_err(|e| {
+        error::InvalidFrameSnafu {
+            reason: e.to_string(),
+        }
+        .build()
+    })?;
+    Ok((records, HEADER_LEN + body_len))
+}
+
+/// Deserializes the OTAP batch of a frame written by [`to_frame`]. Fails if `frame` holds
+/// anything after the frame.
+pub fn from_frame(frame: &[u8]) -> Result<OtapBatch> {
+    let (mut records, len) = read_frame(frame)?;
+    ensure!(len == frame.len(), error::InvalidFrameSnafu {
+        reason: format!("{} trailing bytes", frame.len() - len),
+    });
+    Consumer::default().consume_otap_batch(&mut records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let otap_batch = traces_batch(16, 2);
+        let frame = to_frame(&otap_batch, 7).unwrap();
+        assert_eq!(frame[..4], FRAME_MAGIC);
+
+        let decoded = from_frame(&frame).unwrap();
+        for payload_type in [ArrowPayloadType::Spans, ArrowPayloadType::SpanAttrs] {
+            assert_eq!(decoded.get(payload_type), otap_batch.get(payload_type));
+        }
+
+        // concatenated frames are read one after the other
+        let mut frames = frame.clone();
+        frames.extend(to_frame(&otap_batch, 8).unwrap());
+        let (records, len) = read_frame(&frames).unwrap();
+        assert_eq!((records.batch_id, len), (7, frame.len()));
+        let (records, _) = read_frame(&frames[len..]).unwrap();
+        assert_eq!(records.batch_id, 8);
+
+        assert!(from_frame(&frames).is_err());
+        assert!(from_frame(&frame[..frame.len() - 1]).is_err());
+        let mut bad_version = frame.clone();
+        bad_version[4] = FRAME_VERSION + 1;
+        assert!(from_frame(&bad_version).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Metadata of the OTAP batches, e.g. a tenant id or an auth context, carried by the
+//! `headers` of the `BatchArrowRecords`.
+//!
+//! The headers are an HPACK encoded block of header fields. The producers attach the
+//! [`BatchHeaders`] of a batch with [`BatchArrowRecords::set_batch_headers`] once it is
+//! encoded, and the receivers read them with [`BatchArrowRecords::batch_headers`] alongside
+//! the request they decode. The headers are written as literal fields, without indexing nor
+//! Huffman coding, so they don't depend on the headers of the previous batches of the
+//! stream. The fields sent otherwise, e.g. by encoders keeping a dynamic table across the
+//! batches, are skipped when read.
+
+use snafu::OptionExt;
+
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+
+/// Header fields of a batch, in the order they are sent. A name can be sent several times.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BatchHeaders {
+    fields: Vec<(String, String)>,
+}
+
+impl BatchHeaders {
+    /// Creates empty headers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a field.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.fields.push((name.into(), value.into()));
+    }
+
+    /// Appends a field, see [`BatchHeaders::insert`].
+    #[must_use]
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    /// Returns the value of the last field with the name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the fields in the order they are sent.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns the number of fields.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns whether there is no field.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns the HPACK block of the fields.
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (name, value) in &self.fields {
+            write_literal(&mut out, name.as_bytes(), value.as_bytes());
+        }
+        out
+    }
+
+    /// Reads the fields of the HPACK block, skipping the ones that are indexed, Huffman
+    /// coded or not UTF-8. Fails if the block is truncated.
+    pub fn decode(mut block: &[u8]) -> Result<Self> {
+        let mut headers = Self::new();
+        while let Some(field) = next_header(&mut block).context(error::InvalidBatchHeadersSnafu)? {
+            if let (Some(name), Some(value)) = field {
+                if let (Ok(name), Ok(value)) =
+                    (std::str::from_utf8(name), std::str::from_utf8(value))
+                {
+                    headers.insert(name, value);
+                }
+            }
+        }
+        Ok(headers)
+    }
+}
+
+impl BatchArrowRecords {
+    /// Returns the headers of the batch, see [`BatchHeaders::decode`].
+    pub fn batch_headers(&self) -> Result<BatchHeaders> {
+        BatchHeaders::decode(&self.headers)
+    }
+
+    /// Replaces the headers of the batch.
+    pub fn set_batch_headers(&mut self, headers: &BatchHeaders) {
+        self.headers = headers.encode();
+    }
+}
+
+/// Writes a literal header field without indexing, with a new name.
+pub(crate) fn write_literal(out: &mut Vec<u8>, name: &[u8], value: &[u8]) {
+    out.push(0x00);
+    write_string(out, name);
+    write_string(out, value);
+}
+
+/// Writes a HPACK string literal, not Huffman coded.
+pub(crate) fn write_string(out: &mut Vec<u8>, value: &[u8]) {
+    write_integer(out, 0x00, 7, value.len());
+    out.extend_from_slice(value);
+}
+
+/// Writes a HPACK integer with a prefix of `prefix_bits` bits, the higher bits of the first
+/// byte being `flags`.
+fn write_integer(out: &mut Vec<u8>, flags: u8, prefix_bits: u32, mut value: usize) {
+    let max_prefix = (1 << prefix_bits) - 1;
+    if value < max_prefix {
+        out.push(flags | value as u8);
+        return;
+    }
+    out.push(flags | max_prefix as u8);
+    value -= max_prefix;
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Name and value of a header field, when they are sent as literals that are not Huffman
+/// coded.
+pub(crate) type HeaderField<'a> = (Option<&'a [u8]>, Option<&'a [u8]>);
+
+/// Reads the next header field representation. Returns `Some(None)` at the end of the
+/// input and `None` if it is truncated.
+pub(crate) fn next_header<'a>(input: &mut &'a [u8]) -> Option<Option<HeaderField<'a>>> {
+    let Some(&first) = input.first() else {
+        return Some(None);
+    };
+    let (name_index_bits, is_literal) = match first {
+        // indexed header field
+        0x80..=0xff => (7, false),
+        // literal header field with incremental indexing
+        0x40..=0x7f => (6, true),
+        // dynamic table size update
+        0x20..=0x3f => (5, false),
+        // literal header field without indexing or never indexed
+        _ => (4, true),
+    };
+    let name_index = read_integer(input, name_index_bits)?;
+    if !is_literal {
+        return Some(Some((None, None)));
+    }
+    let name = if name_index == 0 {
+        read_string(input)?
+    } else {
+        None
+    };
+    let value = read_string(input)?;
+    Some(Some((name, value)))
+}
+
+/// Reads a HPACK string literal, `Some(None)` if it is Huffman coded.
+fn read_string<'a>(input: &mut &'a [u8]) -> Option<Option<&'a [u8]>> {
+    let huffman = input.first()? & 0x80 != 0;
+    let len = read_integer(input, 7)?;
+    let (value, rest) = input.split_at_checked(len)?;
+    *input = rest;
+    Some((!huffman).then_some(value))
+}
+
+/// Reads a HPACK integer with a prefix of `prefix_bits` bits.
+fn read_integer(input: &mut &[u8], prefix_bits: u32) -> Option<usize> {
+    let (&first, mut rest) = input.split_first()?;
+    let max_prefix = (1 << prefix_bits) - 1;
+    let mut value = usize::from(first) & max_prefix;
+    if value == max_prefix {
+        let mut shift = 0;
+        loop {
+            let (&byte, next) = rest.split_first()?;
+            rest = next;
+            value = value.checked_add(usize::from(byte & 0x7f).checked_shl(shift)?)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+    *input = rest;
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::Consumer;
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_batch_headers() {
+        let headers = BatchHeaders::new()
+            .with("tenant-id", "acme")
+            .with("x-long", "x".repeat(200))
+            .with("tenant-id", "other");
+        assert_eq!(headers.get("tenant-id"), Some("other"));
+        assert_eq!(headers.len(), 3);
+
+        let mut records = to_batch_arrow_records(&traces_batch(4, 1), 3).unwrap();
+        records.set_batch_headers(&headers);
+        // an indexed field and a Huffman coded value sent by another encoder are skipped
+        records
+            .headers
+            .extend_from_slice(&[0x82, 0x00, 0x01, b'k', 0x81, 0xff]);
+        let _ = Consumer::default().consume_batches(&mut records).unwrap();
+        assert_eq!(records.batch_headers().unwrap(), headers);
+
+        records.headers.truncate(records.headers.len() - 1);
+        assert!(matches!(
+            records.batch_headers(),
+            Err(error::Error::InvalidBatchHeaders { .. })
+        ));
+        assert!(BatchHeaders::decode(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,252 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Inspection of received `BatchArrowRecords` without decoding them, so receivers can admit
+//! or reject a batch, e.g. on its size, before paying the decode cost.
+//!
+//! The accessors only read the lengths of the payloads and the headers of their Arrow IPC
+//! messages: the number of rows of a payload is the one declared by the headers of its
+//! record batch messages, whose bodies are neither decompressed nor validated. The total
+//! serialized size of a batch is its protobuf encoded length, `prost::Message::encoded_len`.
+//!
+//! [`DecodedSizeEstimator`] predicts from the same headers the memory the batch takes once
+//! decoded into an OTLP request: the rows of every payload weigh the size of the message
+//! they decode to, e.g. a `Span` for a row of the spans, and the attributes also weigh the
+//! bytes of their payloads, which mostly hold their keys and string values. The estimate
+//! ignores the dictionaries shared across the batches of a stream, and the strings of the
+//! other payloads, e.g. the bodies of the logs, only counting their message.
+
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use arrow::ipc::MessageHeader;
+
+use crate::compression::{IpcMessage, split_ipc_messages};
+use crate::error::{ErrorContextExt, Result};
+use crate::otap::graph::is_attributes;
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayload, ArrowPayloadType, BatchArrowRecords};
+use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+use crate::proto::opentelemetry::logs::v1::LogRecord;
+use crate::proto::opentelemetry::metrics::v1::{
+    Exemplar, ExponentialHistogramDataPoint, HistogramDataPoint, Metric, NumberDataPoint,
+    SummaryDataPoint,
+};
+use crate::proto::opentelemetry::trace::v1::Span;
+use crate::proto::opentelemetry::trace::v1::span::{Event, Link};
+
+/// Summary of a payload of a batch, see [`BatchArrowRecords::payload_infos`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayloadInfo {
+    /// Type of the payload, `Unknown` for the types this crate doesn't know.
+    pub payload_type: ArrowPayloadType,
+    /// Size of the serialized Arrow IPC messages of the payload.
+    pub bytes: usize,
+    /// Number of rows declared by the record batch messages of the payload, none if it only
+    /// carries a schema or dictionaries.
+    pub rows: Option<usize>,
+}
+
+impl ArrowPayload {
+    /// Returns the number of rows declared by the record batch messages of the payload, none
+    /// if it only carries a schema or dictionaries.
+    pub fn declared_rows(&self) -> Result<Option<usize>> {
+        let mut rows = None;
+        for IpcMessage { message, .. } in
+            split_ipc_messages(&self.record).in_payload(self.r#type())?
+        {
+            if message.header_type() == MessageHeader::RecordBatch {
+                let length = message
+                    .header_as_record_batch()
+                    .map_or(0, |record_batch| record_batch.length());
+                *rows.get_or_insert(0) += usize::try_from(length).unwrap_or_default();
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl BatchArrowRecords {
+    /// Returns the types of the payloads of the batch, in the order of their first payload.
+    #[must_use]
+    pub fn payload_types(&self) -> Vec<ArrowPayloadType> {
+        let mut payload_types = Vec::with_capacity(self.arrow_payloads.len());
+        for payload in &self.arrow_payloads {
+            if !payload_types.contains(&payload.r#type()) {
+                payload_types.push(payload.r#type());
+            }
+        }
+        payload_types
+    }
+
+    /// Returns the size of the serialized Arrow IPC messages of all the payloads.
+    #[must_use]
+    pub fn payload_bytes(&self) -> usize {
+        self.arrow_payloads.iter().map(|p| p.record.len()).sum()
+    }
+
+    /// Returns the size of the serialized Arrow IPC messages of the payloads of the type.
+    #[must_use]
+    pub fn payload_bytes_of(&self, payload_type: ArrowPayloadType) -> usize {
+        self.arrow_payloads
+            .iter()
+            .filter(|p| p.r#type() == payload_type)
+            .map(|p| p.record.len())
+            .sum()
+    }
+
+    /// Returns the number of rows declared by the payloads of the type, none if the batch
+    /// has no record batch of this type.
+    pub fn declared_rows(&self, payload_type: ArrowPayloadType) -> Result<Option<usize>> {
+        let mut rows = None;
+        for payload in &self.arrow_payloads {
+            if payload.r#type() == payload_type {
+                if let Some(payload_rows) = payload.declared_rows()? {
+                    *rows.get_or_insert(0) += payload_rows;
+                }
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Returns the summary of every payload of the batch, in order.
+    pub fn payload_infos(&self) -> Result<Vec<PayloadInfo>> {
+        self.arrow_payloads
+            .iter()
+            .map(|payload| {
+                Ok(PayloadInfo {
+                    payload_type: payload.r#type(),
+                    bytes: payload.record.len(),
+                    rows: payload.declared_rows()?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Estimator of the memory a batch takes once decoded, see the module documentation.
+#[derive(Clone, Debug, Default)]
+pub struct DecodedSizeEstimator {
+    row_weights: HashMap<ArrowPayloadType, usize>,
+}
+
+impl DecodedSizeEstimator {
+    /// Creates an estimator weighing the rows with the size of their OTLP message.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the bytes a row of the payload weighs, instead of the size of its message.
+    #[must_use]
+    pub fn with_row_weight(mut self, payload_type: ArrowPayloadType, bytes: usize) -> Self {
+        let _ = self.row_weights.insert(payload_type, bytes);
+        self
+    }
+
+    /// Returns the bytes a row of the payload weighs.
+    #[must_use]
+    pub fn row_weight(&self, payload_type: ArrowPayloadType) -> usize {
+        if let Some(weight) = self.row_weights.get(&payload_type) {
+            return *weight;
+        }
+        match payload_type {
+            ArrowPayloadType::Logs => size_of::<LogRecord>(),
+            ArrowPayloadType::Spans => size_of::<Span>(),
+            ArrowPayloadType::SpanEvents => size_of::<Event>(),
+            ArrowPayloadType::SpanLinks => size_of::<Link>(),
+            ArrowPayloadType::UnivariateMetrics => size_of::<Metric>(),
+            ArrowPayloadType::NumberDataPoints => size_of::<NumberDataPoint>(),
+            ArrowPayloadType::SummaryDataPoints => size_of::<SummaryDataPoint>(),
+            ArrowPayloadType::HistogramDataPoints => size_of::<HistogramDataPoint>(),
+            ArrowPayloadType::ExpHistogramDataPoints => size_of::<ExponentialHistogramDataPoint>(),
+            ArrowPayloadType::NumberDpExemplars
+            | ArrowPayloadType::HistogramDpExemplars
+            | ArrowPayloadType::ExpHistogramDpExemplars => size_of::<Exemplar>(),
+            payload_type if is_attributes(payload_type) => {
+                size_of::<KeyValue>() + size_of::<AnyValue>()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Returns the estimated size of the batch once decoded, in bytes.
+    pub fn estimate(&self, records: &BatchArrowRecords) -> Result<usize> {
+        let mut size = 0usize;
+        for payload in &records.arrow_payloads {
+            let payload_type = payload.r#type();
+            let rows = payload.declared_rows()?.unwrap_or_default();
+            size = size.saturating_add(rows.saturating_mul(self.row_weight(payload_type)));
+            if is_attributes(payload_type) {
+                size = size.saturating_add(payload.record.len());
+            }
+        }
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+
+    use super::*;
+
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_inspect_batch_arrow_records() {
+        let otap_batch = traces_batch(8, 2);
+        let mut records = to_batch_arrow_records(&otap_batch, 1).unwrap();
+        assert_eq!(records.payload_types(), vec![
+            ArrowPayloadType::Spans,
+            ArrowPayloadType::SpanAttrs
+        ]);
+        assert_eq!(
+            records.declared_rows(ArrowPayloadType::Spans).unwrap(),
+            Some(8)
+        );
+        assert_eq!(
+            records.declared_rows(ArrowPayloadType::SpanAttrs).unwrap(),
+            Some(16)
+        );
+        assert_eq!(records.declared_rows(ArrowPayloadType::Logs).unwrap(), None);
+
+        let infos = records.payload_infos().unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(
+            infos.iter().map(|i| i.bytes).sum::<usize>(),
+            records.payload_bytes()
+        );
+        assert_eq!(
+            records.payload_bytes_of(ArrowPayloadType::Spans),
+            infos[0].bytes
+        );
+        assert!(records.encoded_len() > records.payload_bytes());
+
+        records.arrow_payloads.push(ArrowPayload {
+            schema_id: "".into(),
+            r#type: ArrowPayloadType::Spans as i32,
+            record: vec![0xff, 0xff, 0xff, 0xff, 8],
+        });
+        assert!(records.declared_rows(ArrowPayloadType::Spans).is_err());
+    }
+
+    #[test]
+    fn test_decoded_size_estimator() {
+        let records = to_batch_arrow_records(&traces_batch(8, 2), 1).unwrap();
+        let estimator = DecodedSizeEstimator::new();
+        let span_attrs = records.payload_bytes_of(ArrowPayloadType::SpanAttrs);
+        let expected = 8 * size_of::<Span>()
+            + 16 * (size_of::<KeyValue>() + size_of::<AnyValue>())
+            + span_attrs;
+        assert_eq!(estimator.estimate(&records).unwrap(), expected);
+
+        let estimator = estimator
+            .with_row_weight(ArrowPayloadType::Spans, 100)
+            .with_row_weight(ArrowPayloadType::SpanAttrs, 10);
+        assert_eq!(
+            estimator.estimate(&records).unwrap(),
+            800 + 160 + span_attrs
+        );
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tracking of the time batches spend in the pipeline, to verify freshness SLOs.
+//!
+//! The receiver stamps every batch with a [`ReceiveTime`] when it arrives. When the batch is
+//! exported, the exporter passes the stamp to a [`DwellTimeTracker`], which records the
+//! dwell time of the batch in a histogram. The dwell time can optionally be added to the
+//! resources of the exported request with [`inject_dwell_time_attribute`], so it can be
+//! checked downstream.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::ExportRequest;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+use crate::proto::opentelemetry::resource::v1::Resource;
+
+/// Default resource attribute key used to report the dwell time of a batch, in nanoseconds.
+pub const DWELL_TIME_ATTRIBUTE: &str = "otel_arrow.pipeline.dwell_time_ns";
+
+/// Time at which a batch was received.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ReceiveTime(SystemTime);
+
+impl ReceiveTime {
+    /// Stamps a batch received now.
+    #[must_use]
+    pub fn now() -> Self {
+        Self(SystemTime::now())
+    }
+
+    /// Returns the receive time as nanoseconds since the unix epoch.
+    #[must_use]
+    pub fn as_unix_nano(&self) -> u64 {
+        self.0
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    }
+
+    /// Returns the time elapsed between the reception of the batch and `now`. Returns zero
+    /// if `now` is before the receive time.
+    #[must_use]
+    pub fn dwell_time(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.0).unwrap_or_default()
+    }
+}
+
+impl From<SystemTime> for ReceiveTime {
+    fn from(time: SystemTime) -> Self {
+        Self(time)
+    }
+}
+
+/// Distribution of the dwell times recorded by a [`DwellTimeTracker`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DwellTimeDistribution {
+    /// Number of batches recorded.
+    pub count: u64,
+    /// Sum of the dwell times.
+    pub sum: Duration,
+    /// Smallest dwell time recorded.
+    pub min: Option<Duration>,
+    /// Largest dwell time recorded.
+    pub max: Option<Duration>,
+    /// Upper bounds (inclusive) of the histogram buckets, sorted in ascending order.
+    pub bounds: Vec<Duration>,
+    /// Number of batches per bucket. The last bucket counts the dwell times above the
+    /// largest bound, so there is one more bucket than bounds.
+    pub bucket_counts: Vec<u64>,
+}
+
+impl DwellTimeDistribution {
+    /// Returns the mean dwell time, if any batch was recorded.
+    #[must_use]
+    pub fn mean(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.sum / self.count as u32)
+    }
+
+    /// Returns the fraction of batches whose dwell time is below or equal to `bound`, which
+    /// must be one of the bucket bounds. Returns `None` if it isn't or if no batch was
+    /// recorded.
+    #[must_use]
+    pub fn fraction_within(&self, bound: Duration) -> Option<f64> {
+        let idx = self.bounds.iter().position(|b| *b == bound)?;
+        if self.count == 0 {
+            return None;
+        }
+        let within: u64 = self.bucket_counts[..=idx].iter().sum();
+        Some(within as f64 / self.count as f64)
+    }
+}
+
+/// Records the dwell time of the exported batches in a histogram.
+#[derive(Clone, Debug)]
+pub struct DwellTimeTracker {
+    distribution: DwellTimeDistribution,
+}
+
+impl Default for DwellTimeTracker {
+    fn default() -> Self {
+        Self::new(
+            [1, 5, 10, 50, 100, 500, 1_000, 5_000, 10_000]
+                .into_iter()
+                .map(Duration::from_millis)
+                .collect(),
+        )
+    }
+}
+
+impl DwellTimeTracker {
+    /// Creates a tracker using the given bucket upper bounds.
+    #[must_use]
+    pub fn new(mut bounds: Vec<Duration>) -> Self {
+        bounds.sort_unstable();
+        bounds.dedup();
+        let bucket_counts = vec![0; bounds.len() + 1];
+        Self {
+            distribution: DwellTimeDistribution {
+                bounds,
+                bucket_counts,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Records the dwell time of a batch received at `received_at` and exported at
+    /// `exported_at`. Returns the dwell time.
+    pub fn record(&mut self, received_at: ReceiveTime, exported_at: SystemTime) -> Duration {
+        let dwell_time = received_at.dwell_time(exported_at);
+        let distribution = &mut self.distribution;
+        distribution.count += 1;
+        distribution.sum += dwell_time;
+        distribution.min = Some(distribution.min.map_or(dwell_time, |m| m.min(dwell_time)));
+        distribution.max = Some(distribution.max.map_or(dwell_time, |m| m.max(dwell_time)));
+        let bucket = distribution.bounds.partition_point(|b| *b < dwell_time);
+        distribution.bucket_counts[bucket] += 1;
+        dwell_time
+    }
+
+    /// Returns the distribution of the dwell times recorded so far.
+    #[must_use]
+    pub fn distribution(&self) -> &DwellTimeDistribution {
+        &self.distribution
+    }
+
+    /// Returns the distribution of the dwell times recorded so far, and starts a new one.
+    pub fn take_distribution(&mut self) -> DwellTimeDistribution {
+        let bounds = self.distribution.bounds.clone();
+        std::mem::replace(&mut self.distribution, Self::new(bounds).distribution)
+    }
+}
+
+/// Sets the `key` attribute of every resource of the request to the dwell time of the
+/// batch, in nanoseconds. An existing attribute with the same key is replaced.
+pub fn inject_dwell_time_attribute(request: &mut ExportRequest, key: &str, dwell_time: Duration) {
+    let value = Value::IntValue(i64::try_from(dwell_time.as_nanos()).unwrap_or(i64::MAX));
+    let resources: Vec<&mut Resource> = match request {
+        ExportRequest::Logs(logs) => logs
+            .resource_logs
+            .iter_mut()
+            .map(|r| r.resource.get_or_insert_default())
+            .collect(),
+        ExportRequest::Metrics(metrics) => metrics
+            .resource_metrics
+            .iter_mut()
+            .map(|r| r.resource.get_or_insert_default())
+            .collect(),
+        ExportRequest::Traces(traces) => traces
+            .resource_spans
+            .iter_mut()
+            .map(|r| r.resource.get_or_insert_default())
+            .collect(),
+    };
+
+    for resource in resources {
+        let value = Some(AnyValue {
+            value: Some(value.clone()),
+        });
+        match resource.attributes.iter_mut().find(|kv| kv.key == key) {
+            Some(kv) => kv.value = value,
+            None => resource.attributes.push(KeyValue {
+                key: key.to_string(),
+                value,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+    use crate::proto::opentelemetry::trace::v1::ResourceSpans;
+
+    #[test]
+    fn test_dwell_time_tracker() {
+        let mut tracker =
+            DwellTimeTracker::new(vec![Duration::from_millis(100), Duration::from_millis(10)]);
+        let received_at = ReceiveTime::from(UNIX_EPOCH + Duration::from_secs(1));
+        for (ms, expected) in [(5, 5), (10, 10), (50, 50), (500, 500)] {
+            let exported_at = UNIX_EPOCH + Duration::from_secs(1) + Duration::from_millis(ms);
+            assert_eq!(
+                tracker.record(received_at, exported_at),
+                Duration::from_millis(expected)
+            );
+        }
+        // clock going backwards
+        assert_eq!(tracker.record(received_at, UNIX_EPOCH), Duration::ZERO);
+
+        let distribution = tracker.take_distribution();
+        assert_eq!(distribution.count, 5);
+        assert_eq!(distribution.bounds, vec![
+            Duration::from_millis(10),
+            Duration::from_millis(100)
+        ]);
+        assert_eq!(distribution.bucket_counts, vec![3, 1, 1]);
+        assert_eq!(distribution.min, Some(Duration::ZERO));
+        assert_eq!(distribution.max, Some(Duration::from_millis(500)));
+        assert_eq!(distribution.mean(), Some(Duration::from_millis(113)));
+        assert_eq!(
+            distribution.fraction_within(Duration::from_millis(100)),
+            Some(0.8)
+        );
+        assert_eq!(received_at.as_unix_nano(), 1_000_000_000);
+
+        assert_eq!(tracker.distribution().count, 0);
+        assert_eq!(tracker.distribution().mean(), None);
+    }
+
+    #[test]
+    fn test_inject_dwell_time_attribute() {
+        let mut request = ExportRequest::Traces(ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans::default(), ResourceSpans::default()],
+        });
+        inject_dwell_time_attribute(&mut request, DWELL_TIME_ATTRIBUTE, Duration::from_secs(1));
+        inject_dwell_time_attribute(&mut request, DWELL_TIME_ATTRIBUTE, Duration::from_secs(2));
+
+        let ExportRequest::Traces(traces) = request else {
+            unreachable!()
+        };
+        for resource_spans in traces.resource_spans {
+            assert_eq!(resource_spans.resource.unwrap().attributes, vec![
+                KeyValue {
+                    key: DWELL_TIME_ATTRIBUTE.to_string(),
+                    value: Some(AnyValue {
+                        value: Some(Value::IntValue(2_000_000_000)),
+                    }),
+                }
+            ]);
+        }
+    }
+}
@@ -14,21 +14,69 @@
 //! It contains code for decoding OTAP Arrow record batches into OTLP protos,
 //! and encoding OTLP protos into OTAP Messages. It also contains
 //! the rust implementation of pdata.
+//!
+//! The [`prelude`] re-exports the stable API of the crate. The column names of the payloads
+//! and the parent id decoders of the attributes are only public with the `internal`
+//! feature, and the data generators with the `bench` feature, both with no stability
+//! guarantee.
 
+pub mod archive;
 #[allow(dead_code)]
 pub(crate) mod arrays;
+pub mod cancel;
+pub mod compression;
+#[cfg(any(test, feature = "bench"))]
+pub mod datagen;
 mod decode;
+mod delta;
+pub mod encode;
 mod error;
+#[cfg(feature = "ffi")]
+#[allow(unsafe_code)]
+pub mod ffi;
+#[cfg(feature = "flight")]
+pub mod flight;
+pub mod frame;
+pub mod headers;
+pub mod inspect;
+pub mod latency;
+pub mod memory;
+#[cfg(feature = "client")]
+pub mod negotiate;
 pub mod otap;
 pub mod otlp;
+pub mod pipeline;
+pub mod prelude;
+pub mod queue;
+pub mod replay;
+pub mod retry;
 #[allow(dead_code)]
 pub mod schema;
+#[cfg(feature = "zstd")]
+pub mod size_report;
+pub mod status;
+pub mod stream;
+pub mod telemetry;
+/// Synthetic data shared by the tests and the benchmarks.
+#[cfg(any(test, feature = "bench"))]
+#[doc(hidden)]
+pub mod test_util;
 #[cfg(test)]
-mod test_util;
-#[cfg(test)]
+#[cfg(all(feature = "client", feature = "server"))]
 mod validation;
+pub mod value;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub mod pdata;
 pub mod proto;
 
-pub use decode::decoder::Consumer;
+pub use decode::checkpoint::ConsumerCheckpoint;
+pub use decode::decoder::{
+    Consumer, ExportRequest, MultiSignalRequest, PartialMultiSignalRequest, SkippedPayload,
+};
+pub use decode::passthrough::{PassthroughBatch, PassthroughConsumer};
+pub use decode::payload_registry::{PayloadDecoder, PayloadRegistry};
+pub use decode::routing::{RoutedBatch, RoutingKey, RoutingKeyExtractor};
+pub use decode::schema_registry::{SchemaEvent, SchemaRegistry};
+pub use error::ErrorContext;
@@ -0,0 +1,183 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Accounting of the memory of the batches produced, so it shows up in the memory
+//! accounting of the host and can be capped.
+//!
+//! A [`MemoryPool`] is passed to the producers, e.g. with
+//! [`LogsProducer::with_memory_pool`](crate::encode::LogsProducer::with_memory_pool). Once a
+//! batch is produced, the memory of its arrays is reserved in the pool, the production
+//! failing if the pool refuses it. The [`MemoryReservation`] is held by the batch and
+//! released when it is dropped.
+//!
+//! [`TrackingMemoryPool`] counts the bytes reserved, up to an optional limit. Hosts with
+//! their own accounting, e.g. the pool of a query engine, implement [`MemoryPool`] on top
+//! of it.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::{self, Result};
+
+/// Pool the memory of the batches is reserved from.
+pub trait MemoryPool: Debug + Send + Sync {
+    /// Reserves the bytes, returning false if the pool can't hold them.
+    fn try_grow(&self, bytes: usize) -> bool;
+
+    /// Releases bytes previously reserved.
+    fn shrink(&self, bytes: usize);
+
+    /// Returns the number of bytes reserved.
+    fn reserved(&self) -> usize;
+}
+
+/// Pool counting the bytes reserved, refusing the reservations over its limit if it has
+/// one.
+#[derive(Debug, Default)]
+pub struct TrackingMemoryPool {
+    reserved: AtomicUsize,
+    limit: Option<usize>,
+}
+
+impl TrackingMemoryPool {
+    /// Creates a pool without limit.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a pool holding up to `limit` bytes.
+    #[must_use]
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            reserved: AtomicUsize::new(0),
+            limit: Some(limit),
+        }
+    }
+
+    /// Returns the number of bytes the pool holds at most, if limited.
+    #[must_use]
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+}
+
+impl MemoryPool for TrackingMemoryPool {
+    fn try_grow(&self, bytes: usize) -> bool {
+        self.reserved
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |reserved| {
+                let reserved = reserved.checked_add(bytes)?;
+                match self.limit {
+                    Some(limit) if reserved > limit => None,
+                    _ => Some(reserved),
+                }
+            })
+            .is_ok()
+    }
+
+    fn shrink(&self, bytes: usize) {
+        let _ = self
+            .reserved
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |reserved| {
+                Some(reserved.saturating_sub(bytes))
+            });
+    }
+
+    fn reserved(&self) -> usize {
+        self.reserved.load(Ordering::Relaxed)
+    }
+}
+
+/// Bytes reserved in a pool, released when dropped.
+#[derive(Debug)]
+pub struct MemoryReservation {
+    pool: Arc<dyn MemoryPool>,
+    bytes: usize,
+}
+
+impl MemoryReservation {
+    /// Returns the number of bytes reserved.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        self.pool.shrink(self.bytes);
+    }
+}
+
+/// Reserves the bytes in the pool, if any, failing if the pool refuses them.
+pub(crate) fn reserve(
+    pool: Option<&Arc<dyn MemoryPool>>,
+    bytes: usize,
+) -> Result<Option<MemoryReservation>> {
+    let Some(pool) = pool else {
+        return Ok(None);
+    };
+    if !pool.try_grow(bytes) {
+        return error::MemoryLimitExceededSnafu {
+            requested: bytes,
+            reserved: pool.reserved(),
+        }
+        .fail();
+    }
+    Ok(Some(MemoryReservation {
+        pool: pool.clone(),
+        bytes,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::LogsProducer;
+    use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    #[test]
+    fn test_memory_pool() {
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![LogRecord {
+                        time_unix_nano: 1,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let pool = Arc::new(TrackingMemoryPool::new());
+        let producer = LogsProducer::new().with_memory_pool(pool.clone());
+        let otap_batch = producer.produce(&request).unwrap();
+        let size = otap_batch.memory_size();
+        assert!(size > 0);
+        assert_eq!(
+            otap_batch.reservation().map(MemoryReservation::size),
+            Some(size)
+        );
+        assert_eq!(pool.reserved(), size);
+        let second = producer.produce(&request).unwrap();
+        assert_eq!(pool.reserved(), 2 * size);
+        drop(otap_batch);
+        drop(second);
+        assert_eq!(pool.reserved(), 0);
+
+        let pool = Arc::new(TrackingMemoryPool::with_limit(size - 1));
+        let result = LogsProducer::new()
+            .with_memory_pool(pool.clone())
+            .produce(&request);
+        assert!(matches!(
+            result,
+            Err(error::Error::MemoryLimitExceeded { .. })
+        ));
+        assert_eq!(pool.reserved(), 0);
+    }
+}
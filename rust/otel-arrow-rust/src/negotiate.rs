@@ -0,0 +1,123 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Negotiation of the protocol of an exporter, so it can be deployed against a fleet of
+//! receivers of which only some support OTAP.
+//!
+//! The exporter opens its OTel Arrow streams through a [`ProtocolNegotiator`], which
+//! tries the stream first and downgrades to the standard OTLP gRPC services when the
+//! receiver fails it with `UNIMPLEMENTED`, see [`is_otap_unsupported`]. The receivers may
+//! also only fail the stream on its first batch, its error is then passed to
+//! [`ProtocolNegotiator::on_stream_error`], and the batches in flight are sent again with
+//! OTLP, see [`crate::retry::RetryQueue::on_stream_closed`].
+//!
+//! Once downgraded, the negotiator doesn't try OTAP again until it is
+//! [reset](ProtocolNegotiator::reset), e.g. periodically or when the exporter reconnects to
+//! another receiver.
+
+use std::future::Future;
+
+use tonic::Status;
+
+pub use crate::retry::{Protocol, is_otap_unsupported};
+
+/// Outcome of opening a stream, see [`ProtocolNegotiator::open`].
+#[derive(Debug)]
+pub enum Negotiated<T> {
+    /// The OTel Arrow stream was opened.
+    Otap(T),
+    /// The receiver doesn't support OTAP, the batches are sent with OTLP.
+    Otlp,
+}
+
+/// Negotiates the protocol of an exporter, see the module documentation.
+#[derive(Clone, Debug, Default)]
+pub struct ProtocolNegotiator {
+    protocol: Option<Protocol>,
+}
+
+impl ProtocolNegotiator {
+    /// Creates a negotiator trying OTAP first.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the negotiated protocol, none before a stream was opened.
+    #[must_use]
+    pub fn protocol(&self) -> Option<Protocol> {
+        self.protocol
+    }
+
+    /// Opens the OTel Arrow stream with `open_stream`, unless the negotiator already
+    /// downgraded to OTLP. The errors other than the receiver not supporting OTAP are
+    /// returned as is, the protocol being still unknown.
+    pub async fn open<T, F, Fut>(&mut self, open_stream: F) -> Result<Negotiated<T>, Status>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, Status>>,
+    {
+        if self.protocol == Some(Protocol::Otlp) {
+            return Ok(Negotiated::Otlp);
+        }
+        match open_stream().await {
+            Ok(stream) => {
+                self.protocol = Some(Protocol::Otap);
+                Ok(Negotiated::Otap(stream))
+            }
+            Err(status) if self.on_stream_error(&status) => Ok(Negotiated::Otlp),
+            Err(status) => Err(status),
+        }
+    }
+
+    /// Records the error an OTel Arrow stream failed with, returning whether the
+    /// negotiator downgraded to OTLP.
+    pub fn on_stream_error(&mut self, status: &Status) -> bool {
+        let unsupported = is_otap_unsupported(status);
+        if unsupported {
+            self.protocol = Some(Protocol::Otlp);
+        }
+        unsupported
+    }
+
+    /// Forgets the negotiated protocol, the next stream trying OTAP again.
+    pub fn reset(&mut self) {
+        self.protocol = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_protocol_negotiator() {
+        let mut negotiator = ProtocolNegotiator::new();
+        assert_eq!(negotiator.protocol(), None);
+        let unavailable = negotiator
+            .open(|| async { Err::<(), _>(Status::unavailable("down")) })
+            .await;
+        assert!(unavailable.is_err());
+        assert_eq!(negotiator.protocol(), None);
+
+        let stream = negotiator.open(|| async { Ok(42) }).await.unwrap();
+        assert!(matches!(stream, Negotiated::Otap(42)));
+        assert_eq!(negotiator.protocol(), Some(Protocol::Otap));
+
+        // the receiver fails the stream on its first batch
+        assert!(!negotiator.on_stream_error(&Status::internal("bug")));
+        assert!(negotiator.on_stream_error(&Status::unimplemented("no arrow")));
+        assert_eq!(negotiator.protocol(), Some(Protocol::Otlp));
+        let stream = negotiator
+            .open(|| async { panic!("OTAP is not tried again") })
+            .await;
+        assert!(matches!(stream, Ok(Negotiated::<()>::Otlp)));
+
+        negotiator.reset();
+        let stream = negotiator
+            .open(|| async { Err::<(), _>(Status::unimplemented("no arrow")) })
+            .await;
+        assert!(matches!(stream, Ok(Negotiated::Otlp)));
+        assert_eq!(negotiator.protocol(), Some(Protocol::Otlp));
+    }
+}
@@ -7,9 +7,26 @@
 use arrow::array::RecordBatch;
 
 use crate::{
-    decode::record_message::RecordMessage, proto::opentelemetry::arrow::v1::ArrowPayloadType,
+    decode::record_message::RecordMessage, memory::MemoryReservation,
+    proto::opentelemetry::arrow::v1::ArrowPayloadType,
 };
 
+pub mod column_cache;
+pub mod diff;
+pub mod filter;
+pub mod graph;
+#[cfg(feature = "integrity")]
+pub mod integrity;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod partition;
+pub mod projection;
+#[cfg(feature = "id-remap")]
+pub mod remap;
+pub mod sampling;
+pub mod stats;
+pub mod summary;
+pub mod trace_groups;
 #[allow(missing_docs)]
 pub mod transform;
 
@@ -45,6 +62,65 @@ impl OtapBatch {
             Self::Traces(spans) => spans.get(payload_type),
         }
     }
+
+    /// Returns the main payload type for this type of telemetry signal. Per the
+    /// specification, this is the payload type that is the first payload in the
+    /// `BatchArrowRecords`.
+    #[must_use]
+    pub fn main_payload_type(&self) -> ArrowPayloadType {
+        match self {
+            Self::Logs(_) => ArrowPayloadType::Logs,
+            Self::Metrics(_) => ArrowPayloadType::UnivariateMetrics,
+            Self::Traces(_) => ArrowPayloadType::Spans,
+        }
+    }
+
+    /// Returns the payload types for which a record batch is present in this batch.
+    /// The main payload type, if present, is always returned first.
+    #[must_use]
+    pub fn payload_types(&self) -> Vec<ArrowPayloadType> {
+        let main_payload_type = self.main_payload_type();
+        let mut payload_types = Vec::new();
+        if self.get(main_payload_type).is_some() {
+            payload_types.push(main_payload_type);
+        }
+        payload_types.extend(
+            (0..POSITION_LOOKUP.len() as i32)
+                .filter_map(|t| ArrowPayloadType::try_from(t).ok())
+                .filter(|t| *t != main_payload_type && self.get(*t).is_some()),
+        );
+        payload_types
+    }
+
+    /// Returns the memory of the arrays of the record batches, in bytes.
+    #[must_use]
+    pub fn memory_size(&self) -> usize {
+        self.payload_types()
+            .into_iter()
+            .filter_map(|payload_type| self.get(payload_type))
+            .map(RecordBatch::get_array_memory_size)
+            .sum()
+    }
+
+    /// Returns the memory reserved for the record batches by their producer, see
+    /// [`crate::memory`].
+    #[must_use]
+    pub fn reservation(&self) -> Option<&MemoryReservation> {
+        match self {
+            Self::Logs(logs) => logs.reservation.as_ref(),
+            Self::Metrics(metrics) => metrics.reservation.as_ref(),
+            Self::Traces(spans) => spans.reservation.as_ref(),
+        }
+    }
+
+    /// Holds the reservation until the batch is dropped.
+    pub(crate) fn set_reservation(&mut self, reservation: Option<MemoryReservation>) {
+        match self {
+            Self::Logs(logs) => logs.reservation = reservation,
+            Self::Metrics(metrics) => metrics.reservation = reservation,
+            Self::Traces(spans) => spans.reservation = reservation,
+        }
+    }
 }
 
 /// The ArrowBatchStore helper trait is used to define a common interface for
@@ -161,6 +237,7 @@ const UNUSED_INDEX: usize = 99;
 #[derive(Default)]
 pub struct Logs {
     batches: [Option<RecordBatch>; 4],
+    reservation: Option<MemoryReservation>,
 }
 
 impl OtapBatchStore for Logs {
@@ -182,6 +259,7 @@ impl OtapBatchStore for Logs {
 #[derive(Default)]
 pub struct Metrics {
     batches: [Option<RecordBatch>; 18],
+    reservation: Option<MemoryReservation>,
 }
 
 impl OtapBatchStore for Metrics {
@@ -217,6 +295,7 @@ impl OtapBatchStore for Metrics {
 #[derive(Default)]
 pub struct Traces {
     batches: [Option<RecordBatch>; 8],
+    reservation: Option<MemoryReservation>,
 }
 
 impl OtapBatchStore for Traces {
@@ -301,6 +380,29 @@ mod test {
         assert!(otap_batch.get(ArrowPayloadType::LogAttrs).is_some());
     }
 
+    #[test]
+    fn test_payload_types() {
+        let mut otap_batch = OtapBatch::Logs(Logs::new());
+        assert!(otap_batch.payload_types().is_empty());
+
+        let schema = Schema::new(vec![Field::new("a", DataType::UInt8, false)]);
+        let record_batch = RecordBatch::try_new(Arc::new(schema), vec![Arc::new(
+            UInt8Array::from_iter_values(vec![1]),
+        )])
+        .unwrap();
+
+        otap_batch.set(ArrowPayloadType::LogAttrs, record_batch.clone());
+        otap_batch.set(ArrowPayloadType::ResourceAttrs, record_batch.clone());
+        otap_batch.set(ArrowPayloadType::Logs, record_batch);
+
+        // main payload type comes first, the rest are in enum order
+        assert_eq!(otap_batch.payload_types(), vec![
+            ArrowPayloadType::Logs,
+            ArrowPayloadType::ResourceAttrs,
+            ArrowPayloadType::LogAttrs,
+        ]);
+    }
+
     #[test]
     fn test_metrics_getset() {
         let mut otap_batch = OtapBatch::Metrics(Metrics::new());
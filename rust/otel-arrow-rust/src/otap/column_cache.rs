@@ -0,0 +1,174 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lazy cache of materialized columns of an [`OtapBatch`].
+//!
+//! Many columns of OTAP record batches are dictionary encoded (e.g. span names, attribute
+//! keys). Transforms that need the plain values of such a column all have to resolve the
+//! dictionary. When several transforms of a pipeline query the same batch, a
+//! [`ColumnCache`] can be shared between them so that each column is only resolved once.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use arrow::array::{Array, ArrayRef};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use snafu::ResultExt;
+
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+/// Memoizes the materialized columns of a batch. The cache borrows the batch, so the
+/// batch can't be modified while cached columns may be handed out.
+pub struct ColumnCache<'a> {
+    otap_batch: &'a OtapBatch,
+    columns: RefCell<HashMap<(ArrowPayloadType, String), ArrayRef>>,
+}
+
+impl<'a> ColumnCache<'a> {
+    /// Creates an empty cache for the given batch.
+    #[must_use]
+    pub fn new(otap_batch: &'a OtapBatch) -> Self {
+        Self {
+            otap_batch,
+            columns: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the batch the columns are materialized from.
+    #[must_use]
+    pub fn otap_batch(&self) -> &'a OtapBatch {
+        self.otap_batch
+    }
+
+    /// Returns the column of the given payload with its dictionary, if any, resolved into
+    /// plain values. Columns that are not dictionary encoded are returned as is. Returns
+    /// `None` if the payload or the column is not present in the batch.
+    ///
+    /// The column is only materialized on the first call, following calls return the same
+    /// array.
+    pub fn materialized(
+        &self,
+        payload_type: ArrowPayloadType,
+        column_name: &str,
+    ) -> Result<Option<ArrayRef>> {
+        let key = (payload_type, column_name.to_string());
+        if let Some(column) = self.columns.borrow().get(&key) {
+            return Ok(Some(column.clone()));
+        }
+
+        let Some(column) = self
+            .otap_batch
+            .get(payload_type)
+            .and_then(|rb| rb.column_by_name(column_name))
+        else {
+            return Ok(None);
+        };
+
+        let column = match column.data_type() {
+            DataType::Dictionary(_, value_type) => {
+                cast(column, value_type).context(error::ReadRecordBatchSnafu)?
+            }
+            _ => column.clone(),
+        };
+        let _ = self.columns.borrow_mut().insert(key, column.clone());
+        Ok(Some(column))
+    }
+
+    /// Returns the number of columns currently cached.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.columns.borrow().len()
+    }
+
+    /// Returns true if no column is cached.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.columns.borrow().is_empty()
+    }
+
+    /// Drops all the cached columns.
+    pub fn clear(&self) {
+        self.columns.borrow_mut().clear();
+    }
+}
+
+impl std::fmt::Debug for ColumnCache<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ColumnCache")
+            .field("columns", &self.columns.borrow().keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{AsArray, DictionaryArray, RecordBatch, StringArray, UInt16Array};
+    use arrow::datatypes::{Field, Schema, UInt8Type};
+
+    use crate::otap::Traces;
+    use crate::schema::consts;
+
+    #[test]
+    fn test_materialized_columns_are_cached() {
+        let names: DictionaryArray<UInt8Type> = vec!["a", "b", "a"].into_iter().collect();
+        let mut otap_batch = OtapBatch::Traces(Traces::default());
+        otap_batch.set(
+            ArrowPayloadType::Spans,
+            RecordBatch::try_new(
+                Arc::new(Schema::new(vec![
+                    Field::new(consts::ID, DataType::UInt16, true),
+                    Field::new(consts::NAME, names.data_type().clone(), true),
+                ])),
+                vec![Arc::new(UInt16Array::from(vec![0, 1, 2])), Arc::new(names)],
+            )
+            .unwrap(),
+        );
+
+        let cache = ColumnCache::new(&otap_batch);
+        let names = cache
+            .materialized(ArrowPayloadType::Spans, consts::NAME)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            names.as_string::<i32>(),
+            &StringArray::from(vec!["a", "b", "a"])
+        );
+        let cached = cache
+            .materialized(ArrowPayloadType::Spans, consts::NAME)
+            .unwrap()
+            .unwrap();
+        assert!(Arc::ptr_eq(&names, &cached));
+
+        // plain columns are returned as is
+        let ids = cache
+            .materialized(ArrowPayloadType::Spans, consts::ID)
+            .unwrap()
+            .unwrap();
+        let spans = otap_batch.get(ArrowPayloadType::Spans).unwrap();
+        assert!(Arc::ptr_eq(&ids, spans.column(0)));
+        assert_eq!(cache.len(), 2);
+
+        assert!(
+            cache
+                .materialized(ArrowPayloadType::Spans, consts::KIND)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            cache
+                .materialized(ArrowPayloadType::SpanAttrs, consts::NAME)
+                .unwrap()
+                .is_none()
+        );
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,416 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Column level comparison of two OTAP batches, e.g. to check the batches this crate encodes
+//! against the ones of the Go implementation, or to triage a regression.
+//!
+//! [`diff_batches`] compares the record batches of every payload type of the two batches,
+//! column by column and row by row: the payloads and columns only in one of the batches,
+//! the columns of different types, the payloads of different lengths and the rows whose
+//! values differ are reported, with the values of both batches. The columns are compared by
+//! value, whatever the encoding: a dictionary encoded column equals the plain column of the
+//! same values. [`diff_records`] reads two `BatchArrowRecords` to compare them, and
+//! [`diff_round_trip`] compares a `BatchArrowRecords` to the batch this crate encodes from
+//! its decoded request, where the rows may come in another order and the ids differ.
+
+use std::fmt;
+
+use arrow::array::{Array, ArrayRef, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use arrow::util::display::array_value_to_string;
+
+use crate::decode::decoder::{Consumer, ExportRequest};
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayloadType, BatchArrowRecords};
+
+/// Number of mismatching rows reported with their values per column, the others are only
+/// counted.
+pub const MAX_REPORTED_ROWS: usize = 8;
+
+/// Batch of the two compared, the left one being the first argument.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Side {
+    /// The first batch.
+    Left,
+    /// The second batch.
+    Right,
+}
+
+/// Row of a column whose values differ between the batches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RowMismatch {
+    /// Index of the row.
+    pub row: usize,
+    /// Value of the left batch, formatted.
+    pub left: String,
+    /// Value of the right batch, formatted.
+    pub right: String,
+}
+
+/// Difference between the batches.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// The payload type is only in one of the batches.
+    MissingPayload {
+        /// Type of the payload.
+        payload_type: ArrowPayloadType,
+        /// Batch without the payload.
+        missing_from: Side,
+    },
+    /// The payloads don't have the same number of rows, the rows they share are still
+    /// compared.
+    RowCount {
+        /// Type of the payload.
+        payload_type: ArrowPayloadType,
+        /// Rows of the left payload.
+        left: usize,
+        /// Rows of the right payload.
+        right: usize,
+    },
+    /// The column is only in one of the payloads.
+    MissingColumn {
+        /// Type of the payload.
+        payload_type: ArrowPayloadType,
+        /// Name of the column.
+        column: String,
+        /// Batch without the column.
+        missing_from: Side,
+    },
+    /// The columns have types that can't be compared by value.
+    ColumnType {
+        /// Type of the payload.
+        payload_type: ArrowPayloadType,
+        /// Name of the column.
+        column: String,
+        /// Type of the left column.
+        left: DataType,
+        /// Type of the right column.
+        right: DataType,
+    },
+    /// Rows of the column have different values.
+    Values {
+        /// Type of the payload.
+        payload_type: ArrowPayloadType,
+        /// Name of the column.
+        column: String,
+        /// Number of rows whose values differ.
+        mismatched_rows: usize,
+        /// The first [`MAX_REPORTED_ROWS`] rows whose values differ.
+        rows: Vec<RowMismatch>,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPayload {
+                payload_type,
+                missing_from,
+            } => write!(
+                f,
+                "{}: payload missing from the {missing_from:?} batch",
+                payload_type.as_str_name()
+            ),
+            Self::RowCount {
+                payload_type,
+                left,
+                right,
+            } => write!(
+                f,
+                "{}: {left} rows != {right} rows",
+                payload_type.as_str_name()
+            ),
+            Self::MissingColumn {
+                payload_type,
+                column,
+                missing_from,
+            } => write!(
+                f,
+                "{}.{column}: column missing from the {missing_from:?} batch",
+                payload_type.as_str_name()
+            ),
+            Self::ColumnType {
+                payload_type,
+                column,
+                left,
+                right,
+            } => write!(
+                f,
+                "{}.{column}: {left} != {right}",
+                payload_type.as_str_name()
+            ),
+            Self::Values {
+                payload_type,
+                column,
+                mismatched_rows,
+                rows,
+            } => {
+                write!(
+                    f,
+                    "{}.{column}: {mismatched_rows} rows differ",
+                    payload_type.as_str_name()
+                )?;
+                for row in rows {
+                    write!(f, "\n  row {}: {} != {}", row.row, row.left, row.right)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Differences between two batches, see the module documentation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchDiff {
+    /// The differences, by payload type in the order of the left batch.
+    pub differences: Vec<Difference>,
+}
+
+impl BatchDiff {
+    /// Returns whether the batches hold the same values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+impl fmt::Display for BatchDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for difference in &self.differences {
+            writeln!(f, "{difference}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the two batches.
+#[must_use]
+pub fn diff_batches(left: &OtapBatch, right: &OtapBatch) -> BatchDiff {
+    let mut payload_types = left.payload_types();
+    for payload_type in right.payload_types() {
+        if !payload_types.contains(&payload_type) {
+            payload_types.push(payload_type);
+        }
+    }
+    let mut differences = Vec::new();
+    for payload_type in payload_types {
+        match (left.get(payload_type), right.get(payload_type)) {
+            (Some(left), Some(right)) => {
+                diff_payloads(payload_type, left, right, &mut differences);
+            }
+            (left, _) => differences.push(Difference::MissingPayload {
+                payload_type,
+                missing_from: if left.is_none() {
+                    Side::Left
+                } else {
+                    Side::Right
+                },
+            }),
+        }
+    }
+    BatchDiff { differences }
+}
+
+/// Reads the two batches with consumers of their own and compares them. The batches must
+/// carry the schemas of their payloads, e.g. be the first batches of their streams.
+pub fn diff_records(left: &BatchArrowRecords, right: &BatchArrowRecords) -> Result<BatchDiff> {
+    let left = Consumer::default().consume_otap_batch(&mut left.clone())?;
+    let right = Consumer::default().consume_otap_batch(&mut right.clone())?;
+    Ok(diff_batches(&left, &right))
+}
+
+/// Compares the batch, on the left, to the batch this crate encodes from its decoded
+/// request, on the right. The batch must carry the schemas of its payloads.
+pub fn diff_round_trip(records: &BatchArrowRecords) -> Result<BatchDiff> {
+    let original = Consumer::default().consume_otap_batch(&mut records.clone())?;
+    let round_trip = match Consumer::default().consume_batches(&mut records.clone())? {
+        ExportRequest::Logs(request) => LogsProducer::new().produce(&request)?,
+        ExportRequest::Metrics(request) => MetricsProducer::new().produce(&request)?,
+        ExportRequest::Traces(request) => TracesProducer::new().produce(&request)?,
+    };
+    Ok(diff_batches(&original, &round_trip))
+}
+
+fn diff_payloads(
+    payload_type: ArrowPayloadType,
+    left: &RecordBatch,
+    right: &RecordBatch,
+    differences: &mut Vec<Difference>,
+) {
+    if left.num_rows() != right.num_rows() {
+        differences.push(Difference::RowCount {
+            payload_type,
+            left: left.num_rows(),
+            right: right.num_rows(),
+        });
+    }
+    let rows = left.num_rows().min(right.num_rows());
+
+    for field in left.schema().fields() {
+        let column = field.name();
+        let Some(right_column) = right.column_by_name(column) else {
+            differences.push(Difference::MissingColumn {
+                payload_type,
+                column: column.clone(),
+                missing_from: Side::Right,
+            });
+            continue;
+        };
+        // safety: the field comes from the schema of the record batch
+        let left_column = left.column_by_name(column).expect("column of the schema");
+        let Some((left_column, right_column)) = comparable(left_column, right_column) else {
+            differences.push(Difference::ColumnType {
+                payload_type,
+                column: column.clone(),
+                left: left_column.data_type().clone(),
+                right: right_column.data_type().clone(),
+            });
+            continue;
+        };
+
+        let mut mismatched_rows = 0;
+        let mut reported = Vec::new();
+        for row in 0..rows {
+            if left_column.slice(row, 1).to_data() == right_column.slice(row, 1).to_data() {
+                continue;
+            }
+            mismatched_rows += 1;
+            if reported.len() < MAX_REPORTED_ROWS {
+                reported.push(RowMismatch {
+                    row,
+                    left: format_value(&left_column, row),
+                    right: format_value(&right_column, row),
+                });
+            }
+        }
+        if mismatched_rows > 0 {
+            differences.push(Difference::Values {
+                payload_type,
+                column: column.clone(),
+                mismatched_rows,
+                rows: reported,
+            });
+        }
+    }
+
+    for field in right.schema().fields() {
+        if left.column_by_name(field.name()).is_none() {
+            differences.push(Difference::MissingColumn {
+                payload_type,
+                column: field.name().clone(),
+                missing_from: Side::Left,
+            });
+        }
+    }
+}
+
+/// Returns the columns as arrays of the same type, the dictionary encoded ones being
+/// decoded, `None` if their values are of different types.
+fn comparable(left: &ArrayRef, right: &ArrayRef) -> Option<(ArrayRef, ArrayRef)> {
+    if left.data_type() == right.data_type() {
+        return Some((left.clone(), right.clone()));
+    }
+    let value_type = |array: &ArrayRef| match array.data_type() {
+        DataType::Dictionary(_, value_type) => value_type.as_ref().clone(),
+        data_type => data_type.clone(),
+    };
+    let data_type = value_type(left);
+    if data_type != value_type(right) {
+        return None;
+    }
+    Some((cast(left, &data_type).ok()?, cast(right, &data_type).ok()?))
+}
+
+fn format_value(array: &ArrayRef, row: usize) -> String {
+    if array.is_null(row) {
+        return "null".to_string();
+    }
+    array_value_to_string(array, row).unwrap_or_else(|_| "?".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{DictionaryArray, StringArray, UInt8Array, UInt16Array};
+    use arrow::datatypes::{Field, Schema, UInt8Type};
+
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::otap::Logs;
+    use crate::schema::consts;
+    use crate::test_util::workloads::traces_batch;
+
+    fn logs(ids: Vec<u16>, names: ArrayRef) -> OtapBatch {
+        let schema = Schema::new(vec![
+            Field::new(consts::ID, DataType::UInt16, false),
+            Field::new(consts::SEVERITY_TEXT, names.data_type().clone(), true),
+        ]);
+        let rb = RecordBatch::try_new(Arc::new(schema), vec![
+            Arc::new(UInt16Array::from(ids)),
+            names,
+        ])
+        .unwrap();
+        let mut batch = OtapBatch::Logs(Logs::default());
+        batch.set(ArrowPayloadType::Logs, rb);
+        batch
+    }
+
+    #[test]
+    fn test_diff_batches() {
+        let plain = logs(
+            vec![0, 1, 2],
+            Arc::new(StringArray::from(vec![Some("INFO"), None, Some("WARN")])),
+        );
+        let dictionary = DictionaryArray::<UInt8Type>::new(
+            UInt8Array::from(vec![Some(0), None, Some(1)]),
+            Arc::new(StringArray::from(vec!["INFO", "WARN"])),
+        );
+        let encoded = logs(vec![0, 1, 2], Arc::new(dictionary));
+        assert!(diff_batches(&plain, &encoded).is_empty());
+
+        let other = logs(
+            vec![0, 1],
+            Arc::new(StringArray::from(vec![Some("INFO"), Some("DEBUG")])),
+        );
+        let diff = diff_batches(&plain, &other);
+        assert_eq!(diff.differences, vec![
+            Difference::RowCount {
+                payload_type: ArrowPayloadType::Logs,
+                left: 3,
+                right: 2,
+            },
+            Difference::Values {
+                payload_type: ArrowPayloadType::Logs,
+                column: consts::SEVERITY_TEXT.to_string(),
+                mismatched_rows: 1,
+                rows: vec![RowMismatch {
+                    row: 1,
+                    left: "null".to_string(),
+                    right: "DEBUG".to_string(),
+                }],
+            },
+        ]);
+        assert_eq!(
+            diff.to_string(),
+            "LOGS: 3 rows != 2 rows\nLOGS.severity_text: 1 rows differ\n  row 1: null != DEBUG\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_round_trip() {
+        let records = to_batch_arrow_records(&traces_batch(16, 2), 0).unwrap();
+        assert!(diff_records(&records, &records).unwrap().is_empty());
+        let diff = diff_round_trip(&records).unwrap();
+        assert!(
+            diff.differences
+                .iter()
+                .all(|d| !matches!(d, Difference::MissingPayload { .. })),
+            "{diff}"
+        );
+    }
+}
@@ -0,0 +1,1044 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Filtering of the rows of a payload along with the rows of the payloads referencing them.
+//!
+//! The payloads of a batch form a tree, see [`graph`](crate::otap::graph): the rows of the main payload are
+//! referenced by the parent ids of their attributes and of the child payloads, e.g. the
+//! spans by the span events, whose rows are referenced in turn by the event attributes.
+//! When rows are dropped from a payload, [`filter_with_children`] drops the rows of the
+//! payloads below it referencing them, and the resource and scope attributes no record
+//! references anymore. The ids and parent ids of the kept rows are encoded again, so the
+//! filtered batch decodes like a batch produced with only the kept rows:
+//!
+//! - the ids of the records, resources and scopes stay delta encoded;
+//! - the parent ids of the attributes are made plain;
+//! - the parent ids of the other payloads stay delta encoded within their runs, e.g. of
+//!   consecutive span events with the same name. Two runs of a key brought together by the
+//!   filter, which the producers never write, are merged by reordering the rows by key and
+//!   parent id, which only changes the order of the rows of a parent with different keys.
+//!   The ids of the rows are then renumbered in their new order.
+//!
+//! The ids and parent ids marked `plain` by the `encoding` metadata of their column, e.g. in
+//! the batches of the encoders remapping their ids, are not delta encoded: they are kept
+//! plain, and only filtered.
+//!
+//! [`slice_payload`] slices the rows of a payload the same way: the slice of a delta
+//! encoded column starts with the absolute id of its first row, rather than with a delta
+//! from a row left out, so the slices can be processed on their own, e.g. in parallel.
+
+use std::collections::HashMap;
+use std::ops::{AddAssign, Range};
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, ArrowPrimitiveType, AsArray, BooleanArray, PrimitiveArray, RecordBatch,
+    StructArray, UInt32Array,
+};
+use arrow::compute::{
+    cast, filter, filter_record_batch, prep_null_mask_filter, take, take_record_batch,
+};
+use arrow::datatypes::{ArrowNativeTypeOp, DataType, Field, Schema, UInt16Type, UInt32Type};
+use snafu::{OptionExt, ensure};
+
+use crate::arrays::{
+    ByteArrayAccessor, ColumnAccessor, NullableArrayAccessor, StringArrayAccessor,
+    get_f64_array_opt, get_i64_array_opt, get_required_array, get_u16_array_opt,
+};
+use crate::error::{self, ErrorContextExt, Result};
+use crate::otap::OtapBatch;
+use crate::otap::graph::{ParentIdEncoding, children, is_attributes, parent_id_encoding};
+use crate::otap::transform::{materialize_parent_ids, remove_delta_encoding_from_column};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts::{self, metadata};
+
+/// Plain ids of the rows kept in a payload, mapped to the plain ids they have once the
+/// payload is filtered.
+type KeptIds = HashMap<u32, u32>;
+
+/// Key of the run the parent id of a row is delta encoded in, see [`ParentIdEncoding`].
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum RunKey {
+    /// The row continues the run of the previous row, whatever its key, and the next rows
+    /// continue the run of the previous key, the exemplars without value.
+    Previous,
+    /// The row continues the run of the previous row with the same key, or starts a run.
+    Key(Option<Vec<u8>>),
+}
+
+/// Returns whether the ids of the column are marked plain rather than delta encoded.
+fn is_plain(field: &Field) -> bool {
+    field
+        .metadata()
+        .get(metadata::COLUMN_ENCODING)
+        .map(String::as_str)
+        == Some(metadata::encodings::PLAIN)
+}
+
+/// Returns whether the column of the batch is marked plain, see [`is_plain`].
+fn is_plain_column(rb: &RecordBatch, name: &str) -> bool {
+    rb.schema_ref().field_with_name(name).is_ok_and(is_plain)
+}
+
+/// Keeps the rows of the payload selected by the mask, a null keeping no row, and the rows
+/// of the payloads below it referencing them, see the module documentation. Returns the
+/// number of rows dropped from the payload.
+pub fn filter_with_children(
+    otap_batch: &mut OtapBatch,
+    payload_type: ArrowPayloadType,
+    mask: &BooleanArray,
+) -> Result<usize> {
+    let Some(rb) = otap_batch.get(payload_type).cloned() else {
+        return Ok(0);
+    };
+    ensure!(
+        mask.len() == rb.num_rows(),
+        error::MaskLengthMismatchSnafu {
+            expected: rb.num_rows(),
+            actual: mask.len(),
+        }
+    );
+    let mask = match mask.nulls() {
+        Some(_) => prep_null_mask_filter(mask),
+        None => mask.clone(),
+    };
+    let dropped = mask.len() - mask.true_count();
+    if dropped == 0 {
+        return Ok(0);
+    }
+
+    let kept = if payload_type == otap_batch.main_payload_type() {
+        filter_main_payload(otap_batch, payload_type, &rb, &mask)?
+    } else if is_attributes(payload_type) {
+        let rb = plain_parent_ids(&rb).in_payload(payload_type)?;
+        // safety: the mask has as many rows as the batch
+        let rb = filter_record_batch(&rb, &mask).expect("mask matches the batch");
+        otap_batch.set(payload_type, rb);
+        return Ok(dropped);
+    } else {
+        let parent_ids = parent_ids(&rb, payload_type).in_payload(payload_type)?;
+        filter_records(otap_batch, payload_type, &rb, &mask, &parent_ids)?
+    };
+    filter_children(otap_batch, payload_type, &kept)?;
+    Ok(dropped)
+}
+
+/// Returns the rows of the payload in the range, with their ids and parent ids encoded
+/// again for the slice, see the module documentation. The parent ids of the attributes are
+/// made plain.
+pub fn slice_payload(
+    rb: &RecordBatch,
+    payload_type: ArrowPayloadType,
+    range: Range<usize>,
+) -> Result<RecordBatch> {
+    ensure!(
+        range.start <= range.end && range.end <= rb.num_rows(),
+        error::InvalidSliceRangeSnafu {
+            start: range.start,
+            end: range.end,
+            rows: rb.num_rows(),
+        }
+    );
+    let rb = if is_attributes(payload_type) {
+        plain_parent_ids(rb).in_payload(payload_type)?
+    } else {
+        rb.clone()
+    };
+    let parent_ids = match parent_id_encoding(payload_type) {
+        Some(ParentIdEncoding::Attributes) | None => None,
+        Some(_) => Some(parent_ids(&rb, payload_type).in_payload(payload_type)?),
+    };
+
+    let sliced = rb.slice(range.start, range.len());
+    let mut columns = sliced.columns().to_vec();
+    for (idx, field) in rb.schema_ref().fields().iter().enumerate() {
+        let column = rb.column(idx);
+        match (field.name().as_str(), column.as_struct_opt()) {
+            (consts::ID, _) if is_plain(field) => {}
+            (consts::ID, _) => {
+                columns[idx] = slice_delta_ids(column, range.clone()).in_payload(payload_type)?;
+            }
+            (consts::RESOURCE | consts::SCOPE, Some(struct_array)) => {
+                columns[idx] =
+                    slice_struct_ids(struct_array, range.clone()).in_payload(payload_type)?;
+            }
+            (consts::PARENT_ID, _) if is_plain(field) => {}
+            (consts::PARENT_ID, _) => {
+                let Some(parent_ids) = &parent_ids else {
+                    continue;
+                };
+                let keys = run_keys(&sliced, payload_type).in_payload(payload_type)?;
+                let rows: Vec<u32> = (0..range.len() as u32).collect();
+                // safety: the rows keep their order, the parent ids of every run are sorted
+                let encoded = encode_parent_ids(&keys, &parent_ids[range.clone()], &rows)
+                    .expect("parent ids are sorted");
+                let encoded: ArrayRef = Arc::new(UInt32Array::from(encoded));
+                // safety: the parent ids are encoded from values of the column
+                columns[idx] = cast(&encoded, field.data_type()).expect("parent ids can be cast");
+            }
+            _ => {}
+        }
+    }
+    // safety: the columns keep their types and all have the rows of the slice
+    Ok(RecordBatch::try_new(sliced.schema(), columns).expect("columns match the schema"))
+}
+
+/// Returns the rows of the delta encoded ids column in the range, encoded again.
+fn slice_delta_ids(column: &ArrayRef, range: Range<usize>) -> Result<ArrayRef> {
+    fn slice<T>(ids: &PrimitiveArray<T>, range: Range<usize>) -> ArrayRef
+    where
+        T: ArrowPrimitiveType,
+        T::Native: AddAssign,
+    {
+        let ids = remove_delta_encoding_from_column(ids).slice(range.start, range.len());
+        Arc::new(delta_ids(&ids))
+    }
+    match column.data_type() {
+        DataType::UInt16 => Ok(slice(column.as_primitive::<UInt16Type>(), range)),
+        DataType::UInt32 => Ok(slice(column.as_primitive::<UInt32Type>(), range)),
+        data_type => error::ColumnDataTypeMismatchSnafu {
+            name: consts::ID,
+            expect: DataType::UInt32,
+            actual: data_type.clone(),
+        }
+        .fail(),
+    }
+}
+
+/// Returns the rows of the `resource` or `scope` column in the range, its ids encoded
+/// again.
+fn slice_struct_ids(struct_array: &StructArray, range: Range<usize>) -> Result<ArrayRef> {
+    let sliced = struct_array.slice(range.start, range.len());
+    let mut columns = sliced.columns().to_vec();
+    for (idx, field) in struct_array.fields().iter().enumerate() {
+        if field.name() == consts::ID && !is_plain(field) {
+            columns[idx] = slice_delta_ids(struct_array.column(idx), range.clone())?;
+        }
+    }
+    // safety: the columns keep their types and all have the rows of the slice
+    let struct_array = StructArray::try_new(
+        struct_array.fields().clone(),
+        columns,
+        sliced.nulls().cloned(),
+    )
+    .expect("columns match the fields");
+    Ok(Arc::new(struct_array))
+}
+
+/// Keeps the rows of the children of the payload whose parent is in `kept`.
+fn filter_children(
+    otap_batch: &mut OtapBatch,
+    payload_type: ArrowPayloadType,
+    kept: &KeptIds,
+) -> Result<()> {
+    for &child in children(payload_type) {
+        if is_attributes(child) {
+            filter_attrs(otap_batch, child, kept)?;
+            continue;
+        }
+        let Some(rb) = otap_batch.get(child).cloned() else {
+            continue;
+        };
+        let parent_ids = parent_ids(&rb, child).in_payload(child)?;
+        let mask: BooleanArray = parent_ids
+            .iter()
+            .map(|parent_id| Some(kept.contains_key(parent_id)))
+            .collect();
+        let parent_ids: Vec<_> = parent_ids
+            .iter()
+            .map(|parent_id| kept.get(parent_id).copied().unwrap_or_default())
+            .collect();
+        let kept_children = filter_records(otap_batch, child, &rb, &mask, &parent_ids)?;
+        filter_children(otap_batch, child, &kept_children)?;
+    }
+    Ok(())
+}
+
+/// Keeps the rows of the main payload selected by the mask, and the resource and scope
+/// attributes still referenced. Returns the ids of the kept records, which keep their ids.
+fn filter_main_payload(
+    otap_batch: &mut OtapBatch,
+    payload_type: ArrowPayloadType,
+    rb: &RecordBatch,
+    mask: &BooleanArray,
+) -> Result<KeptIds> {
+    let plain_ids = is_plain_column(rb, consts::ID);
+    let kept = get_u16_array_opt(rb, consts::ID)
+        .in_payload(payload_type)?
+        .map(|ids| {
+            let ids = if plain_ids {
+                ids.clone()
+            } else {
+                remove_delta_encoding_from_column(ids)
+            };
+            ids.iter()
+                .zip(mask.values())
+                .filter_map(|(id, kept)| id.filter(|_| kept))
+                .map(|id| (u32::from(id), u32::from(id)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut columns = Vec::with_capacity(rb.num_columns());
+    for (field, column) in rb.schema_ref().fields().iter().zip(rb.columns()) {
+        let column = match (field.name().as_str(), column.as_struct_opt()) {
+            (consts::ID, _) if !is_plain(field) => {
+                filter_delta_ids::<UInt16Type>(column, mask).in_payload(payload_type)?
+            }
+            (consts::RESOURCE | consts::SCOPE, Some(struct_array)) => {
+                filter_struct_ids(struct_array, mask).in_payload(payload_type)?
+            }
+            // safety: the mask has as many rows as the batch
+            _ => filter(column, mask).expect("mask matches the batch"),
+        };
+        columns.push(column);
+    }
+    // safety: the columns keep their types and all have the rows kept by the mask
+    let rb = RecordBatch::try_new(rb.schema(), columns).expect("columns match the schema");
+    let resource_ids = struct_ids(&rb, consts::RESOURCE);
+    let scope_ids = struct_ids(&rb, consts::SCOPE);
+    otap_batch.set(payload_type, rb);
+
+    if let Some(resource_ids) = resource_ids {
+        filter_attrs(otap_batch, ArrowPayloadType::ResourceAttrs, &resource_ids)?;
+    }
+    if let Some(scope_ids) = scope_ids {
+        filter_attrs(otap_batch, ArrowPayloadType::ScopeAttrs, &scope_ids)?;
+    }
+    Ok(kept)
+}
+
+/// Returns the ids of the `resource` or `scope` column of the main payload.
+fn struct_ids(rb: &RecordBatch, name: &str) -> Option<KeptIds> {
+    let struct_array = rb.column_by_name(name)?.as_struct_opt()?;
+    let (idx, _) = struct_array.fields().find(consts::ID)?;
+    let ids = struct_array.column(idx).as_primitive_opt::<UInt16Type>()?;
+    let ids = if is_plain(&struct_array.fields()[idx]) {
+        ids.clone()
+    } else {
+        remove_delta_encoding_from_column(ids)
+    };
+    Some(
+        ids.iter()
+            .flatten()
+            .map(|id| (u32::from(id), u32::from(id)))
+            .collect(),
+    )
+}
+
+/// Returns the delta encoding of the ids, the nulls being skipped.
+fn delta_ids<T: ArrowPrimitiveType>(ids: &PrimitiveArray<T>) -> PrimitiveArray<T> {
+    let mut prev = T::Native::ZERO;
+    ids.iter()
+        .map(|id| {
+            id.map(|id| {
+                let delta = id.sub_wrapping(prev);
+                prev = id;
+                delta
+            })
+        })
+        .collect()
+}
+
+/// Keeps the rows of the delta encoded ids column selected by the mask, encoding their ids
+/// again.
+fn filter_delta_ids<T>(column: &ArrayRef, mask: &BooleanArray) -> Result<ArrayRef>
+where
+    T: ArrowPrimitiveType,
+    T::Native: AddAssign,
+{
+    let ids =
+        column
+            .as_primitive_opt::<T>()
+            .with_context(|| error::ColumnDataTypeMismatchSnafu {
+                name: consts::ID,
+                expect: T::DATA_TYPE,
+                actual: column.data_type().clone(),
+            })?;
+    // safety: the mask has as many rows as the column
+    let ids =
+        filter(&remove_delta_encoding_from_column(ids), mask).expect("mask matches the column");
+    Ok(Arc::new(delta_ids(ids.as_primitive::<T>())))
+}
+
+/// Keeps the rows of the `resource` or `scope` column selected by the mask, encoding its
+/// ids again.
+fn filter_struct_ids(struct_array: &StructArray, mask: &BooleanArray) -> Result<ArrayRef> {
+    // safety: the mask has as many rows as the column
+    let filtered = filter(struct_array, mask).expect("mask matches the column");
+    let filtered = filtered.as_struct();
+    let mut columns = Vec::with_capacity(struct_array.num_columns());
+    for ((field, column), filtered) in struct_array
+        .fields()
+        .iter()
+        .zip(struct_array.columns())
+        .zip(filtered.columns())
+    {
+        columns.push(if field.name() == consts::ID && !is_plain(field) {
+            filter_delta_ids::<UInt16Type>(column, mask)?
+        } else {
+            filtered.clone()
+        });
+    }
+    // safety: the columns keep their types and all have the rows kept by the mask
+    let struct_array = StructArray::try_new(
+        struct_array.fields().clone(),
+        columns,
+        filtered.nulls().cloned(),
+    )
+    .expect("columns match the fields");
+    Ok(Arc::new(struct_array))
+}
+
+/// Keeps the attributes whose parent is in `kept`, their parent ids made plain and mapped
+/// to the ids of their parents once filtered.
+fn filter_attrs(
+    otap_batch: &mut OtapBatch,
+    payload_type: ArrowPayloadType,
+    kept: &KeptIds,
+) -> Result<()> {
+    let Some(rb) = otap_batch.get(payload_type) else {
+        return Ok(());
+    };
+    let rb = plain_parent_ids(rb).in_payload(payload_type)?;
+    let idx = rb
+        .schema_ref()
+        .index_of(consts::PARENT_ID)
+        .ok()
+        .context(error::ColumnNotFoundSnafu {
+            name: consts::PARENT_ID,
+        })
+        .in_payload(payload_type)?;
+    let column = rb.column(idx);
+    // safety: plain_parent_ids only returns u16 or u32 parent ids
+    let parent_ids = cast(column, &DataType::UInt32).expect("parent ids can be cast");
+    let parent_ids = parent_ids.as_primitive::<UInt32Type>();
+    let mask: BooleanArray = parent_ids
+        .iter()
+        .map(|id| Some(id.is_some_and(|id| kept.contains_key(&id))))
+        .collect();
+    let new_parent_ids: ArrayRef = Arc::new(UInt32Array::from_iter_values(
+        parent_ids
+            .iter()
+            .flatten()
+            .filter_map(|id| kept.get(&id).copied()),
+    ));
+
+    // safety: the mask has as many rows as the batch
+    let filtered = filter_record_batch(&rb, &mask).expect("mask matches the batch");
+    let mut columns = filtered.columns().to_vec();
+    // safety: the ids of the parents fit the type of the parent ids
+    columns[idx] = cast(&new_parent_ids, column.data_type()).expect("parent ids can be cast");
+    // safety: the columns keep their types and all have the rows kept by the mask
+    let rb = RecordBatch::try_new(filtered.schema(), columns).expect("columns match the schema");
+    otap_batch.set(payload_type, rb);
+    Ok(())
+}
+
+/// Returns the attributes with their parent ids made plain, the ones of the dictionary
+/// encoded columns being cast to their value type first.
+fn plain_parent_ids(rb: &RecordBatch) -> Result<RecordBatch> {
+    let Some(DataType::Dictionary(_, value_type)) = rb
+        .column_by_name(consts::PARENT_ID)
+        .map(|column| column.data_type())
+    else {
+        return materialize_parent_ids(rb);
+    };
+    let schema = rb.schema();
+    // safety: the column was found above
+    let idx = schema
+        .index_of(consts::PARENT_ID)
+        .expect("column of the batch");
+    let mut columns = rb.columns().to_vec();
+    // safety: the dictionaries of integers can be cast to their value type
+    columns[idx] = cast(&columns[idx], value_type).expect("dictionary can be cast");
+    let mut fields: Vec<_> = schema.fields().iter().cloned().collect();
+    fields[idx] = Arc::new(
+        fields[idx]
+            .as_ref()
+            .clone()
+            .with_data_type(*value_type.clone()),
+    );
+    let schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+    // safety: the cast column has the type of its field
+    let rb = RecordBatch::try_new(schema, columns).expect("columns match the schema");
+    materialize_parent_ids(&rb)
+}
+
+/// Returns the keys of the runs the parent ids of the payload are delta encoded in, see
+/// [`ParentIdEncoding`].
+fn run_keys(rb: &RecordBatch, payload_type: ArrowPayloadType) -> Result<Vec<RunKey>> {
+    let rows = 0..rb.num_rows();
+    match parent_id_encoding(payload_type) {
+        Some(ParentIdEncoding::DeltaByColumn(column)) if is_string(rb, column) => {
+            let values = StringArrayAccessor::try_new_for_column(rb, column)?;
+            Ok(rows
+                .map(|idx| RunKey::Key(Some(values.value_at_or_default(idx).into_bytes())))
+                .collect())
+        }
+        Some(ParentIdEncoding::DeltaByColumn(column)) => {
+            let values = ByteArrayAccessor::try_new_for_column_opt(rb, column)?;
+            Ok(rows.map(|idx| RunKey::Key(values.value_at(idx))).collect())
+        }
+        Some(ParentIdEncoding::DeltaByValue) => {
+            let int_values = get_i64_array_opt(rb, consts::INT_VALUE)?;
+            let double_values = get_f64_array_opt(rb, consts::DOUBLE_VALUE)?;
+            Ok(rows
+                .map(
+                    |idx| match (int_values.value_at(idx), double_values.value_at(idx)) {
+                        (Some(int), _) => {
+                            RunKey::Key(Some([&[0][..], &int.to_le_bytes()].concat()))
+                        }
+                        // NaN equals no value, every NaN starts a run of its own
+                        (None, Some(double)) if double.is_nan() => {
+                            RunKey::Key(Some([&[2][..], &(idx as u64).to_le_bytes()].concat()))
+                        }
+                        (None, Some(double)) => {
+                            let double = if double == 0.0 { 0.0 } else { double };
+                            RunKey::Key(Some([&[1][..], &double.to_le_bytes()].concat()))
+                        }
+                        // the exemplars without value add their delta to the previous row
+                        // and keep its value, like in the decoder
+                        (None, None) => RunKey::Previous,
+                    },
+                )
+                .collect())
+        }
+        _ => Ok(vec![RunKey::Key(None); rb.num_rows()]),
+    }
+}
+
+fn is_string(rb: &RecordBatch, column: &str) -> bool {
+    rb.column_by_name(column)
+        .is_some_and(|column| match column.data_type() {
+            DataType::Dictionary(_, value_type) => value_type.as_ref() == &DataType::Utf8,
+            data_type => data_type == &DataType::Utf8,
+        })
+}
+
+/// Returns the plain parent ids of the payload, decoded like the decoders of the payload
+/// do.
+pub(crate) fn parent_ids(rb: &RecordBatch, payload_type: ArrowPayloadType) -> Result<Vec<u32>> {
+    let column = get_required_array(rb, consts::PARENT_ID)?;
+    ensure!(
+        matches!(column.data_type(), DataType::UInt16 | DataType::UInt32),
+        error::ColumnDataTypeMismatchSnafu {
+            name: consts::PARENT_ID,
+            expect: DataType::UInt32,
+            actual: column.data_type().clone(),
+        }
+    );
+    // safety: u16 can be cast to u32
+    let values = cast(column, &DataType::UInt32).expect("parent ids can be cast");
+    let values = values.as_primitive::<UInt32Type>();
+    if is_plain_column(rb, consts::PARENT_ID) {
+        return Ok((0..rb.num_rows())
+            .map(|idx| values.value_at_or_default(idx))
+            .collect());
+    }
+
+    let keys = run_keys(rb, payload_type)?;
+    let mut run_key = None;
+    let mut prev_parent_id = 0u32;
+    Ok(keys
+        .iter()
+        .enumerate()
+        .map(|(idx, key)| {
+            let value = values.value_at_or_default(idx);
+            let parent_id = match key {
+                RunKey::Previous => prev_parent_id.wrapping_add(value),
+                RunKey::Key(key) if run_key == Some(key) => prev_parent_id.wrapping_add(value),
+                RunKey::Key(key) => {
+                    run_key = Some(key);
+                    value
+                }
+            };
+            prev_parent_id = parent_id;
+            parent_id
+        })
+        .collect())
+}
+
+/// Returns the parent ids of the rows, in this order, delta encoded within their runs, none
+/// if a delta would be negative.
+fn encode_parent_ids(keys: &[RunKey], parent_ids: &[u32], rows: &[u32]) -> Option<Vec<u32>> {
+    let mut run_key = None;
+    let mut prev_parent_id = 0u32;
+    rows.iter()
+        .map(|&idx| {
+            let (key, parent_id) = (&keys[idx as usize], parent_ids[idx as usize]);
+            let value = match key {
+                RunKey::Previous => parent_id.checked_sub(prev_parent_id)?,
+                RunKey::Key(key) if run_key == Some(key) => {
+                    parent_id.checked_sub(prev_parent_id)?
+                }
+                RunKey::Key(key) => {
+                    run_key = Some(key);
+                    parent_id
+                }
+            };
+            prev_parent_id = parent_id;
+            Some(value)
+        })
+        .collect()
+}
+
+/// Keeps the rows of a child payload selected by the mask, with the given plain parent
+/// ids. Returns the ids of the kept rows mapped to their new ids.
+fn filter_records(
+    otap_batch: &mut OtapBatch,
+    payload_type: ArrowPayloadType,
+    rb: &RecordBatch,
+    mask: &BooleanArray,
+    parent_ids: &[u32],
+) -> Result<KeptIds> {
+    let mut rows: Vec<u32> = (0..rb.num_rows() as u32)
+        .filter(|idx| mask.value(*idx as usize))
+        .collect();
+    let encoded = if is_plain_column(rb, consts::PARENT_ID) {
+        rows.iter().map(|idx| parent_ids[*idx as usize]).collect()
+    } else {
+        let keys = run_keys(rb, payload_type).in_payload(payload_type)?;
+        match encode_parent_ids(&keys, parent_ids, &rows) {
+            Some(encoded) => encoded,
+            None => {
+                // the rows continuing the previous row come first, in a run of their own
+                rows.sort_by_key(|idx| (&keys[*idx as usize], parent_ids[*idx as usize]));
+                // safety: the parent ids of every run are sorted
+                encode_parent_ids(&keys, parent_ids, &rows).expect("parent ids are sorted")
+            }
+        }
+    };
+    let reordered = !rows.is_sorted();
+    let indices = UInt32Array::from(rows);
+    // safety: the indices are rows of the batch
+    let taken = take_record_batch(rb, &indices).expect("indices of the batch");
+    let schema = taken.schema();
+    let mut columns = taken.columns().to_vec();
+
+    let mut kept = KeptIds::new();
+    if let Ok(idx) = schema.index_of(consts::ID) {
+        let plain_ids = is_plain(schema.field(idx));
+        let ids = rb.column(idx);
+        let ids = ids
+            .as_primitive_opt::<UInt32Type>()
+            .with_context(|| error::ColumnDataTypeMismatchSnafu {
+                name: consts::ID,
+                expect: DataType::UInt32,
+                actual: ids.data_type().clone(),
+            })
+            .in_payload(payload_type)?;
+        let ids = if plain_ids {
+            ids.clone()
+        } else {
+            remove_delta_encoding_from_column(ids)
+        };
+        // safety: the indices are rows of the column
+        let ids = take(&ids, &indices, None).expect("indices of the column");
+        let mut next_id = 0;
+        let new_ids: UInt32Array = ids
+            .as_primitive::<UInt32Type>()
+            .iter()
+            .map(|id| {
+                id.map(|id| {
+                    let new_id = if reordered { next_id } else { id };
+                    next_id += 1;
+                    let _ = kept.insert(id, new_id);
+                    new_id
+                })
+            })
+            .collect();
+        columns[idx] = if plain_ids {
+            Arc::new(new_ids)
+        } else {
+            Arc::new(delta_ids(&new_ids))
+        };
+    }
+    // safety: the column was read by parent_ids
+    let idx = schema
+        .index_of(consts::PARENT_ID)
+        .expect("column of the batch");
+    let encoded: ArrayRef = Arc::new(UInt32Array::from(encoded));
+    // safety: the parent ids are encoded from values of the column
+    columns[idx] = cast(&encoded, schema.field(idx).data_type()).expect("parent ids can be cast");
+
+    // safety: the columns keep their types and all have the rows kept by the mask
+    let rb = RecordBatch::try_new(schema, columns).expect("columns match the schema");
+    otap_batch.set(payload_type, rb);
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrow::array::{StringArray, UInt16Array};
+    use arrow::datatypes::Field;
+
+    use crate::encode::{LogsProducer, MetricsProducer};
+    use crate::error::Error;
+    use crate::otap::{Logs, Traces};
+    use crate::otlp::logs::logs_from;
+    use crate::otlp::metrics::metrics_from;
+    use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+    use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+    use crate::proto::opentelemetry::metrics::v1::{
+        Exemplar, Gauge, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics, exemplar, metric,
+        number_data_point,
+    };
+    use crate::proto::opentelemetry::resource::v1::Resource;
+    use crate::schema::consts::metadata;
+
+    fn attr(key: &str, value: i64) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: Some(AnyValue {
+                value: Some(Value::IntValue(value)),
+            }),
+        }
+    }
+
+    fn metrics_request(keep: impl Fn(i64) -> bool) -> ExportMetricsServiceRequest {
+        let data_point = |value: i64| NumberDataPoint {
+            attributes: vec![attr("dp", value)],
+            time_unix_nano: 1,
+            exemplars: vec![Exemplar {
+                filtered_attributes: vec![attr("exemplar", value)],
+                time_unix_nano: 1,
+                span_id: vec![1; 8],
+                trace_id: vec![1; 16],
+                value: Some(exemplar::Value::AsInt(value % 2)),
+            }],
+            value: Some(number_data_point::Value::AsInt(value)),
+            ..Default::default()
+        };
+        let metric = |name: i64| Metric {
+            name: format!("metric-{name}"),
+            data: Some(metric::Data::Gauge(Gauge {
+                data_points: (name * 10..name * 10 + 4)
+                    .filter(|value| keep(*value))
+                    .map(data_point)
+                    .collect(),
+            })),
+            ..Default::default()
+        };
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource::default()),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: Some(InstrumentationScope::default()),
+                    metrics: (0..3)
+                        .filter(|name| keep(*name * 10) || keep(*name * 10 + 1))
+                        .map(metric)
+                        .collect(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_filter_with_children() {
+        // dropping a metric drops its data points, their exemplars and attributes
+        let mut otap_batch = MetricsProducer::new()
+            .produce(&metrics_request(|_| true))
+            .unwrap();
+        let metrics = otap_batch.get(ArrowPayloadType::UnivariateMetrics).unwrap();
+        let names = StringArrayAccessor::try_new_for_column(metrics, consts::NAME).unwrap();
+        let mask: BooleanArray = (0..metrics.num_rows())
+            .map(|idx| Some(names.value_at_or_default(idx) != "metric-1"))
+            .collect();
+        let dropped =
+            filter_with_children(&mut otap_batch, ArrowPayloadType::UnivariateMetrics, &mask)
+                .unwrap();
+        assert_eq!(dropped, 1);
+        let expected = MetricsProducer::new()
+            .produce(&metrics_request(|value| value / 10 != 1))
+            .unwrap();
+        assert_eq!(
+            metrics_from(otap_batch).unwrap(),
+            metrics_from(expected).unwrap()
+        );
+
+        // the data points can be filtered too
+        let mut otap_batch = MetricsProducer::new()
+            .produce(&metrics_request(|_| true))
+            .unwrap();
+        let data_points = otap_batch.get(ArrowPayloadType::NumberDataPoints).unwrap();
+        let values = get_i64_array_opt(data_points, consts::INT_VALUE)
+            .unwrap()
+            .unwrap();
+        let mask: BooleanArray = values
+            .iter()
+            .map(|value| value.map(|v| v % 3 != 0))
+            .collect();
+        let dropped =
+            filter_with_children(&mut otap_batch, ArrowPayloadType::NumberDataPoints, &mask)
+                .unwrap();
+        assert_eq!(dropped, 4);
+        let expected = MetricsProducer::new()
+            .produce(&metrics_request(|value| value % 3 != 0))
+            .unwrap();
+        assert_eq!(
+            metrics_from(otap_batch).unwrap(),
+            metrics_from(expected).unwrap()
+        );
+
+        // dropping all the records of a resource drops its attributes
+        let logs_request = |resources: i64| ExportLogsServiceRequest {
+            resource_logs: (0..resources)
+                .map(|resource| ResourceLogs {
+                    resource: Some(Resource {
+                        attributes: vec![attr("resource", resource)],
+                        ..Default::default()
+                    }),
+                    scope_logs: vec![ScopeLogs {
+                        scope: Some(InstrumentationScope::default()),
+                        log_records: vec![LogRecord {
+                            time_unix_nano: resource as u64,
+                            attributes: vec![attr("log", resource)],
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })
+                .collect(),
+        };
+        let mut otap_batch = LogsProducer::new().produce(&logs_request(2)).unwrap();
+        let logs = otap_batch.get(ArrowPayloadType::Logs).unwrap();
+        let mask = BooleanArray::from(vec![Some(true), None]);
+        let dropped = (0..logs.num_rows()).len() - 1;
+        assert_eq!(
+            filter_with_children(&mut otap_batch, ArrowPayloadType::Logs, &mask).unwrap(),
+            dropped
+        );
+        assert_eq!(
+            otap_batch
+                .get(ArrowPayloadType::ResourceAttrs)
+                .unwrap()
+                .num_rows(),
+            1
+        );
+        assert_eq!(logs_from(otap_batch).unwrap(), logs_request(1));
+    }
+
+    #[test]
+    fn test_filter_exemplars_without_value() {
+        // the exemplars without value continue the run of the previous exemplar, the encoder
+        // sorts them by value, the ones without value first
+        let request = |keep: &dyn Fn(i64) -> bool| {
+            let data_point = |value: i64| NumberDataPoint {
+                time_unix_nano: 1,
+                exemplars: {
+                    let mut values = [Some(value % 2), (value % 3 != 0).then_some(7), None];
+                    values.sort();
+                    values
+                }
+                .into_iter()
+                .map(|value| Exemplar {
+                    filtered_attributes: vec![attr("exemplar", value.unwrap_or(-1))],
+                    time_unix_nano: 1,
+                    value: value.map(exemplar::Value::AsInt),
+                    ..Default::default()
+                })
+                .collect(),
+                value: Some(number_data_point::Value::AsInt(value)),
+                ..Default::default()
+            };
+            ExportMetricsServiceRequest {
+                resource_metrics: vec![ResourceMetrics {
+                    resource: Some(Resource::default()),
+                    scope_metrics: vec![ScopeMetrics {
+                        scope: Some(InstrumentationScope::default()),
+                        metrics: vec![Metric {
+                            name: "metric".into(),
+                            data: Some(metric::Data::Gauge(Gauge {
+                                data_points: (0..6).filter(|v| keep(*v)).map(data_point).collect(),
+                            })),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+            }
+        };
+        let produce = || MetricsProducer::new().produce(&request(&|_| true)).unwrap();
+        assert_eq!(metrics_from(produce()).unwrap(), request(&|_| true));
+
+        for dropped in [0, 1, 4] {
+            let mut filtered = produce();
+            let data_points = filtered.get(ArrowPayloadType::NumberDataPoints).unwrap();
+            let values = get_i64_array_opt(data_points, consts::INT_VALUE)
+                .unwrap()
+                .unwrap();
+            let mask: BooleanArray = values.iter().map(|v| v.map(|v| v != dropped)).collect();
+            let _ = filter_with_children(&mut filtered, ArrowPayloadType::NumberDataPoints, &mask)
+                .unwrap();
+            assert_eq!(
+                metrics_from(filtered).unwrap(),
+                request(&|value| value != dropped)
+            );
+        }
+    }
+
+    #[test]
+    fn test_slice_payload() {
+        let otap_batch = MetricsProducer::new()
+            .produce(&metrics_request(|_| true))
+            .unwrap();
+        let plain_ids = |rb: &RecordBatch| {
+            let ids = rb.column_by_name(consts::ID).unwrap();
+            let ids = cast(ids, &DataType::UInt32).unwrap();
+            remove_delta_encoding_from_column(ids.as_primitive::<UInt32Type>())
+                .values()
+                .to_vec()
+        };
+        for payload_type in [
+            ArrowPayloadType::UnivariateMetrics,
+            ArrowPayloadType::NumberDataPoints,
+            ArrowPayloadType::NumberDpExemplars,
+            ArrowPayloadType::NumberDpAttrs,
+        ] {
+            let rb = otap_batch.get(payload_type).unwrap();
+            let range = 1..rb.num_rows() - 1;
+            let sliced = slice_payload(rb, payload_type, range.clone()).unwrap();
+            assert_eq!(sliced.num_rows(), range.len());
+            if rb.column_by_name(consts::ID).is_some() {
+                assert_eq!(plain_ids(&sliced), plain_ids(rb)[range.clone()]);
+            }
+            if is_attributes(payload_type) {
+                let parent_ids = |rb: &RecordBatch| {
+                    let rb = plain_parent_ids(rb).unwrap();
+                    let column = cast(
+                        rb.column_by_name(consts::PARENT_ID).unwrap(),
+                        &DataType::UInt32,
+                    );
+                    column
+                        .unwrap()
+                        .as_primitive::<UInt32Type>()
+                        .values()
+                        .to_vec()
+                };
+                assert_eq!(parent_ids(&sliced), parent_ids(rb)[range]);
+            } else if parent_id_encoding(payload_type).is_some() {
+                assert_eq!(
+                    parent_ids(&sliced, payload_type).unwrap(),
+                    parent_ids(rb, payload_type).unwrap()[range]
+                );
+            }
+        }
+
+        let rb = otap_batch.get(ArrowPayloadType::UnivariateMetrics).unwrap();
+        assert!(matches!(
+            slice_payload(
+                rb,
+                ArrowPayloadType::UnivariateMetrics,
+                1..rb.num_rows() + 1
+            ),
+            Err(Error::InvalidSliceRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_filter_plain_ids() {
+        // the ids of the logs and the parent ids of their attributes are plain
+        let plain = |name: &str, data_type| {
+            Field::new(name, data_type, false).with_metadata(
+                [(
+                    metadata::COLUMN_ENCODING.to_string(),
+                    metadata::encodings::PLAIN.to_string(),
+                )]
+                .into(),
+            )
+        };
+        let logs = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![plain(consts::ID, DataType::UInt16)])),
+            vec![Arc::new(UInt16Array::from(vec![0, 1, 2]))],
+        )
+        .unwrap();
+        let log_attrs = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![plain(
+                consts::PARENT_ID,
+                DataType::UInt16,
+            )])),
+            vec![Arc::new(UInt16Array::from(vec![0, 1, 2, 2]))],
+        )
+        .unwrap();
+        let mut otap_batch = OtapBatch::Logs(Logs::default());
+        otap_batch.set(ArrowPayloadType::Logs, logs);
+        otap_batch.set(ArrowPayloadType::LogAttrs, log_attrs);
+
+        let mask = BooleanArray::from(vec![true, false, true]);
+        let dropped = filter_with_children(&mut otap_batch, ArrowPayloadType::Logs, &mask).unwrap();
+        assert_eq!(dropped, 1);
+        let column = |payload_type, name| {
+            let rb = otap_batch.get(payload_type).unwrap();
+            assert!(is_plain_column(rb, name));
+            let column = cast(rb.column_by_name(name).unwrap(), &DataType::UInt32).unwrap();
+            column.as_primitive::<UInt32Type>().values().to_vec()
+        };
+        assert_eq!(column(ArrowPayloadType::Logs, consts::ID), [0, 2]);
+        assert_eq!(column(ArrowPayloadType::LogAttrs, consts::PARENT_ID), [
+            0, 2, 2
+        ]);
+    }
+
+    #[test]
+    fn test_filter_with_children_merged_runs() {
+        // the events a:1, b:0 and a:0, the runs of a are merged once b is dropped
+        let events = RecordBatch::try_from_iter([
+            (
+                consts::ID,
+                Arc::new(UInt32Array::from(vec![0, 1, 1])) as ArrayRef,
+            ),
+            (
+                consts::PARENT_ID,
+                Arc::new(UInt16Array::from(vec![1, 0, 0])),
+            ),
+            (
+                consts::NAME,
+                Arc::new(StringArray::from(vec!["a", "b", "a"])),
+            ),
+        ])
+        .unwrap();
+        let field = Field::new(consts::PARENT_ID, DataType::UInt32, false).with_metadata(
+            [(
+                metadata::COLUMN_ENCODING.to_string(),
+                metadata::encodings::PLAIN.to_string(),
+            )]
+            .into(),
+        );
+        let event_attrs = RecordBatch::try_new(Arc::new(Schema::new(vec![field])), vec![Arc::new(
+            UInt32Array::from(vec![0, 1, 2]),
+        )])
+        .unwrap();
+        let mut otap_batch = OtapBatch::Traces(Traces::default());
+        otap_batch.set(ArrowPayloadType::SpanEvents, events);
+        otap_batch.set(ArrowPayloadType::SpanEventAttrs, event_attrs);
+
+        let mask = BooleanArray::from(vec![true, false, true]);
+        let dropped =
+            filter_with_children(&mut otap_batch, ArrowPayloadType::SpanEvents, &mask).unwrap();
+        assert_eq!(dropped, 1);
+        let column = |payload_type, name| {
+            let rb = otap_batch.get(payload_type).unwrap();
+            let column = cast(rb.column_by_name(name).unwrap(), &DataType::UInt32).unwrap();
+            column.as_primitive::<UInt32Type>().values().to_vec()
+        };
+        // the events are reordered by parent id and renumbered
+        assert_eq!(column(ArrowPayloadType::SpanEvents, consts::PARENT_ID), [
+            0, 1
+        ]);
+        assert_eq!(column(ArrowPayloadType::SpanEvents, consts::ID), [0, 1]);
+        assert_eq!(
+            column(ArrowPayloadType::SpanEventAttrs, consts::PARENT_ID),
+            [1, 0]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The graph of the payloads of the OTAP batches: which payload references the rows of
+//! which through its parent ids, and how these are encoded.
+//!
+//! The payloads of a signal form a tree rooted at its main payload:
+//!
+//! ```text
+//! LOGS                  ─ LOG_ATTRS
+//! SPANS                 ─ SPAN_ATTRS, SPAN_EVENTS, SPAN_LINKS
+//! SPAN_EVENTS           ─ SPAN_EVENT_ATTRS
+//! SPAN_LINKS            ─ SPAN_LINK_ATTRS
+//! UNIVARIATE_METRICS    ─ NUMBER_DATA_POINTS, SUMMARY_DATA_POINTS, HISTOGRAM_DATA_POINTS,
+//!                         EXP_HISTOGRAM_DATA_POINTS
+//! NUMBER_DATA_POINTS    ─ NUMBER_DP_ATTRS, NUMBER_DP_EXEMPLARS
+//! NUMBER_DP_EXEMPLARS   ─ NUMBER_DP_EXEMPLAR_ATTRS
+//! ...
+//! ```
+//!
+//! The resource and scope attributes are referenced by the `resource` and `scope` columns of
+//! the main payloads rather than by parent ids, they have no parent in the graph.
+
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts;
+
+/// Encoding of the parent ids of a payload, i.e. how the decoders read them back.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParentIdEncoding {
+    /// Delta encoded within runs of consecutive rows with the same key and value, the
+    /// attributes. The payloads whose `parent_id` column is marked `plain` are not.
+    Attributes,
+    /// Delta encoded over all the rows, the data points.
+    Delta,
+    /// Delta encoded within runs of consecutive rows with the same value of the column, the
+    /// name of the span events and the trace id of the span links.
+    DeltaByColumn(&'static str),
+    /// Delta encoded within runs of consecutive rows with the same int or double value, the
+    /// exemplars.
+    DeltaByValue,
+}
+
+/// Returns whether the payload holds attributes.
+#[must_use]
+pub fn is_attributes(payload_type: ArrowPayloadType) -> bool {
+    payload_type.as_str_name().ends_with("_ATTRS")
+}
+
+/// Returns the payloads whose parent ids reference the rows of the payload, attributes
+/// first.
+#[must_use]
+pub fn children(payload_type: ArrowPayloadType) -> &'static [ArrowPayloadType] {
+    match payload_type {
+        ArrowPayloadType::Logs => &[ArrowPayloadType::LogAttrs],
+        ArrowPayloadType::Spans => &[
+            ArrowPayloadType::SpanAttrs,
+            ArrowPayloadType::SpanEvents,
+            ArrowPayloadType::SpanLinks,
+        ],
+        ArrowPayloadType::SpanEvents => &[ArrowPayloadType::SpanEventAttrs],
+        ArrowPayloadType::SpanLinks => &[ArrowPayloadType::SpanLinkAttrs],
+        ArrowPayloadType::UnivariateMetrics => &[
+            ArrowPayloadType::NumberDataPoints,
+            ArrowPayloadType::SummaryDataPoints,
+            ArrowPayloadType::HistogramDataPoints,
+            ArrowPayloadType::ExpHistogramDataPoints,
+        ],
+        ArrowPayloadType::NumberDataPoints => &[
+            ArrowPayloadType::NumberDpAttrs,
+            ArrowPayloadType::NumberDpExemplars,
+        ],
+        ArrowPayloadType::SummaryDataPoints => &[ArrowPayloadType::SummaryDpAttrs],
+        ArrowPayloadType::HistogramDataPoints => &[
+            ArrowPayloadType::HistogramDpAttrs,
+            ArrowPayloadType::HistogramDpExemplars,
+        ],
+        ArrowPayloadType::ExpHistogramDataPoints => &[
+            ArrowPayloadType::ExpHistogramDpAttrs,
+            ArrowPayloadType::ExpHistogramDpExemplars,
+        ],
+        ArrowPayloadType::NumberDpExemplars => &[ArrowPayloadType::NumberDpExemplarAttrs],
+        ArrowPayloadType::HistogramDpExemplars => &[ArrowPayloadType::HistogramDpExemplarAttrs],
+        ArrowPayloadType::ExpHistogramDpExemplars => {
+            &[ArrowPayloadType::ExpHistogramDpExemplarAttrs]
+        }
+        _ => &[],
+    }
+}
+
+/// Returns the payload whose rows the parent ids of the payload reference, none for the
+/// main payloads and the resource and scope attributes.
+#[must_use]
+pub fn parent(payload_type: ArrowPayloadType) -> Option<ArrowPayloadType> {
+    let parent = match payload_type {
+        ArrowPayloadType::LogAttrs => ArrowPayloadType::Logs,
+        ArrowPayloadType::SpanAttrs
+        | ArrowPayloadType::SpanEvents
+        | ArrowPayloadType::SpanLinks => ArrowPayloadType::Spans,
+        ArrowPayloadType::SpanEventAttrs => ArrowPayloadType::SpanEvents,
+        ArrowPayloadType::SpanLinkAttrs => ArrowPayloadType::SpanLinks,
+        ArrowPayloadType::NumberDataPoints
+        | ArrowPayloadType::SummaryDataPoints
+        | ArrowPayloadType::HistogramDataPoints
+        | ArrowPayloadType::ExpHistogramDataPoints => ArrowPayloadType::UnivariateMetrics,
+        ArrowPayloadType::NumberDpAttrs | ArrowPayloadType::NumberDpExemplars => {
+            ArrowPayloadType::NumberDataPoints
+        }
+        ArrowPayloadType::SummaryDpAttrs => ArrowPayloadType::SummaryDataPoints,
+        ArrowPayloadType::HistogramDpAttrs | ArrowPayloadType::HistogramDpExemplars => {
+            ArrowPayloadType::HistogramDataPoints
+        }
+        ArrowPayloadType::ExpHistogramDpAttrs | ArrowPayloadType::ExpHistogramDpExemplars => {
+            ArrowPayloadType::ExpHistogramDataPoints
+        }
+        ArrowPayloadType::NumberDpExemplarAttrs => ArrowPayloadType::NumberDpExemplars,
+        ArrowPayloadType::HistogramDpExemplarAttrs => ArrowPayloadType::HistogramDpExemplars,
+        ArrowPayloadType::ExpHistogramDpExemplarAttrs => ArrowPayloadType::ExpHistogramDpExemplars,
+        _ => return None,
+    };
+    Some(parent)
+}
+
+/// Returns the payload holding the attributes of the rows of the payload, if any.
+#[must_use]
+pub fn attributes(payload_type: ArrowPayloadType) -> Option<ArrowPayloadType> {
+    children(payload_type)
+        .iter()
+        .copied()
+        .find(|child| is_attributes(*child))
+}
+
+/// Returns the encoding of the parent ids of the payload, none for the payloads without
+/// parent ids.
+#[must_use]
+pub fn parent_id_encoding(payload_type: ArrowPayloadType) -> Option<ParentIdEncoding> {
+    let encoding = match payload_type {
+        ArrowPayloadType::SpanEvents => ParentIdEncoding::DeltaByColumn(consts::NAME),
+        ArrowPayloadType::SpanLinks => ParentIdEncoding::DeltaByColumn(consts::TRACE_ID),
+        ArrowPayloadType::NumberDpExemplars
+        | ArrowPayloadType::HistogramDpExemplars
+        | ArrowPayloadType::ExpHistogramDpExemplars => ParentIdEncoding::DeltaByValue,
+        payload_type if is_attributes(payload_type) => ParentIdEncoding::Attributes,
+        payload_type if parent(payload_type).is_some() => ParentIdEncoding::Delta,
+        _ => return None,
+    };
+    Some(encoding)
+}
+
+/// Returns the payloads below the payload in the graph, depth first, each payload before
+/// its children.
+#[must_use]
+pub fn descendants(payload_type: ArrowPayloadType) -> Vec<ArrowPayloadType> {
+    let mut descendants = Vec::new();
+    let mut stack: Vec<_> = children(payload_type).iter().rev().copied().collect();
+    while let Some(payload_type) = stack.pop() {
+        descendants.push(payload_type);
+        stack.extend(children(payload_type).iter().rev());
+    }
+    descendants
+}
+
+/// Returns the payloads above the payload in the graph, from its parent to the main
+/// payload.
+#[must_use]
+pub fn ancestors(payload_type: ArrowPayloadType) -> Vec<ArrowPayloadType> {
+    std::iter::successors(parent(payload_type), |payload_type| parent(*payload_type)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_payload_graph() {
+        for payload_type in (0..64).filter_map(|t| ArrowPayloadType::try_from(t).ok()) {
+            for child in children(payload_type) {
+                assert_eq!(parent(*child), Some(payload_type), "{child:?}");
+            }
+            if let Some(parent) = parent(payload_type) {
+                assert!(children(parent).contains(&payload_type), "{payload_type:?}");
+            }
+        }
+
+        assert_eq!(descendants(ArrowPayloadType::Spans), vec![
+            ArrowPayloadType::SpanAttrs,
+            ArrowPayloadType::SpanEvents,
+            ArrowPayloadType::SpanEventAttrs,
+            ArrowPayloadType::SpanLinks,
+            ArrowPayloadType::SpanLinkAttrs,
+        ]);
+        assert_eq!(ancestors(ArrowPayloadType::HistogramDpExemplarAttrs), vec![
+            ArrowPayloadType::HistogramDpExemplars,
+            ArrowPayloadType::HistogramDataPoints,
+            ArrowPayloadType::UnivariateMetrics,
+        ]);
+        assert_eq!(
+            attributes(ArrowPayloadType::Logs),
+            Some(ArrowPayloadType::LogAttrs)
+        );
+        assert_eq!(attributes(ArrowPayloadType::UnivariateMetrics), None);
+        assert_eq!(
+            parent_id_encoding(ArrowPayloadType::SpanLinks),
+            Some(ParentIdEncoding::DeltaByColumn(consts::TRACE_ID))
+        );
+        assert_eq!(
+            parent_id_encoding(ArrowPayloadType::SummaryDataPoints),
+            Some(ParentIdEncoding::Delta)
+        );
+        assert_eq!(parent_id_encoding(ArrowPayloadType::Spans), None);
+    }
+}
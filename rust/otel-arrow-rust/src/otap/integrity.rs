@@ -0,0 +1,139 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Content hash of the OTAP batches, so that the pipelines forwarding them can detect their
+//! corruption or tampering end to end.
+//!
+//! [`batch_hash`] is a 64 bits XXH3 of the payloads of a batch, each written as a fresh
+//! uncompressed IPC stream in the order of [`OtapBatch::payload_types`]. It doesn't depend
+//! on how the payloads were split into IPC messages nor on the dictionaries the stream of a
+//! payload reused, so the sender hashes the batch it encodes and the receiver the batch it
+//! decodes.
+//!
+//! The hash travels in the `headers` of the `BatchArrowRecords`, HPACK encoded like the
+//! other [headers](crate::headers), under [`BATCH_HASH_HEADER`] and as 16 hex digits.
+//! [`set_batch_hash`] appends it as a literal header field and [`verify_batch_hash`] looks
+//! it up and compares it with the hash of the decoded batch.
+
+use std::hash::Hasher;
+use std::io::{self, Write};
+
+use arrow::ipc::writer::StreamWriter;
+use snafu::{ResultExt, ensure};
+use twox_hash::XxHash3_64;
+
+use crate::error::{self, Result};
+use crate::headers::{next_header, write_literal};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+
+/// Name of the header carrying the hash of the batch.
+pub const BATCH_HASH_HEADER: &str = "otel-arrow-batch-hash";
+
+/// Returns the content hash of the batch.
+pub fn batch_hash(otap_batch: &OtapBatch) -> Result<u64> {
+    let mut hasher = HashWriter(XxHash3_64::with_seed(0));
+    for payload_type in otap_batch.payload_types() {
+        // safety: payload_types only returns types that are present in the batch
+        let record_batch = otap_batch
+            .get(payload_type)
+            .expect("payload type present in batch");
+        hasher.0.write(&(payload_type as i32).to_le_bytes());
+        let mut writer = StreamWriter::try_new(&mut hasher, &record_batch.schema())
+            .context(error::WriteRecordBatchSnafu)?;
+        writer
+            .write(record_batch)
+            .context(error::WriteRecordBatchSnafu)?;
+        writer.finish().context(error::WriteRecordBatchSnafu)?;
+    }
+    Ok(hasher.0.finish())
+}
+
+/// Appends the header carrying the hash to the headers of the records.
+pub fn set_batch_hash(records: &mut BatchArrowRecords, hash: u64) {
+    write_literal(
+        &mut records.headers,
+        BATCH_HASH_HEADER.as_bytes(),
+        format!("{hash:016x}").as_bytes(),
+    );
+}
+
+/// Returns the hash carried by the headers of the records, `None` if they don't carry one,
+/// or if it is encoded in a way these functions don't read, e.g. Huffman coded.
+#[must_use]
+pub fn read_batch_hash(records: &BatchArrowRecords) -> Option<u64> {
+    let mut hash = None;
+    let mut input = records.headers.as_slice();
+    while let Some((name, value)) = next_header(&mut input)? {
+        if name == Some(BATCH_HASH_HEADER.as_bytes()) {
+            hash = std::str::from_utf8(value?)
+                .ok()
+                .and_then(|value| u64::from_str_radix(value, 16).ok());
+        }
+    }
+    hash
+}
+
+/// Checks the hash carried by the records against the hash of their decoded batch. Returns
+/// false if the records don't carry a hash, fails if the hashes differ.
+pub fn verify_batch_hash(records: &BatchArrowRecords, otap_batch: &OtapBatch) -> Result<bool> {
+    let Some(expected) = read_batch_hash(records) else {
+        return Ok(false);
+    };
+    let actual = batch_hash(otap_batch)?;
+    ensure!(expected == actual, error::BatchHashMismatchSnafu {
+        expected,
+        actual
+    });
+    Ok(true)
+}
+
+struct HashWriter(XxHash3_64);
+
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::Consumer;
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::headers::write_string;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_batch_hash() {
+        let otap_batch = traces_batch(16, 2);
+        let hash = batch_hash(&otap_batch).unwrap();
+        let mut records = to_batch_arrow_records(&otap_batch, 0).unwrap();
+        // a header already set by the sender, with an indexed name and a long value
+        records.headers.push(0x0f);
+        records.headers.push(0x2b - 0x0f);
+        write_string(&mut records.headers, &[b'x'; 200]);
+        assert_eq!(read_batch_hash(&records), None);
+        set_batch_hash(&mut records, hash);
+        assert_eq!(read_batch_hash(&records), Some(hash));
+
+        let decoded = Consumer::default()
+            .consume_otap_batch(&mut records.clone())
+            .unwrap();
+        assert!(verify_batch_hash(&records, &decoded).unwrap());
+
+        let other = traces_batch(16, 3);
+        assert!(matches!(
+            verify_batch_hash(&records, &other),
+            Err(error::Error::BatchHashMismatch { .. })
+        ));
+        records.headers.clear();
+        assert!(!verify_batch_hash(&records, &other).unwrap());
+    }
+}
@@ -0,0 +1,280 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Persistence of OTAP payload record batches as Parquet files.
+//!
+//! Each payload type of a batch is written to its own Parquet file. Files are partitioned
+//! by payload type, one directory per payload type, and named after the batch id:
+//!
+//! ```text
+//! <root>/spans/42.parquet
+//! <root>/span_attrs/42.parquet
+//! <root>/resource_attrs/42.parquet
+//! ...
+//! ```
+//!
+//! Record batches written this way can be read back either as an [`OtapBatch`] or as a
+//! self-contained [`BatchArrowRecords`] that can be passed to a [`crate::Consumer`].
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use arrow::array::{RecordBatch, RecordBatchReader};
+use arrow::compute::concat_batches;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use snafu::ResultExt;
+
+use crate::encode::split::to_batch_arrow_records;
+use crate::error::{self, Result};
+use crate::otap::{Logs, Metrics, OtapBatch, Traces};
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayloadType, BatchArrowRecords};
+
+const FILE_EXTENSION: &str = "parquet";
+
+/// Returns the name of the directory in which record batches for the given payload
+/// type are stored.
+#[must_use]
+pub fn partition_name(payload_type: ArrowPayloadType) -> String {
+    payload_type.as_str_name().to_lowercase()
+}
+
+/// Returns the path of the Parquet file for the given payload type and batch id.
+#[must_use]
+pub fn file_path(root: &Path, payload_type: ArrowPayloadType, batch_id: i64) -> PathBuf {
+    root.join(partition_name(payload_type))
+        .join(format!("{batch_id}.{FILE_EXTENSION}"))
+}
+
+/// Writes every record batch contained in the OTAP batch to its own Parquet file below
+/// `root`. Returns the paths of the files that were written, main payload first.
+pub fn write_batch(root: &Path, batch_id: i64, otap_batch: &OtapBatch) -> Result<Vec<PathBuf>> {
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+
+    let mut paths = Vec::new();
+    for payload_type in otap_batch.payload_types() {
+        // safety: payload_types only returns types that are present in the batch
+        let record_batch = otap_batch
+            .get(payload_type)
+            .expect("payload type present in batch");
+        let path = file_path(root, payload_type, batch_id);
+        write_record_batch(&path, record_batch, props.clone())?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+fn write_record_batch(
+    path: &Path,
+    record_batch: &RecordBatch,
+    props: WriterProperties,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context(error::IoSnafu { path: parent })?;
+    }
+    let file = File::create(path).context(error::IoSnafu { path })?;
+    let mut writer = ArrowWriter::try_new(file, record_batch.schema(), Some(props))
+        .context(error::WriteParquetSnafu { path })?;
+    writer
+        .write(record_batch)
+        .context(error::WriteParquetSnafu { path })?;
+    let _ = writer.close().context(error::WriteParquetSnafu { path })?;
+    Ok(())
+}
+
+fn read_record_batch(path: &Path) -> Result<RecordBatch> {
+    let file = File::open(path).context(error::IoSnafu { path })?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .context(error::ReadParquetSnafu { path })?
+        .build()
+        .context(error::ReadParquetSnafu { path })?;
+    let schema = reader.schema();
+    let batches = reader
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .context(error::ReadRecordBatchSnafu)?;
+    concat_batches(&schema, &batches).context(error::ReadRecordBatchSnafu)
+}
+
+/// Reads back all the payloads that were written for the given batch id. Returns `None`
+/// if no main payload (logs, univariate metrics or spans) was found for this batch id.
+pub fn read_batch(root: &Path, batch_id: i64) -> Result<Option<OtapBatch>> {
+    let mut otap_batch = if file_path(root, ArrowPayloadType::Logs, batch_id).exists() {
+        OtapBatch::Logs(Logs::default())
+    } else if file_path(root, ArrowPayloadType::UnivariateMetrics, batch_id).exists() {
+        OtapBatch::Metrics(Metrics::default())
+    } else if file_path(root, ArrowPayloadType::Spans, batch_id).exists() {
+        OtapBatch::Traces(Traces::default())
+    } else {
+        return Ok(None);
+    };
+
+    for payload_type in (0..=ArrowPayloadType::SpanLinkAttrs as i32)
+        .filter_map(|t| ArrowPayloadType::try_from(t).ok())
+    {
+        let path = file_path(root, payload_type, batch_id);
+        if path.exists() {
+            otap_batch.set(payload_type, read_record_batch(&path)?);
+        }
+    }
+
+    Ok(Some(otap_batch))
+}
+
+/// Reads back all the payloads that were written for the given batch id and serializes
+/// them as a `BatchArrowRecords`.
+///
+/// Every payload carries its own schema and dictionaries, and the schema ids are unique
+/// to this batch id, so the result can be consumed by a [`crate::Consumer`] that was
+/// also used for other batches.
+pub fn read_batch_arrow_records(root: &Path, batch_id: i64) -> Result<Option<BatchArrowRecords>> {
+    let Some(otap_batch) = read_batch(root, batch_id)? else {
+        return Ok(None);
+    };
+
+    to_batch_arrow_records(&otap_batch, batch_id).map(Some)
+}
+
+/// Lists the batch ids for which a main payload was persisted below `root`, in
+/// ascending order.
+pub fn list_batch_ids(root: &Path) -> Result<Vec<i64>> {
+    let mut batch_ids = Vec::new();
+    for payload_type in [
+        ArrowPayloadType::Logs,
+        ArrowPayloadType::UnivariateMetrics,
+        ArrowPayloadType::Spans,
+    ] {
+        let dir = root.join(partition_name(payload_type));
+        if !dir.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(&dir).context(error::IoSnafu { path: &dir })? {
+            let path = entry.context(error::IoSnafu { path: &dir })?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(FILE_EXTENSION) {
+                continue;
+            }
+            if let Some(batch_id) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<i64>().ok())
+            {
+                batch_ids.push(batch_id);
+            }
+        }
+    }
+    batch_ids.sort_unstable();
+    batch_ids.dedup();
+    Ok(batch_ids)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{DictionaryArray, StringArray, UInt8Array, UInt16Array};
+    use arrow::datatypes::{DataType, Field, Schema, UInt8Type};
+
+    use crate::Consumer;
+    use crate::schema::consts;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "otel-arrow-parquet-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn attrs_batch() -> RecordBatch {
+        let keys: DictionaryArray<UInt8Type> = vec!["k1", "k2", "k1"].into_iter().collect();
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(
+                    consts::ATTRIBUTE_KEY,
+                    DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8)),
+                    false,
+                ),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0, 1])),
+                Arc::new(UInt8Array::from(vec![1, 1, 1])),
+                Arc::new(keys),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn logs_batch() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new(
+                consts::ID,
+                DataType::UInt16,
+                true,
+            )])),
+            vec![Arc::new(UInt16Array::from(vec![0, 1]))],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_write_read_round_trip() {
+        let root = temp_dir("round-trip");
+        let mut otap_batch = OtapBatch::Logs(Logs::default());
+        otap_batch.set(ArrowPayloadType::Logs, logs_batch());
+        otap_batch.set(ArrowPayloadType::LogAttrs, attrs_batch());
+
+        let paths = write_batch(&root, 7, &otap_batch).unwrap();
+        assert_eq!(paths, vec![
+            root.join("logs").join("7.parquet"),
+            root.join("log_attrs").join("7.parquet"),
+        ]);
+        assert_eq!(list_batch_ids(&root).unwrap(), vec![7]);
+
+        let result = read_batch(&root, 7).unwrap().unwrap();
+        assert!(matches!(result, OtapBatch::Logs(_)));
+        assert_eq!(result.get(ArrowPayloadType::Logs), Some(&logs_batch()));
+        // dictionary encoding survives the round trip
+        assert_eq!(result.get(ArrowPayloadType::LogAttrs), Some(&attrs_batch()));
+        assert!(result.get(ArrowPayloadType::ResourceAttrs).is_none());
+
+        assert!(read_batch(&root, 8).unwrap().is_none());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_read_batch_arrow_records() {
+        let root = temp_dir("bar");
+        for batch_id in [1, 2] {
+            let mut otap_batch = OtapBatch::Logs(Logs::default());
+            otap_batch.set(ArrowPayloadType::Logs, logs_batch());
+            otap_batch.set(ArrowPayloadType::LogAttrs, attrs_batch());
+            let _ = write_batch(&root, batch_id, &otap_batch).unwrap();
+        }
+
+        // the same consumer must be able to consume all the batches read back
+        let mut consumer = Consumer::default();
+        for batch_id in list_batch_ids(&root).unwrap() {
+            let mut bar = read_batch_arrow_records(&root, batch_id).unwrap().unwrap();
+            assert_eq!(bar.batch_id, batch_id);
+            assert_eq!(bar.arrow_payloads[0].r#type, ArrowPayloadType::Logs as i32);
+
+            let records = consumer.consume_bar(&mut bar).unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].record, logs_batch());
+            assert_eq!(records[1].record, attrs_batch());
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Partitioning of the spans of a batch by trace, for the routers fanning the batches out
+//! to several collectors that each need all the spans of their traces, e.g. tail samplers.
+//!
+//! [`TraceIdPartitioner`] assigns every trace to a partition with a jump consistent hash of
+//! its id, "A Fast, Minimal Memory, Consistent Hash Algorithm" (Lamping, Veach), so the
+//! routers agree on the assignment without coordination, and adding a partition only moves
+//! the traces that the new partition takes. The trace ids are hashed with FNV-1a first, as
+//! they are not all random, e.g. in the tests of the instrumented services.
+//!
+//! [`TraceIdPartitioner::split`] splits a batch into one batch per partition with
+//! [`filter_spans`], so every sub-batch keeps the attributes, events and links of its spans,
+//! with their parent ids encoded again, and decodes like a batch produced with only its
+//! spans.
+
+use arrow::array::BooleanArray;
+
+use crate::arrays::{ByteArrayAccessor, ColumnAccessor, NullableArrayAccessor};
+use crate::error::{ErrorContextExt, Result};
+use crate::otap::sampling::filter_spans;
+use crate::otap::{OtapBatch, Traces};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Assigns the traces to a number of partitions, see the module documentation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TraceIdPartitioner {
+    partitions: u32,
+}
+
+impl TraceIdPartitioner {
+    /// Creates a partitioner over the given number of partitions, at least one.
+    #[must_use]
+    pub fn new(partitions: u32) -> Self {
+        Self {
+            partitions: partitions.max(1),
+        }
+    }
+
+    /// Returns the number of partitions.
+    #[must_use]
+    pub fn partitions(&self) -> u32 {
+        self.partitions
+    }
+
+    /// Returns the partition of the trace with this id.
+    #[must_use]
+    pub fn partition_of(&self, trace_id: &[u8]) -> u32 {
+        let mut key = trace_id.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+        });
+        let (mut bucket, mut next) = (-1i64, 0i64);
+        while next < i64::from(self.partitions) {
+            bucket = next;
+            key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+            next = ((bucket + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+        }
+        bucket as u32
+    }
+
+    /// Returns the partition of every span of the batch.
+    pub fn evaluate(&self, otap_batch: &OtapBatch) -> Result<Vec<u32>> {
+        let Some(spans) = otap_batch.get(ArrowPayloadType::Spans) else {
+            return Ok(Vec::new());
+        };
+        let trace_ids = ByteArrayAccessor::try_new_for_column(spans, consts::TRACE_ID)
+            .in_payload(ArrowPayloadType::Spans)?;
+        Ok((0..spans.num_rows())
+            .map(|idx| self.partition_of(&trace_ids.value_at_or_default(idx)))
+            .collect())
+    }
+
+    /// Splits the traces batch into one batch per partition, `None` for the partitions
+    /// without any span of the batch.
+    pub fn split(&self, otap_batch: &OtapBatch) -> Result<Vec<Option<OtapBatch>>> {
+        let partitions = self.evaluate(otap_batch)?;
+        (0..self.partitions)
+            .map(|partition| {
+                let mask: BooleanArray = partitions.iter().map(|p| Some(*p == partition)).collect();
+                if mask.true_count() == 0 {
+                    return Ok(None);
+                }
+                let mut sub_batch = OtapBatch::Traces(Traces::default());
+                for payload_type in otap_batch.payload_types() {
+                    // safety: payload_types only returns types that are present in the batch
+                    let record_batch = otap_batch
+                        .get(payload_type)
+                        .expect("payload type present in batch");
+                    sub_batch.set(payload_type, record_batch.clone());
+                }
+                let _ = filter_spans(&mut sub_batch, &mask)?;
+                Ok(Some(sub_batch))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::{HashMap, HashSet};
+
+    use crate::datagen::{DatagenConfig, Generator};
+    use crate::encode::TracesProducer;
+    use crate::otlp::traces::traces_from;
+    use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+    use crate::proto::opentelemetry::trace::v1::Span;
+
+    fn spans(request: &ExportTraceServiceRequest) -> Vec<Span> {
+        let mut spans: Vec<_> = request
+            .resource_spans
+            .iter()
+            .flat_map(|rs| &rs.scope_spans)
+            .flat_map(|ss| ss.spans.clone())
+            .collect();
+        spans.sort_by(|a, b| a.span_id.cmp(&b.span_id));
+        spans
+    }
+
+    #[test]
+    fn test_split_by_trace_id() {
+        let request = Generator::new(DatagenConfig::default()).traces_request(32);
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        let partitioner = TraceIdPartitioner::new(4);
+        let sub_batches = partitioner.split(&otap_batch).unwrap();
+        assert_eq!(sub_batches.len(), 4);
+
+        let mut partition_by_trace = HashMap::new();
+        let mut split_spans = Vec::new();
+        for (partition, sub_batch) in sub_batches.into_iter().enumerate() {
+            let Some(sub_batch) = sub_batch else {
+                continue;
+            };
+            let decoded = traces_from(sub_batch).unwrap();
+            for span in spans(&decoded) {
+                assert_eq!(partitioner.partition_of(&span.trace_id), partition as u32);
+                let previous = partition_by_trace.insert(span.trace_id.clone(), partition);
+                assert!(previous.is_none_or(|previous| previous == partition));
+                split_spans.push(span);
+            }
+        }
+        assert!(partition_by_trace.values().collect::<HashSet<_>>().len() > 1);
+        split_spans.sort_by(|a, b| a.span_id.cmp(&b.span_id));
+        let expected = traces_from(TracesProducer::new().produce(&request).unwrap()).unwrap();
+        assert_eq!(split_spans, spans(&expected));
+
+        // growing the partitions only moves the traces to the new partition
+        let grown = TraceIdPartitioner::new(5);
+        for (trace_id, partition) in &partition_by_trace {
+            let moved = grown.partition_of(trace_id);
+            assert!(moved == *partition as u32 || moved == 4);
+        }
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Projection of the log records or spans of a batch onto a few of their fields,
+//! without decoding the batch into OTLP messages, e.g. to sample or aggregate the spans on
+//! their duration and `http.status_code` attribute.
+//!
+//! A [`Projection`] lists the columns of the main payload and the attributes to keep. The
+//! attributes are joined to their records in Arrow: the result has one row per record and
+//! one column per field, an attribute being null for the records that don't have it.
+//!
+//! The spans can also be projected onto the [`DerivedField`]s almost every consumer
+//! computes, e.g. their duration, which the decoded spans expose as
+//! `Span::duration_ns` and `Span::is_error`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, BooleanArray, RecordBatch, new_null_array};
+use arrow::compute::kernels::numeric::add_wrapping;
+use arrow::compute::{CastOptions, cast, cast_with_options, interleave};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema, TimeUnit};
+use snafu::OptionExt;
+
+use crate::arrays::{get_u8_array, get_u16_array};
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::otlp::attributes::decoder::materialize_parent_id;
+use crate::otlp::attributes::store::AttributeValueType;
+use crate::otlp::filter::{record_ids, string_matches};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::trace::v1::status::StatusCode;
+use crate::schema::consts;
+
+/// A field of the records.
+#[derive(Clone, Debug, PartialEq)]
+enum ProjectedField {
+    /// A column, or a field of a struct column named `<column>.<field>`.
+    Column(String),
+    /// The value of the attribute with this key.
+    Attribute(String),
+    /// A field computed from the columns of the spans.
+    Derived(DerivedField),
+}
+
+/// A field of the spans computed from their columns, see [`Projection::with_derived`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DerivedField {
+    /// The duration of the span in nanoseconds, as an `Int64` column named `duration_ns`.
+    Duration,
+    /// The start time plus the duration of the span, as a nanosecond timestamp column named
+    /// `end_time_unix_nano`.
+    EndTime,
+    /// Whether the status of the span is an error, as a `Boolean` column named `is_error`.
+    /// The spans without status are not.
+    IsError,
+}
+
+impl DerivedField {
+    /// Returns the name of the column of the field.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Duration => "duration_ns",
+            Self::EndTime => "end_time_unix_nano",
+            Self::IsError => "is_error",
+        }
+    }
+
+    fn column(&self, rb: &RecordBatch) -> Result<ArrayRef> {
+        let int64 = |name| {
+            let column = column(rb, name)?;
+            cast(&column, &DataType::Int64).map_err(|_| {
+                error::ColumnDataTypeMismatchSnafu {
+                    name,
+                    expect: DataType::Int64,
+                    actual: column.data_type().clone(),
+                }
+                .build()
+            })
+        };
+        // the duration column holds nanoseconds even though it is typed as milliseconds
+        let duration = int64(consts::DURATION_TIME_UNIX_NANO)?;
+        match self {
+            Self::Duration => Ok(duration),
+            Self::EndTime => {
+                let start = int64(consts::START_TIME_UNIX_NANO)?;
+                // safety: both columns are Int64 columns of the batch
+                let end = add_wrapping(&start, &duration).expect("columns of the same type");
+                // safety: Int64 can be cast to a timestamp
+                Ok(cast(&end, &DataType::Timestamp(TimeUnit::Nanosecond, None))
+                    .expect("Int64 can be cast to a timestamp"))
+            }
+            Self::IsError => {
+                let codes = rb
+                    .column_by_name(consts::STATUS)
+                    .and_then(|status| status.as_struct_opt())
+                    .and_then(|status| status.column_by_name(consts::STATUS_CODE))
+                    .map(|codes| {
+                        cast(codes, &DataType::Int32).map_err(|_| {
+                            error::ColumnDataTypeMismatchSnafu {
+                                name: consts::STATUS_CODE,
+                                expect: DataType::Int32,
+                                actual: codes.data_type().clone(),
+                            }
+                            .build()
+                        })
+                    })
+                    .transpose()?;
+                let is_error: BooleanArray = match &codes {
+                    Some(codes) => codes
+                        .as_primitive::<Int32Type>()
+                        .iter()
+                        .map(|code| Some(code == Some(StatusCode::Error as i32)))
+                        .collect(),
+                    None => vec![Some(false); rb.num_rows()].into_iter().collect(),
+                };
+                Ok(Arc::new(is_error))
+            }
+        }
+    }
+}
+
+/// The fields to project the records of a batch onto, in order.
+#[derive(Clone, Debug, Default)]
+pub struct Projection {
+    fields: Vec<ProjectedField>,
+}
+
+impl Projection {
+    /// Creates an empty projection.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a column of the main payload, e.g. `trace_id`, or a field of one of its struct
+    /// columns, e.g. `resource.id`. The column is named after `name` in the result.
+    #[must_use]
+    pub fn with_column(mut self, name: impl Into<String>) -> Self {
+        self.fields.push(ProjectedField::Column(name.into()));
+        self
+    }
+
+    /// Adds the attribute with the given key, named after the key in the result. The
+    /// values are of the type of the attribute, or strings if the records have values of
+    /// different types for the key. The map and slice values are CBOR encoded.
+    #[must_use]
+    pub fn with_attribute(mut self, key: impl Into<String>) -> Self {
+        self.fields.push(ProjectedField::Attribute(key.into()));
+        self
+    }
+
+    /// Adds a field computed from the columns of the spans, named after
+    /// [`DerivedField::name`] in the result.
+    #[must_use]
+    pub fn with_derived(mut self, field: DerivedField) -> Self {
+        self.fields.push(ProjectedField::Derived(field));
+        self
+    }
+
+    /// Projects the records of the main payload of the given type, `Logs` or `Spans`.
+    /// Returns `None` if the payload is not in the batch.
+    pub fn project(
+        &self,
+        otap_batch: &OtapBatch,
+        payload_type: ArrowPayloadType,
+    ) -> Result<Option<RecordBatch>> {
+        let attrs_payload_type = match payload_type {
+            ArrowPayloadType::Logs => ArrowPayloadType::LogAttrs,
+            ArrowPayloadType::Spans => ArrowPayloadType::SpanAttrs,
+            _ => {
+                return error::UnsupportedPayloadTypeSnafu {
+                    actual: payload_type as i32,
+                }
+                .fail();
+            }
+        };
+        let Some(rb) = otap_batch.get(payload_type) else {
+            return Ok(None);
+        };
+
+        let mut attributes = None;
+        let mut fields = Vec::with_capacity(self.fields.len());
+        let mut columns = Vec::with_capacity(self.fields.len());
+        for field in &self.fields {
+            let (name, column) = match field {
+                ProjectedField::Column(name) => (name.as_str(), column(rb, name)?),
+                ProjectedField::Derived(field) => (field.name(), field.column(rb)?),
+                ProjectedField::Attribute(key) => {
+                    let attributes = match &mut attributes {
+                        Some(attributes) => attributes,
+                        None => attributes.insert(AttributeJoin::try_new(
+                            otap_batch.get(attrs_payload_type),
+                            rb,
+                        )?),
+                    };
+                    (key.as_str(), attributes.column(key)?)
+                }
+            };
+            fields.push(Field::new(name, column.data_type().clone(), true));
+            columns.push(column);
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let options = arrow::array::RecordBatchOptions::new().with_row_count(Some(rb.num_rows()));
+        RecordBatch::try_new_with_options(schema, columns, &options)
+            .map(Some)
+            .map_err(|e| {
+                error::UnexpectedRecordBatchStateSnafu {
+                    reason: e.to_string(),
+                }
+                .build()
+            })
+    }
+}
+
+/// Returns the column, or the field of a struct column, with the given name.
+fn column(rb: &RecordBatch, name: &str) -> Result<ArrayRef> {
+    let column = rb.column_by_name(name).cloned().or_else(|| {
+        let (parent, field) = name.split_once('.')?;
+        rb.column_by_name(parent)?
+            .as_struct_opt()?
+            .column_by_name(field)
+            .cloned()
+    });
+    column.context(error::ColumnNotFoundSnafu { name })
+}
+
+/// The attributes payload of the records, with the materialized parent ids.
+struct AttributeJoin {
+    attrs: Option<RecordBatch>,
+    record_ids: Vec<Option<u16>>,
+}
+
+impl AttributeJoin {
+    fn try_new(attrs: Option<&RecordBatch>, rb: &RecordBatch) -> Result<Self> {
+        Ok(Self {
+            attrs: attrs.map(materialize_parent_id::<u16>).transpose()?,
+            record_ids: record_ids(rb)?,
+        })
+    }
+
+    /// Returns the values of the attribute with the given key, by record.
+    fn column(&self, key: &str) -> Result<ArrayRef> {
+        let num_records = self.record_ids.len();
+        let Some(attrs) = &self.attrs else {
+            return Ok(new_null_array(&DataType::Utf8, num_records));
+        };
+        let keys =
+            attrs
+                .column_by_name(consts::ATTRIBUTE_KEY)
+                .context(error::ColumnNotFoundSnafu {
+                    name: consts::ATTRIBUTE_KEY,
+                })?;
+        let keys = string_matches(consts::ATTRIBUTE_KEY, keys, |k| k == key)?;
+        let types = get_u8_array(attrs, consts::ATTRIBUTE_TYPE)?;
+        let parent_ids = get_u16_array(attrs, consts::PARENT_ID)?;
+
+        // the row of the attribute of every parent, and the value types present
+        let mut rows = HashMap::new();
+        let mut value_types = Vec::new();
+        for idx in (0..attrs.num_rows()).filter(|idx| keys.value(*idx)) {
+            let Ok(value_type) = AttributeValueType::try_from(types.value(idx)) else {
+                continue;
+            };
+            if value_type == AttributeValueType::Empty || parent_ids.is_null(idx) {
+                continue;
+            }
+            if !value_types.contains(&value_type) {
+                value_types.push(value_type);
+            }
+            let _ = rows.insert(parent_ids.value(idx), (value_type, idx));
+        }
+
+        let target_type = match value_types[..] {
+            [] => return Ok(new_null_array(&DataType::Utf8, num_records)),
+            [value_type] => value_array_type(value_type),
+            _ => DataType::Utf8,
+        };
+        // the first source is a null value for the records without the attribute
+        let mut sources = vec![new_null_array(&target_type, 1)];
+        for value_type in &value_types {
+            let name = value_column(*value_type);
+            let values = attrs
+                .column_by_name(name)
+                .context(error::ColumnNotFoundSnafu { name })?;
+            let options = CastOptions {
+                safe: true,
+                ..Default::default()
+            };
+            let values = cast_with_options(values, &target_type, &options).map_err(|_| {
+                error::ColumnDataTypeMismatchSnafu {
+                    name,
+                    expect: target_type.clone(),
+                    actual: values.data_type().clone(),
+                }
+                .build()
+            })?;
+            sources.push(values);
+        }
+        let indices: Vec<_> = self
+            .record_ids
+            .iter()
+            .map(|id| match id.and_then(|id| rows.get(&id)) {
+                Some((value_type, idx)) => {
+                    // safety: every value type of the rows has a source
+                    let source = value_types
+                        .iter()
+                        .position(|t| t == value_type)
+                        .expect("source of the value type");
+                    (source + 1, *idx)
+                }
+                None => (0, 0),
+            })
+            .collect();
+        let sources: Vec<_> = sources.iter().map(|source| source.as_ref()).collect();
+        interleave(&sources, &indices).map_err(|e| {
+            error::UnexpectedRecordBatchStateSnafu {
+                reason: e.to_string(),
+            }
+            .build()
+        })
+    }
+}
+
+fn value_column(value_type: AttributeValueType) -> &'static str {
+    match value_type {
+        AttributeValueType::Str | AttributeValueType::Empty => consts::ATTRIBUTE_STR,
+        AttributeValueType::Int => consts::ATTRIBUTE_INT,
+        AttributeValueType::Double => consts::ATTRIBUTE_DOUBLE,
+        AttributeValueType::Bool => consts::ATTRIBUTE_BOOL,
+        AttributeValueType::Bytes => consts::ATTRIBUTE_BYTES,
+        AttributeValueType::Map | AttributeValueType::Slice => consts::ATTRIBUTE_SER,
+    }
+}
+
+fn value_array_type(value_type: AttributeValueType) -> DataType {
+    match value_type {
+        AttributeValueType::Str | AttributeValueType::Empty => DataType::Utf8,
+        AttributeValueType::Int => DataType::Int64,
+        AttributeValueType::Double => DataType::Float64,
+        AttributeValueType::Bool => DataType::Boolean,
+        AttributeValueType::Bytes | AttributeValueType::Map | AttributeValueType::Slice => {
+            DataType::Binary
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrow::array::{FixedSizeBinaryArray, StringArray};
+    use arrow::datatypes::{Int64Type, TimestampNanosecondType};
+
+    use crate::encode::TracesProducer;
+    use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_project_spans() {
+        let otap_batch = traces_batch(8, 2);
+        let projected = Projection::new()
+            .with_column(consts::TRACE_ID)
+            .with_column(consts::DURATION_TIME_UNIX_NANO)
+            .with_column("scope.name")
+            .with_attribute("key0")
+            .with_attribute("missing")
+            .project(&otap_batch, ArrowPayloadType::Spans)
+            .unwrap()
+            .unwrap();
+        assert_eq!(projected.num_rows(), 8);
+        let names: Vec<_> = projected
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(names, vec![
+            "trace_id",
+            "duration_time_unix_nano",
+            "scope.name",
+            "key0",
+            "missing"
+        ]);
+
+        let trace_ids = projected
+            .column(0)
+            .as_any()
+            .downcast_ref::<FixedSizeBinaryArray>()
+            .unwrap();
+        assert_eq!(trace_ids.value(5), [1; 16]);
+        assert_eq!(
+            projected.column(2).as_string::<i32>(),
+            &StringArray::from(vec!["scope"; 8])
+        );
+        assert_eq!(
+            projected.column(3).as_string::<i32>(),
+            &StringArray::from(vec!["value0"; 8])
+        );
+        assert_eq!(projected.column(4).null_count(), 8);
+
+        assert!(
+            Projection::new()
+                .project(&otap_batch, ArrowPayloadType::Logs)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            Projection::new()
+                .with_column("unknown")
+                .project(&otap_batch, ArrowPayloadType::Spans)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_project_derived_fields() {
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: (0..3)
+                        .map(|idx| Span {
+                            trace_id: vec![1; 16],
+                            span_id: vec![idx; 8],
+                            start_time_unix_nano: 100,
+                            end_time_unix_nano: 100 + u64::from(idx) * 10,
+                            status: (idx > 0).then(|| Status {
+                                code: i32::from(idx),
+                                ..Default::default()
+                            }),
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let otap_batch = TracesProducer::new().produce(&request).unwrap();
+        let projected = Projection::new()
+            .with_column(consts::SPAN_ID)
+            .with_derived(DerivedField::Duration)
+            .with_derived(DerivedField::EndTime)
+            .with_derived(DerivedField::IsError)
+            .project(&otap_batch, ArrowPayloadType::Spans)
+            .unwrap()
+            .unwrap();
+        assert_eq!(projected.schema().field(1).name(), "duration_ns");
+        let span_ids = projected
+            .column(0)
+            .as_any()
+            .downcast_ref::<FixedSizeBinaryArray>()
+            .unwrap();
+        let durations = projected.column(1).as_primitive::<Int64Type>();
+        let end_times = projected
+            .column(2)
+            .as_primitive::<TimestampNanosecondType>();
+        let is_error = projected.column(3).as_boolean();
+        for row in 0..projected.num_rows() {
+            let span =
+                &request.resource_spans[0].scope_spans[0].spans[span_ids.value(row)[0] as usize];
+            assert_eq!(durations.value(row) as u64, span.duration_ns());
+            assert_eq!(end_times.value(row) as u64, span.end_time_unix_nano);
+            assert_eq!(is_error.value(row), span.is_error());
+        }
+        assert_eq!(is_error.true_count(), 1);
+    }
+}
@@ -0,0 +1,457 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Remapping of trace and span ids, e.g. before exporting telemetry to a third party.
+//!
+//! Ids are rewritten in every payload that carries them (logs, spans, span links and
+//! exemplars). The remapping is applied to every occurrence of an id, so references between
+//! records, like parent span ids, links and exemplars pointing to spans of the same batch,
+//! still resolve after the remapping.
+//!
+//! [`HmacIdRemapper`] derives the new ids from a keyed HMAC-SHA256 of the original ones. It
+//! is deterministic for a given key, so ids are also consistent across batches. When the
+//! original ids need to be recovered, a [`MappingStore`] can be attached to the remapper and
+//! later passed to [`restore_ids`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arrow::array::{Array, ArrayRef, AsArray, FixedSizeBinaryArray, RecordBatch};
+use arrow::datatypes::DataType;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use snafu::ResultExt;
+
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts;
+
+/// The kind of id being remapped.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum IdKind {
+    /// A 16 bytes trace id.
+    Trace,
+    /// An 8 bytes span id.
+    Span,
+}
+
+/// Computes the id that replaces an original trace or span id.
+pub trait IdRemapper {
+    /// Returns the id replacing `id`. The returned id must have the same length as `id`, and
+    /// the same id must always be remapped to the same value.
+    fn remap(&mut self, kind: IdKind, id: &[u8]) -> Vec<u8>;
+}
+
+/// Records the original value of the remapped ids so they can be restored.
+pub trait MappingStore {
+    /// Records that `original` was remapped to `remapped`.
+    fn insert(&mut self, kind: IdKind, original: &[u8], remapped: &[u8]);
+
+    /// Returns the original value of a remapped id, if known.
+    fn original(&self, kind: IdKind, remapped: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// [`MappingStore`] keeping the mapping in memory.
+#[derive(Debug, Default)]
+pub struct InMemoryMappingStore {
+    originals: HashMap<(IdKind, Vec<u8>), Vec<u8>>,
+}
+
+impl InMemoryMappingStore {
+    /// Returns the number of ids recorded in the store.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.originals.len()
+    }
+
+    /// Returns true if no id was recorded in the store.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.originals.is_empty()
+    }
+}
+
+impl MappingStore for InMemoryMappingStore {
+    fn insert(&mut self, kind: IdKind, original: &[u8], remapped: &[u8]) {
+        let _ = self
+            .originals
+            .insert((kind, remapped.to_vec()), original.to_vec());
+    }
+
+    fn original(&self, kind: IdKind, remapped: &[u8]) -> Option<Vec<u8>> {
+        self.originals.get(&(kind, remapped.to_vec())).cloned()
+    }
+}
+
+/// [`IdRemapper`] replacing ids with a truncated HMAC-SHA256 of the original id.
+///
+/// All zero ids are left untouched as they represent a missing id (e.g. the parent of a root
+/// span).
+pub struct HmacIdRemapper<S = InMemoryMappingStore> {
+    mac: Hmac<Sha256>,
+    store: Option<S>,
+}
+
+impl HmacIdRemapper {
+    /// Creates a remapper deriving ids from the given secret key.
+    #[must_use]
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            // safety: HMAC accepts keys of any length
+            mac: Hmac::new_from_slice(key).expect("HMAC accepts keys of any length"),
+            store: None,
+        }
+    }
+}
+
+impl<S: MappingStore> HmacIdRemapper<S> {
+    /// Creates a remapper deriving ids from the given secret key and recording the original
+    /// ids in `store`.
+    #[must_use]
+    pub fn with_mapping_store(key: &[u8], store: S) -> Self {
+        Self {
+            // safety: HMAC accepts keys of any length
+            mac: Hmac::new_from_slice(key).expect("HMAC accepts keys of any length"),
+            store: Some(store),
+        }
+    }
+
+    /// Returns the store recording the original ids, if any.
+    #[must_use]
+    pub fn mapping_store(&self) -> Option<&S> {
+        self.store.as_ref()
+    }
+
+    /// Consumes the remapper, returning the store recording the original ids, if any.
+    #[must_use]
+    pub fn into_mapping_store(self) -> Option<S> {
+        self.store
+    }
+}
+
+impl<S: MappingStore> IdRemapper for HmacIdRemapper<S> {
+    fn remap(&mut self, kind: IdKind, id: &[u8]) -> Vec<u8> {
+        if id.iter().all(|b| *b == 0) {
+            return id.to_vec();
+        }
+
+        let mut mac = self.mac.clone();
+        // the kind is part of the message so trace and span ids don't share a mapping
+        mac.update(&[kind as u8]);
+        mac.update(id);
+        let digest = mac.finalize().into_bytes();
+        let mut remapped = digest[..id.len().min(digest.len())].to_vec();
+        if remapped.iter().all(|b| *b == 0) {
+            // an all zero id would be dropped by receivers
+            remapped[0] = 1;
+        }
+
+        if let Some(store) = self.store.as_mut() {
+            store.insert(kind, id, &remapped);
+        }
+        remapped
+    }
+}
+
+/// Payload types containing trace or span ids, and the id columns they contain.
+const ID_COLUMNS: &[(ArrowPayloadType, &[(&str, IdKind)])] = &[
+    (ArrowPayloadType::Logs, &[
+        (consts::TRACE_ID, IdKind::Trace),
+        (consts::SPAN_ID, IdKind::Span),
+    ]),
+    (ArrowPayloadType::Spans, &[
+        (consts::TRACE_ID, IdKind::Trace),
+        (consts::SPAN_ID, IdKind::Span),
+        (consts::PARENT_SPAN_ID, IdKind::Span),
+    ]),
+    (ArrowPayloadType::SpanLinks, &[
+        (consts::TRACE_ID, IdKind::Trace),
+        (consts::SPAN_ID, IdKind::Span),
+    ]),
+    (ArrowPayloadType::NumberDpExemplars, &[
+        (consts::TRACE_ID, IdKind::Trace),
+        (consts::SPAN_ID, IdKind::Span),
+    ]),
+    (ArrowPayloadType::HistogramDpExemplars, &[
+        (consts::TRACE_ID, IdKind::Trace),
+        (consts::SPAN_ID, IdKind::Span),
+    ]),
+    (ArrowPayloadType::ExpHistogramDpExemplars, &[
+        (consts::TRACE_ID, IdKind::Trace),
+        (consts::SPAN_ID, IdKind::Span),
+    ]),
+];
+
+/// Remaps all the trace and span ids contained in the batch.
+pub fn remap_ids<R: IdRemapper>(otap_batch: &mut OtapBatch, remapper: &mut R) -> Result<()> {
+    rewrite_ids(otap_batch, |kind, id| Ok(remapper.remap(kind, id)))
+}
+
+/// Restores the original value of ids remapped by an [`IdRemapper`] that recorded them in
+/// `store`. Fails if an id is not found in the store.
+pub fn restore_ids<S: MappingStore>(otap_batch: &mut OtapBatch, store: &S) -> Result<()> {
+    rewrite_ids(otap_batch, |kind, id| {
+        if id.iter().all(|b| *b == 0) {
+            return Ok(id.to_vec());
+        }
+        store
+            .original(kind, id)
+            .ok_or_else(|| error::RemappedIdNotFoundSnafu { id: id.to_vec() }.build())
+    })
+}
+
+fn rewrite_ids<F>(otap_batch: &mut OtapBatch, mut f: F) -> Result<()>
+where
+    F: FnMut(IdKind, &[u8]) -> Result<Vec<u8>>,
+{
+    for (payload_type, columns) in ID_COLUMNS {
+        let Some(record_batch) = otap_batch.get(*payload_type) else {
+            continue;
+        };
+        let record_batch = rewrite_record_batch(record_batch, columns, &mut f)?;
+        otap_batch.set(*payload_type, record_batch);
+    }
+    Ok(())
+}
+
+fn rewrite_record_batch<F>(
+    record_batch: &RecordBatch,
+    id_columns: &[(&str, IdKind)],
+    f: &mut F,
+) -> Result<RecordBatch>
+where
+    F: FnMut(IdKind, &[u8]) -> Result<Vec<u8>>,
+{
+    let schema = record_batch.schema();
+    let mut columns = record_batch.columns().to_vec();
+    for (name, kind) in id_columns {
+        let Ok(index) = schema.index_of(name) else {
+            continue;
+        };
+        columns[index] = rewrite_column(name, &columns[index], *kind, f)?;
+    }
+
+    RecordBatch::try_new(schema, columns).context(error::WriteRecordBatchSnafu)
+}
+
+fn rewrite_column<F>(name: &str, column: &ArrayRef, kind: IdKind, f: &mut F) -> Result<ArrayRef>
+where
+    F: FnMut(IdKind, &[u8]) -> Result<Vec<u8>>,
+{
+    match column.data_type() {
+        DataType::FixedSizeBinary(_) => {
+            Ok(Arc::new(rewrite_ids_array(column.as_fixed_size_binary(), kind, f)?) as ArrayRef)
+        }
+        DataType::Dictionary(_, value_type)
+            if matches!(value_type.as_ref(), DataType::FixedSizeBinary(_)) =>
+        {
+            // rewriting the dictionary values is enough, keys are left untouched
+            let dict = column.as_any_dictionary();
+            let values = rewrite_ids_array(dict.values().as_fixed_size_binary(), kind, f)?;
+            Ok(dict.with_values(Arc::new(values)))
+        }
+        data_type => error::ColumnDataTypeMismatchSnafu {
+            name,
+            expect: DataType::FixedSizeBinary(match kind {
+                IdKind::Trace => 16,
+                IdKind::Span => 8,
+            }),
+            actual: data_type.clone(),
+        }
+        .fail(),
+    }
+}
+
+fn rewrite_ids_array<F>(
+    array: &FixedSizeBinaryArray,
+    kind: IdKind,
+    f: &mut F,
+) -> Result<FixedSizeBinaryArray>
+where
+    F: FnMut(IdKind, &[u8]) -> Result<Vec<u8>>,
+{
+    let ids = array
+        .iter()
+        .map(|id| id.map(|id| f(kind, id)).transpose())
+        .collect::<Result<Vec<_>>>()?;
+    let ids = ids.iter().map(|id| id.as_deref());
+    if array.null_count() == array.len() {
+        // all null arrays keep their size, it can't be inferred from the values
+        return Ok(FixedSizeBinaryArray::new_null(
+            array.value_length(),
+            array.len(),
+        ));
+    }
+    FixedSizeBinaryArray::try_from_sparse_iter_with_size(ids, array.value_length())
+        .context(error::WriteRecordBatchSnafu)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrow::array::{DictionaryArray, UInt8Array, UInt16Array};
+    use arrow::datatypes::{Field, Schema, UInt8Type};
+
+    use crate::otap::{Metrics, Traces};
+
+    const KEY: &[u8] = b"secret";
+
+    fn ids(width: i32, ids: Vec<Option<[u8; 16]>>) -> FixedSizeBinaryArray {
+        FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+            ids.iter()
+                .map(|id| id.as_ref().map(|id| &id[..width as usize])),
+            width,
+        )
+        .unwrap()
+    }
+
+    fn id(b: u8) -> Option<[u8; 16]> {
+        Some([b; 16])
+    }
+
+    fn traces_batch() -> OtapBatch {
+        let mut batch = OtapBatch::Traces(Traces::default());
+        batch.set(
+            ArrowPayloadType::Spans,
+            RecordBatch::try_new(
+                Arc::new(Schema::new(vec![
+                    Field::new(consts::ID, DataType::UInt16, true),
+                    Field::new(consts::TRACE_ID, DataType::FixedSizeBinary(16), false),
+                    Field::new(consts::SPAN_ID, DataType::FixedSizeBinary(8), false),
+                    Field::new(consts::PARENT_SPAN_ID, DataType::FixedSizeBinary(8), true),
+                ])),
+                vec![
+                    Arc::new(UInt16Array::from(vec![0, 1])),
+                    Arc::new(ids(16, vec![id(1), id(1)])),
+                    Arc::new(ids(8, vec![id(2), id(3)])),
+                    Arc::new(ids(8, vec![None, id(2)])),
+                ],
+            )
+            .unwrap(),
+        );
+        let trace_ids =
+            DictionaryArray::new(UInt8Array::from(vec![0, 0]), Arc::new(ids(16, vec![id(1)])));
+        batch.set(
+            ArrowPayloadType::SpanLinks,
+            RecordBatch::try_new(
+                Arc::new(Schema::new(vec![
+                    Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                    Field::new(
+                        consts::TRACE_ID,
+                        DataType::Dictionary(
+                            Box::new(DataType::UInt8),
+                            Box::new(DataType::FixedSizeBinary(16)),
+                        ),
+                        true,
+                    ),
+                    Field::new(consts::SPAN_ID, DataType::FixedSizeBinary(8), true),
+                ])),
+                vec![
+                    Arc::new(UInt16Array::from(vec![0, 1])),
+                    Arc::new(trace_ids),
+                    Arc::new(ids(8, vec![id(3), id(2)])),
+                ],
+            )
+            .unwrap(),
+        );
+        batch
+    }
+
+    fn column(batch: &OtapBatch, payload_type: ArrowPayloadType, name: &str) -> ArrayRef {
+        batch
+            .get(payload_type)
+            .unwrap()
+            .column_by_name(name)
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_remap_preserves_references() {
+        let mut batch = traces_batch();
+        let mut remapper = HmacIdRemapper::new(KEY);
+        remap_ids(&mut batch, &mut remapper).unwrap();
+
+        let spans_trace_ids = column(&batch, ArrowPayloadType::Spans, consts::TRACE_ID);
+        let span_ids = column(&batch, ArrowPayloadType::Spans, consts::SPAN_ID);
+        let parent_ids = column(&batch, ArrowPayloadType::Spans, consts::PARENT_SPAN_ID);
+        let link_trace_ids = column(&batch, ArrowPayloadType::SpanLinks, consts::TRACE_ID);
+        let link_span_ids = column(&batch, ArrowPayloadType::SpanLinks, consts::SPAN_ID);
+
+        let spans_trace_ids = spans_trace_ids.as_fixed_size_binary();
+        let span_ids = span_ids.as_fixed_size_binary();
+        let parent_ids = parent_ids.as_fixed_size_binary();
+        let link_trace_ids = link_trace_ids.as_dictionary::<UInt8Type>();
+        let link_trace_id = link_trace_ids.values().as_fixed_size_binary().value(0);
+        let link_span_ids = link_span_ids.as_fixed_size_binary();
+
+        assert_ne!(spans_trace_ids.value(0), &[1; 16]);
+        assert_eq!(spans_trace_ids.value(0), spans_trace_ids.value(1));
+        assert_eq!(spans_trace_ids.value(0), link_trace_id);
+        assert_ne!(span_ids.value(0), &[2; 8]);
+        assert_ne!(span_ids.value(0), span_ids.value(1));
+        assert!(parent_ids.is_null(0));
+        assert_eq!(parent_ids.value(1), span_ids.value(0));
+        assert_eq!(link_span_ids.value(0), span_ids.value(1));
+        assert_eq!(link_span_ids.value(1), span_ids.value(0));
+
+        // remapping is deterministic for a given key
+        let mut other = traces_batch();
+        remap_ids(&mut other, &mut HmacIdRemapper::new(KEY)).unwrap();
+        assert_eq!(
+            other.get(ArrowPayloadType::Spans),
+            batch.get(ArrowPayloadType::Spans)
+        );
+        let mut other = traces_batch();
+        remap_ids(&mut other, &mut HmacIdRemapper::new(b"other")).unwrap();
+        assert_ne!(
+            other.get(ArrowPayloadType::Spans),
+            batch.get(ArrowPayloadType::Spans)
+        );
+    }
+
+    #[test]
+    fn test_restore_ids() {
+        let mut batch = traces_batch();
+        let mut remapper = HmacIdRemapper::with_mapping_store(KEY, InMemoryMappingStore::default());
+        remap_ids(&mut batch, &mut remapper).unwrap();
+        let store = remapper.into_mapping_store().unwrap();
+        // one trace id and two span ids
+        assert_eq!(store.len(), 3);
+
+        restore_ids(&mut batch, &store).unwrap();
+        let expected = traces_batch();
+        for payload_type in [ArrowPayloadType::Spans, ArrowPayloadType::SpanLinks] {
+            assert_eq!(batch.get(payload_type), expected.get(payload_type));
+        }
+
+        assert!(restore_ids(&mut batch, &InMemoryMappingStore::default()).is_err());
+    }
+
+    #[test]
+    fn test_remap_exemplars() {
+        let mut batch = OtapBatch::Metrics(Metrics::default());
+        let exemplars = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::TRACE_ID, DataType::FixedSizeBinary(16), true),
+                Field::new(consts::SPAN_ID, DataType::FixedSizeBinary(8), true),
+            ])),
+            vec![
+                Arc::new(FixedSizeBinaryArray::new_null(16, 2)),
+                Arc::new(ids(8, vec![id(2), None])),
+            ],
+        )
+        .unwrap();
+        batch.set(ArrowPayloadType::NumberDpExemplars, exemplars.clone());
+
+        let mut remapper = HmacIdRemapper::new(KEY);
+        remap_ids(&mut batch, &mut remapper).unwrap();
+        let result = batch.get(ArrowPayloadType::NumberDpExemplars).unwrap();
+        assert_eq!(result.column(0), exemplars.column(0));
+        let span_ids = result.column(1).as_fixed_size_binary();
+        assert_eq!(span_ids.value(0), remapper.remap(IdKind::Span, &[2; 8]));
+        assert!(span_ids.is_null(1));
+    }
+}
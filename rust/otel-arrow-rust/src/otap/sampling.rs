@@ -0,0 +1,285 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sampling and rate limiting of the spans of a batch on its Arrow columns, before the batch
+//! is decoded, so the spans that are dropped are never materialized as OTLP messages.
+//!
+//! [`filter_spans`] keeps the spans selected by a mask along with the rows related to them:
+//! their attributes, their events and links, and the attributes of these, so the filtered
+//! batch decodes like a batch produced with only the kept spans.
+//!
+//! [`TraceIdRatioSampler`] keeps the traces whose id falls under a ratio, deciding on the
+//! trace id only, like the `TraceIdRatioBased` sampler of the OpenTelemetry SDKs, so the
+//! spans of a trace get the same decision in every batch and on every instance.
+//! [`SpanRateLimiter`] keeps at most a number of spans per second.
+
+use std::time::Instant;
+
+use arrow::array::{BooleanArray, RecordBatch};
+
+use crate::arrays::{ByteArrayAccessor, ColumnAccessor, NullableArrayAccessor};
+use crate::error::{ErrorContextExt, Result};
+use crate::otap::OtapBatch;
+use crate::otap::filter::filter_with_children;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts;
+
+/// Keeps the spans of the batch selected by the mask, a null keeping no span, and the rows
+/// of the payloads related to them, see [`filter_with_children`]. Returns the number of
+/// spans dropped.
+pub fn filter_spans(otap_batch: &mut OtapBatch, mask: &BooleanArray) -> Result<usize> {
+    filter_with_children(otap_batch, ArrowPayloadType::Spans, mask)
+}
+
+/// Head sampler keeping the spans of a ratio of the traces, see the module documentation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceIdRatioSampler {
+    ratio: f64,
+    /// Bound of the 63 bits the decision is made on.
+    bound: u64,
+}
+
+impl TraceIdRatioSampler {
+    /// Creates a sampler keeping the given ratio of the traces, clamped to `[0, 1]`.
+    #[must_use]
+    pub fn new(ratio: f64) -> Self {
+        let ratio = if ratio.is_nan() {
+            0.0
+        } else {
+            ratio.clamp(0.0, 1.0)
+        };
+        Self {
+            ratio,
+            bound: (ratio * (1u64 << 63) as f64) as u64,
+        }
+    }
+
+    /// Returns the ratio of the traces kept.
+    #[must_use]
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    /// Returns whether the trace with this id is kept. The decision is made on the last 8
+    /// bytes of the id, the random part of the W3C trace ids.
+    #[must_use]
+    pub fn should_sample(&self, trace_id: &[u8]) -> bool {
+        if self.ratio >= 1.0 {
+            return true;
+        }
+        let mut bytes = [0; 8];
+        let tail = &trace_id[trace_id.len().saturating_sub(8)..];
+        bytes[8 - tail.len()..].copy_from_slice(tail);
+        (u64::from_be_bytes(bytes) >> 1) < self.bound
+    }
+
+    /// Returns the mask of the spans of the batch that the sampler keeps.
+    pub fn evaluate(&self, otap_batch: &OtapBatch) -> Result<BooleanArray> {
+        let Some(spans) = otap_batch.get(ArrowPayloadType::Spans) else {
+            return Ok(BooleanArray::from(Vec::<bool>::new()));
+        };
+        let trace_ids = ByteArrayAccessor::try_new_for_column(spans, consts::TRACE_ID)
+            .in_payload(ArrowPayloadType::Spans)?;
+        Ok((0..spans.num_rows())
+            .map(|idx| Some(self.should_sample(&trace_ids.value_at_or_default(idx))))
+            .collect())
+    }
+
+    /// Drops the spans of the traces the sampler doesn't keep, see [`filter_spans`].
+    /// Returns the number of spans dropped.
+    pub fn sample(&self, otap_batch: &mut OtapBatch) -> Result<usize> {
+        let mask = self.evaluate(otap_batch)?;
+        filter_spans(otap_batch, &mask)
+    }
+}
+
+/// Rate limiter keeping at most a number of spans per second, in a token bucket holding up
+/// to a second of spans. The first spans of a batch are kept when the bucket can't hold the
+/// whole batch.
+#[derive(Clone, Debug)]
+pub struct SpanRateLimiter {
+    spans_per_second: f64,
+    tokens: f64,
+    last_refill: Option<Instant>,
+}
+
+impl SpanRateLimiter {
+    /// Creates a limiter keeping at most `spans_per_second` spans per second, starting with
+    /// a full bucket.
+    #[must_use]
+    pub fn new(spans_per_second: u32) -> Self {
+        Self {
+            spans_per_second: f64::from(spans_per_second),
+            tokens: f64::from(spans_per_second),
+            last_refill: None,
+        }
+    }
+
+    /// Drops the spans of the batch over the rate at time `now`, see [`filter_spans`].
+    /// Returns the number of spans dropped.
+    pub fn limit(&mut self, otap_batch: &mut OtapBatch, now: Instant) -> Result<usize> {
+        if let Some(last_refill) = self.last_refill {
+            let elapsed = now.saturating_duration_since(last_refill).as_secs_f64();
+            self.tokens =
+                (self.tokens + elapsed * self.spans_per_second).min(self.spans_per_second);
+        }
+        self.last_refill = Some(now);
+
+        let rows = otap_batch
+            .get(ArrowPayloadType::Spans)
+            .map_or(0, RecordBatch::num_rows);
+        let kept = rows.min(self.tokens as usize);
+        self.tokens -= kept as f64;
+        let mask: BooleanArray = (0..rows).map(|idx| Some(idx < kept)).collect();
+        filter_spans(otap_batch, &mask)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::encode::TracesProducer;
+    use crate::error::Error;
+    use crate::otlp::traces::traces_from;
+    use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+    use crate::proto::opentelemetry::resource::v1::Resource;
+    use crate::proto::opentelemetry::trace::v1::span::{Event, Link};
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+    fn attr(key: &str, value: i64) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: Some(AnyValue {
+                value: Some(Value::IntValue(value)),
+            }),
+        }
+    }
+
+    fn span(trace: u8, idx: u8) -> Span {
+        Span {
+            trace_id: vec![trace * 0x40; 16],
+            span_id: vec![idx; 8],
+            name: format!("span-{idx}"),
+            start_time_unix_nano: u64::from(idx),
+            attributes: vec![attr("idx", i64::from(idx))],
+            events: (0..idx % 3)
+                .map(|event| Event {
+                    name: format!("event-{event}"),
+                    time_unix_nano: u64::from(idx),
+                    attributes: vec![attr("event", i64::from(event))],
+                    ..Default::default()
+                })
+                .collect(),
+            links: (0..idx % 2)
+                .map(|_| Link {
+                    trace_id: vec![7; 16],
+                    span_id: vec![idx; 8],
+                    attributes: vec![attr("link", i64::from(idx))],
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn request(spans: impl Fn(u8) -> bool) -> ExportTraceServiceRequest {
+        let resource_spans = |resource: u8| ResourceSpans {
+            resource: Some(Resource {
+                attributes: vec![attr("resource", i64::from(resource))],
+                ..Default::default()
+            }),
+            scope_spans: vec![ScopeSpans {
+                scope: Some(InstrumentationScope::default()),
+                spans: (0..12)
+                    .filter(|idx| spans(resource * 12 + idx))
+                    .map(|idx| span(idx % 4, resource * 12 + idx))
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut request = ExportTraceServiceRequest {
+            resource_spans: (0..3).map(resource_spans).collect(),
+        };
+        request
+            .resource_spans
+            .retain(|r| !r.scope_spans[0].spans.is_empty());
+        request
+    }
+
+    #[test]
+    fn test_filter_spans() {
+        let mut otap_batch = TracesProducer::new().produce(&request(|_| true)).unwrap();
+        let spans = otap_batch.get(ArrowPayloadType::Spans).unwrap();
+        // the producer reorders the spans, the mask is computed on the payload
+        let span_ids = ByteArrayAccessor::try_new_for_column(spans, consts::SPAN_ID).unwrap();
+        // drop the second resource and every third span
+        let keep = |idx: u8| idx / 12 != 1 && idx % 3 != 0;
+        let mask: BooleanArray = (0..spans.num_rows())
+            .map(|row| Some(keep(span_ids.value_at(row).unwrap()[0])))
+            .collect();
+        let expected_dropped = mask.len() - mask.true_count();
+
+        let dropped = filter_spans(&mut otap_batch, &mask).unwrap();
+        assert_eq!(dropped, expected_dropped);
+        let expected = traces_from(TracesProducer::new().produce(&request(keep)).unwrap());
+        assert_eq!(traces_from(otap_batch).unwrap(), expected.unwrap());
+
+        let mut otap_batch = TracesProducer::new().produce(&request(|_| true)).unwrap();
+        assert!(matches!(
+            filter_spans(&mut otap_batch, &BooleanArray::from(vec![true])),
+            Err(Error::MaskLengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_trace_id_ratio_sampler() {
+        let none = TraceIdRatioSampler::new(0.0);
+        let all = TraceIdRatioSampler::new(2.0);
+        let half = TraceIdRatioSampler::new(0.5);
+        assert_eq!(all.ratio(), 1.0);
+        assert!(!none.should_sample(&[0xff; 16]));
+        assert!(all.should_sample(&[0xff; 16]));
+        assert!(half.should_sample(&[0x3f; 16]));
+        assert!(!half.should_sample(&[0x80; 16]));
+
+        // the traces 0 and 1 are kept, their ids being under the bound
+        let sampled = |trace: u8| half.should_sample(&[trace * 0x40; 16]);
+        assert_eq!((0..4).map(sampled).collect::<Vec<_>>(), vec![
+            true, true, false, false
+        ]);
+        let mut otap_batch = TracesProducer::new().produce(&request(|_| true)).unwrap();
+        let dropped = half.sample(&mut otap_batch).unwrap();
+        assert_eq!(dropped, 18);
+        let expected = request(|idx| sampled((idx % 12) % 4));
+        let expected = traces_from(TracesProducer::new().produce(&expected).unwrap());
+        assert_eq!(traces_from(otap_batch).unwrap(), expected.unwrap());
+    }
+
+    #[test]
+    fn test_span_rate_limiter() {
+        let mut limiter = SpanRateLimiter::new(30);
+        let now = Instant::now();
+        let batch = || TracesProducer::new().produce(&request(|_| true)).unwrap();
+        assert_eq!(limiter.limit(&mut batch(), now).unwrap(), 6);
+        assert_eq!(limiter.limit(&mut batch(), now).unwrap(), 36);
+        // half a second refills 15 spans
+        let mut otap_batch = batch();
+        let dropped = limiter
+            .limit(&mut otap_batch, now + Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(dropped, 21);
+        let request = traces_from(otap_batch).unwrap();
+        let spans: usize = request
+            .resource_spans
+            .iter()
+            .map(|r| r.scope_spans[0].spans.len())
+            .sum();
+        assert_eq!(spans, 15);
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Composition of OTAP batches, to understand why batches are large and tune the encoding.
+//!
+//! [`BatchStats::try_new`] reports, for every payload of a batch, its number of rows, the
+//! size and fill ratio of its dictionaries, its size once IPC encoded with and without
+//! compression and, for the attribute payloads, how much the key / value pairs are shared
+//! between the parents.
+
+use std::collections::HashSet;
+
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::datatypes::DataType;
+use arrow::row::{RowConverter, SortField};
+use snafu::ResultExt;
+
+use crate::compression::{PayloadCompression, PayloadWriter};
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::otap::graph::is_attributes;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts;
+
+/// Composition of a batch, one entry per payload, main payload first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchStats {
+    /// The statistics of the payloads present in the batch.
+    pub payloads: Vec<PayloadStats>,
+}
+
+/// Composition of a payload of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PayloadStats {
+    /// The payload type.
+    pub payload_type: ArrowPayloadType,
+    /// The number of rows.
+    pub rows: usize,
+    /// The dictionary encoded columns, including the fields of struct columns.
+    pub dictionaries: Vec<DictionaryStats>,
+    /// The size of the IPC stream of the payload without compression.
+    pub uncompressed_bytes: usize,
+    /// The size of the IPC stream of the payload with the compression of the stats.
+    pub compressed_bytes: usize,
+    /// For attribute payloads, the share of the rows repeating the key and value of a
+    /// previous row, between 0 and 1.
+    pub attribute_reuse_ratio: Option<f64>,
+}
+
+/// Composition of a dictionary encoded column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DictionaryStats {
+    /// The column name, with the name of the struct column for a field, e.g. `scope.name`.
+    pub column: String,
+    /// The number of values of the dictionary.
+    pub values: usize,
+    /// The number of values of the dictionary over the number of values its key type can
+    /// address, between 0 and 1. A ratio close to 1 means the dictionary is about to
+    /// overflow its key type.
+    pub fill_ratio: f64,
+}
+
+impl BatchStats {
+    /// Computes the composition of the batch, the compressed sizes being measured with the
+    /// given codec.
+    pub fn try_new(otap_batch: &OtapBatch, compression: PayloadCompression) -> Result<Self> {
+        let mut payloads = Vec::new();
+        for payload_type in otap_batch.payload_types() {
+            // safety: payload_types only returns types that are present in the batch
+            let record_batch = otap_batch
+                .get(payload_type)
+                .expect("payload type present in batch");
+            payloads.push(PayloadStats::try_new(
+                payload_type,
+                record_batch,
+                compression,
+            )?);
+        }
+        Ok(Self { payloads })
+    }
+
+    /// Returns the statistics of the payload type, if it is present in the batch.
+    #[must_use]
+    pub fn payload(&self, payload_type: ArrowPayloadType) -> Option<&PayloadStats> {
+        self.payloads
+            .iter()
+            .find(|payload| payload.payload_type == payload_type)
+    }
+
+    /// Returns the total number of rows of the batch.
+    #[must_use]
+    pub fn total_rows(&self) -> usize {
+        self.payloads.iter().map(|payload| payload.rows).sum()
+    }
+
+    /// Returns the total size of the payloads without compression.
+    #[must_use]
+    pub fn total_uncompressed_bytes(&self) -> usize {
+        self.payloads
+            .iter()
+            .map(|payload| payload.uncompressed_bytes)
+            .sum()
+    }
+
+    /// Returns the total size of the payloads with compression.
+    #[must_use]
+    pub fn total_compressed_bytes(&self) -> usize {
+        self.payloads
+            .iter()
+            .map(|payload| payload.compressed_bytes)
+            .sum()
+    }
+}
+
+impl PayloadStats {
+    fn try_new(
+        payload_type: ArrowPayloadType,
+        record_batch: &RecordBatch,
+        compression: PayloadCompression,
+    ) -> Result<Self> {
+        let mut dictionaries = Vec::new();
+        for (field, column) in record_batch
+            .schema()
+            .fields()
+            .iter()
+            .zip(record_batch.columns())
+        {
+            collect_dictionaries(field.name(), column, &mut dictionaries);
+        }
+
+        let attribute_reuse_ratio = if is_attributes(payload_type) {
+            attribute_reuse_ratio(record_batch)?
+        } else {
+            None
+        };
+
+        Ok(Self {
+            payload_type,
+            rows: record_batch.num_rows(),
+            dictionaries,
+            uncompressed_bytes: encoded_len(record_batch, PayloadCompression::None)?,
+            compressed_bytes: encoded_len(record_batch, compression)?,
+            attribute_reuse_ratio,
+        })
+    }
+}
+
+fn collect_dictionaries(name: &str, column: &ArrayRef, dictionaries: &mut Vec<DictionaryStats>) {
+    match column.data_type() {
+        DataType::Dictionary(key_type, _) => {
+            let capacity = match key_type.as_ref() {
+                DataType::UInt8 | DataType::Int8 => 1usize << 8,
+                DataType::UInt16 | DataType::Int16 => 1 << 16,
+                _ => 1 << 32,
+            };
+            let values = column.as_any_dictionary().values().len();
+            dictionaries.push(DictionaryStats {
+                column: name.to_string(),
+                values,
+                fill_ratio: values as f64 / capacity as f64,
+            });
+        }
+        DataType::Struct(fields) => {
+            for (field, child) in fields.iter().zip(column.as_struct().columns()) {
+                collect_dictionaries(&format!("{name}.{}", field.name()), child, dictionaries);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the share of the rows whose key and value columns are equal to the ones of a
+/// previous row, or `None` for an empty payload.
+fn attribute_reuse_ratio(record_batch: &RecordBatch) -> Result<Option<f64>> {
+    if record_batch.num_rows() == 0 {
+        return Ok(None);
+    }
+    let columns: Vec<ArrayRef> = record_batch
+        .schema()
+        .fields()
+        .iter()
+        .zip(record_batch.columns())
+        .filter(|(field, _)| field.name() != consts::PARENT_ID)
+        .map(|(_, column)| column.clone())
+        .collect();
+    let converter = RowConverter::new(
+        columns
+            .iter()
+            .map(|column| SortField::new(column.data_type().clone()))
+            .collect(),
+    )
+    .context(error::WriteRecordBatchSnafu)?;
+    let rows = converter
+        .convert_columns(&columns)
+        .context(error::WriteRecordBatchSnafu)?;
+    let distinct: HashSet<_> = rows.iter().collect();
+    let num_rows = record_batch.num_rows();
+    Ok(Some((num_rows - distinct.len()) as f64 / num_rows as f64))
+}
+
+fn encoded_len(record_batch: &RecordBatch, compression: PayloadCompression) -> Result<usize> {
+    let mut writer = PayloadWriter::try_new(&record_batch.schema(), compression)?;
+    writer.write(record_batch).map(|bytes| bytes.len())
+}
+
+// the stats are measured with zstd compression
+#[cfg(all(test, feature = "zstd"))]
+mod test {
+    use super::*;
+
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_batch_stats() {
+        let otap_batch = traces_batch(64, 4);
+        let stats = BatchStats::try_new(&otap_batch, PayloadCompression::Zstd).unwrap();
+        assert_eq!(stats.payloads.len(), 2);
+        assert_eq!(stats.total_rows(), 64 + 256);
+
+        let spans = stats.payload(ArrowPayloadType::Spans).unwrap();
+        assert_eq!(spans.payload_type, ArrowPayloadType::Spans);
+        assert_eq!(spans.rows, 64);
+        assert_eq!(spans.dictionaries, vec![DictionaryStats {
+            column: consts::NAME.to_string(),
+            values: 16,
+            fill_ratio: 16.0 / 65536.0,
+        }]);
+        assert!(spans.compressed_bytes > 0);
+        assert_eq!(spans.attribute_reuse_ratio, None);
+
+        // 4 distinct key / value pairs over 256 rows
+        let attrs = stats.payload(ArrowPayloadType::SpanAttrs).unwrap();
+        assert_eq!(attrs.dictionaries.len(), 2);
+        assert_eq!(attrs.attribute_reuse_ratio, Some(252.0 / 256.0));
+        assert!(attrs.compressed_bytes < attrs.uncompressed_bytes);
+        assert!(stats.total_compressed_bytes() < stats.total_uncompressed_bytes());
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Compact summary of the content of a batch, e.g. to index the batches of an ingest path
+//! or to observe it: the number of records, the time range they cover and the services
+//! that emitted them.
+//!
+//! [`BatchSummary::try_new`] computes the summary of an OTAP batch with Arrow kernels on its
+//! columns, without decoding it, and [`BatchSummary::from_request`] the one of a decoded
+//! request. The records are the spans, the log records or the data points, and their
+//! times are the start times of the spans and the times of the log records and the data
+//! points, the times left unset, 0, being ignored. The services are the values of the
+//! `service.name` resource attribute.
+
+use std::collections::BTreeSet;
+
+use arrow::array::{Array, ArrayRef, AsArray, Int64Array, RecordBatch, Scalar, StringArray};
+use arrow::compute::kernels::cmp::{eq, neq};
+use arrow::compute::{cast, filter, max, min};
+use arrow::datatypes::{DataType, Int64Type};
+
+use crate::ExportRequest;
+use crate::error::{self, ErrorContextExt, Result};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::metrics::v1::metric::Data;
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::schema::consts;
+
+/// Resource attribute naming the service that emitted the telemetry.
+const SERVICE_NAME: &str = "service.name";
+
+/// The payloads of the data points of the metrics.
+const DATA_POINTS: [ArrowPayloadType; 4] = [
+    ArrowPayloadType::NumberDataPoints,
+    ArrowPayloadType::SummaryDataPoints,
+    ArrowPayloadType::HistogramDataPoints,
+    ArrowPayloadType::ExpHistogramDataPoints,
+];
+
+/// Summary of a batch, see the module documentation.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BatchSummary {
+    /// Number of spans, log records or data points.
+    pub records: usize,
+    /// Earliest time of the records, in nanoseconds since the epoch.
+    pub min_time_unix_nano: Option<u64>,
+    /// Latest time of the records, in nanoseconds since the epoch.
+    pub max_time_unix_nano: Option<u64>,
+    /// Distinct services of the resources, sorted.
+    pub services: Vec<String>,
+}
+
+impl BatchSummary {
+    /// Computes the summary of the OTAP batch.
+    pub fn try_new(otap_batch: &OtapBatch) -> Result<Self> {
+        let (payload_types, time_column) = match otap_batch {
+            OtapBatch::Logs(_) => (&[ArrowPayloadType::Logs][..], consts::TIME_UNIX_NANO),
+            OtapBatch::Metrics(_) => (&DATA_POINTS[..], consts::TIME_UNIX_NANO),
+            OtapBatch::Traces(_) => (&[ArrowPayloadType::Spans][..], consts::START_TIME_UNIX_NANO),
+        };
+        let mut summary = Self::default();
+        for payload_type in payload_types {
+            let Some(rb) = otap_batch.get(*payload_type) else {
+                continue;
+            };
+            summary.records += rb.num_rows();
+            if let Some((min, max)) = time_range(rb, time_column).in_payload(*payload_type)? {
+                summary.add_time(min);
+                summary.add_time(max);
+            }
+        }
+        if let Some(rb) = otap_batch.get(ArrowPayloadType::ResourceAttrs) {
+            summary.services = service_names(rb).in_payload(ArrowPayloadType::ResourceAttrs)?;
+        }
+        Ok(summary)
+    }
+
+    /// Computes the summary of the decoded request.
+    #[must_use]
+    pub fn from_request(request: &ExportRequest) -> Self {
+        let mut summary = Self::default();
+        let mut services = BTreeSet::new();
+        let mut add_resource = |resource: Option<&Resource>| {
+            let values = resource
+                .into_iter()
+                .flat_map(|resource| &resource.attributes);
+            for attr in values.filter(|attr| attr.key == SERVICE_NAME) {
+                if let Some(Value::StringValue(name)) =
+                    attr.value.as_ref().and_then(|v| v.value.as_ref())
+                {
+                    let _ = services.insert(name.clone());
+                }
+            }
+        };
+        match request {
+            ExportRequest::Logs(request) => {
+                for rl in &request.resource_logs {
+                    add_resource(rl.resource.as_ref());
+                    for log in rl.scope_logs.iter().flat_map(|sl| &sl.log_records) {
+                        summary.add_record(log.time_unix_nano);
+                    }
+                }
+            }
+            ExportRequest::Metrics(request) => {
+                for rm in &request.resource_metrics {
+                    add_resource(rm.resource.as_ref());
+                    let metrics = rm.scope_metrics.iter().flat_map(|sm| &sm.metrics);
+                    for data in metrics.filter_map(|metric| metric.data.as_ref()) {
+                        let times: Vec<u64> = match data {
+                            Data::Gauge(g) => {
+                                g.data_points.iter().map(|dp| dp.time_unix_nano).collect()
+                            }
+                            Data::Sum(s) => {
+                                s.data_points.iter().map(|dp| dp.time_unix_nano).collect()
+                            }
+                            Data::Histogram(h) => {
+                                h.data_points.iter().map(|dp| dp.time_unix_nano).collect()
+                            }
+                            Data::ExponentialHistogram(h) => {
+                                h.data_points.iter().map(|dp| dp.time_unix_nano).collect()
+                            }
+                            Data::Summary(s) => {
+                                s.data_points.iter().map(|dp| dp.time_unix_nano).collect()
+                            }
+                        };
+                        for time in times {
+                            summary.add_record(time);
+                        }
+                    }
+                }
+            }
+            ExportRequest::Traces(request) => {
+                for rs in &request.resource_spans {
+                    add_resource(rs.resource.as_ref());
+                    for span in rs.scope_spans.iter().flat_map(|ss| &ss.spans) {
+                        summary.add_record(span.start_time_unix_nano);
+                    }
+                }
+            }
+        }
+        summary.services = services.into_iter().collect();
+        summary
+    }
+
+    fn add_record(&mut self, time_unix_nano: u64) {
+        self.records += 1;
+        if time_unix_nano != 0 {
+            self.add_time(time_unix_nano);
+        }
+    }
+
+    fn add_time(&mut self, time_unix_nano: u64) {
+        self.min_time_unix_nano = Some(
+            self.min_time_unix_nano
+                .map_or(time_unix_nano, |min| min.min(time_unix_nano)),
+        );
+        self.max_time_unix_nano = Some(
+            self.max_time_unix_nano
+                .map_or(time_unix_nano, |max| max.max(time_unix_nano)),
+        );
+    }
+}
+
+/// Casts the column to the given type, e.g. to read a timestamp column as integers or a
+/// dictionary encoded column as plain values.
+fn cast_column(rb: &RecordBatch, name: &str, data_type: &DataType) -> Result<Option<ArrayRef>> {
+    let Some(column) = rb.column_by_name(name) else {
+        return Ok(None);
+    };
+    cast(column, data_type).map(Some).map_err(|_| {
+        error::ColumnDataTypeMismatchSnafu {
+            name,
+            expect: data_type.clone(),
+            actual: column.data_type().clone(),
+        }
+        .build()
+    })
+}
+
+/// Returns the earliest and latest times of the column set in the record batch.
+fn time_range(rb: &RecordBatch, name: &str) -> Result<Option<(u64, u64)>> {
+    let Some(times) = cast_column(rb, name, &DataType::Int64)? else {
+        return Ok(None);
+    };
+    // safety: the arrays are of the same type and length
+    let set = neq(&times, &Scalar::new(Int64Array::from(vec![0]))).expect("comparable times");
+    let set = filter(&times, &set).expect("mask matches the times");
+    let set = set.as_primitive::<Int64Type>();
+    Ok(min(set)
+        .zip(max(set))
+        .map(|(min, max)| (min as u64, max as u64)))
+}
+
+/// Returns the distinct values of the `service.name` attributes, sorted.
+fn service_names(rb: &RecordBatch) -> Result<Vec<String>> {
+    let (Some(keys), Some(values)) = (
+        cast_column(rb, consts::ATTRIBUTE_KEY, &DataType::Utf8)?,
+        cast_column(rb, consts::ATTRIBUTE_STR, &DataType::Utf8)?,
+    ) else {
+        return Ok(Vec::new());
+    };
+    // safety: the arrays are of the same type and length
+    let mask =
+        eq(&keys, &Scalar::new(StringArray::from(vec![SERVICE_NAME]))).expect("comparable keys");
+    let names = filter(&values, &mask).expect("mask matches the values");
+    let names: BTreeSet<_> = names.as_string::<i32>().iter().flatten().collect();
+    Ok(names.into_iter().map(String::from).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::datagen::{DatagenConfig, Generator};
+    use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+
+    #[test]
+    fn test_batch_summary() {
+        let mut generator = Generator::new(DatagenConfig::default().with_services(3));
+
+        let request = generator.traces_request(8);
+        let summary = BatchSummary::try_new(&TracesProducer::new().produce(&request).unwrap());
+        let expected = BatchSummary::from_request(&ExportRequest::Traces(request));
+        assert_eq!(summary.unwrap(), expected);
+        assert!(expected.records > 0);
+        assert!(expected.min_time_unix_nano <= expected.max_time_unix_nano);
+        assert!(!expected.services.is_empty());
+
+        let request = generator.logs_request(32);
+        let summary = BatchSummary::try_new(&LogsProducer::new().produce(&request).unwrap());
+        let expected = BatchSummary::from_request(&ExportRequest::Logs(request));
+        assert_eq!(summary.unwrap(), expected);
+        assert_eq!(expected.records, 32);
+
+        let request = generator.metrics_request(32);
+        let summary = BatchSummary::try_new(&MetricsProducer::new().produce(&request).unwrap());
+        let expected = BatchSummary::from_request(&ExportRequest::Metrics(request));
+        assert_eq!(summary.unwrap(), expected);
+        assert!(expected.records >= 32);
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Grouping of the spans of several batches by trace, the building block of tail sampling.
+//!
+//! A tail sampler decides whether to keep a trace once all its spans had time to arrive,
+//! e.g. keeping the traces with an error or a slow span. [`TraceGroups`] collects, for every
+//! trace of the batches added to it, the aggregates such decisions are based on, read from
+//! the span columns of the OTAP batches without decoding them, or from decoded requests.
+//! The traces are handed back once their decision window has elapsed, together with the
+//! sequence numbers of the batches holding their spans.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, Fields};
+use snafu::OptionExt;
+
+use crate::arrays::{
+    ByteArrayAccessor, ColumnAccessor, DurationMillisArrayAccessor, Int32ArrayAccessor,
+    NullableArrayAccessor, StructColumnAccessor,
+};
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::trace::v1::status::StatusCode;
+use crate::schema::consts;
+
+/// Id of a trace.
+pub type TraceId = [u8; 16];
+
+/// Aggregates of the spans of a trace.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceAggregate {
+    /// Number of spans.
+    pub span_count: usize,
+    /// Number of spans with the `Error` status code.
+    pub error_count: usize,
+    /// Longest duration of the spans.
+    pub max_duration: Duration,
+    /// Sequence numbers of the batches holding spans of the trace, in order.
+    pub batches: Vec<u64>,
+}
+
+impl TraceAggregate {
+    fn add_span(&mut self, batch: u64, duration_nanos: i64, status_code: i32) {
+        self.span_count += 1;
+        if status_code == StatusCode::Error as i32 {
+            self.error_count += 1;
+        }
+        let duration = Duration::from_nanos(duration_nanos.max(0) as u64);
+        self.max_duration = self.max_duration.max(duration);
+        if self.batches.last() != Some(&batch) {
+            self.batches.push(batch);
+        }
+    }
+}
+
+/// Traces of the batches added so far whose decision window has not elapsed yet.
+///
+/// The window of a trace starts when its first span is added. The spans whose trace id is
+/// null or not 16 bytes long are not grouped.
+#[derive(Debug)]
+pub struct TraceGroups {
+    window: Duration,
+    next_batch: u64,
+    traces: HashMap<TraceId, TraceAggregate>,
+    /// Traces in the order their first span was added.
+    first_seen: VecDeque<(Instant, TraceId)>,
+}
+
+impl TraceGroups {
+    /// Creates empty groups handing the traces back `window` after their first span.
+    #[must_use]
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            next_batch: 0,
+            traces: HashMap::new(),
+            first_seen: VecDeque::new(),
+        }
+    }
+
+    /// Adds the spans of the OTAP batch, reading the `trace_id`, `duration_time_unix_nano`
+    /// and `status.code` columns of its `Spans` payload. Returns the sequence number of the
+    /// batch.
+    pub fn add_batch(&mut self, otap_batch: &OtapBatch, now: Instant) -> Result<u64> {
+        let batch = self.next_batch();
+        let Some(rb) = otap_batch.get(ArrowPayloadType::Spans) else {
+            return Ok(batch);
+        };
+        let trace_ids = ByteArrayAccessor::try_new_for_column(rb, consts::TRACE_ID)?;
+        let durations = DurationMillisArrayAccessor::try_new_for_column_opt(
+            rb,
+            consts::DURATION_TIME_UNIX_NANO,
+        )?;
+        let status = rb
+            .column_by_name(consts::STATUS)
+            .map(|column| {
+                column
+                    .as_struct_opt()
+                    .context(error::ColumnDataTypeMismatchSnafu {
+                        name: consts::STATUS,
+                        expect: DataType::Struct(Fields::empty()),
+                        actual: column.data_type().clone(),
+                    })
+            })
+            .transpose()?;
+        let status_codes = status
+            .map(|status| {
+                StructColumnAccessor::new(status)
+                    .accessor_column_op::<Int32ArrayAccessor<'_>>(consts::STATUS_CODE)
+            })
+            .transpose()?
+            .flatten();
+
+        for idx in 0..rb.num_rows() {
+            let Some(trace_id) = trace_ids
+                .value_at(idx)
+                .and_then(|id| TraceId::try_from(id).ok())
+            else {
+                continue;
+            };
+            let status_code = match status {
+                Some(status) if status.is_valid(idx) => status_codes.value_at_or_default(idx),
+                _ => 0,
+            };
+            // the duration column holds nanoseconds even though it is typed as milliseconds
+            let duration = durations.value_at_or_default(idx);
+            self.add_span(trace_id, now, batch, duration, status_code);
+        }
+        Ok(batch)
+    }
+
+    /// Adds the spans of the decoded request. Returns the sequence number of the request.
+    pub fn add_request(&mut self, request: &ExportTraceServiceRequest, now: Instant) -> u64 {
+        let batch = self.next_batch();
+        let spans = request
+            .resource_spans
+            .iter()
+            .flat_map(|resource_spans| &resource_spans.scope_spans)
+            .flat_map(|scope_spans| &scope_spans.spans);
+        for span in spans {
+            let Ok(trace_id) = TraceId::try_from(span.trace_id.as_slice()) else {
+                continue;
+            };
+            let duration = span
+                .end_time_unix_nano
+                .saturating_sub(span.start_time_unix_nano);
+            let status_code = span.status.as_ref().map_or(0, |status| status.code);
+            self.add_span(
+                trace_id,
+                now,
+                batch,
+                i64::try_from(duration).unwrap_or(i64::MAX),
+                status_code,
+            );
+        }
+        batch
+    }
+
+    /// Returns the aggregates of the trace, if its window has not been taken yet.
+    #[must_use]
+    pub fn get(&self, trace_id: &TraceId) -> Option<&TraceAggregate> {
+        self.traces.get(trace_id)
+    }
+
+    /// Returns the number of traces.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    /// Returns true if there are no traces.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    /// Removes the traces whose window has elapsed at `now` and returns them, in the order
+    /// their first span was added. The later spans of these traces start new groups.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<(TraceId, TraceAggregate)> {
+        let mut expired = Vec::new();
+        while let Some((first_seen, trace_id)) = self.first_seen.front() {
+            if now.saturating_duration_since(*first_seen) < self.window {
+                break;
+            }
+            if let Some(aggregate) = self.traces.remove(trace_id) {
+                expired.push((*trace_id, aggregate));
+            }
+            let _ = self.first_seen.pop_front();
+        }
+        expired
+    }
+
+    fn next_batch(&mut self) -> u64 {
+        let batch = self.next_batch;
+        self.next_batch += 1;
+        batch
+    }
+
+    fn add_span(
+        &mut self,
+        trace_id: TraceId,
+        now: Instant,
+        batch: u64,
+        duration_nanos: i64,
+        status_code: i32,
+    ) {
+        let aggregate = self.traces.entry(trace_id).or_insert_with(|| {
+            self.first_seen.push_back((now, trace_id));
+            TraceAggregate::default()
+        });
+        aggregate.add_span(batch, duration_nanos, status_code);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_trace_groups() {
+        let start = Instant::now();
+        let mut groups = TraceGroups::new(Duration::from_secs(10));
+        // two traces of four spans each
+        assert_eq!(groups.add_batch(&traces_batch(8, 1), start).unwrap(), 0);
+        assert_eq!(groups.len(), 2);
+
+        let span = |trace_id: u8, duration: u64, code| Span {
+            trace_id: vec![trace_id; 16],
+            start_time_unix_nano: 100,
+            end_time_unix_nano: 100 + duration,
+            status: Some(Status {
+                code,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![
+                        span(0, 50, StatusCode::Error as i32),
+                        span(2, 1, StatusCode::Ok as i32),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let later = start + Duration::from_secs(5);
+        assert_eq!(groups.add_request(&request, later), 1);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(
+            groups.get(&[0; 16]),
+            Some(&TraceAggregate {
+                span_count: 5,
+                error_count: 1,
+                max_duration: Duration::from_nanos(50),
+                batches: vec![0, 1],
+            })
+        );
+        assert_eq!(
+            groups.get(&[1; 16]),
+            Some(&TraceAggregate {
+                span_count: 4,
+                error_count: 0,
+                max_duration: Duration::from_nanos(7),
+                batches: vec![0],
+            })
+        );
+
+        assert!(
+            groups
+                .take_expired(start + Duration::from_secs(9))
+                .is_empty()
+        );
+        let expired = groups.take_expired(start + Duration::from_secs(12));
+        let ids: Vec<_> = expired.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![[0; 16], [1; 16]]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            groups.take_expired(later + Duration::from_secs(10)).len(),
+            1
+        );
+        assert!(groups.is_empty());
+    }
+}
@@ -14,11 +14,11 @@ use snafu::OptionExt;
 use crate::arrays::get_required_array;
 use crate::error::{self, Result};
 use crate::otlp::attributes::decoder::materialize_parent_id;
-use crate::schema::update_field_metadata;
 use crate::schema::{
     consts::{self, metadata},
     update_schema_metadata,
 };
+use crate::schema::{get_field_metadata, update_field_metadata};
 
 pub fn sort_by_parent_id(record_batch: &RecordBatch) -> Result<RecordBatch> {
     let parent_id_column = record_batch.column_by_name(consts::PARENT_ID);
@@ -68,6 +68,27 @@ pub fn sort_by_parent_id(record_batch: &RecordBatch) -> Result<RecordBatch> {
     Ok(result)
 }
 
+/// Returns the attributes with their parent ids made plain, or as they are when they have no
+/// parent ids or when these are already plain.
+pub(crate) fn materialize_parent_ids(record_batch: &RecordBatch) -> Result<RecordBatch> {
+    let Some(parent_ids) = record_batch.column_by_name(consts::PARENT_ID) else {
+        return Ok(record_batch.clone());
+    };
+    if get_field_metadata(
+        record_batch.schema_ref(),
+        consts::PARENT_ID,
+        metadata::COLUMN_ENCODING,
+    ) == Some(metadata::encodings::PLAIN)
+    {
+        return Ok(record_batch.clone());
+    }
+    match parent_ids.data_type() {
+        DataType::UInt16 => materialize_parent_id::<u16>(record_batch),
+        DataType::UInt32 => materialize_parent_id::<u32>(record_batch),
+        d => error::UnsupportedParentIdTypeSnafu { actual: d.clone() }.fail(),
+    }
+}
+
 pub fn remove_delta_encoding<T>(
     record_batch: &RecordBatch,
     column_name: &str,
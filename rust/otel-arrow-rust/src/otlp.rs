@@ -13,8 +13,28 @@
 // TODO write documentation for this crate
 #![allow(missing_docs)]
 
+pub mod attribute_schema;
 pub mod attributes;
+pub mod coalesce;
+pub mod context;
+pub mod enums;
+pub mod event_logs;
+pub mod events;
+pub mod filter;
+pub mod interner;
+pub mod json;
 pub mod logs;
 pub mod metrics;
+pub mod options;
+#[cfg(feature = "otel-rust")]
+pub mod otel_rust;
+pub mod pool;
+pub mod report;
+#[cfg(feature = "runtime")]
+pub mod task;
+pub mod traces;
+pub mod visitor;
 
-mod common;
+pub(crate) mod common;
+mod extra_columns;
+mod timestamps;
@@ -0,0 +1,322 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Optional validation of the types of the decoded attributes against a user supplied
+//! schema, e.g. "`http.response.status_code` must be an int", for data quality pipelines.
+//!
+//! The check runs on decoded requests and reports the violations, it never fails nor
+//! modifies the request. Attributes whose key is not in the schema are not checked.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use crate::ExportRequest;
+use crate::otlp::attributes::store::AttributeValueType;
+use crate::proto::opentelemetry::common::v1::KeyValue;
+use crate::proto::opentelemetry::metrics::v1::metric::Data;
+
+/// Kind of entity carrying an attribute.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AttributeLocation {
+    /// Attribute of a resource.
+    Resource,
+    /// Attribute of an instrumentation scope.
+    Scope,
+    /// Attribute of a log record.
+    LogRecord,
+    /// Attribute of a span.
+    Span,
+    /// Attribute of a span event.
+    SpanEvent,
+    /// Attribute of a span link.
+    SpanLink,
+    /// Attribute of a metric data point.
+    DataPoint,
+    /// Filtered attribute of an exemplar.
+    Exemplar,
+}
+
+/// Attribute whose value doesn't have the type required by the schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaViolation {
+    /// Entity carrying the attribute.
+    pub location: AttributeLocation,
+    /// Key of the attribute.
+    pub key: String,
+    /// Type required by the schema.
+    pub expected: AttributeValueType,
+    /// Type of the attribute value.
+    pub actual: AttributeValueType,
+}
+
+impl Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} attribute {} is {:?}, expected {:?}",
+            self.location, self.key, self.actual, self.expected
+        )
+    }
+}
+
+/// Types required for the values of attributes, by key.
+#[derive(Clone, Debug, Default)]
+pub struct AttributeSchema {
+    types: HashMap<String, AttributeValueType>,
+}
+
+impl AttributeSchema {
+    /// Creates an empty schema.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a schema with the types of common semantic convention attributes.
+    #[must_use]
+    pub fn semconv() -> Self {
+        [
+            ("service.name", AttributeValueType::Str),
+            ("service.version", AttributeValueType::Str),
+            ("process.pid", AttributeValueType::Int),
+            ("http.request.method", AttributeValueType::Str),
+            ("http.response.status_code", AttributeValueType::Int),
+            ("http.status_code", AttributeValueType::Int),
+            ("http.route", AttributeValueType::Str),
+            ("url.full", AttributeValueType::Str),
+            ("server.address", AttributeValueType::Str),
+            ("server.port", AttributeValueType::Int),
+            ("client.port", AttributeValueType::Int),
+            ("rpc.grpc.status_code", AttributeValueType::Int),
+            ("db.system", AttributeValueType::Str),
+            ("error.type", AttributeValueType::Str),
+        ]
+        .into_iter()
+        .fold(Self::new(), |schema, (key, value_type)| {
+            schema.with_attribute(key, value_type)
+        })
+    }
+
+    /// Requires the values of the attributes with the given key to have the given type.
+    #[must_use]
+    pub fn with_attribute(
+        mut self,
+        key: impl Into<String>,
+        value_type: AttributeValueType,
+    ) -> Self {
+        let _ = self.types.insert(key.into(), value_type);
+        self
+    }
+
+    /// Checks the attributes carried by an entity of the given kind.
+    #[must_use]
+    pub fn check(&self, location: AttributeLocation, attrs: &[KeyValue]) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        self.check_into(location, attrs, &mut violations);
+        violations
+    }
+
+    /// Checks all the attributes of the request.
+    #[must_use]
+    pub fn check_request(&self, request: &ExportRequest) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        let mut check = |location, attrs: &[KeyValue]| {
+            self.check_into(location, attrs, &mut violations);
+        };
+
+        match request {
+            ExportRequest::Logs(logs) => {
+                for resource_logs in &logs.resource_logs {
+                    if let Some(resource) = &resource_logs.resource {
+                        check(AttributeLocation::Resource, &resource.attributes);
+                    }
+                    for scope_logs in &resource_logs.scope_logs {
+                        if let Some(scope) = &scope_logs.scope {
+                            check(AttributeLocation::Scope, &scope.attributes);
+                        }
+                        for log_record in &scope_logs.log_records {
+                            check(AttributeLocation::LogRecord, &log_record.attributes);
+                        }
+                    }
+                }
+            }
+            ExportRequest::Traces(traces) => {
+                for resource_spans in &traces.resource_spans {
+                    if let Some(resource) = &resource_spans.resource {
+                        check(AttributeLocation::Resource, &resource.attributes);
+                    }
+                    for scope_spans in &resource_spans.scope_spans {
+                        if let Some(scope) = &scope_spans.scope {
+                            check(AttributeLocation::Scope, &scope.attributes);
+                        }
+                        for span in &scope_spans.spans {
+                            check(AttributeLocation::Span, &span.attributes);
+                            for event in &span.events {
+                                check(AttributeLocation::SpanEvent, &event.attributes);
+                            }
+                            for link in &span.links {
+                                check(AttributeLocation::SpanLink, &link.attributes);
+                            }
+                        }
+                    }
+                }
+            }
+            ExportRequest::Metrics(metrics) => {
+                for resource_metrics in &metrics.resource_metrics {
+                    if let Some(resource) = &resource_metrics.resource {
+                        check(AttributeLocation::Resource, &resource.attributes);
+                    }
+                    for scope_metrics in &resource_metrics.scope_metrics {
+                        if let Some(scope) = &scope_metrics.scope {
+                            check(AttributeLocation::Scope, &scope.attributes);
+                        }
+                        for metric in &scope_metrics.metrics {
+                            check_metric_data(metric.data.as_ref(), &mut check);
+                        }
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+
+    fn check_into(
+        &self,
+        location: AttributeLocation,
+        attrs: &[KeyValue],
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        for kv in attrs {
+            let Some(expected) = self.types.get(&kv.key) else {
+                continue;
+            };
+            let actual = AttributeValueType::from(kv.value.as_ref().and_then(|v| v.value.as_ref()));
+            if actual != *expected {
+                violations.push(SchemaViolation {
+                    location,
+                    key: kv.key.clone(),
+                    expected: *expected,
+                    actual,
+                });
+            }
+        }
+    }
+}
+
+fn check_metric_data(data: Option<&Data>, check: &mut impl FnMut(AttributeLocation, &[KeyValue])) {
+    match data {
+        Some(Data::Gauge(gauge)) => {
+            for dp in &gauge.data_points {
+                check(AttributeLocation::DataPoint, &dp.attributes);
+                for exemplar in &dp.exemplars {
+                    check(AttributeLocation::Exemplar, &exemplar.filtered_attributes);
+                }
+            }
+        }
+        Some(Data::Sum(sum)) => {
+            for dp in &sum.data_points {
+                check(AttributeLocation::DataPoint, &dp.attributes);
+                for exemplar in &dp.exemplars {
+                    check(AttributeLocation::Exemplar, &exemplar.filtered_attributes);
+                }
+            }
+        }
+        Some(Data::Histogram(histogram)) => {
+            for dp in &histogram.data_points {
+                check(AttributeLocation::DataPoint, &dp.attributes);
+                for exemplar in &dp.exemplars {
+                    check(AttributeLocation::Exemplar, &exemplar.filtered_attributes);
+                }
+            }
+        }
+        Some(Data::ExponentialHistogram(histogram)) => {
+            for dp in &histogram.data_points {
+                check(AttributeLocation::DataPoint, &dp.attributes);
+                for exemplar in &dp.exemplars {
+                    check(AttributeLocation::Exemplar, &exemplar.filtered_attributes);
+                }
+            }
+        }
+        Some(Data::Summary(summary)) => {
+            for dp in &summary.data_points {
+                check(AttributeLocation::DataPoint, &dp.attributes);
+            }
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+    use crate::proto::opentelemetry::common::v1::AnyValue;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::resource::v1::Resource;
+    use crate::proto::opentelemetry::trace::v1::span::Event;
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+    fn attr(key: &str, value: Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn test_check_request() {
+        let request = ExportRequest::Traces(ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![attr("service.name", Value::StringValue("svc".into()))],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        attributes: vec![
+                            attr(
+                                "http.response.status_code",
+                                Value::StringValue("200".into()),
+                            ),
+                            attr("custom", Value::BoolValue(true)),
+                        ],
+                        events: vec![Event {
+                            attributes: vec![KeyValue {
+                                key: "server.port".into(),
+                                value: None,
+                            }],
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        });
+
+        let violations = AttributeSchema::semconv()
+            .with_attribute("custom", AttributeValueType::Bool)
+            .check_request(&request);
+        assert_eq!(violations, vec![
+            SchemaViolation {
+                location: AttributeLocation::Span,
+                key: "http.response.status_code".into(),
+                expected: AttributeValueType::Int,
+                actual: AttributeValueType::Str,
+            },
+            SchemaViolation {
+                location: AttributeLocation::SpanEvent,
+                key: "server.port".into(),
+                expected: AttributeValueType::Int,
+                actual: AttributeValueType::Empty,
+            },
+        ]);
+        assert_eq!(
+            violations[0].to_string(),
+            "Span attribute http.response.status_code is Str, expected Int"
+        );
+    }
+}
@@ -10,7 +10,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub mod cbor;
+mod coercion;
+#[cfg(feature = "internal")]
 pub mod decoder;
+#[cfg(not(feature = "internal"))]
+#[allow(dead_code)]
+pub(crate) mod decoder;
+pub(crate) mod id_map;
 mod parent_id;
 pub mod store;
+mod stored;
+
+pub use crate::value::cbor;
+
+pub(crate) use parent_id::add_delta;
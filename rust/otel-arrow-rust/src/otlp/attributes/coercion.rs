@@ -0,0 +1,634 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lookup of attribute values that are not stored in the column matching their declared
+//! type, and conversion of those values to the declared type.
+
+use crate::arrays::{
+    ByteArrayAccessor, ColumnAccessor, Int64ArrayAccessor, StringArrayAccessor, get_bool_array_opt,
+    get_f64_array_opt, get_required_array,
+};
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::schema::consts;
+use crate::value::{AttributeValueType, default_value};
+use arrow::array::{Array, ArrayRef, AsArray, BooleanArray, RecordBatch, Scalar, UInt8Array};
+use arrow::compute::kernels::boolean::{and, and_not};
+use arrow::compute::kernels::cmp::eq;
+use arrow::compute::{cast, filter, is_not_null, is_null};
+use arrow::datatypes::{DataType, Float64Type, Int64Type};
+use std::vec;
+
+/// Returns the attribute value type of the values stored in an array of the given type.
+fn stored_value_type(data_type: &DataType) -> Option<AttributeValueType> {
+    match data_type {
+        DataType::Dictionary(_, value_type) => stored_value_type(value_type),
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => Some(AttributeValueType::Str),
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => Some(AttributeValueType::Int),
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+            Some(AttributeValueType::Double)
+        }
+        DataType::Boolean => Some(AttributeValueType::Bool),
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => Some(AttributeValueType::Bytes),
+        _ => None,
+    }
+}
+
+/// Returns true if the array can be read with the accessor used for the given type.
+fn is_expected_type(data_type: &DataType, value_type: AttributeValueType) -> bool {
+    let unwrapped = match data_type {
+        DataType::Dictionary(_, value_type) => value_type.as_ref(),
+        data_type => data_type,
+    };
+    match value_type {
+        AttributeValueType::Str => *unwrapped == DataType::Utf8,
+        AttributeValueType::Int => *unwrapped == DataType::Int64,
+        AttributeValueType::Bytes => {
+            matches!(unwrapped, DataType::Binary | DataType::FixedSizeBinary(_))
+        }
+        // the double and bool accessors don't support dictionaries
+        AttributeValueType::Double => *data_type == DataType::Float64,
+        AttributeValueType::Bool => *data_type == DataType::Boolean,
+        _ => false,
+    }
+}
+
+/// Value column whose data type doesn't match the attribute type it was named after. The
+/// values are cast to the arrow type used for the type of value they actually store.
+struct MismatchedColumn {
+    stored_type: AttributeValueType,
+    values: ArrayRef,
+}
+
+impl MismatchedColumn {
+    fn try_new(name: &str, column: &ArrayRef, stored_type: AttributeValueType) -> Result<Self> {
+        let target_type = bulk_type(stored_type);
+        let values = cast(column, &target_type).map_err(|_| {
+            error::ColumnDataTypeMismatchSnafu {
+                name,
+                expect: target_type.clone(),
+                actual: column.data_type().clone(),
+            }
+            .build()
+        })?;
+        Ok(Self {
+            stored_type,
+            values,
+        })
+    }
+}
+
+/// The scalar attribute value types, in the order of the columns of [`ValueColumns`].
+const SCALAR_TYPES: [AttributeValueType; 5] = [
+    AttributeValueType::Str,
+    AttributeValueType::Int,
+    AttributeValueType::Double,
+    AttributeValueType::Bool,
+    AttributeValueType::Bytes,
+];
+
+/// Returns the arrow type the values of a scalar type are converted from in bulk.
+fn bulk_type(value_type: AttributeValueType) -> DataType {
+    match value_type {
+        AttributeValueType::Str => DataType::Utf8,
+        AttributeValueType::Int => DataType::Int64,
+        AttributeValueType::Double => DataType::Float64,
+        AttributeValueType::Bool => DataType::Boolean,
+        _ => DataType::Binary,
+    }
+}
+
+/// Converts the values of an array of the bulk type of `value_type`, see [`bulk_type`].
+fn bulk_values(value_type: AttributeValueType, values: &ArrayRef) -> Vec<Option<Value>> {
+    match value_type {
+        AttributeValueType::Str => values
+            .as_string::<i32>()
+            .iter()
+            .map(|v| v.map(|v| Value::StringValue(v.to_string())))
+            .collect(),
+        AttributeValueType::Int => values
+            .as_primitive::<Int64Type>()
+            .iter()
+            .map(|v| v.map(Value::IntValue))
+            .collect(),
+        AttributeValueType::Double => values
+            .as_primitive::<Float64Type>()
+            .iter()
+            .map(|v| v.map(Value::DoubleValue))
+            .collect(),
+        AttributeValueType::Bool => values
+            .as_boolean()
+            .iter()
+            .map(|v| v.map(Value::BoolValue))
+            .collect(),
+        _ => values
+            .as_binary::<i32>()
+            .iter()
+            .map(|v| v.map(|v| Value::BytesValue(v.to_vec())))
+            .collect(),
+    }
+}
+
+/// Fails with the error of the accessor of `column_type`, for a column whose data type
+/// stores no scalar value type.
+fn check_accessor(
+    rb: &RecordBatch,
+    name: &str,
+    column: &ArrayRef,
+    column_type: AttributeValueType,
+) -> Result<()> {
+    match column_type {
+        AttributeValueType::Str => StringArrayAccessor::try_new(column).map(drop),
+        AttributeValueType::Int => Int64ArrayAccessor::try_new(column).map(drop),
+        AttributeValueType::Double => get_f64_array_opt(rb, name).map(drop),
+        AttributeValueType::Bool => get_bool_array_opt(rb, name).map(drop),
+        _ => ByteArrayAccessor::try_new(column).map(drop),
+    }
+}
+
+/// Where the value of an attribute was found, see [`TypedValues::next`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ValueSource {
+    /// The column named after the declared type of the attribute, whatever its data type.
+    DeclaredColumn,
+    /// The column of another type, the column of the declared type having no value for
+    /// the row.
+    OtherColumn,
+}
+
+/// A value of a row, with the type it was stored as and the column it was found in.
+type TypedValue = (AttributeValueType, Value, ValueSource);
+
+/// The scalar value columns of an attributes record batch, in the order of
+/// [`SCALAR_TYPES`].
+pub(crate) struct ValueColumns<'a> {
+    /// The columns that have the expected data type of their type.
+    columns: [Option<(&'static str, &'a ArrayRef)>; 5],
+    /// The columns whose data type doesn't match the type they are named after.
+    mismatched: [Option<MismatchedColumn>; 5],
+}
+
+impl<'a> ValueColumns<'a> {
+    pub(crate) fn try_new(rb: &'a RecordBatch) -> Result<Self> {
+        // the string column is required
+        let _ = get_required_array(rb, consts::ATTRIBUTE_STR)?;
+        let mut columns: [Option<(&'static str, &'a ArrayRef)>; 5] = Default::default();
+        let mut mismatched: [Option<MismatchedColumn>; 5] = Default::default();
+        let names = [
+            consts::ATTRIBUTE_STR,
+            consts::ATTRIBUTE_INT,
+            consts::ATTRIBUTE_DOUBLE,
+            consts::ATTRIBUTE_BOOL,
+            consts::ATTRIBUTE_BYTES,
+        ];
+        for (slot, (name, column_type)) in names.into_iter().zip(SCALAR_TYPES).enumerate() {
+            let Some(column) = rb.column_by_name(name) else {
+                continue;
+            };
+            let data_type = column.data_type();
+            if is_expected_type(data_type, column_type) {
+                columns[slot] = Some((name, column));
+                continue;
+            }
+            match stored_value_type(data_type) {
+                Some(stored_type) => {
+                    mismatched[slot] = Some(MismatchedColumn::try_new(name, column, stored_type)?);
+                }
+                None => check_accessor(rb, name, column, column_type)?,
+            }
+        }
+        Ok(Self {
+            columns,
+            mismatched,
+        })
+    }
+
+    /// Converts the values of the scalar types in bulk, one type at a time: the rows of
+    /// the type are selected from `value_types` with a filter, and the column of the type
+    /// is converted for all of them at once. The rows that have no value in the column of
+    /// their type are then looked up in bulk too, in the columns of the other types if
+    /// `other_columns` is set, see [`Self::fallback_values`]. The values are read in the
+    /// order of the rows with [`TypedValues::next`].
+    pub(crate) fn typed_values(
+        &self,
+        value_types: &UInt8Array,
+        other_columns: bool,
+    ) -> Result<TypedValues> {
+        let mut values: [vec::IntoIter<Option<TypedValue>>; 5] = Default::default();
+        for (slot, value_type) in SCALAR_TYPES.into_iter().enumerate() {
+            let type_id = Scalar::new(UInt8Array::from(vec![value_type as u8]));
+            // safety: u8 arrays can be compared
+            let mask = eq(value_types, &type_id).expect("u8 arrays can be compared");
+            let rows = mask.true_count();
+            if rows == 0 {
+                continue;
+            }
+            let (mut type_values, missing) = match self.columns[slot] {
+                Some((name, column)) => {
+                    // safety: the mask has a row for every row of the column
+                    let selected = filter(column, &mask).expect("mask matches the column");
+                    let selected = cast_to(name, &selected, column, value_type)?;
+                    let type_values: Vec<_> = bulk_values(value_type, &selected)
+                        .into_iter()
+                        .map(|value| value.map(|v| (value_type, v, ValueSource::DeclaredColumn)))
+                        .collect();
+                    (
+                        type_values,
+                        is_null(&selected).expect("null mask of an array"),
+                    )
+                }
+                None => (vec![None; rows], BooleanArray::from(vec![true; rows])),
+            };
+            if missing.true_count() > 0 {
+                self.fallback_values(slot, &mask, missing, other_columns, &mut type_values)?;
+            }
+            values[slot] = type_values.into_iter();
+        }
+        Ok(TypedValues { values })
+    }
+
+    /// Fills the `missing` values of the rows of the type of `slot`, selected by `mask`,
+    /// from the column named after the type if it has another data type, then from the
+    /// columns of the other types if `other_columns` is set: for every column, the missing
+    /// rows having a value in it are selected with a filter and converted at once.
+    fn fallback_values(
+        &self,
+        slot: usize,
+        mask: &BooleanArray,
+        mut missing: BooleanArray,
+        other_columns: bool,
+        type_values: &mut [Option<TypedValue>],
+    ) -> Result<()> {
+        let others = (0..SCALAR_TYPES.len()).filter(|other| other_columns && *other != slot);
+        let declared = self.mismatched[slot]
+            .as_ref()
+            .map(|column| (column, ValueSource::DeclaredColumn));
+        let candidates = declared
+            .into_iter()
+            .map(|(column, source)| (None, &column.values, column.stored_type, source))
+            .chain(others.clone().filter_map(|other| {
+                let (name, column) = self.columns[other]?;
+                Some((
+                    Some(name),
+                    column,
+                    SCALAR_TYPES[other],
+                    ValueSource::OtherColumn,
+                ))
+            }))
+            .chain(others.filter_map(|other| {
+                let column = self.mismatched[other].as_ref()?;
+                Some((
+                    None,
+                    &column.values,
+                    column.stored_type,
+                    ValueSource::OtherColumn,
+                ))
+            }));
+        for (name, column, stored_type, source) in candidates {
+            if missing.true_count() == 0 {
+                break;
+            }
+            // safety: the masks have a row for every row of the column or of the selection
+            let selected = filter(column, mask).expect("mask matches the column");
+            let found = and(
+                &missing,
+                &is_not_null(&selected).expect("null mask of an array"),
+            )
+            .expect("masks of the same length");
+            if found.true_count() == 0 {
+                continue;
+            }
+            let found_values = filter(&selected, &found).expect("mask matches the selection");
+            let found_values = match name {
+                Some(name) => cast_to(name, &found_values, column, stored_type)?,
+                None => found_values,
+            };
+            for (pos, value) in found
+                .values()
+                .set_indices()
+                .zip(bulk_values(stored_type, &found_values))
+            {
+                type_values[pos] = value.map(|v| (stored_type, v, source));
+            }
+            missing = and_not(&missing, &found).expect("masks of the same length");
+        }
+        Ok(())
+    }
+}
+
+/// Casts the values selected from a column of the expected data type of `value_type` to
+/// the bulk type of `value_type`, e.g. the values of a dictionary.
+fn cast_to(
+    name: &str,
+    selected: &ArrayRef,
+    column: &ArrayRef,
+    value_type: AttributeValueType,
+) -> Result<ArrayRef> {
+    let target_type = bulk_type(value_type);
+    if *selected.data_type() == target_type {
+        return Ok(selected.clone());
+    }
+    cast(selected, &target_type).map_err(|_| {
+        error::ColumnDataTypeMismatchSnafu {
+            name,
+            expect: target_type.clone(),
+            actual: column.data_type().clone(),
+        }
+        .build()
+    })
+}
+
+/// Values of the scalar attribute types converted in bulk, see
+/// [`ValueColumns::typed_values`].
+pub(crate) struct TypedValues {
+    /// The values of the rows of every type, in the order of [`SCALAR_TYPES`].
+    values: [vec::IntoIter<Option<TypedValue>>; 5],
+}
+
+impl TypedValues {
+    /// Returns the value of the next row of the scalar type `value_type`, with the type it
+    /// was stored as and the column it was found in. The rows of a type must be read in
+    /// order. The rows that have no value in any column get the default value of the type.
+    pub(crate) fn next(&mut self, value_type: AttributeValueType) -> TypedValue {
+        SCALAR_TYPES
+            .iter()
+            .position(|t| *t == value_type)
+            .and_then(|slot| self.values[slot].next().flatten())
+            .unwrap_or_else(|| {
+                // safety: default_value returns a value for all the scalar types
+                (
+                    value_type,
+                    default_value(value_type).expect("scalar value type"),
+                    ValueSource::DeclaredColumn,
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{StringArray, UInt8Array, UInt16Array};
+    use arrow::datatypes::{Field, Schema};
+
+    use crate::otlp::attributes::store::Attribute16Store;
+    use crate::otlp::options::{CoercionAction, CoercionPolicy, DecoderOptions};
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+
+    fn attrs_batch() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                // the int column was written with a string data type
+                Field::new(consts::ATTRIBUTE_INT, DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 1, 1])),
+                Arc::new(UInt8Array::from_iter_values([
+                    AttributeValueType::Int as u8,
+                    AttributeValueType::Int as u8,
+                    AttributeValueType::Str as u8,
+                ])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(StringArray::from(vec![None, Some("x"), Some("s")])),
+                Arc::new(StringArray::from(vec![Some("42"), None, None])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn attr(key: &str, value: Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn test_coercion_policy() {
+        let rb = attrs_batch();
+        assert!(Attribute16Store::try_from(&rb).is_err());
+
+        let options = DecoderOptions::default()
+            .with_attribute_coercion(CoercionPolicy::new(CoercionAction::Coerce));
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(&[attr("a", Value::IntValue(42))][..])
+        );
+        // "x" can't be converted to an int, it is kept as a string
+        assert_eq!(
+            store.attribute_by_id(1),
+            Some(
+                &[
+                    attr("b", Value::StringValue("x".into())),
+                    attr("c", Value::StringValue("s".into())),
+                ][..]
+            )
+        );
+
+        let options = DecoderOptions::default().with_attribute_coercion(
+            CoercionPolicy::new(CoercionAction::Skip).with_key_action("a", CoercionAction::Coerce),
+        );
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(&[attr("a", Value::IntValue(42))][..])
+        );
+        assert_eq!(
+            store.attribute_by_id(1),
+            Some(&[attr("c", Value::StringValue("s".into()))][..])
+        );
+    }
+
+    #[test]
+    fn test_coercion_policy_with_conversions() {
+        // the actions apply first, the conversions then apply to the values kept
+        let rb = attrs_batch();
+        let options = DecoderOptions::default().with_attribute_coercion(
+            CoercionPolicy::new(CoercionAction::Skip)
+                .with_key_action("a", CoercionAction::Coerce)
+                .with_conversion(AttributeValueType::Int, AttributeValueType::Double)
+                .with_key_type("b", AttributeValueType::Str)
+                .with_key_type("c", AttributeValueType::Bytes),
+        );
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        // the value of a is coerced to its declared type, then converted
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(&[attr("a", Value::DoubleValue(42.0))][..])
+        );
+        // the value of b is skipped before its key conversion
+        assert_eq!(
+            store.attribute_by_id(1),
+            Some(&[attr("c", Value::BytesValue(b"s".to_vec()))][..])
+        );
+    }
+
+    #[test]
+    fn test_value_in_other_column() {
+        // the int column has the expected data type, the value of "a" was written in the
+        // string column
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0])),
+                Arc::new(UInt8Array::from_iter_values([
+                    AttributeValueType::Int as u8,
+                    AttributeValueType::Int as u8,
+                ])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+                Arc::new(StringArray::from(vec![Some("42"), None])),
+                Arc::new(arrow::array::Int64Array::from(vec![None, Some(1)])),
+            ],
+        )
+        .unwrap();
+
+        // the default action doesn't look up the other columns, "a" gets the default value
+        let store = Attribute16Store::try_from(&rb).unwrap();
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(&[attr("a", Value::IntValue(0)), attr("b", Value::IntValue(1))][..])
+        );
+
+        let options = DecoderOptions::default()
+            .with_attribute_coercion(CoercionPolicy::new(CoercionAction::Coerce));
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(
+                &[
+                    attr("a", Value::IntValue(42)),
+                    attr("b", Value::IntValue(1))
+                ][..]
+            )
+        );
+
+        let options = DecoderOptions::default()
+            .with_attribute_coercion(CoercionPolicy::new(CoercionAction::Skip));
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(&[attr("b", Value::IntValue(1))][..])
+        );
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let rb = attrs_batch();
+        let value_arrs = ValueColumns::try_new(&rb).unwrap();
+        let value_types = rb
+            .column_by_name(consts::ATTRIBUTE_TYPE)
+            .unwrap()
+            .as_primitive::<arrow::datatypes::UInt8Type>();
+        let mut typed_values = value_arrs.typed_values(value_types, true).unwrap();
+        // the int column has a string data type, its values are converted as strings, and
+        // the rows without value in it are looked up in the string column
+        assert_eq!(
+            typed_values.next(AttributeValueType::Int),
+            (
+                AttributeValueType::Str,
+                Value::StringValue("42".into()),
+                ValueSource::DeclaredColumn
+            )
+        );
+        assert_eq!(
+            typed_values.next(AttributeValueType::Int),
+            (
+                AttributeValueType::Str,
+                Value::StringValue("x".into()),
+                ValueSource::OtherColumn
+            )
+        );
+        assert_eq!(
+            typed_values.next(AttributeValueType::Str),
+            (
+                AttributeValueType::Str,
+                Value::StringValue("s".into()),
+                ValueSource::DeclaredColumn
+            )
+        );
+
+        // the values of a type column are converted for the rows of the type only
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(
+                    consts::ATTRIBUTE_STR,
+                    DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8)),
+                    true,
+                ),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(UInt8Array::from_iter_values([
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Int as u8,
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Int as u8,
+                ])),
+                Arc::new(
+                    vec![Some("a"), None, Some("b"), None]
+                        .into_iter()
+                        .collect::<arrow::array::DictionaryArray<arrow::datatypes::UInt8Type>>(),
+                ),
+                Arc::new(arrow::array::Int64Array::from(vec![
+                    None,
+                    Some(1),
+                    None,
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+        let value_arrs = ValueColumns::try_new(&rb).unwrap();
+        let value_types = rb
+            .column_by_name(consts::ATTRIBUTE_TYPE)
+            .unwrap()
+            .as_primitive::<arrow::datatypes::UInt8Type>();
+        let mut typed_values = value_arrs.typed_values(value_types, true).unwrap();
+        let values: Vec<_> = (0..rb.num_rows())
+            .map(|idx| {
+                let value_type = AttributeValueType::try_from(value_types.value(idx)).unwrap();
+                typed_values.next(value_type)
+            })
+            .collect();
+        let declared = ValueSource::DeclaredColumn;
+        assert_eq!(values, vec![
+            (
+                AttributeValueType::Str,
+                Value::StringValue("a".into()),
+                declared
+            ),
+            (AttributeValueType::Int, Value::IntValue(1), declared),
+            (
+                AttributeValueType::Str,
+                Value::StringValue("b".into()),
+                declared
+            ),
+            (AttributeValueType::Int, Value::IntValue(0), declared),
+        ]);
+    }
+}
@@ -12,7 +12,6 @@
 
 // https://github.com/open-telemetry/otel-arrow/blob/985aa1500a012859cec44855e187eacf46eda7c8/pkg/otel/common/arrow/attributes.go#L40
 
-use std::ops::AddAssign;
 use std::sync::Arc;
 
 use arrow::array::{
@@ -23,8 +22,8 @@ use arrow::datatypes::{DataType, UInt8Type, UInt16Type};
 use snafu::{OptionExt, ResultExt};
 
 use crate::arrays::{NullableArrayAccessor, get_u8_array};
-use crate::error::{self, Result};
-use crate::otlp::attributes::parent_id::ParentId;
+use crate::error::{self, ErrorContext, ErrorContextExt, Result};
+use crate::otlp::attributes::parent_id::{ParentId, add_delta};
 use crate::otlp::attributes::store::AttributeValueType;
 use crate::proto::opentelemetry::common::v1::any_value;
 use crate::schema::{
@@ -34,18 +33,28 @@ use crate::schema::{
 
 pub type Attrs16ParentIdDecoder = AttrsParentIdDecoder<u16>;
 pub type Attrs32ParentIdDecoder = AttrsParentIdDecoder<u32>;
+pub type Attrs64ParentIdDecoder = AttrsParentIdDecoder<u64>;
 
-// AttrsParentIdDecoder implements parent_id decoding for attribute
-// sets.  The parent_id in this case is the entity which refers to the
-// set of attributes (e.g., a resource, a scope, a metric) contained
-// in a RecordBatch.
-//
-// Phase 1 note: there were several experimental encoding schemes
-// tested.  Two schemes named "ParentIdDeltaEncoding",
-// "ParentIdNoEncoding" have been removed.
+/// AttrsParentIdDecoder implements parent_id decoding for attribute
+/// sets.  The parent_id in this case is the entity which refers to the
+/// set of attributes (e.g., a resource, a scope, a metric) contained
+/// in a RecordBatch.
+///
+/// The Go producer sorts the attributes by type, key and value, and delta encodes the
+/// parent ids within the groups of consecutive rows sharing the same key and value. The
+/// decoder must be fed every row of the batch in order: a row whose key or value differs
+/// from the previous row starts a new group, and its parent id is not delta encoded. Like
+/// in Go, empty, map and slice values never belong to the same group as the previous row,
+/// and rows without a value must be passed to [`Self::reset`]. The group semantics follow
+/// `AttrsParentIDDecoder.Decode` and `carrow.Equal` of the Go implementation, and are only
+/// covered by the unit tests below, not by batches produced by the Go encoder.
+///
+/// Phase 1 note: there were several experimental encoding schemes
+/// tested.  Two schemes named "ParentIdDeltaEncoding",
+/// "ParentIdNoEncoding" have been removed.
 pub struct AttrsParentIdDecoder<T> {
     prev_parent_id: T,
-    prev_key: Option<String>,
+    prev_key: Option<Arc<str>>,
     prev_value: Option<any_value::Value>,
 }
 
@@ -66,18 +75,69 @@ impl<T> AttrsParentIdDecoder<T>
 where
     T: ParentId,
 {
-    pub fn decode(&mut self, delta_or_parent_id: T, key: &str, value: &any_value::Value) -> T {
-        if self.prev_key.as_deref() == Some(key) && self.prev_value.as_ref() == Some(value) {
-            let parent_id = self.prev_parent_id.add(delta_or_parent_id);
+    /// Returns the parent id of a row, given the value of its parent id column. Fails if
+    /// the delta encoded parent id overflows the id type.
+    pub fn decode(
+        &mut self,
+        delta_or_parent_id: T,
+        key: &str,
+        value: &any_value::Value,
+    ) -> Result<T> {
+        self.decode_with(delta_or_parent_id, key, value, || Arc::from(key))
+    }
+
+    /// Same as [`Self::decode`], for a key interned with the
+    /// [`StringInterner`](crate::otlp::interner::StringInterner) of the session, which
+    /// avoids copying the key whenever it changes.
+    pub(crate) fn decode_interned(
+        &mut self,
+        delta_or_parent_id: T,
+        key: &Arc<str>,
+        value: &any_value::Value,
+    ) -> Result<T> {
+        self.decode_with(delta_or_parent_id, key, value, || key.clone())
+    }
+
+    fn decode_with(
+        &mut self,
+        delta_or_parent_id: T,
+        key: &str,
+        value: &any_value::Value,
+        owned_key: impl FnOnce() -> Arc<str>,
+    ) -> Result<T> {
+        if self.prev_key.as_deref() == Some(key)
+            && self
+                .prev_value
+                .as_ref()
+                .is_some_and(|prev| is_same_group_value(prev, value))
+        {
+            let parent_id = add_delta(self.prev_parent_id, delta_or_parent_id)?;
             self.prev_parent_id = parent_id;
-            parent_id
+            Ok(parent_id)
         } else {
-            self.prev_key = Some(key.to_string());
+            self.prev_key = Some(owned_key());
             self.prev_value = Some(value.clone());
             self.prev_parent_id = delta_or_parent_id;
-            delta_or_parent_id
+            Ok(delta_or_parent_id)
         }
     }
+
+    /// Ends the current group of delta encoded parent ids. Must be called for the rows that
+    /// have no value, e.g. empty attributes, which the Go decoder treats as never equal to
+    /// the previous row.
+    pub fn reset(&mut self) {
+        self.prev_key = None;
+        self.prev_value = None;
+    }
+}
+
+/// Returns true if the parent ids of consecutive rows with the given values are delta encoded,
+/// following the `Equal` function of the Go implementation.
+pub(crate) fn is_same_group_value(prev: &any_value::Value, value: &any_value::Value) -> bool {
+    match value {
+        any_value::Value::KvlistValue(_) | any_value::Value::ArrayValue(_) => false,
+        _ => prev == value,
+    }
 }
 
 // TODO -- everything below should probably eventually move to otap::transform module
@@ -119,7 +179,7 @@ pub fn materialize_parent_id<T>(record_batch: &RecordBatch) -> Result<RecordBatc
 where
     T: ParentId,
     <T as ParentId>::ArrayType: ArrowPrimitiveType,
-    <<T as ParentId>::ArrayType as ArrowPrimitiveType>::Native: AddAssign,
+    <<T as ParentId>::ArrayType as ArrowPrimitiveType>::Native: ParentId,
 {
     // if the batch is empty, just skip all this logic and return a batch
     if record_batch.num_rows() == 0 {
@@ -207,7 +267,8 @@ where
                         && !values_eq_next.is_null(prev_value_range_idx)
                     {
                         // value at current index equals previous so we're delta encoded
-                        curr_parent_id += delta_or_parent_id;
+                        curr_parent_id = add_delta(curr_parent_id, delta_or_parent_id)
+                            .error_context(|| ErrorContext::default().row(batch_idx))?;
                     } else {
                         // value change (or null) breaks the sequence of delta encoding
                         curr_parent_id = delta_or_parent_id;
@@ -312,6 +373,7 @@ fn create_next_eq_array_for_array<T: Array>(arr: T) -> BooleanArray {
 #[cfg(test)]
 mod test {
     use crate::arrays::get_u16_array;
+    use crate::otlp::attributes::store::Attribute16Store;
 
     use super::*;
     use arrow::array::{
@@ -320,6 +382,111 @@ mod test {
     use arrow::datatypes::{ArrowDictionaryKeyType, DataType, Field, Schema, UInt16Type};
     use std::sync::Arc;
 
+    #[test]
+    fn test_decode_go_log_attrs() {
+        // LOG_ATTRS record written by hand after the expectations of
+        // go/pkg/otel/logs/arrow/all_test.go
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+                Field::new(consts::ATTRIBUTE_DOUBLE, DataType::Float64, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1])),
+                Arc::new(UInt8Array::from(
+                    [
+                        AttributeValueType::Str,
+                        AttributeValueType::Int,
+                        AttributeValueType::Double,
+                    ]
+                    .iter()
+                    .flat_map(|t| [*t as u8; 4])
+                    .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from(
+                    ["str", "int", "double"]
+                        .iter()
+                        .flat_map(|k| [*k; 4])
+                        .collect::<Vec<_>>(),
+                )),
+                Arc::new(StringArray::from_iter(
+                    [
+                        Some("string1"),
+                        Some("string2"),
+                        Some("string2"),
+                        Some("string2"),
+                    ]
+                    .into_iter()
+                    .chain([None; 8]),
+                )),
+                Arc::new(Int64Array::from_iter(
+                    [None; 4]
+                        .into_iter()
+                        .chain([Some(1), Some(2), Some(2), Some(2)])
+                        .chain([None; 4]),
+                )),
+                Arc::new(Float64Array::from_iter([None; 8].into_iter().chain([
+                    Some(1.0),
+                    Some(2.0),
+                    Some(2.0),
+                    Some(2.0),
+                ]))),
+            ],
+        )
+        .unwrap();
+
+        let store = Attribute16Store::try_from(&rb).unwrap();
+        for (log_id, (str_val, num_val)) in [
+            ("string1", 1),
+            ("string2", 2),
+            ("string2", 2),
+            ("string2", 2),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let values: Vec<_> = store
+                .attribute_by_id(log_id as u16)
+                .unwrap()
+                .iter()
+                .map(|kv| (kv.key.as_str(), kv.value.clone().unwrap().value.unwrap()))
+                .collect();
+            assert_eq!(values, vec![
+                ("str", any_value::Value::StringValue(str_val.into())),
+                ("int", any_value::Value::IntValue(num_val)),
+                ("double", any_value::Value::DoubleValue(num_val as f64)),
+            ]);
+        }
+    }
+
+    #[test]
+    fn test_decode_group_resets() {
+        let str_val = any_value::Value::StringValue("a".into());
+        let map_val = any_value::Value::KvlistValue(Default::default());
+        let mut decoder = Attrs16ParentIdDecoder::default();
+
+        // delta encoded while key and value stay the same
+        assert_eq!(decoder.decode(1, "k", &str_val).unwrap(), 1);
+        assert_eq!(decoder.decode(2, "k", &str_val).unwrap(), 3);
+        // key change starts a new group
+        assert_eq!(decoder.decode(2, "k2", &str_val).unwrap(), 2);
+        // rows without value end the group
+        decoder.reset();
+        assert_eq!(decoder.decode(1, "k2", &str_val).unwrap(), 1);
+
+        // map values are never in the same group
+        assert_eq!(decoder.decode(1, "m", &map_val).unwrap(), 1);
+        assert_eq!(decoder.decode(1, "m", &map_val).unwrap(), 1);
+
+        // the delta encoded parent id overflows
+        assert_eq!(decoder.decode(u16::MAX, "k3", &str_val).unwrap(), u16::MAX);
+        assert!(decoder.decode(1, "k3", &str_val).is_err());
+    }
+
     #[test]
     fn test_materialize_parent_id_val_change() {
         let test_data = [
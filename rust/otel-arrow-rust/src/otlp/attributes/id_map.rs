@@ -0,0 +1,171 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Map of the attribute sets of a store by parent id.
+//!
+//! The producers assign the parent ids sequentially from zero, so the ids of a payload are
+//! small and dense. The sets are kept in a vector indexed by id as long as the ids stay
+//! dense, which avoids hashing the id on the hot path of the decoding. The ids too large
+//! for the vector, e.g. the ones of a payload whose ids start at some offset, are kept in a
+//! hash map instead.
+//!
+//! The map holds any kind of set, the attribute vectors decoded by the store or their
+//! shared copies.
+
+use std::collections::HashMap;
+
+use crate::otlp::attributes::parent_id::ParentId;
+use crate::proto::opentelemetry::common::v1::KeyValue;
+
+/// Ids smaller than this are always kept in the vector.
+const MIN_DENSE_LEN: usize = 64;
+
+/// Attribute sets by parent id, see the module documentation.
+#[derive(Debug)]
+pub struct IdMap<T, S = Vec<KeyValue>> {
+    /// Sets indexed by id.
+    dense: Vec<Option<S>>,
+    /// Sets whose id is not covered by `dense`.
+    sparse: HashMap<T, S>,
+    len: usize,
+}
+
+impl<T, S> Default for IdMap<T, S> {
+    fn default() -> Self {
+        Self {
+            dense: Vec::new(),
+            sparse: HashMap::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T, S> IdMap<T, S> {
+    /// Returns the number of sets.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Removes all the sets and returns them, keeping the capacity of the map.
+    pub(crate) fn drain(&mut self) -> impl Iterator<Item = S> + '_ {
+        self.len = 0;
+        let dense = self.dense.drain(..).flatten();
+        dense.chain(self.sparse.drain().map(|(_, attrs)| attrs))
+    }
+}
+
+impl<T: ParentId, S> IdMap<T, S> {
+    /// Returns the set with the given id.
+    pub(crate) fn get(&self, id: &T) -> Option<&S> {
+        match dense_index(*id) {
+            Some(idx) if idx < self.dense.len() => self.dense[idx].as_ref(),
+            _ => self.sparse.get(id),
+        }
+    }
+
+    /// Returns the set with the given id, inserting the one returned by `default` if there
+    /// is none.
+    pub(crate) fn get_or_insert_with(&mut self, id: T, default: impl FnOnce() -> S) -> &mut S {
+        let Some(idx) = dense_index(id).filter(|idx| self.fits_dense(*idx)) else {
+            let len = &mut self.len;
+            return self.sparse.entry(id).or_insert_with(|| {
+                *len += 1;
+                default()
+            });
+        };
+
+        if idx >= self.dense.len() {
+            let covered = self.dense.len()..=idx;
+            self.dense.resize_with(idx + 1, || None);
+            // the sets inserted before the vector covered their ids move to the vector
+            if !self.sparse.is_empty() {
+                for idx in covered {
+                    if let Some(attrs) = T::try_from(idx as i128)
+                        .ok()
+                        .and_then(|id| self.sparse.remove(&id))
+                    {
+                        self.dense[idx] = Some(attrs);
+                    }
+                }
+            }
+        }
+        let len = &mut self.len;
+        self.dense[idx].get_or_insert_with(|| {
+            *len += 1;
+            default()
+        })
+    }
+
+    /// Inserts the set with the given id, returning the one it replaces.
+    #[cfg(test)]
+    pub(crate) fn insert(&mut self, id: T, attrs: S) -> Option<S>
+    where
+        S: Default,
+    {
+        let mut inserted = false;
+        let slot = self.get_or_insert_with(id, || {
+            inserted = true;
+            S::default()
+        });
+        let previous = std::mem::replace(slot, attrs);
+        (!inserted).then_some(previous)
+    }
+
+    /// Iterates over the sets, in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (T, &S)> + '_ {
+        let dense = self.dense.iter().enumerate().filter_map(|(idx, attrs)| {
+            let id = T::try_from(idx as i128).ok()?;
+            Some((id, attrs.as_ref()?))
+        });
+        dense.chain(self.sparse.iter().map(|(id, attrs)| (*id, attrs)))
+    }
+
+    /// Returns true if the vector can grow to cover the index, i.e. if it would stay at
+    /// most about twice as long as the number of sets.
+    fn fits_dense(&self, idx: usize) -> bool {
+        idx < self.dense.len() || idx < MIN_DENSE_LEN.max(2 * (self.len + 1))
+    }
+}
+
+fn dense_index<T: ParentId>(id: T) -> Option<usize> {
+    usize::try_from(id.into()).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn attrs(id: u32) -> Vec<KeyValue> {
+        vec![KeyValue {
+            key: id.to_string(),
+            value: None,
+        }]
+    }
+
+    #[test]
+    fn test_dense_and_sparse_ids() {
+        let mut map = IdMap::<u32>::default();
+        // an outlier first, then dense ids growing past it
+        assert_eq!(map.insert(100, attrs(100)), None);
+        for id in 0..200 {
+            let _ = map.get_or_insert_with(id, || attrs(id));
+        }
+        assert_eq!(map.insert(1_000_000, attrs(1_000_000)), None);
+        assert_eq!(map.len(), 201);
+        assert_eq!(map.sparse.len(), 1);
+
+        for id in (0..200).chain([1_000_000]) {
+            assert_eq!(map.get(&id), Some(&attrs(id)));
+        }
+        assert_eq!(map.get(&200), None);
+        assert_eq!(map.get(&u32::MAX), None);
+
+        let mut ids: Vec<_> = map.iter().map(|(id, _)| id).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, (0..200).chain([1_000_000]).collect::<Vec<_>>());
+
+        assert_eq!(map.drain().count(), 201);
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.get(&0), None);
+    }
+}
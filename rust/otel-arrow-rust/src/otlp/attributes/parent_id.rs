@@ -12,23 +12,36 @@
 
 use crate::error::{self, Result};
 use crate::otlp::attributes::decoder::{
-    Attrs16ParentIdDecoder, Attrs32ParentIdDecoder, AttrsParentIdDecoder,
+    Attrs16ParentIdDecoder, Attrs32ParentIdDecoder, Attrs64ParentIdDecoder, AttrsParentIdDecoder,
 };
+use crate::otlp::pool::{DecoderPool, PooledMaps};
 use crate::schema::consts;
 use arrow::array::{ArrowPrimitiveType, PrimitiveArray, RecordBatch};
-use arrow::datatypes::{UInt16Type, UInt32Type};
+use arrow::datatypes::{Int32Type, Int64Type, UInt16Type, UInt32Type, UInt64Type};
 use snafu::OptionExt;
 use std::hash::Hash;
 use std::ops::{Add, AddAssign};
 
-pub trait ParentId: Copy + Hash + Eq + Default + Add<Output = Self> + AddAssign
+/// Integer type of the ids attribute sets are attached to. The OTAP payloads use `u16` and
+/// `u32` ids, the wider and signed types are supported for custom payloads.
+pub trait ParentId:
+    Copy + Hash + Ord + Default + Add<Output = Self> + AddAssign + Into<i128> + TryFrom<i128>
 where
     <Self as ParentId>::ArrayType: ArrowPrimitiveType,
 {
     type ArrayType;
 
+    /// The largest id of the type.
+    const MAX: Self;
+
     fn new_decoder() -> AttrsParentIdDecoder<Self>;
 
+    /// Returns the maps of the pool recycling the attribute stores of this id type, none
+    /// are kept for the types the OTAP payloads don't use.
+    fn pooled_maps(_pool: &DecoderPool) -> Option<&PooledMaps<Self>> {
+        None
+    }
+
     /// Get the parent id columns from the record batch, downcast to the correct type
     fn get_parent_id_column(
         record_batch: &RecordBatch,
@@ -52,18 +65,129 @@ where
     }
 }
 
+/// Returns `id + delta`, failing instead of wrapping around when the sum is out of the range
+/// of the id type, e.g. for a malicious batch.
+pub(crate) fn add_delta<T: ParentId>(id: T, delta: T) -> Result<T> {
+    let (id, delta): (i128, i128) = (id.into(), delta.into());
+    T::try_from(id + delta)
+        .ok()
+        .context(error::InvalidDeltaIdSnafu { last_id: id, delta })
+}
+
 impl ParentId for u16 {
     type ArrayType = UInt16Type;
+    const MAX: Self = u16::MAX;
 
     fn new_decoder() -> AttrsParentIdDecoder<Self> {
         Attrs16ParentIdDecoder::default()
     }
+
+    fn pooled_maps(pool: &DecoderPool) -> Option<&PooledMaps<Self>> {
+        Some(&pool.attrs16_maps)
+    }
 }
 
 impl ParentId for u32 {
     type ArrayType = UInt32Type;
+    const MAX: Self = u32::MAX;
 
     fn new_decoder() -> AttrsParentIdDecoder<Self> {
         Attrs32ParentIdDecoder::default()
     }
+
+    fn pooled_maps(pool: &DecoderPool) -> Option<&PooledMaps<Self>> {
+        Some(&pool.attrs32_maps)
+    }
+}
+
+impl ParentId for u64 {
+    type ArrayType = UInt64Type;
+    const MAX: Self = u64::MAX;
+
+    fn new_decoder() -> AttrsParentIdDecoder<Self> {
+        Attrs64ParentIdDecoder::default()
+    }
+}
+
+impl ParentId for i32 {
+    type ArrayType = Int32Type;
+    const MAX: Self = i32::MAX;
+
+    fn new_decoder() -> AttrsParentIdDecoder<Self> {
+        AttrsParentIdDecoder::default()
+    }
+}
+
+impl ParentId for i64 {
+    type ArrayType = Int64Type;
+    const MAX: Self = i64::MAX;
+
+    fn new_decoder() -> AttrsParentIdDecoder<Self> {
+        AttrsParentIdDecoder::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, Int64Array, StringArray, UInt8Array, UInt64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use crate::otlp::attributes::store::{AttributeStore, AttributeValueType};
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+
+    use super::*;
+
+    fn attrs_batch(parent_ids: ArrayRef) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, parent_ids.data_type().clone(), false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+            ])),
+            vec![
+                parent_ids,
+                Arc::new(UInt8Array::from(vec![AttributeValueType::Str as u8; 3])),
+                Arc::new(StringArray::from(vec!["a", "a", "b"])),
+                Arc::new(StringArray::from(vec!["x", "x", "y"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn str_attr(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue(value.to_string())),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_wide_parent_ids() {
+        let large_id = u64::from(u32::MAX) + 1;
+        let rb = attrs_batch(Arc::new(UInt64Array::from(vec![large_id, 2, large_id])));
+        let store = AttributeStore::<u64>::try_from(&rb).unwrap();
+        assert_eq!(
+            store.attribute_by_id(large_id),
+            Some(&[str_attr("a", "x"), str_attr("b", "y")][..])
+        );
+        assert_eq!(
+            store.attribute_by_id(large_id + 2),
+            Some(&[str_attr("a", "x")][..])
+        );
+
+        let rb = attrs_batch(Arc::new(Int64Array::from(vec![-5, 7, 3])));
+        let store = AttributeStore::<i64>::try_from(&rb).unwrap();
+        assert_eq!(store.attribute_by_id(-5), Some(&[str_attr("a", "x")][..]));
+        assert_eq!(store.attribute_by_id(2), Some(&[str_attr("a", "x")][..]));
+        assert_eq!(store.attribute_by_id(3), Some(&[str_attr("b", "y")][..]));
+
+        // the id type must match the column type
+        assert!(AttributeStore::<u32>::try_from(&rb).is_err());
+    }
 }
@@ -10,172 +10,1248 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::cbor;
+use super::coercion::{ValueColumns, ValueSource};
 use crate::arrays::{
-    ByteArrayAccessor, Int64ArrayAccessor, MaybeDictArrayAccessor, NullableArrayAccessor,
-    StringArrayAccessor, get_bool_array_opt, get_f64_array_opt, get_u8_array,
+    ByteArrayAccessor, ColumnAccessor, MaybeDictArrayAccessor, NullableArrayAccessor,
+    StringArrayAccessor, get_u8_array,
 };
-use crate::error;
+use crate::error::{self, ErrorContext, ErrorContextExt};
+use crate::otap::OtapBatch;
+use crate::otlp::attributes::id_map::IdMap;
 use crate::otlp::attributes::parent_id::ParentId;
+pub use crate::otlp::attributes::stored::Attribute;
+use crate::otlp::attributes::stored::StoredAttribute;
+use crate::otlp::events::DecodeEventKind;
+use crate::otlp::options::{
+    AttributeAction, CoercionAction, DecoderOptions, DeltaIdPolicy, DuplicateKeyPolicy,
+    OversizedValueAction, TRUNCATED_ATTRIBUTE,
+};
+use crate::otlp::pool::{AttributeMap, DecoderPool};
+use crate::otlp::report::{DecodeReport, DroppedRowReason};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use crate::proto::opentelemetry::common::v1::any_value::Value;
-use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
-use crate::schema::consts;
-use arrow::array::{ArrowPrimitiveType, PrimitiveArray, RecordBatch};
-use num_enum::TryFromPrimitive;
+use crate::proto::opentelemetry::common::v1::{ArrayValue, KeyValue, KeyValueList};
+use crate::schema::consts::{self, metadata};
+use crate::schema::get_field_metadata;
+use crate::telemetry;
+pub use crate::value::AttributeValueType;
+use crate::value::{coerce_value, default_value};
+use arrow::array::{ArrayRef, ArrowPrimitiveType, PrimitiveArray, RecordBatch};
+use arrow::compute::partition;
 use snafu::{OptionExt, ResultExt};
 use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// How to resolve an attribute key present with different values in the attribute sets
+/// being merged.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MergeConflict {
+    /// Keep the value of the set merged into.
+    #[default]
+    FirstWins,
+    /// Keep the value of the set being merged.
+    LastWins,
+    /// Fail the merge.
+    Error,
+}
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, TryFromPrimitive)]
-#[repr(u8)]
-pub enum AttributeValueType {
-    Empty = 0,
-    Str = 1,
-    Int = 2,
-    Double = 3,
-    Bool = 4,
-    Map = 5,
-    Slice = 6,
-    Bytes = 7,
+/// Merges the attributes of `other` into `target`. The attributes of `other` whose key is
+/// not in `target` are appended, in order. The keys present in both with different values
+/// are resolved according to `conflict`.
+pub fn merge_key_values(
+    target: &mut Vec<KeyValue>,
+    other: &[KeyValue],
+    conflict: MergeConflict,
+) -> error::Result<()> {
+    for kv in other {
+        match target.iter_mut().find(|t| t.key == kv.key) {
+            None => target.push(kv.clone()),
+            Some(existing) if existing.value == kv.value => {}
+            Some(existing) => match conflict {
+                MergeConflict::FirstWins => {}
+                MergeConflict::LastWins => existing.value = kv.value.clone(),
+                MergeConflict::Error => {
+                    return error::AttributeMergeConflictSnafu { key: &kv.key }.fail();
+                }
+            },
+        }
+    }
+    Ok(())
 }
 
 pub type Attribute32Store = AttributeStore<u32>;
 pub type Attribute16Store = AttributeStore<u16>;
+pub type Attribute64Store = AttributeStore<u64>;
 
-#[derive(Default)]
-pub struct AttributeStore<T> {
+/// Attribute sets of a payload by parent id. The attributes are kept as `KeyValue`s by
+/// default, or as the [`Attribute`]s of another value type.
+///
+/// The sets can also be resolved as shared slices with [`Self::shared_attribute_by_id`],
+/// so the parents referencing the same set, e.g. the spans of a resource, hold one copy of
+/// it instead of one each.
+pub struct AttributeStore<T: ParentId, A: StoredAttribute = KeyValue> {
     last_id: T,
-    attribute_by_ids: HashMap<T, Vec<KeyValue>>,
+    attribute_by_ids: AttributeMap<T, A>,
+    /// Copies of the sets resolved as shared slices, by id.
+    shared: IdMap<T, Arc<[A]>>,
+    /// The last set copied to `shared`, reused by the following equal sets.
+    last_shared: Option<Arc<[A]>>,
+    payload_type: ArrowPayloadType,
+    delta_id_policy: DeltaIdPolicy,
+    /// Pool the map and its vectors are returned to when the store is dropped.
+    pool: Option<Arc<DecoderPool>>,
+}
+
+impl<T: ParentId, A: StoredAttribute> Default for AttributeStore<T, A> {
+    fn default() -> Self {
+        Self {
+            last_id: T::default(),
+            attribute_by_ids: AttributeMap::default(),
+            shared: IdMap::default(),
+            last_shared: None,
+            payload_type: ArrowPayloadType::default(),
+            delta_id_policy: DeltaIdPolicy::default(),
+            pool: None,
+        }
+    }
+}
+
+impl<T: ParentId, A: StoredAttribute> Drop for AttributeStore<T, A> {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            let map = std::mem::take(&mut self.attribute_by_ids);
+            A::recycle_map(&pool, map);
+        }
+    }
 }
 
-impl<T> AttributeStore<T>
+impl<T, A> AttributeStore<T, A>
 where
     T: ParentId,
+    A: StoredAttribute,
 {
-    pub fn attribute_by_delta_id(&mut self, delta: T) -> Option<&[KeyValue]> {
-        self.last_id += delta;
-        self.attribute_by_ids
+    /// Returns the attributes of the id `delta` after the id of the previous lookup. A
+    /// delta making the id wrap around or go backwards is counted in `report` and handled
+    /// according to the [`DeltaIdPolicy`] of the decoder options.
+    pub fn attribute_by_delta_id(
+        &mut self,
+        delta: T,
+        report: &mut DecodeReport,
+    ) -> error::Result<Option<&[A]>> {
+        let last_id: i128 = self.last_id.into();
+        let delta: i128 = delta.into();
+        let id = if delta >= 0 {
+            T::try_from(last_id + delta).ok()
+        } else {
+            None
+        };
+        self.last_id = match id {
+            Some(id) => id,
+            None => {
+                report.record_delta_id_anomaly(self.payload_type);
+                match self.delta_id_policy {
+                    DeltaIdPolicy::Error => {
+                        return error::InvalidDeltaIdSnafu { last_id, delta }
+                            .fail()
+                            .in_payload(self.payload_type);
+                    }
+                    DeltaIdPolicy::Clamp if delta >= 0 => T::MAX,
+                    DeltaIdPolicy::Clamp => self.last_id,
+                    DeltaIdPolicy::Skip => return Ok(None),
+                }
+            }
+        };
+        Ok(self
+            .attribute_by_ids
             .get(&self.last_id)
-            .map(|r| r.as_slice())
+            .map(|r| r.as_slice()))
     }
 
-    pub fn attribute_by_id(&self, id: T) -> Option<&[KeyValue]> {
+    pub fn attribute_by_id(&self, id: T) -> Option<&[A]> {
         self.attribute_by_ids.get(&id).map(|r| r.as_slice())
     }
+
+    /// Returns the attributes of the set with the given id as a shared slice, e.g. for the
+    /// records holding their attributes past the lifetime of the store. The set is copied
+    /// once, on its first resolution, and the sets equal to the previously copied one, e.g.
+    /// the ones of the resources of a batch repeating the same attributes, share its copy.
+    pub fn shared_attribute_by_id(&mut self, id: T) -> Option<Arc<[A]>>
+    where
+        A: Clone + PartialEq,
+    {
+        if let Some(shared) = self.shared.get(&id) {
+            return Some(Arc::clone(shared));
+        }
+        let attrs = self.attribute_by_ids.get(&id)?;
+        let shared = match &self.last_shared {
+            Some(last) if **last == **attrs => Arc::clone(last),
+            _ => Arc::<[A]>::from(attrs.as_slice()),
+        };
+        self.last_shared = Some(Arc::clone(&shared));
+        Some(Arc::clone(self.shared.get_or_insert_with(id, || shared)))
+    }
+
+    /// Returns the attribute sets whose id is in `range`, by increasing id, e.g. to join
+    /// them back to parents sorted by id. The ids without attributes are skipped.
+    ///
+    /// Depending on which is smaller, either the ids of the range are looked up or the sets
+    /// of the store are scanned, so a query over a wide range of sparse ids doesn't cost a
+    /// lookup per id.
+    pub fn attributes_for_range(&self, range: Range<T>) -> impl Iterator<Item = (T, &[A])> + '_ {
+        let (start, end): (i128, i128) = (range.start.into(), range.end.into());
+        let sets: Vec<_> = if end - start <= self.attribute_by_ids.len() as i128 {
+            (start..end)
+                .filter_map(|id| T::try_from(id).ok())
+                .filter_map(|id| Some((id, self.attribute_by_ids.get(&id)?)))
+                .collect()
+        } else {
+            let mut sets: Vec<_> = self
+                .attribute_by_ids
+                .iter()
+                .filter(|(id, _)| range.contains(id))
+                .collect();
+            sets.sort_unstable_by_key(|(id, _)| *id);
+            sets
+        };
+        sets.into_iter().map(|(id, attrs)| (id, attrs.as_slice()))
+    }
+
+    /// Returns all the attribute sets of the store by increasing id, e.g. to inspect or
+    /// re-encode them.
+    pub fn iter(&self) -> impl Iterator<Item = (T, &[A])> + '_ {
+        let mut sets: Vec<_> = self.attribute_by_ids.iter().collect();
+        sets.sort_unstable_by_key(|(id, _)| *id);
+        sets.into_iter().map(|(id, attrs)| (id, attrs.as_slice()))
+    }
+
+    /// Returns the number of attribute sets of the store.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.attribute_by_ids.len()
+    }
+
+    /// Returns true if the store has no attribute sets.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.attribute_by_ids.len() == 0
+    }
 }
 
-impl<T> TryFrom<&RecordBatch> for AttributeStore<T>
+impl<T: ParentId> AttributeStore<T> {
+    /// Merges the attribute sets of `other` into the ones of this store with the same id.
+    /// See [`merge_key_values`] for how the attributes of a set are merged.
+    pub fn merge(&mut self, other: &Self, conflict: MergeConflict) -> error::Result<()> {
+        // the shared copies of the merged sets are resolved again
+        self.shared = IdMap::default();
+        self.last_shared = None;
+        for (id, attrs) in other.attribute_by_ids.iter() {
+            merge_key_values(
+                self.attribute_by_ids.get_or_insert_with(id, Vec::new),
+                attrs,
+                conflict,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Returns the attributes of the set with the given id, overlaid with `overlay`, e.g. the
+    /// attributes of a log record with the ones of its resource. See [`merge_key_values`]
+    /// for how the attributes are merged.
+    pub fn overlay(
+        &self,
+        id: T,
+        overlay: &[KeyValue],
+        conflict: MergeConflict,
+    ) -> error::Result<Vec<KeyValue>> {
+        let mut attrs = self.attribute_by_id(id).unwrap_or_default().to_vec();
+        merge_key_values(&mut attrs, overlay, conflict)?;
+        Ok(attrs)
+    }
+}
+
+impl<T, A> TryFrom<&RecordBatch> for AttributeStore<T, A>
 where
     T: ParentId,
+    A: StoredAttribute,
     <T as ParentId>::ArrayType: ArrowPrimitiveType,
     <<T as ParentId>::ArrayType as ArrowPrimitiveType>::Native: Into<T>,
 {
     type Error = error::Error;
 
     fn try_from(rb: &RecordBatch) -> Result<Self, Self::Error> {
-        let mut store = Self::default();
+        Self::try_from_with_options(rb, &DecoderOptions::default())
+    }
+}
+
+impl<T, A> AttributeStore<T, A>
+where
+    T: ParentId,
+    A: StoredAttribute,
+    <T as ParentId>::ArrayType: ArrowPrimitiveType,
+    <<T as ParentId>::ArrayType as ArrowPrimitiveType>::Native: Into<T>,
+{
+    /// Decodes the attributes record batch, applying the coercion policy of `options` to
+    /// the values that are not stored in the column matching their declared type.
+    pub fn try_from_with_options(
+        rb: &RecordBatch,
+        options: &DecoderOptions,
+    ) -> error::Result<Self> {
+        Self::decode(rb, ArrowPayloadType::Unknown, options, &mut |_| {})
+    }
+
+    /// Decodes the attributes payload of the given type, if it is present in the batch.
+    /// The rows dropped because of `options.skip_bad_rows` are counted in `report`.
+    pub fn from_payload(
+        otap_batch: &OtapBatch,
+        payload_type: ArrowPayloadType,
+        options: &DecoderOptions,
+        report: &mut DecodeReport,
+    ) -> error::Result<Option<Self>> {
+        otap_batch
+            .get(payload_type)
+            .map(|rb| {
+                telemetry::trace_span!(
+                    "otap.attribute_store",
+                    payload_type = payload_type.as_str_name(),
+                    rows = rb.num_rows(),
+                );
+                let mut store = Self::decode(rb, payload_type, options, &mut |reason| {
+                    report.record(payload_type, reason)
+                })
+                .in_payload(payload_type)?;
+                store.payload_type = payload_type;
+                Ok(store)
+            })
+            .transpose()
+    }
+
+    fn decode(
+        rb: &RecordBatch,
+        payload_type: ArrowPayloadType,
+        options: &DecoderOptions,
+        on_dropped_row: &mut impl FnMut(DroppedRowReason),
+    ) -> error::Result<Self> {
+        let emit = |idx, kind| options.emit_event(payload_type, idx, kind);
+        let mut drop_row = |idx, reason| {
+            on_dropped_row(reason);
+            emit(idx, DecodeEventKind::DroppedRow(reason));
+        };
+        let pool = &options.pool;
+        let mut store = Self {
+            last_id: T::default(),
+            attribute_by_ids: A::take_map(pool),
+            shared: IdMap::default(),
+            last_shared: None,
+            payload_type: ArrowPayloadType::default(),
+            delta_id_policy: options.delta_id_policy,
+            pool: Some(pool.clone()),
+        };
+        // the Go encoder sends empty attribute batches without their parent id column
+        if rb.num_rows() == 0 {
+            return Ok(store);
+        }
+        let mut spare_sets = A::take_sets(pool);
 
-        let key_arr = rb
-            .column_by_name(consts::ATTRIBUTE_KEY)
-            .map(StringArrayAccessor::try_new)
-            .transpose()?;
+        let key_arr = StringArrayAccessor::try_new_for_column_opt(rb, consts::ATTRIBUTE_KEY)?;
         let value_type_arr = get_u8_array(rb, consts::ATTRIBUTE_TYPE)?;
+        let value_arrs = ValueColumns::try_new(rb)?;
+        let value_ser_arr = ByteArrayAccessor::try_new_for_column_opt(rb, consts::ATTRIBUTE_SER)?;
 
-        let value_str_arr = StringArrayAccessor::try_new_for_column(rb, consts::ATTRIBUTE_STR)?;
-        let value_int_arr = rb
-            .column_by_name(consts::ATTRIBUTE_INT)
-            .map(Int64ArrayAccessor::try_new)
-            .transpose()?;
-        let value_double_arr = get_f64_array_opt(rb, consts::ATTRIBUTE_DOUBLE)?;
-        let value_bool_arr = get_bool_array_opt(rb, consts::ATTRIBUTE_BOOL)?;
-        let value_bytes_arr = rb
-            .column_by_name(consts::ATTRIBUTE_BYTES)
-            .map(ByteArrayAccessor::try_new)
-            .transpose()?;
-        let value_ser_arr = rb
-            .column_by_name(consts::ATTRIBUTE_SER)
-            .map(ByteArrayAccessor::try_new)
-            .transpose()?;
+        let parent_id_arr =
+            rb.column_by_name(consts::PARENT_ID)
+                .context(error::ColumnNotFoundSnafu {
+                    name: consts::PARENT_ID,
+                })?;
+        let parent_id_arr =
+            MaybeDictArrayAccessor::<PrimitiveArray<<T as ParentId>::ArrayType>>::try_new(
+                parent_id_arr,
+            )?;
 
+        // the parent ids made plain, e.g. by the filters of the OTAP batches, are read as is
+        let plain_parent_ids = get_field_metadata(
+            rb.schema_ref(),
+            consts::PARENT_ID,
+            metadata::COLUMN_ENCODING,
+        ) == Some(metadata::encodings::PLAIN);
         let mut parent_id_decoder = T::new_decoder();
+        let mut keys = options.interner.session();
+        let mut key_index = KeyIndex::default();
+        let value_limits = &options.value_limits;
 
-        for idx in 0..rb.num_rows() {
-            let key = key_arr.value_at_or_default(idx);
-            let value_type = AttributeValueType::try_from(value_type_arr.value_at_or_default(idx))
-                .context(error::UnrecognizedAttributeValueTypeSnafu)?;
-            let value = match value_type {
-                AttributeValueType::Str => {
-                    Value::StringValue(value_str_arr.value_at(idx).unwrap_or_default())
-                }
-                AttributeValueType::Int => Value::IntValue(value_int_arr.value_at_or_default(idx)),
-                AttributeValueType::Double => {
-                    Value::DoubleValue(value_double_arr.value_at_or_default(idx))
-                }
-                AttributeValueType::Bool => {
-                    Value::BoolValue(value_bool_arr.value_at_or_default(idx))
-                }
-                AttributeValueType::Bytes => {
-                    Value::BytesValue(value_bytes_arr.value_at_or_default(idx))
-                }
-                AttributeValueType::Slice | AttributeValueType::Map => {
-                    let bytes = value_ser_arr.value_at(idx);
-                    if bytes.is_none() {
+        // The scalar values are converted in bulk, one type column at a time, for the rows
+        // of the type selected with a filter, see `ValueColumns::typed_values`. The rows are
+        // then decoded in order, the parent ids being delta encoded in the order of the
+        // rows, in runs of consecutive rows of the same type so that the type is only
+        // dispatched once per run. The encoder sorts the rows by type, so a run is usually a
+        // whole type group.
+        let mut typed_values = value_arrs.typed_values(
+            value_type_arr,
+            options.attribute_coercion.looks_up_other_columns(),
+        )?;
+        let value_type_col: ArrayRef = Arc::new(value_type_arr.clone());
+        // safety: partitioning of u8 arrays is supported
+        let runs = partition(&[value_type_col]).expect("u8 arrays can be partitioned");
+        for run in runs.ranges() {
+            let value_type =
+                match AttributeValueType::try_from(value_type_arr.value_at_or_default(run.start)) {
+                    Ok(value_type) => value_type,
+                    Err(_) if options.skip_bad_rows => {
+                        for idx in run {
+                            drop_row(idx, DroppedRowReason::UnrecognizedValueType);
+                        }
+                        parent_id_decoder.reset();
                         continue;
                     }
+                    Err(e) => {
+                        return Err(e)
+                            .context(error::UnrecognizedAttributeValueTypeSnafu)
+                            .error_context(|| {
+                                ErrorContext::default()
+                                    .column(consts::ATTRIBUTE_TYPE)
+                                    .row(run.start)
+                            });
+                    }
+                };
+            if value_type == AttributeValueType::Empty {
+                for idx in run {
+                    emit(idx, DecodeEventKind::EmptyValue);
+                }
+                parent_id_decoder.reset();
+                continue;
+            }
+            for idx in run {
+                let key = match key_arr.as_ref().and_then(|keys| keys.str_at(idx)) {
+                    Some(key) => keys.intern(key),
+                    None => {
+                        emit(idx, DecodeEventKind::MissingKey);
+                        keys.intern("")
+                    }
+                };
+                let mut truncated = false;
+                let (stored_type, value) = match value_type {
+                    AttributeValueType::Slice | AttributeValueType::Map => {
+                        let bytes = value_ser_arr.value_at(idx);
+                        if bytes.is_none() {
+                            emit(idx, DecodeEventKind::MissingSerializedValue);
+                            parent_id_decoder.reset();
+                            continue;
+                        }
 
-                    let decoded_result = cbor::decode_pcommon_val(&bytes.expect("expected Some"))?;
-                    match decoded_result {
-                        Some(value) => value,
-                        None => continue,
+                        match value_limits.decode_serialized(&bytes.expect("expected Some")) {
+                            Ok(Some(value)) => (value_type, value),
+                            Ok(None) => {
+                                emit(idx, DecodeEventKind::EmptySerializedValue);
+                                parent_id_decoder.reset();
+                                continue;
+                            }
+                            Err(e)
+                                if is_oversized(&e)
+                                    && value_limits.action() == OversizedValueAction::Truncate =>
+                            {
+                                // the parent ids of the maps and slices are never delta
+                                // encoded, so the empty value doesn't change them
+                                truncated = true;
+                                (value_type, empty_value(value_type))
+                            }
+                            Err(e) if options.skip_bad_rows => {
+                                let reason = if is_oversized(&e) {
+                                    DroppedRowReason::OversizedValue
+                                } else {
+                                    DroppedRowReason::InvalidSerializedValue
+                                };
+                                drop_row(idx, reason);
+                                parent_id_decoder.reset();
+                                continue;
+                            }
+                            Err(e) => {
+                                return Err(e.with_context(
+                                    ErrorContext::default()
+                                        .column(consts::ATTRIBUTE_SER)
+                                        .row(idx),
+                                ));
+                            }
+                        }
                     }
+                    _ => match typed_values.next(value_type) {
+                        // the values of the other columns are only looked up by the actions
+                        // coercing or skipping them, the error of the default action only
+                        // applies to the column named after the type
+                        (_, _, ValueSource::OtherColumn)
+                            if options.attribute_coercion.action_for(&key)
+                                == CoercionAction::Error =>
+                        {
+                            // safety: default_value returns a value for all the scalar types
+                            (
+                                value_type,
+                                default_value(value_type).expect("scalar value type"),
+                            )
+                        }
+                        (stored_type, value, _) => (stored_type, value),
+                    },
+                };
+
+                // Parse potentially delta encoded parent id field.
+                // the delta encoding of the parent id is based on the value as it was stored
+                let parent_id = parent_id_arr.value_at_or_default(idx).into();
+                let parent_id = if plain_parent_ids {
+                    parent_id
+                } else {
+                    parent_id_decoder
+                        .decode_interned(parent_id, &key, &value)
+                        .error_context(|| {
+                            ErrorContext::default().row(idx).column(consts::PARENT_ID)
+                        })?
+                };
+
+                let value = if stored_type == value_type {
+                    value
+                } else {
+                    match options.attribute_coercion.action_for(&key) {
+                        CoercionAction::Coerce => coerce_value(&value, value_type).unwrap_or(value),
+                        CoercionAction::Skip => continue,
+                        CoercionAction::Error if options.skip_bad_rows => {
+                            drop_row(idx, DroppedRowReason::ValueTypeMismatch);
+                            continue;
+                        }
+                        CoercionAction::Error => {
+                            return error::AttributeValueTypeMismatchSnafu {
+                                key: &*key,
+                                expect: value_type,
+                                actual: stored_type,
+                            }
+                            .fail()
+                            .error_context(|| {
+                                ErrorContext::default().row(idx).parent_id(parent_id)
+                            });
+                        }
+                    }
+                };
+
+                let value = if options.attribute_coercion.converts_values() {
+                    options.attribute_coercion.convert(&key, value)
+                } else {
+                    value
+                };
+
+                let value = match value_limits.truncate(value) {
+                    Ok((value, value_truncated)) => {
+                        truncated |= value_truncated;
+                        value
+                    }
+                    Err(_) if options.skip_bad_rows => {
+                        drop_row(idx, DroppedRowReason::OversizedValue);
+                        continue;
+                    }
+                    Err(e) => {
+                        return Err(e).error_context(|| {
+                            ErrorContext::default().row(idx).parent_id(parent_id)
+                        });
+                    }
+                };
+                if truncated {
+                    emit(idx, DecodeEventKind::TruncatedValue);
+                }
+
+                let (key, value) = match &options.attribute_hook {
+                    None => (key, value),
+                    Some(hook) => match hook.on_attribute(&key, &value) {
+                        AttributeAction::Keep => (key, value),
+                        AttributeAction::Drop => continue,
+                        AttributeAction::Rename(new_key) => (keys.intern(&new_key), value),
+                        AttributeAction::Rewrite(new_value) => (key, new_value),
+                        AttributeAction::Replace(new_key, new_value) => {
+                            (keys.intern(&new_key), new_value)
+                        }
+                    },
+                };
+
+                let attributes = store
+                    .attribute_by_ids
+                    .get_or_insert_with(parent_id, || spare_sets.pop().unwrap_or_default());
+                //todo: support assigning ArrayValue and KvListValue by deep copy as in https://github.com/open-telemetry/opentelemetry-collector/blob/fbf6d103eea79e72ff6b2cc3a2a18fc98a836281/pdata/pcommon/value.go#L323
+                if truncated {
+                    let marker = keys.intern(TRUNCATED_ATTRIBUTE);
+                    let attribute = A::new(&marker, Value::BoolValue(true));
+                    key_index.insert(parent_id, attributes, &marker, attribute);
                 }
-                AttributeValueType::Empty => {
-                    // should warn here.
+                if options.duplicate_key_policy == DuplicateKeyPolicy::KeepAll {
+                    key_index.append(parent_id, attributes, &key, A::new(&key, value));
                     continue;
                 }
-            };
-
-            // Parse potentially delta encoded parent id field.
-            let parent_id_arr =
-                rb.column_by_name(consts::PARENT_ID)
-                    .context(error::ColumnNotFoundSnafu {
-                        name: consts::PARENT_ID,
-                    })?;
-            let parent_id_arr =
-                MaybeDictArrayAccessor::<PrimitiveArray<<T as ParentId>::ArrayType>>::try_new(
-                    parent_id_arr,
-                )?;
-
-            let parent_id = parent_id_decoder.decode(
-                parent_id_arr.value_at_or_default(idx).into(),
-                &key,
-                &value,
-            );
-            let attributes = store.attribute_by_ids.entry(parent_id).or_default();
-            //todo: support assigning ArrayValue and KvListValue by deep copy as in https://github.com/open-telemetry/opentelemetry-collector/blob/fbf6d103eea79e72ff6b2cc3a2a18fc98a836281/pdata/pcommon/value.go#L323
-            *attributes.find_or_append(&key) = Some(AnyValue { value: Some(value) });
+                let position = key_index.position(parent_id, attributes, &key);
+                if position.is_some() {
+                    match options.duplicate_key_policy {
+                        DuplicateKeyPolicy::LastWins | DuplicateKeyPolicy::KeepAll => {}
+                        DuplicateKeyPolicy::FirstWins => continue,
+                        DuplicateKeyPolicy::Error if options.skip_bad_rows => {
+                            drop_row(idx, DroppedRowReason::DuplicateKey);
+                            continue;
+                        }
+                        DuplicateKeyPolicy::Error => {
+                            return error::DuplicateAttributeKeySnafu { key: &*key }
+                                .fail()
+                                .error_context(|| {
+                                    ErrorContext::default().row(idx).parent_id(parent_id)
+                                });
+                        }
+                    }
+                }
+                let attribute = A::new(&key, value);
+                match position {
+                    Some(idx) => attributes[idx] = attribute,
+                    None => key_index.append(parent_id, attributes, &key, attribute),
+                }
+            }
         }
 
+        A::recycle_sets(pool, spare_sets);
         Ok(store)
     }
 }
 
-trait FindOrAppendValue<V> {
-    /// Finds a value with given key and returns the mutable reference to that value.
-    /// Appends a new value if not found and return mutable reference to that newly created value.
-    fn find_or_append(&mut self, key: &str) -> &mut V;
+/// Returns whether the error is a value over the value limits.
+fn is_oversized(error: &error::Error) -> bool {
+    matches!(
+        error,
+        error::Error::AttributeValueTooLarge { .. }
+            | error::Error::SerializedValueTooDeep { .. }
+            | error::Error::TooManySerializedElements { .. }
+    )
 }
 
-impl FindOrAppendValue<Option<AnyValue>> for Vec<KeyValue> {
-    fn find_or_append(&mut self, key: &str) -> &mut Option<AnyValue> {
-        // It's a workaround for https://github.com/rust-lang/rust/issues/51545
-        if let Some((idx, _)) = self.iter().enumerate().find(|(_, kv)| kv.key == key) {
-            return &mut self[idx].value;
+/// Returns the empty value of a map or slice attribute.
+fn empty_value(value_type: AttributeValueType) -> Value {
+    match value_type {
+        AttributeValueType::Map => Value::KvlistValue(KeyValueList::default()),
+        _ => Value::ArrayValue(ArrayValue::default()),
+    }
+}
+
+/// Sets with more attributes than this are looked up through a [`KeyIndex`] rather than
+/// scanned.
+const SCAN_LIMIT: usize = 16;
+
+/// Positions of the keys of the wide attribute sets of a store being decoded, so that the
+/// decoding of the sets with many attributes doesn't scan the set for every attribute.
+/// A set is indexed once it has more than [`SCAN_LIMIT`] attributes.
+#[derive(Default)]
+struct KeyIndex<T> {
+    positions: HashMap<T, HashMap<Arc<str>, usize>>,
+}
+
+impl<T: ParentId> KeyIndex<T> {
+    /// Returns the position of the attribute with the key in the set with id `id`.
+    fn position<A: StoredAttribute>(
+        &mut self,
+        id: T,
+        attributes: &[A],
+        key: &Arc<str>,
+    ) -> Option<usize> {
+        if attributes.len() <= SCAN_LIMIT {
+            return attributes.iter().position(|attr| attr.key() == &**key);
+        }
+        let positions = self.positions.entry(id).or_insert_with(|| {
+            attributes
+                .iter()
+                .enumerate()
+                .map(|(idx, attr)| (Arc::from(attr.key()), idx))
+                .collect()
+        });
+        positions.get(key).copied()
+    }
+
+    /// Appends the attribute to the set with id `id`.
+    fn append<A: StoredAttribute>(
+        &mut self,
+        id: T,
+        attributes: &mut Vec<A>,
+        key: &Arc<str>,
+        attribute: A,
+    ) {
+        if let Some(positions) = self.positions.get_mut(&id) {
+            let _ = positions.insert(key.clone(), attributes.len());
+        }
+        attributes.push(attribute);
+    }
+
+    /// Replaces the attribute with the key in the set with id `id`, or appends it.
+    fn insert<A: StoredAttribute>(
+        &mut self,
+        id: T,
+        attributes: &mut Vec<A>,
+        key: &Arc<str>,
+        attribute: A,
+    ) {
+        match self.position(id, attributes, key) {
+            Some(idx) => attributes[idx] = attribute,
+            None => self.append(id, attributes, key, attribute),
         }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::common::v1::AnyValue;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
 
-        self.push(KeyValue {
+    fn attr(key: &str, value: i64) -> KeyValue {
+        KeyValue {
             key: key.to_string(),
-            value: None,
-        });
-        &mut self.last_mut().expect("vec is not empty").value
+            value: Some(AnyValue {
+                value: Some(Value::IntValue(value)),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_attribute_hook() {
+        use std::sync::Arc;
+
+        use arrow::array::{Int64Array, StringArray, UInt8Array, UInt16Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0, 0, 1])),
+                Arc::new(UInt8Array::from(vec![
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Int as u8,
+                    AttributeValueType::Int as u8,
+                ])),
+                Arc::new(StringArray::from(vec!["email", "secret", "a", "a"])),
+                Arc::new(StringArray::from(vec![
+                    Some("me@example.com"),
+                    Some("hunter2"),
+                    None,
+                    None,
+                ])),
+                Arc::new(Int64Array::from(vec![None, None, Some(1), Some(1)])),
+            ],
+        )
+        .unwrap();
+
+        let options =
+            DecoderOptions::default().with_attribute_hook(|key: &str, _: &Value| match key {
+                "email" => AttributeAction::Rewrite(Value::StringValue("<redacted>".into())),
+                "secret" => AttributeAction::Drop,
+                _ => AttributeAction::Rename(format!("x.{key}")),
+            });
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(
+                &[
+                    KeyValue {
+                        key: "email".into(),
+                        value: Some(AnyValue {
+                            value: Some(Value::StringValue("<redacted>".into())),
+                        }),
+                    },
+                    attr("x.a", 1),
+                ][..]
+            )
+        );
+        // the rewritten attributes don't break the delta encoding of the parent ids
+        assert_eq!(store.attribute_by_id(1), Some(&[attr("x.a", 1)][..]));
+    }
+
+    #[test]
+    fn test_empty_batch() {
+        use std::sync::Arc;
+
+        use arrow::array::{StringArray, UInt8Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+
+        // no parent id column, as sent by the Go encoder
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(UInt8Array::from(Vec::<u8>::new())),
+                Arc::new(StringArray::from(Vec::<&str>::new())),
+            ],
+        )
+        .unwrap();
+        let store = Attribute16Store::try_from(&rb).unwrap();
+        assert_eq!(store.iter().count(), 0);
+        assert_eq!(store.attribute_by_id(0), None);
+    }
+
+    #[test]
+    fn test_duplicate_key_policy() {
+        use std::sync::Arc;
+
+        use arrow::array::{Int64Array, StringArray, UInt8Array, UInt16Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0, 0])),
+                Arc::new(UInt8Array::from(vec![AttributeValueType::Int as u8; 3])),
+                Arc::new(StringArray::from(vec!["a", "a", "b"])),
+                Arc::new(StringArray::from(vec![None::<&str>; 3])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+        )
+        .unwrap();
+        let decode = |policy, skip_bad_rows| {
+            let options = DecoderOptions::default()
+                .with_duplicate_key_policy(policy)
+                .with_skip_bad_rows(skip_bad_rows);
+            Attribute16Store::try_from_with_options(&rb, &options)
+                .map(|store| store.attribute_by_id(0).unwrap_or_default().to_vec())
+        };
+
+        assert_eq!(decode(DuplicateKeyPolicy::LastWins, false).unwrap(), vec![
+            attr("a", 2),
+            attr("b", 3)
+        ]);
+        assert_eq!(decode(DuplicateKeyPolicy::FirstWins, false).unwrap(), vec![
+            attr("a", 1),
+            attr("b", 3)
+        ]);
+        assert_eq!(decode(DuplicateKeyPolicy::KeepAll, false).unwrap(), vec![
+            attr("a", 1),
+            attr("a", 2),
+            attr("b", 3)
+        ]);
+        assert!(matches!(
+            decode(DuplicateKeyPolicy::Error, false),
+            Err(error::Error::Decode { source, .. })
+                if matches!(*source, error::Error::DuplicateAttributeKey { .. })
+        ));
+        assert_eq!(decode(DuplicateKeyPolicy::Error, true).unwrap(), vec![
+            attr("a", 1),
+            attr("b", 3)
+        ]);
+    }
+
+    #[test]
+    fn test_stored_attribute() {
+        use std::sync::Arc;
+
+        use arrow::array::{Int64Array, StringArray, UInt8Array, UInt16Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+
+        /// A value type of a downstream representation.
+        #[derive(Debug, PartialEq)]
+        struct Int(Option<i64>);
+
+        impl From<Value> for Int {
+            fn from(value: Value) -> Self {
+                match value {
+                    Value::IntValue(value) => Int(Some(value)),
+                    _ => Int(None),
+                }
+            }
+        }
+
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0, 1])),
+                Arc::new(UInt8Array::from(vec![AttributeValueType::Int as u8; 3])),
+                Arc::new(StringArray::from(vec!["a", "a", "b"])),
+                Arc::new(StringArray::from(vec![None::<&str>; 3])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+        )
+        .unwrap();
+
+        let store = AttributeStore::<u16, Attribute<Value>>::try_from(&rb).unwrap();
+        assert_eq!(store.attribute_by_id(0).unwrap(), &[Attribute {
+            key: Arc::from("a"),
+            value: Value::IntValue(2),
+        }]);
+
+        let store = AttributeStore::<u16, Attribute<Int>>::try_from(&rb).unwrap();
+        let sets: Vec<_> = store
+            .iter()
+            .map(|(id, attrs)| (id, &*attrs[0].key, &attrs[0].value))
+            .collect();
+        assert_eq!(sets, vec![(0, "a", &Int(Some(2))), (1, "b", &Int(Some(3)))]);
+    }
+
+    #[test]
+    fn test_value_coercion() {
+        use std::sync::Arc;
+
+        use arrow::array::{Int64Array, StringArray, UInt8Array, UInt16Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+
+        use crate::otlp::options::CoercionPolicy;
+
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0, 0, 0])),
+                Arc::new(UInt8Array::from(vec![
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Int as u8,
+                ])),
+                Arc::new(StringArray::from(vec!["count", "flag", "name", "enabled"])),
+                Arc::new(StringArray::from(vec![
+                    Some(" 42"),
+                    Some("true"),
+                    Some("abc"),
+                    None,
+                ])),
+                Arc::new(Int64Array::from(vec![None, None, None, Some(1)])),
+            ],
+        )
+        .unwrap();
+        let policy = CoercionPolicy::default()
+            .with_conversion(AttributeValueType::Str, AttributeValueType::Int)
+            .with_key_type("flag", AttributeValueType::Bool)
+            .with_key_type("enabled", AttributeValueType::Bool);
+        let options = DecoderOptions::default().with_attribute_coercion(policy);
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        let value = |v| Some(AnyValue { value: Some(v) });
+        assert_eq!(store.attribute_by_id(0).unwrap(), &[
+            KeyValue {
+                key: "count".into(),
+                value: value(Value::IntValue(42)),
+            },
+            KeyValue {
+                key: "flag".into(),
+                value: value(Value::BoolValue(true)),
+            },
+            // the values that can't be converted are kept
+            KeyValue {
+                key: "name".into(),
+                value: value(Value::StringValue("abc".into())),
+            },
+            KeyValue {
+                key: "enabled".into(),
+                value: value(Value::BoolValue(true)),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_value_limits() {
+        use std::sync::Arc;
+
+        use arrow::array::{BinaryArray, StringArray, UInt8Array, UInt16Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+
+        use crate::otlp::options::ValueLimits;
+        use crate::proto::opentelemetry::common::v1::KeyValueList;
+        use crate::value::cbor;
+
+        let nested = Value::KvlistValue(KeyValueList::new(vec![KeyValue::new("a", AnyValue {
+            value: Some(Value::KvlistValue(KeyValueList::default())),
+        })]));
+        let nested = cbor::encode_pcommon_val(Some(&nested));
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_SER, DataType::Binary, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0, 1])),
+                Arc::new(UInt8Array::from(vec![
+                    AttributeValueType::Str as u8,
+                    AttributeValueType::Map as u8,
+                    AttributeValueType::Str as u8,
+                ])),
+                Arc::new(StringArray::from(vec!["s", "m", "s"])),
+                Arc::new(StringArray::from(vec![Some("héllo"), None, Some("ok")])),
+                Arc::new(BinaryArray::from(vec![None, Some(nested.as_slice()), None])),
+            ],
+        )
+        .unwrap();
+        let str_attr = |key: &str, value: &str| KeyValue {
+            key: key.into(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue(value.into())),
+            }),
+        };
+        let limits = ValueLimits::new()
+            .with_max_value_len(2)
+            .with_max_serialized_depth(1);
+
+        let options = DecoderOptions::default().with_value_limits(limits);
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        // truncated at a character boundary, the nested map replaced by an empty one
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(
+                &[
+                    KeyValue {
+                        key: TRUNCATED_ATTRIBUTE.into(),
+                        value: Some(AnyValue {
+                            value: Some(Value::BoolValue(true)),
+                        }),
+                    },
+                    str_attr("s", "h"),
+                    KeyValue {
+                        key: "m".into(),
+                        value: Some(AnyValue {
+                            value: Some(Value::KvlistValue(KeyValueList::default())),
+                        }),
+                    },
+                ][..]
+            )
+        );
+        assert_eq!(store.attribute_by_id(1), Some(&[str_attr("s", "ok")][..]));
+
+        let limits = limits.with_action(OversizedValueAction::Reject);
+        let options = DecoderOptions::default().with_value_limits(limits);
+        assert!(matches!(
+            Attribute16Store::try_from_with_options(&rb, &options),
+            Err(error::Error::Decode { source, .. })
+                if matches!(*source, error::Error::AttributeValueTooLarge { len: 6, max_len: 2, .. })
+        ));
+        let options = options.with_skip_bad_rows(true);
+        let store = Attribute16Store::try_from_with_options(&rb, &options).unwrap();
+        assert_eq!(store.attribute_by_id(0), None);
+        assert_eq!(store.attribute_by_id(1), Some(&[str_attr("s", "ok")][..]));
+
+        // the depth limit allows the maps of scalars
+        let flat = cbor::encode_pcommon_val(Some(&Value::KvlistValue(KeyValueList::new(vec![
+            KeyValue::new("a", AnyValue {
+                value: Some(Value::IntValue(1)),
+            }),
+        ]))));
+        assert!(limits.decode_serialized(&flat).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_attributes_for_range() {
+        let mut store = Attribute32Store::default();
+        for id in [1, 2, 4, 7, 1000] {
+            let _ = store
+                .attribute_by_ids
+                .insert(id, vec![attr("id", i64::from(id))]);
+        }
+        let ids = |range| {
+            store
+                .attributes_for_range(range)
+                .map(|(id, attrs)| {
+                    assert_eq!(attrs, &[attr("id", i64::from(id))]);
+                    id
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // ranges narrower than the store are looked up id by id
+        assert_eq!(ids(2..5), vec![2, 4]);
+        assert_eq!(ids(7..7), Vec::<u32>::new());
+        // and the wider ones scanned
+        assert_eq!(ids(0..u32::MAX), vec![1, 2, 4, 7, 1000]);
+        assert_eq!(ids(3..1000), vec![4, 7]);
+
+        assert_eq!(store.len(), 5);
+        assert!(!store.is_empty());
+        let all: Vec<_> = store.iter().map(|(id, _)| id).collect();
+        assert_eq!(all, vec![1, 2, 4, 7, 1000]);
+        assert!(Attribute32Store::default().iter().next().is_none());
+        assert!(Attribute32Store::default().is_empty());
+    }
+
+    #[test]
+    fn test_shared_attributes() {
+        let mut store = Attribute16Store::default();
+        for (id, value) in [(0, 1), (1, 1), (2, 2), (3, 1)] {
+            let _ = store.attribute_by_ids.insert(id, vec![attr("a", value)]);
+        }
+        let first = store.shared_attribute_by_id(0).unwrap();
+        assert_eq!(&*first, &[attr("a", 1)]);
+        assert!(Arc::ptr_eq(
+            &first,
+            &store.shared_attribute_by_id(0).unwrap()
+        ));
+        // the equal set resolved next shares the copy, the ones after another set don't
+        assert!(Arc::ptr_eq(
+            &first,
+            &store.shared_attribute_by_id(1).unwrap()
+        ));
+        assert_eq!(&*store.shared_attribute_by_id(2).unwrap(), &[attr("a", 2)]);
+        let fourth = store.shared_attribute_by_id(3).unwrap();
+        assert_eq!(fourth, first);
+        assert!(!Arc::ptr_eq(&first, &fourth));
+        assert!(store.shared_attribute_by_id(4).is_none());
+
+        // the merged sets are copied again
+        let mut other = Attribute16Store::default();
+        let _ = other.attribute_by_ids.insert(0, vec![attr("b", 3)]);
+        store.merge(&other, MergeConflict::FirstWins).unwrap();
+        assert_eq!(&*store.shared_attribute_by_id(0).unwrap(), &[
+            attr("a", 1),
+            attr("b", 3)
+        ]);
+        assert_eq!(&*first, &[attr("a", 1)]);
+    }
+
+    #[test]
+    fn test_key_index() {
+        let mut index = KeyIndex::<u16>::default();
+        let mut wide = Vec::new();
+        let mut narrow = Vec::new();
+        let keys: Vec<Arc<str>> = (0..40).map(|i| Arc::from(format!("k{i}"))).collect();
+        for (i, key) in keys.iter().enumerate() {
+            index.insert(0, &mut wide, key, attr(key, i as i64));
+        }
+        for key in &keys[..4] {
+            index.insert(1, &mut narrow, key, attr(key, 0));
+        }
+        // the keys already in a set are overwritten in place, scanned or indexed
+        index.insert(0, &mut wide, &keys[3], attr("k3", 100));
+        index.insert(0, &mut wide, &keys[30], attr("k30", 100));
+        index.insert(1, &mut narrow, &keys[3], attr("k3", 100));
+
+        assert_eq!(wide.len(), 40);
+        assert_eq!(wide[3], attr("k3", 100));
+        assert_eq!(wide[30], attr("k30", 100));
+        assert_eq!(wide[39], attr("k39", 39));
+        assert_eq!(narrow.len(), 4);
+        assert_eq!(narrow[3], attr("k3", 100));
+        assert!(!index.positions.contains_key(&1));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = Attribute16Store::default();
+        let _ = first
+            .attribute_by_ids
+            .insert(0, vec![attr("a", 1), attr("b", 2)]);
+        let mut last = Attribute16Store::default();
+        let _ = last
+            .attribute_by_ids
+            .insert(0, vec![attr("b", 3), attr("c", 4)]);
+        let _ = last.attribute_by_ids.insert(1, vec![attr("a", 1)]);
+
+        let mut store = Attribute16Store::default();
+        store.merge(&first, MergeConflict::Error).unwrap();
+        assert!(store.merge(&last, MergeConflict::Error).is_err());
+
+        let mut store = Attribute16Store::default();
+        store.merge(&first, MergeConflict::Error).unwrap();
+        store.merge(&last, MergeConflict::FirstWins).unwrap();
+        assert_eq!(
+            store.attribute_by_id(0),
+            Some(&[attr("a", 1), attr("b", 2), attr("c", 4)][..])
+        );
+        assert_eq!(store.attribute_by_id(1), Some(&[attr("a", 1)][..]));
+
+        assert_eq!(
+            first
+                .overlay(0, &[attr("b", 3), attr("a", 1)], MergeConflict::LastWins)
+                .unwrap(),
+            vec![attr("a", 1), attr("b", 3)]
+        );
+        // equal values are not conflicts
+        assert_eq!(
+            first
+                .overlay(0, &[attr("a", 1)], MergeConflict::Error)
+                .unwrap(),
+            vec![attr("a", 1), attr("b", 2)]
+        );
+    }
+
+    #[test]
+    fn test_delta_id_policy() {
+        use arrow::array::{Int64Array, StringArray, UInt8Array, UInt16Array};
+        use arrow::datatypes::{DataType, Field, Schema};
+
+        use crate::otap::{Logs, OtapBatch};
+
+        let mut otap_batch = OtapBatch::Logs(Logs::default());
+        otap_batch.set(
+            ArrowPayloadType::LogAttrs,
+            RecordBatch::try_new(
+                Arc::new(Schema::new(vec![
+                    Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                    Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                    Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                    Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                    Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+                ])),
+                vec![
+                    Arc::new(UInt16Array::from(vec![0, u16::MAX])),
+                    Arc::new(UInt8Array::from(vec![AttributeValueType::Int as u8; 2])),
+                    Arc::new(StringArray::from(vec!["a", "b"])),
+                    Arc::new(StringArray::from(vec![None::<&str>; 2])),
+                    Arc::new(Int64Array::from(vec![1, 2])),
+                ],
+            )
+            .unwrap(),
+        );
+        let store = |policy| {
+            Attribute16Store::from_payload(
+                &otap_batch,
+                ArrowPayloadType::LogAttrs,
+                &DecoderOptions::default().with_delta_id_policy(policy),
+                &mut DecodeReport::default(),
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        let mut report = DecodeReport::default();
+        for (policy, expected) in [
+            (DeltaIdPolicy::Clamp, Some(&[attr("b", 2)][..])),
+            (DeltaIdPolicy::Skip, None),
+        ] {
+            let mut store = store(policy);
+            assert_eq!(
+                store.attribute_by_delta_id(u16::MAX, &mut report).unwrap(),
+                Some(&[attr("b", 2)][..])
+            );
+            // the id wraps around
+            assert_eq!(
+                store.attribute_by_delta_id(1, &mut report).unwrap(),
+                expected
+            );
+            // the id is still the last one
+            assert_eq!(
+                store.attribute_by_delta_id(0, &mut report).unwrap(),
+                Some(&[attr("b", 2)][..])
+            );
+        }
+
+        let mut store = store(DeltaIdPolicy::Error);
+        assert!(store.attribute_by_delta_id(u16::MAX, &mut report).is_ok());
+        assert!(store.attribute_by_delta_id(1, &mut report).is_err());
+        assert_eq!(report.delta_id_anomalies(ArrowPayloadType::LogAttrs), 3);
+        assert!(!report.is_empty());
     }
 }
@@ -0,0 +1,101 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Representations of the attributes kept by an
+//! [`AttributeStore`](crate::otlp::attributes::store::AttributeStore).
+//!
+//! The stores keep the prost `KeyValue`s the OTLP messages are built from by default. The
+//! ones feeding another representation keep [`Attribute`]s of their value type instead,
+//! e.g. `Attribute<Value>`, `Attribute<AnyValue>` or a type of their own implementing
+//! `From<Value>`, so the decoded values are converted once rather than converted back out
+//! of the `KeyValue`s.
+
+use std::sync::Arc;
+
+use crate::otlp::attributes::parent_id::ParentId;
+use crate::otlp::pool::{AttributeMap, DecoderPool};
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+
+/// An attribute with a value of type `V`, see the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attribute<V> {
+    /// Key of the attribute, interned by the decoder.
+    pub key: Arc<str>,
+    /// Value of the attribute.
+    pub value: V,
+}
+
+/// Representation of the attributes of a store.
+pub trait StoredAttribute: Sized {
+    /// Returns the attribute of the key and the decoded value.
+    fn new(key: &Arc<str>, value: Value) -> Self;
+
+    /// Returns the key of the attribute.
+    fn key(&self) -> &str;
+
+    /// Takes the map of a store out of the pool, the maps of the representations the pool
+    /// doesn't keep are allocated.
+    fn take_map<T: ParentId>(_pool: &DecoderPool) -> AttributeMap<T, Self> {
+        AttributeMap::default()
+    }
+
+    /// Takes the spare vectors of the attribute sets out of the pool.
+    fn take_sets(_pool: &DecoderPool) -> Vec<Vec<Self>> {
+        Vec::new()
+    }
+
+    /// Returns the map of a dropped store to the pool.
+    fn recycle_map<T: ParentId>(_pool: &DecoderPool, _map: AttributeMap<T, Self>) {}
+
+    /// Returns the spare vectors of the attribute sets to the pool.
+    fn recycle_sets(_pool: &DecoderPool, _sets: Vec<Vec<Self>>) {}
+}
+
+impl StoredAttribute for KeyValue {
+    fn new(key: &Arc<str>, value: Value) -> Self {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue::from(value)),
+        }
+    }
+
+    fn key(&self) -> &str {
+        &self.key
+    }
+
+    fn take_map<T: ParentId>(pool: &DecoderPool) -> AttributeMap<T, Self> {
+        DecoderPool::take_map(T::pooled_maps(pool))
+    }
+
+    fn take_sets(pool: &DecoderPool) -> Vec<Vec<Self>> {
+        pool.take_key_values()
+    }
+
+    fn recycle_map<T: ParentId>(pool: &DecoderPool, map: AttributeMap<T, Self>) {
+        pool.recycle(T::pooled_maps(pool), map);
+    }
+
+    fn recycle_sets(pool: &DecoderPool, sets: Vec<Vec<Self>>) {
+        pool.recycle_key_values(sets);
+    }
+}
+
+impl<V: From<Value>> StoredAttribute for Attribute<V> {
+    fn new(key: &Arc<str>, value: Value) -> Self {
+        Attribute {
+            key: key.clone(),
+            value: V::from(value),
+        }
+    }
+
+    fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl From<Value> for AnyValue {
+    fn from(value: Value) -> Self {
+        AnyValue { value: Some(value) }
+    }
+}
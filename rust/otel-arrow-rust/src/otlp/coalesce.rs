@@ -0,0 +1,203 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Coalescing of the duplicated resources and scopes of decoded requests.
+//!
+//! A request decoded from several batches, or from a batch whose producer didn't group its
+//! entities, can hold the same resource or scope several times. The functions of this
+//! module move the records of identical resources and scopes under the first of them, in
+//! place, keeping the order of the records. Two resources or scopes are identical when
+//! their attribute sets, dropped attributes counts and schema urls are equal, whatever the
+//! order of their attributes.
+
+use std::collections::HashMap;
+
+use prost::Message;
+
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::{InstrumentationScope, KeyValue, KeyValueList};
+use crate::proto::opentelemetry::resource::v1::Resource;
+
+/// Moves the records of the identical resources and scopes under the first of them.
+macro_rules! coalesce {
+    ($request:ident, $resources:ident, $scopes:ident, $records:ident) => {
+        let mut resource_ids = HashMap::new();
+        let mut resources = Vec::with_capacity($request.$resources.len());
+        for resource in std::mem::take(&mut $request.$resources) {
+            let key = resource_key(resource.resource.as_ref(), &resource.schema_url);
+            match resource_ids.get(&key) {
+                None => {
+                    let _ = resource_ids.insert(key, resources.len());
+                    resources.push(resource);
+                }
+                Some(&idx) => {
+                    resources[idx].$scopes.extend(resource.$scopes);
+                }
+            }
+        }
+
+        for resource in &mut resources {
+            let mut scope_ids = HashMap::new();
+            let mut scopes = Vec::with_capacity(resource.$scopes.len());
+            for scope in std::mem::take(&mut resource.$scopes) {
+                let key = scope_key(scope.scope.as_ref(), &scope.schema_url);
+                match scope_ids.get(&key) {
+                    None => {
+                        let _ = scope_ids.insert(key, scopes.len());
+                        scopes.push(scope);
+                    }
+                    Some(&idx) => {
+                        scopes[idx].$records.extend(scope.$records);
+                    }
+                }
+            }
+            resource.$scopes = scopes;
+        }
+        $request.$resources = resources;
+    };
+}
+
+/// Coalesces the identical resources and scopes of the logs request.
+pub fn coalesce_logs(request: &mut ExportLogsServiceRequest) {
+    coalesce!(request, resource_logs, scope_logs, log_records);
+}
+
+/// Coalesces the identical resources and scopes of the metrics request. The metrics
+/// themselves are not merged, even if they have the same name.
+pub fn coalesce_metrics(request: &mut ExportMetricsServiceRequest) {
+    coalesce!(request, resource_metrics, scope_metrics, metrics);
+}
+
+/// Coalesces the identical resources and scopes of the traces request.
+pub fn coalesce_traces(request: &mut ExportTraceServiceRequest) {
+    coalesce!(request, resource_spans, scope_spans, spans);
+}
+
+fn resource_key(resource: Option<&Resource>, schema_url: &str) -> Vec<u8> {
+    // a missing resource is the same as an empty one
+    let (attributes, dropped_attributes_count) = resource
+        .map(|resource| (&resource.attributes[..], resource.dropped_attributes_count))
+        .unwrap_or_default();
+    let mut key = Vec::new();
+    push_attributes(&mut key, attributes);
+    key.extend_from_slice(&dropped_attributes_count.to_le_bytes());
+    key.extend_from_slice(schema_url.as_bytes());
+    key
+}
+
+fn scope_key(scope: Option<&InstrumentationScope>, schema_url: &str) -> Vec<u8> {
+    // a missing scope is the same as an empty one
+    let default = InstrumentationScope::default();
+    let scope = scope.unwrap_or(&default);
+    let mut key = Vec::new();
+    push_attributes(&mut key, &scope.attributes);
+    key.extend_from_slice(&scope.dropped_attributes_count.to_le_bytes());
+    // the strings are length delimited so that they can't run into each other
+    push_str(&mut key, &scope.name);
+    push_str(&mut key, &scope.version);
+    key.extend_from_slice(schema_url.as_bytes());
+    key
+}
+
+/// Appends the attributes sorted by key, so that the key doesn't depend on their order.
+fn push_attributes(key: &mut Vec<u8>, attributes: &[KeyValue]) {
+    let mut values = attributes.to_vec();
+    values.sort_by(|a, b| a.key.cmp(&b.key));
+    // safety: encoding into a Vec can't fail
+    KeyValueList { values }
+        .encode_length_delimited(key)
+        .expect("the key buffer grows");
+}
+
+fn push_str(key: &mut Vec<u8>, value: &str) {
+    key.extend_from_slice(&value.len().to_le_bytes());
+    key.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::common::v1::AnyValue;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    fn attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue(value.into())),
+            }),
+        }
+    }
+
+    fn log(body: &str) -> LogRecord {
+        LogRecord {
+            body: Some(AnyValue {
+                value: Some(Value::StringValue(body.into())),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn resource_logs(attributes: Vec<KeyValue>, scope_logs: Vec<ScopeLogs>) -> ResourceLogs {
+        ResourceLogs {
+            resource: Some(Resource {
+                attributes,
+                ..Default::default()
+            }),
+            scope_logs,
+            ..Default::default()
+        }
+    }
+
+    fn scope_logs(name: &str, log_records: Vec<LogRecord>) -> ScopeLogs {
+        ScopeLogs {
+            scope: Some(InstrumentationScope {
+                name: name.into(),
+                ..Default::default()
+            }),
+            log_records,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_coalesce_logs() {
+        let a = || vec![attribute("service.name", "a"), attribute("host", "h")];
+        // same attributes in another order
+        let a_reordered = vec![attribute("host", "h"), attribute("service.name", "a")];
+        let b = || vec![attribute("service.name", "b")];
+        let mut request = ExportLogsServiceRequest {
+            resource_logs: vec![
+                resource_logs(a(), vec![scope_logs("s0", vec![log("1")])]),
+                resource_logs(b(), vec![scope_logs("s0", vec![log("2")])]),
+                resource_logs(a_reordered, vec![
+                    scope_logs("s1", vec![log("3")]),
+                    scope_logs("s0", vec![log("4")]),
+                ]),
+                ResourceLogs {
+                    schema_url: "https://opentelemetry.io/schemas/1.0.0".into(),
+                    ..resource_logs(b(), vec![scope_logs("s0", vec![log("5")])])
+                },
+            ],
+        };
+        coalesce_logs(&mut request);
+
+        assert_eq!(request, ExportLogsServiceRequest {
+            resource_logs: vec![
+                resource_logs(a(), vec![
+                    scope_logs("s0", vec![log("1"), log("4")]),
+                    scope_logs("s1", vec![log("3")]),
+                ]),
+                resource_logs(b(), vec![scope_logs("s0", vec![log("2")])]),
+                ResourceLogs {
+                    schema_url: "https://opentelemetry.io/schemas/1.0.0".into(),
+                    ..resource_logs(b(), vec![scope_logs("s0", vec![log("5")])])
+                },
+            ],
+        });
+    }
+}
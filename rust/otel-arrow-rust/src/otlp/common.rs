@@ -12,7 +12,7 @@ use arrow::datatypes::{DataType, Field, Fields};
 use snafu::OptionExt;
 use std::sync::LazyLock;
 
-pub(in crate::otlp) struct ResourceArrays<'a> {
+pub(crate) struct ResourceArrays<'a> {
     pub id: &'a UInt16Array,
     pub dropped_attributes_count: Option<&'a UInt32Array>,
     pub schema_url: Option<StringArrayAccessor<'a>>,
@@ -56,7 +56,7 @@ impl<'a> TryFrom<&'a RecordBatch> for ResourceArrays<'a> {
             id: struct_col_accessor.primitive_column(consts::ID)?,
             dropped_attributes_count: struct_col_accessor
                 .primitive_column_op(consts::DROPPED_ATTRIBUTES_COUNT)?,
-            schema_url: struct_col_accessor.string_column_op(consts::SCHEMA_URL)?,
+            schema_url: struct_col_accessor.accessor_column_op(consts::SCHEMA_URL)?,
         })
     }
 }
@@ -100,20 +100,20 @@ impl<'a> TryFrom<&'a RecordBatch> for ScopeArrays<'a> {
     type Error = error::Error;
 
     fn try_from(rb: &'a RecordBatch) -> Result<Self, Self::Error> {
-        let struct_array = get_required_array(rb, consts::RESOURCE)?;
+        let struct_array = get_required_array(rb, consts::SCOPE)?;
         let scope_array = struct_array
             .as_any()
             .downcast_ref::<StructArray>()
             .with_context(|| error::ColumnDataTypeMismatchSnafu {
-                name: consts::RESOURCE,
+                name: consts::SCOPE,
                 actual: struct_array.data_type().clone(),
                 expect: Self::data_type().clone(),
             })?;
         let struct_col_accessor = StructColumnAccessor::new(scope_array);
 
         Ok(Self {
-            name: struct_col_accessor.string_column_op(consts::NAME)?,
-            version: struct_col_accessor.string_column_op(consts::VERSION)?,
+            name: struct_col_accessor.accessor_column_op(consts::NAME)?,
+            version: struct_col_accessor.accessor_column_op(consts::VERSION)?,
             dropped_attributes_count: struct_col_accessor
                 .primitive_column_op(consts::DROPPED_ATTRIBUTES_COUNT)?,
             id: struct_col_accessor.primitive_column_op(consts::ID)?,
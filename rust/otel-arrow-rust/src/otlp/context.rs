@@ -0,0 +1,175 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Decoding contexts isolating the state kept across batches by the decoder, e.g. one per
+//! tenant or stream of a receiver.
+//!
+//! The [`StringInterner`] and the [`DecoderPool`] of [`DecoderOptions`] are shared by all
+//! the batches decoded with the options and their clones. A [`DecoderContext`] holds options
+//! with an interner and a pool of their own, so the keys and buffers of a tenant don't grow
+//! the state of the others and are freed with the context. The memory they hold can be
+//! capped with [`MemoryLimits`] and observed with [`DecoderContext::memory_usage`].
+//!
+//! The attribute stores of a batch only live while the batch is decoded, their buffers are
+//! returned to the pool of the context afterwards.
+
+use std::sync::Arc;
+
+use crate::Consumer;
+use crate::otlp::interner::StringInterner;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::pool::DecoderPool;
+
+/// Caps of the memory held by a context, none being set by default.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MemoryLimits {
+    max_interned_bytes: Option<usize>,
+    max_pooled_key_values: Option<usize>,
+}
+
+impl MemoryLimits {
+    /// Creates limits capping nothing.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the total length of the interned strings, see
+    /// [`StringInterner::with_max_bytes`].
+    #[must_use]
+    pub fn with_max_interned_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_interned_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Caps the number of `KeyValue` vectors kept by the pool, see
+    /// [`DecoderPool::with_max_key_values`].
+    #[must_use]
+    pub fn with_max_pooled_key_values(mut self, max_key_values: usize) -> Self {
+        self.max_pooled_key_values = Some(max_key_values);
+        self
+    }
+
+    /// Returns the cap of the total length of the interned strings, if any.
+    #[must_use]
+    pub fn max_interned_bytes(&self) -> Option<usize> {
+        self.max_interned_bytes
+    }
+
+    /// Returns the cap of the number of pooled `KeyValue` vectors, if any.
+    #[must_use]
+    pub fn max_pooled_key_values(&self) -> Option<usize> {
+        self.max_pooled_key_values
+    }
+}
+
+/// Memory held by a context.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MemoryUsage {
+    /// Number of interned strings.
+    pub interned_strings: usize,
+    /// Total length of the interned strings.
+    pub interned_bytes: usize,
+    /// Number of attribute maps and `KeyValue` vectors held by the pool.
+    pub pooled_buffers: usize,
+}
+
+/// Decoder options with their own interner and pool, see the module documentation. The
+/// clones of a context share its state.
+#[derive(Clone, Debug)]
+pub struct DecoderContext {
+    options: DecoderOptions,
+}
+
+impl DecoderContext {
+    /// Creates a context decoding with the given options, but with an empty interner and
+    /// pool of its own.
+    #[must_use]
+    pub fn new(options: &DecoderOptions) -> Self {
+        Self::with_memory_limits(options, MemoryLimits::default())
+    }
+
+    /// Same as [`Self::new`], capping the memory held by the interner and the pool.
+    #[must_use]
+    pub fn with_memory_limits(options: &DecoderOptions, limits: MemoryLimits) -> Self {
+        let interner = match limits.max_interned_bytes {
+            Some(max_bytes) => StringInterner::with_max_bytes(max_bytes),
+            None => StringInterner::default(),
+        };
+        let pool = match limits.max_pooled_key_values {
+            Some(max_key_values) => DecoderPool::with_max_key_values(max_key_values),
+            None => DecoderPool::default(),
+        };
+        Self {
+            options: options
+                .clone()
+                .with_interner(Arc::new(interner))
+                .with_pool(Arc::new(pool)),
+        }
+    }
+
+    /// Returns the options of the context, to decode the batches with.
+    #[must_use]
+    pub fn options(&self) -> &DecoderOptions {
+        &self.options
+    }
+
+    /// Creates a consumer decoding with the options of the context.
+    #[must_use]
+    pub fn consumer(&self) -> Consumer {
+        Consumer::with_options(self.options.clone())
+    }
+
+    /// Returns the memory held by the interner and the pool of the context.
+    #[must_use]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            interned_strings: self.options.interner.len(),
+            interned_bytes: self.options.interner.bytes(),
+            pooled_buffers: self.options.pool.len(),
+        }
+    }
+
+    /// Frees the interned strings and the pooled buffers, e.g. when the tenant goes idle.
+    pub fn clear(&self) {
+        self.options.interner.clear();
+        self.options.pool.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otlp::traces::traces_from_with_options;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_isolated_contexts() {
+        let options = DecoderOptions::default();
+        let tenant_a = DecoderContext::new(&options);
+        let tenant_b = DecoderContext::with_memory_limits(
+            &options,
+            MemoryLimits::new()
+                .with_max_interned_bytes(4)
+                .with_max_pooled_key_values(2),
+        );
+
+        let _ = traces_from_with_options(traces_batch(8, 2), tenant_a.options()).unwrap();
+        let usage = tenant_a.memory_usage();
+        // key0 and key1
+        assert_eq!((usage.interned_strings, usage.interned_bytes), (2, 8));
+        assert!(usage.pooled_buffers > 2);
+        assert_eq!(tenant_b.memory_usage(), MemoryUsage::default());
+        assert!(options.interner.is_empty());
+
+        let _ = traces_from_with_options(traces_batch(8, 2), tenant_b.options()).unwrap();
+        let usage = tenant_b.memory_usage();
+        assert_eq!((usage.interned_strings, usage.interned_bytes), (1, 4));
+        // the map of the span attributes and two vectors
+        assert_eq!(usage.pooled_buffers, 3);
+
+        tenant_a.clear();
+        assert_eq!(tenant_a.memory_usage(), MemoryUsage::default());
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The OTLP enums stored in the columns of the payloads: the span kinds, the span status
+//! codes and the severity numbers of the log records.
+//!
+//! The columns are `Int32` arrays, or `UInt8` arrays as the values fit in a byte, either
+//! unencoded or dictionary encoded, see [`EnumArrayAccessor`](crate::arrays::EnumArrayAccessor).
+//! The decoders copy their values as is by default, OTLP allowing the receivers to forward
+//! the values they don't know. [`EnumValidationPolicy::Strict`] rejects these values
+//! instead, see [`validate_enum`]. The severity numbers also have a canonical text, see
+//! [`severity_text`].
+
+use crate::error::{self, Result};
+use crate::otlp::options::EnumValidationPolicy;
+use crate::proto::opentelemetry::logs::v1::SeverityNumber;
+use crate::proto::opentelemetry::trace::v1::span::SpanKind;
+use crate::proto::opentelemetry::trace::v1::status::StatusCode;
+
+/// An OTLP enum stored in a column.
+pub trait OtlpEnum: Copy + TryFrom<i32> + Into<i32> {
+    /// Name of the enum, used in the errors.
+    const NAME: &'static str;
+
+    /// Returns the enum of the value, `None` if OTLP doesn't define it.
+    #[must_use]
+    fn from_value(value: i32) -> Option<Self> {
+        Self::try_from(value).ok()
+    }
+
+    /// Returns the enum of the value of a `UInt8` column, `None` if OTLP doesn't define it.
+    #[must_use]
+    fn from_u8(value: u8) -> Option<Self> {
+        Self::from_value(i32::from(value))
+    }
+}
+
+impl OtlpEnum for SpanKind {
+    const NAME: &'static str = "SpanKind";
+}
+
+impl OtlpEnum for StatusCode {
+    const NAME: &'static str = "StatusCode";
+}
+
+impl OtlpEnum for SeverityNumber {
+    const NAME: &'static str = "SeverityNumber";
+}
+
+/// Checks the value of an `E` column against the policy, returning the value to decode.
+pub fn validate_enum<E: OtlpEnum>(value: i32, policy: EnumValidationPolicy) -> Result<i32> {
+    match policy {
+        EnumValidationPolicy::Passthrough => Ok(value),
+        EnumValidationPolicy::Strict => match E::from_value(value) {
+            Some(_) => Ok(value),
+            None => error::InvalidEnumValueSnafu {
+                name: E::NAME,
+                value,
+            }
+            .fail(),
+        },
+    }
+}
+
+/// Returns the short name of the severity number, e.g. `INFO` or `WARN2`, as defined by the
+/// log data model. `None` if the number is unspecified or not defined by OTLP.
+#[must_use]
+pub fn severity_text(severity_number: i32) -> Option<&'static str> {
+    match SeverityNumber::from_value(severity_number)? {
+        SeverityNumber::Unspecified => None,
+        number => number.as_str_name().strip_prefix("SEVERITY_NUMBER_"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_enum() {
+        assert_eq!(SpanKind::from_u8(2), Some(SpanKind::Server));
+        assert_eq!(StatusCode::from_value(3), None);
+        assert_eq!(SeverityNumber::from_value(24), Some(SeverityNumber::Fatal4));
+
+        assert_eq!(severity_text(0), None);
+        assert_eq!(severity_text(9), Some("INFO"));
+        assert_eq!(severity_text(14), Some("WARN2"));
+        assert_eq!(severity_text(25), None);
+
+        let passthrough = EnumValidationPolicy::Passthrough;
+        assert_eq!(validate_enum::<SpanKind>(42, passthrough).unwrap(), 42);
+        let strict = EnumValidationPolicy::Strict;
+        assert_eq!(validate_enum::<StatusCode>(2, strict).unwrap(), 2);
+        assert!(matches!(
+            validate_enum::<SeverityNumber>(25, strict),
+            Err(error::Error::InvalidEnumValue {
+                name: "SeverityNumber",
+                value: 25,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,248 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of the span events into log records, for the backends modeling the events as
+//! logs.
+//!
+//! Following the OpenTelemetry events conventions, the log record of an event has the name
+//! of the event as its `event_name`, the time and the attributes of the event, and the
+//! trace id, span id and trace flags of its span. The name is also set as the `event.name`
+//! attribute, the convention predating the field, for the consumers not reading the field
+//! yet. The log records keep the resource and scope of their span, the resources and scopes
+//! without events being left out.
+//!
+//! [`span_events_to_logs`] converts a decoded request, [`span_events_to_logs_batch`] an OTAP
+//! traces batch into an OTAP logs batch, streaming the spans out of the batch with a
+//! [`RecordVisitor`] instead of collecting them into a request first.
+
+use crate::encode::logs::LogsProducer;
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::traces::visit_spans;
+use crate::otlp::visitor::RecordVisitor;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+use crate::proto::opentelemetry::logs::v1::LogRecord;
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::proto::opentelemetry::trace::v1::Span;
+use crate::proto::opentelemetry::trace::v1::span::Event;
+
+/// Attribute carrying the name of the event.
+pub const EVENT_NAME_ATTRIBUTE: &str = "event.name";
+
+/// Mask of the W3C trace flags in the flags of a span, the other bits not applying to logs.
+const TRACE_FLAGS_MASK: u32 = 0xff;
+
+/// Returns the log records of the events of the spans of the request.
+#[must_use]
+pub fn span_events_to_logs(request: &ExportTraceServiceRequest) -> ExportLogsServiceRequest {
+    let mut visitor = EventLogsVisitor::default();
+    for resource_spans in &request.resource_spans {
+        visitor.visit_resource(
+            resource_spans.resource.clone().unwrap_or_default(),
+            resource_spans.schema_url.clone(),
+        );
+        for scope_spans in &resource_spans.scope_spans {
+            visitor.visit_scope(
+                scope_spans.scope.clone().unwrap_or_default(),
+                scope_spans.schema_url.clone(),
+            );
+            for span in &scope_spans.spans {
+                visitor.visit_span_events(span);
+            }
+        }
+    }
+    visitor.finish()
+}
+
+/// Decodes the traces batch and returns the logs batch of the events of its spans.
+pub fn span_events_to_logs_batch(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<OtapBatch> {
+    let mut visitor = EventLogsVisitor::default();
+    let _ = visit_spans(traces_otap_batch, options, &mut visitor)?;
+    LogsProducer::new().produce(&visitor.finish())
+}
+
+/// Returns the log record of an event of the span.
+#[must_use]
+pub fn event_to_log_record(span: &Span, event: &Event) -> LogRecord {
+    let mut attributes = Vec::with_capacity(event.attributes.len() + 1);
+    attributes.push(KeyValue {
+        key: EVENT_NAME_ATTRIBUTE.to_string(),
+        value: Some(AnyValue {
+            value: Some(Value::StringValue(event.name.clone())),
+        }),
+    });
+    attributes.extend(event.attributes.iter().cloned());
+    LogRecord {
+        time_unix_nano: event.time_unix_nano,
+        event_name: event.name.clone(),
+        attributes,
+        dropped_attributes_count: event.dropped_attributes_count,
+        flags: span.flags & TRACE_FLAGS_MASK,
+        trace_id: span.trace_id.clone(),
+        span_id: span.span_id.clone(),
+        ..Default::default()
+    }
+}
+
+/// Visitor collecting the log records of the events of the visited spans.
+#[derive(Default)]
+struct EventLogsVisitor {
+    request: ExportLogsServiceRequest,
+}
+
+impl EventLogsVisitor {
+    fn visit_span_events(&mut self, span: &Span) {
+        for event in &span.events {
+            self.request.visit_record(event_to_log_record(span, event));
+        }
+    }
+
+    /// Returns the request, without the scopes and resources that had no events.
+    fn finish(mut self) -> ExportLogsServiceRequest {
+        for resource_logs in &mut self.request.resource_logs {
+            resource_logs
+                .scope_logs
+                .retain(|scope_logs| !scope_logs.log_records.is_empty());
+        }
+        self.request
+            .resource_logs
+            .retain(|resource_logs| !resource_logs.scope_logs.is_empty());
+        self.request
+    }
+}
+
+impl RecordVisitor<Span> for EventLogsVisitor {
+    fn visit_resource(&mut self, resource: Resource, schema_url: String) {
+        RecordVisitor::<LogRecord>::visit_resource(&mut self.request, resource, schema_url);
+    }
+
+    fn visit_scope(&mut self, scope: InstrumentationScope, schema_url: String) {
+        RecordVisitor::<LogRecord>::visit_scope(&mut self.request, scope, schema_url);
+    }
+
+    fn visit_record(&mut self, span: Span) {
+        self.visit_span_events(&span);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::traces::TracesProducer;
+    use crate::otlp::logs::logs_from;
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans};
+
+    fn span(span_id: u8, events: Vec<Event>) -> Span {
+        Span {
+            trace_id: vec![1; 16],
+            span_id: vec![span_id; 8],
+            name: format!("span-{span_id}"),
+            // trace flags and the parent is remote bits
+            flags: 0x301,
+            start_time_unix_nano: 10,
+            end_time_unix_nano: 20,
+            events,
+            ..Default::default()
+        }
+    }
+
+    fn request() -> ExportTraceServiceRequest {
+        let exception = Event {
+            time_unix_nano: 15,
+            name: "exception".into(),
+            attributes: vec![KeyValue {
+                key: "exception.message".into(),
+                value: Some(AnyValue {
+                    value: Some(Value::StringValue("boom".into())),
+                }),
+            }],
+            dropped_attributes_count: 1,
+        };
+        let retry = Event {
+            time_unix_nano: 12,
+            name: "retry".into(),
+            ..Default::default()
+        };
+        ExportTraceServiceRequest {
+            resource_spans: vec![
+                ResourceSpans {
+                    resource: Some(Resource::default()),
+                    scope_spans: vec![
+                        ScopeSpans {
+                            scope: Some(InstrumentationScope {
+                                name: "a".into(),
+                                ..Default::default()
+                            }),
+                            spans: vec![span(1, vec![retry, exception]), span(2, vec![])],
+                            schema_url: "https://scope".into(),
+                        },
+                        // no events, left out of the logs
+                        ScopeSpans {
+                            scope: Some(InstrumentationScope {
+                                name: "b".into(),
+                                ..Default::default()
+                            }),
+                            spans: vec![span(3, vec![])],
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                },
+                ResourceSpans {
+                    resource: Some(Resource::default()),
+                    scope_spans: vec![ScopeSpans {
+                        scope: Some(InstrumentationScope::default()),
+                        spans: vec![span(4, vec![])],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_span_events_to_logs() {
+        let logs = span_events_to_logs(&request());
+        assert_eq!(logs.resource_logs.len(), 1);
+        let scope_logs = &logs.resource_logs[0].scope_logs;
+        assert_eq!(scope_logs.len(), 1);
+        assert_eq!(scope_logs[0].scope.as_ref().unwrap().name, "a");
+        assert_eq!(scope_logs[0].schema_url, "https://scope");
+
+        let records = &scope_logs[0].log_records;
+        let names: Vec<_> = records.iter().map(|r| r.event_name.as_str()).collect();
+        assert_eq!(names, vec!["retry", "exception"]);
+        let record = &records[1];
+        assert_eq!(record.time_unix_nano, 15);
+        assert_eq!(record.trace_id, vec![1; 16]);
+        assert_eq!(record.span_id, vec![1; 8]);
+        assert_eq!(record.flags, 0x01);
+        let keys: Vec<_> = record.attributes.iter().map(|a| a.key.as_str()).collect();
+        assert_eq!(keys, vec![EVENT_NAME_ATTRIBUTE, "exception.message"]);
+        assert_eq!(record.dropped_attributes_count, 1);
+    }
+
+    #[test]
+    fn test_span_events_to_logs_batch() {
+        let request = request();
+        let traces_batch = TracesProducer::new().produce(&request).unwrap();
+        let logs_batch =
+            span_events_to_logs_batch(traces_batch, &DecoderOptions::default()).unwrap();
+        let logs = logs_from(logs_batch).unwrap();
+
+        // the producer orders the log records by time
+        let mut expected = span_events_to_logs(&request);
+        let records = &mut expected.resource_logs[0].scope_logs[0].log_records;
+        records.sort_by_key(|r| r.time_unix_nano);
+        assert_eq!(logs, expected);
+    }
+}
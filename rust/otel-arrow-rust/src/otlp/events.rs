@@ -0,0 +1,441 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Warnings about the values the decoder skips or defaults without failing the batch.
+//!
+//! The [`DecodeReport`](crate::otlp::report::DecodeReport) of a batch counts the rows
+//! dropped because of the decoder options, but some rows are skipped whatever the options,
+//! e.g. the attributes of the `Empty` type, and some missing values are replaced by their
+//! default. A [`DecodeEventSink`] set in the
+//! [`DecoderOptions`](crate::otlp::options::DecoderOptions) receives a [`DecodeEvent`] for
+//! each of them, and for each dropped row, so that such data loss is observable.
+//!
+//! A producer sending bad data sends it in every batch, so a [`RateLimitedEventSink`]
+//! bounds the events passed to the sink it wraps, e.g. the one logging them: the first
+//! events of every payload type and kind in a window of time, then a summary of the events
+//! suppressed in the window.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::otlp::report::DroppedRowReason;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+/// What the decoder did with a value.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DecodeEventKind {
+    /// The attribute has the `Empty` type and was skipped.
+    EmptyValue,
+    /// The map or slice attribute has no serialized value and was skipped.
+    MissingSerializedValue,
+    /// The serialized value of the map or slice attribute decodes to no value and was
+    /// skipped.
+    EmptySerializedValue,
+    /// The attribute has no key, the empty key was used.
+    MissingKey,
+    /// The attribute value is over the value limits and was truncated.
+    TruncatedValue,
+    /// The row was dropped, and counted in the report of the batch.
+    DroppedRow(DroppedRowReason),
+}
+
+/// A value skipped or defaulted by the decoder.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeEvent {
+    /// Type of the payload of the row, `Unknown` for the record batches decoded without a
+    /// payload type.
+    pub payload_type: ArrowPayloadType,
+    /// Index of the row in its record batch.
+    pub row: usize,
+    /// What the decoder did.
+    pub kind: DecodeEventKind,
+}
+
+impl fmt::Display for DecodeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let payload_type = self.payload_type.as_str_name();
+        let row = self.row;
+        match self.kind {
+            DecodeEventKind::EmptyValue => {
+                write!(
+                    f,
+                    "skipped empty attribute, payload = {payload_type}, row = {row}"
+                )
+            }
+            DecodeEventKind::MissingSerializedValue => write!(
+                f,
+                "skipped attribute without serialized value, payload = {payload_type}, row = {row}"
+            ),
+            DecodeEventKind::EmptySerializedValue => write!(
+                f,
+                "skipped attribute with empty serialized value, payload = {payload_type}, row = {row}"
+            ),
+            DecodeEventKind::MissingKey => {
+                write!(
+                    f,
+                    "defaulted missing attribute key, payload = {payload_type}, row = {row}"
+                )
+            }
+            DecodeEventKind::TruncatedValue => write!(
+                f,
+                "truncated attribute value, payload = {payload_type}, row = {row}"
+            ),
+            DecodeEventKind::DroppedRow(reason) => {
+                write!(
+                    f,
+                    "dropped row ({reason:?}), payload = {payload_type}, row = {row}"
+                )
+            }
+        }
+    }
+}
+
+/// Events of a payload type and kind suppressed by a [`RateLimitedEventSink`] in a window.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SuppressedEvents {
+    /// Type of the payload of the rows.
+    pub payload_type: ArrowPayloadType,
+    /// What the decoder did.
+    pub kind: DecodeEventKind,
+    /// Number of events suppressed.
+    pub count: u64,
+    /// Length of the window the events were suppressed in.
+    pub window: Duration,
+}
+
+impl fmt::Display for SuppressedEvents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "suppressed {} decode events ({:?}) in the last {:?}, payload = {}",
+            self.count,
+            self.kind,
+            self.window,
+            self.payload_type.as_str_name()
+        )
+    }
+}
+
+/// Receives the [`DecodeEvent`]s of the batches decoded with the options it is set in. It is
+/// implemented for closures taking the event.
+pub trait DecodeEventSink: Send + Sync {
+    /// Handles an event.
+    fn on_event(&self, event: &DecodeEvent);
+
+    /// Handles the summary of the events suppressed by a [`RateLimitedEventSink`] wrapping
+    /// the sink. Ignored by default.
+    fn on_suppressed(&self, summary: &SuppressedEvents) {
+        let _ = summary;
+    }
+}
+
+impl<F> DecodeEventSink for F
+where
+    F: Fn(&DecodeEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &DecodeEvent) {
+        self(event)
+    }
+}
+
+impl fmt::Debug for dyn DecodeEventSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DecodeEventSink")
+    }
+}
+
+/// Sink collecting the events, e.g. to attach them to the response of the batch.
+#[derive(Debug, Default)]
+pub struct DecodeEventCollector {
+    events: Mutex<Vec<DecodeEvent>>,
+}
+
+impl DecodeEventCollector {
+    /// Removes the events collected so far and returns them, in order.
+    pub fn take(&self) -> Vec<DecodeEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl DecodeEventSink for DecodeEventCollector {
+    fn on_event(&self, event: &DecodeEvent) {
+        self.events
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(*event);
+    }
+}
+
+/// Sink passing the first `burst` events of every payload type and kind in a window of
+/// `interval` to the sink it wraps, and the summaries of the events it suppresses once the
+/// window is over, see the module documentation.
+///
+/// The windows are closed by the events that follow them, or by [`Self::flush`]. The sink
+/// reads the clock, which panics on the targets without one, e.g.
+/// `wasm32-unknown-unknown`, unless the events are passed with [`Self::record`].
+#[derive(Debug)]
+pub struct RateLimitedEventSink {
+    inner: Arc<dyn DecodeEventSink>,
+    burst: u64,
+    interval: Duration,
+    window: Mutex<EventWindow>,
+}
+
+/// Counts of the events of the current window, by payload type and kind.
+#[derive(Debug, Default)]
+struct EventWindow {
+    started: Option<Instant>,
+    counts: HashMap<(ArrowPayloadType, DecodeEventKind), u64>,
+}
+
+impl EventWindow {
+    /// Starts a new window at `now`, returning the summaries of the events suppressed in
+    /// the current one.
+    fn close(&mut self, burst: u64, now: Instant) -> Vec<SuppressedEvents> {
+        let window = self.started.replace(now).map_or(Duration::ZERO, |started| {
+            now.saturating_duration_since(started)
+        });
+        let mut summaries: Vec<_> = self
+            .counts
+            .drain()
+            .filter(|(_, count)| *count > burst)
+            .map(|((payload_type, kind), count)| SuppressedEvents {
+                payload_type,
+                kind,
+                count: count - burst,
+                window,
+            })
+            .collect();
+        summaries.sort_by_key(|summary| (summary.payload_type, summary.kind));
+        summaries
+    }
+}
+
+impl RateLimitedEventSink {
+    /// Creates a sink passing at most `burst` events of every payload type and kind per
+    /// `interval` to `inner`.
+    #[must_use]
+    pub fn new(inner: Arc<dyn DecodeEventSink>, burst: u64, interval: Duration) -> Self {
+        Self {
+            inner,
+            burst,
+            interval,
+            window: Mutex::default(),
+        }
+    }
+
+    /// Handles an event received at `now`.
+    pub fn record(&self, event: &DecodeEvent, now: Instant) {
+        let (summaries, forward) = {
+            let mut window = self.window.lock().unwrap_or_else(PoisonError::into_inner);
+            let summaries = match window.started {
+                Some(started) if now.saturating_duration_since(started) < self.interval => {
+                    Vec::new()
+                }
+                _ => window.close(self.burst, now),
+            };
+            let count = window
+                .counts
+                .entry((event.payload_type, event.kind))
+                .or_default();
+            *count += 1;
+            (summaries, *count <= self.burst)
+        };
+        for summary in &summaries {
+            self.inner.on_suppressed(summary);
+        }
+        if forward {
+            self.inner.on_event(event);
+        }
+    }
+
+    /// Closes the current window at `now`, passing the summaries of its suppressed events
+    /// to the wrapped sink, e.g. when the receiver shuts down.
+    pub fn flush(&self, now: Instant) {
+        let summaries = self
+            .window
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .close(self.burst, now);
+        for summary in &summaries {
+            self.inner.on_suppressed(summary);
+        }
+    }
+}
+
+impl DecodeEventSink for RateLimitedEventSink {
+    fn on_event(&self, event: &DecodeEvent) {
+        self.record(event, Instant::now());
+    }
+
+    fn on_suppressed(&self, summary: &SuppressedEvents) {
+        self.inner.on_suppressed(summary);
+    }
+}
+
+/// Sink logging the events as `tracing` warnings.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingEventSink;
+
+#[cfg(feature = "tracing")]
+impl DecodeEventSink for TracingEventSink {
+    fn on_event(&self, event: &DecodeEvent) {
+        tracing::warn!(
+            payload_type = event.payload_type.as_str_name(),
+            row = event.row,
+            "{event}"
+        );
+    }
+
+    fn on_suppressed(&self, summary: &SuppressedEvents) {
+        tracing::warn!(
+            payload_type = summary.payload_type.as_str_name(),
+            count = summary.count,
+            "{summary}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{
+        BinaryArray, Int64Array, RecordBatch, StringArray, UInt8Array, UInt16Array,
+    };
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use crate::otap::{Logs, OtapBatch};
+    use crate::otlp::attributes::store::{Attribute16Store, AttributeValueType};
+    use crate::otlp::options::DecoderOptions;
+    use crate::otlp::report::DecodeReport;
+    use crate::schema::consts;
+
+    #[test]
+    fn test_decode_events() {
+        let rb = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                Field::new(consts::ATTRIBUTE_INT, DataType::Int64, true),
+                Field::new(consts::ATTRIBUTE_SER, DataType::Binary, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0, 0, 0, 0, 0])),
+                Arc::new(UInt8Array::from(vec![
+                    AttributeValueType::Empty as u8,
+                    AttributeValueType::Int as u8,
+                    AttributeValueType::Int as u8,
+                    AttributeValueType::Map as u8,
+                    AttributeValueType::Map as u8,
+                ])),
+                Arc::new(StringArray::from(vec![
+                    Some("a"),
+                    None,
+                    Some("b"),
+                    Some("c"),
+                    Some("d"),
+                ])),
+                Arc::new(StringArray::from(vec![None::<&str>; 5])),
+                Arc::new(Int64Array::from(vec![None, Some(1), Some(2), None, None])),
+                Arc::new(BinaryArray::from(vec![
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(&b"\xff"[..]),
+                ])),
+            ],
+        )
+        .unwrap();
+        let mut batch = OtapBatch::Logs(Logs::default());
+        batch.set(ArrowPayloadType::LogAttrs, rb);
+
+        let collector = Arc::new(DecodeEventCollector::default());
+        let options = DecoderOptions::default()
+            .with_skip_bad_rows(true)
+            .with_event_sink(collector.clone());
+        let mut report = DecodeReport::default();
+        let store = Attribute16Store::from_payload(
+            &batch,
+            ArrowPayloadType::LogAttrs,
+            &options,
+            &mut report,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(store.attribute_by_id(0).map(<[_]>::len), Some(2));
+
+        let event = |row, kind| DecodeEvent {
+            payload_type: ArrowPayloadType::LogAttrs,
+            row,
+            kind,
+        };
+        assert_eq!(collector.take(), vec![
+            event(0, DecodeEventKind::EmptyValue),
+            event(1, DecodeEventKind::MissingKey),
+            event(3, DecodeEventKind::MissingSerializedValue),
+            event(
+                4,
+                DecodeEventKind::DroppedRow(DroppedRowReason::InvalidSerializedValue)
+            ),
+        ]);
+        assert!(collector.take().is_empty());
+    }
+
+    #[test]
+    fn test_rate_limited_events() {
+        #[derive(Default)]
+        struct Recorder {
+            events: Mutex<Vec<DecodeEvent>>,
+            summaries: Mutex<Vec<SuppressedEvents>>,
+        }
+        impl DecodeEventSink for Recorder {
+            fn on_event(&self, event: &DecodeEvent) {
+                self.events.lock().unwrap().push(*event);
+            }
+            fn on_suppressed(&self, summary: &SuppressedEvents) {
+                self.summaries.lock().unwrap().push(*summary);
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let sink = RateLimitedEventSink::new(recorder.clone(), 2, Duration::from_secs(10));
+        let event = |row, kind| DecodeEvent {
+            payload_type: ArrowPayloadType::LogAttrs,
+            row,
+            kind,
+        };
+        let start = Instant::now();
+        for row in 0..5 {
+            sink.record(&event(row, DecodeEventKind::EmptyValue), start);
+        }
+        sink.record(&event(5, DecodeEventKind::MissingKey), start);
+        assert_eq!(recorder.events.lock().unwrap().len(), 3);
+        assert!(recorder.summaries.lock().unwrap().is_empty());
+
+        // the next window reports the events suppressed in the first one
+        let later = start + Duration::from_secs(11);
+        sink.record(&event(6, DecodeEventKind::EmptyValue), later);
+        assert_eq!(*recorder.summaries.lock().unwrap(), vec![
+            SuppressedEvents {
+                payload_type: ArrowPayloadType::LogAttrs,
+                kind: DecodeEventKind::EmptyValue,
+                count: 3,
+                window: Duration::from_secs(11),
+            }
+        ]);
+        assert_eq!(recorder.events.lock().unwrap().last().unwrap().row, 6);
+
+        // a window without suppressed events has no summary
+        sink.flush(later + Duration::from_secs(1));
+        assert_eq!(recorder.summaries.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Handling of the columns of a record payload that are not in its canonical schema,
+//! according to the [`UnknownColumnPolicy`] of the decoder options.
+
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type, Int64Type};
+
+use crate::error::{self, Result};
+use crate::otlp::options::{DecoderOptions, UnknownColumnPolicy};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+use crate::schema::definitions::otap_schema;
+
+/// The unknown columns of a record payload passed through as attributes, with their
+/// attribute key. The columns are cast to the array type of their attribute value type.
+#[derive(Default)]
+pub(crate) struct ExtraColumns {
+    columns: Vec<(String, ArrayRef)>,
+}
+
+impl ExtraColumns {
+    /// Looks up the unknown columns of the payload. Fails if the policy rejects them.
+    pub(crate) fn try_new(
+        rb: &RecordBatch,
+        payload_type: ArrowPayloadType,
+        options: &DecoderOptions,
+    ) -> Result<Self> {
+        if options.unknown_column_policy == UnknownColumnPolicy::Ignore {
+            return Ok(Self::default());
+        }
+        let schema = otap_schema(payload_type);
+        let mut columns = Vec::new();
+        for (field, column) in rb.schema().fields().iter().zip(rb.columns()) {
+            if schema.field_with_name(field.name()).is_ok() {
+                continue;
+            }
+            match &options.unknown_column_policy {
+                UnknownColumnPolicy::Ignore => {}
+                UnknownColumnPolicy::Error => {
+                    return error::UnknownColumnSnafu {
+                        name: field.name().clone(),
+                    }
+                    .fail();
+                }
+                UnknownColumnPolicy::Attributes { prefix } => {
+                    // the columns of other types, e.g. nested ones, have no attribute type
+                    let Some(column) = attribute_array_type(column.data_type())
+                        .and_then(|target_type| cast(column, &target_type).ok())
+                    else {
+                        continue;
+                    };
+                    columns.push((format!("{prefix}{}", field.name()), column));
+                }
+            }
+        }
+        Ok(Self { columns })
+    }
+
+    /// Appends the attributes of the row `idx` to `attributes`, the null values being left
+    /// out.
+    pub(crate) fn append_attributes(&self, idx: usize, attributes: &mut Vec<KeyValue>) {
+        for (key, column) in &self.columns {
+            if column.is_null(idx) {
+                continue;
+            }
+            let value = match column.data_type() {
+                DataType::Utf8 => Value::StringValue(column.as_string::<i32>().value(idx).into()),
+                DataType::Int64 => Value::IntValue(column.as_primitive::<Int64Type>().value(idx)),
+                DataType::Float64 => {
+                    Value::DoubleValue(column.as_primitive::<Float64Type>().value(idx))
+                }
+                DataType::Boolean => Value::BoolValue(column.as_boolean().value(idx)),
+                DataType::Binary => Value::BytesValue(column.as_binary::<i32>().value(idx).into()),
+                _ => continue,
+            };
+            attributes.push(KeyValue {
+                key: key.clone(),
+                value: Some(AnyValue { value: Some(value) }),
+            });
+        }
+    }
+}
+
+/// Returns the array type the column is cast to, from the attribute value type it maps to.
+fn attribute_array_type(data_type: &DataType) -> Option<DataType> {
+    match data_type {
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => Some(DataType::Utf8),
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => Some(DataType::Int64),
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => Some(DataType::Float64),
+        DataType::Boolean => Some(DataType::Boolean),
+        DataType::Binary
+        | DataType::LargeBinary
+        | DataType::BinaryView
+        | DataType::FixedSizeBinary(_) => Some(DataType::Binary),
+        DataType::Dictionary(_, value_type) => attribute_array_type(value_type),
+        _ => None,
+    }
+}
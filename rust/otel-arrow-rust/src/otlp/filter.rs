@@ -0,0 +1,373 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Filtering of the log records and spans of a batch before they are decoded, so that a
+//! receiver can drop the unwanted telemetry without reconstructing it.
+//!
+//! A [`RecordFilter`] set in the [`DecoderOptions`] evaluates its [`Predicate`] on the Arrow
+//! columns of the batch, once per batch: the string predicates are evaluated once per
+//! dictionary value for the dictionary encoded columns, and the attribute predicates on the
+//! attributes payload of the records. The records the filter drops are skipped by the
+//! decoder and counted as [`DroppedRowReason::Filtered`] in the report of the batch.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::array::{
+    Array, ArrayRef, AsArray, BinaryArray, BooleanArray, Float64Array, Int32Array, Int64Array,
+    RecordBatch, StringArray,
+};
+use arrow::compute::kernels::boolean::{and, not, or};
+use arrow::compute::kernels::cmp::gt_eq;
+use arrow::compute::{cast, prep_null_mask_filter};
+use arrow::datatypes::{DataType, Float64Type, Int64Type, UInt8Type};
+use regex::Regex;
+
+use crate::arrays::{get_u16_array, get_u16_array_opt};
+use crate::error::{self, Result};
+use crate::otap::{OtapBatch, graph};
+use crate::otlp::attributes::decoder::materialize_parent_id;
+use crate::otlp::attributes::store::AttributeValueType;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DroppedRowReason;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::schema::consts;
+
+/// Condition on a log record or span.
+#[derive(Clone, Debug)]
+pub enum Predicate {
+    /// The record has an attribute with this key and value. The map and slice values never
+    /// match.
+    AttributeEquals {
+        /// Key of the attribute.
+        key: String,
+        /// Value of the attribute.
+        value: Value,
+    },
+    /// The name of the span matches the regex. Never matches a log record.
+    NameMatches(Regex),
+    /// The severity number of the log record is at least this one. Never matches a span.
+    SeverityAtLeast(i32),
+    /// The predicate doesn't match.
+    Not(Box<Predicate>),
+    /// All the predicates match.
+    All(Vec<Predicate>),
+    /// Any of the predicates matches.
+    Any(Vec<Predicate>),
+}
+
+/// Selects the log records and spans to decode.
+#[derive(Clone, Debug)]
+pub enum RecordFilter {
+    /// Decode only the records matching the predicate.
+    Keep(Predicate),
+    /// Drop the records matching the predicate.
+    Drop(Predicate),
+}
+
+impl RecordFilter {
+    /// Returns the mask of the rows of the log records or spans payload of the batch that
+    /// the filter keeps.
+    pub fn evaluate(
+        &self,
+        otap_batch: &OtapBatch,
+        payload_type: ArrowPayloadType,
+    ) -> Result<BooleanArray> {
+        let Some(rb) = otap_batch.get(payload_type) else {
+            return Ok(BooleanArray::from(Vec::<bool>::new()));
+        };
+        let (predicate, keep_matching) = match self {
+            Self::Keep(predicate) => (predicate, true),
+            Self::Drop(predicate) => (predicate, false),
+        };
+        let matches = Evaluation {
+            otap_batch,
+            payload_type,
+            rb,
+            record_ids: None,
+        }
+        .matches(predicate)?;
+        // safety: the masks have no nulls
+        Ok(if keep_matching {
+            matches
+        } else {
+            not(&matches).expect("mask has no nulls")
+        })
+    }
+}
+
+/// The rows of a log records or spans payload kept by the filter of the decoder options.
+pub(crate) struct KeptRows(Option<BooleanArray>);
+
+impl KeptRows {
+    pub(crate) fn try_new(
+        otap_batch: &OtapBatch,
+        payload_type: ArrowPayloadType,
+        options: &DecoderOptions,
+    ) -> Result<Self> {
+        options
+            .filter
+            .as_ref()
+            .map(|filter| filter.evaluate(otap_batch, payload_type))
+            .transpose()
+            .map(Self)
+    }
+
+    /// Returns the reason the row `idx` is dropped, if it is.
+    pub(crate) fn dropped(&self, idx: usize) -> Option<DroppedRowReason> {
+        let kept = self.0.as_ref().is_none_or(|mask| mask.value(idx));
+        (!kept).then_some(DroppedRowReason::Filtered)
+    }
+}
+
+struct Evaluation<'a> {
+    otap_batch: &'a OtapBatch,
+    payload_type: ArrowPayloadType,
+    rb: &'a RecordBatch,
+    /// The ids of the records, computed on the first attribute predicate.
+    record_ids: Option<Vec<Option<u16>>>,
+}
+
+impl Evaluation<'_> {
+    /// Returns the mask of the records matching the predicate, without nulls.
+    fn matches(&mut self, predicate: &Predicate) -> Result<BooleanArray> {
+        let num_rows = self.rb.num_rows();
+        let none = || BooleanArray::from(vec![false; num_rows]);
+        let column = |name| self.rb.column_by_name(name);
+        Ok(match predicate {
+            Predicate::NameMatches(regex) => match column(consts::NAME) {
+                Some(names) if self.payload_type == ArrowPayloadType::Spans => {
+                    string_matches(consts::NAME, names, |name| regex.is_match(name))?
+                }
+                _ => none(),
+            },
+            Predicate::SeverityAtLeast(min) => match column(consts::SEVERITY_NUMBER) {
+                Some(severities) if self.payload_type == ArrowPayloadType::Logs => {
+                    let severities =
+                        cast_column(consts::SEVERITY_NUMBER, severities, &DataType::Int32)?;
+                    let min = Int32Array::new_scalar(*min);
+                    // safety: both sides are int32
+                    no_nulls(gt_eq(&severities, &min).expect("same types"))
+                }
+                _ => none(),
+            },
+            Predicate::AttributeEquals { key, value } => self.attribute_equals(key, value)?,
+            // safety: the masks have no nulls and the same length
+            Predicate::Not(predicate) => not(&self.matches(predicate)?).expect("mask"),
+            Predicate::All(predicates) => {
+                let mut mask = BooleanArray::from(vec![true; num_rows]);
+                for predicate in predicates {
+                    mask = and(&mask, &self.matches(predicate)?).expect("masks");
+                }
+                mask
+            }
+            Predicate::Any(predicates) => {
+                let mut mask = none();
+                for predicate in predicates {
+                    mask = or(&mask, &self.matches(predicate)?).expect("masks");
+                }
+                mask
+            }
+        })
+    }
+
+    fn attribute_equals(&mut self, key: &str, value: &Value) -> Result<BooleanArray> {
+        let Some(attrs_payload_type) = graph::attributes(self.payload_type) else {
+            return Ok(BooleanArray::from(vec![false; self.rb.num_rows()]));
+        };
+        let parent_ids = match self.otap_batch.get(attrs_payload_type) {
+            Some(attrs) => matching_parent_ids(attrs, key, value)?,
+            None => HashSet::new(),
+        };
+        let record_ids = match &self.record_ids {
+            Some(record_ids) => record_ids,
+            None => self.record_ids.insert(record_ids(self.rb)?),
+        };
+        Ok(record_ids
+            .iter()
+            .map(|id| Some(id.is_some_and(|id| parent_ids.contains(&id))))
+            .collect())
+    }
+}
+
+/// Returns the ids of the records, the id column being delta encoded. The records without
+/// an id have no attributes.
+pub(crate) fn record_ids(rb: &RecordBatch) -> Result<Vec<Option<u16>>> {
+    let Some(ids) = get_u16_array_opt(rb, consts::ID)? else {
+        return Ok(vec![None; rb.num_rows()]);
+    };
+    let mut id = 0u16;
+    Ok(ids
+        .iter()
+        .map(|delta| {
+            id = id.wrapping_add(delta.unwrap_or_default());
+            delta.map(|_| id)
+        })
+        .collect())
+}
+
+/// Returns the parent ids of the attributes with the given key and value.
+fn matching_parent_ids(attrs: &RecordBatch, key: &str, value: &Value) -> Result<HashSet<u16>> {
+    let (value_type, column, value): (_, _, ArrayRef) = match value {
+        Value::StringValue(s) => (
+            AttributeValueType::Str,
+            consts::ATTRIBUTE_STR,
+            Arc::new(StringArray::from(vec![s.as_str()])),
+        ),
+        Value::IntValue(i) => (
+            AttributeValueType::Int,
+            consts::ATTRIBUTE_INT,
+            Arc::new(Int64Array::from(vec![*i])),
+        ),
+        Value::DoubleValue(d) => (
+            AttributeValueType::Double,
+            consts::ATTRIBUTE_DOUBLE,
+            Arc::new(Float64Array::from(vec![*d])),
+        ),
+        Value::BoolValue(b) => (
+            AttributeValueType::Bool,
+            consts::ATTRIBUTE_BOOL,
+            Arc::new(BooleanArray::from(vec![*b])),
+        ),
+        Value::BytesValue(bytes) => (
+            AttributeValueType::Bytes,
+            consts::ATTRIBUTE_BYTES,
+            Arc::new(BinaryArray::from(vec![bytes.as_slice()])),
+        ),
+        Value::ArrayValue(_) | Value::KvlistValue(_) => return Ok(HashSet::new()),
+    };
+    let (Some(keys), Some(types), Some(values)) = (
+        attrs.column_by_name(consts::ATTRIBUTE_KEY),
+        attrs.column_by_name(consts::ATTRIBUTE_TYPE),
+        attrs.column_by_name(column),
+    ) else {
+        return Ok(HashSet::new());
+    };
+    let keys = string_matches(consts::ATTRIBUTE_KEY, keys, |k| k == key)?;
+    let types = cast_column(consts::ATTRIBUTE_TYPE, types, &DataType::UInt8)?;
+    let types = types.as_primitive::<UInt8Type>();
+    let values = cast_column(column, values, value.data_type())?;
+    let candidates: Vec<_> = (0..attrs.num_rows())
+        .filter(|idx| keys.value(*idx) && types.value(*idx) == value_type as u8)
+        .filter(|idx| values.is_valid(*idx) && scalar_eq(&values, *idx, &value))
+        .collect();
+    if candidates.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let attrs = materialize_parent_id::<u16>(attrs)?;
+    let parent_ids = get_u16_array(&attrs, consts::PARENT_ID)?;
+    Ok(candidates
+        .into_iter()
+        .filter(|idx| parent_ids.is_valid(*idx))
+        .map(|idx| parent_ids.value(idx))
+        .collect())
+}
+
+/// Returns true if the row `idx` of `values` equals the single value of `value`, both
+/// having the same type.
+fn scalar_eq(values: &ArrayRef, idx: usize, value: &ArrayRef) -> bool {
+    match values.data_type() {
+        DataType::Utf8 => values.as_string::<i32>().value(idx) == value.as_string::<i32>().value(0),
+        DataType::Int64 => {
+            values.as_primitive::<Int64Type>().value(idx)
+                == value.as_primitive::<Int64Type>().value(0)
+        }
+        DataType::Float64 => {
+            values.as_primitive::<Float64Type>().value(idx)
+                == value.as_primitive::<Float64Type>().value(0)
+        }
+        DataType::Boolean => values.as_boolean().value(idx) == value.as_boolean().value(0),
+        DataType::Binary => {
+            values.as_binary::<i32>().value(idx) == value.as_binary::<i32>().value(0)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the mask of the non null strings of the column matching `f`, evaluated once per
+/// value for the dictionary encoded columns.
+pub(crate) fn string_matches(
+    name: &str,
+    column: &ArrayRef,
+    f: impl Fn(&str) -> bool,
+) -> Result<BooleanArray> {
+    let eval = |strings: &ArrayRef| -> Result<BooleanArray> {
+        let strings = cast_column(name, strings, &DataType::Utf8)?;
+        Ok(strings
+            .as_string::<i32>()
+            .iter()
+            .map(|s| Some(s.is_some_and(&f)))
+            .collect())
+    };
+    let Some(dictionary) = column.as_any_dictionary_opt() else {
+        return eval(column);
+    };
+    let value_matches = eval(dictionary.values())?;
+    let keys = dictionary.normalized_keys();
+    Ok((0..column.len())
+        .map(|idx| Some(column.is_valid(idx) && value_matches.value(keys[idx])))
+        .collect())
+}
+
+fn cast_column(name: &str, column: &ArrayRef, target_type: &DataType) -> Result<ArrayRef> {
+    cast(column, target_type).map_err(|_| {
+        error::ColumnDataTypeMismatchSnafu {
+            name,
+            expect: target_type.clone(),
+            actual: column.data_type().clone(),
+        }
+        .build()
+    })
+}
+
+fn no_nulls(mask: BooleanArray) -> BooleanArray {
+    if mask.null_count() == 0 {
+        mask
+    } else {
+        prep_null_mask_filter(&mask)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otlp::traces::traces_from_with_options;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_filter_spans() {
+        let attr = |value: &str| Predicate::AttributeEquals {
+            key: "key0".into(),
+            value: Value::StringValue(value.into()),
+        };
+        let name = |regex| Predicate::NameMatches(Regex::new(regex).unwrap());
+        let decode = |filter| {
+            let options = DecoderOptions::default().with_filter(filter);
+            traces_from_with_options(traces_batch(32, 4), &options).unwrap()
+        };
+
+        // the names are dictionary encoded, span0 to span15
+        let traces = decode(RecordFilter::Drop(name("^span1[0-5]?$")));
+        let spans = &traces.resource_spans[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 32 - 14);
+        assert!(spans.iter().all(|span| !span.name.starts_with("span1")));
+        // the ids of the spans following the dropped ones are still in sync
+        assert!(spans.iter().all(|span| span.attributes.len() == 4));
+
+        let traces = decode(RecordFilter::Keep(Predicate::All(vec![
+            attr("value0"),
+            name("^span2$"),
+        ])));
+        let spans = &traces.resource_spans[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 2);
+
+        let traces = decode(RecordFilter::Keep(Predicate::Any(vec![
+            attr("other"),
+            Predicate::SeverityAtLeast(0),
+        ])));
+        assert!(traces.resource_spans[0].scope_spans[0].spans.is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Interning of the attribute keys decoded during a decoding session.
+//!
+//! The same few keys, e.g. `http.method`, are decoded for most of the rows of the
+//! attribute batches. The keys are interned as [`Arc<str>`] while decoding, so the decoder
+//! can keep track of them without allocating for every row. The interner is shared by all
+//! the batches decoded with the same [`DecoderOptions`](crate::otlp::options::DecoderOptions).
+//!
+//! The OTLP messages own their strings, so every decoded `KeyValue` still gets its own copy
+//! of the key.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Common semantic convention attribute keys, which can be interned up front with
+/// [`StringInterner::with_semconv_keys`].
+pub const SEMCONV_KEYS: &[&str] = &[
+    "service.name",
+    "service.namespace",
+    "service.instance.id",
+    "service.version",
+    "telemetry.sdk.name",
+    "telemetry.sdk.language",
+    "telemetry.sdk.version",
+    "host.name",
+    "host.arch",
+    "os.type",
+    "process.pid",
+    "process.runtime.name",
+    "process.runtime.version",
+    "container.id",
+    "k8s.namespace.name",
+    "k8s.pod.name",
+    "k8s.node.name",
+    "cloud.provider",
+    "cloud.region",
+    "http.request.method",
+    "http.response.status_code",
+    "http.route",
+    "http.method",
+    "http.status_code",
+    "url.full",
+    "url.path",
+    "url.scheme",
+    "server.address",
+    "server.port",
+    "client.address",
+    "network.protocol.name",
+    "network.protocol.version",
+    "rpc.system",
+    "rpc.service",
+    "rpc.method",
+    "db.system",
+    "db.statement",
+    "messaging.system",
+    "exception.type",
+    "exception.message",
+    "error.type",
+];
+
+/// Cache of interned strings, shared by the decoders of a session.
+#[derive(Debug, Default)]
+pub struct StringInterner {
+    strings: Mutex<InternedStrings>,
+    max_bytes: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+struct InternedStrings {
+    set: HashSet<Arc<str>>,
+    /// Total length of the strings of the set.
+    bytes: usize,
+}
+
+impl StringInterner {
+    /// Creates an interner holding the given strings.
+    #[must_use]
+    pub fn with_keys<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        let interner = Self::default();
+        interner.seed(keys);
+        interner
+    }
+
+    /// Creates an interner holding the [`SEMCONV_KEYS`].
+    #[must_use]
+    pub fn with_semconv_keys() -> Self {
+        Self::with_keys(SEMCONV_KEYS.iter().copied())
+    }
+
+    /// Creates an empty interner holding strings of at most `max_bytes` in total. Once
+    /// full, the strings that are not interned yet are still returned but not kept.
+    #[must_use]
+    pub fn with_max_bytes(max_bytes: usize) -> Self {
+        Self {
+            max_bytes: Some(max_bytes),
+            ..Default::default()
+        }
+    }
+
+    /// Interns the given strings.
+    pub fn seed<'a>(&self, keys: impl IntoIterator<Item = &'a str>) {
+        let mut session = self.session();
+        for key in keys {
+            let _ = session.intern(key);
+        }
+    }
+
+    /// Returns the interned copy of `s`, interning it first if needed.
+    pub fn intern(&self, s: &str) -> Arc<str> {
+        self.session().intern(s)
+    }
+
+    /// Returns the number of interned strings.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.session().strings.set.len()
+    }
+
+    /// Returns true if no string was interned.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the total length of the interned strings.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        self.session().strings.bytes
+    }
+
+    /// Removes all the interned strings.
+    pub fn clear(&self) {
+        let mut session = self.session();
+        session.strings.set.clear();
+        session.strings.bytes = 0;
+    }
+
+    /// Locks the interner for the decoding of a batch.
+    pub(crate) fn session(&self) -> InternerSession<'_> {
+        InternerSession {
+            // the set is always left in a consistent state
+            strings: self.strings.lock().unwrap_or_else(PoisonError::into_inner),
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+/// Access to the interned strings, held while decoding a batch.
+pub(crate) struct InternerSession<'a> {
+    strings: MutexGuard<'a, InternedStrings>,
+    max_bytes: Option<usize>,
+}
+
+impl InternerSession<'_> {
+    pub(crate) fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.set.get(s) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(s);
+        let bytes = self.strings.bytes + s.len();
+        if self.max_bytes.is_none_or(|max_bytes| bytes <= max_bytes) {
+            let _ = self.strings.set.insert(interned.clone());
+            self.strings.bytes = bytes;
+        }
+        interned
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let interner = StringInterner::with_semconv_keys();
+        assert_eq!(interner.len(), SEMCONV_KEYS.len());
+
+        let a = interner.intern("http.method");
+        let b = interner.intern(&String::from("http.method"));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(interner.len(), SEMCONV_KEYS.len());
+
+        let c = interner.intern("custom.key");
+        assert_eq!(&*c, "custom.key");
+        assert_eq!(interner.len(), SEMCONV_KEYS.len() + 1);
+    }
+
+    #[test]
+    fn test_intern_bounded() {
+        let interner = StringInterner::with_max_bytes(8);
+        let a = interner.intern("abcd");
+        assert!(Arc::ptr_eq(&a, &interner.intern("abcd")));
+        // over the limit, returned but not kept
+        let b = interner.intern("efghi");
+        assert_eq!(&*b, "efghi");
+        assert!(!Arc::ptr_eq(&b, &interner.intern("efghi")));
+        let _ = interner.intern("efgh");
+        assert_eq!((interner.len(), interner.bytes()), (2, 8));
+
+        interner.clear();
+        assert!(interner.is_empty());
+        assert_eq!(interner.bytes(), 0);
+    }
+}
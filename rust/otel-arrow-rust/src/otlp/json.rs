@@ -0,0 +1,600 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! OTLP/JSON encoding of the decoded export requests, e.g. to inspect a batch or to feed a
+//! backend ingesting JSON lines.
+//!
+//! The requests are encoded following the JSON mapping of the OTLP specification: the
+//! fields are named in lower camel case, the trace and span ids are hex encoded, the other
+//! bytes are base64 encoded, the 64 bit integers are written as decimal strings and the
+//! enums as integers. The fields holding their default value are omitted.
+
+use std::fmt::Write;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::{Map, Value};
+
+use crate::ExportRequest;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::any_value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+use crate::proto::opentelemetry::logs::v1::LogRecord;
+use crate::proto::opentelemetry::metrics::v1::exponential_histogram_data_point::Buckets;
+use crate::proto::opentelemetry::metrics::v1::{
+    Exemplar, ExponentialHistogramDataPoint, HistogramDataPoint, Metric, NumberDataPoint,
+    SummaryDataPoint, exemplar, metric, number_data_point,
+};
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::proto::opentelemetry::trace::v1::{Span, Status, span};
+
+/// Encodes the request as OTLP/JSON.
+#[must_use]
+pub fn to_json(request: &ExportRequest) -> Value {
+    match request {
+        ExportRequest::Logs(request) => logs_to_json(request),
+        ExportRequest::Metrics(request) => metrics_to_json(request),
+        ExportRequest::Traces(request) => traces_to_json(request),
+    }
+}
+
+/// Encodes the request as a single line of OTLP/JSON, without the trailing newline.
+#[must_use]
+pub fn to_json_line(request: &ExportRequest) -> String {
+    to_json(request).to_string()
+}
+
+/// Encodes the logs request as OTLP/JSON.
+#[must_use]
+pub fn logs_to_json(request: &ExportLogsServiceRequest) -> Value {
+    let resource_logs = request.resource_logs.iter().map(|rl| {
+        let scope_logs = rl.scope_logs.iter().map(|sl| {
+            Object::default()
+                .object("scope", sl.scope.as_ref().map(scope))
+                .array("logRecords", sl.log_records.iter().map(log_record))
+                .string("schemaUrl", &sl.schema_url)
+                .into()
+        });
+        Object::default()
+            .object("resource", rl.resource.as_ref().map(resource))
+            .array("scopeLogs", scope_logs)
+            .string("schemaUrl", &rl.schema_url)
+            .into()
+    });
+    Object::default()
+        .array("resourceLogs", resource_logs)
+        .into()
+}
+
+/// Encodes the metrics request as OTLP/JSON.
+#[must_use]
+pub fn metrics_to_json(request: &ExportMetricsServiceRequest) -> Value {
+    let resource_metrics = request.resource_metrics.iter().map(|rm| {
+        let scope_metrics = rm.scope_metrics.iter().map(|sm| {
+            Object::default()
+                .object("scope", sm.scope.as_ref().map(scope))
+                .array("metrics", sm.metrics.iter().map(metric))
+                .string("schemaUrl", &sm.schema_url)
+                .into()
+        });
+        Object::default()
+            .object("resource", rm.resource.as_ref().map(resource))
+            .array("scopeMetrics", scope_metrics)
+            .string("schemaUrl", &rm.schema_url)
+            .into()
+    });
+    Object::default()
+        .array("resourceMetrics", resource_metrics)
+        .into()
+}
+
+/// Encodes the traces request as OTLP/JSON.
+#[must_use]
+pub fn traces_to_json(request: &ExportTraceServiceRequest) -> Value {
+    let resource_spans = request.resource_spans.iter().map(|rs| {
+        let scope_spans = rs.scope_spans.iter().map(|ss| {
+            Object::default()
+                .object("scope", ss.scope.as_ref().map(scope))
+                .array("spans", ss.spans.iter().map(span))
+                .string("schemaUrl", &ss.schema_url)
+                .into()
+        });
+        Object::default()
+            .object("resource", rs.resource.as_ref().map(resource))
+            .array("scopeSpans", scope_spans)
+            .string("schemaUrl", &rs.schema_url)
+            .into()
+    });
+    Object::default()
+        .array("resourceSpans", resource_spans)
+        .into()
+}
+
+/// JSON object under construction, skipping the fields holding their default value.
+#[derive(Default)]
+struct Object(Map<String, Value>);
+
+impl From<Object> for Value {
+    fn from(object: Object) -> Self {
+        Value::Object(object.0)
+    }
+}
+
+impl Object {
+    fn field(mut self, key: &str, value: Value) -> Self {
+        let _ = self.0.insert(key.to_string(), value);
+        self
+    }
+
+    fn string(self, key: &str, value: &str) -> Self {
+        if value.is_empty() {
+            return self;
+        }
+        self.field(key, Value::from(value))
+    }
+
+    fn bool(self, key: &str, value: bool) -> Self {
+        if !value {
+            return self;
+        }
+        self.field(key, Value::from(value))
+    }
+
+    fn number(self, key: &str, value: impl Into<i64>) -> Self {
+        match value.into() {
+            0 => self,
+            value => self.field(key, Value::from(value)),
+        }
+    }
+
+    /// 64 bit integers are written as strings, JSON numbers can't hold all of them.
+    fn long(self, key: &str, value: impl Into<i128>) -> Self {
+        match value.into() {
+            0 => self,
+            value => self.field(key, Value::from(value.to_string())),
+        }
+    }
+
+    fn double(self, key: &str, value: f64) -> Self {
+        if value == 0.0 {
+            return self;
+        }
+        self.field(key, double(value))
+    }
+
+    fn optional_double(self, key: &str, value: Option<f64>) -> Self {
+        match value {
+            Some(value) => self.field(key, double(value)),
+            None => self,
+        }
+    }
+
+    fn hex(self, key: &str, bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            return self;
+        }
+        let mut hex = String::with_capacity(bytes.len() * 2);
+        for b in bytes {
+            // writing to a String can't fail
+            let _ = write!(hex, "{b:02x}");
+        }
+        self.field(key, Value::from(hex))
+    }
+
+    fn object(self, key: &str, value: Option<Value>) -> Self {
+        match value {
+            Some(value) => self.field(key, value),
+            None => self,
+        }
+    }
+
+    fn array(self, key: &str, values: impl IntoIterator<Item = Value>) -> Self {
+        let values: Vec<_> = values.into_iter().collect();
+        if values.is_empty() {
+            return self;
+        }
+        self.field(key, Value::Array(values))
+    }
+
+    fn attributes(self, key: &str, attributes: &[KeyValue]) -> Self {
+        self.array(key, attributes.iter().map(key_value))
+    }
+}
+
+/// The non finite doubles are written as the strings of the protobuf JSON mapping.
+fn double(value: f64) -> Value {
+    match serde_json::Number::from_f64(value) {
+        Some(number) => Value::Number(number),
+        None if value.is_nan() => Value::from("NaN"),
+        None if value > 0.0 => Value::from("Infinity"),
+        None => Value::from("-Infinity"),
+    }
+}
+
+fn key_value(kv: &KeyValue) -> Value {
+    Object::default()
+        .string("key", &kv.key)
+        .object("value", kv.value.as_ref().map(any_value))
+        .into()
+}
+
+fn any_value(value: &AnyValue) -> Value {
+    let object = Object::default();
+    match &value.value {
+        None => object,
+        Some(any_value::Value::StringValue(s)) => object.field("stringValue", Value::from(&**s)),
+        Some(any_value::Value::BoolValue(b)) => object.field("boolValue", Value::from(*b)),
+        Some(any_value::Value::IntValue(i)) => object.field("intValue", Value::from(i.to_string())),
+        Some(any_value::Value::DoubleValue(d)) => object.field("doubleValue", double(*d)),
+        Some(any_value::Value::ArrayValue(array)) => object.field(
+            "arrayValue",
+            Object::default()
+                .array("values", array.values.iter().map(any_value))
+                .into(),
+        ),
+        Some(any_value::Value::KvlistValue(kvlist)) => object.field(
+            "kvlistValue",
+            Object::default()
+                .attributes("values", &kvlist.values)
+                .into(),
+        ),
+        Some(any_value::Value::BytesValue(bytes)) => {
+            object.field("bytesValue", Value::from(BASE64.encode(bytes)))
+        }
+    }
+    .into()
+}
+
+fn resource(resource: &Resource) -> Value {
+    Object::default()
+        .attributes("attributes", &resource.attributes)
+        .number("droppedAttributesCount", resource.dropped_attributes_count)
+        .into()
+}
+
+fn scope(scope: &InstrumentationScope) -> Value {
+    Object::default()
+        .string("name", &scope.name)
+        .string("version", &scope.version)
+        .attributes("attributes", &scope.attributes)
+        .number("droppedAttributesCount", scope.dropped_attributes_count)
+        .into()
+}
+
+fn log_record(log: &LogRecord) -> Value {
+    Object::default()
+        .long("timeUnixNano", log.time_unix_nano)
+        .long("observedTimeUnixNano", log.observed_time_unix_nano)
+        .number("severityNumber", log.severity_number)
+        .string("severityText", &log.severity_text)
+        .object("body", log.body.as_ref().map(any_value))
+        .attributes("attributes", &log.attributes)
+        .number("droppedAttributesCount", log.dropped_attributes_count)
+        .number("flags", log.flags)
+        .hex("traceId", &log.trace_id)
+        .hex("spanId", &log.span_id)
+        .string("eventName", &log.event_name)
+        .into()
+}
+
+fn span(span: &Span) -> Value {
+    Object::default()
+        .hex("traceId", &span.trace_id)
+        .hex("spanId", &span.span_id)
+        .string("traceState", &span.trace_state)
+        .hex("parentSpanId", &span.parent_span_id)
+        .number("flags", span.flags)
+        .string("name", &span.name)
+        .number("kind", span.kind)
+        .long("startTimeUnixNano", span.start_time_unix_nano)
+        .long("endTimeUnixNano", span.end_time_unix_nano)
+        .attributes("attributes", &span.attributes)
+        .number("droppedAttributesCount", span.dropped_attributes_count)
+        .array("events", span.events.iter().map(event))
+        .number("droppedEventsCount", span.dropped_events_count)
+        .array("links", span.links.iter().map(link))
+        .number("droppedLinksCount", span.dropped_links_count)
+        .object("status", span.status.as_ref().map(status))
+        .into()
+}
+
+fn event(event: &span::Event) -> Value {
+    Object::default()
+        .long("timeUnixNano", event.time_unix_nano)
+        .string("name", &event.name)
+        .attributes("attributes", &event.attributes)
+        .number("droppedAttributesCount", event.dropped_attributes_count)
+        .into()
+}
+
+fn link(link: &span::Link) -> Value {
+    Object::default()
+        .hex("traceId", &link.trace_id)
+        .hex("spanId", &link.span_id)
+        .string("traceState", &link.trace_state)
+        .attributes("attributes", &link.attributes)
+        .number("droppedAttributesCount", link.dropped_attributes_count)
+        .number("flags", link.flags)
+        .into()
+}
+
+fn status(status: &Status) -> Value {
+    Object::default()
+        .string("message", &status.message)
+        .number("code", status.code)
+        .into()
+}
+
+fn metric(metric: &Metric) -> Value {
+    let object = Object::default()
+        .string("name", &metric.name)
+        .string("description", &metric.description)
+        .string("unit", &metric.unit)
+        .attributes("metadata", &metric.metadata);
+    match &metric.data {
+        None => object,
+        Some(metric::Data::Gauge(gauge)) => object.field(
+            "gauge",
+            Object::default()
+                .array(
+                    "dataPoints",
+                    gauge.data_points.iter().map(number_data_point),
+                )
+                .into(),
+        ),
+        Some(metric::Data::Sum(sum)) => object.field(
+            "sum",
+            Object::default()
+                .array("dataPoints", sum.data_points.iter().map(number_data_point))
+                .number("aggregationTemporality", sum.aggregation_temporality)
+                .bool("isMonotonic", sum.is_monotonic)
+                .into(),
+        ),
+        Some(metric::Data::Histogram(histogram)) => object.field(
+            "histogram",
+            Object::default()
+                .array(
+                    "dataPoints",
+                    histogram.data_points.iter().map(histogram_data_point),
+                )
+                .number("aggregationTemporality", histogram.aggregation_temporality)
+                .into(),
+        ),
+        Some(metric::Data::ExponentialHistogram(histogram)) => object.field(
+            "exponentialHistogram",
+            Object::default()
+                .array(
+                    "dataPoints",
+                    histogram.data_points.iter().map(exp_histogram_data_point),
+                )
+                .number("aggregationTemporality", histogram.aggregation_temporality)
+                .into(),
+        ),
+        Some(metric::Data::Summary(summary)) => object.field(
+            "summary",
+            Object::default()
+                .array(
+                    "dataPoints",
+                    summary.data_points.iter().map(summary_data_point),
+                )
+                .into(),
+        ),
+    }
+    .into()
+}
+
+fn number_data_point(dp: &NumberDataPoint) -> Value {
+    let object = Object::default()
+        .attributes("attributes", &dp.attributes)
+        .long("startTimeUnixNano", dp.start_time_unix_nano)
+        .long("timeUnixNano", dp.time_unix_nano)
+        .array("exemplars", dp.exemplars.iter().map(exemplar))
+        .number("flags", dp.flags);
+    match dp.value {
+        None => object,
+        Some(number_data_point::Value::AsDouble(d)) => object.field("asDouble", double(d)),
+        Some(number_data_point::Value::AsInt(i)) => {
+            object.field("asInt", Value::from(i.to_string()))
+        }
+    }
+    .into()
+}
+
+fn histogram_data_point(dp: &HistogramDataPoint) -> Value {
+    Object::default()
+        .attributes("attributes", &dp.attributes)
+        .long("startTimeUnixNano", dp.start_time_unix_nano)
+        .long("timeUnixNano", dp.time_unix_nano)
+        .long("count", dp.count)
+        .optional_double("sum", dp.sum)
+        .array(
+            "bucketCounts",
+            dp.bucket_counts.iter().map(|c| Value::from(c.to_string())),
+        )
+        .array(
+            "explicitBounds",
+            dp.explicit_bounds.iter().map(|b| double(*b)),
+        )
+        .array("exemplars", dp.exemplars.iter().map(exemplar))
+        .number("flags", dp.flags)
+        .optional_double("min", dp.min)
+        .optional_double("max", dp.max)
+        .into()
+}
+
+fn exp_histogram_data_point(dp: &ExponentialHistogramDataPoint) -> Value {
+    Object::default()
+        .attributes("attributes", &dp.attributes)
+        .long("startTimeUnixNano", dp.start_time_unix_nano)
+        .long("timeUnixNano", dp.time_unix_nano)
+        .long("count", dp.count)
+        .optional_double("sum", dp.sum)
+        .number("scale", dp.scale)
+        .long("zeroCount", dp.zero_count)
+        .object("positive", dp.positive.as_ref().map(buckets))
+        .object("negative", dp.negative.as_ref().map(buckets))
+        .number("flags", dp.flags)
+        .array("exemplars", dp.exemplars.iter().map(exemplar))
+        .optional_double("min", dp.min)
+        .optional_double("max", dp.max)
+        .double("zeroThreshold", dp.zero_threshold)
+        .into()
+}
+
+fn buckets(buckets: &Buckets) -> Value {
+    Object::default()
+        .number("offset", buckets.offset)
+        .array(
+            "bucketCounts",
+            buckets
+                .bucket_counts
+                .iter()
+                .map(|c| Value::from(c.to_string())),
+        )
+        .into()
+}
+
+fn summary_data_point(dp: &SummaryDataPoint) -> Value {
+    let quantile_values = dp.quantile_values.iter().map(|q| {
+        Object::default()
+            .double("quantile", q.quantile)
+            .double("value", q.value)
+            .into()
+    });
+    Object::default()
+        .attributes("attributes", &dp.attributes)
+        .long("startTimeUnixNano", dp.start_time_unix_nano)
+        .long("timeUnixNano", dp.time_unix_nano)
+        .long("count", dp.count)
+        .double("sum", dp.sum)
+        .array("quantileValues", quantile_values)
+        .number("flags", dp.flags)
+        .into()
+}
+
+fn exemplar(exemplar: &Exemplar) -> Value {
+    let object = Object::default()
+        .attributes("filteredAttributes", &exemplar.filtered_attributes)
+        .long("timeUnixNano", exemplar.time_unix_nano)
+        .hex("spanId", &exemplar.span_id)
+        .hex("traceId", &exemplar.trace_id);
+    match exemplar.value {
+        None => object,
+        Some(exemplar::Value::AsDouble(d)) => object.field("asDouble", double(d)),
+        Some(exemplar::Value::AsInt(i)) => object.field("asInt", Value::from(i.to_string())),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::proto::opentelemetry::logs::v1::{ResourceLogs, ScopeLogs};
+    use crate::proto::opentelemetry::metrics::v1::{Gauge, ResourceMetrics, ScopeMetrics};
+
+    #[test]
+    fn test_logs_to_json() {
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource {
+                    attributes: vec![KeyValue {
+                        key: "service.name".into(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::StringValue("svc".into())),
+                        }),
+                    }],
+                    ..Default::default()
+                }),
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![LogRecord {
+                        time_unix_nano: 1_700_000_000_000_000_000,
+                        severity_number: 9,
+                        body: Some(AnyValue {
+                            value: Some(any_value::Value::BytesValue(b"hi".to_vec())),
+                        }),
+                        attributes: vec![KeyValue {
+                            key: "count".into(),
+                            value: Some(AnyValue {
+                                value: Some(any_value::Value::IntValue(-3)),
+                            }),
+                        }],
+                        trace_id: vec![0xab; 16],
+                        span_id: vec![1, 2, 3, 4, 5, 6, 7, 8],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                schema_url: "https://opentelemetry.io/schemas/1.21.0".into(),
+            }],
+        };
+
+        assert_eq!(
+            to_json(&ExportRequest::Logs(request)),
+            json!({
+                "resourceLogs": [{
+                    "resource": {
+                        "attributes": [{"key": "service.name", "value": {"stringValue": "svc"}}]
+                    },
+                    "scopeLogs": [{
+                        "logRecords": [{
+                            "timeUnixNano": "1700000000000000000",
+                            "severityNumber": 9,
+                            "body": {"bytesValue": "aGk="},
+                            "attributes": [{"key": "count", "value": {"intValue": "-3"}}],
+                            "traceId": "abababababababababababababababab",
+                            "spanId": "0102030405060708"
+                        }]
+                    }],
+                    "schemaUrl": "https://opentelemetry.io/schemas/1.21.0"
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_metrics_to_json() {
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![Metric {
+                        name: "temperature".into(),
+                        data: Some(metric::Data::Gauge(Gauge {
+                            data_points: vec![
+                                NumberDataPoint {
+                                    value: Some(number_data_point::Value::AsDouble(f64::NAN)),
+                                    ..Default::default()
+                                },
+                                NumberDataPoint {
+                                    value: Some(number_data_point::Value::AsDouble(21.5)),
+                                    ..Default::default()
+                                },
+                            ],
+                        })),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        assert_eq!(
+            metrics_to_json(&request),
+            json!({
+                "resourceMetrics": [{
+                    "scopeMetrics": [{
+                        "metrics": [{
+                            "name": "temperature",
+                            "gauge": {"dataPoints": [{"asDouble": "NaN"}, {"asDouble": 21.5}]}
+                        }]
+                    }]
+                }]
+            })
+        );
+    }
+}
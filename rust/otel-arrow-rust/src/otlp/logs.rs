@@ -10,17 +10,27 @@ use related_data::RelatedData;
 use snafu::{OptionExt, ResultExt, ensure};
 
 use crate::arrays::{
-    ByteArrayAccessor, Int32ArrayAccessor, NullableArrayAccessor, StringArrayAccessor,
-    StructColumnAccessor, get_timestamp_nanosecond_array_opt, get_u16_array, get_u32_array_opt,
+    ByteArrayAccessor, ColumnAccessor, EnumArrayAccessor, NullableArrayAccessor,
+    StringArrayAccessor, StructColumnAccessor, get_timestamp_nanosecond_array_opt, get_u16_array,
+    get_u32_array_opt,
 };
-use crate::error::{self, Error, Result};
+use crate::error::{self, Error, ErrorContext, ErrorContextExt, Result};
 use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
 use crate::otlp::common::{ResourceArrays, ScopeArrays};
-use crate::otlp::metrics::AppendAndGet;
+use crate::otlp::enums::{severity_text, validate_enum};
+use crate::otlp::extra_columns::ExtraColumns;
+use crate::otlp::filter::KeptRows;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::timestamps::TimestampChecker;
+use crate::otlp::visitor::{FnVisitor, RecordVisitor};
 use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
-use crate::proto::opentelemetry::common::v1::AnyValue;
 use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope};
+use crate::proto::opentelemetry::logs::v1::{LogRecord, SeverityNumber};
+use crate::proto::opentelemetry::resource::v1::Resource;
 use crate::schema::consts;
 
 use super::attributes::{cbor, store::AttributeValueType};
@@ -34,8 +44,9 @@ struct LogsArrays<'a> {
     observed_time_unix_nano: Option<&'a TimestampNanosecondArray>,
     trace_id: Option<ByteArrayAccessor<'a>>,
     span_id: Option<ByteArrayAccessor<'a>>,
-    severity_number: Option<Int32ArrayAccessor<'a>>,
+    severity_number: Option<EnumArrayAccessor<'a>>,
     severity_text: Option<StringArrayAccessor<'a>>,
+    event_name: Option<StringArrayAccessor<'a>>,
     body: Option<LogBodyArrays<'a>>,
     dropped_attributes_count: Option<&'a UInt32Array>,
     flags: Option<&'a UInt32Array>,
@@ -46,30 +57,17 @@ impl<'a> TryFrom<&'a RecordBatch> for LogsArrays<'a> {
 
     fn try_from(rb: &'a RecordBatch) -> Result<Self> {
         let id = get_u16_array(rb, consts::ID)?;
-        let schema_url = rb
-            .column_by_name(consts::SCHEMA_URL)
-            .map(StringArrayAccessor::try_new)
-            .transpose()?;
+        let schema_url = StringArrayAccessor::try_new_for_column_opt(rb, consts::SCHEMA_URL)?;
 
         let time_unix_nano = get_timestamp_nanosecond_array_opt(rb, consts::TIME_UNIX_NANO)?;
         let observed_time_unix_nano =
             get_timestamp_nanosecond_array_opt(rb, consts::OBSERVED_TIME_UNIX_NANO)?;
-        let trace_id = rb
-            .column_by_name(consts::TRACE_ID)
-            .map(ByteArrayAccessor::try_new)
-            .transpose()?;
-        let span_id = rb
-            .column_by_name(consts::SPAN_ID)
-            .map(ByteArrayAccessor::try_new)
-            .transpose()?;
-        let severity_number = rb
-            .column_by_name(consts::SEVERITY_NUMBER)
-            .map(Int32ArrayAccessor::try_new)
-            .transpose()?;
-        let severity_text = rb
-            .column_by_name(consts::SEVERITY_TEXT)
-            .map(StringArrayAccessor::try_new)
-            .transpose()?;
+        let trace_id = ByteArrayAccessor::try_new_for_column_opt(rb, consts::TRACE_ID)?;
+        let span_id = ByteArrayAccessor::try_new_for_column_opt(rb, consts::SPAN_ID)?;
+        let severity_number =
+            EnumArrayAccessor::try_new_for_column_opt(rb, consts::SEVERITY_NUMBER)?;
+        let severity_text = StringArrayAccessor::try_new_for_column_opt(rb, consts::SEVERITY_TEXT)?;
+        let event_name = StringArrayAccessor::try_new_for_column_opt(rb, consts::EVENT_NAME)?;
 
         let dropped_attributes_count = get_u32_array_opt(rb, consts::DROPPED_ATTRIBUTES_COUNT)?;
         let flags = get_u32_array_opt(rb, consts::FLAGS)?;
@@ -98,6 +96,7 @@ impl<'a> TryFrom<&'a RecordBatch> for LogsArrays<'a> {
             trace_id,
             severity_number,
             severity_text,
+            event_name,
             body,
             dropped_attributes_count,
             flags,
@@ -165,46 +164,94 @@ impl<'a> TryFrom<&'a StructArray> for LogBodyArrays<'a> {
         Ok(Self {
             body,
             value_type: column_accessor.primitive_column(consts::ATTRIBUTE_TYPE)?,
-            str: column_accessor.string_column_op(consts::ATTRIBUTE_STR)?,
+            str: column_accessor.accessor_column_op(consts::ATTRIBUTE_STR)?,
             int: column_accessor.primitive_column_op(consts::ATTRIBUTE_INT)?,
             double: column_accessor.primitive_column_op(consts::ATTRIBUTE_DOUBLE)?,
             bool: column_accessor.bool_column_op(consts::ATTRIBUTE_BOOL)?,
-            bytes: column_accessor.byte_array_column_op(consts::ATTRIBUTE_BYTES)?,
-            ser: column_accessor.byte_array_column_op(consts::ATTRIBUTE_SER)?,
+            bytes: column_accessor.accessor_column_op(consts::ATTRIBUTE_BYTES)?,
+            ser: column_accessor.accessor_column_op(consts::ATTRIBUTE_SER)?,
         })
     }
 }
 
 pub fn logs_from(logs_otap_batch: OtapBatch) -> Result<ExportLogsServiceRequest> {
+    logs_from_with_options(logs_otap_batch, &DecoderOptions::default())
+}
+
+/// Same as [`logs_from`], using the given options to decode the batch.
+pub fn logs_from_with_options(
+    logs_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<ExportLogsServiceRequest> {
+    logs_from_with_report(logs_otap_batch, options).map(|(request, _)| request)
+}
+
+/// Same as [`logs_from_with_options`], also returning the report of the rows dropped
+/// because of [`DecoderOptions::skip_bad_rows`].
+pub fn logs_from_with_report(
+    logs_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<(ExportLogsServiceRequest, DecodeReport)> {
     let mut logs = ExportLogsServiceRequest::default();
+    let report = visit_logs(logs_otap_batch, options, &mut logs)?;
+    Ok((logs, report))
+}
+
+/// Decodes the log records of the batch one at a time, passing each of them to `visit`
+/// along with its resource and scope instead of collecting them into a request.
+pub fn decode_logs_with(
+    logs_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+    visit: impl FnMut(&Resource, &InstrumentationScope, LogRecord),
+) -> Result<DecodeReport> {
+    visit_logs(logs_otap_batch, options, &mut FnVisitor::new(visit))
+}
+
+/// Decodes the log records of the batch, handing them to `visitor` as they are
+/// reconstructed.
+pub fn visit_logs(
+    logs_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+    visitor: &mut impl RecordVisitor<LogRecord>,
+) -> Result<DecodeReport> {
     let mut prev_res_id: Option<u16> = None;
     let mut prev_scope_id: Option<u16> = None;
 
     let mut res_id = 0;
     let mut scope_id = 0;
 
+    let guard = options.start_batch(&logs_otap_batch)?;
     let rb = logs_otap_batch
         .get(ArrowPayloadType::Logs)
         .context(error::LogRecordNotFoundSnafu)?;
 
-    let mut related_data = RelatedData::try_from(&logs_otap_batch)?;
+    let mut related_data = RelatedData::try_from_with_options(&logs_otap_batch, options)?;
+    let timestamps = TimestampChecker::new(options);
+    let extra_columns = ExtraColumns::try_new(rb, ArrowPayloadType::Logs, options)
+        .in_payload(ArrowPayloadType::Logs)?;
+    let kept_rows = KeptRows::try_new(&logs_otap_batch, ArrowPayloadType::Logs, options)
+        .in_payload(ArrowPayloadType::Logs)?;
 
-    let resource_arrays = ResourceArrays::try_from(rb)?;
-    let scope_arrays = ScopeArrays::try_from(rb)?;
-    let logs_arrays = LogsArrays::try_from(rb)?;
+    let resource_arrays = ResourceArrays::try_from(rb).in_payload(ArrowPayloadType::Logs)?;
+    let scope_arrays = ScopeArrays::try_from(rb).in_payload(ArrowPayloadType::Logs)?;
+    let logs_arrays = LogsArrays::try_from(rb).in_payload(ArrowPayloadType::Logs)?;
 
     for idx in 0..rb.num_rows() {
+        guard.check(idx)?;
         let res_delta_id = resource_arrays.id.value_at(idx).unwrap_or_default();
-        res_id += res_delta_id;
+        res_id = add_delta(res_id, res_delta_id).error_context(|| {
+            ErrorContext::default()
+                .payload(ArrowPayloadType::Logs)
+                .row(idx)
+                .column(consts::RESOURCE)
+        })?;
 
         if prev_res_id != Some(res_id) {
             // new resource id
             prev_res_id = Some(res_id);
-            let resource_logs = logs.resource_logs.append_and_get();
             prev_scope_id = None;
 
-            // Update the resource field of the current resource logs
-            let resource = resource_logs.resource.get_or_insert_default();
+            let mut resource = Resource::default();
             if let Some(dropped_attributes_count) =
                 resource_arrays.dropped_attributes_count.value_at(idx)
             {
@@ -215,17 +262,26 @@ pub fn logs_from(logs_otap_batch: OtapBatch) -> Result<ExportLogsServiceRequest>
                 if let Some(attrs) = related_data
                     .res_attr_map_store
                     .as_mut()
-                    .and_then(|store| store.attribute_by_delta_id(res_id))
+                    .map(|store| store.attribute_by_delta_id(res_id, &mut related_data.report))
+                    .transpose()?
+                    .flatten()
                 {
                     resource.attributes = attrs.to_vec();
                 }
             }
 
-            resource_logs.schema_url = resource_arrays.schema_url.value_at(idx).unwrap_or_default();
+            let schema_url = resource_arrays.schema_url.value_at(idx).unwrap_or_default();
+            visitor.visit_resource(resource, schema_url);
         }
 
         let scope_delta_id_opt = scope_arrays.id.value_at(idx);
-        scope_id += scope_delta_id_opt.unwrap_or_default();
+        scope_id =
+            add_delta(scope_id, scope_delta_id_opt.unwrap_or_default()).error_context(|| {
+                ErrorContext::default()
+                    .payload(ArrowPayloadType::Logs)
+                    .row(idx)
+                    .column(consts::SCOPE)
+            })?;
 
         if prev_scope_id != Some(scope_id) {
             prev_scope_id = Some(scope_id);
@@ -234,40 +290,45 @@ pub fn logs_from(logs_otap_batch: OtapBatch) -> Result<ExportLogsServiceRequest>
                 if let Some(attrs) = related_data
                     .scope_attr_map_store
                     .as_mut()
-                    .and_then(|store| store.attribute_by_delta_id(scope_id))
+                    .map(|store| store.attribute_by_delta_id(scope_id, &mut related_data.report))
+                    .transpose()?
+                    .flatten()
                 {
                     scope.attributes = attrs.to_vec();
                 }
             }
 
-            // safety: we must have appended at least one resource logs when reach here
-            let current_scope_logs_slice = &mut logs
-                .resource_logs
-                .last_mut()
-                .expect("At this stage, we should have at least one resource log.")
-                .scope_logs;
-            let scope_logs = current_scope_logs_slice.append_and_get();
-            scope_logs.scope = Some(scope);
-            scope_logs.schema_url = logs_arrays.schema_url.value_at(idx).unwrap_or_default();
+            let schema_url = logs_arrays.schema_url.value_at(idx).unwrap_or_default();
+            visitor.visit_scope(scope, schema_url);
         }
 
-        // safety: we've appended at least one value at each slice when reach here.
-        let current_scope_logs = &mut logs
-            .resource_logs
-            .last_mut()
-            .expect("At this stage, we should have at least one resource log.")
-            .scope_logs
-            .last_mut()
-            .expect("At this stage, we should have added at least one scope log.");
-
-        let current_log_record = current_scope_logs.log_records.append_and_get();
-        let delta_id = logs_arrays.id.value_at_or_default(idx);
-        let log_id = related_data.log_record_id_from_delta(delta_id);
+        let mut current_log_record = LogRecord::default();
+        // the log records without attributes may have no id
+        let delta_id = logs_arrays.id.value_at(idx);
+        let log_id = related_data
+            .log_record_id_from_delta(delta_id.unwrap_or_default())
+            .error_context(|| {
+                ErrorContext::default()
+                    .payload(ArrowPayloadType::Logs)
+                    .row(idx)
+                    .column(consts::ID)
+            })?;
+        if let Some(reason) = kept_rows.dropped(idx) {
+            related_data.report.record(ArrowPayloadType::Logs, reason);
+            continue;
+        }
 
         current_log_record.time_unix_nano =
             logs_arrays.time_unix_nano.value_at_or_default(idx) as u64;
         current_log_record.observed_time_unix_nano =
             logs_arrays.observed_time_unix_nano.value_at_or_default(idx) as u64;
+        timestamps.check_pair(
+            &mut current_log_record.time_unix_nano,
+            &mut current_log_record.observed_time_unix_nano,
+            idx,
+            ArrowPayloadType::Logs,
+            &mut related_data.report,
+        )?;
 
         if let Some(trace_id_bytes) = logs_arrays.trace_id.value_at(idx) {
             ensure!(trace_id_bytes.len() == 16, error::InvalidTraceIdSnafu {
@@ -289,8 +350,23 @@ pub fn logs_from(logs_otap_batch: OtapBatch) -> Result<ExportLogsServiceRequest>
             current_log_record.span_id = span_id_bytes;
         }
 
-        current_log_record.severity_number = logs_arrays.severity_number.value_at_or_default(idx);
+        current_log_record.severity_number = validate_enum::<SeverityNumber>(
+            logs_arrays.severity_number.value_at_or_default(idx),
+            options.enum_validation,
+        )
+        .error_context(|| {
+            ErrorContext::default()
+                .payload(ArrowPayloadType::Logs)
+                .row(idx)
+                .column(consts::SEVERITY_NUMBER)
+        })?;
         current_log_record.severity_text = logs_arrays.severity_text.value_at_or_default(idx);
+        if options.derive_severity_text && current_log_record.severity_text.is_empty() {
+            if let Some(text) = severity_text(current_log_record.severity_number) {
+                current_log_record.severity_text = text.to_string();
+            }
+        }
+        current_log_record.event_name = logs_arrays.event_name.value_at_or_default(idx);
         current_log_record.dropped_attributes_count = logs_arrays
             .dropped_attributes_count
             .value_at_or_default(idx);
@@ -300,14 +376,16 @@ pub fn logs_from(logs_otap_batch: OtapBatch) -> Result<ExportLogsServiceRequest>
             current_log_record.body = Some(body_val?)
         }
 
-        if let Some(attrs) = related_data
-            .log_record_attr_map_store
-            .as_mut()
-            .and_then(|store| store.attribute_by_delta_id(delta_id))
+        if let (Some(delta_id), Some(store)) =
+            (delta_id, related_data.log_record_attr_map_store.as_mut())
         {
-            current_log_record.attributes = attrs.to_vec()
+            if let Some(attrs) = store.attribute_by_delta_id(delta_id, &mut related_data.report)? {
+                current_log_record.attributes = attrs.to_vec()
+            }
         }
+        extra_columns.append_attributes(idx, &mut current_log_record.attributes);
+        visitor.visit_record(current_log_record);
     }
 
-    Ok(logs)
+    Ok(related_data.report)
 }
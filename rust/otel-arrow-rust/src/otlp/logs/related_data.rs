@@ -3,7 +3,10 @@
 
 use crate::error;
 use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
 use crate::otlp::attributes::store::Attribute16Store;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
 use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 
 pub struct RelatedData {
@@ -12,33 +15,50 @@ pub struct RelatedData {
     pub(crate) res_attr_map_store: Option<Attribute16Store>,
     pub(crate) scope_attr_map_store: Option<Attribute16Store>,
     pub(crate) log_record_attr_map_store: Option<Attribute16Store>,
+
+    pub(crate) report: DecodeReport,
 }
 
 impl<'a> TryFrom<&'a OtapBatch> for RelatedData {
     type Error = error::Error;
 
     fn try_from(otap_batch: &'a OtapBatch) -> error::Result<Self> {
+        Self::try_from_with_options(otap_batch, &DecoderOptions::default())
+    }
+}
+
+impl RelatedData {
+    pub fn try_from_with_options(
+        otap_batch: &OtapBatch,
+        options: &DecoderOptions,
+    ) -> error::Result<Self> {
+        let mut report = DecodeReport::default();
         Ok(Self {
             log_record_id: 0,
-            res_attr_map_store: otap_batch
-                .get(ArrowPayloadType::ResourceAttrs)
-                .map(Attribute16Store::try_from)
-                .transpose()?,
-            scope_attr_map_store: otap_batch
-                .get(ArrowPayloadType::ScopeAttrs)
-                .map(Attribute16Store::try_from)
-                .transpose()?,
-            log_record_attr_map_store: otap_batch
-                .get(ArrowPayloadType::LogAttrs)
-                .map(Attribute16Store::try_from)
-                .transpose()?,
+            res_attr_map_store: Attribute16Store::from_payload(
+                otap_batch,
+                ArrowPayloadType::ResourceAttrs,
+                options,
+                &mut report,
+            )?,
+            scope_attr_map_store: Attribute16Store::from_payload(
+                otap_batch,
+                ArrowPayloadType::ScopeAttrs,
+                options,
+                &mut report,
+            )?,
+            log_record_attr_map_store: Attribute16Store::from_payload(
+                otap_batch,
+                ArrowPayloadType::LogAttrs,
+                options,
+                &mut report,
+            )?,
+            report,
         })
     }
-}
 
-impl RelatedData {
-    pub fn log_record_id_from_delta(&mut self, delta: u16) -> u16 {
-        self.log_record_id += delta;
-        self.log_record_id
+    pub fn log_record_id_from_delta(&mut self, delta: u16) -> error::Result<u16> {
+        self.log_record_id = add_delta(self.log_record_id, delta)?;
+        Ok(self.log_record_id)
     }
 }
@@ -11,24 +11,31 @@
 // limitations under the License.
 
 use crate::arrays::{
-    Int32ArrayAccessor, NullableArrayAccessor, StringArrayAccessor, get_bool_array_opt,
-    get_u8_array, get_u16_array,
+    BooleanArrayAccessor, ColumnAccessor, Int32ArrayAccessor, NullableArrayAccessor,
+    StringArrayAccessor, get_u8_array, get_u16_array,
 };
-use crate::error;
+use crate::error::{self, ErrorContext, ErrorContextExt};
 use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
 use crate::otlp::common::{ResourceArrays, ScopeArrays};
 use crate::otlp::metrics::related_data::RelatedData;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::visitor::{FnVisitor, RecordVisitor};
 use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
-use crate::proto::opentelemetry::metrics::v1::metric;
+use crate::proto::opentelemetry::common::v1::InstrumentationScope;
+use crate::proto::opentelemetry::metrics::v1::{Metric, metric};
+use crate::proto::opentelemetry::resource::v1::Resource;
 use crate::schema::consts;
-use arrow::array::{BooleanArray, RecordBatch, UInt8Array, UInt16Array};
+use arrow::array::{RecordBatch, UInt8Array, UInt16Array};
 use num_enum::TryFromPrimitive;
 use snafu::{OptionExt, ResultExt};
 
 pub mod data_points;
 pub mod exemplar;
 mod related_data;
+pub mod temporality;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug, TryFromPrimitive)]
 #[repr(u8)]
@@ -49,7 +56,7 @@ struct MetricsArrays<'a> {
     description: Option<StringArrayAccessor<'a>>,
     unit: Option<StringArrayAccessor<'a>>,
     aggregation_temporality: Option<Int32ArrayAccessor<'a>>,
-    is_monotonic: Option<&'a BooleanArray>,
+    is_monotonic: Option<BooleanArrayAccessor<'a>>,
 }
 
 impl<'a> TryFrom<&'a RecordBatch> for MetricsArrays<'a> {
@@ -63,24 +70,13 @@ impl<'a> TryFrom<&'a RecordBatch> for MetricsArrays<'a> {
                 .context(error::ColumnNotFoundSnafu { name: consts::NAME })?,
         )?;
 
-        let description = rb
-            .column_by_name(consts::DESCRIPTION)
-            .map(StringArrayAccessor::try_new)
-            .transpose()?;
-        let schema_url = rb
-            .column_by_name(consts::SCHEMA_URL)
-            .map(StringArrayAccessor::try_new)
-            .transpose()?;
-
-        let unit = rb
-            .column_by_name(consts::UNIT)
-            .map(StringArrayAccessor::try_new)
-            .transpose()?;
-        let aggregation_temporality = rb
-            .column_by_name(consts::AGGREGATION_TEMPORALITY)
-            .map(Int32ArrayAccessor::try_new)
-            .transpose()?;
-        let is_monotonic = get_bool_array_opt(rb, consts::IS_MONOTONIC)?;
+        let description = StringArrayAccessor::try_new_for_column_opt(rb, consts::DESCRIPTION)?;
+        let schema_url = StringArrayAccessor::try_new_for_column_opt(rb, consts::SCHEMA_URL)?;
+
+        let unit = StringArrayAccessor::try_new_for_column_opt(rb, consts::UNIT)?;
+        let aggregation_temporality =
+            Int32ArrayAccessor::try_new_for_column_opt(rb, consts::AGGREGATION_TEMPORALITY)?;
+        let is_monotonic = BooleanArrayAccessor::try_new_for_column_opt(rb, consts::IS_MONOTONIC)?;
         Ok(Self {
             id,
             metric_type,
@@ -96,35 +92,79 @@ impl<'a> TryFrom<&'a RecordBatch> for MetricsArrays<'a> {
 
 /// Builds [ExportMetricsServiceRequest] from given record batch.
 pub fn metrics_from(metrics_otap_batch: OtapBatch) -> error::Result<ExportMetricsServiceRequest> {
+    metrics_from_with_options(metrics_otap_batch, &DecoderOptions::default())
+}
+
+/// Same as [`metrics_from`], using the given options to decode the batch.
+pub fn metrics_from_with_options(
+    metrics_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> error::Result<ExportMetricsServiceRequest> {
+    metrics_from_with_report(metrics_otap_batch, options).map(|(request, _)| request)
+}
+
+/// Same as [`metrics_from_with_options`], also returning the report of the rows dropped
+/// because of [`DecoderOptions::skip_bad_rows`].
+pub fn metrics_from_with_report(
+    metrics_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> error::Result<(ExportMetricsServiceRequest, DecodeReport)> {
     let mut metrics = ExportMetricsServiceRequest::default();
+    let report = visit_metrics(metrics_otap_batch, options, &mut metrics)?;
+    Ok((metrics, report))
+}
 
+/// Decodes the metrics of the batch one at a time, passing each of them with its data
+/// points to `visit` along with its resource and scope instead of collecting them into a
+/// request.
+pub fn decode_metrics_with(
+    metrics_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+    visit: impl FnMut(&Resource, &InstrumentationScope, Metric),
+) -> error::Result<DecodeReport> {
+    visit_metrics(metrics_otap_batch, options, &mut FnVisitor::new(visit))
+}
+
+/// Decodes the metrics of the batch, handing them to `visitor` as they are reconstructed.
+pub fn visit_metrics(
+    metrics_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+    visitor: &mut impl RecordVisitor<Metric>,
+) -> error::Result<DecodeReport> {
     let mut prev_res_id: Option<u16> = None;
     let mut prev_scope_id: Option<u16> = None;
 
     let mut res_id = 0;
     let mut scope_id = 0;
 
+    let guard = options.start_batch(&metrics_otap_batch)?;
     let rb = metrics_otap_batch
         .get(ArrowPayloadType::UnivariateMetrics)
         .context(error::MetricRecordNotFoundSnafu)?;
-    let mut related_data = RelatedData::try_from(&metrics_otap_batch)?;
+    let mut related_data = RelatedData::try_from_with_options(&metrics_otap_batch, options)?;
 
-    let resource_arrays = ResourceArrays::try_from(rb)?;
-    let scope_arrays = ScopeArrays::try_from(rb)?;
-    let metrics_arrays = MetricsArrays::try_from(rb)?;
+    let resource_arrays =
+        ResourceArrays::try_from(rb).in_payload(ArrowPayloadType::UnivariateMetrics)?;
+    let scope_arrays = ScopeArrays::try_from(rb).in_payload(ArrowPayloadType::UnivariateMetrics)?;
+    let metrics_arrays =
+        MetricsArrays::try_from(rb).in_payload(ArrowPayloadType::UnivariateMetrics)?;
 
     for idx in 0..rb.num_rows() {
+        guard.check(idx)?;
         let res_delta_id = resource_arrays.id.value_at(idx).unwrap_or_default();
-        res_id += res_delta_id;
+        res_id = add_delta(res_id, res_delta_id).error_context(|| {
+            ErrorContext::default()
+                .payload(ArrowPayloadType::UnivariateMetrics)
+                .row(idx)
+                .column(consts::RESOURCE)
+        })?;
 
         if prev_res_id != Some(res_id) {
             // new resource id
             prev_res_id = Some(res_id);
-            let res_metrics = metrics.resource_metrics.append_and_get();
             prev_scope_id = None;
 
-            // Update the resource field of current resource metrics.
-            let resource = res_metrics.resource.get_or_insert_default();
+            let mut resource = Resource::default();
             if let Some(dropped_attributes_count) =
                 resource_arrays.dropped_attributes_count.value_at(idx)
             {
@@ -134,53 +174,51 @@ pub fn metrics_from(metrics_otap_batch: OtapBatch) -> error::Result<ExportMetric
             if let Some(res_id) = resource_arrays.id.value_at(idx) {
                 if let Some(attrs) = related_data
                     .res_attr_map_store
-                    .attribute_by_delta_id(res_id)
+                    .attribute_by_delta_id(res_id, &mut related_data.report)?
                 {
                     resource.attributes = attrs.to_vec();
                 }
             }
 
-            res_metrics.schema_url = resource_arrays.schema_url.value_at(idx).unwrap_or_default();
+            let schema_url = resource_arrays.schema_url.value_at(idx).unwrap_or_default();
+            visitor.visit_resource(resource, schema_url);
         }
 
         let scope_delta_id_opt = scope_arrays.id.value_at(idx);
-        scope_id += scope_delta_id_opt.unwrap_or_default();
+        scope_id =
+            add_delta(scope_id, scope_delta_id_opt.unwrap_or_default()).error_context(|| {
+                ErrorContext::default()
+                    .payload(ArrowPayloadType::UnivariateMetrics)
+                    .row(idx)
+                    .column(consts::SCOPE)
+            })?;
 
         if prev_scope_id != Some(scope_id) {
             prev_scope_id = Some(scope_id);
-            // safety: We must have appended at least one resource metrics when reach here
-            let current_scope_metrics_slice = &mut metrics
-                .resource_metrics
-                .last_mut()
-                .expect("At this stage, we should have at least one resource metrics.")
-                .scope_metrics;
-            let scope_metrics = current_scope_metrics_slice.append_and_get();
             let mut scope = scope_arrays.create_instrumentation_scope(idx);
             if let Some(scope_id) = scope_delta_id_opt {
                 if let Some(attrs) = related_data
                     .scope_attr_map_store
-                    .attribute_by_delta_id(scope_id)
+                    .attribute_by_delta_id(scope_id, &mut related_data.report)?
                 {
                     scope.attributes = attrs.to_vec();
                 }
             }
-            scope_metrics.scope = Some(scope);
             // ScopeMetrics uses the schema_url from metrics arrays.
-            scope_metrics.schema_url = metrics_arrays.schema_url.value_at(idx).unwrap_or_default();
+            let schema_url = metrics_arrays.schema_url.value_at(idx).unwrap_or_default();
+            visitor.visit_scope(scope, schema_url);
         }
 
-        // Creates a metric at the end of current scope metrics slice.
-        // safety: we've append at least one value at each slice when reach here.
-        let current_scope_metrics = &mut metrics
-            .resource_metrics
-            .last_mut()
-            .expect("At this stage, we should have at least one resource metrics.")
-            .scope_metrics
-            .last_mut()
-            .expect("At this stage, we should ahve added at least one scope metrics.");
-        let current_metric = current_scope_metrics.metrics.append_and_get();
+        let mut current_metric = Metric::default();
         let delta_id = metrics_arrays.id.value_at_or_default(idx);
-        let metric_id = related_data.metric_id_from_delta(delta_id);
+        let metric_id = related_data
+            .metric_id_from_delta(delta_id)
+            .error_context(|| {
+                ErrorContext::default()
+                    .payload(ArrowPayloadType::UnivariateMetrics)
+                    .row(idx)
+                    .column(consts::ID)
+            })?;
         let metric_type_val = metrics_arrays.metric_type.value_at_or_default(idx);
         let metric_type =
             MetricType::try_from(metric_type_val).context(error::UnrecognizedMetricTypeSnafu {
@@ -248,9 +286,10 @@ pub fn metrics_from(metrics_otap_batch: OtapBatch) -> error::Result<ExportMetric
             }
             MetricType::Empty => return error::EmptyMetricTypeSnafu.fail(),
         }
+        visitor.visit_record(current_metric);
     }
 
-    Ok(metrics)
+    Ok(related_data.report)
 }
 
 pub trait AppendAndGet<T> {
@@ -266,3 +305,111 @@ where
         self.last_mut().expect("vec is not empty")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{ArrayRef, BooleanArray, DictionaryArray, Int32Array};
+    use arrow::compute::cast;
+    use arrow::datatypes::{DataType, Field, Schema, UInt8Type};
+
+    use crate::encode::metrics::MetricsProducer;
+    use crate::proto::opentelemetry::metrics::v1::{
+        AggregationTemporality, ResourceMetrics, ScopeMetrics, Sum,
+    };
+
+    fn sum(
+        name: &str,
+        aggregation_temporality: AggregationTemporality,
+        is_monotonic: bool,
+    ) -> Metric {
+        Metric {
+            name: name.into(),
+            data: Some(metric::Data::Sum(Sum {
+                data_points: vec![],
+                aggregation_temporality: aggregation_temporality as i32,
+                is_monotonic,
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn replace_column(rb: &RecordBatch, name: &str, column: ArrayRef) -> RecordBatch {
+        let schema = rb.schema();
+        let (idx, _) = schema.column_with_name(name).unwrap();
+        let mut fields = schema.fields().to_vec();
+        fields[idx] = Arc::new(Field::new(name, column.data_type().clone(), true));
+        let mut columns = rb.columns().to_vec();
+        columns[idx] = column;
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap()
+    }
+
+    #[test]
+    fn test_sum_temporality_and_monotonicity() {
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource::default()),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: Some(InstrumentationScope::default()),
+                    metrics: vec![
+                        sum("a", AggregationTemporality::Unspecified, false),
+                        sum("b", AggregationTemporality::Delta, true),
+                        sum("c", AggregationTemporality::Cumulative, false),
+                        sum("d", AggregationTemporality::Cumulative, true),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let produce = || MetricsProducer::new().produce(&request).unwrap();
+        assert_eq!(metrics_from(produce()).unwrap(), request);
+
+        // unencoded temporalities and dictionary encoded monotonicities
+        let mut otap_batch = produce();
+        let rb = otap_batch.get(ArrowPayloadType::UnivariateMetrics).unwrap();
+        let temporality = cast(
+            rb.column_by_name(consts::AGGREGATION_TEMPORALITY).unwrap(),
+            &DataType::Int32,
+        )
+        .unwrap();
+        let is_monotonic = DictionaryArray::<UInt8Type>::try_new(
+            UInt8Array::from(vec![0, 1, 0, 1]),
+            Arc::new(BooleanArray::from(vec![false, true])),
+        )
+        .unwrap();
+        let rb = replace_column(rb, consts::AGGREGATION_TEMPORALITY, temporality);
+        let rb = replace_column(&rb, consts::IS_MONOTONIC, Arc::new(is_monotonic));
+        otap_batch.set(ArrowPayloadType::UnivariateMetrics, rb.clone());
+        assert_eq!(metrics_from(otap_batch).unwrap(), request);
+
+        // the null values and missing columns default to unspecified and not monotonic
+        let rb = replace_column(
+            &rb,
+            consts::AGGREGATION_TEMPORALITY,
+            Arc::new(Int32Array::from(vec![None, Some(1), None, Some(2)])),
+        );
+        let rb = rb
+            .project(
+                &(0..rb.num_columns())
+                    .filter(|idx| rb.schema().field(*idx).name() != consts::IS_MONOTONIC)
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+        let mut otap_batch = produce();
+        otap_batch.set(ArrowPayloadType::UnivariateMetrics, rb);
+        let decoded = metrics_from(otap_batch).unwrap();
+        let sums: Vec<_> = decoded.resource_metrics[0].scope_metrics[0]
+            .metrics
+            .iter()
+            .map(|metric| match &metric.data {
+                Some(metric::Data::Sum(sum)) => (sum.aggregation_temporality, sum.is_monotonic),
+                _ => panic!("expected a sum"),
+            })
+            .collect();
+        assert_eq!(sums, vec![(0, false), (1, false), (0, false), (2, false)]);
+    }
+}
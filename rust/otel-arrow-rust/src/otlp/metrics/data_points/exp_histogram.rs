@@ -14,15 +14,20 @@ use crate::arrays::{
     NullableArrayAccessor, get_f64_array_opt, get_i32_array, get_timestamp_nanosecond_array,
     get_timestamp_nanosecond_array_opt, get_u16_array, get_u32_array_opt, get_u64_array,
 };
-use crate::error;
+use crate::error::{self, ErrorContext, ErrorContextExt};
+use crate::otlp::attributes::add_delta;
 use crate::otlp::attributes::store::Attribute32Store;
 use crate::otlp::metrics::AppendAndGet;
 use crate::otlp::metrics::data_points::data_point_store::EHistogramDataPointsStore;
 use crate::otlp::metrics::data_points::histogram::ListValueAccessor;
 use crate::otlp::metrics::exemplar::ExemplarsStore;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::timestamps::TimestampChecker;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use crate::proto::opentelemetry::metrics::v1::exponential_histogram_data_point::Buckets;
 use crate::schema::consts;
-use arrow::array::{Array, Int32Array, ListArray, RecordBatch, StructArray};
+use arrow::array::{Array, Int32Array, RecordBatch, StructArray};
 use arrow::datatypes::{DataType, Field, FieldRef, Fields, UInt64Type};
 use snafu::OptionExt;
 
@@ -31,7 +36,10 @@ impl EHistogramDataPointsStore {
         rb: &RecordBatch,
         exemplar_store: &mut ExemplarsStore,
         attr_store: &Attribute32Store,
+        options: &DecoderOptions,
+        report: &mut DecodeReport,
     ) -> error::Result<Self> {
+        let timestamps = TimestampChecker::new(options);
         let mut store = Self::default();
 
         let id_arr_opt = get_u32_array_opt(rb, consts::ID)?;
@@ -56,12 +64,20 @@ impl EHistogramDataPointsStore {
 
         for idx in 0..rb.num_rows() {
             let delta = delta_arr.value_at_or_default(idx);
-            let parent_id = prev_parent_id + delta;
+            let parent_id = add_delta(prev_parent_id, delta)
+                .error_context(|| ErrorContext::default().row(idx).column(consts::PARENT_ID))?;
             prev_parent_id = parent_id;
             let ehdps = store.get_or_default(parent_id);
             let hdp = ehdps.append_and_get();
             hdp.start_time_unix_nano = start_time_unix_nano.value_at_or_default(idx) as u64;
             hdp.time_unix_nano = time_unix_nano.value_at_or_default(idx) as u64;
+            timestamps.check_range(
+                &mut hdp.start_time_unix_nano,
+                &mut hdp.time_unix_nano,
+                idx,
+                ArrowPayloadType::ExpHistogramDataPoints,
+                report,
+            )?;
             hdp.count = histogram_count.value_at_or_default(idx);
             hdp.sum = sum_arr.value_at(idx);
             hdp.scale = scale_arr.value_at_or_default(idx);
@@ -82,7 +98,8 @@ impl EHistogramDataPointsStore {
             hdp.min = min_arr.value_at(idx);
 
             if let Some(id) = id_arr_opt.value_at(idx) {
-                last_id += id;
+                last_id = add_delta(last_id, id)
+                    .error_context(|| ErrorContext::default().row(idx).column(consts::ID))?;
                 let exemplars = exemplar_store.get_or_create_exemplar_by_id(last_id);
                 hdp.exemplars = std::mem::take(exemplars);
                 if let Some(attrs) = attr_store.attribute_by_id(last_id) {
@@ -97,7 +114,7 @@ impl EHistogramDataPointsStore {
 
 struct PositiveNegativeArrayAccess<'a> {
     offset_array: &'a Int32Array,
-    bucket_count: ListValueAccessor<'a, UInt64Type>,
+    bucket_count: ListValueAccessor<UInt64Type>,
 }
 
 impl<'a> PositiveNegativeArrayAccess<'a> {
@@ -151,16 +168,7 @@ impl<'a> PositiveNegativeArrayAccess<'a> {
                 name: consts::EXP_HISTOGRAM_BUCKET_COUNTS,
             })?;
 
-        let bucket_count_array = bucket_count_array
-            .as_any()
-            .downcast_ref::<ListArray>()
-            .with_context(|| error::ColumnDataTypeMismatchSnafu {
-                name: consts::EXP_HISTOGRAM_BUCKET_COUNTS,
-                expect: Self::bucket_counts_data_type(),
-                actual: bucket_count_array.data_type().clone(),
-            })?;
-
-        let bucket_count = ListValueAccessor::try_new_from_list(bucket_count_array)?;
+        let bucket_count = ListValueAccessor::try_new(bucket_count_array)?;
         Ok(Self {
             offset_array,
             bucket_count,
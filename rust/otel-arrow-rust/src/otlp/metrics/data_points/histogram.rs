@@ -12,20 +12,25 @@
 
 use crate::arrays::{
     NullableArrayAccessor, get_f64_array_opt, get_timestamp_nanosecond_array,
-    get_timestamp_nanosecond_array_opt, get_u16_array, get_u32_array, get_u32_array_opt,
-    get_u64_array,
+    get_timestamp_nanosecond_array_opt, get_u16_array, get_u32_array_opt, get_u64_array,
 };
-use crate::error;
+use crate::error::{self, ErrorContext, ErrorContextExt};
+use crate::otlp::attributes::add_delta;
 use crate::otlp::attributes::store::Attribute32Store;
 use crate::otlp::metrics::AppendAndGet;
 use crate::otlp::metrics::data_points::data_point_store::HistogramDataPointsStore;
 use crate::otlp::metrics::exemplar::ExemplarsStore;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::timestamps::TimestampChecker;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use crate::schema::consts;
 use arrow::array::{Array, ArrayRef, ListArray, PrimitiveArray, RecordBatch};
+use arrow::compute::{CastOptions, cast_with_options};
 use arrow::datatypes::{
     ArrowNativeType, ArrowPrimitiveType, DataType, Field, FieldRef, Float64Type, UInt64Type,
 };
-use snafu::OptionExt;
+use snafu::{OptionExt, ensure};
 
 impl HistogramDataPointsStore {
     // See https://github.com/open-telemetry/otel-arrow/blob/985aa1500a012859cec44855e187eacf46eda7c8/pkg/otel/metrics/otlp/histogram.go#L139
@@ -33,7 +38,10 @@ impl HistogramDataPointsStore {
         rb: &RecordBatch,
         exemplar_store: &mut ExemplarsStore,
         attrs_store: &Attribute32Store,
+        options: &DecoderOptions,
+        report: &mut DecodeReport,
     ) -> error::Result<HistogramDataPointsStore> {
+        let timestamps = TimestampChecker::new(options);
         let mut store = HistogramDataPointsStore::default();
 
         let id_array_opt = get_u32_array_opt(rb, consts::ID)?;
@@ -43,20 +51,20 @@ impl HistogramDataPointsStore {
         let time_unix_nano = get_timestamp_nanosecond_array(rb, consts::TIME_UNIX_NANO)?;
         let histogram_count = get_u64_array(rb, consts::HISTOGRAM_COUNT)?;
         let sum = get_f64_array_opt(rb, consts::HISTOGRAM_SUM)?;
-        let bucket_counts_arr: ListValueAccessor<'_, UInt64Type> = ListValueAccessor::try_new(
+        let bucket_counts_arr: ListValueAccessor<UInt64Type> = ListValueAccessor::try_new(
             rb.column_by_name(consts::HISTOGRAM_BUCKET_COUNTS).context(
                 error::ColumnNotFoundSnafu {
                     name: consts::HISTOGRAM_BUCKET_COUNTS,
                 },
             )?,
         )?;
-        let explicit_bounds_arr: ListValueAccessor<'_, Float64Type> = ListValueAccessor::try_new(
+        let explicit_bounds_arr: ListValueAccessor<Float64Type> = ListValueAccessor::try_new(
             rb.column_by_name(consts::HISTOGRAM_EXPLICIT_BOUNDS)
                 .context(error::ColumnNotFoundSnafu {
                     name: consts::HISTOGRAM_EXPLICIT_BOUNDS,
                 })?,
         )?;
-        let flags_arr = get_u32_array(rb, consts::FLAGS)?;
+        let flags_arr = get_u32_array_opt(rb, consts::FLAGS)?;
         let max_arr = get_f64_array_opt(rb, consts::HISTOGRAM_MAX)?;
         let min_arr = get_f64_array_opt(rb, consts::HISTOGRAM_MIN)?;
 
@@ -65,7 +73,8 @@ impl HistogramDataPointsStore {
 
         for idx in 0..rb.num_rows() {
             let delta = delta_id.value_at_or_default(idx);
-            let parent_id = prev_parent_id + delta;
+            let parent_id = add_delta(prev_parent_id, delta)
+                .error_context(|| ErrorContext::default().row(idx).column(consts::PARENT_ID))?;
             prev_parent_id = parent_id;
 
             // Creates a new HistogramDataPoint and append to the list.
@@ -73,6 +82,13 @@ impl HistogramDataPointsStore {
 
             hdps.start_time_unix_nano = start_time_unix_nano.value_at_or_default(idx) as u64;
             hdps.time_unix_nano = time_unix_nano.value_at_or_default(idx) as u64;
+            timestamps.check_range(
+                &mut hdps.start_time_unix_nano,
+                &mut hdps.time_unix_nano,
+                idx,
+                ArrowPayloadType::HistogramDataPoints,
+                report,
+            )?;
             hdps.count = histogram_count.value_at_or_default(idx);
             hdps.sum = sum.value_at(idx);
             if let Some(bucket_counts) = bucket_counts_arr.value_at_opt(idx) {
@@ -87,7 +103,8 @@ impl HistogramDataPointsStore {
             hdps.min = min_arr.value_at(idx);
 
             if let Some(id) = id_array_opt.value_at(idx) {
-                last_id += id;
+                last_id = add_delta(last_id, id)
+                    .error_context(|| ErrorContext::default().row(idx).column(consts::ID))?;
                 let exemplars = exemplar_store.get_or_create_exemplar_by_id(last_id);
                 hdps.exemplars = std::mem::take(exemplars);
                 if let Some(attrs) = attrs_store.attribute_by_id(last_id) {
@@ -101,31 +118,59 @@ impl HistogramDataPointsStore {
 }
 
 /// Helper to access the element in a list array.
-pub struct ListValueAccessor<'a, T: ArrowPrimitiveType> {
-    list: &'a ListArray,
-    value: &'a PrimitiveArray<T>,
+///
+/// The producers encode the lists as `List`, `LargeList` or `FixedSizeList`, with values of
+/// any numeric width, e.g. `UInt32` bucket counts. They are cast to a `List` of values of
+/// type `T`, the values out of the range of `T` being read as zero.
+pub struct ListValueAccessor<T: ArrowPrimitiveType> {
+    list: ListArray,
+    value: PrimitiveArray<T>,
 }
 
-impl<'a, T> ListValueAccessor<'a, T>
+impl<T> ListValueAccessor<T>
 where
     T: ArrowPrimitiveType,
 {
-    pub fn try_new(list: &'a ArrayRef) -> error::Result<Self> {
-        let list = list.as_any().downcast_ref::<ListArray>().with_context(|| {
-            error::InvalidListArraySnafu {
-                //todo: maybe set the field name here.
-                expect_oneof: vec![DataType::List(FieldRef::new(Field::new(
-                    "",
-                    T::DATA_TYPE,
-                    true,
-                )))],
-                actual: list.data_type().clone(),
-            }
-        })?;
+    pub fn try_new(list: &ArrayRef) -> error::Result<Self> {
+        let list_type = DataType::List(FieldRef::new(Field::new_list_field(T::DATA_TYPE, true)));
+        let invalid = || error::InvalidListArraySnafu {
+            //todo: maybe set the field name here.
+            expect_oneof: vec![
+                list_type.clone(),
+                DataType::LargeList(FieldRef::new(Field::new_list_field(T::DATA_TYPE, true))),
+                DataType::FixedSizeList(
+                    FieldRef::new(Field::new_list_field(T::DATA_TYPE, true)),
+                    0,
+                ),
+            ],
+            actual: list.data_type().clone(),
+        };
+        let value_type = match list.data_type() {
+            DataType::List(field)
+            | DataType::LargeList(field)
+            | DataType::FixedSizeList(field, _) => field.data_type(),
+            _ => return invalid().fail(),
+        };
+        ensure!(value_type.is_numeric(), invalid());
+        let list = if list.data_type() == &list_type {
+            list.clone()
+        } else {
+            let options = CastOptions {
+                safe: true,
+                ..Default::default()
+            };
+            cast_with_options(list, &list_type, &options).map_err(|_| invalid().build())?
+        };
+        // safety: the list was just cast to the list type
+        let list = list
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .expect("list array")
+            .clone();
         Self::try_new_from_list(list)
     }
 
-    pub fn try_new_from_list(list: &'a ListArray) -> error::Result<Self> {
+    fn try_new_from_list(list: ListArray) -> error::Result<Self> {
         let value_array = list.values();
         let value = value_array
             .as_any()
@@ -133,7 +178,8 @@ where
             .with_context(|| error::InvalidListArraySnafu {
                 expect_oneof: vec![T::DATA_TYPE],
                 actual: value_array.data_type().clone(),
-            })?;
+            })?
+            .clone();
 
         Ok(Self { list, value })
     }
@@ -152,3 +198,57 @@ where
         Some(vec)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{
+        FixedSizeListArray, Float32Array, Int64Array, LargeListArray, StringArray, UInt32Array,
+    };
+    use arrow::buffer::OffsetBuffer;
+
+    #[test]
+    fn test_list_value_accessor_encodings() {
+        let large_list: ArrayRef = Arc::new(LargeListArray::new(
+            Arc::new(Field::new_list_field(DataType::UInt32, true)),
+            OffsetBuffer::from_lengths([2, 0, 1]),
+            Arc::new(UInt32Array::from(vec![1, 2, 3])),
+            None,
+        ));
+        let accessor = ListValueAccessor::<UInt64Type>::try_new(&large_list).unwrap();
+        assert_eq!(accessor.value_at_opt(0), Some(vec![1, 2]));
+        assert_eq!(accessor.value_at_opt(1), Some(vec![]));
+        assert_eq!(accessor.value_at_opt(2), Some(vec![3]));
+
+        // negative counts are out of the range of u64
+        let fixed_size_list: ArrayRef = Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new_list_field(DataType::Int64, true)),
+            2,
+            Arc::new(Int64Array::from(vec![4, -1, 5, 6])),
+            None,
+        ));
+        let accessor = ListValueAccessor::<UInt64Type>::try_new(&fixed_size_list).unwrap();
+        assert_eq!(accessor.value_at_opt(0), Some(vec![4, 0]));
+        assert_eq!(accessor.value_at_opt(1), Some(vec![5, 6]));
+
+        let list: ArrayRef = Arc::new(ListArray::new(
+            Arc::new(Field::new_list_field(DataType::Float32, true)),
+            OffsetBuffer::from_lengths([2]),
+            Arc::new(Float32Array::from(vec![0.5, 1.5])),
+            None,
+        ));
+        let accessor = ListValueAccessor::<Float64Type>::try_new(&list).unwrap();
+        assert_eq!(accessor.value_at_opt(0), Some(vec![0.5, 1.5]));
+
+        let strings: ArrayRef = Arc::new(ListArray::new(
+            Arc::new(Field::new_list_field(DataType::Utf8, true)),
+            OffsetBuffer::from_lengths([1]),
+            Arc::new(StringArray::from(vec!["1"])),
+            None,
+        ));
+        assert!(ListValueAccessor::<UInt64Type>::try_new(&strings).is_err());
+    }
+}
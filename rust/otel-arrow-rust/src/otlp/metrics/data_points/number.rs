@@ -14,10 +14,15 @@ use crate::arrays::{
     NullableArrayAccessor, get_f64_array_opt, get_i64_array_opt, get_timestamp_nanosecond_array,
     get_timestamp_nanosecond_array_opt, get_u16_array, get_u32_array, get_u32_array_opt,
 };
-use crate::error::Result;
+use crate::error::{ErrorContext, ErrorContextExt, Result};
+use crate::otlp::attributes::add_delta;
 use crate::otlp::attributes::store::Attribute32Store;
 use crate::otlp::metrics::data_points::data_point_store::NumberDataPointsStore;
 use crate::otlp::metrics::exemplar::ExemplarsStore;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::timestamps::TimestampChecker;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use crate::proto::opentelemetry::metrics::v1::NumberDataPoint;
 use crate::proto::opentelemetry::metrics::v1::number_data_point::Value;
 use crate::schema::consts;
@@ -29,7 +34,10 @@ impl NumberDataPointsStore {
         rb: &RecordBatch,
         exemplar_store: &mut ExemplarsStore,
         attribute_store: &Attribute32Store,
+        options: &DecoderOptions,
+        report: &mut DecodeReport,
     ) -> Result<NumberDataPointsStore> {
+        let timestamps = TimestampChecker::new(options);
         let mut store = NumberDataPointsStore::default();
 
         let id_array = get_u32_array(rb, consts::ID)?;
@@ -50,7 +58,8 @@ impl NumberDataPointsStore {
         for idx in 0..rb.num_rows() {
             let id = id_array.value_at(idx);
             let delta = parent_id_array.value_at(idx).unwrap_or_default();
-            let parent_id = prev_parent_id + delta;
+            let parent_id = add_delta(prev_parent_id, delta)
+                .error_context(|| ErrorContext::default().row(idx).column(consts::PARENT_ID))?;
             prev_parent_id = parent_id;
 
             let nbdps = store.get_or_default(parent_id);
@@ -63,6 +72,13 @@ impl NumberDataPointsStore {
                 flags: flags.value_at_or_default(idx),
                 value: None,
             };
+            timestamps.check_range(
+                &mut nbdp.start_time_unix_nano,
+                &mut nbdp.time_unix_nano,
+                idx,
+                ArrowPayloadType::NumberDataPoints,
+                report,
+            )?;
 
             match (int_value.value_at(idx), double_value.value_at(idx)) {
                 (Some(int), None) => {
@@ -80,7 +96,8 @@ impl NumberDataPointsStore {
             }
 
             if let Some(id) = id {
-                last_id += id;
+                last_id = add_delta(last_id, id)
+                    .error_context(|| ErrorContext::default().row(idx).column(consts::ID))?;
                 let exemplars = exemplar_store.get_or_create_exemplar_by_id(last_id);
                 nbdp.exemplars.extend(std::mem::take(exemplars));
 
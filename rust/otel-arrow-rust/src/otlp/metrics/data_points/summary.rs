@@ -12,13 +12,17 @@
 
 use crate::arrays::{
     NullableArrayAccessor, get_f64_array, get_timestamp_nanosecond_array,
-    get_timestamp_nanosecond_array_opt, get_u16_array, get_u32_array, get_u32_array_opt,
-    get_u64_array,
+    get_timestamp_nanosecond_array_opt, get_u16_array, get_u32_array_opt, get_u64_array,
 };
-use crate::error;
+use crate::error::{self, ErrorContext, ErrorContextExt};
+use crate::otlp::attributes::add_delta;
 use crate::otlp::attributes::store::Attribute32Store;
 use crate::otlp::metrics::AppendAndGet;
 use crate::otlp::metrics::data_points::data_point_store::SummaryDataPointsStore;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::timestamps::TimestampChecker;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 use crate::proto::opentelemetry::metrics::v1::summary_data_point::ValueAtQuantile;
 use crate::schema::consts;
 use arrow::array::{Array, ArrayRef, Float64Array, ListArray, RecordBatch, StructArray};
@@ -29,7 +33,10 @@ impl SummaryDataPointsStore {
     pub fn from_record_batch(
         rb: &RecordBatch,
         attr_store: &mut Attribute32Store,
+        options: &DecoderOptions,
+        report: &mut DecodeReport,
     ) -> error::Result<SummaryDataPointsStore> {
+        let timestamps = TimestampChecker::new(options);
         let mut store = SummaryDataPointsStore::default();
         let mut prev_parent_id = 0;
 
@@ -46,17 +53,25 @@ impl SummaryDataPointsStore {
                     name: consts::SUMMARY_QUANTILE_VALUES,
                 },
             )?)?;
-        let flag_arr = get_u32_array(rb, consts::FLAGS)?;
+        let flag_arr = get_u32_array_opt(rb, consts::FLAGS)?;
 
         for idx in 0..rb.num_rows() {
             let delta = delta_id_arr.value_at_or_default(idx);
-            let parent_id = prev_parent_id + delta;
+            let parent_id = add_delta(prev_parent_id, delta)
+                .error_context(|| ErrorContext::default().row(idx).column(consts::PARENT_ID))?;
             prev_parent_id = parent_id;
             let nbdps = store.get_or_default(parent_id);
 
             let sdp = nbdps.append_and_get();
             sdp.start_time_unix_nano = start_time_unix_nano_arr.value_at_or_default(idx) as u64;
             sdp.time_unix_nano = time_unix_nano_arr.value_at_or_default(idx) as u64;
+            timestamps.check_range(
+                &mut sdp.start_time_unix_nano,
+                &mut sdp.time_unix_nano,
+                idx,
+                ArrowPayloadType::SummaryDataPoints,
+                report,
+            )?;
             sdp.count = summary_count_arr.value_at_or_default(idx);
             sdp.sum = sum_arr.value_at_or_default(idx);
             if let Some(quantile) = quantile_arr.value_at(idx) {
@@ -64,7 +79,7 @@ impl SummaryDataPointsStore {
             }
             sdp.flags = flag_arr.value_at_or_default(idx);
             if let Some(id) = id_arr_opt.value_at(idx) {
-                if let Some(attr) = attr_store.attribute_by_delta_id(id) {
+                if let Some(attr) = attr_store.attribute_by_delta_id(id, report)? {
                     sdp.attributes = attr.to_vec();
                 }
             }
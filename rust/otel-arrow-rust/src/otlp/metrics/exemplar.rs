@@ -11,12 +11,14 @@
 // limitations under the License.
 
 use crate::arrays::{
-    ByteArrayAccessor, NullableArrayAccessor, get_f64_array_opt, get_i64_array_opt,
+    ByteArrayAccessor, ColumnAccessor, NullableArrayAccessor, get_f64_array_opt, get_i64_array_opt,
     get_timestamp_nanosecond_array, get_u32_array, get_u32_array_opt,
 };
-use crate::error;
+use crate::error::{self, ErrorContext, ErrorContextExt};
+use crate::otlp::attributes::add_delta;
 use crate::otlp::attributes::store::Attribute32Store;
 use crate::otlp::metrics::AppendAndGet;
+use crate::otlp::report::DecodeReport;
 use crate::proto::opentelemetry::metrics::v1::Exemplar;
 use crate::proto::opentelemetry::metrics::v1::exemplar::Value;
 use crate::schema::consts;
@@ -41,7 +43,11 @@ impl ExemplarsStore {
 }
 
 impl ExemplarsStore {
-    pub fn try_from(rb: &RecordBatch, attr_store: &mut Attribute32Store) -> error::Result<Self> {
+    pub fn try_from(
+        rb: &RecordBatch,
+        attr_store: &mut Attribute32Store,
+        report: &mut DecodeReport,
+    ) -> error::Result<Self> {
         let mut exemplars_store = Self::default();
         let mut parent_id_decoder =
             ExemplarParentIdDecoder::new(ParentIdEncoding::ParentIdDeltaGroupEncoding);
@@ -51,17 +57,19 @@ impl ExemplarsStore {
         let double_value_arr = get_f64_array_opt(rb, consts::DOUBLE_VALUE)?;
         let parent_id_arr = get_u32_array(rb, consts::PARENT_ID)?;
         let time_unix_nano_arr = get_timestamp_nanosecond_array(rb, consts::TIME_UNIX_NANO)?;
-        let span_id_arr = ByteArrayAccessor::try_new_for_column(rb, consts::SPAN_ID)?;
-        let trace_id_arr = ByteArrayAccessor::try_new_for_column(rb, consts::TRACE_ID)?;
+        let span_id_arr = ByteArrayAccessor::try_new_for_column_opt(rb, consts::SPAN_ID)?;
+        let trace_id_arr = ByteArrayAccessor::try_new_for_column_opt(rb, consts::TRACE_ID)?;
 
         for idx in 0..rb.num_rows() {
             let int_value = int_value_arr.value_at(idx);
             let double_value = double_value_arr.value_at(idx);
-            let parent_id = parent_id_decoder.decode(
-                parent_id_arr.value_at_or_default(idx),
-                int_value,
-                double_value,
-            );
+            let parent_id = parent_id_decoder
+                .decode(
+                    parent_id_arr.value_at_or_default(idx),
+                    int_value,
+                    double_value,
+                )
+                .error_context(|| ErrorContext::default().row(idx).column(consts::PARENT_ID))?;
             let existing_exemplars = exemplars_store
                 .exemplars_by_ids
                 .entry(parent_id)
@@ -73,16 +81,23 @@ impl ExemplarsStore {
             let time_unix_nano = time_unix_nano_arr.value_at_or_default(idx);
             current_exemplar.time_unix_nano = time_unix_nano as u64;
 
+            // the exemplars recorded outside of a span have no span nor trace id
             let span_id_bytes = span_id_arr.value_at_or_default(idx);
-            ensure!(span_id_bytes.len() == 8, error::InvalidSpanIdSnafu {
-                message: format!("rb: {:?}", rb),
-            });
+            ensure!(
+                span_id_bytes.is_empty() || span_id_bytes.len() == 8,
+                error::InvalidSpanIdSnafu {
+                    message: format!("rb: {:?}", rb),
+                }
+            );
             current_exemplar.span_id = span_id_bytes;
 
             let trace_id_bytes = trace_id_arr.value_at_or_default(idx);
-            ensure!(trace_id_bytes.len() == 16, error::InvalidTraceIdSnafu {
-                message: format!("rb: {:?}", rb),
-            });
+            ensure!(
+                trace_id_bytes.is_empty() || trace_id_bytes.len() == 16,
+                error::InvalidTraceIdSnafu {
+                    message: format!("rb: {:?}", rb),
+                }
+            );
             current_exemplar.trace_id = trace_id_bytes;
 
             match (int_value, double_value) {
@@ -93,6 +108,8 @@ impl ExemplarsStore {
                 (None, Some(double_value)) => {
                     current_exemplar.value = Some(Value::AsDouble(double_value))
                 }
+                // the value of an exemplar is optional
+                (None, None) => {}
                 _ => {
                     return error::InvalidExemplarDataSnafu {
                         message: format!("record batch: {:?}", rb),
@@ -102,7 +119,7 @@ impl ExemplarsStore {
             }
 
             if let Some(id) = id_opt {
-                if let Some(attrs) = attr_store.attribute_by_delta_id(id) {
+                if let Some(attrs) = attr_store.attribute_by_delta_id(id, report)? {
                     current_exemplar.filtered_attributes = attrs.to_vec();
                 }
             }
@@ -159,45 +176,45 @@ impl ExemplarParentIdDecoder {
         parent_id_or_delta: u32,
         int_value: Option<i64>,
         double_value: Option<f64>,
-    ) -> u32 {
+    ) -> error::Result<u32> {
         match self.encoding {
-            ParentIdEncoding::ParentIdNoEncoding => parent_id_or_delta,
+            ParentIdEncoding::ParentIdNoEncoding => Ok(parent_id_or_delta),
             ParentIdEncoding::ParentIdDeltaEncoding => {
-                self.prev_parent_id += parent_id_or_delta;
-                self.prev_parent_id
+                self.prev_parent_id = add_delta(self.prev_parent_id, parent_id_or_delta)?;
+                Ok(self.prev_parent_id)
             }
             ParentIdEncoding::ParentIdDeltaGroupEncoding => {
                 if let Some(int_value) = int_value {
                     return if self.prev_type == ExemplarValueType::Int
                         && self.prev_int_value == Some(int_value)
                     {
-                        self.prev_parent_id += parent_id_or_delta;
-                        self.prev_parent_id
+                        self.prev_parent_id = add_delta(self.prev_parent_id, parent_id_or_delta)?;
+                        Ok(self.prev_parent_id)
                     } else {
                         self.prev_type = ExemplarValueType::Int;
                         self.prev_int_value = Some(int_value);
                         self.prev_double_value = None;
                         self.prev_parent_id = parent_id_or_delta;
-                        self.prev_parent_id
+                        Ok(self.prev_parent_id)
                     };
                 }
                 if let Some(double_value) = double_value {
                     return if self.prev_type == ExemplarValueType::Double
                         && self.prev_double_value == Some(double_value)
                     {
-                        self.prev_parent_id += parent_id_or_delta;
-                        self.prev_parent_id
+                        self.prev_parent_id = add_delta(self.prev_parent_id, parent_id_or_delta)?;
+                        Ok(self.prev_parent_id)
                     } else {
                         self.prev_type = ExemplarValueType::Double;
                         self.prev_double_value = Some(double_value);
                         self.prev_int_value = None;
                         self.prev_parent_id = parent_id_or_delta;
-                        self.prev_parent_id
+                        Ok(self.prev_parent_id)
                     };
                 }
 
-                self.prev_parent_id += parent_id_or_delta;
-                self.prev_parent_id
+                self.prev_parent_id = add_delta(self.prev_parent_id, parent_id_or_delta)?;
+                Ok(self.prev_parent_id)
             }
         }
     }
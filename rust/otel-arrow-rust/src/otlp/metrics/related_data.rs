@@ -10,14 +10,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::error;
+use crate::error::{self, ErrorContextExt};
 use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
 use crate::otlp::attributes::store::{Attribute16Store, Attribute32Store};
 use crate::otlp::metrics::data_points::data_point_store::{
     EHistogramDataPointsStore, HistogramDataPointsStore, NumberDataPointsStore,
     SummaryDataPointsStore,
 };
 use crate::otlp::metrics::exemplar::ExemplarsStore;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
 use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
 
 #[derive(Default)]
@@ -47,12 +50,14 @@ pub struct RelatedData {
     pub(crate) summary_data_points_store: SummaryDataPointsStore,
     pub(crate) histogram_data_points_store: HistogramDataPointsStore,
     pub(crate) e_histogram_data_points_store: EHistogramDataPointsStore,
+
+    pub(crate) report: DecodeReport,
 }
 
 impl RelatedData {
-    pub fn metric_id_from_delta(&mut self, delta: u16) -> u16 {
-        self.metric_id += delta;
-        self.metric_id
+    pub fn metric_id_from_delta(&mut self, delta: u16) -> error::Result<u16> {
+        self.metric_id = add_delta(self.metric_id, delta)?;
+        Ok(self.metric_id)
     }
 }
 
@@ -60,27 +65,60 @@ impl TryFrom<&OtapBatch> for RelatedData {
     type Error = error::Error;
 
     fn try_from(otap_batch: &OtapBatch) -> error::Result<Self> {
+        Self::try_from_with_options(otap_batch, &DecoderOptions::default())
+    }
+}
+
+impl RelatedData {
+    pub fn try_from_with_options(
+        otap_batch: &OtapBatch,
+        options: &DecoderOptions,
+    ) -> error::Result<Self> {
         let mut related_data = RelatedData::default();
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::ResourceAttrs) {
-            related_data.res_attr_map_store = Attribute16Store::try_from(rb)?;
+        if let Some(store) = Attribute16Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::ResourceAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.res_attr_map_store = store;
         }
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::ScopeAttrs) {
-            related_data.scope_attr_map_store = Attribute16Store::try_from(rb)?;
+        if let Some(store) = Attribute16Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::ScopeAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.scope_attr_map_store = store;
         }
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::NumberDpExemplarAttrs) {
-            related_data.number_d_p_exemplar_attrs_store = Attribute32Store::try_from(rb)?;
+        if let Some(store) = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::NumberDpExemplarAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.number_d_p_exemplar_attrs_store = store;
         }
 
         if let Some(rb) = otap_batch.get(ArrowPayloadType::NumberDpExemplars) {
-            related_data.number_data_point_exemplars_store =
-                ExemplarsStore::try_from(rb, &mut related_data.number_d_p_exemplar_attrs_store)?;
+            related_data.number_data_point_exemplars_store = ExemplarsStore::try_from(
+                rb,
+                &mut related_data.number_d_p_exemplar_attrs_store,
+                &mut related_data.report,
+            )
+            .in_payload(ArrowPayloadType::NumberDpExemplars)?;
         }
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::NumberDpAttrs) {
-            related_data.number_d_p_attrs_store = Attribute32Store::try_from(rb)?;
+        if let Some(store) = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::NumberDpAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.number_d_p_attrs_store = store;
         }
 
         if let Some(rb) = otap_batch.get(ArrowPayloadType::NumberDataPoints) {
@@ -88,27 +126,56 @@ impl TryFrom<&OtapBatch> for RelatedData {
                 rb,
                 &mut related_data.number_data_point_exemplars_store,
                 &related_data.number_d_p_attrs_store,
-            )?;
+                options,
+                &mut related_data.report,
+            )
+            .in_payload(ArrowPayloadType::NumberDataPoints)?;
         }
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::SummaryDpAttrs) {
-            related_data.summary_attrs_store = Attribute32Store::try_from(rb)?;
+        if let Some(store) = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::SummaryDpAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.summary_attrs_store = store;
         }
 
         if let Some(rb) = otap_batch.get(ArrowPayloadType::SummaryDataPoints) {
             related_data.summary_data_points_store = SummaryDataPointsStore::from_record_batch(
                 rb,
                 &mut related_data.summary_attrs_store,
-            )?
+                options,
+                &mut related_data.report,
+            )
+            .in_payload(ArrowPayloadType::SummaryDataPoints)?
         }
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::HistogramDpExemplarAttrs) {
-            related_data.histogram_exemplar_attrs_store = Attribute32Store::try_from(rb)?;
+        if let Some(store) = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::HistogramDpExemplarAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.histogram_exemplar_attrs_store = store;
         }
 
         if let Some(rb) = otap_batch.get(ArrowPayloadType::HistogramDpExemplars) {
-            related_data.histogram_data_point_exemplars_store =
-                ExemplarsStore::try_from(rb, &mut related_data.histogram_exemplar_attrs_store)?;
+            related_data.histogram_data_point_exemplars_store = ExemplarsStore::try_from(
+                rb,
+                &mut related_data.histogram_exemplar_attrs_store,
+                &mut related_data.report,
+            )
+            .in_payload(ArrowPayloadType::HistogramDpExemplars)?;
+        }
+
+        if let Some(store) = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::HistogramDpAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.histogram_attrs_store = store;
         }
 
         if let Some(rb) = otap_batch.get(ArrowPayloadType::HistogramDataPoints) {
@@ -116,24 +183,37 @@ impl TryFrom<&OtapBatch> for RelatedData {
                 rb,
                 &mut related_data.histogram_data_point_exemplars_store,
                 &related_data.histogram_attrs_store,
-            )?;
+                options,
+                &mut related_data.report,
+            )
+            .in_payload(ArrowPayloadType::HistogramDataPoints)?;
         }
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::HistogramDpAttrs) {
-            related_data.histogram_attrs_store = Attribute32Store::try_from(rb)?;
+        if let Some(store) = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::ExpHistogramDpAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.exp_histogram_attrs_store = store;
         }
 
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::ExpHistogramDpAttrs) {
-            related_data.exp_histogram_attrs_store = Attribute32Store::try_from(rb)?;
-        }
-
-        if let Some(rb) = otap_batch.get(ArrowPayloadType::ExpHistogramDpExemplarAttrs) {
-            related_data.exp_histogram_exemplar_attrs_store = Attribute32Store::try_from(rb)?;
+        if let Some(store) = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::ExpHistogramDpExemplarAttrs,
+            options,
+            &mut related_data.report,
+        )? {
+            related_data.exp_histogram_exemplar_attrs_store = store;
         }
 
         if let Some(rb) = otap_batch.get(ArrowPayloadType::ExpHistogramDpExemplars) {
-            related_data.e_histogram_data_point_exemplars_store =
-                ExemplarsStore::try_from(rb, &mut related_data.exp_histogram_exemplar_attrs_store)?;
+            related_data.e_histogram_data_point_exemplars_store = ExemplarsStore::try_from(
+                rb,
+                &mut related_data.exp_histogram_exemplar_attrs_store,
+                &mut related_data.report,
+            )
+            .in_payload(ArrowPayloadType::ExpHistogramDpExemplars)?;
         }
 
         if let Some(rb) = otap_batch.get(ArrowPayloadType::ExpHistogramDataPoints) {
@@ -142,7 +222,10 @@ impl TryFrom<&OtapBatch> for RelatedData {
                     rb,
                     &mut related_data.e_histogram_data_point_exemplars_store,
                     &related_data.exp_histogram_attrs_store,
-                )?;
+                    options,
+                    &mut related_data.report,
+                )
+                .in_payload(ArrowPayloadType::ExpHistogramDataPoints)?;
         }
 
         Ok(related_data)
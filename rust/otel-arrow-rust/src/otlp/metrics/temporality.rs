@@ -0,0 +1,449 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of the delta sums and histograms of decoded metrics into cumulative ones,
+//! e.g. to feed a Prometheus style backend that only accepts cumulative streams.
+//!
+//! The converter keeps the running total of every stream, identified by its resource,
+//! scope, metric name, unit, type and data point attributes. The first point of a stream
+//! starts its total. A point that is not newer than the last point of its stream is dropped,
+//! and a histogram point whose bounds differ from the ones of its stream restarts it. The
+//! exponential histograms and the points of the other temporalities are left as is.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use prost::Message;
+
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::common::v1::{
+    AnyValue, InstrumentationScope, KeyValue, KeyValueList, any_value,
+};
+use crate::proto::opentelemetry::metrics::v1::{
+    AggregationTemporality, HistogramDataPoint, NumberDataPoint, metric, number_data_point,
+};
+use crate::proto::opentelemetry::resource::v1::Resource;
+
+/// Converts the delta sums and histograms of metrics requests into cumulative ones.
+#[derive(Debug, Default)]
+pub struct DeltaToCumulative {
+    streams: HashMap<Vec<u8>, Stream>,
+}
+
+#[derive(Debug)]
+struct Stream {
+    start_time_unix_nano: u64,
+    time_unix_nano: u64,
+    total: Total,
+}
+
+#[derive(Debug)]
+enum Total {
+    Int(i64),
+    Double(f64),
+    Histogram {
+        count: u64,
+        sum: Option<f64>,
+        bucket_counts: Vec<u64>,
+        explicit_bounds: Vec<f64>,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+}
+
+impl DeltaToCumulative {
+    /// Creates a converter without any stream.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of streams tracked by the converter.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns true if no stream is tracked.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Forgets the streams without any point after `time_unix_nano`, returning how many were
+    /// removed. Their next point starts a new total.
+    pub fn remove_stale(&mut self, time_unix_nano: u64) -> usize {
+        let len = self.streams.len();
+        self.streams
+            .retain(|_, stream| stream.time_unix_nano > time_unix_nano);
+        len - self.streams.len()
+    }
+
+    /// Converts the delta sums and histograms of the request in place. The metrics left
+    /// without any point are removed.
+    pub fn convert(&mut self, request: &mut ExportMetricsServiceRequest) {
+        for resource_metrics in &mut request.resource_metrics {
+            let resource = resource_metrics.resource.as_ref();
+            for scope_metrics in &mut resource_metrics.scope_metrics {
+                let scope = scope_metrics.scope.as_ref();
+                for metric in &mut scope_metrics.metrics {
+                    let prefix = StreamPrefix {
+                        resource,
+                        scope,
+                        name: &metric.name,
+                        unit: &metric.unit,
+                    };
+                    match &mut metric.data {
+                        Some(metric::Data::Sum(sum))
+                            if sum.aggregation_temporality
+                                == AggregationTemporality::Delta as i32 =>
+                        {
+                            let kind = if sum.is_monotonic { "sum" } else { "sum!" };
+                            sum.data_points.retain_mut(|dp| {
+                                self.accumulate_number(prefix.key(kind, &dp.attributes), dp)
+                            });
+                            sum.aggregation_temporality = AggregationTemporality::Cumulative as i32;
+                        }
+                        Some(metric::Data::Histogram(histogram))
+                            if histogram.aggregation_temporality
+                                == AggregationTemporality::Delta as i32 =>
+                        {
+                            histogram.data_points.retain_mut(|dp| {
+                                self.accumulate_histogram(
+                                    prefix.key("histogram", &dp.attributes),
+                                    dp,
+                                )
+                            });
+                            histogram.aggregation_temporality =
+                                AggregationTemporality::Cumulative as i32;
+                        }
+                        _ => {}
+                    }
+                }
+                scope_metrics.metrics.retain(|metric| match &metric.data {
+                    Some(metric::Data::Sum(sum)) => !sum.data_points.is_empty(),
+                    Some(metric::Data::Histogram(histogram)) => !histogram.data_points.is_empty(),
+                    _ => true,
+                });
+            }
+        }
+    }
+
+    /// Returns the stream of the point, or `None` if the point is older than the last one
+    /// of its stream. A new stream is started at the start time of the point.
+    fn stream(
+        &mut self,
+        key: Vec<u8>,
+        start_time_unix_nano: u64,
+        time_unix_nano: u64,
+        total: impl FnOnce() -> Total,
+    ) -> Option<&mut Stream> {
+        match self.streams.entry(key) {
+            Entry::Occupied(entry) => {
+                let stream = entry.into_mut();
+                if time_unix_nano <= stream.time_unix_nano {
+                    return None;
+                }
+                stream.time_unix_nano = time_unix_nano;
+                Some(stream)
+            }
+            Entry::Vacant(entry) => Some(entry.insert(Stream {
+                start_time_unix_nano,
+                time_unix_nano,
+                total: total(),
+            })),
+        }
+    }
+
+    fn accumulate_number(&mut self, key: Vec<u8>, dp: &mut NumberDataPoint) -> bool {
+        let Some(value) = dp.value else {
+            return false;
+        };
+        let total = || match value {
+            number_data_point::Value::AsInt(_) => Total::Int(0),
+            number_data_point::Value::AsDouble(_) => Total::Double(0.0),
+        };
+        let Some(stream) = self.stream(key, dp.start_time_unix_nano, dp.time_unix_nano, total)
+        else {
+            return false;
+        };
+        dp.value = Some(match (&mut stream.total, value) {
+            (Total::Int(total), number_data_point::Value::AsInt(delta)) => {
+                *total = total.wrapping_add(delta);
+                number_data_point::Value::AsInt(*total)
+            }
+            (Total::Double(total), number_data_point::Value::AsDouble(delta)) => {
+                *total += delta;
+                number_data_point::Value::AsDouble(*total)
+            }
+            // the value type of the stream changed, restart it
+            (total, value) => {
+                stream.start_time_unix_nano = dp.start_time_unix_nano;
+                *total = match value {
+                    number_data_point::Value::AsInt(delta) => Total::Int(delta),
+                    number_data_point::Value::AsDouble(delta) => Total::Double(delta),
+                };
+                value
+            }
+        });
+        dp.start_time_unix_nano = stream.start_time_unix_nano;
+        true
+    }
+
+    fn accumulate_histogram(&mut self, key: Vec<u8>, dp: &mut HistogramDataPoint) -> bool {
+        let empty = || Total::Histogram {
+            count: 0,
+            sum: Some(0.0),
+            bucket_counts: vec![0; dp.bucket_counts.len()],
+            explicit_bounds: dp.explicit_bounds.clone(),
+            min: None,
+            max: None,
+        };
+        let Some(stream) = self.stream(key, dp.start_time_unix_nano, dp.time_unix_nano, empty)
+        else {
+            return false;
+        };
+        let Total::Histogram {
+            count,
+            sum,
+            bucket_counts,
+            explicit_bounds,
+            min,
+            max,
+        } = &mut stream.total
+        else {
+            return false;
+        };
+        if *explicit_bounds != dp.explicit_bounds || bucket_counts.len() != dp.bucket_counts.len() {
+            // the buckets of the stream changed, restart it
+            stream.start_time_unix_nano = dp.start_time_unix_nano;
+            *count = 0;
+            *sum = Some(0.0);
+            *bucket_counts = vec![0; dp.bucket_counts.len()];
+            explicit_bounds.clone_from(&dp.explicit_bounds);
+            *min = None;
+            *max = None;
+        }
+
+        *count += dp.count;
+        *sum = sum.zip(dp.sum).map(|(total, delta)| total + delta);
+        for (total, delta) in bucket_counts.iter_mut().zip(&dp.bucket_counts) {
+            *total += delta;
+        }
+        *min = match (*min, dp.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        *max = match (*max, dp.max) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+
+        dp.start_time_unix_nano = stream.start_time_unix_nano;
+        dp.count = *count;
+        dp.sum = *sum;
+        dp.bucket_counts.clone_from(bucket_counts);
+        dp.min = *min;
+        dp.max = *max;
+        true
+    }
+}
+
+/// Identity of the metric a point belongs to.
+#[derive(Clone, Copy)]
+struct StreamPrefix<'a> {
+    resource: Option<&'a Resource>,
+    scope: Option<&'a InstrumentationScope>,
+    name: &'a str,
+    unit: &'a str,
+}
+
+impl StreamPrefix<'_> {
+    /// Returns the key of the stream of the point with the given attributes. The attributes
+    /// are sorted by key, so their order doesn't matter.
+    fn key(&self, kind: &str, attributes: &[KeyValue]) -> Vec<u8> {
+        let mut key = Vec::new();
+        let mut push = |values: Vec<KeyValue>| {
+            let mut values = values;
+            values.sort_by(|a, b| a.key.cmp(&b.key));
+            // safety: encoding into a Vec can't fail
+            KeyValueList { values }
+                .encode_length_delimited(&mut key)
+                .expect("encoding into a Vec can't fail");
+        };
+        push(
+            self.resource
+                .map(|resource| resource.attributes.clone())
+                .unwrap_or_default(),
+        );
+        let scope = self.scope.cloned().unwrap_or_default();
+        push(vec![
+            string_attr("name", scope.name),
+            string_attr("version", scope.version),
+        ]);
+        push(scope.attributes);
+        push(vec![
+            string_attr("name", self.name.to_string()),
+            string_attr("unit", self.unit.to_string()),
+            string_attr("kind", kind.to_string()),
+        ]);
+        push(attributes.to_vec());
+        key
+    }
+}
+
+fn string_attr(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::metrics::v1::{
+        Histogram, Metric, ResourceMetrics, ScopeMetrics, Sum,
+    };
+
+    fn attr(value: &str) -> Vec<KeyValue> {
+        vec![string_attr("host", value.to_string())]
+    }
+
+    fn request(metrics: Vec<Metric>) -> ExportMetricsServiceRequest {
+        ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                scope_metrics: vec![ScopeMetrics {
+                    metrics,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn sum(points: Vec<(&str, u64, i64)>) -> Metric {
+        Metric {
+            name: "requests".into(),
+            data: Some(metric::Data::Sum(Sum {
+                data_points: points
+                    .into_iter()
+                    .map(|(host, time, value)| NumberDataPoint {
+                        attributes: attr(host),
+                        start_time_unix_nano: time - 10,
+                        time_unix_nano: time,
+                        value: Some(number_data_point::Value::AsInt(value)),
+                        ..Default::default()
+                    })
+                    .collect(),
+                aggregation_temporality: AggregationTemporality::Delta as i32,
+                is_monotonic: true,
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn sum_points(request: &ExportMetricsServiceRequest) -> Vec<(u64, i64)> {
+        request.resource_metrics[0].scope_metrics[0]
+            .metrics
+            .iter()
+            .flat_map(|metric| match &metric.data {
+                Some(metric::Data::Sum(sum)) => {
+                    assert_eq!(
+                        sum.aggregation_temporality,
+                        AggregationTemporality::Cumulative as i32
+                    );
+                    sum.data_points.clone()
+                }
+                _ => Vec::new(),
+            })
+            .map(|dp| match dp.value {
+                Some(number_data_point::Value::AsInt(value)) => (dp.start_time_unix_nano, value),
+                _ => (dp.start_time_unix_nano, 0),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_delta_sums_to_cumulative() {
+        let mut converter = DeltaToCumulative::new();
+
+        let mut first = request(vec![sum(vec![("a", 20, 1), ("b", 20, 5)])]);
+        converter.convert(&mut first);
+        assert_eq!(sum_points(&first), vec![(10, 1), (10, 5)]);
+        assert_eq!(converter.len(), 2);
+
+        // the stale point of a is dropped
+        let mut second = request(vec![sum(vec![("a", 30, 2), ("b", 30, 1), ("a", 30, 7)])]);
+        converter.convert(&mut second);
+        assert_eq!(sum_points(&second), vec![(10, 3), (10, 6)]);
+
+        // a metric without any point left is removed
+        let mut third = request(vec![sum(vec![("b", 25, 1)])]);
+        converter.convert(&mut third);
+        assert!(
+            third.resource_metrics[0].scope_metrics[0]
+                .metrics
+                .is_empty()
+        );
+
+        assert_eq!(converter.remove_stale(30), 2);
+        let mut fourth = request(vec![sum(vec![("a", 40, 4)])]);
+        converter.convert(&mut fourth);
+        assert_eq!(sum_points(&fourth), vec![(30, 4)]);
+    }
+
+    #[test]
+    fn test_delta_histograms_to_cumulative() {
+        let histogram = |time: u64, counts: Vec<u64>, bounds: Vec<f64>| {
+            request(vec![Metric {
+                name: "latency".into(),
+                data: Some(metric::Data::Histogram(Histogram {
+                    data_points: vec![HistogramDataPoint {
+                        start_time_unix_nano: time - 10,
+                        time_unix_nano: time,
+                        count: counts.iter().sum(),
+                        sum: Some(counts.iter().sum::<u64>() as f64),
+                        bucket_counts: counts,
+                        explicit_bounds: bounds,
+                        min: Some(time as f64),
+                        max: Some(time as f64),
+                        ..Default::default()
+                    }],
+                    aggregation_temporality: AggregationTemporality::Delta as i32,
+                })),
+                ..Default::default()
+            }])
+        };
+        let point = |request: &ExportMetricsServiceRequest| match &request.resource_metrics[0]
+            .scope_metrics[0]
+            .metrics[0]
+            .data
+        {
+            Some(metric::Data::Histogram(histogram)) => histogram.data_points[0].clone(),
+            _ => unreachable!(),
+        };
+        let mut converter = DeltaToCumulative::new();
+
+        let mut first = histogram(20, vec![1, 2], vec![1.0]);
+        converter.convert(&mut first);
+        let mut second = histogram(30, vec![3, 4], vec![1.0]);
+        converter.convert(&mut second);
+        let dp = point(&second);
+        assert_eq!(dp.start_time_unix_nano, 10);
+        assert_eq!(dp.count, 10);
+        assert_eq!(dp.sum, Some(10.0));
+        assert_eq!(dp.bucket_counts, vec![4, 6]);
+        assert_eq!((dp.min, dp.max), (Some(20.0), Some(30.0)));
+
+        // new bounds restart the stream
+        let mut third = histogram(40, vec![1, 1, 1], vec![1.0, 2.0]);
+        converter.convert(&mut third);
+        let dp = point(&third);
+        assert_eq!(dp.start_time_unix_nano, 30);
+        assert_eq!(dp.bucket_counts, vec![1, 1, 1]);
+    }
+}
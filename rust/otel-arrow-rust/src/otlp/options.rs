@@ -0,0 +1,846 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Options controlling how OTAP record batches are decoded into OTLP messages.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::cancel::{CancellationToken, check_cancelled};
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::otlp::events::{DecodeEvent, DecodeEventKind, DecodeEventSink};
+use crate::otlp::filter::RecordFilter;
+use crate::otlp::interner::StringInterner;
+use crate::otlp::pool::DecoderPool;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::value::cbor::{self, CborLimits};
+use crate::value::{AttributeValueType, coerce_value};
+
+/// Options used when decoding OTAP record batches into OTLP messages.
+#[derive(Clone, Debug, Default)]
+pub struct DecoderOptions {
+    /// Policy applied to attributes whose value is not stored in the column matching their
+    /// declared type, and conversions of the values to the types expected downstream.
+    pub attribute_coercion: CoercionPolicy,
+    /// Drop the rows that can't be decoded instead of failing the whole batch. The dropped
+    /// rows are counted in the [`DecodeReport`](crate::otlp::report::DecodeReport) of the
+    /// batch.
+    pub skip_bad_rows: bool,
+    /// Interner of the attribute keys, shared by all the batches decoded with these options
+    /// and their clones.
+    pub interner: Arc<StringInterner>,
+    /// Hook invoked for every decoded attribute before it is stored, see [`AttributeHook`].
+    pub attribute_hook: Option<Arc<dyn AttributeHook>>,
+    /// Policy applied to the attributes whose key is already in their attribute set.
+    pub duplicate_key_policy: DuplicateKeyPolicy,
+    /// Policy applied to the delta encoded ids that point out of the range of their id
+    /// type.
+    pub delta_id_policy: DeltaIdPolicy,
+    /// Policy applied to the malformed trace and span ids of the spans and links.
+    pub id_validation: IdValidationPolicy,
+    /// Policy applied to the span kinds, span status codes and severity numbers that OTLP
+    /// doesn't define.
+    pub enum_validation: EnumValidationPolicy,
+    /// Set the severity text of the log records that have a severity number but no text to
+    /// the short name of the number, e.g. `INFO`, see
+    /// [`severity_text`](crate::otlp::enums::severity_text).
+    pub derive_severity_text: bool,
+    /// Checks of the timestamps of the spans, log records and metric data points, none if
+    /// unset.
+    pub timestamp_policy: Option<TimestampPolicy>,
+    /// Policy applied to the columns of the log records and spans payloads that are not in
+    /// their canonical schema.
+    pub unknown_column_policy: UnknownColumnPolicy,
+    /// Policy applied to the payloads of the types that are neither in the OTAP
+    /// specification nor registered in the payload registry of the consumer.
+    pub unknown_payload_policy: UnknownPayloadPolicy,
+    /// Filter selecting the log records and spans to decode, all are decoded if unset.
+    pub filter: Option<RecordFilter>,
+    /// Sink receiving the values skipped or defaulted by the decoder, see
+    /// [`DecodeEventSink`].
+    pub event_sink: Option<Arc<dyn DecodeEventSink>>,
+    /// Token cancelling the decoding of the batches, checked between rows.
+    pub cancellation: Option<CancellationToken>,
+    /// Limits of the batches, rejecting the ones over them, none by default.
+    pub limits: DecodeLimits,
+    /// Limits of the attribute values, truncating or rejecting the ones over them, none by
+    /// default.
+    pub value_limits: ValueLimits,
+    /// Pool of the buffers reused across the batches decoded with these options and their
+    /// clones.
+    pub pool: Arc<DecoderPool>,
+}
+
+impl DecoderOptions {
+    /// Sets the policy applied to attributes whose value is not stored in the column
+    /// matching their declared type, and the conversions of the values.
+    #[must_use]
+    pub fn with_attribute_coercion(mut self, policy: CoercionPolicy) -> Self {
+        self.attribute_coercion = policy;
+        self
+    }
+
+    /// Sets whether rows that can't be decoded are dropped instead of failing the batch.
+    #[must_use]
+    pub fn with_skip_bad_rows(mut self, skip_bad_rows: bool) -> Self {
+        self.skip_bad_rows = skip_bad_rows;
+        self
+    }
+
+    /// Sets the interner of the attribute keys, e.g. one pre-seeded with the common keys.
+    #[must_use]
+    pub fn with_interner(mut self, interner: Arc<StringInterner>) -> Self {
+        self.interner = interner;
+        self
+    }
+
+    /// Sets the hook invoked for every decoded attribute, e.g. to redact or filter them.
+    #[must_use]
+    pub fn with_attribute_hook(mut self, hook: impl AttributeHook + 'static) -> Self {
+        self.attribute_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets the policy applied to the attributes whose key is already in their attribute
+    /// set.
+    #[must_use]
+    pub fn with_duplicate_key_policy(mut self, policy: DuplicateKeyPolicy) -> Self {
+        self.duplicate_key_policy = policy;
+        self
+    }
+
+    /// Sets the policy applied to the delta encoded ids that point out of the range of
+    /// their id type.
+    #[must_use]
+    pub fn with_delta_id_policy(mut self, policy: DeltaIdPolicy) -> Self {
+        self.delta_id_policy = policy;
+        self
+    }
+
+    /// Sets the policy applied to the malformed trace and span ids of the spans and links.
+    #[must_use]
+    pub fn with_id_validation(mut self, policy: IdValidationPolicy) -> Self {
+        self.id_validation = policy;
+        self
+    }
+
+    /// Sets the policy applied to the span kinds, span status codes and severity numbers
+    /// that OTLP doesn't define.
+    #[must_use]
+    pub fn with_enum_validation(mut self, policy: EnumValidationPolicy) -> Self {
+        self.enum_validation = policy;
+        self
+    }
+
+    /// Sets whether the log records that have a severity number but no text get the short
+    /// name of the number as their text, e.g. for the backends requiring the text.
+    #[must_use]
+    pub fn with_derive_severity_text(mut self, derive_severity_text: bool) -> Self {
+        self.derive_severity_text = derive_severity_text;
+        self
+    }
+
+    /// Sets the checks of the timestamps of the spans, log records and metric data points,
+    /// e.g. to catch the producers with a broken clock.
+    #[must_use]
+    pub fn with_timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.timestamp_policy = Some(policy);
+        self
+    }
+
+    /// Sets the policy applied to the columns of the log records and spans payloads that are
+    /// not in their canonical schema, e.g. to keep the experimental fields of a producer.
+    #[must_use]
+    pub fn with_unknown_column_policy(mut self, policy: UnknownColumnPolicy) -> Self {
+        self.unknown_column_policy = policy;
+        self
+    }
+
+    /// Sets the policy applied to the payloads of unknown types, e.g. to keep decoding the
+    /// batches of producers running a newer version of the protocol.
+    #[must_use]
+    pub fn with_unknown_payload_policy(mut self, policy: UnknownPayloadPolicy) -> Self {
+        self.unknown_payload_policy = policy;
+        self
+    }
+
+    /// Sets the filter selecting the log records and spans to decode, e.g. to drop the debug
+    /// logs of a batch.
+    #[must_use]
+    pub fn with_filter(mut self, filter: RecordFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Sets the sink receiving the values skipped or defaulted by the decoder, e.g. a
+    /// [`DecodeEventCollector`](crate::otlp::events::DecodeEventCollector) kept by the
+    /// caller.
+    #[must_use]
+    pub fn with_event_sink(mut self, sink: Arc<dyn DecodeEventSink>) -> Self {
+        self.event_sink = Some(sink);
+        self
+    }
+
+    /// Sets the token cancelling the decoding of the batches, e.g. when the client that
+    /// sent them disconnects.
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets the limits of the batches, e.g. to protect a receiver shared by many clients
+    /// from the pathological ones.
+    #[must_use]
+    pub fn with_limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Sets the limits of the attribute values, e.g. to bound the memory of the decoded
+    /// attributes and protect against the serialized values built to blow up when decoded.
+    #[must_use]
+    pub fn with_value_limits(mut self, value_limits: ValueLimits) -> Self {
+        self.value_limits = value_limits;
+        self
+    }
+
+    /// Sets the pool of the buffers reused across batches, e.g. one shared by the streams
+    /// of a receiver.
+    #[must_use]
+    pub fn with_pool(mut self, pool: Arc<DecoderPool>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Sends the event of the row `row` of the payload to the event sink, if any.
+    pub(crate) fn emit_event(
+        &self,
+        payload_type: ArrowPayloadType,
+        row: usize,
+        kind: DecodeEventKind,
+    ) {
+        if let Some(sink) = &self.event_sink {
+            sink.on_event(&DecodeEvent {
+                payload_type,
+                row,
+                kind,
+            });
+        }
+    }
+
+    /// Fails if the decoding was cancelled.
+    pub(crate) fn check_cancelled(&self) -> Result<()> {
+        check_cancelled(self.cancellation.as_ref())
+    }
+
+    /// Starts decoding the batch, failing if it is over the limits. Returns the guard to
+    /// check between the rows of the main payload.
+    pub(crate) fn start_batch(&self, otap_batch: &OtapBatch) -> Result<BatchGuard<'_>> {
+        self.limits.check_batch(otap_batch)?;
+        Ok(BatchGuard {
+            options: self,
+            deadline: self
+                .limits
+                .max_decode_time
+                .map(|max_decode_time| (Instant::now() + max_decode_time, max_decode_time)),
+        })
+    }
+}
+
+/// Number of rows between two checks of the decoding deadline.
+const DEADLINE_CHECK_ROWS: usize = 64;
+
+/// Checks of the decoding of a batch between the rows of its main payload.
+pub(crate) struct BatchGuard<'a> {
+    options: &'a DecoderOptions,
+    deadline: Option<(Instant, Duration)>,
+}
+
+impl BatchGuard<'_> {
+    /// Fails if the decoding was cancelled or, every few rows, if it is past its deadline.
+    pub(crate) fn check(&self, idx: usize) -> Result<()> {
+        self.options.check_cancelled()?;
+        match self.deadline {
+            Some((deadline, max_decode_time))
+                if idx % DEADLINE_CHECK_ROWS == 0 && Instant::now() >= deadline =>
+            {
+                error::DecodeTimeExceededSnafu { max_decode_time }.fail()
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Key of the attribute marking the attribute sets with a value truncated by the
+/// [`ValueLimits`], its value being `true`.
+pub const TRUNCATED_ATTRIBUTE: &str = "otel_arrow.truncated";
+
+/// What to do with an attribute value over the [`ValueLimits`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OversizedValueAction {
+    /// Truncate the strings and bytes to the maximum length, at a character boundary for
+    /// the strings, and replace the maps and slices by empty ones. The attribute set is
+    /// marked with the [`TRUNCATED_ATTRIBUTE`] attribute.
+    #[default]
+    Truncate,
+    /// Fail decoding the batch, or drop the attribute if
+    /// [`DecoderOptions::skip_bad_rows`] is set.
+    Reject,
+}
+
+/// Limits of the attribute values, none being set by default.
+///
+/// The length of the strings and bytes is checked once they are decoded, the size and
+/// nesting depth of the serialized values of the maps and slices before they are decoded.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ValueLimits {
+    max_value_len: Option<usize>,
+    max_serialized_len: Option<usize>,
+    cbor: CborLimits,
+    action: OversizedValueAction,
+}
+
+impl ValueLimits {
+    /// Creates limits accepting every value.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the strings and bytes to `max_len` bytes.
+    #[must_use]
+    pub fn with_max_value_len(mut self, max_len: usize) -> Self {
+        self.max_value_len = Some(max_len);
+        self
+    }
+
+    /// Limits the serialized values of the maps and slices to `max_len` bytes.
+    #[must_use]
+    pub fn with_max_serialized_len(mut self, max_len: usize) -> Self {
+        self.max_serialized_len = Some(max_len);
+        self
+    }
+
+    /// Limits the nesting of the maps and slices of the serialized values to `max_depth`
+    /// levels.
+    #[must_use]
+    pub fn with_max_serialized_depth(mut self, max_depth: usize) -> Self {
+        self.cbor = self.cbor.with_max_depth(max_depth);
+        self
+    }
+
+    /// Sets the limits of the serialized values, e.g. to also limit their number of items
+    /// or require their preferred serialization.
+    #[must_use]
+    pub fn with_cbor_limits(mut self, cbor: CborLimits) -> Self {
+        self.cbor = cbor;
+        self
+    }
+
+    /// Sets what to do with the values over the limits.
+    #[must_use]
+    pub fn with_action(mut self, action: OversizedValueAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Returns the maximum length of the strings and bytes, if limited.
+    #[must_use]
+    pub fn max_value_len(&self) -> Option<usize> {
+        self.max_value_len
+    }
+
+    /// Returns the maximum length of the serialized values, if limited.
+    #[must_use]
+    pub fn max_serialized_len(&self) -> Option<usize> {
+        self.max_serialized_len
+    }
+
+    /// Returns the limits of the serialized values.
+    #[must_use]
+    pub fn cbor_limits(&self) -> CborLimits {
+        self.cbor
+    }
+
+    /// Returns what is done with the values over the limits.
+    #[must_use]
+    pub fn action(&self) -> OversizedValueAction {
+        self.action
+    }
+
+    /// Decodes a serialized value, failing with a `AttributeValueTooLarge`,
+    /// `SerializedValueTooDeep` or `TooManySerializedElements` error if it is over the
+    /// limits.
+    pub(crate) fn decode_serialized(&self, bytes: &[u8]) -> Result<Option<Value>> {
+        if let Some(max_len) = self.max_serialized_len {
+            if bytes.len() > max_len {
+                return error::AttributeValueTooLargeSnafu {
+                    len: bytes.len(),
+                    max_len,
+                }
+                .fail();
+            }
+        }
+        cbor::decode_pcommon_val_with_limits(bytes, &self.cbor)
+    }
+
+    /// Returns the value truncated to the maximum length, and whether it was truncated.
+    /// Fails with a `AttributeValueTooLarge` error instead if the values are rejected.
+    pub(crate) fn truncate(&self, value: Value) -> Result<(Value, bool)> {
+        let Some(max_len) = self.max_value_len else {
+            return Ok((value, false));
+        };
+        let len = match &value {
+            Value::StringValue(s) => s.len(),
+            Value::BytesValue(b) => b.len(),
+            _ => return Ok((value, false)),
+        };
+        if len <= max_len {
+            return Ok((value, false));
+        }
+        if self.action == OversizedValueAction::Reject {
+            return error::AttributeValueTooLargeSnafu { len, max_len }.fail();
+        }
+        let value = match value {
+            Value::StringValue(mut s) => {
+                let mut end = max_len;
+                while !s.is_char_boundary(end) {
+                    end -= 1;
+                }
+                s.truncate(end);
+                Value::StringValue(s)
+            }
+            Value::BytesValue(mut b) => {
+                b.truncate(max_len);
+                Value::BytesValue(b)
+            }
+            value => value,
+        };
+        Ok((value, true))
+    }
+}
+
+/// Limits of the decoded batches, none being set by default. The batches over the limits
+/// fail with a `TooManyRows`, `TooManyPayloads` or `DecodeTimeExceeded` error.
+///
+/// The rows and payloads are checked before decoding a batch, and by the
+/// [`Consumer`](crate::Consumer) as soon as the payloads are received, before reading the
+/// next ones. The decoding time is checked between the rows of the main payload.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DecodeLimits {
+    max_rows_per_payload: Option<usize>,
+    max_payloads_per_batch: Option<usize>,
+    max_decode_time: Option<Duration>,
+}
+
+impl DecodeLimits {
+    /// Creates limits accepting every batch.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects the batches with a payload of more than `max_rows` rows.
+    #[must_use]
+    pub fn with_max_rows_per_payload(mut self, max_rows: usize) -> Self {
+        self.max_rows_per_payload = Some(max_rows);
+        self
+    }
+
+    /// Rejects the batches of more than `max_payloads` payloads.
+    #[must_use]
+    pub fn with_max_payloads_per_batch(mut self, max_payloads: usize) -> Self {
+        self.max_payloads_per_batch = Some(max_payloads);
+        self
+    }
+
+    /// Stops decoding the batches taking longer than `max_decode_time`.
+    #[must_use]
+    pub fn with_max_decode_time(mut self, max_decode_time: Duration) -> Self {
+        self.max_decode_time = Some(max_decode_time);
+        self
+    }
+
+    /// Returns the maximum number of rows of a payload, if limited.
+    #[must_use]
+    pub fn max_rows_per_payload(&self) -> Option<usize> {
+        self.max_rows_per_payload
+    }
+
+    /// Returns the maximum number of payloads of a batch, if limited.
+    #[must_use]
+    pub fn max_payloads_per_batch(&self) -> Option<usize> {
+        self.max_payloads_per_batch
+    }
+
+    /// Returns the maximum decoding time of a batch, if limited.
+    #[must_use]
+    pub fn max_decode_time(&self) -> Option<Duration> {
+        self.max_decode_time
+    }
+
+    /// Fails if a batch has more than the maximum number of payloads.
+    pub(crate) fn check_payloads(&self, payloads: usize) -> Result<()> {
+        match self.max_payloads_per_batch {
+            Some(max_payloads) if payloads > max_payloads => error::TooManyPayloadsSnafu {
+                payloads,
+                max_payloads,
+            }
+            .fail(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a payload has more than the maximum number of rows.
+    pub(crate) fn check_rows(&self, payload_type: ArrowPayloadType, rows: usize) -> Result<()> {
+        match self.max_rows_per_payload {
+            Some(max_rows) if rows > max_rows => error::TooManyRowsSnafu {
+                payload_type,
+                rows,
+                max_rows,
+            }
+            .fail(),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_batch(&self, otap_batch: &OtapBatch) -> Result<()> {
+        let payload_types = otap_batch.payload_types();
+        self.check_payloads(payload_types.len())?;
+        for payload_type in payload_types {
+            if let Some(rb) = otap_batch.get(payload_type) {
+                self.check_rows(payload_type, rb.num_rows())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What to do with an attribute whose key is already in the attribute set of its parent,
+/// which the OTLP data model forbids but which some producers and backends use to carry
+/// ordered multi-maps.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicateKeyPolicy {
+    /// Overwrite the value in place, the attribute keeping the position of the first one.
+    #[default]
+    LastWins,
+    /// Keep the first value and drop the attribute.
+    FirstWins,
+    /// Append the attribute, the set keeping all the values of the key in order.
+    KeepAll,
+    /// Fail decoding the batch, or drop the attribute if bad rows are skipped.
+    Error,
+}
+
+/// What to do when adding a delta to the previous id of an attribute lookup wraps around
+/// the id type or goes backwards, which a well behaved producer never does. Such ids are
+/// counted in the [`DecodeReport`](crate::otlp::report::DecodeReport) of the batch,
+/// whatever the policy.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DeltaIdPolicy {
+    /// Fail decoding the batch.
+    #[default]
+    Error,
+    /// Clamp the id to the range of the id type: an overflowing id becomes the largest id,
+    /// and a negative delta leaves the id unchanged.
+    Clamp,
+    /// Leave the id unchanged and attach no attributes to the row.
+    Skip,
+}
+
+/// How the trace ids (16 bytes) and span ids (8 bytes) of the spans and links are checked.
+///
+/// Except with [`LengthOnly`](Self::LengthOnly), an id is malformed when it has the wrong
+/// length or is all zeros. A parent span id is malformed only when it has the wrong length,
+/// a missing or all-zero one denoting a root span. The malformed ids that don't fail the
+/// batch are counted in the [`DecodeReport`](crate::otlp::report::DecodeReport) of the
+/// batch.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum IdValidationPolicy {
+    /// Fail decoding the batch if an id has the wrong length, the ids of the links being
+    /// allowed to be missing. All-zero ids are kept.
+    #[default]
+    LengthOnly,
+    /// Fail decoding the batch if an id is malformed.
+    Error,
+    /// Drop the spans and links with a malformed id. The dropped rows are counted in the
+    /// report as well.
+    Drop,
+    /// Replace the malformed ids by all-zero ids of the right length, and the malformed
+    /// parent span ids by a missing one.
+    ZeroFill,
+}
+
+/// How the values of the enum columns are checked, see [`crate::otlp::enums`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EnumValidationPolicy {
+    /// Keep the values as they are, including the ones OTLP doesn't define.
+    #[default]
+    Passthrough,
+    /// Fail decoding the batch if a value is not defined by OTLP.
+    Strict,
+}
+
+/// What to do with a record whose timestamps fail the checks of the [`TimestampPolicy`].
+/// Such records are counted in the [`DecodeReport`](crate::otlp::report::DecodeReport) of
+/// the batch, whatever the action.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TimestampAction {
+    /// Keep the timestamps as they are.
+    #[default]
+    Flag,
+    /// Clamp the timestamps to the window around the current time, then the end timestamp
+    /// to the start timestamp.
+    Clamp,
+    /// Fail decoding the batch.
+    Error,
+}
+
+/// Checks of the timestamps of the decoded records: the end timestamp of a span, and the
+/// timestamp of a metric data point, must not be before the start timestamp, and the
+/// timestamps must be within the window around the current time, if any. The unset
+/// timestamps, which are zero, are not checked.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TimestampPolicy {
+    action: TimestampAction,
+    max_past: Option<Duration>,
+    max_future: Option<Duration>,
+}
+
+impl TimestampPolicy {
+    /// Creates a policy applying `action` to the records whose end timestamp is before
+    /// their start timestamp.
+    #[must_use]
+    pub fn new(action: TimestampAction) -> Self {
+        Self {
+            action,
+            ..Default::default()
+        }
+    }
+
+    /// Also checks that the timestamps are at most `max_past` before the current time.
+    #[must_use]
+    pub fn with_max_past(mut self, max_past: Duration) -> Self {
+        self.max_past = Some(max_past);
+        self
+    }
+
+    /// Also checks that the timestamps are at most `max_future` after the current time,
+    /// e.g. a few minutes to allow for some clock skew.
+    #[must_use]
+    pub fn with_max_future(mut self, max_future: Duration) -> Self {
+        self.max_future = Some(max_future);
+        self
+    }
+
+    /// Returns the action applied to the records failing the checks.
+    #[must_use]
+    pub fn action(&self) -> TimestampAction {
+        self.action
+    }
+
+    /// Returns how far before the current time the timestamps can be, if checked.
+    #[must_use]
+    pub fn max_past(&self) -> Option<Duration> {
+        self.max_past
+    }
+
+    /// Returns how far after the current time the timestamps can be, if checked.
+    #[must_use]
+    pub fn max_future(&self) -> Option<Duration> {
+        self.max_future
+    }
+}
+
+/// What to do with the columns of a log records or spans payload that are not in its
+/// canonical schema.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum UnknownColumnPolicy {
+    /// Ignore the columns.
+    #[default]
+    Ignore,
+    /// Fail decoding the batch.
+    Error,
+    /// Append the values of the columns to the attributes of their record, under the name of
+    /// the column prefixed with `prefix`. The string, integer, float, boolean and binary
+    /// columns are mapped to attributes of the matching type, the columns of other types
+    /// are ignored, as are the null values.
+    Attributes {
+        /// Prefix of the attribute keys, e.g. `"otap.extra."`.
+        prefix: String,
+    },
+}
+
+/// What to do with the payloads of a type that is neither in the OTAP specification nor
+/// registered in the [`PayloadRegistry`](crate::PayloadRegistry) of the consumer.
+///
+/// The batches whose main payload is of an unknown type fail whatever the policy, as the
+/// signal they carry is unknown.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnknownPayloadPolicy {
+    /// Fail decoding the batch.
+    #[default]
+    Error,
+    /// Decode the rest of the batch. The skipped payloads are kept as received, e.g. to be
+    /// forwarded along with the batch re-encoded from the decoded request, see
+    /// [`Consumer::take_skipped_payloads`](crate::Consumer::take_skipped_payloads).
+    Skip,
+}
+
+/// What to do with a decoded attribute, as decided by an [`AttributeHook`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeAction {
+    /// Store the attribute as is.
+    Keep,
+    /// Drop the attribute.
+    Drop,
+    /// Store the value under another key.
+    Rename(String),
+    /// Store another value under the same key.
+    Rewrite(Value),
+    /// Store another value under another key.
+    Replace(String, Value),
+}
+
+/// Hook invoked with the key and value of every attribute decoded from the attribute
+/// payloads, before it is stored. It can be used to redact PII or to keep only an allowlist
+/// of attributes without a second pass over the OTLP messages.
+///
+/// The hook is applied after the value has been coerced to its declared type, and doesn't
+/// affect the decoding of the delta encoded parent ids, which uses the stored values. It is
+/// implemented for closures taking the key and the value.
+pub trait AttributeHook: Send + Sync {
+    /// Returns what to do with the attribute.
+    fn on_attribute(&self, key: &str, value: &Value) -> AttributeAction;
+}
+
+impl<F> AttributeHook for F
+where
+    F: Fn(&str, &Value) -> AttributeAction + Send + Sync,
+{
+    fn on_attribute(&self, key: &str, value: &Value) -> AttributeAction {
+        self(key, value)
+    }
+}
+
+impl fmt::Debug for dyn AttributeHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AttributeHook")
+    }
+}
+
+/// What to do with an attribute whose value is stored in a column that doesn't match its
+/// declared type, e.g. an int attribute whose value was written in the string column, or
+/// an int column written with a string data type.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CoercionAction {
+    /// Convert the value to the declared type. If the value can't be converted, it is
+    /// kept with the type it was stored as.
+    Coerce,
+    /// Drop the attribute.
+    Skip,
+    /// Fail decoding the batch. Only the values of a column named after their type but
+    /// written with another data type fail it: the values stored in the column of another
+    /// type are not looked up, the attribute getting the default value of its type.
+    #[default]
+    Error,
+}
+
+/// Selects the [`CoercionAction`] applied to mismatched attribute values, per attribute key,
+/// and the conversions of the decoded values to the types the downstream backends expect,
+/// e.g. for the producers sending numbers as strings or booleans as ints.
+///
+/// The action applies first, to the values stored in another column than the one of their
+/// declared type: a skipped value is dropped and a coerced one gets its declared type. The
+/// conversions then apply to every value kept, coerced or not. A conversion applies to the
+/// values of a type, whatever their key, or to all the values of a key, the conversions of
+/// the keys taking precedence. The values are converted with [`coerce_value`], the ones that
+/// can't be converted, e.g. a string that isn't a number, are kept as they are.
+#[derive(Clone, Debug, Default)]
+pub struct CoercionPolicy {
+    default_action: CoercionAction,
+    key_actions: HashMap<String, CoercionAction>,
+    type_conversions: Vec<(AttributeValueType, AttributeValueType)>,
+    key_types: HashMap<String, AttributeValueType>,
+}
+
+impl CoercionPolicy {
+    /// Creates a policy applying `default_action` to every key, and converting no value.
+    #[must_use]
+    pub fn new(default_action: CoercionAction) -> Self {
+        Self {
+            default_action,
+            ..Self::default()
+        }
+    }
+
+    /// Applies `action` to the attributes with the given key instead of the default action.
+    #[must_use]
+    pub fn with_key_action(mut self, key: impl Into<String>, action: CoercionAction) -> Self {
+        let _ = self.key_actions.insert(key.into(), action);
+        self
+    }
+
+    /// Returns the action applied to the attributes with the given key.
+    #[must_use]
+    pub fn action_for(&self, key: &str) -> CoercionAction {
+        self.key_actions
+            .get(key)
+            .copied()
+            .unwrap_or(self.default_action)
+    }
+
+    /// Converts the values of type `from` to type `to`, e.g. the strings to ints.
+    #[must_use]
+    pub fn with_conversion(mut self, from: AttributeValueType, to: AttributeValueType) -> Self {
+        self.type_conversions
+            .retain(|(type_from, _)| *type_from != from);
+        self.type_conversions.push((from, to));
+        self
+    }
+
+    /// Converts the values of the attributes with the given key to `value_type`.
+    #[must_use]
+    pub fn with_key_type(mut self, key: impl Into<String>, value_type: AttributeValueType) -> Self {
+        let _ = self.key_types.insert(key.into(), value_type);
+        self
+    }
+
+    /// Returns whether an action other than [`CoercionAction::Error`] applies to some key,
+    /// so that the values stored in the column of another type are looked up.
+    pub(crate) fn looks_up_other_columns(&self) -> bool {
+        self.default_action != CoercionAction::Error
+            || self
+                .key_actions
+                .values()
+                .any(|action| *action != CoercionAction::Error)
+    }
+
+    /// Returns whether the policy converts some values.
+    #[must_use]
+    pub fn converts_values(&self) -> bool {
+        !self.type_conversions.is_empty() || !self.key_types.is_empty()
+    }
+
+    /// Returns the value of the attribute with the given key, converted according to the
+    /// conversions of the policy.
+    #[must_use]
+    pub fn convert(&self, key: &str, value: Value) -> Value {
+        let value_type = AttributeValueType::from(Some(&value));
+        let target = self.key_types.get(key).copied().or_else(|| {
+            self.type_conversions
+                .iter()
+                .find(|(from, _)| *from == value_type)
+                .map(|(_, to)| *to)
+        });
+        match target {
+            Some(target) if target != value_type => coerce_value(&value, target).unwrap_or(value),
+            _ => value,
+        }
+    }
+}
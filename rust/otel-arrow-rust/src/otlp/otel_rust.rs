@@ -0,0 +1,445 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of the decoded batches to the types of the `opentelemetry` and
+//! `opentelemetry_sdk` crates, for the consumers built on top of them rather than on the
+//! OTLP protos, e.g. to feed the spans of a batch to an SDK span exporter.
+//!
+//! The attributes are converted to [`opentelemetry::KeyValue`]s, whose values can't hold
+//! maps, bytes or arrays of mixed types: such values are converted to strings, the bytes
+//! base64 encoded and the maps and arrays JSON encoded. The log record bodies and
+//! attributes can be converted without loss to [`opentelemetry::logs::AnyValue`]s.
+//!
+//! The spans are converted to [`SpanData`]s, grouped by resource. The log records and the
+//! metrics data of the SDK can only be built by the SDK itself, so there is no conversion
+//! to them.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD as BASE64;
+use opentelemetry::trace::{
+    Event, Link, SpanContext, SpanId, SpanKind, Status, TraceFlags, TraceId,
+};
+use opentelemetry::{Array, InstrumentationScope, StringValue, Value, logs};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{SpanData, SpanEvents, SpanLinks};
+
+use crate::error::Result;
+use crate::otap::OtapBatch;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::traces::traces_from_with_options;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue, any_value};
+use crate::proto::opentelemetry::resource::v1;
+use crate::proto::opentelemetry::trace::v1::{Span, span, status};
+
+/// Bit of the span and link flags telling whether the `is_remote` bit is set.
+const HAS_IS_REMOTE_MASK: u32 = 0x100;
+/// Bit of the span and link flags telling whether the parent span or the linked span is
+/// remote.
+const IS_REMOTE_MASK: u32 = 0x200;
+
+/// Spans of a resource.
+#[derive(Clone, Debug)]
+pub struct ResourceSpanData {
+    /// The resource of the spans.
+    pub resource: Resource,
+    /// The spans, with their instrumentation scope.
+    pub spans: Vec<SpanData>,
+}
+
+/// Decodes a traces batch into the spans of its resources.
+pub fn span_data_from(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<Vec<ResourceSpanData>> {
+    Ok(span_data(&traces_from_with_options(
+        traces_otap_batch,
+        options,
+    )?))
+}
+
+/// Converts the spans of a decoded request, grouped by resource.
+#[must_use]
+pub fn span_data(request: &ExportTraceServiceRequest) -> Vec<ResourceSpanData> {
+    request
+        .resource_spans
+        .iter()
+        .map(|resource_spans| ResourceSpanData {
+            resource: resource(resource_spans.resource.as_ref(), &resource_spans.schema_url),
+            spans: resource_spans
+                .scope_spans
+                .iter()
+                .flat_map(|scope_spans| {
+                    let scope =
+                        instrumentation_scope(scope_spans.scope.as_ref(), &scope_spans.schema_url);
+                    scope_spans
+                        .spans
+                        .iter()
+                        .map(move |span| span_data_of(span, scope.clone()))
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Converts an attribute, see the module documentation for the values that are converted
+/// to strings.
+#[must_use]
+pub fn key_value(kv: &KeyValue) -> opentelemetry::KeyValue {
+    opentelemetry::KeyValue::new(kv.key.clone(), value(kv.value.as_ref()))
+}
+
+/// Converts a value, e.g. the body of a log record, without loss.
+#[must_use]
+pub fn log_any_value(value: &AnyValue) -> Option<logs::AnyValue> {
+    Some(match value.value.as_ref()? {
+        any_value::Value::StringValue(s) => logs::AnyValue::String(s.clone().into()),
+        any_value::Value::BoolValue(b) => logs::AnyValue::Boolean(*b),
+        any_value::Value::IntValue(i) => logs::AnyValue::Int(*i),
+        any_value::Value::DoubleValue(d) => logs::AnyValue::Double(*d),
+        any_value::Value::BytesValue(bytes) => logs::AnyValue::Bytes(Box::new(bytes.clone())),
+        any_value::Value::ArrayValue(array) => logs::AnyValue::ListAny(Box::new(
+            array.values.iter().filter_map(log_any_value).collect(),
+        )),
+        any_value::Value::KvlistValue(kvlist) => logs::AnyValue::Map(Box::new(
+            kvlist
+                .values
+                .iter()
+                .filter_map(|kv| {
+                    let value = log_any_value(kv.value.as_ref()?)?;
+                    Some((kv.key.clone().into(), value))
+                })
+                .collect::<HashMap<_, _>>(),
+        )),
+    })
+}
+
+fn value(value: Option<&AnyValue>) -> Value {
+    let Some(value) = value.and_then(|value| value.value.as_ref()) else {
+        return Value::String(StringValue::from(""));
+    };
+    match value {
+        any_value::Value::StringValue(s) => Value::String(s.clone().into()),
+        any_value::Value::BoolValue(b) => Value::Bool(*b),
+        any_value::Value::IntValue(i) => Value::I64(*i),
+        any_value::Value::DoubleValue(d) => Value::F64(*d),
+        any_value::Value::BytesValue(bytes) => Value::String(BASE64.encode(bytes).into()),
+        any_value::Value::ArrayValue(array) => homogeneous_array(&array.values)
+            .map(Value::Array)
+            .unwrap_or_else(|| Value::String(json(value).to_string().into())),
+        any_value::Value::KvlistValue(_) => Value::String(json(value).to_string().into()),
+    }
+}
+
+/// Returns the array of the values if they all have the same scalar type.
+fn homogeneous_array(values: &[AnyValue]) -> Option<Array> {
+    let values: Vec<_> = values
+        .iter()
+        .map(|value| value.value.as_ref())
+        .collect::<Option<_>>()?;
+    macro_rules! collect {
+        ($variant:ident, $array:ident, $convert:expr) => {
+            values
+                .iter()
+                .map(|value| match value {
+                    any_value::Value::$variant(v) => Some($convert(v)),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(Array::$array)
+        };
+    }
+    match values.first() {
+        None => Some(Array::String(Vec::new())),
+        Some(any_value::Value::StringValue(_)) => {
+            collect!(StringValue, String, |s: &String| StringValue::from(
+                s.clone()
+            ))
+        }
+        Some(any_value::Value::BoolValue(_)) => collect!(BoolValue, Bool, |b: &bool| *b),
+        Some(any_value::Value::IntValue(_)) => collect!(IntValue, I64, |i: &i64| *i),
+        Some(any_value::Value::DoubleValue(_)) => collect!(DoubleValue, F64, |d: &f64| *d),
+        Some(_) => None,
+    }
+}
+
+/// Plain JSON encoding of a value, unlike the OTLP/JSON mapping the values are not wrapped
+/// in objects naming their type.
+fn json(value: &any_value::Value) -> serde_json::Value {
+    let json_of = |value: &AnyValue| value.value.as_ref().map_or(serde_json::Value::Null, json);
+    match value {
+        any_value::Value::StringValue(s) => s.clone().into(),
+        any_value::Value::BoolValue(b) => (*b).into(),
+        any_value::Value::IntValue(i) => (*i).into(),
+        any_value::Value::DoubleValue(d) => (*d).into(),
+        any_value::Value::BytesValue(bytes) => BASE64.encode(bytes).into(),
+        any_value::Value::ArrayValue(array) => array.values.iter().map(json_of).collect(),
+        any_value::Value::KvlistValue(kvlist) => kvlist
+            .values
+            .iter()
+            .map(|kv| {
+                let value = kv.value.as_ref().map_or(serde_json::Value::Null, json_of);
+                (kv.key.clone(), value)
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+    }
+}
+
+fn key_values(attributes: &[KeyValue]) -> Vec<opentelemetry::KeyValue> {
+    attributes.iter().map(key_value).collect()
+}
+
+fn resource(resource: Option<&v1::Resource>, schema_url: &str) -> Resource {
+    let attributes = resource.map_or_else(Vec::new, |resource| key_values(&resource.attributes));
+    let builder = Resource::builder_empty();
+    if schema_url.is_empty() {
+        builder.with_attributes(attributes).build()
+    } else {
+        builder
+            .with_schema_url(attributes, schema_url.to_string())
+            .build()
+    }
+}
+
+fn instrumentation_scope(
+    scope: Option<&crate::proto::opentelemetry::common::v1::InstrumentationScope>,
+    schema_url: &str,
+) -> InstrumentationScope {
+    let mut builder =
+        InstrumentationScope::builder(scope.map_or_else(String::new, |scope| scope.name.clone()));
+    if let Some(scope) = scope {
+        if !scope.version.is_empty() {
+            builder = builder.with_version(scope.version.clone());
+        }
+        builder = builder.with_attributes(key_values(&scope.attributes));
+    }
+    if !schema_url.is_empty() {
+        builder = builder.with_schema_url(schema_url.to_string());
+    }
+    builder.build()
+}
+
+fn span_data_of(span: &Span, instrumentation_scope: InstrumentationScope) -> SpanData {
+    let mut events = SpanEvents::default();
+    events.events = span.events.iter().map(event).collect();
+    events.dropped_count = span.dropped_events_count;
+    let mut links = SpanLinks::default();
+    links.links = span.links.iter().map(link).collect();
+    links.dropped_count = span.dropped_links_count;
+
+    SpanData {
+        span_context: span_context(&span.trace_id, &span.span_id, span.flags, &span.trace_state),
+        parent_span_id: span_id(&span.parent_span_id),
+        span_kind: span_kind(span.kind),
+        name: Cow::Owned(span.name.clone()),
+        start_time: time(span.start_time_unix_nano),
+        end_time: time(span.end_time_unix_nano),
+        attributes: key_values(&span.attributes),
+        dropped_attributes_count: span.dropped_attributes_count,
+        events,
+        links,
+        status: span.status.as_ref().map_or(Status::Unset, |status| {
+            match status::StatusCode::try_from(status.code) {
+                Ok(status::StatusCode::Ok) => Status::Ok,
+                Ok(status::StatusCode::Error) => Status::error(status.message.clone()),
+                _ => Status::Unset,
+            }
+        }),
+        instrumentation_scope,
+    }
+}
+
+fn event(event: &span::Event) -> Event {
+    Event::new(
+        event.name.clone(),
+        time(event.time_unix_nano),
+        key_values(&event.attributes),
+        event.dropped_attributes_count,
+    )
+}
+
+fn link(link: &span::Link) -> Link {
+    Link::new(
+        span_context(&link.trace_id, &link.span_id, link.flags, &link.trace_state),
+        key_values(&link.attributes),
+        link.dropped_attributes_count,
+    )
+}
+
+/// The malformed ids are converted to the invalid ids.
+fn span_context(
+    trace_id: &[u8],
+    span_id_bytes: &[u8],
+    flags: u32,
+    trace_state: &str,
+) -> SpanContext {
+    let trace_id = trace_id
+        .try_into()
+        .map_or(TraceId::INVALID, TraceId::from_bytes);
+    let is_remote = flags & HAS_IS_REMOTE_MASK != 0 && flags & IS_REMOTE_MASK != 0;
+    SpanContext::new(
+        trace_id,
+        span_id(span_id_bytes),
+        TraceFlags::new((flags & 0xff) as u8),
+        is_remote,
+        trace_state.parse().unwrap_or_default(),
+    )
+}
+
+fn span_id(span_id: &[u8]) -> SpanId {
+    span_id
+        .try_into()
+        .map_or(SpanId::INVALID, SpanId::from_bytes)
+}
+
+fn span_kind(kind: i32) -> SpanKind {
+    match span::SpanKind::try_from(kind) {
+        Ok(span::SpanKind::Server) => SpanKind::Server,
+        Ok(span::SpanKind::Client) => SpanKind::Client,
+        Ok(span::SpanKind::Producer) => SpanKind::Producer,
+        Ok(span::SpanKind::Consumer) => SpanKind::Consumer,
+        _ => SpanKind::Internal,
+    }
+}
+
+fn time(unix_nano: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_nanos(unix_nano)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::proto::opentelemetry::common::v1::{ArrayValue, KeyValueList};
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans};
+
+    fn kv(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn test_key_value() {
+        let int = |i| AnyValue {
+            value: Some(any_value::Value::IntValue(i)),
+        };
+        let array = |values| any_value::Value::ArrayValue(ArrayValue { values });
+        let mixed = vec![int(1), AnyValue {
+            value: Some(any_value::Value::BoolValue(true)),
+        }];
+
+        assert_eq!(
+            key_value(&kv("a", array(vec![int(1), int(2)]))),
+            opentelemetry::KeyValue::new("a", Value::Array(Array::I64(vec![1, 2])))
+        );
+        assert_eq!(
+            key_value(&kv("a", array(mixed))),
+            opentelemetry::KeyValue::new("a", "[1,true]")
+        );
+        assert_eq!(
+            key_value(&kv(
+                "a",
+                any_value::Value::KvlistValue(KeyValueList {
+                    values: vec![kv("b", any_value::Value::IntValue(1))],
+                })
+            )),
+            opentelemetry::KeyValue::new("a", r#"{"b":1}"#)
+        );
+        assert_eq!(
+            key_value(&kv("a", any_value::Value::BytesValue(vec![1, 2]))),
+            opentelemetry::KeyValue::new("a", "AQI=")
+        );
+        assert_eq!(
+            log_any_value(&AnyValue {
+                value: Some(any_value::Value::BytesValue(vec![1, 2]))
+            }),
+            Some(logs::AnyValue::Bytes(Box::new(vec![1, 2])))
+        );
+    }
+
+    #[test]
+    fn test_span_data() {
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(v1::Resource {
+                    attributes: vec![kv(
+                        "service.name",
+                        any_value::Value::StringValue("a".into()),
+                    )],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(
+                        crate::proto::opentelemetry::common::v1::InstrumentationScope {
+                            name: "scope".into(),
+                            version: "1.0".into(),
+                            ..Default::default()
+                        },
+                    ),
+                    spans: vec![Span {
+                        trace_id: vec![1; 16],
+                        span_id: vec![2; 8],
+                        parent_span_id: vec![3; 8],
+                        trace_state: "k=v".into(),
+                        flags: 0x301,
+                        name: "span".into(),
+                        kind: span::SpanKind::Server as i32,
+                        start_time_unix_nano: 1_000,
+                        end_time_unix_nano: 2_000,
+                        links: vec![span::Link {
+                            trace_id: vec![4; 15],
+                            span_id: vec![5; 8],
+                            ..Default::default()
+                        }],
+                        status: Some(crate::proto::opentelemetry::trace::v1::Status {
+                            message: "failed".into(),
+                            code: status::StatusCode::Error as i32,
+                        }),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let batches = span_data(&request);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            batches[0].resource.get(&"service.name".into()),
+            Some(Value::from("a"))
+        );
+        let [span] = &batches[0].spans[..] else {
+            panic!("expected one span");
+        };
+        assert_eq!(span.span_context.trace_id(), TraceId::from_bytes([1; 16]));
+        assert_eq!(span.span_context.span_id(), SpanId::from_bytes([2; 8]));
+        assert!(span.span_context.is_remote());
+        assert!(span.span_context.is_sampled());
+        assert_eq!(span.span_context.trace_state().get("k"), Some("v"));
+        assert_eq!(span.parent_span_id, SpanId::from_bytes([3; 8]));
+        assert_eq!(span.span_kind, SpanKind::Server);
+        assert_eq!(
+            span.end_time
+                .duration_since(span.start_time)
+                .unwrap()
+                .as_nanos(),
+            1_000
+        );
+        assert_eq!(span.status, Status::error("failed"));
+        assert_eq!(span.instrumentation_scope.name(), "scope");
+        assert_eq!(span.instrumentation_scope.version(), Some("1.0"));
+        // the malformed trace id of the link is converted to the invalid id
+        assert_eq!(
+            span.links.links[0].span_context.trace_id(),
+            TraceId::INVALID
+        );
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Buffers of the decoder reused across the batches decoded with the same
+//! [`DecoderOptions`](crate::otlp::options::DecoderOptions), e.g. the batches of a stream.
+//!
+//! The attribute stores of a batch are dropped once its OTLP message is built. Their id
+//! maps and `KeyValue` vectors are cleared and returned to the pool instead of being
+//! freed, and the stores of the next batch are built from them, so a stream under
+//! sustained load doesn't allocate them again for every batch. The pool keeps a bounded
+//! number of buffers, the extra ones are freed.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::otlp::attributes::id_map::IdMap;
+use crate::proto::opentelemetry::common::v1::KeyValue;
+
+/// Largest number of attribute maps kept per id type, a batch uses at most a dozen.
+const MAX_POOLED_MAPS: usize = 32;
+
+/// Largest number of `KeyValue` vectors kept, one is used per attribute set of a batch.
+const MAX_POOLED_KEY_VALUES: usize = 1 << 16;
+
+pub(crate) type AttributeMap<T, A = KeyValue> = IdMap<T, Vec<A>>;
+
+pub(crate) type PooledMaps<T> = Mutex<Vec<AttributeMap<T>>>;
+
+/// Cleared buffers of the decoder, shared by the batches decoded with the same options.
+#[derive(Debug)]
+pub struct DecoderPool {
+    pub(crate) attrs16_maps: PooledMaps<u16>,
+    pub(crate) attrs32_maps: PooledMaps<u32>,
+    key_values: Mutex<Vec<Vec<KeyValue>>>,
+    max_key_values: usize,
+}
+
+impl Default for DecoderPool {
+    fn default() -> Self {
+        Self::with_max_key_values(MAX_POOLED_KEY_VALUES)
+    }
+}
+
+impl DecoderPool {
+    /// Creates an empty pool.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty pool keeping at most `max_key_values` `KeyValue` vectors, instead
+    /// of 65536, e.g. to cap the memory held by the pool of a small stream.
+    #[must_use]
+    pub fn with_max_key_values(max_key_values: usize) -> Self {
+        Self {
+            attrs16_maps: PooledMaps::default(),
+            attrs32_maps: PooledMaps::default(),
+            key_values: Mutex::default(),
+            max_key_values,
+        }
+    }
+
+    /// Returns the number of attribute maps and `KeyValue` vectors held by the pool.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        lock(&self.attrs16_maps).len()
+            + lock(&self.attrs32_maps).len()
+            + lock(&self.key_values).len()
+    }
+
+    /// Returns true if the pool holds no buffer.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frees the buffers held by the pool, e.g. after a load spike.
+    pub fn clear(&self) {
+        lock(&self.attrs16_maps).clear();
+        lock(&self.attrs32_maps).clear();
+        lock(&self.key_values).clear();
+    }
+
+    /// Takes a cleared map out of the pool, or allocates a new one.
+    pub(crate) fn take_map<T>(maps: Option<&PooledMaps<T>>) -> AttributeMap<T> {
+        maps.and_then(|maps| lock(maps).pop()).unwrap_or_default()
+    }
+
+    /// Takes all the cleared `KeyValue` vectors out of the pool, so they can be used
+    /// without locking the pool for every attribute set.
+    pub(crate) fn take_key_values(&self) -> Vec<Vec<KeyValue>> {
+        std::mem::take(&mut *lock(&self.key_values))
+    }
+
+    /// Clears the map and its vectors and returns them to the pool.
+    pub(crate) fn recycle<T>(&self, maps: Option<&PooledMaps<T>>, mut map: AttributeMap<T>) {
+        self.recycle_key_values(map.drain());
+        if let Some(maps) = maps {
+            let mut maps = lock(maps);
+            if maps.len() < MAX_POOLED_MAPS {
+                maps.push(map);
+            }
+        }
+    }
+
+    /// Clears the vectors and returns them to the pool.
+    pub(crate) fn recycle_key_values(&self, buffers: impl IntoIterator<Item = Vec<KeyValue>>) {
+        let mut key_values = lock(&self.key_values);
+        for mut buffer in buffers {
+            if key_values.len() >= self.max_key_values {
+                break;
+            }
+            buffer.clear();
+            key_values.push(buffer);
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // the buffers are cleared before they are returned, a panic leaves no partial state
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::encode::LogsProducer;
+    use crate::otlp::logs::logs_from_with_options;
+    use crate::otlp::options::DecoderOptions;
+    use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+    use crate::proto::opentelemetry::common::v1::AnyValue;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    #[test]
+    fn test_pool_reused_across_batches() {
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: (0..8)
+                        .map(|i| LogRecord {
+                            time_unix_nano: i,
+                            attributes: vec![KeyValue {
+                                key: "k".into(),
+                                value: Some(AnyValue {
+                                    value: Some(Value::IntValue(i as i64)),
+                                }),
+                            }],
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let pool = Arc::new(DecoderPool::new());
+        let options = DecoderOptions::default().with_pool(pool.clone());
+        let producer = LogsProducer::new();
+
+        let decoded =
+            logs_from_with_options(producer.produce(&request).unwrap(), &options).unwrap();
+        assert_eq!(
+            decoded.resource_logs[0].scope_logs[0].log_records,
+            request.resource_logs[0].scope_logs[0].log_records
+        );
+        // the store of the log attributes is returned with its 8 attribute sets
+        assert_eq!(lock(&pool.attrs16_maps).len(), 1);
+        assert_eq!(lock(&pool.key_values).len(), 8);
+        assert!(lock(&pool.key_values).iter().all(Vec::is_empty));
+
+        // the next batch reuses them instead of growing the pool
+        let decoded =
+            logs_from_with_options(producer.produce(&request).unwrap(), &options).unwrap();
+        assert_eq!(
+            decoded.resource_logs[0].scope_logs[0].log_records,
+            request.resource_logs[0].scope_logs[0].log_records
+        );
+        assert_eq!(pool.len(), 9);
+
+        pool.clear();
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Summary of the rows dropped while decoding a batch with
+//! [`DecoderOptions::skip_bad_rows`](crate::otlp::options::DecoderOptions::skip_bad_rows),
+//! of the delta encoded ids handled by the
+//! [`DeltaIdPolicy`](crate::otlp::options::DeltaIdPolicy), and of the malformed trace and span
+//! ids handled by the [`IdValidationPolicy`](crate::otlp::options::IdValidationPolicy), and of
+//! the records failing the checks of the
+//! [`TimestampPolicy`](crate::otlp::options::TimestampPolicy).
+
+use std::collections::BTreeMap;
+
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+/// Why a row was dropped.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum DroppedRowReason {
+    /// The attribute value type is not a known `AttributeValueType`.
+    UnrecognizedValueType,
+    /// The CBOR encoded value of a map or slice attribute could not be decoded.
+    InvalidSerializedValue,
+    /// The attribute value is not stored in the column matching its declared type, and the
+    /// coercion policy rejects it.
+    ValueTypeMismatch,
+    /// The attribute key is already in the attribute set, and the duplicate key policy
+    /// rejects it.
+    DuplicateKey,
+    /// The log record or span is dropped by the record filter.
+    Filtered,
+    /// The trace or span id of the span or link is malformed, and the id validation policy
+    /// drops it.
+    InvalidId,
+    /// The attribute value is over the value limits, and they reject it.
+    OversizedValue,
+}
+
+/// Counts of the rows dropped while decoding a batch, per payload type and reason, and of
+/// the out of range delta ids, per attribute payload type, and of the malformed trace and
+/// span ids and of the records with invalid timestamps, per payload type.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DecodeReport {
+    dropped_rows: BTreeMap<(ArrowPayloadType, DroppedRowReason), u64>,
+    delta_id_anomalies: BTreeMap<ArrowPayloadType, u64>,
+    invalid_ids: BTreeMap<ArrowPayloadType, u64>,
+    timestamp_anomalies: BTreeMap<ArrowPayloadType, u64>,
+}
+
+impl DecodeReport {
+    pub(crate) fn record(&mut self, payload_type: ArrowPayloadType, reason: DroppedRowReason) {
+        *self.dropped_rows.entry((payload_type, reason)).or_default() += 1;
+    }
+
+    pub(crate) fn record_delta_id_anomaly(&mut self, payload_type: ArrowPayloadType) {
+        *self.delta_id_anomalies.entry(payload_type).or_default() += 1;
+    }
+
+    pub(crate) fn record_invalid_id(&mut self, payload_type: ArrowPayloadType) {
+        *self.invalid_ids.entry(payload_type).or_default() += 1;
+    }
+
+    pub(crate) fn record_timestamp_anomaly(&mut self, payload_type: ArrowPayloadType) {
+        *self.timestamp_anomalies.entry(payload_type).or_default() += 1;
+    }
+
+    /// Returns true if no row was dropped, no delta id was out of range, no trace or span
+    /// id was malformed and no timestamp failed the checks.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.dropped_rows.is_empty()
+            && self.delta_id_anomalies.is_empty()
+            && self.invalid_ids.is_empty()
+            && self.timestamp_anomalies.is_empty()
+    }
+
+    /// Returns the number of rows of the payload type dropped for the given reason.
+    #[must_use]
+    pub fn dropped_rows(&self, payload_type: ArrowPayloadType, reason: DroppedRowReason) -> u64 {
+        self.dropped_rows
+            .get(&(payload_type, reason))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the total number of rows dropped.
+    #[must_use]
+    pub fn total_dropped_rows(&self) -> u64 {
+        self.dropped_rows.values().sum()
+    }
+
+    /// Iterates over the number of dropped rows per payload type and reason.
+    pub fn iter(&self) -> impl Iterator<Item = (ArrowPayloadType, DroppedRowReason, u64)> + '_ {
+        self.dropped_rows
+            .iter()
+            .map(|((payload_type, reason), count)| (*payload_type, *reason, *count))
+    }
+
+    /// Returns the number of delta ids looking up the attributes of the payload type that
+    /// were out of the range of their id type.
+    #[must_use]
+    pub fn delta_id_anomalies(&self, payload_type: ArrowPayloadType) -> u64 {
+        self.delta_id_anomalies
+            .get(&payload_type)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Iterates over the number of out of range delta ids per attribute payload type.
+    pub fn iter_delta_id_anomalies(&self) -> impl Iterator<Item = (ArrowPayloadType, u64)> + '_ {
+        self.delta_id_anomalies
+            .iter()
+            .map(|(payload_type, count)| (*payload_type, *count))
+    }
+
+    /// Returns the number of malformed trace and span ids of the payload type that were
+    /// dropped or replaced.
+    #[must_use]
+    pub fn invalid_ids(&self, payload_type: ArrowPayloadType) -> u64 {
+        self.invalid_ids
+            .get(&payload_type)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Iterates over the number of malformed trace and span ids per payload type.
+    pub fn iter_invalid_ids(&self) -> impl Iterator<Item = (ArrowPayloadType, u64)> + '_ {
+        self.invalid_ids
+            .iter()
+            .map(|(payload_type, count)| (*payload_type, *count))
+    }
+
+    /// Returns the number of records of the payload type whose timestamps failed the checks.
+    #[must_use]
+    pub fn timestamp_anomalies(&self, payload_type: ArrowPayloadType) -> u64 {
+        self.timestamp_anomalies
+            .get(&payload_type)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Iterates over the number of records whose timestamps failed the checks per payload
+    /// type.
+    pub fn iter_timestamp_anomalies(&self) -> impl Iterator<Item = (ArrowPayloadType, u64)> + '_ {
+        self.timestamp_anomalies
+            .iter()
+            .map(|(payload_type, count)| (*payload_type, *count))
+    }
+
+    /// Adds the counts of `other` to this report.
+    pub fn merge(&mut self, other: &DecodeReport) {
+        for (key, count) in &other.dropped_rows {
+            *self.dropped_rows.entry(*key).or_default() += count;
+        }
+        for (key, count) in &other.delta_id_anomalies {
+            *self.delta_id_anomalies.entry(*key).or_default() += count;
+        }
+        for (key, count) in &other.invalid_ids {
+            *self.invalid_ids.entry(*key).or_default() += count;
+        }
+        for (key, count) in &other.timestamp_anomalies {
+            *self.timestamp_anomalies.entry(*key).or_default() += count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{RecordBatch, StringArray, UInt8Array, UInt16Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+
+    use crate::otap::{Logs, OtapBatch};
+    use crate::otlp::attributes::store::{Attribute16Store, AttributeValueType};
+    use crate::otlp::options::DecoderOptions;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+    use crate::schema::consts;
+
+    #[test]
+    fn test_skip_bad_rows() {
+        let mut otap_batch = OtapBatch::Logs(Logs::default());
+        otap_batch.set(
+            ArrowPayloadType::LogAttrs,
+            RecordBatch::try_new(
+                Arc::new(Schema::new(vec![
+                    Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                    Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                    Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                    Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+                    // the int column was written with a string data type
+                    Field::new(consts::ATTRIBUTE_INT, DataType::Utf8, true),
+                ])),
+                vec![
+                    Arc::new(UInt16Array::from(vec![0, 1, 2, 3])),
+                    Arc::new(UInt8Array::from(vec![
+                        AttributeValueType::Str as u8,
+                        42,
+                        AttributeValueType::Int as u8,
+                        AttributeValueType::Str as u8,
+                    ])),
+                    Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+                    Arc::new(StringArray::from(vec![
+                        Some("x"),
+                        Some("y"),
+                        None,
+                        Some("z"),
+                    ])),
+                    Arc::new(StringArray::from(vec![None, None, Some("1"), None])),
+                ],
+            )
+            .unwrap(),
+        );
+
+        let mut report = DecodeReport::default();
+        assert!(
+            Attribute16Store::from_payload(
+                &otap_batch,
+                ArrowPayloadType::LogAttrs,
+                &DecoderOptions::default(),
+                &mut report,
+            )
+            .is_err()
+        );
+
+        let store = Attribute16Store::from_payload(
+            &otap_batch,
+            ArrowPayloadType::LogAttrs,
+            &DecoderOptions::default().with_skip_bad_rows(true),
+            &mut report,
+        )
+        .unwrap()
+        .unwrap();
+        let str_attr = |key: &str, value: &str| KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue(value.to_string())),
+            }),
+        };
+        assert_eq!(store.attribute_by_id(0), Some(&[str_attr("a", "x")][..]));
+        assert_eq!(store.attribute_by_id(1), None);
+        assert_eq!(store.attribute_by_id(2), None);
+        assert_eq!(store.attribute_by_id(3), Some(&[str_attr("d", "z")][..]));
+
+        assert_eq!(report.total_dropped_rows(), 2);
+        assert_eq!(report.iter().collect::<Vec<_>>(), vec![
+            (
+                ArrowPayloadType::LogAttrs,
+                DroppedRowReason::UnrecognizedValueType,
+                1
+            ),
+            (
+                ArrowPayloadType::LogAttrs,
+                DroppedRowReason::ValueTypeMismatch,
+                1
+            ),
+        ]);
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversions of OTAP batches into OTLP messages running on the blocking thread pool of
+//! tokio, so that decoding a large batch doesn't stall the async tasks of the runtime, e.g.
+//! the other streams of a receiver.
+//!
+//! The returned [`DecodeTask`]s are futures resolving to the result of the conversion.
+//! Dropping a task cancels the conversion if it hasn't started yet, a conversion already
+//! running completes and its result is discarded. The functions must be called from within
+//! a tokio runtime.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::task::JoinHandle;
+
+use crate::ExportRequest;
+use crate::error::{self, Result};
+use crate::otap::OtapBatch;
+use crate::otlp::logs::logs_from_with_report;
+use crate::otlp::metrics::metrics_from_with_report;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::traces::traces_from_with_report;
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+
+/// Conversion running on the blocking thread pool, resolving to its result.
+///
+/// A panic of the conversion is resumed when the task is awaited.
+#[must_use = "the conversion is cancelled when the task is dropped"]
+#[derive(Debug)]
+pub struct DecodeTask<T> {
+    handle: JoinHandle<Result<T>>,
+}
+
+impl<T> Future for DecodeTask<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.handle)
+            .poll(cx)
+            .map(|result| match result {
+                Ok(result) => result,
+                Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+                Err(_) => error::DecodeTaskCancelledSnafu.fail(),
+            })
+    }
+}
+
+impl<T> Drop for DecodeTask<T> {
+    fn drop(&mut self) {
+        // only prevents the conversion from starting, a running one can't be interrupted
+        self.handle.abort();
+    }
+}
+
+/// Runs the conversion on the blocking thread pool.
+pub fn spawn_decode<T, F>(decode: F) -> DecodeTask<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    DecodeTask {
+        handle: tokio::task::spawn_blocking(decode),
+    }
+}
+
+/// Converts the logs batch on the blocking thread pool, see
+/// [`logs_from_with_report`].
+pub fn spawn_logs_from(
+    otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> DecodeTask<(ExportLogsServiceRequest, DecodeReport)> {
+    let options = options.clone();
+    spawn_decode(move || logs_from_with_report(otap_batch, &options))
+}
+
+/// Converts the metrics batch on the blocking thread pool, see
+/// [`metrics_from_with_report`].
+pub fn spawn_metrics_from(
+    otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> DecodeTask<(ExportMetricsServiceRequest, DecodeReport)> {
+    let options = options.clone();
+    spawn_decode(move || metrics_from_with_report(otap_batch, &options))
+}
+
+/// Converts the traces batch on the blocking thread pool, see
+/// [`traces_from_with_report`].
+pub fn spawn_traces_from(
+    otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> DecodeTask<(ExportTraceServiceRequest, DecodeReport)> {
+    let options = options.clone();
+    spawn_decode(move || traces_from_with_report(otap_batch, &options))
+}
+
+/// Converts the batch into the export request of its signal on the blocking thread pool.
+pub fn spawn_request_from(
+    otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> DecodeTask<(ExportRequest, DecodeReport)> {
+    let options = options.clone();
+    spawn_decode(move || match otap_batch {
+        OtapBatch::Logs(_) => logs_from_with_report(otap_batch, &options)
+            .map(|(request, report)| (ExportRequest::Logs(request), report)),
+        OtapBatch::Metrics(_) => metrics_from_with_report(otap_batch, &options)
+            .map(|(request, report)| (ExportRequest::Metrics(request), report)),
+        OtapBatch::Traces(_) => traces_from_with_report(otap_batch, &options)
+            .map(|(request, report)| (ExportRequest::Traces(request), report)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::LogsProducer;
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    #[tokio::test]
+    async fn test_spawn_decode() {
+        let request = ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![LogRecord {
+                        time_unix_nano: 1,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let producer = LogsProducer::new();
+        let otap_batch = producer.produce(&request).unwrap();
+        let (decoded, report) = spawn_logs_from(otap_batch, &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            decoded.resource_logs[0].scope_logs[0].log_records,
+            request.resource_logs[0].scope_logs[0].log_records
+        );
+        assert_eq!(report, DecodeReport::default());
+
+        let otap_batch = producer.produce(&request).unwrap();
+        let (decoded, _) = spawn_request_from(otap_batch, &Default::default())
+            .await
+            .unwrap();
+        assert!(matches!(decoded, ExportRequest::Logs(_)));
+
+        let result: Result<()> = spawn_decode(|| error::EmptyBatchSnafu.fail()).await;
+        assert!(result.is_err());
+    }
+}
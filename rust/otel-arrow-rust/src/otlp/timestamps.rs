@@ -0,0 +1,114 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Checks of the timestamps of the decoded records, according to the [`TimestampPolicy`]
+//! of the decoder options.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use snafu::ensure;
+
+use crate::error::{self, Result};
+use crate::otlp::options::{DecoderOptions, TimestampAction, TimestampPolicy};
+use crate::otlp::report::DecodeReport;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+/// Applies the timestamp policy of a batch, the window being computed once from the time
+/// the decoding of the batch started.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TimestampChecker {
+    policy: Option<TimestampPolicy>,
+    min: u64,
+    max: u64,
+}
+
+impl TimestampChecker {
+    pub(crate) fn new(options: &DecoderOptions) -> Self {
+        let Some(policy) = options.timestamp_policy else {
+            return Self::default();
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let nanos = |d: Duration| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX);
+        Self {
+            policy: Some(policy),
+            min: policy
+                .max_past()
+                .map_or(0, |max_past| nanos(now.saturating_sub(max_past))),
+            max: policy
+                .max_future()
+                .map_or(u64::MAX, |max_future| nanos(now.saturating_add(max_future))),
+        }
+    }
+
+    /// Checks the start and end timestamps of the row `idx` of the payload, the end being
+    /// expected to not be before the start.
+    pub(crate) fn check_range(
+        &self,
+        start: &mut u64,
+        end: &mut u64,
+        idx: usize,
+        payload_type: ArrowPayloadType,
+        report: &mut DecodeReport,
+    ) -> Result<()> {
+        self.check(start, end, true, idx, payload_type, report)
+    }
+
+    /// Checks two unrelated timestamps of the row `idx` of the payload.
+    pub(crate) fn check_pair(
+        &self,
+        first: &mut u64,
+        second: &mut u64,
+        idx: usize,
+        payload_type: ArrowPayloadType,
+        report: &mut DecodeReport,
+    ) -> Result<()> {
+        self.check(first, second, false, idx, payload_type, report)
+    }
+
+    fn check(
+        &self,
+        start: &mut u64,
+        end: &mut u64,
+        ordered: bool,
+        idx: usize,
+        payload_type: ArrowPayloadType,
+        report: &mut DecodeReport,
+    ) -> Result<()> {
+        let Some(policy) = self.policy else {
+            return Ok(());
+        };
+        // the unset timestamps are zero
+        let in_window = |time: u64| time == 0 || (self.min..=self.max).contains(&time);
+        let in_order = !ordered || *start == 0 || *end == 0 || *start <= *end;
+        if in_window(*start) && in_window(*end) && in_order {
+            return Ok(());
+        }
+
+        ensure!(
+            policy.action() != TimestampAction::Error,
+            error::InvalidTimestampSnafu {
+                message: format!(
+                    "payload = {}, index = {}, timestamps = {}, {}",
+                    payload_type.as_str_name(),
+                    idx,
+                    start,
+                    end
+                ),
+            }
+        );
+        report.record_timestamp_anomaly(payload_type);
+        if policy.action() == TimestampAction::Clamp {
+            for time in [&mut *start, &mut *end] {
+                if *time != 0 {
+                    *time = (*time).clamp(self.min, self.max);
+                }
+            }
+            if ordered && *start != 0 && *end != 0 && *end < *start {
+                *end = *start;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,942 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use arrow::array::{
+    Array, RecordBatch, StructArray, TimestampNanosecondArray, UInt16Array, UInt32Array,
+};
+use arrow::datatypes::{DataType, Fields};
+use ids::{IdKind, validate_id};
+use related_data::RelatedData;
+use snafu::OptionExt;
+
+use crate::arrays::{
+    ByteArrayAccessor, ColumnAccessor, DurationMillisArrayAccessor, EnumArrayAccessor,
+    NullableArrayAccessor, StringArrayAccessor, StructColumnAccessor,
+    get_timestamp_nanosecond_array_opt, get_u16_array_opt, get_u32_array_opt,
+};
+use crate::error::{self, Error, ErrorContext, ErrorContextExt, Result};
+use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
+use crate::otlp::common::{ResourceArrays, ScopeArrays};
+use crate::otlp::enums::validate_enum;
+use crate::otlp::extra_columns::ExtraColumns;
+use crate::otlp::filter::KeptRows;
+use crate::otlp::options::{DecoderOptions, EnumValidationPolicy};
+use crate::otlp::report::{DecodeReport, DroppedRowReason};
+use crate::otlp::timestamps::TimestampChecker;
+use crate::otlp::visitor::{FnVisitor, RecordVisitor};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::InstrumentationScope;
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::proto::opentelemetry::trace::v1::span::SpanKind;
+use crate::proto::opentelemetry::trace::v1::status::StatusCode;
+use crate::proto::opentelemetry::trace::v1::{Span, Status};
+use crate::schema::consts;
+
+mod ids;
+mod proto_bytes;
+mod related_data;
+mod span_event;
+mod span_link;
+
+pub use proto_bytes::{
+    TracesBytesDecoder, traces_bytes_from, traces_bytes_from_with_options,
+    traces_bytes_from_with_report,
+};
+
+struct SpansArrays<'a> {
+    id: Option<&'a UInt16Array>,
+    schema_url: Option<StringArrayAccessor<'a>>,
+    start_time_unix_nano: Option<&'a TimestampNanosecondArray>,
+    duration_time_unix_nano: Option<DurationMillisArrayAccessor<'a>>,
+    trace_id: ByteArrayAccessor<'a>,
+    span_id: ByteArrayAccessor<'a>,
+    trace_state: Option<StringArrayAccessor<'a>>,
+    parent_span_id: Option<ByteArrayAccessor<'a>>,
+    name: Option<StringArrayAccessor<'a>>,
+    kind: Option<EnumArrayAccessor<'a>>,
+    flags: Option<&'a UInt32Array>,
+    dropped_attributes_count: Option<&'a UInt32Array>,
+    dropped_events_count: Option<&'a UInt32Array>,
+    dropped_links_count: Option<&'a UInt32Array>,
+    status: Option<SpanStatusArrays<'a>>,
+}
+
+impl<'a> TryFrom<&'a RecordBatch> for SpansArrays<'a> {
+    type Error = Error;
+
+    fn try_from(rb: &'a RecordBatch) -> Result<Self> {
+        let id = get_u16_array_opt(rb, consts::ID)?;
+        let schema_url = StringArrayAccessor::try_new_for_column_opt(rb, consts::SCHEMA_URL)?;
+        let start_time_unix_nano =
+            get_timestamp_nanosecond_array_opt(rb, consts::START_TIME_UNIX_NANO)?;
+        let duration_time_unix_nano = DurationMillisArrayAccessor::try_new_for_column_opt(
+            rb,
+            consts::DURATION_TIME_UNIX_NANO,
+        )?;
+        let trace_id = ByteArrayAccessor::try_new_for_column(rb, consts::TRACE_ID)?;
+        let span_id = ByteArrayAccessor::try_new_for_column(rb, consts::SPAN_ID)?;
+        let trace_state = StringArrayAccessor::try_new_for_column_opt(rb, consts::TRACE_STATE)?;
+        let parent_span_id = ByteArrayAccessor::try_new_for_column_opt(rb, consts::PARENT_SPAN_ID)?;
+        let name = StringArrayAccessor::try_new_for_column_opt(rb, consts::NAME)?;
+        let kind = EnumArrayAccessor::try_new_for_column_opt(rb, consts::KIND)?;
+        let flags = get_u32_array_opt(rb, consts::FLAGS)?;
+        let dropped_attributes_count = get_u32_array_opt(rb, consts::DROPPED_ATTRIBUTES_COUNT)?;
+        let dropped_events_count = get_u32_array_opt(rb, consts::DROPPED_EVENTS_COUNT)?;
+        let dropped_links_count = get_u32_array_opt(rb, consts::DROPPED_LINKS_COUNT)?;
+
+        let status = rb
+            .column_by_name(consts::STATUS)
+            .map(|arr| {
+                let status = arr.as_any().downcast_ref::<StructArray>().context(
+                    error::ColumnDataTypeMismatchSnafu {
+                        name: consts::STATUS,
+                        actual: arr.data_type().clone(),
+                        expect: DataType::Struct(Fields::default()),
+                    },
+                )?;
+
+                SpanStatusArrays::try_from(status)
+            })
+            .transpose()?;
+
+        Ok(Self {
+            id,
+            schema_url,
+            start_time_unix_nano,
+            duration_time_unix_nano,
+            trace_id,
+            span_id,
+            trace_state,
+            parent_span_id,
+            name,
+            kind,
+            flags,
+            dropped_attributes_count,
+            dropped_events_count,
+            dropped_links_count,
+            status,
+        })
+    }
+}
+
+impl SpansArrays<'_> {
+    /// Returns the kind of the span at the given index, checked against the policy.
+    fn kind_at(&self, idx: usize, policy: EnumValidationPolicy) -> Result<i32> {
+        validate_enum::<SpanKind>(self.kind.value_at_or_default(idx), policy).error_context(|| {
+            ErrorContext::default()
+                .payload(ArrowPayloadType::Spans)
+                .row(idx)
+                .column(consts::KIND)
+        })
+    }
+
+    /// Returns the status of the span at the given index, its code being checked against
+    /// the policy.
+    fn status_at(&self, idx: usize, policy: EnumValidationPolicy) -> Result<Option<Status>> {
+        let Some(mut status) = self.status.value_at(idx) else {
+            return Ok(None);
+        };
+        status.code = validate_enum::<StatusCode>(status.code, policy).error_context(|| {
+            ErrorContext::default()
+                .payload(ArrowPayloadType::Spans)
+                .row(idx)
+                .column(consts::STATUS_CODE)
+        })?;
+        Ok(Some(status))
+    }
+}
+
+struct SpanStatusArrays<'a> {
+    status: &'a StructArray,
+    code: Option<EnumArrayAccessor<'a>>,
+    message: Option<StringArrayAccessor<'a>>,
+}
+
+impl NullableArrayAccessor for SpanStatusArrays<'_> {
+    type Native = Status;
+
+    fn value_at(&self, idx: usize) -> Option<Self::Native> {
+        if !self.status.is_valid(idx) {
+            return None;
+        }
+
+        Some(Status {
+            code: self.code.value_at_or_default(idx),
+            message: self.message.value_at_or_default(idx),
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a StructArray> for SpanStatusArrays<'a> {
+    type Error = Error;
+
+    fn try_from(status: &'a StructArray) -> Result<Self> {
+        let column_accessor = StructColumnAccessor::new(status);
+        Ok(Self {
+            status,
+            code: column_accessor.accessor_column_op(consts::STATUS_CODE)?,
+            message: column_accessor.accessor_column_op(consts::STATUS_MESSAGE)?,
+        })
+    }
+}
+
+pub fn traces_from(traces_otap_batch: OtapBatch) -> Result<ExportTraceServiceRequest> {
+    traces_from_with_options(traces_otap_batch, &DecoderOptions::default())
+}
+
+/// Same as [`traces_from`], using the given options to decode the batch.
+pub fn traces_from_with_options(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<ExportTraceServiceRequest> {
+    traces_from_with_report(traces_otap_batch, options).map(|(request, _)| request)
+}
+
+/// Same as [`traces_from_with_options`], also returning the report of the rows dropped
+/// because of [`DecoderOptions::skip_bad_rows`].
+pub fn traces_from_with_report(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<(ExportTraceServiceRequest, DecodeReport)> {
+    let mut traces = ExportTraceServiceRequest::default();
+    let report = visit_spans(traces_otap_batch, options, &mut traces)?;
+    Ok((traces, report))
+}
+
+/// Decodes the spans of the batch one at a time, passing each of them to `visit` along
+/// with its resource and scope instead of collecting them into a request.
+pub fn decode_spans_with(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+    visit: impl FnMut(&Resource, &InstrumentationScope, Span),
+) -> Result<DecodeReport> {
+    visit_spans(traces_otap_batch, options, &mut FnVisitor::new(visit))
+}
+
+/// Decodes the spans of the batch, handing them to `visitor` as they are reconstructed.
+pub fn visit_spans(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+    visitor: &mut impl RecordVisitor<Span>,
+) -> Result<DecodeReport> {
+    let mut prev_res_id: Option<u16> = None;
+    let mut prev_scope_id: Option<u16> = None;
+
+    let mut res_id = 0;
+    let mut scope_id = 0;
+
+    let guard = options.start_batch(&traces_otap_batch)?;
+    let rb = traces_otap_batch
+        .get(ArrowPayloadType::Spans)
+        .context(error::SpanRecordNotFoundSnafu)?;
+
+    let mut related_data = RelatedData::try_from_with_options(&traces_otap_batch, options)?;
+    let timestamps = TimestampChecker::new(options);
+    let extra_columns = ExtraColumns::try_new(rb, ArrowPayloadType::Spans, options)
+        .in_payload(ArrowPayloadType::Spans)?;
+    let kept_rows = KeptRows::try_new(&traces_otap_batch, ArrowPayloadType::Spans, options)
+        .in_payload(ArrowPayloadType::Spans)?;
+
+    let resource_arrays = ResourceArrays::try_from(rb).in_payload(ArrowPayloadType::Spans)?;
+    let scope_arrays = ScopeArrays::try_from(rb).in_payload(ArrowPayloadType::Spans)?;
+    let spans_arrays = SpansArrays::try_from(rb).in_payload(ArrowPayloadType::Spans)?;
+
+    for idx in 0..rb.num_rows() {
+        guard.check(idx)?;
+        let res_delta_id = resource_arrays.id.value_at(idx).unwrap_or_default();
+        res_id = add_delta(res_id, res_delta_id).error_context(|| {
+            ErrorContext::default()
+                .payload(ArrowPayloadType::Spans)
+                .row(idx)
+                .column(consts::RESOURCE)
+        })?;
+
+        if prev_res_id != Some(res_id) {
+            // new resource id
+            prev_res_id = Some(res_id);
+            prev_scope_id = None;
+
+            let mut resource = Resource::default();
+            if let Some(dropped_attributes_count) =
+                resource_arrays.dropped_attributes_count.value_at(idx)
+            {
+                resource.dropped_attributes_count = dropped_attributes_count;
+            }
+
+            if let Some(res_id) = resource_arrays.id.value_at(idx) {
+                if let Some(attrs) = related_data
+                    .res_attr_map_store
+                    .as_mut()
+                    .map(|store| store.attribute_by_delta_id(res_id, &mut related_data.report))
+                    .transpose()?
+                    .flatten()
+                {
+                    resource.attributes = attrs.to_vec();
+                }
+            }
+
+            let schema_url = resource_arrays.schema_url.value_at(idx).unwrap_or_default();
+            visitor.visit_resource(resource, schema_url);
+        }
+
+        let scope_delta_id_opt = scope_arrays.id.value_at(idx);
+        scope_id =
+            add_delta(scope_id, scope_delta_id_opt.unwrap_or_default()).error_context(|| {
+                ErrorContext::default()
+                    .payload(ArrowPayloadType::Spans)
+                    .row(idx)
+                    .column(consts::SCOPE)
+            })?;
+
+        if prev_scope_id != Some(scope_id) {
+            prev_scope_id = Some(scope_id);
+            let mut scope = scope_arrays.create_instrumentation_scope(idx);
+            if let Some(scope_id) = scope_delta_id_opt {
+                if let Some(attrs) = related_data
+                    .scope_attr_map_store
+                    .as_mut()
+                    .map(|store| store.attribute_by_delta_id(scope_id, &mut related_data.report))
+                    .transpose()?
+                    .flatten()
+                {
+                    scope.attributes = attrs.to_vec();
+                }
+            }
+
+            let schema_url = spans_arrays.schema_url.value_at(idx).unwrap_or_default();
+            visitor.visit_scope(scope, schema_url);
+        }
+
+        if let Some(reason) = kept_rows.dropped(idx) {
+            // the id of the span is still decoded to keep the delta encoded ids in sync
+            if let Some(delta_id) = spans_arrays.id.value_at(idx) {
+                let _ = related_data
+                    .span_id_from_delta(delta_id)
+                    .error_context(|| {
+                        ErrorContext::default()
+                            .payload(ArrowPayloadType::Spans)
+                            .row(idx)
+                            .column(consts::ID)
+                    })?;
+            }
+            related_data.report.record(ArrowPayloadType::Spans, reason);
+            continue;
+        }
+
+        let mut current_span = Span::default();
+
+        let mut validate = |id, kind| {
+            validate_id(
+                id,
+                kind,
+                idx,
+                options.id_validation,
+                ArrowPayloadType::Spans,
+                &mut related_data.report,
+            )
+        };
+        let trace_id = validate(
+            spans_arrays.trace_id.value_at_or_default(idx),
+            IdKind::Trace,
+        )?;
+        let span_id = validate(spans_arrays.span_id.value_at_or_default(idx), IdKind::Span)?;
+        let parent_span_id = validate(
+            spans_arrays.parent_span_id.value_at_or_default(idx),
+            IdKind::ParentSpan,
+        )?;
+        // the rest of the row is still decoded to keep the delta encoded ids in sync
+        let dropped = trace_id.is_none() || span_id.is_none() || parent_span_id.is_none();
+        current_span.trace_id = trace_id.unwrap_or_default();
+        current_span.span_id = span_id.unwrap_or_default();
+        current_span.parent_span_id = parent_span_id.unwrap_or_default();
+        current_span.trace_state = spans_arrays.trace_state.value_at_or_default(idx);
+
+        current_span.name = spans_arrays.name.value_at_or_default(idx);
+        current_span.kind = spans_arrays.kind_at(idx, options.enum_validation)?;
+        // the W3C trace flags and the parent is remote bits are kept as is
+        current_span.flags = spans_arrays.flags.value_at_or_default(idx);
+
+        // the duration column holds nanoseconds even though it is typed as milliseconds,
+        // same as the encoder on the Go side.
+        let start_time_unix_nano = spans_arrays.start_time_unix_nano.value_at_or_default(idx);
+        let duration = spans_arrays
+            .duration_time_unix_nano
+            .value_at_or_default(idx);
+        current_span.start_time_unix_nano = start_time_unix_nano as u64;
+        current_span.end_time_unix_nano = (start_time_unix_nano + duration) as u64;
+        timestamps.check_range(
+            &mut current_span.start_time_unix_nano,
+            &mut current_span.end_time_unix_nano,
+            idx,
+            ArrowPayloadType::Spans,
+            &mut related_data.report,
+        )?;
+
+        current_span.dropped_attributes_count = spans_arrays
+            .dropped_attributes_count
+            .value_at_or_default(idx);
+        current_span.dropped_events_count =
+            spans_arrays.dropped_events_count.value_at_or_default(idx);
+        current_span.dropped_links_count =
+            spans_arrays.dropped_links_count.value_at_or_default(idx);
+        current_span.status = spans_arrays.status_at(idx, options.enum_validation)?;
+
+        if let Some(delta_id) = spans_arrays.id.value_at(idx) {
+            let span_id = related_data
+                .span_id_from_delta(delta_id)
+                .error_context(|| {
+                    ErrorContext::default()
+                        .payload(ArrowPayloadType::Spans)
+                        .row(idx)
+                        .column(consts::ID)
+                })?;
+
+            if let Some(attrs) = related_data
+                .span_attr_map_store
+                .as_ref()
+                .and_then(|store| store.attribute_by_id(span_id))
+            {
+                current_span.attributes = attrs.to_vec();
+            }
+
+            current_span.events = related_data.span_events_store.take_events_by_id(span_id);
+            current_span.links = related_data.span_links_store.take_links_by_id(span_id);
+        }
+        extra_columns.append_attributes(idx, &mut current_span.attributes);
+        if dropped {
+            related_data
+                .report
+                .record(ArrowPayloadType::Spans, DroppedRowReason::InvalidId);
+        } else {
+            visitor.visit_record(current_span);
+        }
+    }
+
+    Ok(related_data.report)
+}
+
+impl Span {
+    /// Returns the duration of the span in nanoseconds, zero if it ends before it starts.
+    #[must_use]
+    pub fn duration_ns(&self) -> u64 {
+        self.end_time_unix_nano
+            .saturating_sub(self.start_time_unix_nano)
+    }
+
+    /// Returns whether the status of the span is an error.
+    #[must_use]
+    pub fn is_error(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|status| status.code == StatusCode::Error as i32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use arrow::array::{
+        ArrayRef, BinaryArray, DictionaryArray, DurationMillisecondArray, FixedSizeBinaryArray,
+        Int32Array, StringArray, UInt8Array,
+    };
+    use arrow::datatypes::{Field, Schema, TimeUnit, UInt8Type};
+
+    use prost::Message;
+
+    use crate::cancel::CancellationToken;
+    use crate::otap::Traces;
+    use crate::otlp::options::{
+        DecodeLimits, IdValidationPolicy, TimestampAction, TimestampPolicy, UnknownColumnPolicy,
+    };
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+
+    fn struct_array(fields: Vec<(Field, ArrayRef)>) -> ArrayRef {
+        Arc::new(StructArray::from(
+            fields
+                .into_iter()
+                .map(|(f, a)| (Arc::new(f), a))
+                .collect::<Vec<_>>(),
+        ))
+    }
+
+    fn spans_batch() -> RecordBatch {
+        let resource = struct_array(vec![(
+            Field::new(consts::ID, DataType::UInt16, true),
+            Arc::new(UInt16Array::from(vec![0, 0, 1])),
+        )]);
+        let scope = struct_array(vec![
+            (
+                Field::new(consts::ID, DataType::UInt16, true),
+                Arc::new(UInt16Array::from(vec![0, 0, 1])),
+            ),
+            (
+                Field::new(consts::NAME, DataType::Utf8, true),
+                Arc::new(StringArray::from(vec!["s0", "s0", "s1"])),
+            ),
+        ]);
+        let status = struct_array(vec![
+            (
+                Field::new(consts::STATUS_CODE, DataType::Int32, true),
+                Arc::new(Int32Array::from(vec![Some(2), None, None])),
+            ),
+            (
+                Field::new(consts::STATUS_MESSAGE, DataType::Utf8, true),
+                Arc::new(StringArray::from(vec![Some("boom"), None, None])),
+            ),
+        ]);
+        let names: DictionaryArray<UInt8Type> = vec!["a", "b", "a"].into_iter().collect();
+        let trace_ids =
+            FixedSizeBinaryArray::try_from_iter(vec![[1u8; 16], [1u8; 16], [2u8; 16]].into_iter())
+                .unwrap();
+        let span_ids =
+            FixedSizeBinaryArray::try_from_iter(vec![[1u8; 8], [2u8; 8], [3u8; 8]].into_iter())
+                .unwrap();
+        let parent_span_ids = FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+            vec![None, Some([1u8; 8]), None].into_iter(),
+            8,
+        )
+        .unwrap();
+
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            (consts::ID, Arc::new(UInt16Array::from(vec![0, 1, 1]))),
+            (consts::RESOURCE, resource),
+            (consts::SCOPE, scope),
+            (
+                consts::START_TIME_UNIX_NANO,
+                Arc::new(TimestampNanosecondArray::from(vec![100, 200, 300])),
+            ),
+            (
+                consts::DURATION_TIME_UNIX_NANO,
+                Arc::new(DurationMillisecondArray::from(vec![10, 20, 30])),
+            ),
+            (consts::TRACE_ID, Arc::new(trace_ids)),
+            (consts::SPAN_ID, Arc::new(span_ids)),
+            (consts::PARENT_SPAN_ID, Arc::new(parent_span_ids)),
+            (consts::NAME, Arc::new(names)),
+            (consts::KIND, Arc::new(Int32Array::from(vec![1, 2, 3]))),
+            (consts::STATUS, status),
+        ];
+        RecordBatch::try_new(
+            Arc::new(Schema::new(
+                columns
+                    .iter()
+                    .map(|(name, arr)| Field::new(*name, arr.data_type().clone(), true))
+                    .collect::<Vec<_>>(),
+            )),
+            columns.into_iter().map(|(_, arr)| arr).collect(),
+        )
+        .unwrap()
+    }
+
+    fn span_attrs_batch() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::ATTRIBUTE_TYPE, DataType::UInt8, false),
+                Field::new(consts::ATTRIBUTE_KEY, DataType::Utf8, false),
+                Field::new(consts::ATTRIBUTE_STR, DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![1])),
+                Arc::new(UInt8Array::from(vec![1])),
+                Arc::new(StringArray::from(vec!["k"])),
+                Arc::new(StringArray::from(vec!["v"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn span_events_batch() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(
+                    consts::TIME_UNIX_NANO,
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    true,
+                ),
+                Field::new(consts::NAME, DataType::Utf8, false),
+            ])),
+            vec![
+                // parent ids are delta encoded within a group of events with the same name
+                Arc::new(UInt16Array::from(vec![0, 1, 1])),
+                Arc::new(TimestampNanosecondArray::from(vec![101, 201, 202])),
+                Arc::new(StringArray::from(vec!["e1", "e1", "e2"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn span_links_batch() -> RecordBatch {
+        let trace_ids = FixedSizeBinaryArray::try_from_iter(vec![[9u8; 16]].into_iter()).unwrap();
+        let span_ids = FixedSizeBinaryArray::try_from_iter(vec![[9u8; 8]].into_iter()).unwrap();
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new(consts::PARENT_ID, DataType::UInt16, false),
+                Field::new(consts::TRACE_ID, DataType::FixedSizeBinary(16), true),
+                Field::new(consts::SPAN_ID, DataType::FixedSizeBinary(8), true),
+            ])),
+            vec![
+                Arc::new(UInt16Array::from(vec![0])),
+                Arc::new(trace_ids),
+                Arc::new(span_ids),
+            ],
+        )
+        .unwrap()
+    }
+
+    pub(super) fn traces_batch() -> OtapBatch {
+        let mut otap_batch = OtapBatch::Traces(Traces::default());
+        otap_batch.set(ArrowPayloadType::Spans, spans_batch());
+        otap_batch.set(ArrowPayloadType::SpanAttrs, span_attrs_batch());
+        otap_batch.set(ArrowPayloadType::SpanEvents, span_events_batch());
+        otap_batch.set(ArrowPayloadType::SpanLinks, span_links_batch());
+        otap_batch
+    }
+
+    #[test]
+    fn test_traces_from() {
+        let traces = traces_from(traces_batch()).unwrap();
+        assert_eq!(traces.resource_spans.len(), 2);
+        let scope_spans = &traces.resource_spans[0].scope_spans;
+        assert_eq!(scope_spans.len(), 1);
+        assert_eq!(scope_spans[0].scope.as_ref().unwrap().name, "s0");
+        assert_eq!(traces.resource_spans[1].scope_spans[0].spans.len(), 1);
+
+        let spans = &scope_spans[0].spans;
+        assert_eq!(spans.len(), 2);
+
+        let span = &spans[0];
+        assert_eq!(span.name, "a");
+        assert_eq!(span.kind, 1);
+        assert_eq!(span.trace_id, vec![1u8; 16]);
+        assert_eq!(span.span_id, vec![1u8; 8]);
+        assert!(span.parent_span_id.is_empty());
+        assert_eq!(span.start_time_unix_nano, 100);
+        assert_eq!(span.end_time_unix_nano, 110);
+        assert_eq!(
+            span.status,
+            Some(Status {
+                code: 2,
+                message: "boom".to_string(),
+            })
+        );
+        assert!(span.attributes.is_empty());
+        assert_eq!(span.events.len(), 1);
+        assert_eq!(span.events[0].name, "e1");
+        assert_eq!(span.links.len(), 1);
+        assert_eq!(span.links[0].trace_id, vec![9u8; 16]);
+
+        let span = &spans[1];
+        assert_eq!(span.name, "b");
+        assert_eq!(span.parent_span_id, vec![1u8; 8]);
+        assert_eq!(span.status, Some(Status::default()));
+        assert_eq!(span.attributes, vec![KeyValue {
+            key: "k".to_string(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue("v".to_string())),
+            }),
+        }]);
+        let event_names: Vec<_> = span.events.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(event_names, vec!["e1", "e2"]);
+        assert!(span.links.is_empty());
+    }
+
+    #[test]
+    fn test_traces_from_cancelled() {
+        let token = CancellationToken::new();
+        let options = DecoderOptions::default().with_cancellation(token.clone());
+        assert!(traces_from_with_options(traces_batch(), &options).is_ok());
+
+        token.cancel();
+        let err = traces_from_with_options(traces_batch(), &options).unwrap_err();
+        assert!(matches!(err, Error::Cancelled { .. }));
+    }
+
+    #[test]
+    fn test_traces_from_over_limits() {
+        let otap_batch = traces_batch();
+        let payloads = otap_batch.payload_types().len();
+        let decode = |limits| {
+            traces_from_with_options(traces_batch(), &DecoderOptions {
+                limits,
+                ..Default::default()
+            })
+        };
+        let limits = DecodeLimits::new()
+            .with_max_rows_per_payload(3)
+            .with_max_payloads_per_batch(payloads)
+            .with_max_decode_time(Duration::from_secs(60));
+        assert!(decode(limits).is_ok());
+
+        let err = decode(limits.with_max_payloads_per_batch(payloads - 1)).unwrap_err();
+        assert!(matches!(err, Error::TooManyPayloads { .. }));
+        let err = decode(limits.with_max_rows_per_payload(2)).unwrap_err();
+        assert!(matches!(err, Error::TooManyRows { rows: 3, .. }));
+        let err = decode(limits.with_max_decode_time(Duration::ZERO)).unwrap_err();
+        assert!(matches!(err, Error::DecodeTimeExceeded { .. }));
+    }
+
+    fn with_column(rb: RecordBatch, name: &str, column: ArrayRef) -> RecordBatch {
+        let mut columns = rb.columns().to_vec();
+        columns[rb.schema().index_of(name).unwrap()] = column;
+        RecordBatch::try_new(rb.schema(), columns).unwrap()
+    }
+
+    #[test]
+    fn test_traces_from_enum_columns() {
+        // span kinds stored as a dictionary of bytes, the last one not defined by OTLP
+        let rb = spans_batch();
+        let kinds: ArrayRef = Arc::new(
+            DictionaryArray::<UInt8Type>::try_new(
+                UInt8Array::from(vec![0, 1, 2]),
+                Arc::new(UInt8Array::from(vec![1, 2, 9])),
+            )
+            .unwrap(),
+        );
+        let idx = rb.schema().index_of(consts::KIND).unwrap();
+        let mut fields = rb.schema().fields().to_vec();
+        fields[idx] = Arc::new(Field::new(consts::KIND, kinds.data_type().clone(), true));
+        let mut columns = rb.columns().to_vec();
+        columns[idx] = kinds;
+        let rb = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+        let decode = |policy| {
+            let mut otap_batch = traces_batch();
+            otap_batch.set(ArrowPayloadType::Spans, rb.clone());
+            traces_from_with_options(
+                otap_batch,
+                &DecoderOptions::default().with_enum_validation(policy),
+            )
+        };
+
+        let traces = decode(EnumValidationPolicy::Passthrough).unwrap();
+        let kinds: Vec<_> = traces
+            .resource_spans
+            .iter()
+            .flat_map(|r| &r.scope_spans[0].spans)
+            .map(|s| s.kind)
+            .collect();
+        assert_eq!(kinds, vec![1, 2, 9]);
+
+        let err = decode(EnumValidationPolicy::Strict).unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(context.row_index, Some(2));
+        assert_eq!(context.column_name.as_deref(), Some(consts::KIND));
+        assert!(matches!(
+            err,
+            Error::Decode { source, .. }
+                if matches!(*source, Error::InvalidEnumValue { name: "SpanKind", value: 9, .. })
+        ));
+    }
+
+    #[test]
+    fn test_traces_from_overflowing_ids() {
+        // the delta encoded span ids wrap around
+        let mut otap_batch = traces_batch();
+        otap_batch.set(
+            ArrowPayloadType::Spans,
+            with_column(
+                spans_batch(),
+                consts::ID,
+                Arc::new(UInt16Array::from(vec![0, u16::MAX, 1])),
+            ),
+        );
+        let err = traces_from(otap_batch).unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(context.payload_type, Some(ArrowPayloadType::Spans));
+        assert_eq!(context.row_index, Some(2));
+
+        // the parent ids of the events of the same name wrap around
+        let mut otap_batch = traces_batch();
+        otap_batch.set(
+            ArrowPayloadType::SpanEvents,
+            with_column(
+                span_events_batch(),
+                consts::PARENT_ID,
+                Arc::new(UInt16Array::from(vec![u16::MAX, 1, 0])),
+            ),
+        );
+        let err = traces_from(otap_batch).unwrap_err();
+        let context = err.context().unwrap();
+        assert_eq!(context.payload_type, Some(ArrowPayloadType::SpanEvents));
+        assert_eq!(context.row_index, Some(1));
+    }
+
+    #[test]
+    fn test_traces_from_invalid_ids() {
+        // the first span has an all-zero span id, its link a trace id of the wrong length
+        let otap_batch = || {
+            let mut otap_batch = traces_batch();
+            otap_batch.set(
+                ArrowPayloadType::Spans,
+                with_column(
+                    spans_batch(),
+                    consts::SPAN_ID,
+                    Arc::new(
+                        FixedSizeBinaryArray::try_from_iter(
+                            vec![[0u8; 8], [2u8; 8], [3u8; 8]].into_iter(),
+                        )
+                        .unwrap(),
+                    ),
+                ),
+            );
+            let span_links = span_links_batch();
+            let mut fields = span_links.schema().fields().to_vec();
+            fields[1] = Arc::new(Field::new(consts::TRACE_ID, DataType::Binary, true));
+            let mut columns = span_links.columns().to_vec();
+            columns[1] = Arc::new(BinaryArray::from(vec![&[9u8; 15][..]]));
+            otap_batch.set(
+                ArrowPayloadType::SpanLinks,
+                RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap(),
+            );
+            otap_batch
+        };
+        let decode = |policy| {
+            let options = DecoderOptions::default().with_id_validation(policy);
+            traces_from_with_report(otap_batch(), &options)
+        };
+
+        // the link trace id has the wrong length
+        for policy in [IdValidationPolicy::LengthOnly, IdValidationPolicy::Error] {
+            let err = decode(policy).unwrap_err();
+            let context = err.context().unwrap();
+            assert_eq!(context.payload_type, Some(ArrowPayloadType::SpanLinks));
+            assert!(matches!(
+                err,
+                Error::Decode { source, .. } if matches!(*source, Error::InvalidTraceId { .. })
+            ));
+        }
+
+        let (traces, report) = decode(IdValidationPolicy::ZeroFill).unwrap();
+        let spans = &traces.resource_spans[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].span_id, vec![0u8; 8]);
+        assert_eq!(spans[0].links[0].trace_id, vec![0u8; 16]);
+        assert_eq!(report.invalid_ids(ArrowPayloadType::Spans), 1);
+        assert_eq!(report.invalid_ids(ArrowPayloadType::SpanLinks), 1);
+        assert_eq!(report.total_dropped_rows(), 0);
+
+        let (traces, report) = decode(IdValidationPolicy::Drop).unwrap();
+        let spans = &traces.resource_spans[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 1);
+        // the ids of the following span are still in sync
+        assert_eq!(spans[0].name, "b");
+        assert_eq!(spans[0].attributes.len(), 1);
+        assert_eq!(spans[0].events.len(), 2);
+        assert_eq!(
+            report.dropped_rows(ArrowPayloadType::Spans, DroppedRowReason::InvalidId),
+            1
+        );
+        assert_eq!(
+            report.dropped_rows(ArrowPayloadType::SpanLinks, DroppedRowReason::InvalidId),
+            1
+        );
+
+        // the bytes decoder drops the same spans
+        let options = DecoderOptions::default().with_id_validation(IdValidationPolicy::Drop);
+        let (bytes, bytes_report) = traces_bytes_from_with_report(otap_batch(), &options).unwrap();
+        assert_eq!(bytes, traces.encode_to_vec());
+        assert_eq!(bytes_report, report);
+    }
+
+    #[test]
+    fn test_traces_from_timestamps() {
+        let decode = |policy| {
+            let options = DecoderOptions::default().with_timestamp_policy(policy);
+            traces_from_with_report(traces_batch(), &options)
+        };
+
+        // the timestamps of the batch are in order
+        let (_, report) = decode(TimestampPolicy::new(TimestampAction::Error)).unwrap();
+        assert!(report.is_empty());
+
+        // but they are long before the current time
+        let policy = |action| TimestampPolicy::new(action).with_max_past(Duration::from_secs(60));
+        let err = decode(policy(TimestampAction::Error)).unwrap_err();
+        assert!(matches!(err, Error::InvalidTimestamp { .. }));
+
+        let (traces, report) = decode(policy(TimestampAction::Flag)).unwrap();
+        assert_eq!(report.timestamp_anomalies(ArrowPayloadType::Spans), 3);
+        let span = &traces.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.start_time_unix_nano, 100);
+        assert_eq!(span.end_time_unix_nano, 110);
+
+        let (traces, report) = decode(policy(TimestampAction::Clamp)).unwrap();
+        assert_eq!(report.timestamp_anomalies(ArrowPayloadType::Spans), 3);
+        let span = &traces.resource_spans[0].scope_spans[0].spans[0];
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        assert!(span.start_time_unix_nano > now - 120_000_000_000);
+        assert!(span.start_time_unix_nano <= now);
+        assert_eq!(span.end_time_unix_nano, span.start_time_unix_nano);
+    }
+
+    #[test]
+    fn test_traces_from_unknown_columns() {
+        let otap_batch = || {
+            let spans = spans_batch();
+            let mut fields = spans.schema().fields().to_vec();
+            let mut columns = spans.columns().to_vec();
+            fields.push(Arc::new(Field::new(
+                "sampling.rate",
+                DataType::UInt32,
+                true,
+            )));
+            columns.push(Arc::new(UInt32Array::from(vec![Some(10), None, Some(30)])));
+            fields.push(Arc::new(Field::new("team", DataType::Utf8, true)));
+            columns.push(Arc::new(StringArray::from(vec!["a", "b", "c"])));
+
+            let mut otap_batch = traces_batch();
+            otap_batch.set(
+                ArrowPayloadType::Spans,
+                RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap(),
+            );
+            otap_batch
+        };
+        let decode = |policy| {
+            let options = DecoderOptions::default().with_unknown_column_policy(policy);
+            traces_from_with_options(otap_batch(), &options)
+        };
+
+        let traces = decode(UnknownColumnPolicy::Ignore).unwrap();
+        assert_eq!(traces, traces_from(traces_batch()).unwrap());
+
+        let err = decode(UnknownColumnPolicy::Error).unwrap_err();
+        assert_eq!(
+            err.context().unwrap().payload_type,
+            Some(ArrowPayloadType::Spans)
+        );
+
+        let policy = UnknownColumnPolicy::Attributes {
+            prefix: "x.".into(),
+        };
+        let traces = decode(policy.clone()).unwrap();
+        let spans = &traces.resource_spans[0].scope_spans[0].spans;
+        let attr = |key: &str, value| KeyValue {
+            key: key.into(),
+            value: Some(AnyValue { value: Some(value) }),
+        };
+        assert_eq!(spans[0].attributes, vec![
+            attr("x.sampling.rate", Value::IntValue(10)),
+            attr("x.team", Value::StringValue("a".into())),
+        ]);
+        // the extra attributes come after the ones of the attributes payload
+        assert_eq!(spans[1].attributes, vec![
+            attr("k", Value::StringValue("v".into())),
+            attr("x.team", Value::StringValue("b".into())),
+        ]);
+
+        let options = DecoderOptions::default().with_unknown_column_policy(policy);
+        let bytes = traces_bytes_from_with_options(otap_batch(), &options).unwrap();
+        assert_eq!(bytes, traces.encode_to_vec());
+    }
+}
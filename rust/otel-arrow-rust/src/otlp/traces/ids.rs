@@ -0,0 +1,99 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Validation of the trace and span ids of the spans and links, according to the
+//! [`IdValidationPolicy`] of the decoder options.
+
+use crate::error::{self, Result};
+use crate::otlp::options::IdValidationPolicy;
+use crate::otlp::report::DecodeReport;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+/// The kind of an id, which sets its expected length and whether it can be missing.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum IdKind {
+    /// The trace id of a span.
+    Trace,
+    /// The span id of a span.
+    Span,
+    /// The parent span id of a span, empty for a root span.
+    ParentSpan,
+    /// The trace id of a link.
+    LinkTrace,
+    /// The span id of a link.
+    LinkSpan,
+}
+
+impl IdKind {
+    fn len(self) -> usize {
+        match self {
+            IdKind::Trace | IdKind::LinkTrace => 16,
+            IdKind::Span | IdKind::ParentSpan | IdKind::LinkSpan => 8,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            IdKind::Trace => "trace_id",
+            IdKind::Span => "span_id",
+            IdKind::ParentSpan => "parent_span_id",
+            IdKind::LinkTrace => "link trace_id",
+            IdKind::LinkSpan => "link span_id",
+        }
+    }
+
+    fn invalid(self, idx: usize, id: &[u8]) -> error::Error {
+        let message = format!("index = {}, {} = {:?}", idx, self.name(), id);
+        match self {
+            IdKind::Trace | IdKind::LinkTrace => error::InvalidTraceIdSnafu { message }.build(),
+            IdKind::Span | IdKind::ParentSpan | IdKind::LinkSpan => {
+                error::InvalidSpanIdSnafu { message }.build()
+            }
+        }
+    }
+}
+
+/// Checks the id of the row `idx` of the payload. Returns the id to store, or `None` if the
+/// row must be dropped.
+pub(crate) fn validate_id(
+    id: Vec<u8>,
+    kind: IdKind,
+    idx: usize,
+    policy: IdValidationPolicy,
+    payload_type: ArrowPayloadType,
+    report: &mut DecodeReport,
+) -> Result<Option<Vec<u8>>> {
+    if policy == IdValidationPolicy::LengthOnly {
+        // the ids of the links and the parent span ids can be missing
+        let optional = !matches!(kind, IdKind::Trace | IdKind::Span);
+        if id.len() != kind.len() && !(optional && id.is_empty()) {
+            return Err(kind.invalid(idx, &id));
+        }
+        return Ok(Some(id));
+    }
+
+    let valid = match kind {
+        // the parent span id is either missing or a valid span id, the all-zero span id
+        // being used by some producers for root spans
+        IdKind::ParentSpan => id.is_empty() || id.len() == kind.len(),
+        _ => id.len() == kind.len() && id.iter().any(|b| *b != 0),
+    };
+    if valid {
+        return Ok(Some(id));
+    }
+
+    match policy {
+        IdValidationPolicy::LengthOnly | IdValidationPolicy::Error => Err(kind.invalid(idx, &id)),
+        IdValidationPolicy::Drop => {
+            report.record_invalid_id(payload_type);
+            Ok(None)
+        }
+        IdValidationPolicy::ZeroFill => {
+            report.record_invalid_id(payload_type);
+            match kind {
+                IdKind::ParentSpan => Ok(Some(Vec::new())),
+                _ => Ok(Some(vec![0; kind.len()])),
+            }
+        }
+    }
+}
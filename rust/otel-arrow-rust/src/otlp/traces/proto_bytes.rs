@@ -0,0 +1,557 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of OTAP traces batches straight to a serialized `ExportTraceServiceRequest`.
+//!
+//! The protobuf fields are written while iterating over the columns, so no intermediate
+//! `ResourceSpans`, `ScopeSpans` or `Span` structs are built, and attributes are encoded
+//! from the attribute stores instead of being cloned into each span. Fields are written
+//! in the same order as prost does, so the output is identical to encoding the result of
+//! [`super::traces_from`].
+
+use prost::Message;
+use prost::encoding::{WireType, encode_key, encode_varint, message};
+use snafu::OptionExt;
+
+use super::SpansArrays;
+use super::ids::{IdKind, validate_id};
+use super::related_data::RelatedData;
+use crate::arrays::NullableArrayAccessor;
+use crate::error::{self, ErrorContext, ErrorContextExt, Result};
+use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
+use crate::otlp::common::{ResourceArrays, ScopeArrays};
+use crate::otlp::extra_columns::ExtraColumns;
+use crate::otlp::filter::KeptRows;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::{DecodeReport, DroppedRowReason};
+use crate::otlp::timestamps::TimestampChecker;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::common::v1::KeyValue;
+use crate::schema::consts;
+
+// field numbers of the messages defined in opentelemetry/proto/trace/v1/trace.proto
+const REQUEST_RESOURCE_SPANS: u32 = 1;
+const RESOURCE_SPANS_RESOURCE: u32 = 1;
+const RESOURCE_SPANS_SCOPE_SPANS: u32 = 2;
+const RESOURCE_SPANS_SCHEMA_URL: u32 = 3;
+const RESOURCE_ATTRIBUTES: u32 = 1;
+const RESOURCE_DROPPED_ATTRIBUTES_COUNT: u32 = 2;
+const SCOPE_SPANS_SCOPE: u32 = 1;
+const SCOPE_SPANS_SPANS: u32 = 2;
+const SCOPE_SPANS_SCHEMA_URL: u32 = 3;
+const SCOPE_NAME: u32 = 1;
+const SCOPE_VERSION: u32 = 2;
+const SCOPE_ATTRIBUTES: u32 = 3;
+const SCOPE_DROPPED_ATTRIBUTES_COUNT: u32 = 4;
+const SPAN_TRACE_ID: u32 = 1;
+const SPAN_SPAN_ID: u32 = 2;
+const SPAN_PARENT_SPAN_ID: u32 = 4;
+const SPAN_NAME: u32 = 5;
+const SPAN_KIND: u32 = 6;
+const SPAN_START_TIME_UNIX_NANO: u32 = 7;
+const SPAN_END_TIME_UNIX_NANO: u32 = 8;
+const SPAN_ATTRIBUTES: u32 = 9;
+const SPAN_DROPPED_ATTRIBUTES_COUNT: u32 = 10;
+const SPAN_EVENTS: u32 = 11;
+const SPAN_DROPPED_EVENTS_COUNT: u32 = 12;
+const SPAN_LINKS: u32 = 13;
+const SPAN_DROPPED_LINKS_COUNT: u32 = 14;
+const SPAN_STATUS: u32 = 15;
+
+fn encode_bytes(tag: u32, value: &[u8], buf: &mut Vec<u8>) {
+    if !value.is_empty() {
+        encode_nested(tag, value, buf);
+    }
+}
+
+fn encode_nested(tag: u32, value: &[u8], buf: &mut Vec<u8>) {
+    encode_key(tag, WireType::LengthDelimited, buf);
+    encode_varint(value.len() as u64, buf);
+    buf.extend_from_slice(value);
+}
+
+fn encode_varint_field(tag: u32, value: u64, buf: &mut Vec<u8>) {
+    if value != 0 {
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(value, buf);
+    }
+}
+
+fn encode_fixed64(tag: u32, value: u64, buf: &mut Vec<u8>) {
+    if value != 0 {
+        encode_key(tag, WireType::SixtyFourBit, buf);
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn encode_attributes(tag: u32, attributes: Option<&[KeyValue]>, buf: &mut Vec<u8>) {
+    for kv in attributes.unwrap_or_default() {
+        message::encode(tag, kv, buf);
+    }
+}
+
+/// Buffers holding the messages currently being written, from the innermost to the
+/// outermost. They are reused across messages to avoid allocations.
+#[derive(Debug, Default)]
+struct Buffers {
+    request: Vec<u8>,
+    resource_spans: Vec<u8>,
+    resource_schema_url: Option<String>,
+    scope_spans: Vec<u8>,
+    scope_schema_url: Option<String>,
+    message: Vec<u8>,
+}
+
+impl Buffers {
+    /// Empties the buffers, keeping their capacity.
+    fn clear(&mut self) {
+        self.request.clear();
+        self.resource_spans.clear();
+        self.resource_schema_url = None;
+        self.scope_spans.clear();
+        self.scope_schema_url = None;
+        self.message.clear();
+    }
+
+    fn finish_scope_spans(&mut self) {
+        if let Some(schema_url) = self.scope_schema_url.take() {
+            encode_bytes(
+                SCOPE_SPANS_SCHEMA_URL,
+                schema_url.as_bytes(),
+                &mut self.scope_spans,
+            );
+            encode_nested(
+                RESOURCE_SPANS_SCOPE_SPANS,
+                &self.scope_spans,
+                &mut self.resource_spans,
+            );
+            self.scope_spans.clear();
+        }
+    }
+
+    fn finish_resource_spans(&mut self) {
+        self.finish_scope_spans();
+        if let Some(schema_url) = self.resource_schema_url.take() {
+            encode_bytes(
+                RESOURCE_SPANS_SCHEMA_URL,
+                schema_url.as_bytes(),
+                &mut self.resource_spans,
+            );
+            encode_nested(
+                REQUEST_RESOURCE_SPANS,
+                &self.resource_spans,
+                &mut self.request,
+            );
+            self.resource_spans.clear();
+        }
+    }
+}
+
+/// Converts the traces batch to a protobuf encoded `ExportTraceServiceRequest`.
+pub fn traces_bytes_from(traces_otap_batch: OtapBatch) -> Result<Vec<u8>> {
+    traces_bytes_from_with_options(traces_otap_batch, &DecoderOptions::default())
+}
+
+/// Same as [`traces_bytes_from`], using the given options to decode the batch.
+pub fn traces_bytes_from_with_options(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<Vec<u8>> {
+    traces_bytes_from_with_report(traces_otap_batch, options).map(|(request, _)| request)
+}
+
+/// Same as [`traces_bytes_from_with_options`], also returning the report of the rows dropped
+/// because of [`DecoderOptions::skip_bad_rows`].
+pub fn traces_bytes_from_with_report(
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<(Vec<u8>, DecodeReport)> {
+    let mut request = Vec::new();
+    let report = TracesBytesDecoder::new().decode_into(traces_otap_batch, options, &mut request)?;
+    Ok((request, report))
+}
+
+/// Converts traces batches to protobuf encoded `ExportTraceServiceRequest`s, reusing its
+/// buffers across the batches.
+///
+/// A gateway decoding many batches keeps a decoder and an output buffer per stream, so the
+/// buffers are allocated once and only grow to the size of the largest batch, instead of
+/// being allocated again for every batch.
+#[derive(Debug, Default)]
+pub struct TracesBytesDecoder {
+    buffers: Buffers,
+}
+
+impl TracesBytesDecoder {
+    /// Creates a decoder with empty buffers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the request of the traces batch to `out`, replacing its content but keeping
+    /// its capacity. Returns the report of the rows dropped because of
+    /// [`DecoderOptions::skip_bad_rows`]. `out` is left empty if the batch fails to decode.
+    pub fn decode_into(
+        &mut self,
+        traces_otap_batch: OtapBatch,
+        options: &DecoderOptions,
+        out: &mut Vec<u8>,
+    ) -> Result<DecodeReport> {
+        self.buffers.clear();
+        out.clear();
+        std::mem::swap(&mut self.buffers.request, out);
+        let result = write_traces(&mut self.buffers, traces_otap_batch, options);
+        std::mem::swap(&mut self.buffers.request, out);
+        if result.is_err() {
+            out.clear();
+        }
+        result
+    }
+}
+
+fn write_traces(
+    buffers: &mut Buffers,
+    traces_otap_batch: OtapBatch,
+    options: &DecoderOptions,
+) -> Result<DecodeReport> {
+    let mut prev_res_id: Option<u16> = None;
+    let mut prev_scope_id: Option<u16> = None;
+
+    let mut res_id = 0;
+    let mut scope_id = 0;
+
+    let guard = options.start_batch(&traces_otap_batch)?;
+    let rb = traces_otap_batch
+        .get(ArrowPayloadType::Spans)
+        .context(error::SpanRecordNotFoundSnafu)?;
+
+    let mut related_data = RelatedData::try_from_with_options(&traces_otap_batch, options)?;
+    let timestamps = TimestampChecker::new(options);
+    let extra_columns = ExtraColumns::try_new(rb, ArrowPayloadType::Spans, options)
+        .in_payload(ArrowPayloadType::Spans)?;
+    let kept_rows = KeptRows::try_new(&traces_otap_batch, ArrowPayloadType::Spans, options)
+        .in_payload(ArrowPayloadType::Spans)?;
+    let mut extra_attributes = Vec::new();
+
+    let resource_arrays = ResourceArrays::try_from(rb).in_payload(ArrowPayloadType::Spans)?;
+    let scope_arrays = ScopeArrays::try_from(rb).in_payload(ArrowPayloadType::Spans)?;
+    let spans_arrays = SpansArrays::try_from(rb).in_payload(ArrowPayloadType::Spans)?;
+
+    for idx in 0..rb.num_rows() {
+        guard.check(idx)?;
+        let res_delta_id = resource_arrays.id.value_at(idx).unwrap_or_default();
+        res_id = add_delta(res_id, res_delta_id).error_context(|| {
+            ErrorContext::default()
+                .payload(ArrowPayloadType::Spans)
+                .row(idx)
+                .column(consts::RESOURCE)
+        })?;
+
+        if prev_res_id != Some(res_id) {
+            // new resource id
+            prev_res_id = Some(res_id);
+            prev_scope_id = None;
+            buffers.finish_resource_spans();
+
+            let attrs = match resource_arrays.id.value_at(idx) {
+                Some(res_id) => related_data
+                    .res_attr_map_store
+                    .as_mut()
+                    .map(|store| store.attribute_by_delta_id(res_id, &mut related_data.report))
+                    .transpose()?
+                    .flatten(),
+                None => None,
+            };
+            buffers.message.clear();
+            encode_attributes(RESOURCE_ATTRIBUTES, attrs, &mut buffers.message);
+            encode_varint_field(
+                RESOURCE_DROPPED_ATTRIBUTES_COUNT,
+                resource_arrays
+                    .dropped_attributes_count
+                    .value_at_or_default(idx)
+                    .into(),
+                &mut buffers.message,
+            );
+            encode_nested(
+                RESOURCE_SPANS_RESOURCE,
+                &buffers.message,
+                &mut buffers.resource_spans,
+            );
+
+            buffers.resource_schema_url =
+                Some(resource_arrays.schema_url.value_at(idx).unwrap_or_default());
+        }
+
+        let scope_delta_id_opt = scope_arrays.id.value_at(idx);
+        scope_id =
+            add_delta(scope_id, scope_delta_id_opt.unwrap_or_default()).error_context(|| {
+                ErrorContext::default()
+                    .payload(ArrowPayloadType::Spans)
+                    .row(idx)
+                    .column(consts::SCOPE)
+            })?;
+
+        if prev_scope_id != Some(scope_id) {
+            prev_scope_id = Some(scope_id);
+            buffers.finish_scope_spans();
+
+            let attrs = match scope_delta_id_opt {
+                Some(scope_id) => related_data
+                    .scope_attr_map_store
+                    .as_mut()
+                    .map(|store| store.attribute_by_delta_id(scope_id, &mut related_data.report))
+                    .transpose()?
+                    .flatten(),
+                None => None,
+            };
+            buffers.message.clear();
+            encode_bytes(
+                SCOPE_NAME,
+                scope_arrays
+                    .name
+                    .value_at(idx)
+                    .unwrap_or_default()
+                    .as_bytes(),
+                &mut buffers.message,
+            );
+            encode_bytes(
+                SCOPE_VERSION,
+                scope_arrays.version.value_at_or_default(idx).as_bytes(),
+                &mut buffers.message,
+            );
+            encode_attributes(SCOPE_ATTRIBUTES, attrs, &mut buffers.message);
+            encode_varint_field(
+                SCOPE_DROPPED_ATTRIBUTES_COUNT,
+                scope_arrays
+                    .dropped_attributes_count
+                    .value_at_or_default(idx)
+                    .into(),
+                &mut buffers.message,
+            );
+            encode_nested(
+                SCOPE_SPANS_SCOPE,
+                &buffers.message,
+                &mut buffers.scope_spans,
+            );
+
+            buffers.scope_schema_url =
+                Some(spans_arrays.schema_url.value_at(idx).unwrap_or_default());
+        }
+
+        if let Some(reason) = kept_rows.dropped(idx) {
+            // the id of the span is still decoded to keep the delta encoded ids in sync
+            if let Some(delta_id) = spans_arrays.id.value_at(idx) {
+                let _ = related_data
+                    .span_id_from_delta(delta_id)
+                    .error_context(|| {
+                        ErrorContext::default()
+                            .payload(ArrowPayloadType::Spans)
+                            .row(idx)
+                            .column(consts::ID)
+                    })?;
+            }
+            related_data.report.record(ArrowPayloadType::Spans, reason);
+            continue;
+        }
+
+        let span = &mut buffers.message;
+        span.clear();
+
+        let mut validate = |id, kind| {
+            validate_id(
+                id,
+                kind,
+                idx,
+                options.id_validation,
+                ArrowPayloadType::Spans,
+                &mut related_data.report,
+            )
+        };
+        let trace_id = validate(
+            spans_arrays.trace_id.value_at_or_default(idx),
+            IdKind::Trace,
+        )?;
+        let span_id = validate(spans_arrays.span_id.value_at_or_default(idx), IdKind::Span)?;
+        let parent_span_id = validate(
+            spans_arrays.parent_span_id.value_at_or_default(idx),
+            IdKind::ParentSpan,
+        )?;
+        // the rest of the row is still decoded to keep the delta encoded ids in sync
+        let dropped = trace_id.is_none() || span_id.is_none() || parent_span_id.is_none();
+        encode_bytes(SPAN_TRACE_ID, &trace_id.unwrap_or_default(), span);
+        encode_bytes(SPAN_SPAN_ID, &span_id.unwrap_or_default(), span);
+        encode_bytes(
+            SPAN_PARENT_SPAN_ID,
+            &parent_span_id.unwrap_or_default(),
+            span,
+        );
+
+        encode_bytes(
+            SPAN_NAME,
+            spans_arrays.name.value_at_or_default(idx).as_bytes(),
+            span,
+        );
+        // enums are encoded as sign extended varints, same as int32
+        encode_varint_field(
+            SPAN_KIND,
+            i64::from(spans_arrays.kind_at(idx, options.enum_validation)?) as u64,
+            span,
+        );
+
+        // the duration column holds nanoseconds even though it is typed as milliseconds,
+        // same as the encoder on the Go side.
+        let start_time_unix_nano = spans_arrays.start_time_unix_nano.value_at_or_default(idx);
+        let duration = spans_arrays
+            .duration_time_unix_nano
+            .value_at_or_default(idx);
+        let mut start = start_time_unix_nano as u64;
+        let mut end = (start_time_unix_nano + duration) as u64;
+        timestamps.check_range(
+            &mut start,
+            &mut end,
+            idx,
+            ArrowPayloadType::Spans,
+            &mut related_data.report,
+        )?;
+        encode_fixed64(SPAN_START_TIME_UNIX_NANO, start, span);
+        encode_fixed64(SPAN_END_TIME_UNIX_NANO, end, span);
+
+        let span_id = spans_arrays
+            .id
+            .value_at(idx)
+            .map(|delta_id| related_data.span_id_from_delta(delta_id))
+            .transpose()
+            .error_context(|| {
+                ErrorContext::default()
+                    .payload(ArrowPayloadType::Spans)
+                    .row(idx)
+                    .column(consts::ID)
+            })?;
+        let attrs = span_id.and_then(|span_id| {
+            related_data
+                .span_attr_map_store
+                .as_ref()
+                .and_then(|store| store.attribute_by_id(span_id))
+        });
+        encode_attributes(SPAN_ATTRIBUTES, attrs, span);
+        extra_attributes.clear();
+        extra_columns.append_attributes(idx, &mut extra_attributes);
+        encode_attributes(SPAN_ATTRIBUTES, Some(&extra_attributes), span);
+        encode_varint_field(
+            SPAN_DROPPED_ATTRIBUTES_COUNT,
+            spans_arrays
+                .dropped_attributes_count
+                .value_at_or_default(idx)
+                .into(),
+            span,
+        );
+
+        if let Some(span_id) = span_id {
+            for event in related_data.span_events_store.take_events_by_id(span_id) {
+                message::encode(SPAN_EVENTS, &event, span);
+            }
+        }
+        encode_varint_field(
+            SPAN_DROPPED_EVENTS_COUNT,
+            spans_arrays
+                .dropped_events_count
+                .value_at_or_default(idx)
+                .into(),
+            span,
+        );
+
+        if let Some(span_id) = span_id {
+            for link in related_data.span_links_store.take_links_by_id(span_id) {
+                message::encode(SPAN_LINKS, &link, span);
+            }
+        }
+        encode_varint_field(
+            SPAN_DROPPED_LINKS_COUNT,
+            spans_arrays
+                .dropped_links_count
+                .value_at_or_default(idx)
+                .into(),
+            span,
+        );
+
+        if let Some(status) = spans_arrays.status_at(idx, options.enum_validation)? {
+            encode_key(SPAN_STATUS, WireType::LengthDelimited, span);
+            encode_varint(status.encoded_len() as u64, span);
+            status.encode_raw(span);
+        }
+
+        if dropped {
+            related_data
+                .report
+                .record(ArrowPayloadType::Spans, DroppedRowReason::InvalidId);
+        } else {
+            encode_nested(SCOPE_SPANS_SPANS, span, &mut buffers.scope_spans);
+        }
+    }
+
+    buffers.finish_resource_spans();
+    Ok(related_data.report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use arrow::array::{RecordBatch, UInt16Array};
+
+    use crate::otlp::traces::test::traces_batch;
+    use crate::otlp::traces::traces_from;
+
+    #[test]
+    fn test_traces_bytes_from() {
+        let expected = traces_from(traces_batch()).unwrap().encode_to_vec();
+        let bytes = traces_bytes_from(traces_batch()).unwrap();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_traces_bytes_from_empty_batch() {
+        let mut otap_batch = traces_batch();
+        let spans = otap_batch.get(ArrowPayloadType::Spans).unwrap().slice(0, 0);
+        otap_batch.set(ArrowPayloadType::Spans, spans);
+        assert!(traces_bytes_from(otap_batch).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_traces_bytes_decoder_reuse() {
+        let expected = traces_bytes_from(traces_batch()).unwrap();
+        let mut decoder = TracesBytesDecoder::new();
+        let mut out = Vec::new();
+        for _ in 0..2 {
+            let _ = decoder
+                .decode_into(traces_batch(), &DecoderOptions::default(), &mut out)
+                .unwrap();
+            assert_eq!(out, expected);
+        }
+
+        // a batch failing after its first spans leaves nothing behind for the next one
+        let mut otap_batch = traces_batch();
+        let spans = otap_batch.get(ArrowPayloadType::Spans).unwrap().clone();
+        let mut columns = spans.columns().to_vec();
+        columns[spans.schema().index_of(consts::ID).unwrap()] =
+            Arc::new(UInt16Array::from(vec![0, u16::MAX, 1]));
+        otap_batch.set(
+            ArrowPayloadType::Spans,
+            RecordBatch::try_new(spans.schema(), columns).unwrap(),
+        );
+        assert!(
+            decoder
+                .decode_into(otap_batch, &DecoderOptions::default(), &mut out)
+                .is_err()
+        );
+        assert!(out.is_empty());
+        let _ = decoder
+            .decode_into(traces_batch(), &DecoderOptions::default(), &mut out)
+            .unwrap();
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error::{self, ErrorContextExt};
+use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
+use crate::otlp::attributes::store::{Attribute16Store, Attribute32Store};
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::otlp::traces::span_event::SpanEventsStore;
+use crate::otlp::traces::span_link::SpanLinksStore;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+pub struct RelatedData {
+    pub(crate) span_id: u16,
+
+    pub(crate) res_attr_map_store: Option<Attribute16Store>,
+    pub(crate) scope_attr_map_store: Option<Attribute16Store>,
+    pub(crate) span_attr_map_store: Option<Attribute16Store>,
+
+    pub(crate) span_events_store: SpanEventsStore,
+    pub(crate) span_links_store: SpanLinksStore,
+
+    pub(crate) report: DecodeReport,
+}
+
+impl<'a> TryFrom<&'a OtapBatch> for RelatedData {
+    type Error = error::Error;
+
+    fn try_from(otap_batch: &'a OtapBatch) -> error::Result<Self> {
+        Self::try_from_with_options(otap_batch, &DecoderOptions::default())
+    }
+}
+
+impl RelatedData {
+    pub fn try_from_with_options(
+        otap_batch: &OtapBatch,
+        options: &DecoderOptions,
+    ) -> error::Result<Self> {
+        let mut report = DecodeReport::default();
+        let mut span_event_attr_map_store = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::SpanEventAttrs,
+            options,
+            &mut report,
+        )?
+        .unwrap_or_default();
+        let mut span_link_attr_map_store = Attribute32Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::SpanLinkAttrs,
+            options,
+            &mut report,
+        )?
+        .unwrap_or_default();
+
+        Ok(Self {
+            span_id: 0,
+            res_attr_map_store: Attribute16Store::from_payload(
+                otap_batch,
+                ArrowPayloadType::ResourceAttrs,
+                options,
+                &mut report,
+            )?,
+            scope_attr_map_store: Attribute16Store::from_payload(
+                otap_batch,
+                ArrowPayloadType::ScopeAttrs,
+                options,
+                &mut report,
+            )?,
+            span_attr_map_store: Attribute16Store::from_payload(
+                otap_batch,
+                ArrowPayloadType::SpanAttrs,
+                options,
+                &mut report,
+            )?,
+            span_events_store: otap_batch
+                .get(ArrowPayloadType::SpanEvents)
+                .map(|rb| {
+                    SpanEventsStore::try_from(rb, &mut span_event_attr_map_store, &mut report)
+                        .in_payload(ArrowPayloadType::SpanEvents)
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            span_links_store: otap_batch
+                .get(ArrowPayloadType::SpanLinks)
+                .map(|rb| {
+                    SpanLinksStore::try_from(
+                        rb,
+                        &mut span_link_attr_map_store,
+                        options.id_validation,
+                        &mut report,
+                    )
+                    .in_payload(ArrowPayloadType::SpanLinks)
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            report,
+        })
+    }
+
+    pub fn span_id_from_delta(&mut self, delta: u16) -> error::Result<u16> {
+        self.span_id = add_delta(self.span_id, delta)?;
+        Ok(self.span_id)
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::arrays::{
+    ColumnAccessor, NullableArrayAccessor, StringArrayAccessor, get_timestamp_nanosecond_array_opt,
+    get_u16_array, get_u32_array_opt,
+};
+use crate::error::{self, ErrorContext, ErrorContextExt};
+use crate::otlp::attributes::add_delta;
+use crate::otlp::attributes::store::Attribute32Store;
+use crate::otlp::metrics::AppendAndGet;
+use crate::otlp::report::DecodeReport;
+use crate::proto::opentelemetry::trace::v1::span::Event;
+use crate::schema::consts;
+use arrow::array::RecordBatch;
+use std::collections::HashMap;
+
+/// Span events of a batch, indexed by the id of the span they belong to.
+#[derive(Default)]
+pub struct SpanEventsStore {
+    events_by_ids: HashMap<u16, Vec<Event>>,
+}
+
+impl SpanEventsStore {
+    /// Removes the events of the span with given id from the store and returns them.
+    pub fn take_events_by_id(&mut self, id: u16) -> Vec<Event> {
+        self.events_by_ids.remove(&id).unwrap_or_default()
+    }
+
+    pub fn try_from(
+        rb: &RecordBatch,
+        attr_store: &mut Attribute32Store,
+        report: &mut DecodeReport,
+    ) -> error::Result<Self> {
+        let mut events_store = Self::default();
+        let mut parent_id_decoder = EventParentIdDecoder::default();
+
+        let id_arr_opt = get_u32_array_opt(rb, consts::ID)?;
+        let parent_id_arr = get_u16_array(rb, consts::PARENT_ID)?;
+        let time_unix_nano_arr = get_timestamp_nanosecond_array_opt(rb, consts::TIME_UNIX_NANO)?;
+        let name_arr = StringArrayAccessor::try_new_for_column(rb, consts::NAME)?;
+        let dropped_attributes_count_arr = get_u32_array_opt(rb, consts::DROPPED_ATTRIBUTES_COUNT)?;
+
+        for idx in 0..rb.num_rows() {
+            let name = name_arr.value_at_or_default(idx);
+            let parent_id = parent_id_decoder
+                .decode(parent_id_arr.value_at_or_default(idx), &name)
+                .error_context(|| ErrorContext::default().row(idx).column(consts::PARENT_ID))?;
+
+            let current_event = events_store
+                .events_by_ids
+                .entry(parent_id)
+                .or_default()
+                .append_and_get();
+
+            current_event.time_unix_nano = time_unix_nano_arr.value_at_or_default(idx) as u64;
+            current_event.dropped_attributes_count =
+                dropped_attributes_count_arr.value_at_or_default(idx);
+
+            if let Some(id) = id_arr_opt.value_at(idx) {
+                if let Some(attrs) = attr_store.attribute_by_delta_id(id, report)? {
+                    current_event.attributes = attrs.to_vec();
+                }
+            }
+            current_event.name = name;
+        }
+
+        Ok(events_store)
+    }
+}
+
+/// Decodes the parent ids of span events. The parent id is delta encoded from the
+/// previous parent id within a group of consecutive events that have the same name.
+#[derive(Default)]
+struct EventParentIdDecoder {
+    prev_parent_id: u16,
+    prev_name: Option<String>,
+}
+
+impl EventParentIdDecoder {
+    fn decode(&mut self, parent_id_or_delta: u16, name: &str) -> error::Result<u16> {
+        if self.prev_name.as_deref() == Some(name) {
+            self.prev_parent_id = add_delta(self.prev_parent_id, parent_id_or_delta)?;
+        } else {
+            self.prev_name = Some(name.to_string());
+            self.prev_parent_id = parent_id_or_delta;
+        }
+        Ok(self.prev_parent_id)
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::arrays::{
+    ByteArrayAccessor, ColumnAccessor, NullableArrayAccessor, StringArrayAccessor, get_u16_array,
+    get_u32_array_opt,
+};
+use crate::error::{self, ErrorContext, ErrorContextExt};
+use crate::otlp::attributes::add_delta;
+use crate::otlp::attributes::store::Attribute32Store;
+use crate::otlp::metrics::AppendAndGet;
+use crate::otlp::options::IdValidationPolicy;
+use crate::otlp::report::{DecodeReport, DroppedRowReason};
+use crate::otlp::traces::ids::{IdKind, validate_id};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::trace::v1::span::Link;
+use crate::schema::consts;
+use arrow::array::RecordBatch;
+use std::collections::HashMap;
+
+/// Span links of a batch, indexed by the id of the span they belong to.
+#[derive(Default)]
+pub struct SpanLinksStore {
+    links_by_ids: HashMap<u16, Vec<Link>>,
+}
+
+impl SpanLinksStore {
+    /// Removes the links of the span with given id from the store and returns them.
+    pub fn take_links_by_id(&mut self, id: u16) -> Vec<Link> {
+        self.links_by_ids.remove(&id).unwrap_or_default()
+    }
+
+    pub fn try_from(
+        rb: &RecordBatch,
+        attr_store: &mut Attribute32Store,
+        policy: IdValidationPolicy,
+        report: &mut DecodeReport,
+    ) -> error::Result<Self> {
+        let mut links_store = Self::default();
+        let mut parent_id_decoder = LinkParentIdDecoder::default();
+
+        let id_arr_opt = get_u32_array_opt(rb, consts::ID)?;
+        let parent_id_arr = get_u16_array(rb, consts::PARENT_ID)?;
+        let trace_id_arr = ByteArrayAccessor::try_new_for_column_opt(rb, consts::TRACE_ID)?;
+        let span_id_arr = ByteArrayAccessor::try_new_for_column_opt(rb, consts::SPAN_ID)?;
+        let trace_state_arr = StringArrayAccessor::try_new_for_column_opt(rb, consts::TRACE_STATE)?;
+        let flags_arr = get_u32_array_opt(rb, consts::FLAGS)?;
+        let dropped_attributes_count_arr = get_u32_array_opt(rb, consts::DROPPED_ATTRIBUTES_COUNT)?;
+
+        for idx in 0..rb.num_rows() {
+            let trace_id = trace_id_arr.value_at(idx);
+            let parent_id = parent_id_decoder
+                .decode(parent_id_arr.value_at_or_default(idx), &trace_id)
+                .error_context(|| ErrorContext::default().row(idx).column(consts::PARENT_ID))?;
+
+            let current_link = links_store
+                .links_by_ids
+                .entry(parent_id)
+                .or_default()
+                .append_and_get();
+
+            let mut validate =
+                |id, kind| validate_id(id, kind, idx, policy, ArrowPayloadType::SpanLinks, report);
+            let trace_id = validate(trace_id.unwrap_or_default(), IdKind::LinkTrace)?;
+            let span_id = validate(span_id_arr.value_at_or_default(idx), IdKind::LinkSpan)?;
+            // the rest of the row is still decoded to keep the delta encoded ids in sync
+            let dropped = trace_id.is_none() || span_id.is_none();
+            current_link.trace_id = trace_id.unwrap_or_default();
+            current_link.span_id = span_id.unwrap_or_default();
+
+            current_link.trace_state = trace_state_arr.value_at_or_default(idx);
+            current_link.flags = flags_arr.value_at_or_default(idx);
+            current_link.dropped_attributes_count =
+                dropped_attributes_count_arr.value_at_or_default(idx);
+
+            if let Some(id) = id_arr_opt.value_at(idx) {
+                if let Some(attrs) = attr_store.attribute_by_delta_id(id, report)? {
+                    current_link.attributes = attrs.to_vec();
+                }
+            }
+
+            if dropped {
+                let _ = links_store
+                    .links_by_ids
+                    .get_mut(&parent_id)
+                    .and_then(|links| links.pop());
+                report.record(ArrowPayloadType::SpanLinks, DroppedRowReason::InvalidId);
+            }
+        }
+
+        Ok(links_store)
+    }
+}
+
+/// Decodes the parent ids of span links. The parent id is delta encoded from the
+/// previous parent id within a group of consecutive links that have the same trace id.
+#[derive(Default)]
+struct LinkParentIdDecoder {
+    prev_parent_id: u16,
+    prev_trace_id: Option<Option<Vec<u8>>>,
+}
+
+impl LinkParentIdDecoder {
+    fn decode(
+        &mut self,
+        parent_id_or_delta: u16,
+        trace_id: &Option<Vec<u8>>,
+    ) -> error::Result<u16> {
+        if self.prev_trace_id.as_ref() == Some(trace_id) {
+            self.prev_parent_id = add_delta(self.prev_parent_id, parent_id_or_delta)?;
+        } else {
+            self.prev_trace_id = Some(trace_id.clone());
+            self.prev_parent_id = parent_id_or_delta;
+        }
+        Ok(self.prev_parent_id)
+    }
+}
@@ -0,0 +1,230 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Streamed decoding of OTAP batches: the records are handed to a [`RecordVisitor`] as soon
+//! as they are reconstructed instead of being collected into an export request, e.g. to
+//! push them to a bounded queue without holding the whole request in memory.
+//!
+//! The records are visited in the order of the batch. Each resource and scope is announced
+//! once, before the records that belong to it. The `decode_*_with` functions take a
+//! closure receiving every record along with its resource and scope. To stop a decoding
+//! early, cancel the token of the
+//! [`DecoderOptions`](crate::otlp::options::DecoderOptions).
+
+use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+use crate::proto::opentelemetry::collector::metrics::v1::ExportMetricsServiceRequest;
+use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+use crate::proto::opentelemetry::common::v1::InstrumentationScope;
+use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+use crate::proto::opentelemetry::metrics::v1::{Metric, ResourceMetrics, ScopeMetrics};
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+/// Receives the records of a batch as they are decoded.
+pub trait RecordVisitor<R> {
+    /// Starts a new resource, the following scopes belong to it.
+    fn visit_resource(&mut self, resource: Resource, schema_url: String);
+
+    /// Starts a new scope of the current resource, the following records belong to it.
+    fn visit_scope(&mut self, scope: InstrumentationScope, schema_url: String);
+
+    /// Receives a record of the current scope.
+    fn visit_record(&mut self, record: R);
+}
+
+/// Visitor passing every record to a closure along with its resource and scope.
+pub(crate) struct FnVisitor<F> {
+    resource: Resource,
+    scope: InstrumentationScope,
+    visit: F,
+}
+
+impl<F> FnVisitor<F> {
+    pub(crate) fn new(visit: F) -> Self {
+        Self {
+            resource: Resource::default(),
+            scope: InstrumentationScope::default(),
+            visit,
+        }
+    }
+}
+
+impl<R, F> RecordVisitor<R> for FnVisitor<F>
+where
+    F: FnMut(&Resource, &InstrumentationScope, R),
+{
+    fn visit_resource(&mut self, resource: Resource, _schema_url: String) {
+        self.resource = resource;
+    }
+
+    fn visit_scope(&mut self, scope: InstrumentationScope, _schema_url: String) {
+        self.scope = scope;
+    }
+
+    fn visit_record(&mut self, record: R) {
+        (self.visit)(&self.resource, &self.scope, record)
+    }
+}
+
+/// Implements the visitor collecting the records into the export request of their signal.
+macro_rules! impl_request_visitor {
+    ($request:ty, $record:ty, $resources:ident, $resource:ident, $scopes:ident, $scope:ident, $records:ident, $signal:literal) => {
+        impl RecordVisitor<$record> for $request {
+            fn visit_resource(&mut self, resource: Resource, schema_url: String) {
+                self.$resources.push($resource {
+                    resource: Some(resource),
+                    schema_url,
+                    ..Default::default()
+                });
+            }
+
+            fn visit_scope(&mut self, scope: InstrumentationScope, schema_url: String) {
+                // safety: the decoder visits a resource before its scopes
+                let resource = self.$resources.last_mut().expect(concat!(
+                    "a resource ",
+                    $signal,
+                    " was visited"
+                ));
+                resource.$scopes.push($scope {
+                    scope: Some(scope),
+                    schema_url,
+                    ..Default::default()
+                });
+            }
+
+            fn visit_record(&mut self, record: $record) {
+                // safety: the decoder visits a resource and a scope before their records
+                self.$resources
+                    .last_mut()
+                    .and_then(|resource| resource.$scopes.last_mut())
+                    .expect(concat!("a scope ", $signal, " was visited"))
+                    .$records
+                    .push(record);
+            }
+        }
+    };
+}
+
+impl_request_visitor!(
+    ExportLogsServiceRequest,
+    LogRecord,
+    resource_logs,
+    ResourceLogs,
+    scope_logs,
+    ScopeLogs,
+    log_records,
+    "logs"
+);
+impl_request_visitor!(
+    ExportMetricsServiceRequest,
+    Metric,
+    resource_metrics,
+    ResourceMetrics,
+    scope_metrics,
+    ScopeMetrics,
+    metrics,
+    "metrics"
+);
+impl_request_visitor!(
+    ExportTraceServiceRequest,
+    Span,
+    resource_spans,
+    ResourceSpans,
+    scope_spans,
+    ScopeSpans,
+    spans,
+    "spans"
+);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::TracesProducer;
+    use crate::otlp::traces::{decode_spans_with, traces_from};
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+
+    fn span(name: &str, id: u8) -> Span {
+        Span {
+            trace_id: vec![1; 16],
+            span_id: vec![id; 8],
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_decode_spans_with() {
+        let resource = |service: &str| Resource {
+            attributes: vec![KeyValue {
+                key: "service.name".into(),
+                value: Some(AnyValue {
+                    value: Some(Value::StringValue(service.into())),
+                }),
+            }],
+            ..Default::default()
+        };
+        let scope_spans = |scope: &str, spans: Vec<Span>| ScopeSpans {
+            scope: Some(InstrumentationScope {
+                name: scope.into(),
+                ..Default::default()
+            }),
+            spans,
+            ..Default::default()
+        };
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![
+                ResourceSpans {
+                    resource: Some(resource("a")),
+                    scope_spans: vec![
+                        scope_spans("s0", vec![span("x", 1), span("y", 2)]),
+                        scope_spans("s1", vec![span("z", 3)]),
+                    ],
+                    ..Default::default()
+                },
+                ResourceSpans {
+                    resource: Some(resource("b")),
+                    scope_spans: vec![scope_spans("s0", vec![span("w", 4)])],
+                    ..Default::default()
+                },
+            ],
+        };
+        let producer = TracesProducer::new();
+
+        let mut visited = Vec::new();
+        let report = decode_spans_with(
+            producer.produce(&request).unwrap(),
+            &Default::default(),
+            |resource, scope, span| {
+                let service = match &resource.attributes[0].value {
+                    Some(AnyValue {
+                        value: Some(Value::StringValue(service)),
+                    }) => service.clone(),
+                    _ => String::new(),
+                };
+                visited.push((service, scope.name.clone(), span.name));
+            },
+        )
+        .unwrap();
+        assert_eq!(report, Default::default());
+        let visited: Vec<_> = visited
+            .iter()
+            .map(|(service, scope, span)| (service.as_str(), scope.as_str(), span.as_str()))
+            .collect();
+        assert_eq!(visited, vec![
+            ("a", "s0", "x"),
+            ("a", "s0", "y"),
+            ("a", "s1", "z"),
+            ("b", "s0", "w")
+        ]);
+
+        // the request built by the request visitor has the same resources and scopes
+        let decoded = traces_from(producer.produce(&request).unwrap()).unwrap();
+        assert_eq!(decoded.resource_spans.len(), 2);
+        assert_eq!(decoded.resource_spans[0].scope_spans.len(), 2);
+        assert_eq!(decoded.resource_spans[0].scope_spans[1].spans, vec![span(
+            "z", 3
+        )]);
+    }
+}
@@ -0,0 +1,271 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pipelines decoding the `BatchArrowRecords` of a stream, transforming the decoded requests
+//! and re-encoding them, so collector-like components can be assembled from the crate.
+//!
+//! A [`Pipeline`] runs three stages on every batch:
+//!
+//! 1. the batch is decoded by the [`Consumer`] of the pipeline. The filtering and redaction
+//!    configured in its [`DecoderOptions`], i.e. the [`RecordFilter`] and the
+//!    [`AttributeHook`], are applied while the batch is decoded, in the single pass the
+//!    decoder makes over the attribute payloads;
+//! 2. the [`Transform`]s of the pipeline are applied to the decoded request in order, e.g.
+//!    the [`ResourceEnricher`](enrich::ResourceEnricher) adding attributes to the resources;
+//! 3. the request is re-encoded in the [`OutputFormat`] of the pipeline.
+//!
+//! The stages keep their state across the batches: the consumer its streams and the
+//! buffers of the pool of its options, and the producers of the pipeline their sorters.
+//!
+//! [`RecordFilter`]: crate::otlp::filter::RecordFilter
+//! [`AttributeHook`]: crate::otlp::options::AttributeHook
+
+pub mod enrich;
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::decode::decoder::{Consumer, ExportRequest};
+use crate::encode::split::to_batch_arrow_records;
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::error::Result;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+
+/// Transformation of the requests decoded by a pipeline. It is implemented for closures
+/// taking the request.
+pub trait Transform: Send + Sync {
+    /// Transforms the request in place. An error fails the batch the request was decoded
+    /// from.
+    fn transform(&self, request: &mut ExportRequest) -> Result<()>;
+}
+
+impl<F> Transform for F
+where
+    F: Fn(&mut ExportRequest) -> Result<()> + Send + Sync,
+{
+    fn transform(&self, request: &mut ExportRequest) -> Result<()> {
+        self(request)
+    }
+}
+
+impl fmt::Debug for dyn Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Transform")
+    }
+}
+
+/// Format the requests are re-encoded in by a pipeline.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// Re-encode the requests as OTAP `BatchArrowRecords`.
+    #[default]
+    Otap,
+    /// Return the decoded OTLP requests.
+    Otlp,
+}
+
+/// Request re-encoded by a pipeline.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PipelineOutput {
+    /// The request as a `BatchArrowRecords`, with the batch id and the headers of the batch
+    /// it was decoded from.
+    Otap(BatchArrowRecords),
+    /// The OTLP request.
+    Otlp(ExportRequest),
+}
+
+/// Decodes, transforms and re-encodes the batches of a stream, see the module
+/// documentation.
+///
+/// The batches must be processed in the order they were received on their stream, as the
+/// consumer of the pipeline keeps the state of the stream.
+#[derive(Default)]
+pub struct Pipeline {
+    consumer: Consumer,
+    transforms: Vec<Arc<dyn Transform>>,
+    output: OutputFormat,
+    logs_producer: LogsProducer,
+    metrics_producer: MetricsProducer,
+    traces_producer: TracesProducer,
+}
+
+impl Pipeline {
+    /// Creates a pipeline decoding the batches with the default options, without any
+    /// transform, and re-encoding them as `BatchArrowRecords`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the consumer decoding the batches, e.g. one with a payload registry.
+    #[must_use]
+    pub fn with_consumer(mut self, consumer: Consumer) -> Self {
+        self.consumer = consumer;
+        self
+    }
+
+    /// Decodes the batches with a consumer using the given options.
+    #[must_use]
+    pub fn with_options(self, options: DecoderOptions) -> Self {
+        self.with_consumer(Consumer::with_options(options))
+    }
+
+    /// Appends a transform, applied after the ones already added.
+    #[must_use]
+    pub fn with_transform(mut self, transform: impl Transform + 'static) -> Self {
+        self.transforms.push(Arc::new(transform));
+        self
+    }
+
+    /// Sets the format the requests are re-encoded in.
+    #[must_use]
+    pub fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Sets the producers re-encoding the requests as OTAP batches, e.g. ones with custom
+    /// sorters.
+    #[must_use]
+    pub fn with_producers(
+        mut self,
+        logs_producer: LogsProducer,
+        metrics_producer: MetricsProducer,
+        traces_producer: TracesProducer,
+    ) -> Self {
+        self.logs_producer = logs_producer;
+        self.metrics_producer = metrics_producer;
+        self.traces_producer = traces_producer;
+        self
+    }
+
+    /// Returns the consumer decoding the batches.
+    #[must_use]
+    pub fn consumer(&self) -> &Consumer {
+        &self.consumer
+    }
+
+    /// Returns the report of the rows dropped since the last call, see
+    /// [`Consumer::take_decode_report`].
+    pub fn take_decode_report(&mut self) -> DecodeReport {
+        self.consumer.take_decode_report()
+    }
+
+    /// Decodes the batch, transforms the decoded request and re-encodes it.
+    pub fn process(&mut self, records: &mut BatchArrowRecords) -> Result<PipelineOutput> {
+        let batch_id = records.batch_id;
+        let mut request = self.consumer.consume_batches(records)?;
+        for transform in &self.transforms {
+            transform.transform(&mut request)?;
+        }
+        let otap_batch = match (self.output, &request) {
+            (OutputFormat::Otlp, _) => return Ok(PipelineOutput::Otlp(request)),
+            (OutputFormat::Otap, ExportRequest::Logs(request)) => {
+                self.logs_producer.produce(request)?
+            }
+            (OutputFormat::Otap, ExportRequest::Metrics(request)) => {
+                self.metrics_producer.produce(request)?
+            }
+            (OutputFormat::Otap, ExportRequest::Traces(request)) => {
+                self.traces_producer.produce(request)?
+            }
+        };
+        let mut output = to_batch_arrow_records(&otap_batch, batch_id)?;
+        // the metadata of the batch, e.g. its tenant, is passed through
+        output.headers = records.headers.clone();
+        Ok(PipelineOutput::Otap(output))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::headers::BatchHeaders;
+    use crate::otlp::options::AttributeAction;
+    use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, InstrumentationScope, KeyValue};
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+    use crate::proto::opentelemetry::resource::v1::Resource;
+
+    fn attr(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.into(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue(value.into())),
+            }),
+        }
+    }
+
+    fn request() -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(Resource::default()),
+                scope_logs: vec![ScopeLogs {
+                    scope: Some(InstrumentationScope::default()),
+                    log_records: vec![LogRecord {
+                        time_unix_nano: 1,
+                        attributes: vec![attr("user.email", "a@b.c"), attr("k", "v")],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let options = DecoderOptions::default().with_attribute_hook(|key: &str, _: &Value| {
+            if key == "user.email" {
+                AttributeAction::Drop
+            } else {
+                AttributeAction::Keep
+            }
+        });
+        let enrich = |request: &mut ExportRequest| {
+            if let ExportRequest::Logs(request) = request {
+                for resource_logs in &mut request.resource_logs {
+                    let resource = resource_logs.resource.get_or_insert_with(Default::default);
+                    resource.attributes.push(attr("pipeline", "test"));
+                }
+            }
+            Ok(())
+        };
+        let mut pipeline = Pipeline::new().with_options(options).with_transform(enrich);
+
+        let otap_batch = LogsProducer::new().produce(&request()).unwrap();
+        let mut records = to_batch_arrow_records(&otap_batch, 7).unwrap();
+        let headers = BatchHeaders::new().with("tenant-id", "acme");
+        records.set_batch_headers(&headers);
+        let PipelineOutput::Otap(mut output) = pipeline.process(&mut records).unwrap() else {
+            panic!("expected an OTAP output");
+        };
+        assert_eq!(output.batch_id, 7);
+        assert_eq!(output.batch_headers().unwrap(), headers);
+
+        let mut expected = request();
+        expected.resource_logs[0].resource = Some(Resource {
+            attributes: vec![attr("pipeline", "test")],
+            ..Default::default()
+        });
+        let _ = expected.resource_logs[0].scope_logs[0].log_records[0]
+            .attributes
+            .remove(0);
+        let decoded = Consumer::default()
+            .consume_logs_batches(&mut output)
+            .unwrap();
+        assert_eq!(decoded, expected);
+
+        let mut pipeline = pipeline.with_output(OutputFormat::Otlp);
+        let mut records = to_batch_arrow_records(&otap_batch, 8).unwrap();
+        assert_eq!(
+            pipeline.process(&mut records).unwrap(),
+            PipelineOutput::Otlp(ExportRequest::Logs(expected))
+        );
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enrichment of the resources of the batches with attributes describing where they were
+//! processed, e.g. by the edge agents adding the name of their host.
+//!
+//! A [`ResourceEnricher`] holds the attributes to add, set one by one or detected once
+//! by [`ResourceDetector`]s when the enricher is built: [`HostDetector`] detects the
+//! `host.name`, and the detectors of the cloud metadata are plugged in as closures. The
+//! attributes are merged into the resources of the decoded requests, as a [`Transform`] of
+//! a pipeline, or straight into the resource attributes payload of an OTAP batch, without
+//! decoding the batch. By default the attributes a resource already has are kept, see
+//! [`MergeConflict`].
+
+use std::collections::BTreeSet;
+
+use crate::arrays::NullableArrayAccessor;
+use crate::decode::decoder::ExportRequest;
+use crate::encode::attributes::Attributes16Accumulator;
+use crate::error::{ErrorContext, ErrorContextExt, Result};
+use crate::otap::OtapBatch;
+use crate::otlp::attributes::add_delta;
+use crate::otlp::attributes::store::{Attribute16Store, MergeConflict, merge_key_values};
+use crate::otlp::common::ResourceArrays;
+use crate::otlp::options::DecoderOptions;
+use crate::otlp::report::DecodeReport;
+use crate::pipeline::Transform;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+use crate::proto::opentelemetry::resource::v1::Resource;
+use crate::schema::consts;
+
+/// Resource attribute carrying the name of the host.
+pub const HOST_NAME_ATTRIBUTE: &str = "host.name";
+
+/// Detects resource attributes of the environment the batches are processed in. It is
+/// implemented for closures returning the attributes.
+pub trait ResourceDetector {
+    /// Returns the detected attributes, none if the detector doesn't apply.
+    fn detect(&self) -> Vec<KeyValue>;
+}
+
+impl<F> ResourceDetector for F
+where
+    F: Fn() -> Vec<KeyValue>,
+{
+    fn detect(&self) -> Vec<KeyValue> {
+        self()
+    }
+}
+
+/// Detects the `host.name`, from the `HOSTNAME` environment variable or `/etc/hostname`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HostDetector;
+
+impl ResourceDetector for HostDetector {
+    fn detect(&self) -> Vec<KeyValue> {
+        let host_name = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        host_name
+            .map(|name| vec![key_value(HOST_NAME_ATTRIBUTE, Value::StringValue(name))])
+            .unwrap_or_default()
+    }
+}
+
+/// Adds attributes to the resources of the batches, see the module documentation.
+#[derive(Clone, Debug, Default)]
+pub struct ResourceEnricher {
+    attributes: Vec<KeyValue>,
+    conflict: MergeConflict,
+}
+
+impl ResourceEnricher {
+    /// Creates an enricher without attributes.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an attribute, unless the enricher already has one with the same key.
+    #[must_use]
+    pub fn with_attribute(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.add(vec![key_value(key, value)]);
+        self
+    }
+
+    /// Adds the attributes detected by the detector, except the ones whose key the enricher
+    /// already has.
+    #[must_use]
+    pub fn with_detector(mut self, detector: &impl ResourceDetector) -> Self {
+        self.add(detector.detect());
+        self
+    }
+
+    /// Sets how the attributes a resource already has are resolved, instead of
+    /// [`MergeConflict::FirstWins`] keeping them.
+    #[must_use]
+    pub fn with_conflict(mut self, conflict: MergeConflict) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    /// Returns the attributes added to the resources.
+    #[must_use]
+    pub fn attributes(&self) -> &[KeyValue] {
+        &self.attributes
+    }
+
+    /// Merges the attributes into the resource.
+    pub fn enrich_resource(&self, resource: &mut Resource) -> Result<()> {
+        merge_key_values(&mut resource.attributes, &self.attributes, self.conflict)
+    }
+
+    /// Merges the attributes into the resources of the request, the missing resources being
+    /// created.
+    pub fn enrich_request(&self, request: &mut ExportRequest) -> Result<()> {
+        let resources: Vec<_> = match request {
+            ExportRequest::Logs(request) => request
+                .resource_logs
+                .iter_mut()
+                .map(|r| &mut r.resource)
+                .collect(),
+            ExportRequest::Metrics(request) => request
+                .resource_metrics
+                .iter_mut()
+                .map(|r| &mut r.resource)
+                .collect(),
+            ExportRequest::Traces(request) => request
+                .resource_spans
+                .iter_mut()
+                .map(|r| &mut r.resource)
+                .collect(),
+        };
+        for resource in resources {
+            self.enrich_resource(resource.get_or_insert_with(Resource::default))?;
+        }
+        Ok(())
+    }
+
+    /// Merges the attributes into the resource attributes payload of the batch, e.g. before
+    /// forwarding it without decoding it. The payload is re-encoded with the attributes of
+    /// every resource referenced by the main payload, the rows referencing no resource are
+    /// left as is.
+    pub fn enrich_otap_batch(&self, otap_batch: &mut OtapBatch) -> Result<()> {
+        let main_payload_type = otap_batch.main_payload_type();
+        let Some(rb) = otap_batch.get(main_payload_type) else {
+            return Ok(());
+        };
+        let resource_arrays = ResourceArrays::try_from(rb).in_payload(main_payload_type)?;
+        let mut resource_ids = BTreeSet::new();
+        let mut resource_id = 0;
+        for idx in 0..rb.num_rows() {
+            if let Some(delta) = resource_arrays.id.value_at(idx) {
+                resource_id = add_delta(resource_id, delta).error_context(|| {
+                    ErrorContext::default()
+                        .payload(main_payload_type)
+                        .row(idx)
+                        .column(consts::RESOURCE)
+                })?;
+                let _ = resource_ids.insert(resource_id);
+            }
+        }
+
+        let store = Attribute16Store::from_payload(
+            otap_batch,
+            ArrowPayloadType::ResourceAttrs,
+            &DecoderOptions::default(),
+            &mut DecodeReport::default(),
+        )?
+        .unwrap_or_default();
+        let mut sets = Vec::with_capacity(resource_ids.len());
+        for (id, attrs) in store.iter() {
+            if !resource_ids.contains(&id) {
+                sets.push((id, attrs.to_vec()));
+            }
+        }
+        for id in resource_ids {
+            sets.push((id, store.overlay(id, &self.attributes, self.conflict)?));
+        }
+
+        let mut resource_attrs = Attributes16Accumulator::default();
+        for (id, attrs) in &sets {
+            resource_attrs.append(*id, attrs);
+        }
+        if let Some(rb) = resource_attrs.finish()? {
+            otap_batch.set(ArrowPayloadType::ResourceAttrs, rb);
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, attributes: Vec<KeyValue>) {
+        // safety: the first set wins, the merge never fails
+        merge_key_values(&mut self.attributes, &attributes, MergeConflict::FirstWins)
+            .expect("first wins merge never fails");
+    }
+}
+
+impl Transform for ResourceEnricher {
+    fn transform(&self, request: &mut ExportRequest) -> Result<()> {
+        self.enrich_request(request)
+    }
+}
+
+fn key_value(key: impl Into<String>, value: Value) -> KeyValue {
+    KeyValue {
+        key: key.into(),
+        value: Some(AnyValue { value: Some(value) }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::TracesProducer;
+    use crate::otlp::traces::traces_from;
+    use crate::proto::opentelemetry::collector::trace::v1::ExportTraceServiceRequest;
+    use crate::proto::opentelemetry::common::v1::InstrumentationScope;
+    use crate::proto::opentelemetry::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+    fn string(key: &str, value: &str) -> KeyValue {
+        key_value(key, Value::StringValue(value.into()))
+    }
+
+    fn request() -> ExportTraceServiceRequest {
+        let resource_spans = |resource: Option<Resource>, span_id| ResourceSpans {
+            resource,
+            scope_spans: vec![ScopeSpans {
+                scope: Some(InstrumentationScope::default()),
+                spans: vec![Span {
+                    trace_id: vec![1; 16],
+                    span_id: vec![span_id; 8],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        ExportTraceServiceRequest {
+            resource_spans: vec![
+                resource_spans(
+                    Some(Resource {
+                        attributes: vec![string("service.name", "a"), string("host.name", "h1")],
+                        ..Default::default()
+                    }),
+                    1,
+                ),
+                resource_spans(Some(Resource::default()), 2),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_resource_enricher() {
+        let cloud = || {
+            vec![
+                string("cloud.provider", "local"),
+                string(HOST_NAME_ATTRIBUTE, "ignored"),
+            ]
+        };
+        let enricher = ResourceEnricher::new()
+            .with_attribute(HOST_NAME_ATTRIBUTE, Value::StringValue("edge".into()))
+            .with_detector(&cloud);
+        assert_eq!(enricher.attributes(), &[
+            string(HOST_NAME_ATTRIBUTE, "edge"),
+            string("cloud.provider", "local")
+        ]);
+
+        let mut expected = ExportRequest::Traces(request());
+        enricher.enrich_request(&mut expected).unwrap();
+        let ExportRequest::Traces(mut expected) = expected else {
+            unreachable!()
+        };
+        // the attributes of the resource are kept
+        assert_eq!(
+            expected.resource_spans[0]
+                .resource
+                .as_ref()
+                .unwrap()
+                .attributes,
+            vec![
+                string("service.name", "a"),
+                string("host.name", "h1"),
+                string("cloud.provider", "local"),
+            ]
+        );
+        assert_eq!(
+            expected.resource_spans[1]
+                .resource
+                .as_ref()
+                .unwrap()
+                .attributes,
+            enricher.attributes()
+        );
+
+        let mut otap_batch = TracesProducer::new().produce(&request()).unwrap();
+        enricher.enrich_otap_batch(&mut otap_batch).unwrap();
+        // the payload orders the attributes by key
+        for resource_spans in &mut expected.resource_spans {
+            let resource = resource_spans.resource.as_mut().unwrap();
+            resource.attributes.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+        assert_eq!(traces_from(otap_batch).unwrap(), expected);
+
+        let enricher = enricher.with_conflict(MergeConflict::Error);
+        let mut otap_batch = TracesProducer::new().produce(&request()).unwrap();
+        assert!(enricher.enrich_otap_batch(&mut otap_batch).is_err());
+    }
+}
@@ -0,0 +1,28 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The stable API of the crate, to import with `use otel_arrow_rust::prelude::*`.
+//!
+//! The prelude gathers the consumer and the producers converting between OTAP and OTLP,
+//! their options, the attribute stores and the record visitors. These items only change
+//! in a breaking way with a major release, like the modules they are defined in. The
+//! column names of the payloads and the parent id decoders of the attributes are only
+//! public with the `internal` feature, for the tools and benchmarks that need them, and
+//! may change in any release.
+
+pub use crate::decode::decoder::{
+    Consumer, ExportRequest, MultiSignalRequest, PartialMultiSignalRequest,
+};
+pub use crate::decode::schema_registry::SchemaEvent;
+pub use crate::encode::{
+    BatchMerger, BatchSplitter, LogsProducer, MetricsProducer, StreamEncoder, TracesProducer,
+};
+pub use crate::otap::OtapBatch;
+pub use crate::otlp::attributes::store::{Attribute16Store, Attribute32Store, AttributeStore};
+pub use crate::otlp::logs::{logs_from, logs_from_with_options, visit_logs};
+pub use crate::otlp::metrics::{metrics_from, metrics_from_with_options, visit_metrics};
+pub use crate::otlp::options::{DecodeLimits, DecoderOptions, ValueLimits};
+pub use crate::otlp::report::DecodeReport;
+pub use crate::otlp::traces::{traces_from, traces_from_with_options, visit_spans};
+pub use crate::otlp::visitor::RecordVisitor;
+pub use crate::value::AttributeValueType;
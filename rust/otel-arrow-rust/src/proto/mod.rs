@@ -70,3 +70,29 @@ pub mod opentelemetry {
         pub mod v1;
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::opentelemetry::common::v1::any_value::Value;
+    use super::opentelemetry::common::v1::{AnyValue, KeyValue};
+    use super::opentelemetry::logs::v1::LogRecord;
+
+    #[test]
+    fn test_serde_round_trip() {
+        let log_record = LogRecord {
+            time_unix_nano: 1,
+            attributes: vec![KeyValue {
+                key: "k".into(),
+                value: Some(AnyValue {
+                    value: Some(Value::IntValue(2)),
+                }),
+            }],
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&log_record).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LogRecord>(&json).unwrap(),
+            log_record
+        );
+    }
+}
@@ -1,16 +1,19 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.collector.logs.v1.ExportLogsServiceRequest")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ExportLogsServiceRequest {
     #[prost(message, repeated, tag = "1")]
     pub resource_logs: ::prost::alloc::vec::Vec<super::super::super::logs::v1::ResourceLogs>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.collector.logs.v1.ExportLogsServiceResponse")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ExportLogsServiceResponse {
     #[prost(message, optional, tag = "1")]
     pub partial_success: ::core::option::Option<ExportLogsPartialSuccess>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.collector.logs.v1.ExportLogsPartialSuccess")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ExportLogsPartialSuccess {
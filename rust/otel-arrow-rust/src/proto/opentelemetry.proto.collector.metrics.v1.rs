@@ -1,4 +1,5 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified(
     "opentelemetry.proto.collector.metrics.v1.ExportMetricsServiceRequest"
 )]
@@ -8,6 +9,7 @@ pub struct ExportMetricsServiceRequest {
     pub resource_metrics:
         ::prost::alloc::vec::Vec<super::super::super::metrics::v1::ResourceMetrics>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified(
     "opentelemetry.proto.collector.metrics.v1.ExportMetricsServiceResponse"
 )]
@@ -16,6 +18,7 @@ pub struct ExportMetricsServiceResponse {
     #[prost(message, optional, tag = "1")]
     pub partial_success: ::core::option::Option<ExportMetricsPartialSuccess>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified(
     "opentelemetry.proto.collector.metrics.v1.ExportMetricsPartialSuccess"
 )]
@@ -1,10 +1,12 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.collector.trace.v1.ExportTraceServiceRequest")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ExportTraceServiceRequest {
     #[prost(message, repeated, tag = "1")]
     pub resource_spans: ::prost::alloc::vec::Vec<super::super::super::trace::v1::ResourceSpans>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified(
     "opentelemetry.proto.collector.trace.v1.ExportTraceServiceResponse"
 )]
@@ -13,6 +15,7 @@ pub struct ExportTraceServiceResponse {
     #[prost(message, optional, tag = "1")]
     pub partial_success: ::core::option::Option<ExportTracePartialSuccess>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.collector.trace.v1.ExportTracePartialSuccess")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ExportTracePartialSuccess {
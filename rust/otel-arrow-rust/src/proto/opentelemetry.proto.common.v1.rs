@@ -1,4 +1,5 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.common.v1.AnyValue")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct AnyValue {
@@ -7,6 +8,7 @@ pub struct AnyValue {
 }
 /// Nested message and enum types in `AnyValue`.
 pub mod any_value {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Value {
         #[prost(string, tag = "1")]
@@ -25,18 +27,21 @@ pub mod any_value {
         BytesValue(::prost::alloc::vec::Vec<u8>),
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.common.v1.ArrayValue")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ArrayValue {
     #[prost(message, repeated, tag = "1")]
     pub values: ::prost::alloc::vec::Vec<AnyValue>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.common.v1.KeyValueList")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct KeyValueList {
     #[prost(message, repeated, tag = "1")]
     pub values: ::prost::alloc::vec::Vec<KeyValue>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.common.v1.KeyValue")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct KeyValue {
@@ -45,6 +50,7 @@ pub struct KeyValue {
     #[prost(message, optional, tag = "2")]
     pub value: ::core::option::Option<AnyValue>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.common.v1.InstrumentationScope")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct InstrumentationScope {
@@ -57,6 +63,7 @@ pub struct InstrumentationScope {
     #[prost(uint32, tag = "4")]
     pub dropped_attributes_count: u32,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.common.v1.EntityRef")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct EntityRef {
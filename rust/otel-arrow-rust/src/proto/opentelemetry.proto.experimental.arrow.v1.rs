@@ -1,4 +1,5 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BatchArrowRecords {
     #[prost(int64, tag = "1")]
@@ -8,6 +9,7 @@ pub struct BatchArrowRecords {
     #[prost(bytes = "vec", tag = "3")]
     pub headers: ::prost::alloc::vec::Vec<u8>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ArrowPayload {
     #[prost(string, tag = "1")]
@@ -17,6 +19,7 @@ pub struct ArrowPayload {
     #[prost(bytes = "vec", tag = "3")]
     pub record: ::prost::alloc::vec::Vec<u8>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BatchStatus {
     #[prost(int64, tag = "1")]
@@ -26,6 +29,7 @@ pub struct BatchStatus {
     #[prost(string, tag = "3")]
     pub status_message: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum ArrowPayloadType {
@@ -127,6 +131,7 @@ impl ArrowPayloadType {
         }
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum StatusCode {
@@ -1,10 +1,12 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.logs.v1.LogsData")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct LogsData {
     #[prost(message, repeated, tag = "1")]
     pub resource_logs: ::prost::alloc::vec::Vec<ResourceLogs>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.logs.v1.ResourceLogs")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ResourceLogs {
@@ -15,6 +17,7 @@ pub struct ResourceLogs {
     #[prost(string, tag = "3")]
     pub schema_url: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.logs.v1.ScopeLogs")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ScopeLogs {
@@ -25,6 +28,7 @@ pub struct ScopeLogs {
     #[prost(string, tag = "3")]
     pub schema_url: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.logs.v1.LogRecord")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct LogRecord {
@@ -51,6 +55,7 @@ pub struct LogRecord {
     #[prost(string, tag = "12")]
     pub event_name: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum SeverityNumber {
@@ -146,6 +151,7 @@ impl SeverityNumber {
         }
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum LogRecordFlags {
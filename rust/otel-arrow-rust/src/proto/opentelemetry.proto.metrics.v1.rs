@@ -1,10 +1,12 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.MetricsData")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct MetricsData {
     #[prost(message, repeated, tag = "1")]
     pub resource_metrics: ::prost::alloc::vec::Vec<ResourceMetrics>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.ResourceMetrics")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ResourceMetrics {
@@ -15,6 +17,7 @@ pub struct ResourceMetrics {
     #[prost(string, tag = "3")]
     pub schema_url: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.ScopeMetrics")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ScopeMetrics {
@@ -25,6 +28,7 @@ pub struct ScopeMetrics {
     #[prost(string, tag = "3")]
     pub schema_url: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.Metric")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Metric {
@@ -41,6 +45,7 @@ pub struct Metric {
 }
 /// Nested message and enum types in `Metric`.
 pub mod metric {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Data {
         #[prost(message, tag = "5")]
@@ -55,12 +60,14 @@ pub mod metric {
         Summary(super::Summary),
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.Gauge")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Gauge {
     #[prost(message, repeated, tag = "1")]
     pub data_points: ::prost::alloc::vec::Vec<NumberDataPoint>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.Sum")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Sum {
@@ -71,6 +78,7 @@ pub struct Sum {
     #[prost(bool, tag = "3")]
     pub is_monotonic: bool,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.Histogram")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Histogram {
@@ -79,6 +87,7 @@ pub struct Histogram {
     #[prost(enumeration = "AggregationTemporality", tag = "2")]
     pub aggregation_temporality: i32,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.ExponentialHistogram")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ExponentialHistogram {
@@ -87,12 +96,14 @@ pub struct ExponentialHistogram {
     #[prost(enumeration = "AggregationTemporality", tag = "2")]
     pub aggregation_temporality: i32,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.Summary")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Summary {
     #[prost(message, repeated, tag = "1")]
     pub data_points: ::prost::alloc::vec::Vec<SummaryDataPoint>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.NumberDataPoint")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct NumberDataPoint {
@@ -111,6 +122,7 @@ pub struct NumberDataPoint {
 }
 /// Nested message and enum types in `NumberDataPoint`.
 pub mod number_data_point {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Clone, Copy, PartialEq, ::prost::Oneof)]
     pub enum Value {
         #[prost(double, tag = "4")]
@@ -119,6 +131,7 @@ pub mod number_data_point {
         AsInt(i64),
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.HistogramDataPoint")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct HistogramDataPoint {
@@ -145,6 +158,7 @@ pub struct HistogramDataPoint {
     #[prost(double, optional, tag = "12")]
     pub max: ::core::option::Option<f64>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.ExponentialHistogramDataPoint")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ExponentialHistogramDataPoint {
@@ -179,6 +193,7 @@ pub struct ExponentialHistogramDataPoint {
 }
 /// Nested message and enum types in `ExponentialHistogramDataPoint`.
 pub mod exponential_histogram_data_point {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[crate::pdata::otlp::qualified(
         "opentelemetry.proto.metrics.v1.ExponentialHistogramDataPoint.Buckets"
     )]
@@ -190,6 +205,7 @@ pub mod exponential_histogram_data_point {
         pub bucket_counts: ::prost::alloc::vec::Vec<u64>,
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.SummaryDataPoint")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct SummaryDataPoint {
@@ -210,6 +226,7 @@ pub struct SummaryDataPoint {
 }
 /// Nested message and enum types in `SummaryDataPoint`.
 pub mod summary_data_point {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[crate::pdata::otlp::qualified(
         "opentelemetry.proto.metrics.v1.SummaryDataPoint.ValueAtQuantile"
     )]
@@ -221,6 +238,7 @@ pub mod summary_data_point {
         pub value: f64,
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.metrics.v1.Exemplar")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Exemplar {
@@ -237,6 +255,7 @@ pub struct Exemplar {
 }
 /// Nested message and enum types in `Exemplar`.
 pub mod exemplar {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Clone, Copy, PartialEq, ::prost::Oneof)]
     pub enum Value {
         #[prost(double, tag = "3")]
@@ -245,6 +264,7 @@ pub mod exemplar {
         AsInt(i64),
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum AggregationTemporality {
@@ -274,6 +294,7 @@ impl AggregationTemporality {
         }
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum DataPointFlags {
@@ -1,10 +1,12 @@
 // This file is @generated by prost-build.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.trace.v1.TracesData")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct TracesData {
     #[prost(message, repeated, tag = "1")]
     pub resource_spans: ::prost::alloc::vec::Vec<ResourceSpans>,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.trace.v1.ResourceSpans")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ResourceSpans {
@@ -15,6 +17,7 @@ pub struct ResourceSpans {
     #[prost(string, tag = "3")]
     pub schema_url: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.trace.v1.ScopeSpans")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct ScopeSpans {
@@ -25,6 +28,7 @@ pub struct ScopeSpans {
     #[prost(string, tag = "3")]
     pub schema_url: ::prost::alloc::string::String,
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.trace.v1.Span")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Span {
@@ -63,6 +67,7 @@ pub struct Span {
 }
 /// Nested message and enum types in `Span`.
 pub mod span {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[crate::pdata::otlp::qualified("opentelemetry.proto.trace.v1.Span.Event")]
     #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
     pub struct Event {
@@ -75,6 +80,7 @@ pub mod span {
         #[prost(uint32, tag = "4")]
         pub dropped_attributes_count: u32,
     }
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[crate::pdata::otlp::qualified("opentelemetry.proto.trace.v1.Span.Link")]
     #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
     pub struct Link {
@@ -91,6 +97,7 @@ pub mod span {
         #[prost(fixed32, tag = "6")]
         pub flags: u32,
     }
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum SpanKind {
@@ -130,6 +137,7 @@ pub mod span {
         }
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[crate::pdata::otlp::qualified("opentelemetry.proto.trace.v1.Status")]
 #[derive(crate::pdata::otlp::Message, Clone, PartialEq, ::prost::Message)]
 pub struct Status {
@@ -140,6 +148,7 @@ pub struct Status {
 }
 /// Nested message and enum types in `Status`.
 pub mod status {
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum StatusCode {
@@ -170,6 +179,7 @@ pub mod status {
         }
     }
 }
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum SpanFlags {
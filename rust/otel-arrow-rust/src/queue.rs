@@ -0,0 +1,266 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bounded queue between the encoding of the batches and their transport, so the memory
+//! held by an exporter stays bounded when the receivers slow down.
+//!
+//! The encoders push the batches into a [`BoundedQueue`] and the sender pops them. When
+//! the queue is full, its [`OverflowPolicy`] either blocks the encoders until the sender
+//! catches up, drops the oldest batch queued, or rejects the new one. The queue reports
+//! its depth and the batches it dropped to the [`MetricsSink`] it is given.
+//!
+//! The queue blocks the calling thread, the async senders pop it from a blocking task.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::telemetry::{self, MetricsSink};
+
+/// What a full [`BoundedQueue`] does with a new item.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Waits for the consumer to pop an item.
+    #[default]
+    Block,
+    /// Drops the oldest item queued to make room.
+    DropOldest,
+    /// Rejects the new item.
+    Reject,
+}
+
+/// Outcome of [`BoundedQueue::push`].
+#[derive(Debug, Eq, PartialEq)]
+pub enum PushOutcome<T> {
+    /// The item was queued.
+    Queued,
+    /// The item was queued and the oldest item dropped, see [`OverflowPolicy::DropOldest`].
+    DroppedOldest(T),
+    /// The queue is full and the item was rejected, see [`OverflowPolicy::Reject`].
+    Rejected(T),
+    /// The queue is closed and the item was not queued.
+    Closed(T),
+}
+
+#[derive(Debug)]
+struct State<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+/// Bounded queue of items, see the module documentation.
+pub struct BoundedQueue<T> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    metrics_sink: Option<Arc<dyn MetricsSink>>,
+}
+
+impl<T> BoundedQueue<T> {
+    /// Creates a queue holding up to `capacity` items, at least one.
+    #[must_use]
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            policy,
+            state: Mutex::new(State {
+                items: VecDeque::with_capacity(capacity.max(1)),
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            metrics_sink: None,
+        }
+    }
+
+    /// Reports the depth of the queue and the items it drops to the given sink.
+    #[must_use]
+    pub fn with_metrics_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics_sink = Some(sink);
+        self
+    }
+
+    /// Returns the maximum number of items queued.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of items queued.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock().items.len()
+    }
+
+    /// Returns whether no item is queued.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Queues the item, applying the overflow policy when the queue is full.
+    pub fn push(&self, item: T) -> PushOutcome<T> {
+        let mut state = self.lock();
+        if self.policy == OverflowPolicy::Block {
+            while !state.closed && state.items.len() >= self.capacity {
+                // safety: the lock is only poisoned by a panic while holding it
+                state = self.not_full.wait(state).expect("queue lock poisoned");
+            }
+        }
+        if state.closed {
+            return PushOutcome::Closed(item);
+        }
+
+        let mut outcome = PushOutcome::Queued;
+        if state.items.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::Reject => {
+                    drop(state);
+                    self.record_dropped("rejected");
+                    return PushOutcome::Rejected(item);
+                }
+                _ => {
+                    if let Some(oldest) = state.items.pop_front() {
+                        outcome = PushOutcome::DroppedOldest(oldest);
+                    }
+                }
+            }
+        }
+        state.items.push_back(item);
+        let depth = state.items.len();
+        drop(state);
+        self.not_empty.notify_one();
+
+        if let Some(sink) = &self.metrics_sink {
+            sink.record_histogram(telemetry::QUEUE_DEPTH, depth as f64, &[]);
+        }
+        if matches!(outcome, PushOutcome::DroppedOldest(_)) {
+            self.record_dropped("dropped_oldest");
+        }
+        outcome
+    }
+
+    /// Pops the oldest item, waiting for one to be queued. None once the queue is closed
+    /// and empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.lock();
+        while state.items.is_empty() && !state.closed {
+            // safety: the lock is only poisoned by a panic while holding it
+            state = self.not_empty.wait(state).expect("queue lock poisoned");
+        }
+        self.take(state)
+    }
+
+    /// Like [`BoundedQueue::pop`], waiting at most `timeout` for an item to be queued.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        while state.items.is_empty() && !state.closed {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            // safety: the lock is only poisoned by a panic while holding it
+            state = self
+                .not_empty
+                .wait_timeout(state, remaining)
+                .expect("queue lock poisoned")
+                .0;
+        }
+        self.take(state)
+    }
+
+    /// Pops the oldest item, if any, without waiting.
+    pub fn try_pop(&self) -> Option<T> {
+        let state = self.lock();
+        self.take(state)
+    }
+
+    /// Closes the queue: the items pushed next are not queued, and the consumer pops the
+    /// items left before getting none.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+
+    fn take(&self, mut state: MutexGuard<'_, State<T>>) -> Option<T> {
+        let item = state.items.pop_front();
+        drop(state);
+        if item.is_some() {
+            self.not_full.notify_one();
+        }
+        item
+    }
+
+    fn record_dropped(&self, reason: &str) {
+        if let Some(sink) = &self.metrics_sink {
+            sink.add_counter(telemetry::QUEUE_DROPPED, 1, &[(
+                telemetry::REASON_ATTRIBUTE,
+                reason,
+            )]);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // safety: the lock is only poisoned by a panic while holding it
+        self.state.lock().expect("queue lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Default)]
+    struct DroppedSink(AtomicU64);
+
+    impl MetricsSink for DroppedSink {
+        fn add_counter(&self, name: &'static str, value: u64, _: &[(&'static str, &str)]) {
+            assert_eq!(name, telemetry::QUEUE_DROPPED);
+            let _ = self.0.fetch_add(value, Ordering::Relaxed);
+        }
+
+        fn record_histogram(&self, name: &'static str, value: f64, _: &[(&'static str, &str)]) {
+            assert_eq!(name, telemetry::QUEUE_DEPTH);
+            assert!(value <= 2.0);
+        }
+    }
+
+    #[test]
+    fn test_bounded_queue() {
+        let sink = Arc::new(DroppedSink::default());
+        let queue =
+            BoundedQueue::new(2, OverflowPolicy::DropOldest).with_metrics_sink(sink.clone());
+        assert_eq!(queue.push(1), PushOutcome::Queued);
+        assert_eq!(queue.push(2), PushOutcome::Queued);
+        assert_eq!(queue.push(3), PushOutcome::DroppedOldest(1));
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.pop(), Some(2));
+
+        let queue = BoundedQueue::new(1, OverflowPolicy::Reject).with_metrics_sink(sink.clone());
+        assert_eq!(queue.push(1), PushOutcome::Queued);
+        assert_eq!(queue.push(2), PushOutcome::Rejected(2));
+        assert_eq!(sink.0.load(Ordering::Relaxed), 2);
+        queue.close();
+        assert_eq!(queue.push(3), PushOutcome::Closed(3));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), None);
+
+        let queue = Arc::new(BoundedQueue::new(1, OverflowPolicy::Block));
+        assert_eq!(queue.pop_timeout(Duration::from_millis(1)), None);
+        assert_eq!(queue.push(1), PushOutcome::Queued);
+        let producer = {
+            let queue = queue.clone();
+            std::thread::spawn(move || queue.push(2))
+        };
+        // the producer waits for room
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(producer.join().unwrap(), PushOutcome::Queued);
+        assert_eq!(queue.try_pop(), Some(2));
+        assert!(queue.is_empty());
+    }
+}
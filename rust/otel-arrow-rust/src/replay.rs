@@ -0,0 +1,329 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Recording of the `BatchArrowRecords` of a live stream to a file, and their replay
+//! through a decoder, for load testing and the reproduction of bugs.
+//!
+//! Unlike [frames](crate::frame), the batches are recorded as they were received: their
+//! payloads rely on the schemas and dictionaries sent earlier on the stream, so a recording
+//! holds a single stream and is replayed in order through a single [`Consumer`]. The layout
+//! of a recording is:
+//!
+//! - the magic bytes `OTAR`.
+//! - the version of the layout, [`RECORDING_VERSION`], on one byte.
+//! - for every batch, the time it was received since the start of the recording in
+//!   nanoseconds, a little endian u64, the length of the batch, a little endian u32, and
+//!   the protobuf encoded `BatchArrowRecords`.
+//!
+//! The [`Replayer`] waits between the batches as long as the recorded stream did, or less
+//! with an accelerated [`ReplaySpeed`].
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use prost::Message;
+use snafu::{ResultExt, ensure};
+
+use crate::Consumer;
+use crate::decode::decoder::ExportRequest;
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+
+/// Magic bytes starting every recording.
+pub const RECORDING_MAGIC: [u8; 4] = *b"OTAR";
+
+/// Version of the layout of the recordings written by this crate.
+pub const RECORDING_VERSION: u8 = 1;
+
+/// Writes the batches of a stream to a recording, see the module documentation.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Creates the recording at the path, replacing the file if it exists. The times of the
+    /// batches are measured from now.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::create(&path).context(error::IoSnafu { path: &path })?;
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(&RECORDING_MAGIC)
+            .and_then(|()| writer.write_all(&[RECORDING_VERSION]))
+            .context(error::IoSnafu { path: &path })?;
+        Ok(Self {
+            path,
+            writer,
+            started: Instant::now(),
+        })
+    }
+
+    /// Records the batch, received now.
+    pub fn record(&mut self, records: &BatchArrowRecords) -> Result<()> {
+        self.record_at(self.started.elapsed(), records)
+    }
+
+    /// Records the batch, received `offset` after the start of the recording.
+    pub fn record_at(&mut self, offset: Duration, records: &BatchArrowRecords) -> Result<()> {
+        let body = records.encode_to_vec();
+        let body_len = u32::try_from(body.len()).map_err(|_| {
+            error::InvalidRecordingSnafu {
+                reason: format!("batch of {} bytes is too large", body.len()),
+            }
+            .build()
+        })?;
+        let offset = u64::try_from(offset.as_nanos()).unwrap_or(u64::MAX);
+        self.writer
+            .write_all(&offset.to_le_bytes())
+            .and_then(|()| self.writer.write_all(&body_len.to_le_bytes()))
+            .and_then(|()| self.writer.write_all(&body))
+            .context(error::IoSnafu { path: &self.path })
+    }
+
+    /// Flushes the recording to its file.
+    pub fn finish(mut self) -> Result<()> {
+        self.writer
+            .flush()
+            .context(error::IoSnafu { path: &self.path })
+    }
+}
+
+/// A batch read from a recording.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedBatch {
+    /// Time the batch was received since the start of the recording.
+    pub offset: Duration,
+    /// The batch as received.
+    pub records: BatchArrowRecords,
+}
+
+/// Reads the batches of a recording in the order they were recorded.
+#[derive(Debug)]
+pub struct Recording {
+    path: PathBuf,
+    reader: BufReader<File>,
+    done: bool,
+}
+
+impl Recording {
+    /// Opens the recording at the path, checking its header.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path).context(error::IoSnafu { path: &path })?;
+        let mut reader = BufReader::new(file);
+        let mut header = [0; RECORDING_MAGIC.len() + 1];
+        reader
+            .read_exact(&mut header)
+            .context(error::IoSnafu { path: &path })?;
+        ensure!(
+            header[..RECORDING_MAGIC.len()] == RECORDING_MAGIC,
+            error::InvalidRecordingSnafu {
+                reason: "bad magic bytes"
+            }
+        );
+        let version = header[RECORDING_MAGIC.len()];
+        ensure!(version == RECORDING_VERSION, error::InvalidRecordingSnafu {
+            reason: format!("unsupported version {version}"),
+        });
+        Ok(Self {
+            path,
+            reader,
+            done: false,
+        })
+    }
+
+    fn read_batch(&mut self) -> Result<Option<RecordedBatch>> {
+        let mut offset = [0; 8];
+        match self.reader.read_exact(&mut offset) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            result => result.context(error::IoSnafu { path: &self.path })?,
+        }
+        let mut body_len = [0; 4];
+        self.reader
+            .read_exact(&mut body_len)
+            .context(error::IoSnafu { path: &self.path })?;
+        let mut body = vec![0; u32::from_le_bytes(body_len) as usize];
+        self.reader
+            .read_exact(&mut body)
+            .context(error::IoSnafu { path: &self.path })?;
+        let records = BatchArrowRecords::decode(body.as_slice()).map_err(|e| {
+            error::InvalidRecordingSnafu {
+                reason: e.to_string(),
+            }
+            .build()
+        })?;
+        Ok(Some(RecordedBatch {
+            offset: Duration::from_nanos(u64::from_le_bytes(offset)),
+            records,
+        }))
+    }
+}
+
+impl Iterator for Recording {
+    type Item = Result<RecordedBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let batch = self.read_batch().transpose();
+        // a truncated or corrupted recording ends with its error
+        self.done = !matches!(batch, Some(Ok(_)));
+        batch
+    }
+}
+
+/// Pace of a replay.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReplaySpeed {
+    /// Waits between the batches as long as the recorded stream did.
+    #[default]
+    Original,
+    /// Waits between the batches that many times less than the recorded stream did.
+    Accelerated(f64),
+    /// Replays the batches without waiting.
+    Unthrottled,
+}
+
+impl ReplaySpeed {
+    fn scale(self, offset: Duration) -> Option<Duration> {
+        match self {
+            Self::Original => Some(offset),
+            Self::Accelerated(factor) if factor > 0.0 => Some(offset.div_f64(factor)),
+            Self::Accelerated(_) | Self::Unthrottled => None,
+        }
+    }
+}
+
+/// Counts of a replay.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ReplayStats {
+    /// Number of batches replayed.
+    pub batches: usize,
+    /// Number of batches that failed to decode.
+    pub failed: usize,
+    /// Time the replay took.
+    pub elapsed: Duration,
+}
+
+/// Feeds the batches of a recording back through a decoder, see the module documentation.
+#[derive(Default)]
+pub struct Replayer {
+    consumer: Consumer,
+    speed: ReplaySpeed,
+}
+
+impl Replayer {
+    /// Creates a replayer decoding the batches with a default consumer at the given speed.
+    #[must_use]
+    pub fn new(speed: ReplaySpeed) -> Self {
+        Self {
+            speed,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the consumer decoding the batches, e.g. one with the options of the receiver
+    /// the bug is reproduced with.
+    #[must_use]
+    pub fn with_consumer(mut self, consumer: Consumer) -> Self {
+        self.consumer = consumer;
+        self
+    }
+
+    /// Returns the consumer decoding the batches.
+    #[must_use]
+    pub fn consumer(&self) -> &Consumer {
+        &self.consumer
+    }
+
+    /// Replays the batches of the recording, passing the result of the decoding of each
+    /// batch, along with its batch id, to `handle`. The decoding errors are counted and the
+    /// replay goes on, the errors reading the recording end it.
+    pub fn replay<F>(&mut self, recording: Recording, mut handle: F) -> Result<ReplayStats>
+    where
+        F: FnMut(i64, Result<ExportRequest>),
+    {
+        let started = Instant::now();
+        let mut stats = ReplayStats::default();
+        for batch in recording {
+            let RecordedBatch {
+                offset,
+                mut records,
+            } = batch?;
+            if let Some(due) = self.speed.scale(offset) {
+                if let Some(wait) = due.checked_sub(started.elapsed()) {
+                    std::thread::sleep(wait);
+                }
+            }
+            let result = self.consumer.consume_batches(&mut records);
+            stats.batches += 1;
+            if result.is_err() {
+                stats.failed += 1;
+            }
+            handle(records.batch_id, result);
+        }
+        stats.elapsed = started.elapsed();
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::encode::split::to_batch_arrow_records;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_record_replay() {
+        let path = std::env::temp_dir().join(format!("otap-recording-{}", std::process::id()));
+        let mut recorder = Recorder::create(&path).unwrap();
+        let otap_batch = traces_batch(8, 2);
+        for batch_id in 0..3 {
+            let records = to_batch_arrow_records(&otap_batch, batch_id).unwrap();
+            recorder
+                .record_at(Duration::from_millis(20 * batch_id as u64), &records)
+                .unwrap();
+        }
+        // a batch the receiver fails to decode
+        let mut invalid = to_batch_arrow_records(&otap_batch, 9).unwrap();
+        invalid.arrow_payloads[0].record = vec![0; 4];
+        recorder
+            .record_at(Duration::from_millis(40), &invalid)
+            .unwrap();
+        recorder.finish().unwrap();
+
+        let recording = Recording::open(&path).unwrap();
+        let mut batch_ids = Vec::new();
+        let stats = Replayer::new(ReplaySpeed::Original)
+            .replay(recording, |batch_id, result| {
+                batch_ids.push((batch_id, result.is_ok()));
+            })
+            .unwrap();
+        assert_eq!(batch_ids, vec![(0, true), (1, true), (2, true), (9, false)]);
+        assert_eq!((stats.batches, stats.failed), (4, 1));
+        assert!(stats.elapsed >= Duration::from_millis(40));
+
+        let recording = Recording::open(&path).unwrap();
+        let stats = Replayer::new(ReplaySpeed::Accelerated(1000.0))
+            .replay(recording, |_, _| {})
+            .unwrap();
+        assert!(stats.elapsed < Duration::from_millis(40));
+
+        // truncated recordings fail once their complete batches are replayed
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let batches: Vec<_> = Recording::open(&path).unwrap().collect();
+        assert_eq!(batches.len(), 4);
+        assert!(batches[3].is_err());
+        std::fs::write(&path, b"OTAF\x01").unwrap();
+        assert!(Recording::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
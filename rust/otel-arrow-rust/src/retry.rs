@@ -0,0 +1,341 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Retrying the batches of an exporter from the `BatchStatus` replies of the receiver.
+//!
+//! The exporter registers every batch it sends in a [`RetryQueue`], along with what it
+//! needs to send it again, typically the OTLP request the batch was encoded from. A batch
+//! is re-encoded rather than resent as is when it is retried: the stream it was sent on may
+//! have been reset, and with it the schemas and dictionaries its payloads rely on.
+//!
+//! When a status comes back, [`RetryQueue::on_status`] tells whether the batch is done,
+//! should be sent again after a jittered exponential backoff, see [`RetryPolicy`], or
+//! dropped, its failure being permanent, see [`crate::status::is_retryable`], or its
+//! attempts exhausted.
+//!
+//! Like the Go exporter, the queue downgrades to OTLP when the receiver doesn't support
+//! OTAP, i.e. when it fails the stream with `UNIMPLEMENTED`: the batches in flight are
+//! returned by [`RetryQueue::on_stream_closed`] to be sent again, and [`RetryQueue::protocol`]
+//! tells to send them, and the next ones, with the standard OTLP gRPC services.
+
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use crate::proto::opentelemetry::arrow::v1::{BatchStatus, StatusCode};
+use crate::status::is_retryable;
+
+/// Backoff of the batches retried, exponential with jitter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    jitter: f64,
+    max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    /// The defaults of the retries of the OpenTelemetry collector exporters: 5 seconds
+    /// first, multiplied by 1.5 up to 30 seconds, plus or minus half, for 5 attempts.
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 1.5,
+            jitter: 0.5,
+            max_attempts: 5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Sets the backoff before the first retry.
+    #[must_use]
+    pub fn with_initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the backoff the exponential growth stops at, before the jitter.
+    #[must_use]
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets the factor the backoff is multiplied by after every attempt, at least 1.
+    #[must_use]
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Sets the fraction of the backoff randomly added or removed, clamped to `[0, 1]`.
+    #[must_use]
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the number of times a batch is sent, the first time included, before it is
+    /// dropped.
+    #[must_use]
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Returns the backoff before sending again a batch already sent `attempts` times,
+    /// `random` in `[0, 1)` drawing the jitter.
+    #[must_use]
+    pub fn backoff(&self, attempts: u32, random: f64) -> Duration {
+        let exponent = i32::try_from(attempts.saturating_sub(1)).unwrap_or(i32::MAX);
+        let backoff = (self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter * (2.0 * random - 1.0);
+        Duration::from_secs_f64((backoff * (1.0 + jitter)).max(0.0))
+    }
+}
+
+/// Protocol the exporter sends the batches with.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Protocol {
+    /// The OTel Arrow streams.
+    #[default]
+    Otap,
+    /// The standard OTLP gRPC services, the receiver not supporting OTAP.
+    Otlp,
+}
+
+/// A batch to send again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Retry<T> {
+    /// What the batch is re-encoded from.
+    pub item: T,
+    /// Number of times the batch was already sent.
+    pub attempts: u32,
+    /// Backoff before sending the batch again.
+    pub after: Duration,
+}
+
+/// What the exporter does with a batch once its status is received.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RetryAction<T> {
+    /// The batch was processed.
+    Done(T),
+    /// The batch failed transiently, it should be sent again, see [`RetryQueue::retry`].
+    Retry(Retry<T>),
+    /// The batch failed permanently or ran out of attempts.
+    Drop {
+        /// What the batch was encoded from.
+        item: T,
+        /// Status of the last attempt.
+        status_code: StatusCode,
+    },
+}
+
+/// Batches in flight waiting for their status, see the module documentation.
+#[derive(Debug)]
+pub struct RetryQueue<T> {
+    policy: RetryPolicy,
+    protocol: Protocol,
+    in_flight: HashMap<i64, (T, u32)>,
+    rng: u64,
+}
+
+impl<T> RetryQueue<T> {
+    /// Creates an empty queue retrying the batches with the policy.
+    #[must_use]
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self::with_seed(policy, RandomState::new().hash_one(0u64))
+    }
+
+    /// Like [`RetryQueue::new`], drawing the jitter from the seed, e.g. to be reproducible.
+    #[must_use]
+    pub fn with_seed(policy: RetryPolicy, seed: u64) -> Self {
+        Self {
+            policy,
+            protocol: Protocol::Otap,
+            in_flight: HashMap::new(),
+            // xorshift is stuck at 0
+            rng: seed | 1,
+        }
+    }
+
+    /// Returns the protocol to send the batches with.
+    #[must_use]
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Returns the number of batches waiting for their status.
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Registers a batch sent for the first time.
+    pub fn track(&mut self, batch_id: i64, item: T) {
+        let _ = self.in_flight.insert(batch_id, (item, 1));
+    }
+
+    /// Registers a batch sent again, under the id of its new encoding.
+    pub fn retry(&mut self, batch_id: i64, retry: Retry<T>) {
+        let _ = self
+            .in_flight
+            .insert(batch_id, (retry.item, retry.attempts.saturating_add(1)));
+    }
+
+    /// Returns what to do with the batch the status is for, none if the batch is not in
+    /// flight.
+    pub fn on_status(&mut self, status: &BatchStatus) -> Option<RetryAction<T>> {
+        let (item, attempts) = self.in_flight.remove(&status.batch_id)?;
+        let status_code =
+            StatusCode::try_from(status.status_code).unwrap_or(StatusCode::InvalidArgument);
+        let action = if status_code == StatusCode::Ok {
+            RetryAction::Done(item)
+        } else if is_retryable(status_code) && attempts < self.policy.max_attempts {
+            RetryAction::Retry(self.next_retry(item, attempts))
+        } else {
+            RetryAction::Drop { item, status_code }
+        };
+        Some(action)
+    }
+
+    /// Returns the batches in flight when the stream is closed, to send again on a new
+    /// stream, or with OTLP when `unsupported` tells the receiver doesn't support OTAP.
+    /// The backoff of the batches is the first one, the stream failing rather than them.
+    pub fn on_stream_closed(&mut self, unsupported: bool) -> Vec<Retry<T>> {
+        if unsupported {
+            self.protocol = Protocol::Otlp;
+        }
+        let mut in_flight: Vec<_> = self.in_flight.drain().collect();
+        // resend in the order the batches were sent
+        in_flight.sort_by_key(|(batch_id, _)| *batch_id);
+        let mut retries = Vec::with_capacity(in_flight.len());
+        for (_, (item, attempts)) in in_flight {
+            let random = self.next_random();
+            let after = if unsupported {
+                Duration::ZERO
+            } else {
+                self.policy.backoff(1, random)
+            };
+            retries.push(Retry {
+                item,
+                attempts,
+                after,
+            });
+        }
+        retries
+    }
+
+    fn next_retry(&mut self, item: T, attempts: u32) -> Retry<T> {
+        let random = self.next_random();
+        Retry {
+            item,
+            attempts,
+            after: self.policy.backoff(attempts, random),
+        }
+    }
+
+    fn next_random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Returns whether the error the receiver failed the stream with tells it doesn't support
+/// OTAP, so the exporter should downgrade to OTLP.
+#[cfg(feature = "client")]
+#[must_use]
+pub fn is_otap_unsupported(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::Unimplemented
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn status(batch_id: i64, status_code: StatusCode) -> BatchStatus {
+        BatchStatus {
+            batch_id,
+            status_code: status_code as i32,
+            status_message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let policy = RetryPolicy::default()
+            .with_initial_backoff(Duration::from_secs(2))
+            .with_max_backoff(Duration::from_secs(5))
+            .with_multiplier(2.0);
+        assert_eq!(policy.backoff(1, 0.5), Duration::from_secs(2));
+        assert_eq!(policy.backoff(2, 0.5), Duration::from_secs(4));
+        assert_eq!(policy.backoff(3, 0.5), Duration::from_secs(5));
+        assert_eq!(policy.backoff(1, 0.0), Duration::from_secs(1));
+        assert_eq!(
+            policy.with_jitter(0.0).backoff(1, 0.0),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[test]
+    fn test_retry_queue() {
+        let mut queue = RetryQueue::with_seed(RetryPolicy::default().with_max_attempts(2), 42);
+        queue.track(1, "a");
+        queue.track(2, "b");
+        queue.track(3, "c");
+        assert_eq!(
+            queue.on_status(&status(1, StatusCode::Ok)),
+            Some(RetryAction::Done("a"))
+        );
+        assert_eq!(queue.on_status(&status(1, StatusCode::Ok)), None);
+        assert_eq!(
+            queue.on_status(&status(2, StatusCode::InvalidArgument)),
+            Some(RetryAction::Drop {
+                item: "b",
+                status_code: StatusCode::InvalidArgument
+            })
+        );
+
+        let Some(RetryAction::Retry(retry)) = queue.on_status(&status(3, StatusCode::Unavailable))
+        else {
+            panic!("expected a retry");
+        };
+        assert_eq!((retry.item, retry.attempts), ("c", 1));
+        assert!(
+            retry.after >= Duration::from_millis(2500)
+                && retry.after <= Duration::from_millis(7500)
+        );
+        // the second attempt is the last one
+        queue.retry(4, retry);
+        assert!(matches!(
+            queue.on_status(&status(4, StatusCode::Unavailable)),
+            Some(RetryAction::Drop { item: "c", .. })
+        ));
+
+        queue.track(6, "e");
+        queue.track(5, "d");
+        assert_eq!(queue.protocol(), Protocol::Otap);
+        let retries = queue.on_stream_closed(true);
+        assert_eq!(queue.protocol(), Protocol::Otlp);
+        assert_eq!(queue.in_flight(), 0);
+        assert_eq!(retries, vec![
+            Retry {
+                item: "d",
+                attempts: 1,
+                after: Duration::ZERO
+            },
+            Retry {
+                item: "e",
+                attempts: 1,
+                after: Duration::ZERO
+            },
+        ]);
+    }
+}
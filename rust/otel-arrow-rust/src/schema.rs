@@ -19,7 +19,13 @@ use arrow::array::RecordBatch;
 use arrow::datatypes::Schema;
 use std::sync::Arc;
 
+pub mod builders;
+pub mod compat;
+#[cfg(feature = "internal")]
 pub mod consts;
+#[cfg(not(feature = "internal"))]
+pub(crate) mod consts;
+pub mod definitions;
 
 /// Returns a new record batch with the new key/value updated in the schema metadata.
 #[must_use]
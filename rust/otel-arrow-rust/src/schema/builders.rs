@@ -0,0 +1,884 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Typed builders of the record batches of the OTAP payloads.
+//!
+//! Every builder has an `append_*` method per column, taking a value of the Rust type of
+//! the column, and an accessor per struct column returning the builder of its fields, so
+//! the record batches they produce always have the column names and types the decoder
+//! expects.
+//!
+//! Each row takes one value, possibly `None`, in every column the caller fills. A column
+//! that is never appended to is left out of the record batch, like the optional columns
+//! only holding nulls. The values are written as given, e.g. the ids must already be delta
+//! encoded where the payload expects it, see [`crate::encode`] for a producer doing so.
+//! The string, integer and binary columns are dictionary encoded when their cardinality
+//! allows it, like the Go producer does, following their
+//! [`definitions`](crate::schema::definitions).
+
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayBuilder, ArrayRef, ArrowPrimitiveType, BinaryBuilder, BooleanBuilder,
+    FixedSizeBinaryBuilder, Float64Array, ListArray, ListBuilder, NullBufferBuilder,
+    PrimitiveBuilder, RecordBatch, StringBuilder,
+};
+use arrow::buffer::{NullBuffer, OffsetBuffer};
+use arrow::datatypes::{
+    DurationMillisecondType, Field, Float64Type, Int32Type, Int64Type, TimestampNanosecondType,
+    UInt8Type, UInt16Type, UInt32Type, UInt64Type,
+};
+use paste::paste;
+use snafu::ResultExt;
+
+use crate::encode::record::{Columns, dictionary};
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::proto::opentelemetry::metrics::v1::summary_data_point::ValueAtQuantile;
+use crate::schema::consts;
+use crate::schema::definitions::{self, DictionaryKey};
+
+/// The Arrow builder of a column.
+trait ColumnBuilder: Default {
+    fn len(&self) -> usize;
+
+    fn finish(&mut self) -> Result<ArrayRef>;
+}
+
+/// A column builder appending values of type `V`.
+trait AppendColumn<V>: ColumnBuilder {
+    fn append(&mut self, value: Option<V>);
+}
+
+impl<T: ArrowPrimitiveType> ColumnBuilder for PrimitiveBuilder<T> {
+    fn len(&self) -> usize {
+        ArrayBuilder::len(self)
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef> {
+        Ok(Arc::new(PrimitiveBuilder::finish(self)))
+    }
+}
+
+impl<T: ArrowPrimitiveType> AppendColumn<T::Native> for PrimitiveBuilder<T> {
+    fn append(&mut self, value: Option<T::Native>) {
+        self.append_option(value);
+    }
+}
+
+impl ColumnBuilder for BooleanBuilder {
+    fn len(&self) -> usize {
+        ArrayBuilder::len(self)
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef> {
+        Ok(Arc::new(BooleanBuilder::finish(self)))
+    }
+}
+
+impl AppendColumn<bool> for BooleanBuilder {
+    fn append(&mut self, value: Option<bool>) {
+        self.append_option(value);
+    }
+}
+
+impl ColumnBuilder for StringBuilder {
+    fn len(&self) -> usize {
+        ArrayBuilder::len(self)
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef> {
+        Ok(Arc::new(StringBuilder::finish(self)))
+    }
+}
+
+impl<'v> AppendColumn<&'v str> for StringBuilder {
+    fn append(&mut self, value: Option<&'v str>) {
+        self.append_option(value);
+    }
+}
+
+impl ColumnBuilder for BinaryBuilder {
+    fn len(&self) -> usize {
+        ArrayBuilder::len(self)
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef> {
+        Ok(Arc::new(BinaryBuilder::finish(self)))
+    }
+}
+
+impl<'v> AppendColumn<&'v [u8]> for BinaryBuilder {
+    fn append(&mut self, value: Option<&'v [u8]>) {
+        self.append_option(value);
+    }
+}
+
+/// Builder of a fixed size binary column of `N` bytes, whose values are arrays of this
+/// size, e.g. the trace and span ids.
+struct FixedSizeBinaryColumn<const N: usize>(FixedSizeBinaryBuilder);
+
+impl<const N: usize> Default for FixedSizeBinaryColumn<N> {
+    fn default() -> Self {
+        Self(FixedSizeBinaryBuilder::new(N as i32))
+    }
+}
+
+impl<const N: usize> ColumnBuilder for FixedSizeBinaryColumn<N> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef> {
+        Ok(Arc::new(self.0.finish()))
+    }
+}
+
+impl<'v, const N: usize> AppendColumn<&'v [u8; N]> for FixedSizeBinaryColumn<N> {
+    fn append(&mut self, value: Option<&'v [u8; N]>) {
+        match value {
+            // safety: the builder has the size of the value
+            Some(value) => self
+                .0
+                .append_value(value)
+                .expect("value of the builder size"),
+            None => self.0.append_null(),
+        }
+    }
+}
+
+impl<T: ArrowPrimitiveType> ColumnBuilder for ListBuilder<PrimitiveBuilder<T>> {
+    fn len(&self) -> usize {
+        ArrayBuilder::len(self)
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef> {
+        Ok(Arc::new(ListBuilder::finish(self)))
+    }
+}
+
+impl<'v, T: ArrowPrimitiveType> AppendColumn<&'v [T::Native]> for ListBuilder<PrimitiveBuilder<T>> {
+    fn append(&mut self, value: Option<&'v [T::Native]>) {
+        self.append_option(value.map(|values| values.iter().copied().map(Some)));
+    }
+}
+
+/// Builder of the `quantile` list column of the summary data points, a list of structs
+/// with the `quantile` and `value` fields.
+#[derive(Default)]
+struct QuantileValuesColumn {
+    lengths: Vec<usize>,
+    validity: Vec<bool>,
+    quantiles: Vec<f64>,
+    values: Vec<f64>,
+}
+
+impl ColumnBuilder for QuantileValuesColumn {
+    fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    fn finish(&mut self) -> Result<ArrayRef> {
+        let len = self.quantiles.len();
+        let mut columns = Columns::default();
+        columns.required(
+            consts::SUMMARY_QUANTILE,
+            Arc::new(Float64Array::from(std::mem::take(&mut self.quantiles))),
+        );
+        columns.required(
+            consts::SUMMARY_VALUE,
+            Arc::new(Float64Array::from(std::mem::take(&mut self.values))),
+        );
+        let values = columns.into_struct_array(len, None)?;
+
+        let field = Arc::new(Field::new_list_field(values.data_type().clone(), true));
+        let offsets = OffsetBuffer::from_lengths(std::mem::take(&mut self.lengths));
+        let validity = std::mem::take(&mut self.validity);
+        let nulls = validity
+            .contains(&false)
+            .then(|| NullBuffer::from(validity));
+        let list = ListArray::try_new(field, offsets, values, nulls)
+            .context(error::WriteRecordBatchSnafu)?;
+        Ok(Arc::new(list))
+    }
+}
+
+impl<'v> AppendColumn<&'v [ValueAtQuantile]> for QuantileValuesColumn {
+    fn append(&mut self, value: Option<&'v [ValueAtQuantile]>) {
+        self.validity.push(value.is_some());
+        let value = value.unwrap_or_default();
+        self.lengths.push(value.len());
+        self.quantiles.extend(value.iter().map(|q| q.quantile));
+        self.values.extend(value.iter().map(|q| q.value));
+    }
+}
+
+/// Returns the finished column, dictionary encoded or not.
+macro_rules! encode {
+    (plain, $array:expr) => {
+        $array
+    };
+    (dict8, $array:expr) => {
+        dictionary($array, DictionaryKey::U8)
+    };
+    (dict16, $array:expr) => {
+        dictionary($array, DictionaryKey::U16)
+    };
+}
+
+/// Adds a finished column to the columns of a record batch or struct array.
+macro_rules! push_column {
+    (required, $columns:ident, $name:expr, $array:expr) => {
+        $columns.required($name, $array)
+    };
+    (optional, $columns:ident, $name:expr, $array:expr) => {
+        $columns.optional($name, $array)
+    };
+}
+
+/// Adds a finished struct column, the optional struct columns left empty are left out.
+macro_rules! push_struct_column {
+    (required, $columns:ident, $name:expr, $builder:expr, $len:ident) => {
+        $columns.required($name, $builder.finish($len)?)
+    };
+    (optional, $columns:ident, $name:expr, $builder:expr, $len:ident) => {
+        if !$builder.is_empty() {
+            $columns.optional($name, $builder.finish($len)?)
+        }
+    };
+}
+
+/// Defines the `append_*` methods of the columns of a builder, and the `columns` method
+/// returning the finished columns.
+macro_rules! impl_columns {
+    (
+        $builder:ident {
+            $(
+                $(#[$column_meta:meta])*
+                $column:ident: $value:ty => $name:path, $values:ty, $encoding:ident, $presence:ident;
+            )*
+        }
+    ) => {
+        paste! {
+            impl $builder {
+                $(
+                    $(#[$column_meta])*
+                    pub fn [<append_ $column>](&mut self, value: Option<$value>) {
+                        AppendColumn::<$value>::append(&mut self.$column, value);
+                    }
+                )*
+
+                fn columns(&mut self) -> Result<Columns> {
+                    let mut columns = Columns::default();
+                    $(
+                        if ColumnBuilder::len(&self.$column) > 0 {
+                            push_column!(
+                                $presence,
+                                columns,
+                                $name,
+                                encode!($encoding, ColumnBuilder::finish(&mut self.$column)?)
+                            );
+                        }
+                    )*
+                    Ok(columns)
+                }
+
+                fn columns_len(&self) -> usize {
+                    0 $(.max(ColumnBuilder::len(&self.$column)))*
+                }
+            }
+        }
+    };
+}
+
+/// Defines the builder of a record batch, made of columns and of struct columns.
+macro_rules! record_batch_builder {
+    (
+        $(#[$meta:meta])*
+        pub struct $builder:ident for $payload_type:ident {
+            $(
+                $(#[$column_meta:meta])*
+                $column:ident: $value:ty => $name:path, $values:ty, $encoding:ident, $presence:ident;
+            )*
+        }
+        $(
+            structs {
+                $(
+                    $(#[$struct_meta:meta])*
+                    $struct_column:ident: $struct_builder:ty => $struct_name:path, $struct_presence:ident;
+                )*
+            }
+        )?
+    ) => {
+        $(#[$meta])*
+        #[derive(Default)]
+        pub struct $builder {
+            $($column: $values,)*
+            $($($struct_column: $struct_builder,)*)?
+        }
+
+        impl_columns!($builder {
+            $(
+                $(#[$column_meta])*
+                $column: $value => $name, $values, $encoding, $presence;
+            )*
+        });
+
+        impl $builder {
+            /// Creates an empty builder.
+            #[must_use]
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            $($(
+                $(#[$struct_meta])*
+                pub fn $struct_column(&mut self) -> &mut $struct_builder {
+                    &mut self.$struct_column
+                }
+            )*)?
+
+            /// Returns the number of rows appended.
+            #[must_use]
+            pub fn len(&self) -> usize {
+                self.columns_len() $($(.max(self.$struct_column.len()))*)?
+            }
+
+            /// Returns whether no row was appended.
+            #[must_use]
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Returns the record batch of the rows appended and resets the builder.
+            pub fn finish(&mut self) -> Result<RecordBatch> {
+                #[allow(unused_variables)]
+                let len = self.len();
+                #[allow(unused_mut)]
+                let mut columns = self.columns()?;
+                $($(
+                    push_struct_column!(
+                        $struct_presence,
+                        columns,
+                        $struct_name,
+                        self.$struct_column,
+                        len
+                    );
+                )*)?
+                let record_batch = columns.into_record_batch()?;
+                debug_assert!(
+                    definitions::validate_schema(
+                        ArrowPayloadType::$payload_type,
+                        &record_batch.schema()
+                    )
+                    .is_ok()
+                );
+                Ok(record_batch)
+            }
+        }
+    };
+}
+
+/// Defines the builder of the fields of a struct column.
+macro_rules! struct_column_builder {
+    (
+        $(#[$meta:meta])*
+        pub struct $builder:ident {
+            $(
+                $(#[$column_meta:meta])*
+                $column:ident: $value:ty => $name:path, $values:ty, $encoding:ident, $presence:ident;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        pub struct $builder {
+            $($column: $values,)*
+            nulls: NullBufferBuilder,
+        }
+
+        impl Default for $builder {
+            fn default() -> Self {
+                Self {
+                    $($column: Default::default(),)*
+                    nulls: NullBufferBuilder::new(0),
+                }
+            }
+        }
+
+        impl_columns!($builder {
+            $(
+                $(#[$column_meta])*
+                $column: $value => $name, $values, $encoding, $presence;
+            )*
+        });
+
+        impl $builder {
+            /// Appends a null struct, a null is appended to every field.
+            pub fn append_null(&mut self) {
+                let len = self.len();
+                self.nulls.append_n_non_nulls(len.saturating_sub(self.nulls.len()));
+                self.nulls.append_null();
+                $(AppendColumn::<$value>::append(&mut self.$column, None);)*
+            }
+
+            /// Returns the number of rows appended.
+            #[must_use]
+            pub fn len(&self) -> usize {
+                self.columns_len().max(self.nulls.len())
+            }
+
+            /// Returns whether no row was appended.
+            #[must_use]
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            fn finish(&mut self, len: usize) -> Result<ArrayRef> {
+                let columns = self.columns()?;
+                if !self.nulls.is_empty() {
+                    self.nulls.append_n_non_nulls(len.saturating_sub(self.nulls.len()));
+                }
+                columns.into_struct_array(len, self.nulls.finish())
+            }
+        }
+    };
+}
+
+struct_column_builder! {
+    /// Builder of the `resource` struct column of the logs, spans and metrics.
+    pub struct ResourceColumnBuilder {
+        /// Appends the delta encoded id of the resource, see [`ResourceAttrsBatchBuilder`].
+        id: u16 => consts::ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        schema_url: &str => consts::SCHEMA_URL, StringBuilder, dict8, optional;
+        dropped_attributes_count: u32 => consts::DROPPED_ATTRIBUTES_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+    }
+}
+
+struct_column_builder! {
+    /// Builder of the `scope` struct column of the logs, spans and metrics.
+    pub struct ScopeColumnBuilder {
+        /// Appends the delta encoded id of the scope, see [`ScopeAttrsBatchBuilder`].
+        id: u16 => consts::ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        name: &str => consts::NAME, StringBuilder, dict8, optional;
+        version: &str => consts::VERSION, StringBuilder, dict8, optional;
+        dropped_attributes_count: u32 => consts::DROPPED_ATTRIBUTES_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+    }
+}
+
+struct_column_builder! {
+    /// Builder of the `status` struct column of the spans.
+    pub struct StatusColumnBuilder {
+        code: i32 => consts::STATUS_CODE, PrimitiveBuilder<Int32Type>, dict8, optional;
+        status_message: &str => consts::STATUS_MESSAGE, StringBuilder, dict8, optional;
+    }
+}
+
+struct_column_builder! {
+    /// Builder of the `body` struct column of the logs, holding an `AnyValue` like the
+    /// attributes, see [`AttributeValueType`](crate::otlp::attributes::store::AttributeValueType)
+    /// for the value types.
+    pub struct AnyValueColumnBuilder {
+        /// Appends the type of the value, which selects the column holding it.
+        r#type: u8 => consts::ATTRIBUTE_TYPE, PrimitiveBuilder<UInt8Type>, plain, required;
+        str: &str => consts::ATTRIBUTE_STR, StringBuilder, dict16, optional;
+        int: i64 => consts::ATTRIBUTE_INT, PrimitiveBuilder<Int64Type>, dict16, optional;
+        double: f64 => consts::ATTRIBUTE_DOUBLE, PrimitiveBuilder<Float64Type>, plain, optional;
+        bool: bool => consts::ATTRIBUTE_BOOL, BooleanBuilder, plain, optional;
+        bytes: &[u8] => consts::ATTRIBUTE_BYTES, BinaryBuilder, dict16, optional;
+        /// Appends a CBOR serialized map or slice value.
+        ser: &[u8] => consts::ATTRIBUTE_SER, BinaryBuilder, dict16, optional;
+    }
+}
+
+struct_column_builder! {
+    /// Builder of the `positive` and `negative` struct columns of the exponential histogram
+    /// data points.
+    pub struct BucketsColumnBuilder {
+        offset: i32 => consts::EXP_HISTOGRAM_OFFSET, PrimitiveBuilder<Int32Type>, plain, required;
+        bucket_counts: &[u64] => consts::EXP_HISTOGRAM_BUCKET_COUNTS, ListBuilder<PrimitiveBuilder<UInt64Type>>, plain, required;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the attributes record batches whose parent ids are 16 bits, e.g. the
+    /// `LOG_ATTRS` payload.
+    pub struct Attrs16BatchBuilder for LogAttrs {
+        /// Appends the id of the parent, delta encoded between the consecutive attributes
+        /// with the same key and value.
+        parent_id: u16 => consts::PARENT_ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        key: &str => consts::ATTRIBUTE_KEY, StringBuilder, dict8, required;
+        /// Appends the type of the value, which selects the column holding it.
+        r#type: u8 => consts::ATTRIBUTE_TYPE, PrimitiveBuilder<UInt8Type>, plain, required;
+        str: &str => consts::ATTRIBUTE_STR, StringBuilder, dict16, required;
+        int: i64 => consts::ATTRIBUTE_INT, PrimitiveBuilder<Int64Type>, dict16, optional;
+        double: f64 => consts::ATTRIBUTE_DOUBLE, PrimitiveBuilder<Float64Type>, plain, optional;
+        bool: bool => consts::ATTRIBUTE_BOOL, BooleanBuilder, plain, optional;
+        bytes: &[u8] => consts::ATTRIBUTE_BYTES, BinaryBuilder, dict16, optional;
+        /// Appends a CBOR serialized map or slice value.
+        ser: &[u8] => consts::ATTRIBUTE_SER, BinaryBuilder, dict16, optional;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the attributes record batches whose parent ids are 32 bits, e.g. the
+    /// `SPAN_EVENT_ATTRS` payload.
+    pub struct Attrs32BatchBuilder for SpanEventAttrs {
+        /// Appends the id of the parent, delta encoded between the consecutive attributes
+        /// with the same key and value.
+        parent_id: u32 => consts::PARENT_ID, PrimitiveBuilder<UInt32Type>, dict8, required;
+        key: &str => consts::ATTRIBUTE_KEY, StringBuilder, dict8, required;
+        /// Appends the type of the value, which selects the column holding it.
+        r#type: u8 => consts::ATTRIBUTE_TYPE, PrimitiveBuilder<UInt8Type>, plain, required;
+        str: &str => consts::ATTRIBUTE_STR, StringBuilder, dict16, required;
+        int: i64 => consts::ATTRIBUTE_INT, PrimitiveBuilder<Int64Type>, dict16, optional;
+        double: f64 => consts::ATTRIBUTE_DOUBLE, PrimitiveBuilder<Float64Type>, plain, optional;
+        bool: bool => consts::ATTRIBUTE_BOOL, BooleanBuilder, plain, optional;
+        bytes: &[u8] => consts::ATTRIBUTE_BYTES, BinaryBuilder, dict16, optional;
+        /// Appends a CBOR serialized map or slice value.
+        ser: &[u8] => consts::ATTRIBUTE_SER, BinaryBuilder, dict16, optional;
+    }
+}
+
+/// Builder of the `RESOURCE_ATTRS` record batches.
+pub type ResourceAttrsBatchBuilder = Attrs16BatchBuilder;
+/// Builder of the `SCOPE_ATTRS` record batches.
+pub type ScopeAttrsBatchBuilder = Attrs16BatchBuilder;
+/// Builder of the `LOG_ATTRS` record batches.
+pub type LogAttrsBatchBuilder = Attrs16BatchBuilder;
+/// Builder of the `SPAN_ATTRS` record batches.
+pub type SpanAttrsBatchBuilder = Attrs16BatchBuilder;
+/// Builder of the `METRIC_ATTRS` record batches.
+pub type MetricAttrsBatchBuilder = Attrs16BatchBuilder;
+/// Builder of the `SPAN_EVENT_ATTRS` record batches.
+pub type SpanEventAttrsBatchBuilder = Attrs32BatchBuilder;
+/// Builder of the `SPAN_LINK_ATTRS` record batches.
+pub type SpanLinkAttrsBatchBuilder = Attrs32BatchBuilder;
+/// Builder of the `NUMBER_DP_ATTRS` record batches.
+pub type NumberDpAttrsBatchBuilder = Attrs32BatchBuilder;
+/// Builder of the `SUMMARY_DP_ATTRS` record batches.
+pub type SummaryDpAttrsBatchBuilder = Attrs32BatchBuilder;
+/// Builder of the `HISTOGRAM_DP_ATTRS` record batches.
+pub type HistogramDpAttrsBatchBuilder = Attrs32BatchBuilder;
+/// Builder of the `EXP_HISTOGRAM_DP_ATTRS` record batches.
+pub type ExpHistogramDpAttrsBatchBuilder = Attrs32BatchBuilder;
+/// Builder of the `*_DP_EXEMPLAR_ATTRS` record batches.
+pub type ExemplarAttrsBatchBuilder = Attrs32BatchBuilder;
+
+record_batch_builder! {
+    /// Builder of the `LOGS` record batches.
+    pub struct LogsBatchBuilder for Logs {
+        /// Appends the delta encoded id of the log record, see [`LogAttrsBatchBuilder`].
+        id: u16 => consts::ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        schema_url: &str => consts::SCHEMA_URL, StringBuilder, dict8, optional;
+        time_unix_nano: i64 => consts::TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, optional;
+        observed_time_unix_nano: i64 => consts::OBSERVED_TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, optional;
+        trace_id: &[u8; 16] => consts::TRACE_ID, FixedSizeBinaryColumn<16>, dict8, optional;
+        span_id: &[u8; 8] => consts::SPAN_ID, FixedSizeBinaryColumn<8>, dict8, optional;
+        severity_number: i32 => consts::SEVERITY_NUMBER, PrimitiveBuilder<Int32Type>, dict8, optional;
+        severity_text: &str => consts::SEVERITY_TEXT, StringBuilder, dict8, optional;
+        event_name: &str => consts::EVENT_NAME, StringBuilder, dict8, optional;
+        dropped_attributes_count: u32 => consts::DROPPED_ATTRIBUTES_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+        flags: u32 => consts::FLAGS, PrimitiveBuilder<UInt32Type>, plain, optional;
+    }
+    structs {
+        /// Returns the builder of the `resource` column.
+        resource: ResourceColumnBuilder => consts::RESOURCE, required;
+        /// Returns the builder of the `scope` column.
+        scope: ScopeColumnBuilder => consts::SCOPE, required;
+        /// Returns the builder of the `body` column.
+        body: AnyValueColumnBuilder => consts::BODY, optional;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `SPANS` record batches.
+    pub struct SpansBatchBuilder for Spans {
+        /// Appends the delta encoded id of the span, see [`SpanAttrsBatchBuilder`].
+        id: u16 => consts::ID, PrimitiveBuilder<UInt16Type>, plain, optional;
+        schema_url: &str => consts::SCHEMA_URL, StringBuilder, dict8, optional;
+        start_time_unix_nano: i64 => consts::START_TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, required;
+        /// Appends the duration of the span, in nanoseconds despite the milliseconds unit of
+        /// the column.
+        duration_time_unix_nano: i64 => consts::DURATION_TIME_UNIX_NANO, PrimitiveBuilder<DurationMillisecondType>, dict8, required;
+        trace_id: &[u8; 16] => consts::TRACE_ID, FixedSizeBinaryColumn<16>, plain, required;
+        span_id: &[u8; 8] => consts::SPAN_ID, FixedSizeBinaryColumn<8>, plain, required;
+        trace_state: &str => consts::TRACE_STATE, StringBuilder, dict8, optional;
+        parent_span_id: &[u8; 8] => consts::PARENT_SPAN_ID, FixedSizeBinaryColumn<8>, plain, optional;
+        name: &str => consts::NAME, StringBuilder, dict8, required;
+        kind: i32 => consts::KIND, PrimitiveBuilder<Int32Type>, dict8, optional;
+        flags: u32 => consts::FLAGS, PrimitiveBuilder<UInt32Type>, plain, optional;
+        dropped_attributes_count: u32 => consts::DROPPED_ATTRIBUTES_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+        dropped_events_count: u32 => consts::DROPPED_EVENTS_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+        dropped_links_count: u32 => consts::DROPPED_LINKS_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+    }
+    structs {
+        /// Returns the builder of the `resource` column.
+        resource: ResourceColumnBuilder => consts::RESOURCE, required;
+        /// Returns the builder of the `scope` column.
+        scope: ScopeColumnBuilder => consts::SCOPE, required;
+        /// Returns the builder of the `status` column.
+        status: StatusColumnBuilder => consts::STATUS, optional;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `SPAN_EVENTS` record batches.
+    pub struct SpanEventsBatchBuilder for SpanEvents {
+        /// Appends the delta encoded id of the event, see [`SpanEventAttrsBatchBuilder`].
+        id: u32 => consts::ID, PrimitiveBuilder<UInt32Type>, plain, optional;
+        /// Appends the id of the span, delta encoded between consecutive events.
+        parent_id: u16 => consts::PARENT_ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        time_unix_nano: i64 => consts::TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, optional;
+        name: &str => consts::NAME, StringBuilder, dict8, required;
+        dropped_attributes_count: u32 => consts::DROPPED_ATTRIBUTES_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `SPAN_LINKS` record batches.
+    pub struct SpanLinksBatchBuilder for SpanLinks {
+        /// Appends the delta encoded id of the link, see [`SpanLinkAttrsBatchBuilder`].
+        id: u32 => consts::ID, PrimitiveBuilder<UInt32Type>, plain, optional;
+        /// Appends the id of the span, delta encoded between consecutive links.
+        parent_id: u16 => consts::PARENT_ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        trace_id: &[u8; 16] => consts::TRACE_ID, FixedSizeBinaryColumn<16>, dict8, optional;
+        span_id: &[u8; 8] => consts::SPAN_ID, FixedSizeBinaryColumn<8>, dict8, optional;
+        trace_state: &str => consts::TRACE_STATE, StringBuilder, dict8, optional;
+        flags: u32 => consts::FLAGS, PrimitiveBuilder<UInt32Type>, plain, optional;
+        dropped_attributes_count: u32 => consts::DROPPED_ATTRIBUTES_COUNT, PrimitiveBuilder<UInt32Type>, plain, optional;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `UNIVARIATE_METRICS` record batches.
+    pub struct UnivariateMetricsBatchBuilder for UnivariateMetrics {
+        /// Appends the delta encoded id of the metric, see [`MetricAttrsBatchBuilder`].
+        id: u16 => consts::ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        schema_url: &str => consts::SCHEMA_URL, StringBuilder, dict8, optional;
+        /// Appends the metric type, see
+        /// [`MetricType`](crate::otlp::metrics::MetricType).
+        metric_type: u8 => consts::METRIC_TYPE, PrimitiveBuilder<UInt8Type>, plain, required;
+        name: &str => consts::NAME, StringBuilder, dict8, required;
+        description: &str => consts::DESCRIPTION, StringBuilder, dict8, optional;
+        unit: &str => consts::UNIT, StringBuilder, dict8, optional;
+        aggregation_temporality: i32 => consts::AGGREGATION_TEMPORALITY, PrimitiveBuilder<Int32Type>, dict8, optional;
+        is_monotonic: bool => consts::IS_MONOTONIC, BooleanBuilder, plain, optional;
+    }
+    structs {
+        /// Returns the builder of the `resource` column.
+        resource: ResourceColumnBuilder => consts::RESOURCE, required;
+        /// Returns the builder of the `scope` column.
+        scope: ScopeColumnBuilder => consts::SCOPE, required;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `NUMBER_DATA_POINTS` record batches.
+    pub struct NumberDpBatchBuilder for NumberDataPoints {
+        /// Appends the delta encoded id of the data point, see [`NumberDpAttrsBatchBuilder`].
+        id: u32 => consts::ID, PrimitiveBuilder<UInt32Type>, plain, required;
+        /// Appends the id of the metric, delta encoded between consecutive data points.
+        parent_id: u16 => consts::PARENT_ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        start_time_unix_nano: i64 => consts::START_TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, optional;
+        time_unix_nano: i64 => consts::TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, required;
+        int_value: i64 => consts::INT_VALUE, PrimitiveBuilder<Int64Type>, plain, optional;
+        double_value: f64 => consts::DOUBLE_VALUE, PrimitiveBuilder<Float64Type>, plain, optional;
+        flags: u32 => consts::FLAGS, PrimitiveBuilder<UInt32Type>, plain, optional;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `SUMMARY_DATA_POINTS` record batches.
+    pub struct SummaryDpBatchBuilder for SummaryDataPoints {
+        /// Appends the delta encoded id of the data point, see [`SummaryDpAttrsBatchBuilder`].
+        id: u32 => consts::ID, PrimitiveBuilder<UInt32Type>, plain, optional;
+        /// Appends the id of the metric, delta encoded between consecutive data points.
+        parent_id: u16 => consts::PARENT_ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        start_time_unix_nano: i64 => consts::START_TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, optional;
+        time_unix_nano: i64 => consts::TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, required;
+        count: u64 => consts::SUMMARY_COUNT, PrimitiveBuilder<UInt64Type>, plain, required;
+        sum: f64 => consts::SUMMARY_SUM, PrimitiveBuilder<Float64Type>, plain, required;
+        quantile: &[ValueAtQuantile] => consts::SUMMARY_QUANTILE_VALUES, QuantileValuesColumn, plain, required;
+        flags: u32 => consts::FLAGS, PrimitiveBuilder<UInt32Type>, plain, required;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `HISTOGRAM_DATA_POINTS` record batches.
+    pub struct HistogramDpBatchBuilder for HistogramDataPoints {
+        /// Appends the delta encoded id of the data point, see
+        /// [`HistogramDpAttrsBatchBuilder`].
+        id: u32 => consts::ID, PrimitiveBuilder<UInt32Type>, plain, optional;
+        /// Appends the id of the metric, delta encoded between consecutive data points.
+        parent_id: u16 => consts::PARENT_ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        start_time_unix_nano: i64 => consts::START_TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, optional;
+        time_unix_nano: i64 => consts::TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, required;
+        count: u64 => consts::HISTOGRAM_COUNT, PrimitiveBuilder<UInt64Type>, plain, required;
+        sum: f64 => consts::HISTOGRAM_SUM, PrimitiveBuilder<Float64Type>, plain, optional;
+        bucket_counts: &[u64] => consts::HISTOGRAM_BUCKET_COUNTS, ListBuilder<PrimitiveBuilder<UInt64Type>>, plain, required;
+        explicit_bounds: &[f64] => consts::HISTOGRAM_EXPLICIT_BOUNDS, ListBuilder<PrimitiveBuilder<Float64Type>>, plain, required;
+        flags: u32 => consts::FLAGS, PrimitiveBuilder<UInt32Type>, plain, required;
+        min: f64 => consts::HISTOGRAM_MIN, PrimitiveBuilder<Float64Type>, plain, optional;
+        max: f64 => consts::HISTOGRAM_MAX, PrimitiveBuilder<Float64Type>, plain, optional;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `EXP_HISTOGRAM_DATA_POINTS` record batches.
+    pub struct ExpHistogramDpBatchBuilder for ExpHistogramDataPoints {
+        /// Appends the delta encoded id of the data point, see
+        /// [`ExpHistogramDpAttrsBatchBuilder`].
+        id: u32 => consts::ID, PrimitiveBuilder<UInt32Type>, plain, optional;
+        /// Appends the id of the metric, delta encoded between consecutive data points.
+        parent_id: u16 => consts::PARENT_ID, PrimitiveBuilder<UInt16Type>, plain, required;
+        start_time_unix_nano: i64 => consts::START_TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, optional;
+        time_unix_nano: i64 => consts::TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, required;
+        count: u64 => consts::HISTOGRAM_COUNT, PrimitiveBuilder<UInt64Type>, plain, required;
+        sum: f64 => consts::HISTOGRAM_SUM, PrimitiveBuilder<Float64Type>, plain, optional;
+        scale: i32 => consts::EXP_HISTOGRAM_SCALE, PrimitiveBuilder<Int32Type>, plain, required;
+        zero_count: u64 => consts::EXP_HISTOGRAM_ZERO_COUNT, PrimitiveBuilder<UInt64Type>, plain, required;
+        flags: u32 => consts::FLAGS, PrimitiveBuilder<UInt32Type>, plain, optional;
+        min: f64 => consts::HISTOGRAM_MIN, PrimitiveBuilder<Float64Type>, plain, optional;
+        max: f64 => consts::HISTOGRAM_MAX, PrimitiveBuilder<Float64Type>, plain, optional;
+    }
+    structs {
+        /// Returns the builder of the `positive` buckets column.
+        positive: BucketsColumnBuilder => consts::EXP_HISTOGRAM_POSITIVE, required;
+        /// Returns the builder of the `negative` buckets column.
+        negative: BucketsColumnBuilder => consts::EXP_HISTOGRAM_NEGATIVE, required;
+    }
+}
+
+record_batch_builder! {
+    /// Builder of the `*_DP_EXEMPLARS` record batches.
+    pub struct ExemplarsBatchBuilder for NumberDpExemplars {
+        /// Appends the delta encoded id of the exemplar, see [`ExemplarAttrsBatchBuilder`].
+        id: u32 => consts::ID, PrimitiveBuilder<UInt32Type>, plain, optional;
+        /// Appends the id of the data point, delta encoded between consecutive exemplars
+        /// with the same value.
+        parent_id: u32 => consts::PARENT_ID, PrimitiveBuilder<UInt32Type>, plain, required;
+        time_unix_nano: i64 => consts::TIME_UNIX_NANO, PrimitiveBuilder<TimestampNanosecondType>, plain, required;
+        int_value: i64 => consts::INT_VALUE, PrimitiveBuilder<Int64Type>, plain, optional;
+        double_value: f64 => consts::DOUBLE_VALUE, PrimitiveBuilder<Float64Type>, plain, optional;
+        span_id: &[u8; 8] => consts::SPAN_ID, FixedSizeBinaryColumn<8>, plain, required;
+        trace_id: &[u8; 16] => consts::TRACE_ID, FixedSizeBinaryColumn<16>, plain, required;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrow::datatypes::DataType;
+
+    use crate::otap::{OtapBatch, Traces};
+    use crate::otlp::traces::traces_from;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+    use crate::proto::opentelemetry::trace::v1::Status;
+
+    #[test]
+    fn test_spans_batch_builder() {
+        let mut spans = SpansBatchBuilder::new();
+        for i in 0..2u8 {
+            spans.append_id(Some(i.into()));
+            spans.resource().append_id(Some(0));
+            spans.scope().append_id(Some(0));
+            spans.scope().append_name(Some("scope"));
+            spans.append_start_time_unix_nano(Some(100));
+            spans.append_duration_time_unix_nano(Some(10));
+            spans.append_trace_id(Some(&[1; 16]));
+            spans.append_span_id(Some(&[i; 8]));
+            spans.append_parent_span_id((i == 1).then_some(&[0; 8]));
+            spans.append_name(Some("span"));
+        }
+        spans.status().append_code(Some(2));
+        spans.status().append_status_message(Some("boom"));
+        spans.status().append_null();
+        let spans = spans.finish().unwrap();
+        assert_eq!(spans.num_rows(), 2);
+        assert_eq!(
+            spans.column_by_name(consts::NAME).unwrap().data_type(),
+            &DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8))
+        );
+        assert!(spans.column_by_name(consts::KIND).is_none());
+
+        let mut span_attrs = SpanAttrsBatchBuilder::new();
+        span_attrs.append_parent_id(Some(1));
+        span_attrs.append_key(Some("k"));
+        span_attrs.append_type(Some(1));
+        span_attrs.append_str(Some("v"));
+        let span_attrs = span_attrs.finish().unwrap();
+
+        let mut otap_batch = OtapBatch::Traces(Traces::default());
+        otap_batch.set(ArrowPayloadType::Spans, spans);
+        otap_batch.set(ArrowPayloadType::SpanAttrs, span_attrs);
+        let traces = traces_from(otap_batch).unwrap();
+        let spans = &traces.resource_spans[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].name, "span");
+        assert_eq!(spans[0].end_time_unix_nano, 110);
+        assert_eq!(
+            spans[0].status,
+            Some(Status {
+                code: 2,
+                message: "boom".to_string(),
+            })
+        );
+        assert!(spans[0].attributes.is_empty());
+        assert_eq!(spans[1].parent_span_id, vec![0; 8]);
+        assert_eq!(spans[1].attributes, vec![KeyValue {
+            key: "k".to_string(),
+            value: Some(AnyValue {
+                value: Some(Value::StringValue("v".to_string())),
+            }),
+        }]);
+    }
+
+    #[test]
+    fn test_data_points_batch_builders() {
+        let mut exp_histograms = ExpHistogramDpBatchBuilder::new();
+        exp_histograms.append_parent_id(Some(0));
+        exp_histograms.append_time_unix_nano(Some(1));
+        exp_histograms.append_count(Some(3));
+        exp_histograms.append_scale(Some(2));
+        exp_histograms.append_zero_count(Some(0));
+        exp_histograms.positive().append_offset(Some(1));
+        exp_histograms
+            .positive()
+            .append_bucket_counts(Some(&[1, 2]));
+        let exp_histograms = exp_histograms.finish().unwrap();
+        definitions::validate_schema(
+            ArrowPayloadType::ExpHistogramDataPoints,
+            &exp_histograms.schema(),
+        )
+        .unwrap();
+        let negative = exp_histograms
+            .column_by_name(consts::EXP_HISTOGRAM_NEGATIVE)
+            .unwrap();
+        assert_eq!(negative.len(), 1);
+
+        let mut summaries = SummaryDpBatchBuilder::new();
+        for quantiles in [None, Some(&[ValueAtQuantile::default()][..])] {
+            summaries.append_parent_id(Some(0));
+            summaries.append_time_unix_nano(Some(1));
+            summaries.append_count(Some(1));
+            summaries.append_sum(Some(1.0));
+            summaries.append_quantile(quantiles);
+            summaries.append_flags(Some(0));
+        }
+        let summaries = summaries.finish().unwrap();
+        let quantiles = summaries
+            .column_by_name(consts::SUMMARY_QUANTILE_VALUES)
+            .unwrap();
+        assert_eq!(quantiles.null_count(), 1);
+        assert!(
+            definitions::columns(ArrowPayloadType::SummaryDataPoints)
+                .iter()
+                .all(
+                    |definition| summaries.column_by_name(definition.name).is_some()
+                        || definition.nullable
+                )
+        );
+    }
+}
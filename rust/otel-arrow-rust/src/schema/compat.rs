@@ -0,0 +1,289 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Comparison of the schemas of a payload type, e.g. the schema a stream switches to on a
+//! reset against the one it replaces, or the schema of a producer against the canonical
+//! one of this crate.
+//!
+//! [`compare_schemas`] lists the columns added, removed or changed between two schemas and
+//! gives each change a verdict against the [`definitions`](crate::schema::definitions) of
+//! the payload type: a change is compatible if this crate decodes the new schema like the
+//! old one. The columns not defined for the payload type are ignored by the decoder and the
+//! defined ones can be left out, so the only incompatible changes are the defined columns
+//! taking a type their definition does not accept.
+
+use std::fmt;
+
+use arrow::datatypes::{DataType, Schema};
+
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::definitions::{columns, otap_schema};
+
+/// Whether the decoder reads a schema change like the schema it replaces.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Compatibility {
+    /// The new schema is decoded like the old one.
+    Compatible,
+    /// The new schema has columns the decoder rejects.
+    Incompatible,
+}
+
+/// Change of a column between two schemas.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColumnChange {
+    /// The column is only in the new schema.
+    Added {
+        /// Type of the column.
+        data_type: DataType,
+    },
+    /// The column is only in the old schema.
+    Removed {
+        /// Type the column had.
+        data_type: DataType,
+    },
+    /// The type of the column changed.
+    TypeChanged {
+        /// Type of the column in the old schema.
+        from: DataType,
+        /// Type of the column in the new schema.
+        to: DataType,
+    },
+    /// The column became nullable, or stopped being.
+    NullabilityChanged {
+        /// Whether the column is nullable in the new schema.
+        nullable: bool,
+    },
+}
+
+/// Change of a column with its verdict.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaChange {
+    /// Name of the column.
+    pub column: String,
+    /// How the column changed.
+    pub change: ColumnChange,
+    /// Verdict of the change.
+    pub compatibility: Compatibility,
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self.compatibility {
+            Compatibility::Compatible => "compatible",
+            Compatibility::Incompatible => "incompatible",
+        };
+        match &self.change {
+            ColumnChange::Added { data_type } => {
+                write!(f, "{verdict}: column {} added as {data_type}", self.column)
+            }
+            ColumnChange::Removed { data_type } => {
+                write!(
+                    f,
+                    "{verdict}: column {} of {data_type} removed",
+                    self.column
+                )
+            }
+            ColumnChange::TypeChanged { from, to } => {
+                write!(
+                    f,
+                    "{verdict}: column {} changed from {from} to {to}",
+                    self.column
+                )
+            }
+            ColumnChange::NullabilityChanged { nullable } => {
+                let nullable = if *nullable {
+                    "nullable"
+                } else {
+                    "not nullable"
+                };
+                write!(f, "{verdict}: column {} made {nullable}", self.column)
+            }
+        }
+    }
+}
+
+/// Changes between two schemas of a payload type, in the order of the columns of the old
+/// schema then of the added ones.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchemaDiff {
+    /// The changes of the columns.
+    pub changes: Vec<SchemaChange>,
+}
+
+impl SchemaDiff {
+    /// Returns the worst verdict of the changes, compatible if there are none.
+    #[must_use]
+    pub fn compatibility(&self) -> Compatibility {
+        self.changes
+            .iter()
+            .map(|change| change.compatibility)
+            .max()
+            .unwrap_or(Compatibility::Compatible)
+    }
+
+    /// Returns whether all the changes are compatible.
+    #[must_use]
+    pub fn is_compatible(&self) -> bool {
+        self.compatibility() == Compatibility::Compatible
+    }
+
+    /// Returns whether the schemas have the same columns.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns the incompatible changes.
+    pub fn incompatible(&self) -> impl Iterator<Item = &SchemaChange> {
+        self.changes
+            .iter()
+            .filter(|change| change.compatibility == Compatibility::Incompatible)
+    }
+}
+
+/// Compares the schema `new` of the payload type to the schema `old` it replaces.
+#[must_use]
+pub fn compare_schemas(payload_type: ArrowPayloadType, old: &Schema, new: &Schema) -> SchemaDiff {
+    let definitions = columns(payload_type);
+    // the decoder ignores the columns it does not define
+    let verdict = |column: &str, data_type: &DataType| match definitions
+        .iter()
+        .find(|definition| definition.name == column)
+    {
+        Some(definition) if !definition.accepts(data_type) => Compatibility::Incompatible,
+        _ => Compatibility::Compatible,
+    };
+
+    let mut changes = Vec::new();
+    for old_field in old.fields() {
+        let column = old_field.name();
+        let Ok(new_field) = new.field_with_name(column) else {
+            changes.push(SchemaChange {
+                column: column.clone(),
+                change: ColumnChange::Removed {
+                    data_type: old_field.data_type().clone(),
+                },
+                compatibility: Compatibility::Compatible,
+            });
+            continue;
+        };
+        if old_field.data_type() != new_field.data_type() {
+            changes.push(SchemaChange {
+                column: column.clone(),
+                change: ColumnChange::TypeChanged {
+                    from: old_field.data_type().clone(),
+                    to: new_field.data_type().clone(),
+                },
+                compatibility: verdict(column, new_field.data_type()),
+            });
+        }
+        if old_field.is_nullable() != new_field.is_nullable() {
+            changes.push(SchemaChange {
+                column: column.clone(),
+                change: ColumnChange::NullabilityChanged {
+                    nullable: new_field.is_nullable(),
+                },
+                compatibility: Compatibility::Compatible,
+            });
+        }
+    }
+    for new_field in new.fields() {
+        let column = new_field.name();
+        if old.field_with_name(column).is_err() {
+            changes.push(SchemaChange {
+                column: column.clone(),
+                change: ColumnChange::Added {
+                    data_type: new_field.data_type().clone(),
+                },
+                compatibility: verdict(column, new_field.data_type()),
+            });
+        }
+    }
+    SchemaDiff { changes }
+}
+
+/// Compares the schema of a producer to the canonical schema of the payload type in this
+/// crate, see [`otap_schema`].
+#[must_use]
+pub fn compare_to_canonical(payload_type: ArrowPayloadType, schema: &Schema) -> SchemaDiff {
+    compare_schemas(payload_type, &otap_schema(payload_type), schema)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use arrow::datatypes::Field;
+
+    use crate::schema::consts;
+
+    fn dictionary(key: DataType) -> DataType {
+        DataType::Dictionary(Box::new(key), Box::new(DataType::Utf8))
+    }
+
+    #[test]
+    fn test_compare_schemas() {
+        let spans = ArrowPayloadType::Spans;
+        let old = Schema::new(vec![
+            Field::new(consts::ID, DataType::UInt16, true),
+            Field::new(consts::NAME, dictionary(DataType::UInt8), false),
+            Field::new(consts::KIND, DataType::Int32, true),
+        ]);
+        assert!(compare_schemas(spans, &old, &old).is_empty());
+
+        let new = Schema::new(vec![
+            Field::new(consts::ID, DataType::UInt16, false),
+            Field::new(consts::NAME, DataType::Utf8, false),
+            Field::new("custom", DataType::Float32, true),
+        ]);
+        let diff = compare_schemas(spans, &old, &new);
+        assert_eq!(diff.changes, vec![
+            SchemaChange {
+                column: consts::ID.to_string(),
+                change: ColumnChange::NullabilityChanged { nullable: false },
+                compatibility: Compatibility::Compatible,
+            },
+            SchemaChange {
+                column: consts::NAME.to_string(),
+                change: ColumnChange::TypeChanged {
+                    from: dictionary(DataType::UInt8),
+                    to: DataType::Utf8,
+                },
+                compatibility: Compatibility::Compatible,
+            },
+            SchemaChange {
+                column: consts::KIND.to_string(),
+                change: ColumnChange::Removed {
+                    data_type: DataType::Int32,
+                },
+                compatibility: Compatibility::Compatible,
+            },
+            SchemaChange {
+                column: "custom".to_string(),
+                change: ColumnChange::Added {
+                    data_type: DataType::Float32,
+                },
+                compatibility: Compatibility::Compatible,
+            },
+        ]);
+        assert!(diff.is_compatible());
+
+        // a defined column taking a type its definition does not accept
+        let new = Schema::new(vec![Field::new(consts::NAME, DataType::Int64, false)]);
+        let diff = compare_schemas(spans, &old, &new);
+        assert_eq!(diff.compatibility(), Compatibility::Incompatible);
+        let incompatible: Vec<_> = diff.incompatible().map(|c| c.to_string()).collect();
+        assert_eq!(incompatible, vec![format!(
+            "incompatible: column {} changed from {} to Int64",
+            consts::NAME,
+            dictionary(DataType::UInt8)
+        )]);
+
+        // the canonical schema only differs from the producers' by the left out columns
+        let diff = compare_to_canonical(spans, &otap_schema(spans));
+        assert!(diff.is_empty());
+        let diff = compare_to_canonical(spans, &old);
+        assert!(diff.is_compatible());
+        assert!(!diff.is_empty());
+    }
+}
@@ -22,6 +22,7 @@ pub const DURATION_TIME_UNIX_NANO: &str = "duration_time_unix_nano";
 pub const OBSERVED_TIME_UNIX_NANO: &str = "observed_time_unix_nano";
 pub const SEVERITY_NUMBER: &str = "severity_number";
 pub const SEVERITY_TEXT: &str = "severity_text";
+pub const EVENT_NAME: &str = "event_name";
 pub const DROPPED_ATTRIBUTES_COUNT: &str = "dropped_attributes_count";
 pub const DROPPED_EVENTS_COUNT: &str = "dropped_events_count";
 pub const DROPPED_LINKS_COUNT: &str = "dropped_links_count";
@@ -0,0 +1,592 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Definitions of the columns of the OTAP payloads.
+//!
+//! Every payload type has a constant list of [`ColumnDefinition`]s giving the name, type,
+//! nullability and dictionary encoding of its columns, from which [`otap_schema`] derives
+//! the canonical Arrow schema of the payload and [`validate_schema`] checks the schema of a
+//! received record batch. The typed builders of [`builders`](crate::schema::builders) check
+//! the record batches they produce against these definitions.
+//!
+//! The lists are checked at compile time to not define a column twice.
+
+use std::sync::Arc;
+
+use arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use snafu::ensure;
+
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts;
+
+use self::ColumnDefinition as C;
+use self::ColumnType as T;
+use self::DictionaryKey::{U8, U16};
+
+/// Widest key type a column can be dictionary encoded with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DictionaryKey {
+    /// `UInt8` keys.
+    U8,
+    /// `UInt16` keys, or `UInt8` keys when they fit.
+    U16,
+}
+
+impl DictionaryKey {
+    fn data_type(self) -> DataType {
+        match self {
+            Self::U8 => DataType::UInt8,
+            Self::U16 => DataType::UInt16,
+        }
+    }
+
+    fn accepts(self, key_type: &DataType) -> bool {
+        match self {
+            Self::U8 => *key_type == DataType::UInt8,
+            Self::U16 => matches!(key_type, DataType::UInt8 | DataType::UInt16),
+        }
+    }
+}
+
+/// Type of the values of a column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColumnType {
+    Boolean,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    Int32,
+    Int64,
+    Float64,
+    /// Strings, also accepted as large or view strings.
+    Utf8,
+    /// Bytes, also accepted as large or view binaries.
+    Binary,
+    FixedSizeBinary(i32),
+    TimestampNanosecond,
+    DurationMillisecond,
+    /// List of nullable values of the given type.
+    List(&'static ColumnType),
+    /// Struct of the given fields.
+    Struct(&'static [ColumnDefinition]),
+}
+
+impl ColumnType {
+    /// Returns the canonical Arrow type of the values.
+    #[must_use]
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Self::Boolean => DataType::Boolean,
+            Self::UInt8 => DataType::UInt8,
+            Self::UInt16 => DataType::UInt16,
+            Self::UInt32 => DataType::UInt32,
+            Self::UInt64 => DataType::UInt64,
+            Self::Int32 => DataType::Int32,
+            Self::Int64 => DataType::Int64,
+            Self::Float64 => DataType::Float64,
+            Self::Utf8 => DataType::Utf8,
+            Self::Binary => DataType::Binary,
+            Self::FixedSizeBinary(size) => DataType::FixedSizeBinary(*size),
+            Self::TimestampNanosecond => DataType::Timestamp(TimeUnit::Nanosecond, None),
+            Self::DurationMillisecond => DataType::Duration(TimeUnit::Millisecond),
+            Self::List(item) => DataType::new_list(item.data_type(), true),
+            Self::Struct(fields) => DataType::Struct(fields_of(fields)),
+        }
+    }
+
+    /// Returns whether the values can be of the given Arrow type.
+    #[must_use]
+    pub fn accepts(&self, data_type: &DataType) -> bool {
+        match (self, data_type) {
+            (Self::Utf8, DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View) => true,
+            (Self::Binary, DataType::Binary | DataType::LargeBinary | DataType::BinaryView) => true,
+            (Self::List(item), DataType::List(field)) => item.accepts(field.data_type()),
+            (Self::Struct(definitions), DataType::Struct(fields)) => fields.iter().all(|field| {
+                definitions
+                    .iter()
+                    .find(|definition| definition.name == field.name())
+                    .is_some_and(|definition| definition.accepts(field.data_type()))
+            }),
+            (Self::List(_) | Self::Struct(_), _) => false,
+            _ => self.data_type() == *data_type,
+        }
+    }
+}
+
+/// Definition of a column of an OTAP payload.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColumnDefinition {
+    /// Name of the column.
+    pub name: &'static str,
+    /// Type of the values of the column.
+    pub column_type: ColumnType,
+    /// Whether the column can hold nulls.
+    pub nullable: bool,
+    /// Widest key type the column is dictionary encoded with, if it is.
+    pub dictionary: Option<DictionaryKey>,
+}
+
+impl ColumnDefinition {
+    const fn new(name: &'static str, column_type: ColumnType) -> Self {
+        Self {
+            name,
+            column_type,
+            nullable: false,
+            dictionary: None,
+        }
+    }
+
+    const fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    const fn dictionary(mut self, key: DictionaryKey) -> Self {
+        self.dictionary = Some(key);
+        self
+    }
+
+    /// Returns the canonical Arrow type of the column, dictionary encoded with the widest
+    /// key type if the column is.
+    #[must_use]
+    pub fn data_type(&self) -> DataType {
+        let data_type = self.column_type.data_type();
+        match self.dictionary {
+            Some(key) => DataType::Dictionary(Box::new(key.data_type()), Box::new(data_type)),
+            None => data_type,
+        }
+    }
+
+    /// Returns the canonical Arrow field of the column.
+    #[must_use]
+    pub fn field(&self) -> Field {
+        Field::new(self.name, self.data_type(), self.nullable)
+    }
+
+    /// Returns whether the column can be of the given Arrow type. The dictionary encoded
+    /// columns can also be left as is, or use a narrower key type.
+    #[must_use]
+    pub fn accepts(&self, data_type: &DataType) -> bool {
+        match (self.dictionary, data_type) {
+            (Some(key), DataType::Dictionary(key_type, value_type)) => {
+                key.accepts(key_type) && self.column_type.accepts(value_type)
+            }
+            _ => self.column_type.accepts(data_type),
+        }
+    }
+}
+
+fn fields_of(definitions: &[ColumnDefinition]) -> Fields {
+    definitions
+        .iter()
+        .map(ColumnDefinition::field)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+const fn same_name(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn unique_names(definitions: &[ColumnDefinition]) -> bool {
+    let mut i = 0;
+    while i < definitions.len() {
+        let mut j = i + 1;
+        while j < definitions.len() {
+            if same_name(definitions[i].name, definitions[j].name) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Defines a list of column definitions, checked at compile time to have unique names.
+macro_rules! definitions {
+    ($(#[$meta:meta])* $name:ident = [$($column:expr),* $(,)?]) => {
+        $(#[$meta])*
+        pub const $name: &[ColumnDefinition] = &[$($column),*];
+        const _: () = assert!(unique_names($name), "column defined twice");
+    };
+}
+
+definitions! {
+    /// Fields of the `resource` struct column.
+    RESOURCE = [
+        C::new(consts::ID, T::UInt16).nullable(),
+        C::new(consts::SCHEMA_URL, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::DROPPED_ATTRIBUTES_COUNT, T::UInt32).nullable(),
+    ]
+}
+
+definitions! {
+    /// Fields of the `scope` struct column.
+    SCOPE = [
+        C::new(consts::ID, T::UInt16).nullable(),
+        C::new(consts::NAME, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::VERSION, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::DROPPED_ATTRIBUTES_COUNT, T::UInt32).nullable(),
+    ]
+}
+
+definitions! {
+    /// Fields of the `status` struct column of the spans.
+    STATUS = [
+        C::new(consts::STATUS_CODE, T::Int32).nullable().dictionary(U8),
+        C::new(consts::STATUS_MESSAGE, T::Utf8).nullable().dictionary(U8),
+    ]
+}
+
+definitions! {
+    /// Fields of the `body` struct column of the logs, an `AnyValue` like the attributes.
+    ANY_VALUE = [
+        C::new(consts::ATTRIBUTE_TYPE, T::UInt8),
+        C::new(consts::ATTRIBUTE_STR, T::Utf8).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_INT, T::Int64).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_DOUBLE, T::Float64).nullable(),
+        C::new(consts::ATTRIBUTE_BOOL, T::Boolean).nullable(),
+        C::new(consts::ATTRIBUTE_BYTES, T::Binary).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_SER, T::Binary).nullable().dictionary(U16),
+    ]
+}
+
+definitions! {
+    /// Fields of the `positive` and `negative` struct columns of the exponential histogram
+    /// data points.
+    BUCKETS = [
+        C::new(consts::EXP_HISTOGRAM_OFFSET, T::Int32),
+        C::new(consts::EXP_HISTOGRAM_BUCKET_COUNTS, T::List(&T::UInt64)),
+    ]
+}
+
+definitions! {
+    /// Fields of the structs of the `quantile` list column of the summary data points.
+    QUANTILE_VALUE = [
+        C::new(consts::SUMMARY_QUANTILE, T::Float64),
+        C::new(consts::SUMMARY_VALUE, T::Float64),
+    ]
+}
+
+definitions! {
+    /// Columns of the attributes payloads whose parent ids are 16 bits.
+    ATTRS_16 = [
+        C::new(consts::PARENT_ID, T::UInt16),
+        C::new(consts::ATTRIBUTE_KEY, T::Utf8).dictionary(U8),
+        C::new(consts::ATTRIBUTE_TYPE, T::UInt8),
+        C::new(consts::ATTRIBUTE_STR, T::Utf8).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_INT, T::Int64).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_DOUBLE, T::Float64).nullable(),
+        C::new(consts::ATTRIBUTE_BOOL, T::Boolean).nullable(),
+        C::new(consts::ATTRIBUTE_BYTES, T::Binary).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_SER, T::Binary).nullable().dictionary(U16),
+    ]
+}
+
+definitions! {
+    /// Columns of the attributes payloads whose parent ids are 32 bits.
+    ATTRS_32 = [
+        C::new(consts::PARENT_ID, T::UInt32).dictionary(U8),
+        C::new(consts::ATTRIBUTE_KEY, T::Utf8).dictionary(U8),
+        C::new(consts::ATTRIBUTE_TYPE, T::UInt8),
+        C::new(consts::ATTRIBUTE_STR, T::Utf8).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_INT, T::Int64).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_DOUBLE, T::Float64).nullable(),
+        C::new(consts::ATTRIBUTE_BOOL, T::Boolean).nullable(),
+        C::new(consts::ATTRIBUTE_BYTES, T::Binary).nullable().dictionary(U16),
+        C::new(consts::ATTRIBUTE_SER, T::Binary).nullable().dictionary(U16),
+    ]
+}
+
+definitions! {
+    /// Columns of the `LOGS` payload.
+    LOGS = [
+        C::new(consts::ID, T::UInt16).nullable(),
+        C::new(consts::RESOURCE, T::Struct(RESOURCE)).nullable(),
+        C::new(consts::SCOPE, T::Struct(SCOPE)).nullable(),
+        C::new(consts::SCHEMA_URL, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::TIME_UNIX_NANO, T::TimestampNanosecond).nullable(),
+        C::new(consts::OBSERVED_TIME_UNIX_NANO, T::TimestampNanosecond).nullable(),
+        C::new(consts::TRACE_ID, T::FixedSizeBinary(16)).nullable().dictionary(U8),
+        C::new(consts::SPAN_ID, T::FixedSizeBinary(8)).nullable().dictionary(U8),
+        C::new(consts::SEVERITY_NUMBER, T::Int32).nullable().dictionary(U8),
+        C::new(consts::SEVERITY_TEXT, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::EVENT_NAME, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::BODY, T::Struct(ANY_VALUE)).nullable(),
+        C::new(consts::DROPPED_ATTRIBUTES_COUNT, T::UInt32).nullable(),
+        C::new(consts::FLAGS, T::UInt32).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `SPANS` payload.
+    SPANS = [
+        C::new(consts::ID, T::UInt16).nullable(),
+        C::new(consts::RESOURCE, T::Struct(RESOURCE)).nullable(),
+        C::new(consts::SCOPE, T::Struct(SCOPE)).nullable(),
+        C::new(consts::SCHEMA_URL, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::START_TIME_UNIX_NANO, T::TimestampNanosecond),
+        C::new(consts::DURATION_TIME_UNIX_NANO, T::DurationMillisecond).dictionary(U8),
+        C::new(consts::TRACE_ID, T::FixedSizeBinary(16)).dictionary(U8),
+        C::new(consts::SPAN_ID, T::FixedSizeBinary(8)).dictionary(U8),
+        C::new(consts::TRACE_STATE, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::PARENT_SPAN_ID, T::FixedSizeBinary(8)).nullable().dictionary(U8),
+        C::new(consts::NAME, T::Utf8).dictionary(U8),
+        C::new(consts::KIND, T::Int32).nullable().dictionary(U8),
+        C::new(consts::FLAGS, T::UInt32).nullable(),
+        C::new(consts::DROPPED_ATTRIBUTES_COUNT, T::UInt32).nullable(),
+        C::new(consts::DROPPED_EVENTS_COUNT, T::UInt32).nullable(),
+        C::new(consts::DROPPED_LINKS_COUNT, T::UInt32).nullable(),
+        C::new(consts::STATUS, T::Struct(STATUS)).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `SPAN_EVENTS` payload.
+    SPAN_EVENTS = [
+        C::new(consts::ID, T::UInt32).nullable(),
+        C::new(consts::PARENT_ID, T::UInt16),
+        C::new(consts::TIME_UNIX_NANO, T::TimestampNanosecond).nullable(),
+        C::new(consts::NAME, T::Utf8).dictionary(U8),
+        C::new(consts::DROPPED_ATTRIBUTES_COUNT, T::UInt32).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `SPAN_LINKS` payload.
+    SPAN_LINKS = [
+        C::new(consts::ID, T::UInt32).nullable(),
+        C::new(consts::PARENT_ID, T::UInt16),
+        C::new(consts::TRACE_ID, T::FixedSizeBinary(16)).nullable().dictionary(U8),
+        C::new(consts::SPAN_ID, T::FixedSizeBinary(8)).nullable().dictionary(U8),
+        C::new(consts::TRACE_STATE, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::FLAGS, T::UInt32).nullable(),
+        C::new(consts::DROPPED_ATTRIBUTES_COUNT, T::UInt32).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `UNIVARIATE_METRICS` payload.
+    UNIVARIATE_METRICS = [
+        C::new(consts::ID, T::UInt16),
+        C::new(consts::RESOURCE, T::Struct(RESOURCE)).nullable(),
+        C::new(consts::SCOPE, T::Struct(SCOPE)).nullable(),
+        C::new(consts::SCHEMA_URL, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::METRIC_TYPE, T::UInt8),
+        C::new(consts::NAME, T::Utf8).dictionary(U8),
+        C::new(consts::DESCRIPTION, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::UNIT, T::Utf8).nullable().dictionary(U8),
+        C::new(consts::AGGREGATION_TEMPORALITY, T::Int32).nullable().dictionary(U8),
+        C::new(consts::IS_MONOTONIC, T::Boolean).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `NUMBER_DATA_POINTS` payload.
+    NUMBER_DATA_POINTS = [
+        C::new(consts::ID, T::UInt32),
+        C::new(consts::PARENT_ID, T::UInt16),
+        C::new(consts::START_TIME_UNIX_NANO, T::TimestampNanosecond).nullable(),
+        C::new(consts::TIME_UNIX_NANO, T::TimestampNanosecond),
+        C::new(consts::INT_VALUE, T::Int64).nullable(),
+        C::new(consts::DOUBLE_VALUE, T::Float64).nullable(),
+        C::new(consts::FLAGS, T::UInt32).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `SUMMARY_DATA_POINTS` payload.
+    SUMMARY_DATA_POINTS = [
+        C::new(consts::ID, T::UInt32).nullable(),
+        C::new(consts::PARENT_ID, T::UInt16),
+        C::new(consts::START_TIME_UNIX_NANO, T::TimestampNanosecond).nullable(),
+        C::new(consts::TIME_UNIX_NANO, T::TimestampNanosecond),
+        C::new(consts::SUMMARY_COUNT, T::UInt64),
+        C::new(consts::SUMMARY_SUM, T::Float64),
+        C::new(
+            consts::SUMMARY_QUANTILE_VALUES,
+            T::List(&T::Struct(QUANTILE_VALUE)),
+        ),
+        C::new(consts::FLAGS, T::UInt32).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `HISTOGRAM_DATA_POINTS` payload.
+    HISTOGRAM_DATA_POINTS = [
+        C::new(consts::ID, T::UInt32).nullable(),
+        C::new(consts::PARENT_ID, T::UInt16),
+        C::new(consts::START_TIME_UNIX_NANO, T::TimestampNanosecond).nullable(),
+        C::new(consts::TIME_UNIX_NANO, T::TimestampNanosecond),
+        C::new(consts::HISTOGRAM_COUNT, T::UInt64),
+        C::new(consts::HISTOGRAM_SUM, T::Float64).nullable(),
+        C::new(consts::HISTOGRAM_BUCKET_COUNTS, T::List(&T::UInt64)),
+        C::new(consts::HISTOGRAM_EXPLICIT_BOUNDS, T::List(&T::Float64)),
+        C::new(consts::FLAGS, T::UInt32).nullable(),
+        C::new(consts::HISTOGRAM_MIN, T::Float64).nullable(),
+        C::new(consts::HISTOGRAM_MAX, T::Float64).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the `EXP_HISTOGRAM_DATA_POINTS` payload.
+    EXP_HISTOGRAM_DATA_POINTS = [
+        C::new(consts::ID, T::UInt32).nullable(),
+        C::new(consts::PARENT_ID, T::UInt16),
+        C::new(consts::START_TIME_UNIX_NANO, T::TimestampNanosecond).nullable(),
+        C::new(consts::TIME_UNIX_NANO, T::TimestampNanosecond),
+        C::new(consts::HISTOGRAM_COUNT, T::UInt64),
+        C::new(consts::HISTOGRAM_SUM, T::Float64).nullable(),
+        C::new(consts::EXP_HISTOGRAM_SCALE, T::Int32),
+        C::new(consts::EXP_HISTOGRAM_ZERO_COUNT, T::UInt64),
+        C::new(consts::EXP_HISTOGRAM_POSITIVE, T::Struct(BUCKETS)),
+        C::new(consts::EXP_HISTOGRAM_NEGATIVE, T::Struct(BUCKETS)),
+        C::new(consts::FLAGS, T::UInt32).nullable(),
+        C::new(consts::HISTOGRAM_MIN, T::Float64).nullable(),
+        C::new(consts::HISTOGRAM_MAX, T::Float64).nullable(),
+    ]
+}
+
+definitions! {
+    /// Columns of the exemplars payloads.
+    EXEMPLARS = [
+        C::new(consts::ID, T::UInt32).nullable(),
+        C::new(consts::PARENT_ID, T::UInt32),
+        C::new(consts::TIME_UNIX_NANO, T::TimestampNanosecond),
+        C::new(consts::INT_VALUE, T::Int64).nullable(),
+        C::new(consts::DOUBLE_VALUE, T::Float64).nullable(),
+        C::new(consts::SPAN_ID, T::FixedSizeBinary(8)).nullable().dictionary(U8),
+        C::new(consts::TRACE_ID, T::FixedSizeBinary(16)).nullable().dictionary(U8),
+    ]
+}
+
+/// Returns the definitions of the columns of the payload type, none for the payload types
+/// the crate doesn't support.
+#[must_use]
+pub fn columns(payload_type: ArrowPayloadType) -> &'static [ColumnDefinition] {
+    match payload_type {
+        ArrowPayloadType::Unknown | ArrowPayloadType::MultivariateMetrics => &[],
+        ArrowPayloadType::ResourceAttrs
+        | ArrowPayloadType::ScopeAttrs
+        | ArrowPayloadType::LogAttrs
+        | ArrowPayloadType::SpanAttrs => ATTRS_16,
+        ArrowPayloadType::NumberDpAttrs
+        | ArrowPayloadType::SummaryDpAttrs
+        | ArrowPayloadType::HistogramDpAttrs
+        | ArrowPayloadType::ExpHistogramDpAttrs
+        | ArrowPayloadType::NumberDpExemplarAttrs
+        | ArrowPayloadType::HistogramDpExemplarAttrs
+        | ArrowPayloadType::ExpHistogramDpExemplarAttrs
+        | ArrowPayloadType::SpanEventAttrs
+        | ArrowPayloadType::SpanLinkAttrs => ATTRS_32,
+        ArrowPayloadType::Logs => LOGS,
+        ArrowPayloadType::Spans => SPANS,
+        ArrowPayloadType::SpanEvents => SPAN_EVENTS,
+        ArrowPayloadType::SpanLinks => SPAN_LINKS,
+        ArrowPayloadType::UnivariateMetrics => UNIVARIATE_METRICS,
+        ArrowPayloadType::NumberDataPoints => NUMBER_DATA_POINTS,
+        ArrowPayloadType::SummaryDataPoints => SUMMARY_DATA_POINTS,
+        ArrowPayloadType::HistogramDataPoints => HISTOGRAM_DATA_POINTS,
+        ArrowPayloadType::ExpHistogramDataPoints => EXP_HISTOGRAM_DATA_POINTS,
+        ArrowPayloadType::NumberDpExemplars
+        | ArrowPayloadType::HistogramDpExemplars
+        | ArrowPayloadType::ExpHistogramDpExemplars => EXEMPLARS,
+    }
+}
+
+/// Returns the canonical schema of the payload type, with all its columns and the widest
+/// dictionary keys.
+#[must_use]
+pub fn otap_schema(payload_type: ArrowPayloadType) -> SchemaRef {
+    Arc::new(Schema::new(fields_of(columns(payload_type))))
+}
+
+/// Checks the columns of the schema have the types defined for the payload type. The
+/// columns not defined for the payload type are ignored, and the defined columns can be
+/// left out.
+pub fn validate_schema(payload_type: ArrowPayloadType, schema: &Schema) -> Result<()> {
+    let definitions = columns(payload_type);
+    for field in schema.fields() {
+        let Some(definition) = definitions.iter().find(|d| d.name == field.name()) else {
+            continue;
+        };
+        ensure!(
+            definition.accepts(field.data_type()),
+            error::ColumnDataTypeMismatchSnafu {
+                name: field.name(),
+                expect: definition.data_type(),
+                actual: field.data_type().clone(),
+            }
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_otap_schema() {
+        let schema = otap_schema(ArrowPayloadType::Spans);
+        let name = schema.field_with_name(consts::NAME).unwrap();
+        assert_eq!(
+            name.data_type(),
+            &DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::Utf8))
+        );
+        assert!(!name.is_nullable());
+        let status = schema.field_with_name(consts::STATUS).unwrap();
+        assert!(matches!(status.data_type(), DataType::Struct(fields) if fields.len() == 2));
+        assert!(otap_schema(ArrowPayloadType::Unknown).fields().is_empty());
+
+        // the canonical schemas pass their own validation
+        for payload_type in (0..64).filter_map(|t| ArrowPayloadType::try_from(t).ok()) {
+            validate_schema(payload_type, &otap_schema(payload_type)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_validate_schema() {
+        let schema = Schema::new(vec![
+            Field::new(consts::PARENT_ID, DataType::UInt16, false),
+            Field::new(
+                consts::ATTRIBUTE_KEY,
+                DataType::Dictionary(Box::new(DataType::UInt8), Box::new(DataType::LargeUtf8)),
+                false,
+            ),
+            Field::new(consts::ATTRIBUTE_STR, DataType::Utf8View, true),
+            Field::new("extension", DataType::Null, true),
+        ]);
+        validate_schema(ArrowPayloadType::LogAttrs, &schema).unwrap();
+        // the parent ids of the span event attributes are 32 bits
+        assert!(validate_schema(ArrowPayloadType::SpanEventAttrs, &schema).is_err());
+
+        let key_type = Field::new(
+            consts::ATTRIBUTE_KEY,
+            DataType::Dictionary(Box::new(DataType::UInt16), Box::new(DataType::Utf8)),
+            false,
+        );
+        assert!(validate_schema(ArrowPayloadType::LogAttrs, &Schema::new(vec![key_type])).is_err());
+
+        let status = Field::new(
+            consts::STATUS,
+            DataType::Struct(vec![Field::new(consts::STATUS_CODE, DataType::Int64, true)].into()),
+            true,
+        );
+        assert!(validate_schema(ArrowPayloadType::Spans, &Schema::new(vec![status])).is_err());
+    }
+}
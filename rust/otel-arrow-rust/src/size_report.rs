@@ -0,0 +1,184 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Comparison of the size of a request encoded as OTLP and as OTAP, to evaluate what OTAP
+//! saves on a given workload.
+//!
+//! [`SizeReport::try_new`] encodes the request as an OTLP protobuf message, compressed with
+//! zstd like an exporter compressing its gRPC messages, and as an OTAP batch whose payloads
+//! are written both without compression and with the zstd body compression of Arrow IPC,
+//! see [`PayloadCompression`]. The OTAP sizes are those of the first batch of a stream: the
+//! payloads carry their schemas and dictionaries, which the later batches of the stream only
+//! send when they change.
+
+use std::fmt::{self, Display};
+
+use arrow::array::RecordBatch;
+use prost::Message;
+use snafu::ResultExt;
+
+use crate::ExportRequest;
+use crate::compression::{PayloadCompression, PayloadWriter};
+use crate::encode::split::schema_id;
+use crate::encode::{LogsProducer, MetricsProducer, TracesProducer};
+use crate::error::{self, Result};
+use crate::proto::opentelemetry::arrow::v1::{ArrowPayload, ArrowPayloadType, BatchArrowRecords};
+
+/// Sizes of a payload of the OTAP batch.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PayloadSize {
+    /// Type of the payload.
+    pub payload_type: ArrowPayloadType,
+    /// Number of rows of the payload.
+    pub rows: usize,
+    /// Size of the Arrow IPC messages of the payload, without compression.
+    pub bytes: usize,
+    /// Size of the Arrow IPC messages of the payload, with zstd compression.
+    pub compressed_bytes: usize,
+}
+
+/// Sizes of a request encoded as OTLP and as OTAP, see the module documentation.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SizeReport {
+    /// Size of the OTLP protobuf message.
+    pub otlp_bytes: usize,
+    /// Size of the OTLP protobuf message, compressed with zstd.
+    pub otlp_compressed_bytes: usize,
+    /// Size of the protobuf encoded `BatchArrowRecords`, without compression.
+    pub otap_bytes: usize,
+    /// Size of the protobuf encoded `BatchArrowRecords`, with zstd compressed payloads.
+    pub otap_compressed_bytes: usize,
+    /// Sizes of the payloads of the OTAP batch, main payload first.
+    pub payloads: Vec<PayloadSize>,
+}
+
+impl SizeReport {
+    /// Encodes the request as OTLP and as OTAP and measures the encodings.
+    pub fn try_new(request: &ExportRequest) -> Result<Self> {
+        let (otlp, otap_batch) = match request {
+            ExportRequest::Logs(request) => (
+                request.encode_to_vec(),
+                LogsProducer::new().produce(request)?,
+            ),
+            ExportRequest::Metrics(request) => (
+                request.encode_to_vec(),
+                MetricsProducer::new().produce(request)?,
+            ),
+            ExportRequest::Traces(request) => (
+                request.encode_to_vec(),
+                TracesProducer::new().produce(request)?,
+            ),
+        };
+        let otlp_compressed = zstd::bulk::compress(&otlp, zstd::DEFAULT_COMPRESSION_LEVEL)
+            .context(error::CompressRequestSnafu)?;
+
+        let mut payloads = Vec::new();
+        let mut plain = BatchArrowRecords::default();
+        let mut compressed = BatchArrowRecords::default();
+        for payload_type in otap_batch.payload_types() {
+            // safety: payload_types only returns types that are present in the batch
+            let record_batch = otap_batch
+                .get(payload_type)
+                .expect("payload type present in batch");
+            let record = write_payload(record_batch, PayloadCompression::None)?;
+            let compressed_record = write_payload(record_batch, PayloadCompression::Zstd)?;
+            payloads.push(PayloadSize {
+                payload_type,
+                rows: record_batch.num_rows(),
+                bytes: record.len(),
+                compressed_bytes: compressed_record.len(),
+            });
+            plain.arrow_payloads.push(ArrowPayload {
+                schema_id: schema_id(payload_type, 0),
+                r#type: payload_type as i32,
+                record,
+            });
+            compressed.arrow_payloads.push(ArrowPayload {
+                schema_id: schema_id(payload_type, 0),
+                r#type: payload_type as i32,
+                record: compressed_record,
+            });
+        }
+
+        Ok(Self {
+            otlp_bytes: otlp.len(),
+            otlp_compressed_bytes: otlp_compressed.len(),
+            otap_bytes: plain.encoded_len(),
+            otap_compressed_bytes: compressed.encoded_len(),
+            payloads,
+        })
+    }
+
+    /// Returns the size of the compressed OTAP batch relative to the compressed OTLP
+    /// message, below 1 when OTAP is smaller.
+    #[must_use]
+    pub fn compressed_ratio(&self) -> f64 {
+        self.otap_compressed_bytes as f64 / self.otlp_compressed_bytes.max(1) as f64
+    }
+}
+
+fn write_payload(record_batch: &RecordBatch, compression: PayloadCompression) -> Result<Vec<u8>> {
+    PayloadWriter::try_new(&record_batch.schema(), compression)?.write(record_batch)
+}
+
+impl Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<28} {:>8} {:>12} {:>12}",
+            "encoding", "rows", "bytes", "zstd"
+        )?;
+        writeln!(
+            f,
+            "{:<28} {:>8} {:>12} {:>12}",
+            "otlp", "", self.otlp_bytes, self.otlp_compressed_bytes
+        )?;
+        writeln!(
+            f,
+            "{:<28} {:>8} {:>12} {:>12}",
+            "otap", "", self.otap_bytes, self.otap_compressed_bytes
+        )?;
+        for payload in &self.payloads {
+            writeln!(
+                f,
+                "  {:<26} {:>8} {:>12} {:>12}",
+                payload.payload_type.as_str_name().to_lowercase(),
+                payload.rows,
+                payload.bytes,
+                payload.compressed_bytes
+            )?;
+        }
+        write!(f, "otap/otlp (zstd): {:.2}", self.compressed_ratio())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::datagen::{DatagenConfig, Generator};
+
+    #[test]
+    fn test_size_report() {
+        let request = Generator::new(DatagenConfig::default()).traces_request(16);
+        let spans: usize = request
+            .resource_spans
+            .iter()
+            .flat_map(|rs| &rs.scope_spans)
+            .map(|ss| ss.spans.len())
+            .sum();
+        let report = SizeReport::try_new(&ExportRequest::Traces(request.clone())).unwrap();
+        assert_eq!(report.otlp_bytes, request.encoded_len());
+        assert!(report.otlp_compressed_bytes < report.otlp_bytes);
+        assert_eq!(report.payloads[0].payload_type, ArrowPayloadType::Spans);
+        assert_eq!(report.payloads[0].rows, spans);
+        let payload_bytes: usize = report.payloads.iter().map(|p| p.bytes).sum();
+        assert!(report.otap_bytes > payload_bytes);
+        assert!(report.otap_compressed_bytes < report.otap_bytes);
+        assert!(report.payloads.iter().all(|p| p.compressed_bytes > 0));
+
+        let table = report.to_string();
+        assert!(table.contains("spans"));
+        assert!(table.starts_with("encoding"));
+    }
+}
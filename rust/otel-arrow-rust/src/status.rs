@@ -0,0 +1,134 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Building the `BatchStatus` responses of the receivers from the errors of this crate, so
+//! producers can tell the batches worth retrying from the ones they should drop.
+//!
+//! [`status_code`] maps the errors to the status codes of the OTel Arrow protocol:
+//!
+//! - the malformed batches are `INVALID_ARGUMENT`, sending them again fails the same way.
+//! - the batches over the limits of the receiver are `RESOURCE_EXHAUSTED`.
+//! - the decodes running out of time or cancelled are `DEADLINE_EXCEEDED` and `CANCELED`.
+//! - the transient I/O failures are `UNAVAILABLE`.
+//! - the bugs of the receiver are `INTERNAL`.
+//!
+//! [`is_retryable`] tells which of these codes the producers should retry.
+
+use crate::error::{Error, Result};
+use crate::proto::opentelemetry::arrow::v1::{BatchStatus, StatusCode};
+
+/// Returns the status code reporting the error to the producer of the batch.
+#[must_use]
+pub fn status_code(error: &Error) -> StatusCode {
+    match error {
+        Error::Decode { source, .. } => status_code(source),
+        Error::BatchTooLarge { .. }
+        | Error::TooManyRows { .. }
+        | Error::TooManyPayloads { .. }
+        | Error::MemoryLimitExceeded { .. } => StatusCode::ResourceExhausted,
+        Error::DecodeTimeExceeded { .. } => StatusCode::DeadlineExceeded,
+        Error::Cancelled { .. } | Error::DecodeTaskCancelled { .. } => StatusCode::Canceled,
+        Error::Io { .. } => StatusCode::Unavailable,
+        Error::WriteRecordBatch { .. }
+        | Error::UnexpectedRecordBatchState { .. }
+        | Error::InvalidCheckpoint { .. }
+        | Error::MaskLengthMismatch { .. }
+        | Error::InvalidSliceRange { .. } => StatusCode::Internal,
+        _ => StatusCode::InvalidArgument,
+    }
+}
+
+/// Returns whether sending the batch again may succeed, i.e. the code reports a transient
+/// failure rather than a problem of the batch itself.
+#[must_use]
+pub fn is_retryable(status_code: StatusCode) -> bool {
+    matches!(
+        status_code,
+        StatusCode::Canceled
+            | StatusCode::DeadlineExceeded
+            | StatusCode::Aborted
+            | StatusCode::Unavailable
+    )
+}
+
+impl BatchStatus {
+    /// Creates the status of a batch processed successfully.
+    #[must_use]
+    pub fn ok(batch_id: i64) -> Self {
+        Self {
+            batch_id,
+            status_code: StatusCode::Ok as i32,
+            status_message: String::new(),
+        }
+    }
+
+    /// Creates the status of a batch that failed with the error, see [`status_code`].
+    #[must_use]
+    pub fn from_error(batch_id: i64, error: &Error) -> Self {
+        Self {
+            batch_id,
+            status_code: status_code(error) as i32,
+            status_message: error.to_string(),
+        }
+    }
+
+    /// Creates the status of a batch from the result of its processing.
+    #[must_use]
+    pub fn from_result<T>(batch_id: i64, result: &Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::ok(batch_id),
+            Err(error) => Self::from_error(batch_id, error),
+        }
+    }
+
+    /// Returns whether the producer should send the batch again, see [`is_retryable`].
+    #[must_use]
+    pub fn is_retryable(&self) -> bool {
+        StatusCode::try_from(self.status_code).is_ok_and(is_retryable)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::error::{DecodeTimeExceededSnafu, ErrorContextExt, TooManyRowsSnafu};
+    use crate::proto::opentelemetry::arrow::v1::{ArrowPayloadType, BatchArrowRecords};
+    use crate::{Consumer, ExportRequest};
+
+    #[test]
+    fn test_batch_status() {
+        let timeout = DecodeTimeExceededSnafu {
+            max_decode_time: Duration::from_secs(1),
+        }
+        .build();
+        let status = BatchStatus::from_error(3, &timeout);
+        assert_eq!(status.status_code, StatusCode::DeadlineExceeded as i32);
+        assert!(status.is_retryable());
+
+        let too_many_rows: Result<()> = TooManyRowsSnafu {
+            payload_type: ArrowPayloadType::Logs,
+            rows: 10usize,
+            max_rows: 5usize,
+        }
+        .fail();
+        let too_many_rows = too_many_rows
+            .in_payload(ArrowPayloadType::Logs)
+            .unwrap_err();
+        assert_eq!(status_code(&too_many_rows), StatusCode::ResourceExhausted);
+        assert!(!BatchStatus::from_error(3, &too_many_rows).is_retryable());
+
+        let mut bar = BatchArrowRecords {
+            batch_id: 7,
+            ..Default::default()
+        };
+        let result: Result<ExportRequest> = Consumer::default().consume_batches(&mut bar);
+        let status = BatchStatus::from_result(bar.batch_id, &result);
+        assert_eq!(status.batch_id, 7);
+        assert_eq!(status.status_code, StatusCode::InvalidArgument as i32);
+        assert!(!status.is_retryable());
+        assert_eq!(BatchStatus::from_result(7, &Ok(())), BatchStatus::ok(7));
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lifecycle of the OTel Arrow streams of an exporter, recycled periodically like the
+//! streams of the Go exporter.
+//!
+//! A stream is closed once it reaches its maximum lifetime, so the load balancers in front
+//! of the receivers don't cut it on their own timeouts, the load spreads over the new
+//! receivers, and the dictionaries of the payloads, which only grow on a stream, are reset.
+//! The lifetime is shortened by a random jitter so the streams opened together aren't
+//! recycled together. A stream with no batch sent for its maximum idle time is recycled
+//! as well.
+//!
+//! The stream isn't closed abruptly: [`StreamLifecycle`] first stops accepting batches and
+//! drains, i.e. waits for the statuses of the batches in flight, see [`StreamState`], and
+//! only gives up on them after the drain timeout. The keepalive pings are not part of the
+//! OTAP protocol, they are configured on the gRPC channel.
+//!
+//! A [`StreamPool`] spreads the batches of an exporter over several streams, each with its
+//! own encoder state, so the throughput isn't bound by the encoding and serialization of a
+//! single stream. The streams that start draining are replaced at once, the pool keeps them
+//! aside until their batches in flight are done.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+
+/// Configuration of the lifecycle of the streams, see the module documentation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamConfig {
+    max_lifetime: Duration,
+    jitter: f64,
+    max_idle: Option<Duration>,
+    drain_timeout: Duration,
+}
+
+impl Default for StreamConfig {
+    /// Streams living 30 seconds, minus up to a tenth, never idle, drained for 10 seconds.
+    fn default() -> Self {
+        Self {
+            max_lifetime: Duration::from_secs(30),
+            jitter: 0.1,
+            max_idle: None,
+            drain_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl StreamConfig {
+    /// Sets the time after which the streams stop accepting batches and drain.
+    #[must_use]
+    pub fn with_max_lifetime(mut self, max_lifetime: Duration) -> Self {
+        self.max_lifetime = max_lifetime;
+        self
+    }
+
+    /// Sets the fraction of the lifetime randomly removed, clamped to `[0, 1]`.
+    #[must_use]
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the time without batch sent after which the streams drain, e.g. under the idle
+    /// timeout of the load balancers.
+    #[must_use]
+    pub fn with_max_idle(mut self, max_idle: Duration) -> Self {
+        self.max_idle = Some(max_idle);
+        self
+    }
+
+    /// Sets how long a draining stream waits for the statuses of its batches in flight.
+    #[must_use]
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+}
+
+/// State of a stream.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StreamState {
+    /// The stream accepts batches.
+    Open,
+    /// The stream waits for the statuses of its batches in flight before closing.
+    Draining,
+    /// The stream should be closed and a new one opened. The batches still in flight, if
+    /// the drain timed out, are to be sent again.
+    Closed,
+}
+
+/// Lifecycle of one stream, see the module documentation.
+#[derive(Clone, Debug)]
+pub struct StreamLifecycle {
+    config: StreamConfig,
+    deadline: Instant,
+    last_sent: Instant,
+    drain_started: Option<Instant>,
+    in_flight: usize,
+}
+
+impl StreamLifecycle {
+    /// Starts the lifecycle of a stream opened at `now`.
+    #[must_use]
+    pub fn new(config: StreamConfig, now: Instant) -> Self {
+        let random = RandomState::new().hash_one(0u64) as f64 / u64::MAX as f64;
+        Self::with_random(config, now, random)
+    }
+
+    /// Like [`StreamLifecycle::new`], `random` in `[0, 1]` drawing the jitter.
+    #[must_use]
+    pub fn with_random(config: StreamConfig, now: Instant, random: f64) -> Self {
+        let lifetime = config
+            .max_lifetime
+            .mul_f64(1.0 - config.jitter * random.clamp(0.0, 1.0));
+        Self {
+            deadline: now + lifetime,
+            last_sent: now,
+            drain_started: None,
+            in_flight: 0,
+            config,
+        }
+    }
+
+    /// Returns the time the stream starts draining at, unless it is idle before.
+    #[must_use]
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Returns the number of batches sent whose status wasn't received yet.
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Returns the state of the stream at `now`, starting to drain it when it reached its
+    /// lifetime or has been idle for too long.
+    pub fn state(&mut self, now: Instant) -> StreamState {
+        let drain_started = match self.drain_started {
+            Some(drain_started) => drain_started,
+            None => {
+                let idle = self.config.max_idle.is_some_and(|max_idle| {
+                    now.saturating_duration_since(self.last_sent) >= max_idle
+                });
+                if now < self.deadline && !idle {
+                    return StreamState::Open;
+                }
+                *self.drain_started.insert(now)
+            }
+        };
+        if self.in_flight == 0
+            || now.saturating_duration_since(drain_started) >= self.config.drain_timeout
+        {
+            StreamState::Closed
+        } else {
+            StreamState::Draining
+        }
+    }
+
+    /// Returns whether a batch can be sent on the stream at `now`, recording it as in
+    /// flight if it can. Otherwise the batch should be sent on a new stream.
+    pub fn try_send(&mut self, now: Instant) -> bool {
+        if self.state(now) != StreamState::Open {
+            return false;
+        }
+        self.in_flight += 1;
+        self.last_sent = now;
+        true
+    }
+
+    /// Records the status of a batch in flight received.
+    pub fn on_status(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+    }
+
+    /// Starts draining the stream at `now`, e.g. when the exporter shuts down.
+    pub fn drain(&mut self, now: Instant) {
+        let _ = self.drain_started.get_or_insert(now);
+    }
+}
+
+/// How a [`StreamPool`] spreads the batches over its streams.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Balancing {
+    /// Every stream in turn.
+    #[default]
+    RoundRobin,
+    /// The stream with the fewest batches in flight, the first one on ties.
+    LeastLoaded,
+}
+
+/// Identifier of a stream of a [`StreamPool`], unique over the lifetime of the pool.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct StreamId(u64);
+
+#[derive(Debug)]
+struct PooledStream<S> {
+    id: StreamId,
+    lifecycle: StreamLifecycle,
+    state: S,
+}
+
+/// Pool of streams of an exporter, see the module documentation. `S` is the state of a
+/// stream, e.g. its encoder and the sender of its batches.
+#[derive(Debug)]
+pub struct StreamPool<S> {
+    config: StreamConfig,
+    balancing: Balancing,
+    streams: Vec<PooledStream<S>>,
+    draining: Vec<PooledStream<S>>,
+    next_id: u64,
+    next_stream: usize,
+}
+
+impl<S> StreamPool<S> {
+    /// Creates a pool of `size` streams, at least one, opened at `now` by `open`.
+    pub fn new(
+        size: usize,
+        config: StreamConfig,
+        balancing: Balancing,
+        now: Instant,
+        mut open: impl FnMut(StreamId) -> S,
+    ) -> Self {
+        let mut pool = Self {
+            config,
+            balancing,
+            streams: Vec::with_capacity(size.max(1)),
+            draining: Vec::new(),
+            next_id: 0,
+            next_stream: 0,
+        };
+        for _ in 0..size.max(1) {
+            let stream = pool.open(now, &mut open);
+            pool.streams.push(stream);
+        }
+        pool
+    }
+
+    /// Returns the number of streams accepting batches.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.streams.len()
+    }
+
+    /// Returns the number of batches in flight on all the streams, draining ones included.
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.streams
+            .iter()
+            .chain(&self.draining)
+            .map(|stream| stream.lifecycle.in_flight())
+            .sum()
+    }
+
+    /// Selects the stream to send a batch on at `now` and records the batch as in flight
+    /// on it. None if no stream accepts batches, the pool then needs to be
+    /// [maintained](StreamPool::maintain).
+    pub fn select(&mut self, now: Instant) -> Option<(StreamId, &mut S)> {
+        let len = self.streams.len();
+        let order: Vec<usize> = match self.balancing {
+            Balancing::RoundRobin => (0..len).map(|i| (self.next_stream + i) % len).collect(),
+            Balancing::LeastLoaded => {
+                let mut order: Vec<usize> = (0..len).collect();
+                order.sort_by_key(|idx| self.streams[*idx].lifecycle.in_flight());
+                order
+            }
+        };
+        let idx = order
+            .into_iter()
+            .find(|idx| self.streams[*idx].lifecycle.try_send(now))?;
+        self.next_stream = (idx + 1) % len;
+        let stream = &mut self.streams[idx];
+        Some((stream.id, &mut stream.state))
+    }
+
+    /// Records the status of a batch sent on the stream received.
+    pub fn on_status(&mut self, id: StreamId) {
+        if let Some(stream) = self
+            .streams
+            .iter_mut()
+            .chain(&mut self.draining)
+            .find(|stream| stream.id == id)
+        {
+            stream.lifecycle.on_status();
+        }
+    }
+
+    /// Replaces the streams that no longer accept batches at `now` by streams opened with
+    /// `open`, and returns the states of the streams done draining, to close. Their batches
+    /// still in flight, if the drain timed out, are to be sent again.
+    pub fn maintain(&mut self, now: Instant, mut open: impl FnMut(StreamId) -> S) -> Vec<S> {
+        for idx in 0..self.streams.len() {
+            if self.streams[idx].lifecycle.state(now) != StreamState::Open {
+                let stream = self.open(now, &mut open);
+                let old = std::mem::replace(&mut self.streams[idx], stream);
+                self.draining.push(old);
+            }
+        }
+        let mut closed = Vec::new();
+        let mut idx = 0;
+        while idx < self.draining.len() {
+            if self.draining[idx].lifecycle.state(now) == StreamState::Closed {
+                closed.push(self.draining.swap_remove(idx).state);
+            } else {
+                idx += 1;
+            }
+        }
+        closed
+    }
+
+    /// Starts draining all the streams at `now`, e.g. when the exporter shuts down, and
+    /// returns the states of the streams done draining, see [`StreamPool::maintain`].
+    pub fn drain(&mut self, now: Instant) -> Vec<S> {
+        for stream in self.streams.iter_mut().chain(&mut self.draining) {
+            stream.lifecycle.drain(now);
+        }
+        self.draining.append(&mut self.streams);
+        let (closed, draining) = std::mem::take(&mut self.draining)
+            .into_iter()
+            .partition(|stream| stream.lifecycle.clone().state(now) == StreamState::Closed);
+        self.draining = draining;
+        closed.into_iter().map(|stream| stream.state).collect()
+    }
+
+    fn open(&mut self, now: Instant, open: &mut impl FnMut(StreamId) -> S) -> PooledStream<S> {
+        let id = StreamId(self.next_id);
+        self.next_id += 1;
+        PooledStream {
+            id,
+            lifecycle: StreamLifecycle::new(self.config.clone(), now),
+            state: open(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stream_lifecycle() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let config = StreamConfig::default()
+            .with_max_lifetime(Duration::from_secs(10))
+            .with_jitter(0.2)
+            .with_drain_timeout(Duration::from_secs(1));
+        let mut stream = StreamLifecycle::with_random(config.clone(), start, 0.5);
+        assert_eq!(stream.deadline(), at(9000));
+
+        assert!(stream.try_send(at(0)));
+        assert!(stream.try_send(at(8999)));
+        assert_eq!(stream.in_flight(), 2);
+        stream.on_status();
+        // the stream drains its last batch in flight
+        assert!(!stream.try_send(at(9000)));
+        assert_eq!(stream.state(at(9500)), StreamState::Draining);
+        stream.on_status();
+        assert_eq!(stream.state(at(9500)), StreamState::Closed);
+
+        // the drain times out
+        let mut stream = StreamLifecycle::with_random(config.clone(), start, 0.0);
+        assert!(stream.try_send(at(100)));
+        stream.drain(at(200));
+        assert_eq!(stream.state(at(1199)), StreamState::Draining);
+        assert_eq!(stream.state(at(1200)), StreamState::Closed);
+
+        let config = config.with_max_idle(Duration::from_secs(2));
+        let mut stream = StreamLifecycle::with_random(config, start, 0.0);
+        assert!(stream.try_send(at(1000)));
+        stream.on_status();
+        assert_eq!(stream.state(at(2999)), StreamState::Open);
+        assert_eq!(stream.state(at(3000)), StreamState::Closed);
+    }
+
+    #[test]
+    fn test_stream_pool() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let config = StreamConfig::default()
+            .with_max_lifetime(Duration::from_secs(10))
+            .with_jitter(0.0);
+        let open = |id: StreamId| (id, 0usize);
+        let mut pool = StreamPool::new(3, config.clone(), Balancing::RoundRobin, start, open);
+        let select = |pool: &mut StreamPool<(StreamId, usize)>, millis| {
+            let (id, state) = pool.select(at(millis)).unwrap();
+            state.1 += 1;
+            id
+        };
+        let ids: Vec<_> = (0..4).map(|_| select(&mut pool, 0)).collect();
+        assert_eq!(ids, vec![
+            StreamId(0),
+            StreamId(1),
+            StreamId(2),
+            StreamId(0)
+        ]);
+        assert_eq!(pool.in_flight(), 4);
+
+        // the streams reached their lifetime, stream 0 still has batches in flight
+        pool.on_status(StreamId(0));
+        pool.on_status(StreamId(1));
+        assert!(pool.select(at(10_000)).is_none());
+        let closed = pool.maintain(at(10_000), open);
+        assert_eq!(closed, vec![(StreamId(1), 1)]);
+        assert_eq!(pool.size(), 3);
+        assert_eq!(select(&mut pool, 10_000), StreamId(4));
+        pool.on_status(StreamId(0));
+        pool.on_status(StreamId(2));
+        let mut closed = pool.maintain(at(10_001), open);
+        closed.sort();
+        assert_eq!(closed, vec![(StreamId(0), 2), (StreamId(2), 1)]);
+        assert_eq!(pool.in_flight(), 1);
+        assert_eq!(pool.drain(at(10_002)).len(), 2);
+        pool.on_status(StreamId(4));
+        assert_eq!(pool.drain(at(10_003)), vec![(StreamId(4), 1)]);
+
+        let mut pool = StreamPool::new(2, config, Balancing::LeastLoaded, start, open);
+        assert_eq!(select(&mut pool, 0), StreamId(0));
+        assert_eq!(select(&mut pool, 0), StreamId(1));
+        pool.on_status(StreamId(1));
+        assert_eq!(select(&mut pool, 0), StreamId(1));
+        assert_eq!(select(&mut pool, 0), StreamId(0));
+    }
+}
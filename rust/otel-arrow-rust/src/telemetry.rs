@@ -0,0 +1,343 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Internal telemetry of the decoder and encoder.
+//!
+//! The [`Consumer`](crate::Consumer) and the [`BatchSplitter`](crate::encode::BatchSplitter)
+//! report what they do to a [`MetricsSink`], which the host implements to export the
+//! measurements with the metrics library of its choice, e.g. as Prometheus or OTLP
+//! metrics. No measurement is taken when no sink is set.
+//!
+//! The consumer can also pass the statistics of every batch it decodes to a
+//! [`DecodeStatsCallback`], for the hosts recording them with their own telemetry, e.g.
+//! per tenant.
+//!
+//! With the `tracing` feature, the decoding and encoding stages are also wrapped in
+//! `tracing` spans at the debug level, carrying the batch and schema ids, so their latency
+//! shows in the traces of the host.
+
+use std::time::Duration;
+
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+/// Receives the measurements of the crate.
+///
+/// The attributes are passed as key / value pairs, see the `*_ATTRIBUTE` constants for
+/// their keys.
+pub trait MetricsSink: Send + Sync {
+    /// Adds `value` to the counter `name`.
+    fn add_counter(&self, name: &'static str, value: u64, attributes: &[(&'static str, &str)]);
+
+    /// Records `value` in the histogram `name`.
+    fn record_histogram(&self, name: &'static str, value: f64, attributes: &[(&'static str, &str)]);
+}
+
+/// Statistics of the decoding of a batch, see [`DecodeStatsCallback`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DecodeStats {
+    /// Signal of the batch: `logs`, `metrics` or `traces`.
+    pub signal: &'static str,
+    /// Id of the batch.
+    pub batch_id: i64,
+    /// Whether the batch was decoded.
+    pub success: bool,
+    /// Payloads read, by type in the order the types were first read.
+    pub payloads: Vec<PayloadStats>,
+    /// Size of the Arrow IPC messages of the payloads read.
+    pub bytes_in: usize,
+    /// Protobuf encoded size of the decoded request, 0 if the batch failed to decode.
+    pub bytes_out: usize,
+    /// Time spent reading the Arrow IPC messages of the payloads into record batches.
+    pub read_duration: Duration,
+    /// Time spent converting the record batches into the OTLP request.
+    pub convert_duration: Duration,
+}
+
+impl DecodeStats {
+    pub(crate) fn add_payload(&mut self, payload_type: ArrowPayloadType, rows: usize) {
+        match self
+            .payloads
+            .iter_mut()
+            .find(|stats| stats.payload_type == payload_type)
+        {
+            Some(stats) => {
+                stats.payloads += 1;
+                stats.rows += rows;
+            }
+            None => self.payloads.push(PayloadStats {
+                payload_type,
+                payloads: 1,
+                rows,
+            }),
+        }
+    }
+}
+
+/// Payloads of a type read in a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PayloadStats {
+    /// Type of the payloads.
+    pub payload_type: ArrowPayloadType,
+    /// Number of payloads of the type.
+    pub payloads: usize,
+    /// Number of rows of the payloads.
+    pub rows: usize,
+}
+
+/// Receives the [`DecodeStats`] of every batch decoded by the consumer it is set on, failed
+/// or not. It is implemented for closures taking the statistics.
+pub trait DecodeStatsCallback: Send + Sync {
+    /// Called once the batch is decoded.
+    fn on_batch_decoded(&self, stats: &DecodeStats);
+}
+
+impl<F: Fn(&DecodeStats) + Send + Sync> DecodeStatsCallback for F {
+    fn on_batch_decoded(&self, stats: &DecodeStats) {
+        self(stats)
+    }
+}
+
+/// Counter of the batches decoded, with the signal and outcome attributes.
+pub const BATCHES_DECODED: &str = "otel_arrow.decoder.batches";
+
+/// Counter of the rows decoded, with the payload type attribute.
+pub const ROWS_DECODED: &str = "otel_arrow.decoder.rows";
+
+/// Histogram of the time spent decoding a batch, in seconds, with the signal and outcome
+/// attributes.
+pub const DECODE_DURATION: &str = "otel_arrow.decoder.duration";
+
+/// Counter of the rows dropped by the decoder, with the payload type and reason attributes.
+pub const DROPPED_ROWS: &str = "otel_arrow.decoder.dropped_rows";
+
+/// Counter of the out of range delta ids met by the decoder, with the payload type
+/// attribute.
+pub const DELTA_ID_ANOMALIES: &str = "otel_arrow.decoder.delta_id_anomalies";
+
+/// Counter of the payload streams reset because their schema changed, which also resets
+/// their dictionaries, with the payload type attribute.
+pub const DICTIONARY_RESETS: &str = "otel_arrow.decoder.dictionary_resets";
+
+/// Counter of the schema resets whose new schema has columns the decoder rejects, see
+/// [`compat`](crate::schema::compat), with the payload type attribute.
+pub const INCOMPATIBLE_SCHEMA_RESETS: &str = "otel_arrow.decoder.incompatible_schema_resets";
+
+/// Counter of the payloads of unknown types skipped by the decoder, with the payload type
+/// attribute holding the number of the type.
+pub const SKIPPED_PAYLOADS: &str = "otel_arrow.decoder.skipped_payloads";
+
+/// Counter of the batches encoded, with the signal attribute.
+pub const BATCHES_ENCODED: &str = "otel_arrow.encoder.batches";
+
+/// Histogram of the size of the encoded batches, in bytes, with the signal attribute.
+pub const ENCODED_BYTES: &str = "otel_arrow.encoder.bytes";
+
+/// Histogram of the time spent encoding a request, in seconds, with the signal attribute.
+pub const ENCODE_DURATION: &str = "otel_arrow.encoder.duration";
+
+/// Histogram of the depth of the queue between the encoders and the sender of an exporter,
+/// recorded on every item queued.
+pub const QUEUE_DEPTH: &str = "otel_arrow.exporter.queue_depth";
+
+/// Counter of the items dropped by the queue of an exporter when full, with the reason
+/// attribute: `dropped_oldest` or `rejected`.
+pub const QUEUE_DROPPED: &str = "otel_arrow.exporter.queue_dropped";
+
+/// Attribute holding the signal: `logs`, `metrics` or `traces`.
+pub const SIGNAL_ATTRIBUTE: &str = "signal";
+
+/// Attribute holding the payload type, e.g. `SPAN_ATTRS`.
+pub const PAYLOAD_TYPE_ATTRIBUTE: &str = "payload_type";
+
+/// Attribute holding the outcome of an operation: `success` or `failure`.
+pub const OUTCOME_ATTRIBUTE: &str = "outcome";
+
+/// Attribute holding the reason a row was dropped, e.g. `ValueTypeMismatch`.
+pub const REASON_ATTRIBUTE: &str = "reason";
+
+/// Enters a debug `tracing` span for the rest of the enclosing block when the `tracing`
+/// feature is enabled, the fields are not evaluated otherwise.
+macro_rules! trace_span {
+    ($name:expr $(, $($fields:tt)*)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($name $(, $($fields)*)?).entered();
+    };
+}
+pub(crate) use trace_span;
+
+pub(crate) fn outcome<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() { "success" } else { "failure" }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+
+    use crate::Consumer;
+    use prost::Message;
+
+    use crate::encode::BatchSplitter;
+    use crate::proto::opentelemetry::collector::logs::v1::ExportLogsServiceRequest;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+    use crate::proto::opentelemetry::common::v1::{AnyValue, KeyValue};
+    use crate::proto::opentelemetry::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    /// Sums the counters and counts the histogram records, keyed by name and attributes.
+    #[derive(Default)]
+    struct RecordingSink {
+        counters: Mutex<BTreeMap<String, u64>>,
+        histograms: Mutex<BTreeMap<String, u64>>,
+    }
+
+    fn key(name: &str, attributes: &[(&str, &str)]) -> String {
+        let attributes: Vec<_> = attributes.iter().map(|(k, v)| format!("{k}={v}")).collect();
+        format!("{name}{{{}}}", attributes.join(","))
+    }
+
+    impl MetricsSink for RecordingSink {
+        fn add_counter(&self, name: &'static str, value: u64, attributes: &[(&'static str, &str)]) {
+            *self
+                .counters
+                .lock()
+                .unwrap()
+                .entry(key(name, attributes))
+                .or_default() += value;
+        }
+
+        fn record_histogram(
+            &self,
+            name: &'static str,
+            _value: f64,
+            attributes: &[(&'static str, &str)],
+        ) {
+            *self
+                .histograms
+                .lock()
+                .unwrap()
+                .entry(key(name, attributes))
+                .or_default() += 1;
+        }
+    }
+
+    fn request() -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: (0..4)
+                        .map(|i| LogRecord {
+                            time_unix_nano: i,
+                            attributes: vec![KeyValue {
+                                key: "k".into(),
+                                value: Some(AnyValue {
+                                    value: Some(Value::IntValue(i as i64)),
+                                }),
+                            }],
+                            ..Default::default()
+                        })
+                        .collect(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_metrics_sink() {
+        let request = request();
+        let sink = Arc::new(RecordingSink::default());
+
+        let mut splitter = BatchSplitter::new(usize::MAX);
+        splitter.set_metrics_sink(sink.clone());
+        let batches = splitter.split_logs(&request).unwrap();
+
+        let mut consumer = Consumer::default();
+        consumer.set_metrics_sink(sink.clone());
+        for mut batch in batches {
+            let _ = consumer.consume_logs_batches(&mut batch).unwrap();
+        }
+        assert!(
+            consumer
+                .consume_logs_batches(&mut Default::default())
+                .is_err()
+        );
+
+        let counters = sink.counters.lock().unwrap();
+        assert_eq!(counters[&key(BATCHES_ENCODED, &[("signal", "logs")])], 1);
+        assert_eq!(
+            counters[&key(BATCHES_DECODED, &[
+                ("signal", "logs"),
+                ("outcome", "success")
+            ])],
+            1
+        );
+        assert_eq!(
+            counters[&key(BATCHES_DECODED, &[
+                ("signal", "logs"),
+                ("outcome", "failure")
+            ])],
+            1
+        );
+        assert_eq!(counters[&key(ROWS_DECODED, &[("payload_type", "LOGS")])], 4);
+        assert_eq!(
+            counters[&key(ROWS_DECODED, &[("payload_type", "LOG_ATTRS")])],
+            4
+        );
+
+        let histograms = sink.histograms.lock().unwrap();
+        assert_eq!(histograms[&key(ENCODED_BYTES, &[("signal", "logs")])], 1);
+        assert_eq!(histograms[&key(ENCODE_DURATION, &[("signal", "logs")])], 1);
+        assert_eq!(
+            histograms[&key(DECODE_DURATION, &[
+                ("signal", "logs"),
+                ("outcome", "success")
+            ])],
+            1
+        );
+    }
+
+    #[test]
+    fn test_decode_stats_callback() {
+        let request = request();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut consumer = Consumer::default();
+        let stats = received.clone();
+        consumer.set_decode_stats_callback(Arc::new(move |s: &DecodeStats| {
+            stats.lock().unwrap().push(s.clone())
+        }));
+
+        let mut batches = BatchSplitter::new(usize::MAX).split_logs(&request).unwrap();
+        assert_eq!(batches.len(), 1);
+        let decoded = consumer.consume_logs_batches(&mut batches[0]).unwrap();
+        assert!(
+            consumer
+                .consume_logs_batches(&mut Default::default())
+                .is_err()
+        );
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let stats = &received[0];
+        assert_eq!(stats.signal, "logs");
+        assert!(stats.success);
+        assert_eq!(stats.payloads, vec![
+            PayloadStats {
+                payload_type: ArrowPayloadType::Logs,
+                payloads: 1,
+                rows: 4,
+            },
+            PayloadStats {
+                payload_type: ArrowPayloadType::LogAttrs,
+                payloads: 1,
+                rows: 4,
+            },
+        ]);
+        assert!(stats.bytes_in > 0);
+        assert_eq!(stats.bytes_out, decoded.encoded_len());
+        assert!(!received[1].success);
+        assert_eq!(received[1].bytes_out, 0);
+    }
+}
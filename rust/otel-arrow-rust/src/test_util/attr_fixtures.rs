@@ -0,0 +1,374 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Exhaustive fixtures of the attributes payloads, covering the paths of the attribute
+//! store for every value type.
+//!
+//! A fixture holds a row of every [`AttributeValueType`] for a single parent, with distinct
+//! keys so no parent id is delta encoded, and is built for every combination of:
+//!
+//! - the optional value columns present in the record batch, the `str` column being
+//!   required;
+//! - the plain and dictionary encodings of the keys and of the value columns that support
+//!   them, all but `double` and `bool`;
+//! - the null patterns: no null, null values, and null keys.
+//!
+//! Each fixture comes with the attributes and the decode events the store is expected to
+//! produce, derived from the rows rather than from the decoder.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray,
+    UInt8Array, UInt16Array,
+};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Schema};
+
+use crate::otlp::attributes::store::AttributeValueType;
+use crate::otlp::events::DecodeEventKind;
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, ArrayValue, KeyValue, KeyValueList};
+use crate::schema::consts;
+use crate::value::cbor::encode_pcommon_val;
+use crate::value::default_value;
+
+/// The optional value columns of the attributes payloads.
+pub(crate) const OPTIONAL_COLUMNS: [&str; 5] = [
+    consts::ATTRIBUTE_INT,
+    consts::ATTRIBUTE_DOUBLE,
+    consts::ATTRIBUTE_BOOL,
+    consts::ATTRIBUTE_BYTES,
+    consts::ATTRIBUTE_SER,
+];
+
+/// Encoding of the keys and of the value columns supporting dictionaries.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Encoding {
+    Plain,
+    Dictionary,
+}
+
+/// Which cells of the fixture are null.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum NullPattern {
+    None,
+    Values,
+    Keys,
+}
+
+/// A row of a fixture.
+#[derive(Clone, Debug)]
+struct Row {
+    key: Option<String>,
+    value_type: AttributeValueType,
+    value: Option<Value>,
+}
+
+/// An attributes payload, with the attributes of its parent 0 and the events of its rows
+/// expected from the attribute store.
+#[derive(Debug)]
+pub(crate) struct AttrsFixture {
+    pub(crate) description: String,
+    pub(crate) record_batch: RecordBatch,
+    pub(crate) expected: Vec<KeyValue>,
+    pub(crate) events: Vec<(usize, DecodeEventKind)>,
+}
+
+const VALUE_TYPES: [AttributeValueType; 8] = [
+    AttributeValueType::Empty,
+    AttributeValueType::Str,
+    AttributeValueType::Int,
+    AttributeValueType::Double,
+    AttributeValueType::Bool,
+    AttributeValueType::Map,
+    AttributeValueType::Slice,
+    AttributeValueType::Bytes,
+];
+
+fn sample_value(value_type: AttributeValueType) -> Option<Value> {
+    let int = |value| AnyValue {
+        value: Some(Value::IntValue(value)),
+    };
+    match value_type {
+        AttributeValueType::Empty => None,
+        AttributeValueType::Str => Some(Value::StringValue("text".into())),
+        AttributeValueType::Int => Some(Value::IntValue(42)),
+        AttributeValueType::Double => Some(Value::DoubleValue(1.5)),
+        AttributeValueType::Bool => Some(Value::BoolValue(true)),
+        AttributeValueType::Map => Some(Value::KvlistValue(KeyValueList {
+            values: vec![KeyValue {
+                key: "nested".into(),
+                value: Some(int(1)),
+            }],
+        })),
+        AttributeValueType::Slice => Some(Value::ArrayValue(ArrayValue {
+            values: vec![int(1), int(2)],
+        })),
+        AttributeValueType::Bytes => Some(Value::BytesValue(vec![1, 2, 3])),
+    }
+}
+
+fn rows(nulls: NullPattern) -> Vec<Row> {
+    VALUE_TYPES
+        .into_iter()
+        .map(|value_type| {
+            // the keys of the empty and map rows are null, so at most one empty key is kept
+            let null_key = nulls == NullPattern::Keys
+                && matches!(
+                    value_type,
+                    AttributeValueType::Empty | AttributeValueType::Map
+                );
+            Row {
+                key: (!null_key).then(|| format!("key.{value_type:?}").to_lowercase()),
+                value_type,
+                value: match nulls {
+                    NullPattern::Values => None,
+                    _ => sample_value(value_type),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Returns the column the values of the type are stored in.
+fn column_of(value_type: AttributeValueType) -> Option<&'static str> {
+    match value_type {
+        AttributeValueType::Empty => None,
+        AttributeValueType::Str => Some(consts::ATTRIBUTE_STR),
+        AttributeValueType::Int => Some(consts::ATTRIBUTE_INT),
+        AttributeValueType::Double => Some(consts::ATTRIBUTE_DOUBLE),
+        AttributeValueType::Bool => Some(consts::ATTRIBUTE_BOOL),
+        AttributeValueType::Map | AttributeValueType::Slice => Some(consts::ATTRIBUTE_SER),
+        AttributeValueType::Bytes => Some(consts::ATTRIBUTE_BYTES),
+    }
+}
+
+fn values_of<'a, T>(
+    rows: &'a [Row],
+    name: &'a str,
+    value: impl Fn(&Value) -> Option<T> + 'a,
+) -> impl Iterator<Item = Option<T>> + 'a {
+    rows.iter().map(move |row| {
+        row.value
+            .as_ref()
+            .filter(|_| column_of(row.value_type) == Some(name))
+            .and_then(&value)
+    })
+}
+
+fn encode(column: ArrayRef, key_type: DataType, encoding: Encoding) -> ArrayRef {
+    match encoding {
+        Encoding::Plain => column,
+        Encoding::Dictionary => {
+            let dictionary_type =
+                DataType::Dictionary(Box::new(key_type), Box::new(column.data_type().clone()));
+            // safety: the dictionaries of strings, ints and binaries are supported
+            cast(&column, &dictionary_type).expect("dictionary cast")
+        }
+    }
+}
+
+fn column(rows: &[Row], name: &str, encoding: Encoding) -> ArrayRef {
+    match name {
+        consts::ATTRIBUTE_STR => encode(
+            Arc::new(StringArray::from_iter(values_of(rows, name, |v| match v {
+                Value::StringValue(s) => Some(s.clone()),
+                _ => None,
+            }))),
+            DataType::UInt16,
+            encoding,
+        ),
+        consts::ATTRIBUTE_INT => encode(
+            Arc::new(Int64Array::from_iter(values_of(rows, name, |v| match v {
+                Value::IntValue(i) => Some(*i),
+                _ => None,
+            }))),
+            DataType::UInt16,
+            encoding,
+        ),
+        consts::ATTRIBUTE_DOUBLE => {
+            Arc::new(Float64Array::from_iter(values_of(
+                rows,
+                name,
+                |v| match v {
+                    Value::DoubleValue(d) => Some(*d),
+                    _ => None,
+                },
+            )))
+        }
+        consts::ATTRIBUTE_BOOL => Arc::new(BooleanArray::from_iter(values_of(
+            rows,
+            name,
+            |v| match v {
+                Value::BoolValue(b) => Some(*b),
+                _ => None,
+            },
+        ))),
+        consts::ATTRIBUTE_BYTES => encode(
+            Arc::new(BinaryArray::from_iter(values_of(rows, name, |v| match v {
+                Value::BytesValue(b) => Some(b.clone()),
+                _ => None,
+            }))),
+            DataType::UInt16,
+            encoding,
+        ),
+        _ => encode(
+            Arc::new(BinaryArray::from_iter(values_of(rows, name, |v| {
+                Some(encode_pcommon_val(Some(v)))
+            }))),
+            DataType::UInt16,
+            encoding,
+        ),
+    }
+}
+
+fn build(rows: &[Row], optional_columns: &[&str], encoding: Encoding) -> AttrsFixture {
+    let num_rows = rows.len();
+    let keys: ArrayRef = Arc::new(StringArray::from_iter(
+        rows.iter().map(|row| row.key.clone()),
+    ));
+    let mut columns = vec![
+        (
+            consts::PARENT_ID,
+            Arc::new(UInt16Array::from(vec![0; num_rows])) as ArrayRef,
+        ),
+        (
+            consts::ATTRIBUTE_TYPE,
+            Arc::new(UInt8Array::from_iter_values(
+                rows.iter().map(|row| row.value_type as u8),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_KEY,
+            encode(keys, DataType::UInt8, encoding),
+        ),
+        (
+            consts::ATTRIBUTE_STR,
+            column(rows, consts::ATTRIBUTE_STR, encoding),
+        ),
+    ];
+    columns.extend(
+        optional_columns
+            .iter()
+            .map(|name| (*name, column(rows, name, encoding))),
+    );
+    let schema = Schema::new(
+        columns
+            .iter()
+            .map(|(name, column)| Field::new(*name, column.data_type().clone(), true))
+            .collect::<Vec<_>>(),
+    );
+    // safety: all the columns have a value for every row
+    let record_batch = RecordBatch::try_new(
+        Arc::new(schema),
+        columns.into_iter().map(|(_, column)| column).collect(),
+    )
+    .expect("valid record batch");
+
+    let mut expected = Vec::new();
+    let mut events = Vec::new();
+    for (idx, row) in rows.iter().enumerate() {
+        if row.value_type == AttributeValueType::Empty {
+            events.push((idx, DecodeEventKind::EmptyValue));
+            continue;
+        }
+        if row.key.is_none() {
+            events.push((idx, DecodeEventKind::MissingKey));
+        }
+        let stored = column_of(row.value_type)
+            .filter(|name| *name == consts::ATTRIBUTE_STR || optional_columns.contains(name));
+        let value = match stored {
+            Some(_) => row.value.clone(),
+            None => None,
+        };
+        let value = match row.value_type {
+            AttributeValueType::Map | AttributeValueType::Slice if value.is_none() => {
+                events.push((idx, DecodeEventKind::MissingSerializedValue));
+                continue;
+            }
+            _ => value.or_else(|| default_value(row.value_type)),
+        };
+        expected.push(KeyValue {
+            key: row.key.clone().unwrap_or_default(),
+            value: Some(AnyValue { value }),
+        });
+    }
+
+    AttrsFixture {
+        description: format!(
+            "columns = {optional_columns:?}, encoding = {encoding:?}, nulls = {:?}",
+            rows.iter()
+                .map(|row| (row.key.is_some(), row.value.is_some()))
+                .collect::<Vec<_>>()
+        ),
+        record_batch,
+        expected,
+        events,
+    }
+}
+
+/// Returns the fixtures of every combination of optional columns, encodings and null
+/// patterns, see the module documentation.
+pub(crate) fn all_fixtures() -> Vec<AttrsFixture> {
+    let mut fixtures = Vec::new();
+    for mask in 0..1u32 << OPTIONAL_COLUMNS.len() {
+        let optional_columns: Vec<_> = OPTIONAL_COLUMNS
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| mask & (1 << idx) != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        for encoding in [Encoding::Plain, Encoding::Dictionary] {
+            for nulls in [NullPattern::None, NullPattern::Values, NullPattern::Keys] {
+                fixtures.push(build(&rows(nulls), &optional_columns, encoding));
+            }
+        }
+    }
+    fixtures
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otap::{Logs, OtapBatch};
+    use crate::otlp::attributes::store::Attribute16Store;
+    use crate::otlp::events::DecodeEventCollector;
+    use crate::otlp::options::DecoderOptions;
+    use crate::otlp::report::DecodeReport;
+    use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+
+    #[test]
+    fn test_attribute_store_fixtures() {
+        let fixtures = all_fixtures();
+        assert_eq!(fixtures.len(), 32 * 2 * 3);
+        for fixture in fixtures {
+            let mut batch = OtapBatch::Logs(Logs::default());
+            batch.set(ArrowPayloadType::LogAttrs, fixture.record_batch);
+            let collector = Arc::new(DecodeEventCollector::default());
+            let options = DecoderOptions::default().with_event_sink(collector.clone());
+            let store = Attribute16Store::from_payload(
+                &batch,
+                ArrowPayloadType::LogAttrs,
+                &options,
+                &mut DecodeReport::default(),
+            )
+            .unwrap_or_else(|e| panic!("{}: {e}", fixture.description))
+            .unwrap();
+
+            assert_eq!(
+                store.attribute_by_id(0).unwrap_or_default(),
+                fixture.expected.as_slice(),
+                "{}",
+                fixture.description
+            );
+            let events: Vec<_> = collector
+                .take()
+                .into_iter()
+                .map(|event| (event.row, event.kind))
+                .collect();
+            assert_eq!(events, fixture.events, "{}", fixture.description);
+        }
+    }
+}
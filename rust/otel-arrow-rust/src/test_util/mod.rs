@@ -10,6 +10,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(test)]
+mod attr_fixtures;
+#[cfg(test)]
 mod create_array;
+#[cfg(test)]
+mod parent_ids;
+pub mod workloads;
 
+#[cfg(test)]
 pub(crate) use create_array::{create_record_batch, create_test_schema};
@@ -0,0 +1,331 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Differential tests of the decoders of the delta encoded parent ids of the attributes.
+//!
+//! Random attributes are encoded by a reference model of the Go encoder: the rows are
+//! sorted by type, key, value and parent id, and the parent id of a row with the same key
+//! and value as the previous row is the delta from the previous parent id. The empty, map
+//! and slice values never continue a group. The ids are then written with each of the
+//! supported encodings of the parent id column, and every decoder must reconstruct the
+//! parent ids of the model.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BinaryArray, BooleanArray, DictionaryArray, Float64Array, Int64Array, RecordBatch,
+    StringArray, UInt8Array, UInt16Array, UInt32Array,
+};
+use arrow::datatypes::{Field, Schema, UInt8Type};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::arrays::get_u16_array;
+use crate::encode::attributes::{Attributes16Accumulator, Attributes32Accumulator};
+use crate::otlp::attributes::decoder::{
+    Attrs16ParentIdDecoder, Attrs32ParentIdDecoder, materialize_parent_id,
+};
+use crate::otlp::attributes::store::{Attribute16Store, Attribute32Store, AttributeValueType};
+use crate::otlp::options::{DecoderOptions, DuplicateKeyPolicy};
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, ArrayValue, KeyValue, KeyValueList};
+use crate::schema::consts;
+use crate::value::cbor::encode_pcommon_val;
+
+/// Number of random sequences checked per encoding.
+const SEQUENCES: u64 = 200;
+
+/// A row of the attributes payload, `None` being an attribute of the `Empty` type.
+#[derive(Clone, Debug)]
+struct Row {
+    parent_id: u32,
+    key: &'static str,
+    value: Option<Value>,
+}
+
+impl Row {
+    fn value_type(&self) -> AttributeValueType {
+        AttributeValueType::from(self.value.as_ref())
+    }
+
+    /// Whether the value can continue the group of the previous row.
+    fn is_groupable(&self) -> bool {
+        !matches!(
+            self.value_type(),
+            AttributeValueType::Empty | AttributeValueType::Map | AttributeValueType::Slice
+        )
+    }
+}
+
+/// Returns a random sequence of rows, the small domains of the keys, values and parent ids
+/// making long groups of delta encoded ids likely.
+fn random_rows(rng: &mut StdRng, max_parent_id: u32) -> Vec<Row> {
+    let len = rng.random_range(0..40);
+    (0..len)
+        .map(|_| {
+            let value = match rng.random_range(0..8) {
+                0 => None,
+                1 => Some(Value::StringValue(
+                    ["x", "y"][rng.random_range(0..2)].into(),
+                )),
+                2 => Some(Value::IntValue(rng.random_range(-1..2))),
+                3 => Some(Value::DoubleValue(
+                    [0.5, -0.0, 1e10][rng.random_range(0..3)],
+                )),
+                4 => Some(Value::BoolValue(rng.random_bool(0.5))),
+                5 => Some(Value::BytesValue(vec![rng.random_range(1..3)])),
+                6 => Some(Value::KvlistValue(KeyValueList::default())),
+                _ => Some(Value::ArrayValue(ArrayValue {
+                    values: vec![AnyValue {
+                        value: Some(Value::IntValue(1)),
+                    }],
+                })),
+            };
+            Row {
+                parent_id: rng.random_range(0..=max_parent_id),
+                key: ["a", "b", "c"][rng.random_range(0..3)],
+                value,
+            }
+        })
+        .collect()
+}
+
+/// Sorts the rows like the Go encoder and returns the encoded parent ids of the model.
+fn encode_model(rows: &mut [Row]) -> Vec<u32> {
+    rows.sort_by(|a, b| {
+        (a.value_type() as u8)
+            .cmp(&(b.value_type() as u8))
+            .then_with(|| a.key.cmp(b.key))
+            .then_with(|| format!("{:?}", a.value).cmp(&format!("{:?}", b.value)))
+            .then_with(|| a.parent_id.cmp(&b.parent_id))
+    });
+    let mut prev: Option<&Row> = None;
+    rows.iter()
+        .map(|row| {
+            let grouped = prev.is_some_and(|prev| {
+                prev.is_groupable()
+                    && row.is_groupable()
+                    && prev.key == row.key
+                    && prev.value == row.value
+            });
+            let encoded = match prev {
+                Some(prev) if grouped => row.parent_id - prev.parent_id,
+                _ => row.parent_id,
+            };
+            prev = Some(row);
+            encoded
+        })
+        .collect()
+}
+
+/// Returns the attributes record batch of the rows, with the given parent id column.
+fn record_batch(rows: &[Row], parent_ids: ArrayRef) -> RecordBatch {
+    let strs: StringArray = rows
+        .iter()
+        .map(|row| match &row.value {
+            Some(Value::StringValue(s)) => Some(s.as_str()),
+            _ => None,
+        })
+        .collect();
+    let ints: Int64Array = rows
+        .iter()
+        .map(|row| match row.value {
+            Some(Value::IntValue(i)) => Some(i),
+            _ => None,
+        })
+        .collect();
+    let doubles: Float64Array = rows
+        .iter()
+        .map(|row| match row.value {
+            Some(Value::DoubleValue(d)) => Some(d),
+            _ => None,
+        })
+        .collect();
+    let bools: BooleanArray = rows
+        .iter()
+        .map(|row| match row.value {
+            Some(Value::BoolValue(b)) => Some(b),
+            _ => None,
+        })
+        .collect();
+    let bytes = BinaryArray::from_iter(rows.iter().map(|row| match &row.value {
+        Some(Value::BytesValue(b)) => Some(b.clone()),
+        _ => None,
+    }));
+    let sers = BinaryArray::from_iter(rows.iter().map(|row| match &row.value {
+        Some(value @ (Value::KvlistValue(_) | Value::ArrayValue(_))) => {
+            Some(encode_pcommon_val(Some(value)))
+        }
+        _ => None,
+    }));
+    let types = UInt8Array::from_iter_values(rows.iter().map(|row| row.value_type() as u8));
+    let keys = StringArray::from_iter_values(rows.iter().map(|row| row.key));
+
+    let columns: Vec<(&str, ArrayRef)> = vec![
+        (consts::PARENT_ID, parent_ids),
+        (consts::ATTRIBUTE_TYPE, Arc::new(types)),
+        (consts::ATTRIBUTE_KEY, Arc::new(keys)),
+        (consts::ATTRIBUTE_STR, Arc::new(strs)),
+        (consts::ATTRIBUTE_INT, Arc::new(ints)),
+        (consts::ATTRIBUTE_DOUBLE, Arc::new(doubles)),
+        (consts::ATTRIBUTE_BOOL, Arc::new(bools)),
+        (consts::ATTRIBUTE_BYTES, Arc::new(bytes)),
+        (consts::ATTRIBUTE_SER, Arc::new(sers)),
+    ];
+    RecordBatch::try_new(
+        Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|(name, arr)| Field::new(*name, arr.data_type().clone(), true))
+                .collect::<Vec<_>>(),
+        )),
+        columns.into_iter().map(|(_, arr)| arr).collect(),
+    )
+    .unwrap()
+}
+
+/// Returns the attribute sets of the rows with a value, in the order of the rows.
+fn expected_sets(rows: &[Row], max_parent_id: u32) -> Vec<Vec<KeyValue>> {
+    let mut sets = vec![Vec::new(); max_parent_id as usize + 1];
+    for row in rows {
+        if let Some(value) = &row.value {
+            sets[row.parent_id as usize].push(KeyValue {
+                key: row.key.into(),
+                value: Some(AnyValue {
+                    value: Some(value.clone()),
+                }),
+            });
+        }
+    }
+    sets
+}
+
+fn options() -> DecoderOptions {
+    DecoderOptions::default().with_duplicate_key_policy(DuplicateKeyPolicy::KeepAll)
+}
+
+#[test]
+fn test_parent_id_decoder_against_model() {
+    let mut delta_encoded = 0;
+    for seed in 0..SEQUENCES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut rows = random_rows(&mut rng, 5);
+        let encoded = encode_model(&mut rows);
+        delta_encoded += rows
+            .iter()
+            .zip(&encoded)
+            .filter(|(row, encoded)| row.parent_id != **encoded)
+            .count();
+
+        let mut decoder16 = Attrs16ParentIdDecoder::default();
+        let mut decoder32 = Attrs32ParentIdDecoder::default();
+        for (row, encoded) in rows.iter().zip(&encoded) {
+            let Some(value) = &row.value else {
+                decoder16.reset();
+                decoder32.reset();
+                continue;
+            };
+            let parent_id = decoder16.decode(*encoded as u16, row.key, value).unwrap();
+            assert_eq!(u32::from(parent_id), row.parent_id, "seed {seed}");
+            let parent_id = decoder32.decode(*encoded, row.key, value).unwrap();
+            assert_eq!(parent_id, row.parent_id, "seed {seed}");
+        }
+    }
+    // the sequences exercise the delta encoding, not only the first rows of the groups
+    assert!(delta_encoded > SEQUENCES as usize);
+}
+
+#[test]
+fn test_materialized_parent_ids_against_model() {
+    for seed in 0..SEQUENCES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut rows = random_rows(&mut rng, 5);
+        // the empty attributes have no value to compare, the materialization leaves them out
+        rows.retain(|row| row.value.is_some());
+        let encoded = encode_model(&mut rows);
+        let parent_ids = UInt16Array::from_iter_values(encoded.iter().map(|id| *id as u16));
+
+        let rb = record_batch(&rows, Arc::new(parent_ids));
+        let materialized = materialize_parent_id::<u16>(&rb).unwrap();
+        let parent_ids = get_u16_array(&materialized, consts::PARENT_ID).unwrap();
+        let expected: Vec<_> = rows.iter().map(|row| row.parent_id as u16).collect();
+        assert_eq!(parent_ids.values().to_vec(), expected, "seed {seed}");
+    }
+}
+
+#[test]
+fn test_attribute_stores_against_model() {
+    let max_parent_id = 5;
+    for seed in 0..SEQUENCES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut rows = random_rows(&mut rng, max_parent_id);
+        let encoded = encode_model(&mut rows);
+        let expected = expected_sets(&rows, max_parent_id);
+
+        let u16_ids = UInt16Array::from_iter_values(encoded.iter().map(|id| *id as u16));
+        let u32_ids = UInt32Array::from_iter_values(encoded.iter().copied());
+        // the Go encoder dictionary encodes the 32 bits parent ids
+        let dict_ids = DictionaryArray::<UInt8Type>::try_new(
+            UInt8Array::from_iter_values(0..encoded.len() as u8),
+            Arc::new(u32_ids.clone()),
+        )
+        .unwrap();
+
+        let rb = record_batch(&rows, Arc::new(u16_ids));
+        let store = Attribute16Store::try_from_with_options(&rb, &options()).unwrap();
+        for (id, set) in expected.iter().enumerate() {
+            let decoded = store.attribute_by_id(id as u16).unwrap_or_default();
+            assert_eq!(decoded, &set[..], "seed {seed}, u16 parent id {id}");
+        }
+        for parent_ids in [Arc::new(u32_ids) as ArrayRef, Arc::new(dict_ids)] {
+            let rb = record_batch(&rows, parent_ids);
+            let store = Attribute32Store::try_from_with_options(&rb, &options()).unwrap();
+            for (id, set) in expected.iter().enumerate() {
+                let decoded = store.attribute_by_id(id as u32).unwrap_or_default();
+                assert_eq!(decoded, &set[..], "seed {seed}, u32 parent id {id}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_encoders_against_model() {
+    let max_parent_id = 5;
+    for seed in 0..SEQUENCES {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let rows = random_rows(&mut rng, max_parent_id);
+        // the encoders drop the empty attributes
+        let mut rows: Vec<_> = rows.into_iter().filter(|row| row.value.is_some()).collect();
+        let sets = expected_sets(&rows, max_parent_id);
+        let encoded = encode_model(&mut rows);
+
+        let mut accumulator16 = Attributes16Accumulator::default();
+        let mut accumulator32 = Attributes32Accumulator::default();
+        for (id, set) in sets.iter().enumerate() {
+            accumulator16.append(id as u16, set);
+            accumulator32.append(id as u32, set);
+        }
+        let (Some(rb16), Some(rb32)) = (
+            accumulator16.finish().unwrap(),
+            accumulator32.finish().unwrap(),
+        ) else {
+            assert!(rows.is_empty(), "seed {seed}");
+            continue;
+        };
+
+        let parent_ids = get_u16_array(&rb16, consts::PARENT_ID).unwrap();
+        let expected: Vec<_> = encoded.iter().map(|id| *id as u16).collect();
+        assert_eq!(parent_ids.values().to_vec(), expected, "seed {seed}");
+
+        let store = Attribute32Store::try_from_with_options(&rb32, &options()).unwrap();
+        for (id, set) in sets.iter().enumerate() {
+            let decoded = store.attribute_by_id(id as u32).unwrap_or_default();
+            let mut decoded = decoded.to_vec();
+            let mut set = set.clone();
+            // the encoders order the attributes of a set like their rows
+            decoded.sort_by_key(|kv| format!("{kv:?}"));
+            set.sort_by_key(|kv| format!("{kv:?}"));
+            assert_eq!(decoded, set, "seed {seed}, u32 parent id {id}");
+        }
+    }
+}
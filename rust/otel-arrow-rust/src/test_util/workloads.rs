@@ -0,0 +1,358 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Synthetic OTAP record batches used by the tests and by the benchmarks in `benches/`.
+//!
+//! Attribute batches are generated the way the Go encoder produces them: sorted by key
+//! and value, with the parent ids of consecutive rows having the same key and value delta
+//! encoded.
+
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BinaryArray, DictionaryArray, DurationMillisecondArray, FixedSizeBinaryArray,
+    Int32Array, Int64Array, RecordBatch, StringArray, StructArray, TimestampNanosecondArray,
+    UInt8Array, UInt16Array,
+};
+use arrow::datatypes::{Field, Schema, UInt16Type};
+
+use crate::otap::{OtapBatch, Traces};
+use crate::otlp::attributes::store::AttributeValueType;
+use crate::proto::opentelemetry::arrow::v1::ArrowPayloadType;
+use crate::schema::consts;
+
+fn record_batch(columns: Vec<(&str, ArrayRef)>) -> RecordBatch {
+    let schema = Schema::new(
+        columns
+            .iter()
+            .map(|(name, arr)| Field::new(*name, arr.data_type().clone(), true))
+            .collect::<Vec<_>>(),
+    );
+    // safety: all the columns are built with the same length
+    RecordBatch::try_new(
+        Arc::new(schema),
+        columns.into_iter().map(|(_, arr)| arr).collect(),
+    )
+    .expect("valid record batch")
+}
+
+fn struct_array(fields: Vec<(&str, ArrayRef)>) -> ArrayRef {
+    Arc::new(StructArray::from(
+        fields
+            .into_iter()
+            .map(|(name, arr)| {
+                (
+                    Arc::new(Field::new(name, arr.data_type().clone(), true)),
+                    arr,
+                )
+            })
+            .collect::<Vec<_>>(),
+    ))
+}
+
+fn dictionary(values: impl Iterator<Item = String>) -> DictionaryArray<UInt16Type> {
+    let values: Vec<_> = values.collect();
+    values.iter().map(String::as_str).collect()
+}
+
+/// String attributes whose keys are all distinct, spread over `num_rows / 8` parents.
+#[must_use]
+pub fn high_cardinality_attrs(num_rows: usize) -> RecordBatch {
+    record_batch(vec![
+        (
+            consts::PARENT_ID,
+            Arc::new(UInt16Array::from_iter_values(
+                (0..num_rows).map(|i| (i / 8) as u16),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_TYPE,
+            Arc::new(UInt8Array::from(vec![
+                AttributeValueType::Str as u8;
+                num_rows
+            ])),
+        ),
+        (
+            consts::ATTRIBUTE_KEY,
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|i| format!("key.{i:08}")),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_STR,
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|i| format!("value{i}")),
+            )),
+        ),
+    ])
+}
+
+/// Int attributes whose keys are all distinct, spread over `num_rows / 8` parents. Only
+/// one row in 16 has its value in the int column, the values of the others were written
+/// as strings in the string column.
+#[must_use]
+pub fn sparse_int_attrs(num_rows: usize) -> RecordBatch {
+    record_batch(vec![
+        (
+            consts::PARENT_ID,
+            Arc::new(UInt16Array::from_iter_values(
+                (0..num_rows).map(|i| (i / 8) as u16),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_TYPE,
+            Arc::new(UInt8Array::from(vec![
+                AttributeValueType::Int as u8;
+                num_rows
+            ])),
+        ),
+        (
+            consts::ATTRIBUTE_KEY,
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|i| format!("key.{i:08}")),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_STR,
+            Arc::new(StringArray::from_iter(
+                (0..num_rows).map(|i| (i % 16 != 0).then(|| i.to_string())),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_INT,
+            Arc::new(Int64Array::from_iter(
+                (0..num_rows).map(|i| (i % 16 == 0).then_some(i as i64)),
+            )),
+        ),
+    ])
+}
+
+/// Map attributes, one per parent, each holding a CBOR encoded map of `map_len` string
+/// and int entries.
+#[must_use]
+pub fn cbor_map_attrs(num_rows: usize, map_len: usize) -> RecordBatch {
+    let values = (0..num_rows).map(|i| {
+        let map = ciborium::Value::Map(
+            (0..map_len)
+                .map(|j| {
+                    let value = if j % 2 == 0 {
+                        ciborium::Value::Text(format!("v{i}.{j}"))
+                    } else {
+                        ciborium::Value::Integer((i * j).into())
+                    };
+                    (ciborium::Value::Text(format!("k{j}")), value)
+                })
+                .collect(),
+        );
+        let mut bytes = Vec::new();
+        // safety: writing to a Vec can't fail
+        ciborium::into_writer(&map, &mut bytes).expect("cbor encoding");
+        bytes
+    });
+
+    record_batch(vec![
+        (
+            consts::PARENT_ID,
+            Arc::new(UInt16Array::from_iter_values(
+                (0..num_rows).map(|i| i as u16),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_TYPE,
+            Arc::new(UInt8Array::from(vec![
+                AttributeValueType::Map as u8;
+                num_rows
+            ])),
+        ),
+        (
+            consts::ATTRIBUTE_KEY,
+            Arc::new(StringArray::from(vec!["map"; num_rows])),
+        ),
+        (
+            consts::ATTRIBUTE_STR,
+            Arc::new(StringArray::new_null(num_rows)),
+        ),
+        (
+            consts::ATTRIBUTE_SER,
+            Arc::new(BinaryArray::from_iter_values(values)),
+        ),
+    ])
+}
+
+/// String attributes with `cardinality` distinct key / value pairs, both dictionary
+/// encoded. Every parent has each of the pairs, so the parent ids of a pair are delta
+/// encoded.
+#[must_use]
+pub fn dictionary_attrs(num_rows: usize, cardinality: usize) -> RecordBatch {
+    let cardinality = cardinality.max(1);
+    let parents = num_rows.div_ceil(cardinality);
+    let pair = |i: usize| i / parents;
+
+    let keys = dictionary((0..num_rows).map(|i| format!("key{}", pair(i))));
+    let values = dictionary((0..num_rows).map(|i| format!("value{}", pair(i))));
+    record_batch(vec![
+        (
+            consts::PARENT_ID,
+            Arc::new(UInt16Array::from_iter_values(
+                (0..num_rows).map(|i| u16::from(i % parents != 0)),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_TYPE,
+            Arc::new(UInt8Array::from(vec![
+                AttributeValueType::Str as u8;
+                num_rows
+            ])),
+        ),
+        (consts::ATTRIBUTE_KEY, Arc::new(keys)),
+        (consts::ATTRIBUTE_STR, Arc::new(values)),
+    ])
+}
+
+/// String attributes of `num_rows / width` parents having `width` distinct keys each, sorted
+/// by key. The values are distinct, so no parent id is delta encoded.
+#[must_use]
+pub fn wide_attrs(num_rows: usize, width: usize) -> RecordBatch {
+    let parents = num_rows.div_ceil(width.max(1));
+    record_batch(vec![
+        (
+            consts::PARENT_ID,
+            Arc::new(UInt16Array::from_iter_values(
+                (0..num_rows).map(|i| (i % parents) as u16),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_TYPE,
+            Arc::new(UInt8Array::from(vec![
+                AttributeValueType::Str as u8;
+                num_rows
+            ])),
+        ),
+        (
+            consts::ATTRIBUTE_KEY,
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|i| format!("key.{:04}", i / parents)),
+            )),
+        ),
+        (
+            consts::ATTRIBUTE_STR,
+            Arc::new(StringArray::from_iter_values(
+                (0..num_rows).map(|i| format!("value{i}")),
+            )),
+        ),
+    ])
+}
+
+/// Traces batch of `num_spans` spans of a single resource and scope, each with
+/// `attrs_per_span` dictionary encoded attributes.
+#[must_use]
+pub fn traces_batch(num_spans: usize, attrs_per_span: usize) -> OtapBatch {
+    let ids = |value: u16| Arc::new(UInt16Array::from(vec![value; num_spans]));
+    let names = dictionary((0..num_spans).map(|i| format!("span{}", i % 16)));
+    // safety: all the ids have the right size
+    let trace_ids = FixedSizeBinaryArray::try_from_iter(
+        (0..num_spans)
+            .map(|i| [(i / 4) as u8; 16])
+            .collect::<Vec<_>>()
+            .into_iter(),
+    )
+    .expect("valid trace ids");
+    let span_ids = FixedSizeBinaryArray::try_from_iter(
+        (0..num_spans)
+            .map(|i| (i as u64).to_le_bytes())
+            .collect::<Vec<_>>()
+            .into_iter(),
+    )
+    .expect("valid span ids");
+
+    let spans = record_batch(vec![
+        (
+            consts::ID,
+            Arc::new(UInt16Array::from_iter_values(
+                (0..num_spans).map(|i| u16::from(i != 0)),
+            )),
+        ),
+        (consts::RESOURCE, struct_array(vec![(consts::ID, ids(0))])),
+        (
+            consts::SCOPE,
+            struct_array(vec![
+                (consts::ID, ids(0)),
+                (
+                    consts::NAME,
+                    Arc::new(StringArray::from(vec!["scope"; num_spans])),
+                ),
+            ]),
+        ),
+        (
+            consts::START_TIME_UNIX_NANO,
+            Arc::new(TimestampNanosecondArray::from_iter_values(
+                (0..num_spans).map(|i| i as i64 * 1_000),
+            )),
+        ),
+        (
+            consts::DURATION_TIME_UNIX_NANO,
+            Arc::new(DurationMillisecondArray::from_iter_values(
+                (0..num_spans).map(|i| (i % 100) as i64),
+            )),
+        ),
+        (consts::TRACE_ID, Arc::new(trace_ids)),
+        (consts::SPAN_ID, Arc::new(span_ids)),
+        (consts::NAME, Arc::new(names)),
+        (
+            consts::KIND,
+            Arc::new(Int32Array::from_iter_values(
+                (0..num_spans).map(|i| (i % 5) as i32),
+            )),
+        ),
+    ]);
+
+    let mut otap_batch = OtapBatch::Traces(Traces::default());
+    otap_batch.set(ArrowPayloadType::Spans, spans);
+    otap_batch.set(
+        ArrowPayloadType::SpanAttrs,
+        dictionary_attrs(num_spans * attrs_per_span, attrs_per_span),
+    );
+    otap_batch
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::otlp::attributes::store::Attribute16Store;
+    use crate::otlp::traces::traces_from;
+    use crate::proto::opentelemetry::common::v1::any_value::Value;
+
+    #[test]
+    fn test_attrs_workloads_decode() {
+        let store = Attribute16Store::try_from(&high_cardinality_attrs(64)).unwrap();
+        assert_eq!(store.attribute_by_id(7).unwrap().len(), 8);
+
+        let store = Attribute16Store::try_from(&cbor_map_attrs(8, 4)).unwrap();
+        let attrs = store.attribute_by_id(3).unwrap();
+        let Some(Value::KvlistValue(map)) = attrs[0].value.as_ref().and_then(|v| v.value.as_ref())
+        else {
+            panic!("expected a map attribute, got {attrs:?}");
+        };
+        assert_eq!(map.values.len(), 4);
+
+        let store = Attribute16Store::try_from(&dictionary_attrs(60, 3)).unwrap();
+        for parent_id in 0..20 {
+            let keys: Vec<_> = store
+                .attribute_by_id(parent_id)
+                .unwrap()
+                .iter()
+                .map(|kv| kv.key.as_str())
+                .collect();
+            assert_eq!(keys, vec!["key0", "key1", "key2"]);
+        }
+    }
+
+    #[test]
+    fn test_traces_workload_decode() {
+        let traces = traces_from(traces_batch(32, 4)).unwrap();
+        let spans = &traces.resource_spans[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 32);
+        assert!(spans.iter().all(|span| span.attributes.len() == 4));
+    }
+}
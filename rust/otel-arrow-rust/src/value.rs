@@ -0,0 +1,166 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The attribute and value model shared by the decoder and the encoder: the
+//! [`AttributeValueType`] of the attribute payloads, the conversions of the OTLP values and
+//! the [`cbor`] codec of the serialized maps and slices.
+//!
+//! This module only depends on the generated messages, `prost` and `ciborium`, not on the
+//! `tokio` and `tonic` transports gated behind the `runtime`, `client` and `server`
+//! features, so that it links without them, e.g. in an embedded agent built with
+//! `default-features = false`.
+
+use num_enum::TryFromPrimitive;
+
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+
+/// CBOR codec of the `ser` column of the attribute payloads and of the log bodies.
+pub mod cbor;
+
+/// Type of an attribute value, stored in the `type` column of the attribute payloads.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, TryFromPrimitive)]
+#[repr(u8)]
+pub enum AttributeValueType {
+    /// No value.
+    Empty = 0,
+    /// String value, in the `str` column.
+    Str = 1,
+    /// Integer value, in the `int` column.
+    Int = 2,
+    /// Double value, in the `double` column.
+    Double = 3,
+    /// Boolean value, in the `bool` column.
+    Bool = 4,
+    /// Map value, serialized in the `ser` column.
+    Map = 5,
+    /// Slice value, serialized in the `ser` column.
+    Slice = 6,
+    /// Bytes value, in the `bytes` column.
+    Bytes = 7,
+}
+
+impl From<Option<&Value>> for AttributeValueType {
+    /// Returns the type of a value, `Empty` if there is no value.
+    fn from(value: Option<&Value>) -> Self {
+        match value {
+            None => Self::Empty,
+            Some(Value::StringValue(_)) => Self::Str,
+            Some(Value::IntValue(_)) => Self::Int,
+            Some(Value::DoubleValue(_)) => Self::Double,
+            Some(Value::BoolValue(_)) => Self::Bool,
+            Some(Value::BytesValue(_)) => Self::Bytes,
+            Some(Value::KvlistValue(_)) => Self::Map,
+            Some(Value::ArrayValue(_)) => Self::Slice,
+        }
+    }
+}
+
+/// Returns the default value of the given type, used when no value was stored for the
+/// attribute.
+#[must_use]
+pub fn default_value(value_type: AttributeValueType) -> Option<Value> {
+    match value_type {
+        AttributeValueType::Str => Some(Value::StringValue(String::new())),
+        AttributeValueType::Int => Some(Value::IntValue(0)),
+        AttributeValueType::Double => Some(Value::DoubleValue(0.0)),
+        AttributeValueType::Bool => Some(Value::BoolValue(false)),
+        AttributeValueType::Bytes => Some(Value::BytesValue(Vec::new())),
+        _ => None,
+    }
+}
+
+/// Converts the value to the given type. Returns `None` if the value can't be represented
+/// with this type.
+#[must_use]
+pub fn coerce_value(value: &Value, value_type: AttributeValueType) -> Option<Value> {
+    match (value_type, value) {
+        (AttributeValueType::Str, Value::StringValue(s)) => Some(Value::StringValue(s.clone())),
+        (AttributeValueType::Str, Value::IntValue(i)) => Some(Value::StringValue(i.to_string())),
+        (AttributeValueType::Str, Value::DoubleValue(d)) => Some(Value::StringValue(d.to_string())),
+        (AttributeValueType::Str, Value::BoolValue(b)) => Some(Value::StringValue(b.to_string())),
+        (AttributeValueType::Str, Value::BytesValue(b)) => {
+            String::from_utf8(b.clone()).ok().map(Value::StringValue)
+        }
+        (AttributeValueType::Int, Value::IntValue(i)) => Some(Value::IntValue(*i)),
+        (AttributeValueType::Int, Value::StringValue(s)) => {
+            s.trim().parse().ok().map(Value::IntValue)
+        }
+        (AttributeValueType::Int, Value::DoubleValue(d))
+            if d.fract() == 0.0 && *d >= i64::MIN as f64 && *d < i64::MAX as f64 =>
+        {
+            Some(Value::IntValue(*d as i64))
+        }
+        (AttributeValueType::Int, Value::BoolValue(b)) => Some(Value::IntValue(i64::from(*b))),
+        (AttributeValueType::Double, Value::DoubleValue(d)) => Some(Value::DoubleValue(*d)),
+        (AttributeValueType::Double, Value::StringValue(s)) => {
+            s.trim().parse().ok().map(Value::DoubleValue)
+        }
+        (AttributeValueType::Double, Value::IntValue(i)) => Some(Value::DoubleValue(*i as f64)),
+        (AttributeValueType::Bool, Value::BoolValue(b)) => Some(Value::BoolValue(*b)),
+        (AttributeValueType::Bool, Value::StringValue(s)) => {
+            match s.trim().to_ascii_lowercase().as_str() {
+                "true" => Some(Value::BoolValue(true)),
+                "false" => Some(Value::BoolValue(false)),
+                _ => None,
+            }
+        }
+        (AttributeValueType::Bool, Value::IntValue(i @ (0 | 1))) => Some(Value::BoolValue(*i == 1)),
+        (AttributeValueType::Bytes, Value::BytesValue(b)) => Some(Value::BytesValue(b.clone())),
+        (AttributeValueType::Bytes, Value::StringValue(s)) => {
+            Some(Value::BytesValue(s.clone().into_bytes()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_coerce_value() {
+        let test_data = [
+            (
+                Value::StringValue("42".into()),
+                AttributeValueType::Int,
+                Some(Value::IntValue(42)),
+            ),
+            (
+                Value::StringValue("x".into()),
+                AttributeValueType::Int,
+                None,
+            ),
+            (
+                Value::DoubleValue(2.0),
+                AttributeValueType::Int,
+                Some(Value::IntValue(2)),
+            ),
+            (Value::DoubleValue(2.5), AttributeValueType::Int, None),
+            (
+                Value::IntValue(7),
+                AttributeValueType::Str,
+                Some(Value::StringValue("7".into())),
+            ),
+            (
+                Value::IntValue(7),
+                AttributeValueType::Double,
+                Some(Value::DoubleValue(7.0)),
+            ),
+            (
+                Value::StringValue("True".into()),
+                AttributeValueType::Bool,
+                Some(Value::BoolValue(true)),
+            ),
+            (Value::IntValue(2), AttributeValueType::Bool, None),
+            (Value::BytesValue(vec![0xff]), AttributeValueType::Str, None),
+        ];
+
+        for (value, value_type, expected) in test_data {
+            assert_eq!(
+                coerce_value(&value, value_type),
+                expected,
+                "{value:?} to {value_type:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,581 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::error::{self, Error, Result};
+use crate::proto::opentelemetry::common::v1::any_value::Value;
+use crate::proto::opentelemetry::common::v1::{AnyValue, ArrayValue, KeyValue, KeyValueList};
+use snafu::ResultExt;
+
+/// Decode bytes from a serialized attribute into pcommon value.
+///
+/// This should be used for values in the `ser` column of attributes and Log bodies. The
+/// value is checked against the default [`CborLimits`] before it is decoded.
+pub fn decode_pcommon_val(input: &[u8]) -> Result<Option<Value>> {
+    decode_pcommon_val_with_limits(input, &CborLimits::default())
+}
+
+/// Same as [`decode_pcommon_val`], checking the value against `limits` before decoding it,
+/// so that a malicious value can't make the decoder recurse deeply or allocate more than
+/// the input.
+pub fn decode_pcommon_val_with_limits(input: &[u8], limits: &CborLimits) -> Result<Option<Value>> {
+    let checked = check_limits(input, limits)?;
+    let decoded_val = match ciborium::de::from_reader_with_recursion_limit::<ciborium::Value, &[u8]>(
+        &input[..checked.unwrap_or(input.len())],
+        limits.max_depth,
+    ) {
+        Err(ciborium::de::Error::RecursionLimitExceeded) => {
+            return error::SerializedValueTooDeepSnafu {
+                max_depth: limits.max_depth,
+            }
+            .fail();
+        }
+        result => result.context(error::InvalidSerializedAttributeBytesSnafu)?,
+    };
+
+    MaybeValue::try_from(decoded_val).map(Into::into)
+}
+
+/// Default maximum nesting depth of the maps and slices, the one of the CBOR decoder.
+const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Limits of the serialized values, checked before they are decoded.
+///
+/// By default, the values can nest up to 256 maps and slices and have any number of
+/// elements. In strict mode, the values must also be in the preferred serialization of
+/// RFC 8949: definite lengths, arguments on the fewest bytes, and no bytes after the value.
+/// The floats are not required to be on the fewest bytes, the encoders writing the doubles
+/// on 8 bytes. The Go implementation writes the maps and slices with indefinite lengths, so
+/// the strict mode only suits the producers known to write definite lengths.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CborLimits {
+    max_depth: usize,
+    max_elements: Option<usize>,
+    strict: bool,
+}
+
+impl Default for CborLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_elements: None,
+            strict: false,
+        }
+    }
+}
+
+impl CborLimits {
+    /// Creates the default limits.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the nesting of the maps and slices to `max_depth` levels.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Limits the values to `max_elements` items, counting the value itself, the elements
+    /// of its slices and both the keys and the values of its maps.
+    #[must_use]
+    pub fn with_max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = Some(max_elements);
+        self
+    }
+
+    /// Sets whether the values must be in the preferred serialization.
+    #[must_use]
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Returns the maximum nesting depth of the maps and slices.
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Returns the maximum number of items of a value, if limited.
+    #[must_use]
+    pub fn max_elements(&self) -> Option<usize> {
+        self.max_elements
+    }
+
+    /// Returns whether the values must be in the preferred serialization.
+    #[must_use]
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+}
+
+/// A map, slice or indefinite length string being checked, with the number of items left,
+/// `None` if its length is indefinite.
+struct OpenContainer {
+    remaining: Option<u64>,
+    /// Major type of the chunks of an indefinite length string.
+    chunk_major: Option<u8>,
+}
+
+/// Checks the first value of the input against the limits without decoding it. Returns the
+/// length of the value, or `None` if it has a tag, whose content is left to the decoder.
+fn check_limits(input: &[u8], limits: &CborLimits) -> Result<Option<usize>> {
+    let malformed = |offset, reason| error::MalformedSerializedValueSnafu { offset, reason }.fail();
+    let non_canonical =
+        |offset, reason| error::NonCanonicalSerializedValueSnafu { offset, reason }.fail();
+
+    let mut pos = 0;
+    let mut stack: Vec<OpenContainer> = Vec::new();
+    let mut elements = 0;
+    let mut read_root = false;
+    loop {
+        match stack.last() {
+            Some(OpenContainer {
+                remaining: Some(0), ..
+            }) => {
+                let _ = stack.pop();
+                continue;
+            }
+            None if read_root => break,
+            _ => {}
+        }
+
+        let offset = pos;
+        let Some(&initial) = input.get(pos) else {
+            return malformed(offset, "truncated value");
+        };
+        pos += 1;
+        if initial == BREAK {
+            match stack.last() {
+                Some(OpenContainer {
+                    remaining: None, ..
+                }) => {
+                    let _ = stack.pop();
+                    continue;
+                }
+                _ => return malformed(offset, "unexpected break"),
+            }
+        }
+        let major = initial >> 5;
+        if major == MAJOR_TAG {
+            return Ok(None);
+        }
+
+        match stack.last_mut() {
+            Some(OpenContainer {
+                chunk_major: Some(chunk_major),
+                ..
+            }) if major != *chunk_major || initial & 0x1f == INDEFINITE => {
+                return malformed(offset, "invalid string chunk");
+            }
+            Some(OpenContainer {
+                remaining: Some(remaining),
+                ..
+            }) => *remaining -= 1,
+            Some(_) => {}
+            None => read_root = true,
+        }
+        elements += 1;
+        if let Some(max_elements) = limits.max_elements {
+            if elements > max_elements {
+                return error::TooManySerializedElementsSnafu { max_elements }.fail();
+            }
+        }
+
+        let info = initial & 0x1f;
+        if major == MAJOR_SIMPLE {
+            pos += match info {
+                0..=23 | 31 => 0,
+                24 => 1,
+                25 => 2,
+                26 => 4,
+                27 => 8,
+                _ => return malformed(offset, "reserved simple value"),
+            };
+            if pos > input.len() {
+                return malformed(offset, "truncated value");
+            }
+            continue;
+        }
+
+        let length = if info == INDEFINITE {
+            if major < MAJOR_BYTES {
+                return malformed(offset, "indefinite length integer");
+            }
+            if limits.strict {
+                return non_canonical(offset, "indefinite length");
+            }
+            None
+        } else {
+            let Some((arg, arg_len)) = read_argument(&input[pos..], info) else {
+                return malformed(offset, "truncated or reserved argument");
+            };
+            pos += arg_len;
+            if limits.strict && arg_len > 0 && arg_len > shortest_argument_len(arg) {
+                return non_canonical(offset, "argument not on the fewest bytes");
+            }
+            Some(arg)
+        };
+
+        match (major, length) {
+            (MAJOR_UNSIGNED | MAJOR_NEGATIVE, _) => {}
+            (MAJOR_BYTES | MAJOR_TEXT, Some(len)) => {
+                match usize::try_from(len)
+                    .ok()
+                    .filter(|len| *len <= input.len() - pos)
+                {
+                    Some(len) => pos += len,
+                    None => return malformed(offset, "string longer than the input"),
+                }
+            }
+            // the chunks of an indefinite length string are read as the items of a container
+            (MAJOR_BYTES | MAJOR_TEXT, None) => stack.push(OpenContainer {
+                remaining: None,
+                chunk_major: Some(major),
+            }),
+            (_, length) => {
+                let items = match length {
+                    Some(len) if major == MAJOR_MAP => len.checked_mul(2),
+                    length => length,
+                };
+                // every item takes at least one byte, so the lengths over the rest of the
+                // input are rejected before the decoder allocates them
+                if items.is_some_and(|items| items > (input.len() - pos) as u64) {
+                    return malformed(offset, "container longer than the input");
+                }
+                stack.push(OpenContainer {
+                    remaining: items,
+                    chunk_major: None,
+                });
+                if stack.len() > limits.max_depth {
+                    return error::SerializedValueTooDeepSnafu {
+                        max_depth: limits.max_depth,
+                    }
+                    .fail();
+                }
+            }
+        }
+    }
+
+    if limits.strict && pos < input.len() {
+        return non_canonical(pos, "bytes after the value");
+    }
+    Ok(Some(pos))
+}
+
+/// Reads the argument of an item head with the additional information `info`, returning it
+/// with the number of bytes it was read from.
+fn read_argument(input: &[u8], info: u8) -> Option<(u64, usize)> {
+    let len = match info {
+        0..=23 => return Some((u64::from(info), 0)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    let bytes = input.get(..len)?;
+    let arg = bytes.iter().fold(0, |arg, b| (arg << 8) | u64::from(*b));
+    Some((arg, len))
+}
+
+/// Returns the number of bytes of the shortest encoding of an argument, after the initial
+/// byte.
+fn shortest_argument_len(arg: u64) -> usize {
+    match arg {
+        0..=23 => 0,
+        24..=0xff => 1,
+        0x100..=0xffff => 2,
+        0x1_0000..=0xffff_ffff => 4,
+        _ => 8,
+    }
+}
+
+/// Serializes a pcommon value into the bytes of the `ser` column of attributes and Log
+/// bodies, the reverse of [`decode_pcommon_val`].
+///
+/// The encoding is the one of the Go implementation: maps and slices are written as
+/// indefinite length items in the order of their entries, doubles are always written on 8
+/// bytes, except NaN and infinities written as half floats, and empty bytes are written as
+/// null.
+#[must_use]
+pub fn encode_pcommon_val(value: Option<&Value>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    encode_into(value, &mut buf);
+    buf
+}
+
+const MAJOR_UNSIGNED: u8 = 0;
+const MAJOR_NEGATIVE: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+const INDEFINITE: u8 = 31;
+const INDEFINITE_ARRAY: u8 = 0x9f;
+const INDEFINITE_MAP: u8 = 0xbf;
+const BREAK: u8 = 0xff;
+const FALSE: u8 = 0xf4;
+const TRUE: u8 = 0xf5;
+const NULL: u8 = 0xf6;
+const HALF_FLOAT: u8 = 0xf9;
+const DOUBLE: u8 = 0xfb;
+
+fn encode_into(value: Option<&Value>, buf: &mut Vec<u8>) {
+    match value {
+        None => buf.push(NULL),
+        Some(Value::StringValue(s)) => encode_str(s, buf),
+        Some(Value::BoolValue(b)) => buf.push(if *b { TRUE } else { FALSE }),
+        Some(Value::IntValue(i)) => {
+            if *i >= 0 {
+                encode_head(MAJOR_UNSIGNED, *i as u64, buf);
+            } else {
+                encode_head(MAJOR_NEGATIVE, !(*i) as u64, buf);
+            }
+        }
+        Some(Value::DoubleValue(d)) => {
+            if d.is_nan() {
+                buf.extend_from_slice(&[HALF_FLOAT, 0x7e, 0x00]);
+            } else if d.is_infinite() {
+                let sign = if d.is_sign_negative() { 0xfc } else { 0x7c };
+                buf.extend_from_slice(&[HALF_FLOAT, sign, 0x00]);
+            } else {
+                buf.push(DOUBLE);
+                buf.extend_from_slice(&d.to_be_bytes());
+            }
+        }
+        Some(Value::BytesValue(bytes)) if bytes.is_empty() => buf.push(NULL),
+        Some(Value::BytesValue(bytes)) => {
+            encode_head(MAJOR_BYTES, bytes.len() as u64, buf);
+            buf.extend_from_slice(bytes);
+        }
+        Some(Value::ArrayValue(array)) => {
+            buf.push(INDEFINITE_ARRAY);
+            for element in &array.values {
+                encode_into(element.value.as_ref(), buf);
+            }
+            buf.push(BREAK);
+        }
+        Some(Value::KvlistValue(kvs)) => {
+            buf.push(INDEFINITE_MAP);
+            for kv in &kvs.values {
+                encode_str(&kv.key, buf);
+                encode_into(kv.value.as_ref().and_then(|v| v.value.as_ref()), buf);
+            }
+            buf.push(BREAK);
+        }
+    }
+}
+
+fn encode_str(s: &str, buf: &mut Vec<u8>) {
+    encode_head(MAJOR_TEXT, s.len() as u64, buf);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Writes the head of an item with the shortest encoding of its argument.
+fn encode_head(major: u8, arg: u64, buf: &mut Vec<u8>) {
+    let major = major << 5;
+    if arg < 24 {
+        buf.push(major | arg as u8);
+    } else if let Ok(arg) = u8::try_from(arg) {
+        buf.extend_from_slice(&[major | 24, arg]);
+    } else if let Ok(arg) = u16::try_from(arg) {
+        buf.push(major | 25);
+        buf.extend_from_slice(&arg.to_be_bytes());
+    } else if let Ok(arg) = u32::try_from(arg) {
+        buf.push(major | 26);
+        buf.extend_from_slice(&arg.to_be_bytes());
+    } else {
+        buf.push(major | 27);
+        buf.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+/// `MaybeValue` is a thin wrapper around `Option<Value>`.
+///
+/// We use this so we to avoid violating the coherence rule when implementing TryFrom.
+struct MaybeValue(Option<Value>);
+
+impl From<MaybeValue> for Option<Value> {
+    fn from(value: MaybeValue) -> Self {
+        value.0
+    }
+}
+
+impl TryFrom<ciborium::Value> for MaybeValue {
+    type Error = Error;
+
+    fn try_from(value: ciborium::Value) -> Result<Self> {
+        let val = match value {
+            ciborium::Value::Null => None,
+            ciborium::Value::Text(string_val) => Some(Value::StringValue(string_val)),
+            ciborium::Value::Float(double_val) => Some(Value::DoubleValue(double_val)),
+            ciborium::Value::Bytes(bytes_val) => Some(Value::BytesValue(bytes_val)),
+            ciborium::Value::Bool(bool_val) => Some(Value::BoolValue(bool_val)),
+            ciborium::Value::Integer(int_val) => Some(Value::IntValue(
+                int_val
+                    .try_into()
+                    .context(error::InvalidSerializedIntAttributeValueSnafu)?,
+            )),
+            ciborium::Value::Array(array_vals) => Some(Value::try_from(array_vals)?),
+            ciborium::Value::Map(kv_vals) => Some(Value::try_from(kv_vals)?),
+            other => {
+                return error::UnsupportedSerializedAttributeValueSnafu { actual: other }.fail();
+            }
+        };
+
+        Ok(Self(val))
+    }
+}
+
+/// Converts an array of cbor values into the ArrayValue variant of Value
+impl TryFrom<Vec<ciborium::Value>> for Value {
+    type Error = Error;
+
+    fn try_from(values: Vec<ciborium::Value>) -> std::result::Result<Self, Self::Error> {
+        let vals: Result<Vec<_>> = values
+            .into_iter()
+            .map(|element| match MaybeValue::try_from(element) {
+                Ok(val) => Ok(AnyValue { value: val.into() }),
+                Err(e) => Err(e),
+            })
+            .collect();
+
+        Ok(Value::ArrayValue(ArrayValue { values: vals? }))
+    }
+}
+
+/// Converts the array of cbor kv pairs into the KvlistValue variant of Value
+impl TryFrom<Vec<(ciborium::Value, ciborium::Value)>> for Value {
+    type Error = Error;
+
+    fn try_from(
+        kv_values: Vec<(ciborium::Value, ciborium::Value)>,
+    ) -> std::result::Result<Self, Self::Error> {
+        let kvs: Result<Vec<_>> = kv_values
+            .into_iter()
+            .map(|(k, v)| {
+                if let ciborium::Value::Text(key) = k {
+                    match MaybeValue::try_from(v) {
+                        Ok(val) => Ok(KeyValue::new(key, AnyValue { value: val.into() })),
+                        Err(e) => Err(e),
+                    }
+                } else {
+                    error::InvalidSerializedMapKeyTypeSnafu { actual: k }.fail()
+                }
+            })
+            .collect();
+
+        Ok(Value::KvlistValue(KeyValueList::new(kvs?)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_pcommon_val() {
+        let value = Value::KvlistValue(KeyValueList::new(vec![
+            KeyValue::new("a", AnyValue {
+                value: Some(Value::IntValue(-500)),
+            }),
+            KeyValue::new("b", AnyValue {
+                value: Some(Value::ArrayValue(ArrayValue {
+                    values: vec![
+                        AnyValue {
+                            value: Some(Value::BoolValue(true)),
+                        },
+                        AnyValue {
+                            value: Some(Value::DoubleValue(1.5)),
+                        },
+                        AnyValue { value: None },
+                    ],
+                })),
+            }),
+        ]));
+
+        // bytes produced by the Go implementation for the same value
+        let bytes = encode_pcommon_val(Some(&value));
+        assert_eq!(bytes, vec![
+            0xbf, 0x61, b'a', 0x39, 0x01, 0xf3, 0x61, b'b', 0x9f, 0xf5, 0xfb, 0x3f, 0xf8, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0xf6, 0xff, 0xff,
+        ]);
+        assert_eq!(decode_pcommon_val(&bytes).unwrap(), Some(value));
+
+        let value = Value::StringValue("x".repeat(300));
+        let bytes = encode_pcommon_val(Some(&value));
+        assert_eq!(bytes[..3], [0x79, 0x01, 0x2c]);
+        assert_eq!(decode_pcommon_val(&bytes).unwrap(), Some(value));
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        let strict = CborLimits::new().with_strict(true);
+        let decode =
+            |input: &[u8], limits: CborLimits| decode_pcommon_val_with_limits(input, &limits);
+        let is_non_canonical = |input: &[u8]| {
+            matches!(
+                decode(input, strict),
+                Err(Error::NonCanonicalSerializedValue { .. })
+            )
+        };
+        let is_malformed = |input: &[u8]| {
+            matches!(
+                decode_pcommon_val(input),
+                Err(Error::MalformedSerializedValue { .. })
+            )
+        };
+
+        // the indefinite lengths of the Go implementation
+        let go_bytes = encode_pcommon_val(Some(&Value::ArrayValue(ArrayValue::default())));
+        assert!(decode_pcommon_val(&go_bytes).unwrap().is_some());
+        assert!(is_non_canonical(&go_bytes));
+        let flat_map = [0xa1, 0x61, b'a', 0x01];
+        assert!(decode(&flat_map, strict).unwrap().is_some());
+        // argument not on the fewest bytes, bytes after the value
+        let long_argument = [0xa1, 0x61, b'a', 0x18, 0x01];
+        assert!(decode_pcommon_val(&long_argument).unwrap().is_some());
+        assert!(is_non_canonical(&long_argument));
+        assert_eq!(
+            decode_pcommon_val(&[0x01, 0x00]).unwrap(),
+            Some(Value::IntValue(1))
+        );
+        assert!(is_non_canonical(&[0x01, 0x00]));
+
+        // lengths over the input, rejected before they are allocated
+        assert!(is_malformed(&[
+            0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+        ]));
+        assert!(is_malformed(&[
+            0x7b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff
+        ]));
+        assert!(is_malformed(&[0x82, 0x01]));
+        assert!(is_malformed(&[0xff]));
+        assert!(is_malformed(&[0xfb, 0x00]));
+        assert_eq!(
+            decode_pcommon_val(&[0x7f, 0x61, b'a', 0x61, b'b', 0xff]).unwrap(),
+            Some(Value::StringValue("ab".into()))
+        );
+        assert!(is_malformed(&[0x7f, 0x01, 0xff]));
+
+        let mut deep = vec![0x81; 300];
+        deep.push(0x01);
+        assert!(matches!(
+            decode_pcommon_val(&deep),
+            Err(Error::SerializedValueTooDeep { max_depth: 256, .. })
+        ));
+        let limits = CborLimits::new().with_max_elements(3);
+        assert!(decode(&[0x82, 0x01, 0x02], limits).is_ok());
+        assert!(matches!(
+            decode(&[0x83, 0x01, 0x02, 0x03], limits),
+            Err(Error::TooManySerializedElements {
+                max_elements: 3,
+                ..
+            })
+        ));
+    }
+}
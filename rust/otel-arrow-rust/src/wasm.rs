@@ -0,0 +1,102 @@
+// Copyright The OpenTelemetry Authors
+// SPDX-License-Identifier: Apache-2.0
+
+//! JavaScript bindings of the decoder, e.g. for a trace viewer decoding the OTAP batches in
+//! the browser. They are built with the `wasm` feature, usually along with
+//! `default-features = false` for the `wasm32-unknown-unknown` target:
+//!
+//! ```text
+//! cargo build --target wasm32-unknown-unknown --no-default-features --features wasm
+//! wasm-bindgen --target web target/wasm32-unknown-unknown/debug/otel_arrow_rust.wasm
+//! ```
+//!
+//! The decoding runs on the calling thread and only reads the clock for the limits and
+//! measures not set by these bindings. The decoded requests are returned as OTLP/JSON, see
+//! [`crate::otlp::json`].
+//!
+//! The `zstd` feature compiles the zstd C library, which needs a C toolchain for the target,
+//! so the wasm builds leave it out: the payloads decoded in the browser must not be zstd
+//! compressed.
+
+use prost::Message;
+use snafu::ResultExt;
+use wasm_bindgen::prelude::{JsError, wasm_bindgen};
+
+use crate::decode::decoder::Consumer;
+use crate::error::{self, Result};
+use crate::frame::read_frame;
+use crate::otlp::json::to_json_line;
+use crate::proto::opentelemetry::arrow::v1::BatchArrowRecords;
+
+/// Decoder of the `BatchArrowRecords` messages of a stream, keeping the schemas and
+/// dictionaries the messages reference across them.
+#[wasm_bindgen]
+#[derive(Default)]
+pub struct StreamDecoder {
+    consumer: Consumer,
+}
+
+#[wasm_bindgen]
+impl StreamDecoder {
+    /// Creates a decoder for a new stream.
+    #[wasm_bindgen(constructor)]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes the next protobuf encoded `BatchArrowRecords` message of the stream and
+    /// returns its export request as OTLP/JSON.
+    pub fn decode(&mut self, message: &[u8]) -> std::result::Result<String, JsError> {
+        self.decode_json(message).map_err(js_error)
+    }
+}
+
+impl StreamDecoder {
+    fn decode_json(&mut self, message: &[u8]) -> Result<String> {
+        let mut records =
+            BatchArrowRecords::decode(message).context(error::InvalidBatchArrowRecordsSnafu)?;
+        let request = self.consumer.consume_batches(&mut records)?;
+        Ok(to_json_line(&request))
+    }
+}
+
+/// Decodes a frame written by [`crate::frame::to_frame`] and returns its export request as
+/// OTLP/JSON.
+#[wasm_bindgen(js_name = decodeFrame)]
+pub fn decode_frame(frame: &[u8]) -> std::result::Result<String, JsError> {
+    decode_frame_json(frame).map_err(js_error)
+}
+
+fn decode_frame_json(frame: &[u8]) -> Result<String> {
+    let (mut records, _) = read_frame(frame)?;
+    let request = Consumer::default().consume_batches(&mut records)?;
+    Ok(to_json_line(&request))
+}
+
+fn js_error(error: error::Error) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::frame::to_frame;
+    use crate::test_util::workloads::traces_batch;
+
+    #[test]
+    fn test_decode_json() {
+        let frame = to_frame(&traces_batch(2, 1), 0).unwrap();
+        let json = decode_frame_json(&frame).unwrap();
+        assert!(json.starts_with(r#"{"resourceSpans":"#), "{json}");
+
+        let (records, _) = read_frame(&frame).unwrap();
+        let mut decoder = StreamDecoder::new();
+        assert_eq!(decoder.decode_json(&records.encode_to_vec()).unwrap(), json);
+        assert!(matches!(
+            decoder.decode_json(b"\xff"),
+            Err(error::Error::InvalidBatchArrowRecords { .. })
+        ));
+    }
+}